@@ -0,0 +1,122 @@
+//! Moving each change in a revision into the ancestor that last changed the
+//! same lines, like `jj absorb`.
+
+use anyhow::{Context, Result};
+use itertools::Itertools as _;
+use jj_lib::absorb::{AbsorbSource, SelectedTrees, absorb_hunks, split_hunks_to_trees};
+use jj_lib::backend::{CommitId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
+use jj_lib::repo::{MutableRepo, Repo as _};
+use jj_lib::repo_path::RepoPath;
+use jj_lib::revset::ResolvedRevsetExpression;
+use std::sync::Arc;
+
+/// What [`absorb`] moved where.
+pub struct AbsorbOutcome {
+    /// The revisions that received changes, rewritten, parents first. Empty
+    /// if none of the changes could be attributed.
+    pub destinations: Vec<Commit>,
+    /// The revision rewritten without the absorbed changes, or `None` if it
+    /// was left empty and abandoned or nothing moved.
+    pub source: Option<Commit>,
+    /// Paths whose changes stayed in the revision, with the reason.
+    pub skipped: Vec<(String, String)>,
+}
+
+/// The value of the submodule at `path` in `tree`, if that's what it is.
+fn submodule(tree: &MergedTree, path: &RepoPath) -> Result<Option<TreeValue>> {
+    Ok(match tree.path_value(path)?.into_resolved() {
+        Ok(Some(value @ TreeValue::GitSubmodule(_))) => Some(value),
+        _ => None,
+    })
+}
+
+/// Moves each submodule pointer change jj-lib skipped, as a whole, to the
+/// nearest of `destinations` that changed the pointer, provided nothing
+/// after it did. A submodule has no lines to annotate, so this is the
+/// pointer's own history.
+fn absorb_submodules(
+    repo: &MutableRepo,
+    commit: &Commit,
+    destinations: &Arc<ResolvedRevsetExpression>,
+    selected: &mut SelectedTrees,
+) -> Result<()> {
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree()?;
+    let mut candidates = Vec::new();
+    for (path, _) in &selected.skipped_paths {
+        if let (Some(before), Some(after)) =
+            (submodule(&parent_tree, path)?, submodule(&tree, path)?)
+        {
+            candidates.push((path.clone(), before, after));
+        }
+    }
+    if candidates.is_empty() {
+        return Ok(());
+    }
+    // Newest first.
+    let destination_ids: Vec<CommitId> = destinations
+        .clone()
+        .evaluate(repo)?
+        .iter()
+        .filter_ok(|id| repo.index().is_ancestor(id, commit.id()) && id != commit.id())
+        .try_collect()?;
+    for (path, before, after) in candidates {
+        for id in &destination_ids {
+            let destination = repo.store().get_commit(id)?;
+            if submodule(&destination.tree()?, &path)? != Some(before.clone()) {
+                // Changed again since, by a revision that isn't a destination.
+                break;
+            }
+            if submodule(&destination.parent_tree(repo)?, &path)? == Some(before.clone()) {
+                continue;
+            }
+            selected
+                .target_commits
+                .entry(id.clone())
+                .or_insert_with(|| MergedTreeBuilder::new(parent_tree.id()))
+                .set_or_remove(path.clone(), Merge::normal(after));
+            selected
+                .skipped_paths
+                .retain(|(skipped, _)| *skipped != path);
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Absorbs the changes `commit` makes into the revisions of `destinations`
+/// that last changed the same lines, rebasing their descendants.
+pub async fn absorb(
+    repo: &mut MutableRepo,
+    commit: &Commit,
+    destinations: &Arc<ResolvedRevsetExpression>,
+) -> Result<AbsorbOutcome> {
+    let source = AbsorbSource::from_commit(repo, commit.clone())?;
+    let mut selected = split_hunks_to_trees(repo, &source, destinations, &EverythingMatcher)
+        .await
+        .context("Failed to find where the changes belong")?;
+    absorb_submodules(repo, commit, destinations, &mut selected)?;
+    let skipped = selected
+        .skipped_paths
+        .iter()
+        .map(|(path, reason)| (path.as_internal_file_string().to_string(), reason.clone()))
+        .collect();
+    if selected.target_commits.is_empty() {
+        return Ok(AbsorbOutcome {
+            destinations: Vec::new(),
+            source: None,
+            skipped,
+        });
+    }
+    let stats = absorb_hunks(repo, &source, selected.target_commits)?;
+    repo.rebase_descendants()?;
+    Ok(AbsorbOutcome {
+        destinations: stats.rewritten_destinations,
+        source: stats.rewritten_source,
+        skipped,
+    })
+}
@@ -0,0 +1,474 @@
+// Adapted from jj-lib 0.34's `diff` module:
+//
+// Copyright 2021 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Line and word diffs of two texts.
+//!
+//! This is jj-lib's histogram diff cut down to two inputs compared byte for
+//! byte, so the pure parts of the crate build without jj-lib, which doesn't
+//! build for `wasm32-unknown-unknown`. The hunks are the same as
+//! `jj_lib::diff::ContentDiff`'s, which the tests check.
+
+use std::collections::{BTreeMap, HashMap};
+use std::iter;
+use std::ops::Range;
+
+fn find_line_ranges(text: &[u8]) -> Vec<Range<usize>> {
+    text.split_inclusive(|b| *b == b'\n')
+        .scan(0, |total, line| {
+            let start = *total;
+            *total += line.len();
+            Some(start..*total)
+        })
+        .collect()
+}
+
+fn is_word_byte(b: u8) -> bool {
+    // Count 0x80..0xff as word bytes so multi-byte UTF-8 chars are treated
+    // as a single unit.
+    matches!(
+        b,
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'\x80'..=b'\xff'
+    )
+}
+
+fn find_word_ranges(text: &[u8]) -> Vec<Range<usize>> {
+    let mut word_ranges = vec![];
+    let mut word_start = 0;
+    let mut in_word = false;
+    for (i, b) in text.iter().enumerate() {
+        if in_word && !is_word_byte(*b) {
+            in_word = false;
+            word_ranges.push(word_start..i);
+            word_start = i;
+        } else if !in_word && is_word_byte(*b) {
+            in_word = true;
+            word_start = i;
+        }
+    }
+    if in_word && word_start < text.len() {
+        word_ranges.push(word_start..text.len());
+    }
+    word_ranges
+}
+
+fn find_nonword_ranges(text: &[u8]) -> Vec<Range<usize>> {
+    (0..text.len())
+        .filter(|&i| !is_word_byte(text[i]))
+        .map(|i| i..i + 1)
+        .collect()
+}
+
+/// The tokens of one input that are being diffed, as byte ranges of `text`,
+/// with `offset` tokens before them left out.
+#[derive(Clone, Copy)]
+struct Source<'a> {
+    text: &'a [u8],
+    ranges: &'a [Range<usize>],
+    offset: usize,
+}
+
+impl<'a> Source<'a> {
+    fn narrowed(&self, positions: Range<usize>) -> Self {
+        Source {
+            text: self.text,
+            ranges: &self.ranges[positions.clone()],
+            offset: self.offset + positions.start,
+        }
+    }
+
+    fn word(&self, position: usize) -> &'a [u8] {
+        &self.text[self.ranges[position].clone()]
+    }
+
+    fn words(&self) -> impl DoubleEndedIterator<Item = &'a [u8]> + ExactSizeIterator + '_ {
+        (0..self.ranges.len()).map(|position| self.word(position))
+    }
+
+    /// The positions of each word, keeping one more than `max_occurrences`
+    /// so the words with too many can be skipped.
+    fn histogram(&self, max_occurrences: usize) -> HashMap<&'a [u8], Vec<usize>> {
+        let mut histogram: HashMap<&[u8], Vec<usize>> = HashMap::new();
+        for (position, word) in self.words().enumerate() {
+            let positions = histogram.entry(word).or_default();
+            if positions.len() <= max_occurrences {
+                positions.push(position);
+            }
+        }
+        histogram
+    }
+}
+
+/// Finds the LCS given an array where `input[i]` is the position in the left
+/// array of element `i` of the right array.
+fn find_lcs(input: &[usize]) -> Vec<(usize, usize)> {
+    if input.is_empty() {
+        return vec![];
+    }
+
+    let mut chain = vec![(0, 0, 0); input.len()];
+    let mut global_longest = 0;
+    let mut global_longest_right_pos = 0;
+    for (right_pos, &left_pos) in input.iter().enumerate() {
+        let mut longest_from_here = 1;
+        let mut previous_right_pos = usize::MAX;
+        for i in (0..right_pos).rev() {
+            let (previous_len, previous_left_pos, _) = chain[i];
+            if previous_left_pos < left_pos {
+                let len = previous_len + 1;
+                if len > longest_from_here {
+                    longest_from_here = len;
+                    previous_right_pos = i;
+                    if len > global_longest {
+                        global_longest = len;
+                        global_longest_right_pos = right_pos;
+                        // No earlier value can make a longer chain than the
+                        // longest one so far.
+                        break;
+                    }
+                }
+            }
+        }
+        chain[right_pos] = (longest_from_here, left_pos, previous_right_pos);
+    }
+
+    let mut result = vec![];
+    let mut right_pos = global_longest_right_pos;
+    loop {
+        let (_, left_pos, previous_right_pos) = chain[right_pos];
+        result.push((left_pos, right_pos));
+        if previous_right_pos == usize::MAX {
+            break;
+        }
+        right_pos = previous_right_pos;
+    }
+    result.reverse();
+    result
+}
+
+/// Adds the positions of the words that are unchanged between `left` and
+/// `right` to `found`, in order.
+fn collect_unchanged_words(found: &mut Vec<(usize, usize)>, left: Source, right: Source) {
+    if left.ranges.is_empty() || right.ranges.is_empty() {
+        return;
+    }
+
+    // Prefer the LCS of uncommon words to leading and trailing matches.
+    let old_len = found.len();
+    collect_unchanged_words_lcs(found, left, right);
+    if found.len() != old_len {
+        return;
+    }
+
+    let leading = iter::zip(left.words(), right.words())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let trailing = iter::zip(
+        left.words().skip(leading).rev(),
+        right.words().skip(leading).rev(),
+    )
+    .take_while(|(l, r)| l == r)
+    .count();
+    found.extend((0..leading).map(|i| (left.offset + i, right.offset + i)));
+    found.extend((1..=trailing).rev().map(|i| {
+        (
+            left.offset + left.ranges.len() - i,
+            right.offset + right.ranges.len() - i,
+        )
+    }));
+}
+
+fn collect_unchanged_words_lcs(found: &mut Vec<(usize, usize)>, left: Source, right: Source) {
+    let max_occurrences = 100;
+    let left_histogram = left.histogram(max_occurrences);
+    let mut left_count_to_words: BTreeMap<usize, Vec<&[u8]>> = BTreeMap::new();
+    for (word, positions) in &left_histogram {
+        left_count_to_words
+            .entry(positions.len())
+            .or_default()
+            .push(word);
+    }
+    if *left_count_to_words.keys().next().unwrap() > max_occurrences {
+        // Give up if every word occurs very often.
+        return;
+    }
+    let right_histogram = right.histogram(max_occurrences);
+    // The least frequent words of `left` that occur as often in `right` are
+    // the anchors.
+    let Some(shared_positions) = left_count_to_words.values().find_map(|words| {
+        let shared: Vec<(&Vec<usize>, &Vec<usize>)> = words
+            .iter()
+            .filter_map(|word| {
+                let left_positions = &left_histogram[word];
+                let right_positions = right_histogram.get(word)?;
+                (left_positions.len() == right_positions.len())
+                    .then_some((left_positions, right_positions))
+            })
+            .collect();
+        (!shared.is_empty()).then_some(shared)
+    }) else {
+        return;
+    };
+
+    // Pairs of (position, serial), where the serial identifies a word and
+    // its occurrence on both sides.
+    let (mut left_positions, mut right_positions): (Vec<_>, Vec<_>) = shared_positions
+        .into_iter()
+        .flat_map(|(lefts, rights)| iter::zip(lefts, rights))
+        .enumerate()
+        .map(|(serial, (&left_pos, &right_pos))| ((left_pos, serial), (right_pos, serial)))
+        .unzip();
+    left_positions.sort_unstable_by_key(|&(pos, _)| pos);
+    right_positions.sort_unstable_by_key(|&(pos, _)| pos);
+    let left_index_by_right_index: Vec<usize> = {
+        let mut left_index_map = vec![0; left_positions.len()];
+        for (i, &(_, serial)) in left_positions.iter().enumerate() {
+            left_index_map[serial] = i;
+        }
+        right_positions
+            .iter()
+            .map(|&(_, serial)| left_index_map[serial])
+            .collect()
+    };
+
+    // Recurse into the changed areas between the words of the LCS.
+    let mut previous_left = 0;
+    let mut previous_right = 0;
+    for (left_index, right_index) in find_lcs(&left_index_by_right_index) {
+        let (left_position, _) = left_positions[left_index];
+        let (right_position, _) = right_positions[right_index];
+        collect_unchanged_words(
+            found,
+            left.narrowed(previous_left..left_position),
+            right.narrowed(previous_right..right_position),
+        );
+        found.push((left.offset + left_position, right.offset + right_position));
+        previous_left = left_position + 1;
+        previous_right = right_position + 1;
+    }
+    collect_unchanged_words(
+        found,
+        left.narrowed(previous_left..left.ranges.len()),
+        right.narrowed(previous_right..right.ranges.len()),
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffHunkKind {
+    Matching,
+    Different,
+}
+
+/// One run of matching or different text, before and after.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffHunk<'a> {
+    pub kind: DiffHunkKind,
+    pub contents: [&'a [u8]; 2],
+}
+
+/// The regions two inputs have in common.
+pub struct ContentDiff<'a> {
+    inputs: [&'a [u8]; 2],
+    /// Sorted byte ranges of the unchanged regions of each input. The first
+    /// and last are empty if the inputs start or end with changes.
+    unchanged: Vec<[Range<usize>; 2]>,
+}
+
+impl<'a> ContentDiff<'a> {
+    fn for_tokenizer(
+        inputs: [&'a [u8]; 2],
+        tokenizer: impl Fn(&[u8]) -> Vec<Range<usize>>,
+    ) -> Self {
+        // Non-empty inputs are all different from an empty one, so there's
+        // no need to tokenize.
+        let ranges = if inputs.iter().any(|input| input.is_empty()) {
+            [vec![], vec![]]
+        } else {
+            inputs.map(&tokenizer)
+        };
+        let [left, right] = [0, 1].map(|side| Source {
+            text: inputs[side],
+            ranges: &ranges[side],
+            offset: 0,
+        });
+        let mut positions = Vec::new();
+        collect_unchanged_words(&mut positions, left, right);
+
+        let mut unchanged = vec![[0..0, 0..0]];
+        unchanged.extend(
+            positions
+                .into_iter()
+                .map(|(l, r)| [left.ranges[l].clone(), right.ranges[r].clone()]),
+        );
+        unchanged.push(inputs.map(|input| input.len()..input.len()));
+        let mut diff = ContentDiff { inputs, unchanged };
+        diff.compact_unchanged_regions();
+        diff
+    }
+
+    /// Compares the inputs line by line.
+    pub fn by_line(inputs: [&'a [u8]; 2]) -> Self {
+        ContentDiff::for_tokenizer(inputs, find_line_ranges)
+    }
+
+    /// Compares the inputs word by word, then the changed regions between
+    /// the words byte by byte.
+    pub fn by_word(inputs: [&'a [u8]; 2]) -> Self {
+        let mut diff = ContentDiff::for_tokenizer(inputs, find_word_ranges);
+        diff.refine_changed_regions(find_nonword_ranges);
+        diff
+    }
+
+    fn between(&self, previous: &[Range<usize>; 2], current: &[Range<usize>; 2]) -> [&'a [u8]; 2] {
+        [0, 1].map(|side| &self.inputs[side][previous[side].end..current[side].start])
+    }
+
+    /// Splits each changed region into tokens and finds unchanged ones among
+    /// them.
+    fn refine_changed_regions(&mut self, tokenizer: impl Fn(&[u8]) -> Vec<Range<usize>>) {
+        let mut refined_unchanged = vec![self.unchanged[0].clone()];
+        for window in self.unchanged.windows(2) {
+            let [previous, current] = [&window[0], &window[1]];
+            let refined = ContentDiff::for_tokenizer(self.between(previous, current), &tokenizer);
+            for ranges in &refined.unchanged {
+                refined_unchanged.push([0, 1].map(|side| {
+                    let offset = previous[side].end;
+                    ranges[side].start + offset..ranges[side].end + offset
+                }));
+            }
+            refined_unchanged.push(current.clone());
+        }
+        self.unchanged = refined_unchanged;
+        self.compact_unchanged_regions();
+    }
+
+    /// Merges unchanged regions with nothing changed between them.
+    fn compact_unchanged_regions(&mut self) {
+        let mut compacted: Vec<[Range<usize>; 2]> = Vec::new();
+        for current in self.unchanged.drain(..) {
+            match compacted.last_mut() {
+                Some(previous)
+                    if previous[0].end == current[0].start
+                        && previous[1].end == current[1].start =>
+                {
+                    previous[0].end = current[0].end;
+                    previous[1].end = current[1].end;
+                }
+                _ => compacted.push(current),
+            }
+        }
+        self.unchanged = compacted;
+    }
+
+    /// The matching and different runs of the inputs, in order.
+    pub fn hunks(&self) -> impl Iterator<Item = DiffHunk<'a>> + '_ {
+        let is_empty = |ranges: &[Range<usize>; 2]| ranges.iter().all(Range::is_empty);
+        let matching = |ranges: &[Range<usize>; 2]| DiffHunk {
+            kind: DiffHunkKind::Matching,
+            contents: [0, 1].map(|side| &self.inputs[side][ranges[side].clone()]),
+        };
+        let first = (!is_empty(&self.unchanged[0])).then(|| matching(&self.unchanged[0]));
+        first
+            .into_iter()
+            .chain(self.unchanged.windows(2).flat_map(move |window| {
+                let different = DiffHunk {
+                    kind: DiffHunkKind::Different,
+                    contents: self.between(&window[0], &window[1]),
+                };
+                let current = (!is_empty(&window[1])).then(|| matching(&window[1]));
+                iter::once(different).chain(current)
+            }))
+    }
+}
+
+#[cfg(all(test, feature = "repo"))]
+mod tests {
+    use super::*;
+
+    /// Checks that the hunks match jj-lib's for each pair of inputs.
+    fn assert_same_hunks(by_line: bool, inputs: &[(&str, &str)]) {
+        for (before, after) in inputs {
+            let [before, after] = [before.as_bytes(), after.as_bytes()];
+            let (ours, theirs) = if by_line {
+                (
+                    ContentDiff::by_line([before, after]),
+                    jj_lib::diff::ContentDiff::by_line([before, after]),
+                )
+            } else {
+                (
+                    ContentDiff::by_word([before, after]),
+                    jj_lib::diff::ContentDiff::by_word([before, after]),
+                )
+            };
+            let ours: Vec<_> = ours
+                .hunks()
+                .map(|hunk| (hunk.kind == DiffHunkKind::Matching, hunk.contents.to_vec()))
+                .collect();
+            let theirs: Vec<_> = theirs
+                .hunks()
+                .map(|hunk| {
+                    let contents = hunk.contents.into_iter().map(AsRef::as_ref).collect();
+                    (hunk.kind == jj_lib::diff::DiffHunkKind::Matching, contents)
+                })
+                .collect();
+            assert_eq!(ours, theirs, "{:?}", (before, after));
+        }
+    }
+
+    #[test]
+    fn test_by_line_matches_jj() {
+        assert_same_hunks(
+            true,
+            &[
+                ("", ""),
+                ("", "a\n"),
+                ("a\n", ""),
+                ("a\nb\nc\n", "a\nb\nc\n"),
+                ("a\nb\nc\n", "a\nx\nc\n"),
+                ("a\nb\nc", "a\nb\nc\n"),
+                ("a\nb\n", "b\na\n"),
+                ("x\na\nb\nx\n", "a\nx\nb\nx\nx\n"),
+                (
+                    "fn a() {\n}\n\nfn b() {\n}\n",
+                    "fn b() {\n}\n\nfn a() {\n}\n\nfn c() {\n}\n",
+                ),
+                ("}\n}\n}\n}\n", "}\n}\n"),
+                // Real files, with their many repeated lines.
+                (include_str!("selection.rs"), include_str!("split.rs")),
+                (
+                    include_str!("unified_diff.rs"),
+                    include_str!("fingerprint.rs"),
+                ),
+                ("one\ntwo\nthree\nfour\n", "zero\none\nthree\nfour\nfive\n"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_by_word_matches_jj() {
+        assert_same_hunks(
+            false,
+            &[
+                ("", "word"),
+                ("let x = 1;", "let y = 1;"),
+                ("foo(bar, baz)", "foo(baz, bar)"),
+                ("héllo wörld", "héllo world"),
+                ("a b c d", "a c b d"),
+                ("call(a);\ncall(b);", "call(a, b);\ncall(c);"),
+                (include_str!("range.rs"), include_str!("line_index.rs")),
+            ],
+        );
+    }
+}
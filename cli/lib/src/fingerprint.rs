@@ -0,0 +1,321 @@
+//! Content-defined hunk fingerprints, for carrying selections across
+//! rewrites.
+//!
+//! A fingerprint hashes a hunk's removed/added lines with whitespace
+//! normalized, plus each of the surrounding unchanged lines separately. After
+//! a rewrite (rebase, amend, reorder), a hunk is looked up by content first;
+//! the context hashes then tell whether it is still in the same surroundings
+//! or only matched fuzzily.
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::LineRange;
+use crate::unified_diff::{LineKind, unified_hunks};
+
+/// Unchanged lines hashed on each side of a hunk.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// Hash of the normalized removed and added lines.
+    pub content: String,
+    /// Hashes of the lines before the hunk, nearest first.
+    pub context_before: Vec<String>,
+    /// Hashes of the lines after the hunk, nearest first.
+    pub context_after: Vec<String>,
+}
+
+impl Fingerprint {
+    /// Number of context lines that are equal, comparing outwards from the
+    /// hunk.
+    fn context_score(&self, other: &Fingerprint) -> usize {
+        let matching = |a: &[String], b: &[String]| a.iter().zip(b).filter(|(a, b)| a == b).count();
+        matching(&self.context_before, &other.context_before)
+            + matching(&self.context_after, &other.context_after)
+    }
+
+    fn context_matches(&self, other: &Fingerprint) -> bool {
+        self.context_before == other.context_before && self.context_after == other.context_after
+    }
+}
+
+/// A block of changed lines in one file, located by the lines it covers in
+/// the new version of the file (the lines a [`LineRange`] selects).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FingerprintedHunk {
+    pub path: String,
+    pub start: usize,
+    pub end: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub fingerprint: Fingerprint,
+}
+
+fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn hash_hex(data: &[u8], len: usize) -> String {
+    let digest = format!("{:x}", Sha1::digest(data));
+    digest[..len].to_string()
+}
+
+fn hash_line(line: &str) -> String {
+    hash_hex(normalize(line).as_bytes(), 8)
+}
+
+/// Splits the diff of one file into contiguous change blocks and
+/// fingerprints each.
+pub fn fingerprint_file(path: &str, before: &[u8], after: &[u8]) -> Vec<FingerprintedHunk> {
+    let after_text = String::from_utf8_lossy(after);
+    let after_lines: Vec<&str> = after_text.lines().collect();
+    unified_hunks(before, after, 0)
+        .into_iter()
+        .map(|hunk| {
+            let mut content = String::new();
+            for line in &hunk.lines {
+                let sign = if line.kind == LineKind::Added {
+                    '+'
+                } else {
+                    '-'
+                };
+                content.push(sign);
+                content.push_str(&normalize(&line.text));
+                content.push('\n');
+            }
+            // 0-indexed span of the block in the new file. A pure deletion
+            // sits right after line `new_start`.
+            let begin = if hunk.new_len == 0 {
+                hunk.new_start
+            } else {
+                hunk.new_start - 1
+            };
+            let end = begin + hunk.new_len;
+            let context_before = after_lines[begin.saturating_sub(CONTEXT_LINES)..begin]
+                .iter()
+                .rev()
+                .map(|line| hash_line(line))
+                .collect();
+            let context_after = after_lines[end..(end + CONTEXT_LINES).min(after_lines.len())]
+                .iter()
+                .map(|line| hash_line(line))
+                .collect();
+            let (start, last) = if hunk.new_len == 0 {
+                let anchor = (begin + 1).min(after_lines.len()).max(1);
+                (anchor, anchor)
+            } else {
+                (begin + 1, end)
+            };
+            FingerprintedHunk {
+                path: path.to_string(),
+                start,
+                end: last,
+                added: hunk.new_len,
+                removed: hunk.old_len,
+                fingerprint: Fingerprint {
+                    content: hash_hex(content.as_bytes(), 16),
+                    context_before,
+                    context_after,
+                },
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RemapStatus {
+    /// Same content, context and location.
+    Unchanged,
+    /// Same content and context at a different location.
+    Relocated,
+    /// Same content, but the surrounding lines changed.
+    Fuzzy,
+    /// No hunk with this content anymore.
+    Lost,
+}
+
+impl RemapStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RemapStatus::Unchanged => "unchanged",
+            RemapStatus::Relocated => "relocated",
+            RemapStatus::Fuzzy => "fuzzy",
+            RemapStatus::Lost => "lost",
+        }
+    }
+}
+
+/// Finds where `hunk` went among `candidates`, preferring hunks that weren't
+/// matched yet, the same path, the best context match and the closest line.
+fn match_hunk(
+    hunk: &FingerprintedHunk,
+    candidates: &[FingerprintedHunk],
+    used: &mut [bool],
+) -> (RemapStatus, Option<usize>) {
+    let best = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, candidate)| candidate.fingerprint.content == hunk.fingerprint.content)
+        .min_by_key(|(index, candidate)| {
+            (
+                used[*index],
+                candidate.path != hunk.path,
+                std::cmp::Reverse(candidate.fingerprint.context_score(&hunk.fingerprint)),
+                candidate.start.abs_diff(hunk.start),
+            )
+        });
+    let Some((index, candidate)) = best else {
+        return (RemapStatus::Lost, None);
+    };
+    used[index] = true;
+    let status = if !candidate.fingerprint.context_matches(&hunk.fingerprint) {
+        RemapStatus::Fuzzy
+    } else if candidate.path == hunk.path && candidate.start == hunk.start {
+        RemapStatus::Unchanged
+    } else {
+        RemapStatus::Relocated
+    };
+    (status, Some(index))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemappedSelector {
+    pub old: LineRange,
+    pub new: Vec<LineRange>,
+    /// The worst status of the hunks the selector covers.
+    pub status: RemapStatus,
+}
+
+/// Remaps selectors written against `old_hunks` onto `new_hunks`.
+///
+/// A selector covering part of a hunk covers the same part of the remapped
+/// hunk. Selectors that don't touch any changed line are reported as lost.
+pub fn remap_selectors(
+    selectors: &[LineRange],
+    old_hunks: &[FingerprintedHunk],
+    new_hunks: &[FingerprintedHunk],
+) -> Vec<RemappedSelector> {
+    let mut used = vec![false; new_hunks.len()];
+    let matches: Vec<_> = old_hunks
+        .iter()
+        .map(|hunk| match_hunk(hunk, new_hunks, &mut used))
+        .collect();
+
+    selectors
+        .iter()
+        .map(|selector| {
+            let mut status = None;
+            let mut new: Vec<LineRange> = Vec::new();
+            for (hunk, (hunk_status, new_index)) in old_hunks.iter().zip(&matches) {
+                if hunk.path != selector.path
+                    || hunk.end < selector.start
+                    || hunk.start > selector.end
+                {
+                    continue;
+                }
+                status = status.max(Some(*hunk_status));
+                let Some(new_hunk) = new_index.map(|index| &new_hunks[index]) else {
+                    continue;
+                };
+                let start_offset = selector.start.saturating_sub(hunk.start);
+                let end_offset = selector.end.min(hunk.end) - hunk.start;
+                let range = LineRange {
+                    path: new_hunk.path.clone(),
+                    start: (new_hunk.start + start_offset).min(new_hunk.end),
+                    end: (new_hunk.start + end_offset).min(new_hunk.end),
+                };
+                match new.last_mut() {
+                    Some(last) if last.path == range.path && range.start <= last.end + 1 => {
+                        last.end = last.end.max(range.end);
+                    }
+                    _ => new.push(range),
+                }
+            }
+            RemappedSelector {
+                old: selector.clone(),
+                new,
+                status: status.unwrap_or(RemapStatus::Lost),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(path: &str, start: usize, end: usize) -> LineRange {
+        LineRange {
+            path: path.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_file_locates_blocks() {
+        let hunks = fingerprint_file("f", b"a\nb\nc\nd\n", b"a\nB\nc\nd\nE\n");
+        let spans: Vec<_> = hunks.iter().map(|h| (h.start, h.end)).collect();
+        assert_eq!(spans, vec![(2, 2), (5, 5)]);
+        assert_eq!(hunks[0].fingerprint.context_before.len(), 1);
+        assert_eq!(hunks[0].fingerprint.context_after.len(), 3);
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_whitespace() {
+        let a = fingerprint_file("f", b"x\n", b"fn f() {}\n");
+        let b = fingerprint_file("f", b"x\n", b"fn  f()   {}\n");
+        assert_eq!(a[0].fingerprint, b[0].fingerprint);
+    }
+
+    #[test]
+    fn test_remap_unchanged_and_relocated() {
+        let old = fingerprint_file(
+            "f",
+            b"1\n2\n3\n4\n5\n6\n7\n8\n",
+            b"1\n2\n3\nfour\n5\n6\n7\n8\n",
+        );
+        // Two new lines at the top shift the hunk down without touching its context.
+        let new = fingerprint_file(
+            "f",
+            b"0\n0\n1\n2\n3\n4\n5\n6\n7\n8\n",
+            b"0\n0\n1\n2\n3\nfour\n5\n6\n7\n8\n",
+        );
+        let remapped = remap_selectors(&[range("f", 4, 4)], &old, &new);
+        assert_eq!(remapped[0].status, RemapStatus::Relocated);
+        assert_eq!(remapped[0].new, vec![range("f", 6, 6)]);
+
+        let remapped = remap_selectors(&[range("f", 4, 4)], &old, &old);
+        assert_eq!(remapped[0].status, RemapStatus::Unchanged);
+    }
+
+    #[test]
+    fn test_remap_fuzzy_when_context_changes() {
+        let old = fingerprint_file("f", b"a\nb\nc\n", b"a\nB\nc\n");
+        let new = fingerprint_file("f", b"x\nb\nc\n", b"x\nB\nc\n");
+        let remapped = remap_selectors(&[range("f", 2, 2)], &old, &new);
+        assert_eq!(remapped[0].status, RemapStatus::Fuzzy);
+        assert_eq!(remapped[0].new, vec![range("f", 2, 2)]);
+    }
+
+    #[test]
+    fn test_remap_lost() {
+        let old = fingerprint_file("f", b"a\nb\n", b"a\nB\n");
+        let new = fingerprint_file("f", b"a\nb\n", b"a\nC\n");
+        let remapped = remap_selectors(&[range("f", 2, 2), range("f", 1, 1)], &old, &new);
+        assert_eq!(remapped[0].status, RemapStatus::Lost);
+        assert!(remapped[0].new.is_empty());
+        // Unchanged lines aren't covered by any hunk.
+        assert_eq!(remapped[1].status, RemapStatus::Lost);
+    }
+
+    #[test]
+    fn test_remap_partial_selection_and_moved_file() {
+        let old = fingerprint_file("old.rs", b"", b"1\n2\n3\n4\n");
+        let new = fingerprint_file("new.rs", b"", b"1\n2\n3\n4\n");
+        let remapped = remap_selectors(&[range("old.rs", 2, 3)], &old, &new);
+        assert_eq!(remapped[0].status, RemapStatus::Relocated);
+        assert_eq!(remapped[0].new, vec![range("new.rs", 2, 3)]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Showing commits the way jj does, in messages.
+
+use itertools::Itertools as _;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId;
+
+/// Number of hex digits shown for change and commit ids, matching jj's
+/// `short()` default.
+const SHORT_ID_LEN: usize = 12;
+
+pub fn short_change_id(commit: &Commit) -> String {
+    commit.change_id().reverse_hex()[..SHORT_ID_LEN].to_string()
+}
+
+pub fn short_commit_id(commit: &Commit) -> String {
+    commit.id().hex()[..SHORT_ID_LEN].to_string()
+}
+
+/// First line of the description, or a placeholder like jj's.
+pub fn subject(commit: &Commit) -> &str {
+    commit
+        .description()
+        .lines()
+        .next()
+        .filter(|line| !line.trim().is_empty())
+        .unwrap_or("(no description set)")
+}
+
+/// `<change id> <commit id> <subject>` on one line.
+pub fn commit_summary(commit: &Commit) -> String {
+    format!(
+        "{} {} {}",
+        short_change_id(commit),
+        short_commit_id(commit),
+        subject(commit)
+    )
+}
+
+/// `"; did you mean <path>?"` naming up to three of `candidates` that look
+/// like `path`, closest first, or nothing if none do. A candidate looks
+/// like it by edit distance, or by having the same file name.
+pub fn did_you_mean<S: AsRef<str>>(path: &str, candidates: &[S]) -> String {
+    let file_name = |path: &str| path.rsplit('/').next().unwrap_or(path).to_owned();
+    let max_distance = (path.chars().count() / 4).max(2);
+    let suggestions: Vec<&str> = candidates
+        .iter()
+        .map(AsRef::as_ref)
+        .filter_map(|candidate| {
+            let distance = if file_name(candidate) == file_name(path) {
+                0
+            } else {
+                strsim::levenshtein(path, candidate)
+            };
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .sorted()
+        .take(3)
+        .map(|(_, candidate)| candidate)
+        .collect();
+    match suggestions.as_slice() {
+        [] => String::new(),
+        [only] => format!("; did you mean {only}?"),
+        [first @ .., last] => format!("; did you mean {} or {last}?", first.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_did_you_mean() {
+        let changed = ["src/main.rs", "src/lib.rs", "tests/main.rs", "README.md"];
+        assert_eq!(
+            did_you_mean("src/mian.rs", &changed),
+            "; did you mean src/main.rs?"
+        );
+        assert_eq!(
+            did_you_mean("main.rs", &changed),
+            "; did you mean src/main.rs or tests/main.rs?"
+        );
+        assert_eq!(did_you_mean("docs/guide.md", &changed), "");
+    }
+}
@@ -0,0 +1,65 @@
+//! The line-range selection and commit splitting behind jjka, for tools
+//! that want to split commits without running the CLI.
+//!
+//! A [`Selection`] is a set of [`LineRange`]s, numbered as in one revision's
+//! version of each file. [`plan_split`] works out which of the revision's
+//! changes make up the selected lines, and [`split`] turns a plan into two
+//! commits in a transaction:
+//!
+//! ```no_run
+//! # async fn example(
+//! #     repo: &std::sync::Arc<jj_lib::repo::ReadonlyRepo>,
+//! #     commit: &jj_lib::commit::Commit,
+//! # ) -> anyhow::Result<()> {
+//! use jjka_lib::{LineRange, Selection, plan_split, split};
+//!
+//! let selection: Selection = [LineRange::parse("src/lib.rs:10-20")?].into_iter().collect();
+//! let plan = plan_split(repo.as_ref(), commit, &selection, |_path| {}).await?;
+//! plan.check_not_empty()?;
+//! let mut tx = repo.start_transaction();
+//! let outcome = split(tx.repo_mut(), &plan, Some("Extract a helper"))?;
+//! tx.repo_mut().rebase_descendants()?;
+//! tx.commit(format!("split commit {}", outcome.remaining.id()))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`SplitPlanBuilder`] does all of this in one go, and can also select
+//! hunks and whole files. [`absorb`] moves a revision's changes into the
+//! ancestors that last touched the same lines.
+//!
+//! Errors are [`anyhow::Error`]s, except for [`RangeParseError`], which is
+//! the caller's input being wrong rather than the repo.
+//!
+//! Everything that reads or writes a repo is behind the default `repo`
+//! feature. Without it, the crate is only the pure parts — ranges,
+//! [`Selection`], [`split_file`], unified hunks and fingerprints — which
+//! build for `wasm32-unknown-unknown`; `jjka-wasm` wraps them for browsers.
+
+#[cfg(feature = "repo")]
+pub mod absorb;
+mod diff;
+pub mod fingerprint;
+#[cfg(feature = "repo")]
+pub mod format;
+pub mod line_index;
+pub mod range;
+pub mod selection;
+#[cfg(feature = "repo")]
+pub mod split;
+#[cfg(feature = "repo")]
+pub mod tree_diff;
+pub mod unified_diff;
+
+#[cfg(feature = "repo")]
+pub use absorb::{AbsorbOutcome, absorb};
+pub use line_index::LineIndex;
+pub use range::{LineRange, RangeParseError};
+pub use selection::{
+    Selection, SplitFile, coalesce_ranges, split_deletion, split_file, unchanged_ranges,
+};
+#[cfg(feature = "repo")]
+pub use split::{
+    EmptyCommit, SplitCommitIds, SplitOutcome, SplitPlan, SplitPlanBuilder, plan_split,
+    plan_split_with_jobs, split,
+};
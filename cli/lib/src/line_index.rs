@@ -0,0 +1,173 @@
+//! Where each line of a file is, so selected lines can be sliced out of it,
+//! or read from disk, without holding the file split into lines.
+//!
+//! Lines are counted like [`str::lines`]: they end at `\n`, a `\r` before it
+//! isn't part of the line, and there's no empty line after a final newline.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+/// Bytes read at a time by [`LineIndex::from_reader`].
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The byte offsets of each line of a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Per line, the offset of its first byte and of its line ending (or the
+    /// end of the file, for a last line without one).
+    lines: Vec<(u64, u64)>,
+}
+
+impl LineIndex {
+    pub fn new(content: &[u8]) -> Self {
+        let mut builder = Builder::default();
+        builder.feed(content);
+        builder.finish()
+    }
+
+    /// Indexes everything `reader` yields, a chunk at a time.
+    pub fn from_reader(mut reader: impl Read) -> io::Result<Self> {
+        let mut builder = Builder::default();
+        let mut chunk = vec![0; CHUNK_SIZE];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => return Ok(builder.finish()),
+                Ok(n) => builder.feed(&chunk[..n]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// The number of lines.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The bytes of 1-indexed `line`, without its line ending.
+    pub fn line(&self, line: usize) -> Range<u64> {
+        let (start, end) = self.lines[line - 1];
+        start..end
+    }
+
+    /// Slices 1-indexed `line` out of the content that was indexed.
+    pub fn slice<'a>(&self, content: &'a [u8], line: usize) -> &'a [u8] {
+        let range = self.line(line);
+        &content[range.start as usize..range.end as usize]
+    }
+
+    /// Reads 1-indexed `line` from the file that was indexed, appending it
+    /// to `buf`.
+    pub fn read_line(
+        &self,
+        file: &mut (impl Read + Seek),
+        line: usize,
+        buf: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let range = self.line(line);
+        file.seek(SeekFrom::Start(range.start))?;
+        file.take(range.end - range.start).read_to_end(buf)?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    lines: Vec<(u64, u64)>,
+    /// Offset of the first byte not fed yet.
+    offset: u64,
+    /// Where the current line starts, if it has any bytes yet.
+    line_start: Option<u64>,
+    /// Whether the last byte fed was a `\r`.
+    after_cr: bool,
+}
+
+impl Builder {
+    fn feed(&mut self, chunk: &[u8]) {
+        let mut rest = chunk;
+        while !rest.is_empty() {
+            let start = self.offset + (chunk.len() - rest.len()) as u64;
+            self.line_start.get_or_insert(start);
+            let Some(newline) = rest.iter().position(|b| *b == b'\n') else {
+                self.after_cr = rest.last() == Some(&b'\r');
+                break;
+            };
+            let end = start + newline as u64;
+            let cr = if newline > 0 {
+                rest[newline - 1] == b'\r'
+            } else {
+                self.after_cr
+            };
+            let line_start = self.line_start.take().unwrap();
+            self.lines
+                .push((line_start, if cr { end - 1 } else { end }));
+            self.after_cr = false;
+            rest = &rest[newline + 1..];
+        }
+        self.offset += chunk.len() as u64;
+    }
+
+    fn finish(mut self) -> LineIndex {
+        // A last line without a newline keeps any `\r`, as in str::lines.
+        if let Some(start) = self.line_start {
+            self.lines.push((start, self.offset));
+        }
+        LineIndex { lines: self.lines }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(content: &[u8]) -> Vec<&[u8]> {
+        let index = LineIndex::new(content);
+        (1..=index.len())
+            .map(|line| index.slice(content, line))
+            .collect()
+    }
+
+    #[test]
+    fn test_lines_match_str_lines() {
+        for content in [
+            "",
+            "\n",
+            "a",
+            "a\nb",
+            "a\nb\n",
+            "a\r\nb\r\n",
+            "a\n\n\nb",
+            "trailing cr\r",
+            "\r\n\r\n",
+        ] {
+            let expected: Vec<&[u8]> = content.lines().map(str::as_bytes).collect();
+            assert_eq!(lines(content.as_bytes()), expected, "{content:?}");
+        }
+    }
+
+    #[test]
+    fn test_from_reader_across_chunks() {
+        // A `\r\n` split between the first two chunks, and a line longer
+        // than a chunk.
+        let first = "x".repeat(CHUNK_SIZE - 1);
+        let long = "z".repeat(CHUNK_SIZE + 10);
+        let content = format!("{first}\r\nshort\n{}\r\n{long}", "y".repeat(CHUNK_SIZE - 2));
+        let index = LineIndex::from_reader(content.as_bytes()).unwrap();
+        assert_eq!(index, LineIndex::new(content.as_bytes()));
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.line(1), 0..first.len() as u64);
+
+        let mut file = std::io::Cursor::new(content.as_bytes());
+        let mut buf = Vec::new();
+        index.read_line(&mut file, 2, &mut buf).unwrap();
+        index.read_line(&mut file, 3, &mut buf).unwrap();
+        assert_eq!(
+            buf,
+            format!("short{}", "y".repeat(CHUNK_SIZE - 2)).as_bytes()
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! `path:start-end` line ranges, as given on jjka's command line.
+
+use std::fmt;
+use std::num::ParseIntError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRange {
+    pub path: String,
+    pub start: usize, // 1-indexed, inclusive
+    pub end: usize,   // 1-indexed, inclusive
+}
+
+/// Why a line range couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeParseError {
+    /// There is no `:` between the path and the lines.
+    MissingLines(String),
+    /// The lines aren't `start-end`.
+    InvalidLines(String),
+    InvalidStart(ParseIntError),
+    InvalidEnd(ParseIntError),
+    Zero,
+    Inverted,
+}
+
+impl fmt::Display for RangeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeParseError::MissingLines(range) => write!(
+                f,
+                "Invalid range format. Expected path:start-end, got: {range}"
+            ),
+            RangeParseError::InvalidLines(lines) => {
+                write!(f, "Invalid range format. Expected start-end, got: {lines}")
+            }
+            RangeParseError::InvalidStart(_) => f.write_str("Failed to parse start line number"),
+            RangeParseError::InvalidEnd(_) => f.write_str("Failed to parse end line number"),
+            RangeParseError::Zero => f.write_str("Line numbers must be >= 1"),
+            RangeParseError::Inverted => f.write_str("Start line must be <= end line"),
+        }
+    }
+}
+
+impl std::error::Error for RangeParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RangeParseError::InvalidStart(err) | RangeParseError::InvalidEnd(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.path, self.start, self.end)
+    }
+}
+
+impl LineRange {
+    pub fn parse(s: &str) -> Result<Self, RangeParseError> {
+        let parts: Vec<&str> = s.rsplitn(2, ':').collect();
+        if parts.len() != 2 {
+            return Err(RangeParseError::MissingLines(s.to_string()));
+        }
+
+        let (range_str, path_str) = (parts[0], parts[1]);
+        let range_parts: Vec<&str> = range_str.split('-').collect();
+        if range_parts.len() != 2 {
+            return Err(RangeParseError::InvalidLines(range_str.to_string()));
+        }
+
+        let start = range_parts[0]
+            .parse::<usize>()
+            .map_err(RangeParseError::InvalidStart)?;
+        let end = range_parts[1]
+            .parse::<usize>()
+            .map_err(RangeParseError::InvalidEnd)?;
+
+        if start < 1 || end < 1 {
+            return Err(RangeParseError::Zero);
+        }
+
+        if start > end {
+            return Err(RangeParseError::Inverted);
+        }
+
+        Ok(LineRange {
+            path: path_str.to_string(),
+            start,
+            end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_range_parse_valid() {
+        let range = LineRange::parse("src/main.rs:10-20").unwrap();
+        assert_eq!(range.path, "src/main.rs");
+        assert_eq!(range.start, 10);
+        assert_eq!(range.end, 20);
+    }
+
+    #[test]
+    fn test_line_range_parse_with_colons_in_path() {
+        let range = LineRange::parse("C:/Users/test/file.rs:5-15").unwrap();
+        assert_eq!(range.path, "C:/Users/test/file.rs");
+        assert_eq!(range.start, 5);
+        assert_eq!(range.end, 15);
+    }
+
+    #[test]
+    fn test_line_range_parse_single_line() {
+        let range = LineRange::parse("test.txt:42-42").unwrap();
+        assert_eq!(range.path, "test.txt");
+        assert_eq!(range.start, 42);
+        assert_eq!(range.end, 42);
+    }
+
+    #[test]
+    fn test_line_range_display_round_trips() {
+        let range = LineRange::parse("src/a:b.rs:5-15").unwrap();
+        assert_eq!(range.to_string(), "src/a:b.rs:5-15");
+        assert_eq!(LineRange::parse(&range.to_string()).unwrap(), range);
+    }
+
+    #[test]
+    fn test_line_range_parse_invalid_format() {
+        assert!(LineRange::parse("src/main.rs").is_err());
+        assert!(LineRange::parse("src/main.rs:10").is_err());
+        assert!(LineRange::parse("src/main.rs:10-20-30").is_err());
+    }
+
+    #[test]
+    fn test_line_range_parse_invalid_numbers() {
+        assert!(LineRange::parse("src/main.rs:abc-def").is_err());
+        assert!(LineRange::parse("src/main.rs:10-abc").is_err());
+        assert!(LineRange::parse("src/main.rs:0-10").is_err());
+    }
+
+    #[test]
+    fn test_line_range_parse_inverted_range() {
+        assert!(LineRange::parse("src/main.rs:20-10").is_err());
+    }
+}
@@ -0,0 +1,356 @@
+//! Selecting lines of a revision's version of files, and working out the
+//! parts of a diff that produce them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::LineRange;
+use crate::diff::{ContentDiff, DiffHunkKind};
+use crate::unified_diff::count_changed_lines;
+
+/// The lines selected in each file, as sorted 1-indexed inclusive
+/// `(start, end)` ranges, and the files whose executable bit change is
+/// selected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    files: BTreeMap<String, Vec<(usize, usize)>>,
+    modes: BTreeSet<String>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection::default()
+    }
+
+    /// Adds a range, merged with any it overlaps or touches.
+    pub fn add(&mut self, range: &LineRange) {
+        let ranges = self.files.entry(range.path.clone()).or_default();
+        ranges.push((range.start, range.end));
+        *ranges = coalesce_ranges(ranges.iter().copied());
+    }
+
+    /// Selects the change to the executable bit of the file at `path`,
+    /// which otherwise stays with the rest of the changes.
+    pub fn add_mode(&mut self, path: &str) {
+        self.files.entry(path.to_string()).or_default();
+        self.modes.insert(path.to_string());
+    }
+
+    /// Whether the executable bit change of the file at `path` is selected.
+    pub fn has_mode(&self, path: &str) -> bool {
+        self.modes.contains(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The number of files with selected lines.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Each file with its ranges, in path order. A file with only its mode
+    /// selected has none.
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[(usize, usize)])> {
+        self.files
+            .iter()
+            .map(|(path, ranges)| (path.as_str(), ranges.as_slice()))
+    }
+}
+
+impl FromIterator<LineRange> for Selection {
+    fn from_iter<I: IntoIterator<Item = LineRange>>(ranges: I) -> Self {
+        let mut selection = Selection::new();
+        for range in ranges {
+            selection.add(&range);
+        }
+        selection
+    }
+}
+
+impl<'a> FromIterator<&'a LineRange> for Selection {
+    fn from_iter<I: IntoIterator<Item = &'a LineRange>>(ranges: I) -> Self {
+        let mut selection = Selection::new();
+        for range in ranges {
+            selection.add(range);
+        }
+        selection
+    }
+}
+
+/// Sorts inclusive `(start, end)` ranges and merges the ones that overlap or
+/// touch, so no line is in more than one.
+pub fn coalesce_ranges(ranges: impl IntoIterator<Item = (usize, usize)>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<_> = ranges.into_iter().collect();
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+pub fn line_count(content: &[u8]) -> usize {
+    content.split_inclusive(|b| *b == b'\n').count()
+}
+
+/// Applies to `target` the changes that turn it into `source`, but only
+/// where they produce lines of `source` in `ranges`. The lines a selected
+/// line replaces are dropped; a deletion is copied when a line next to it
+/// is selected.
+pub fn copy_lines(target: &[u8], source: &[u8], ranges: &[(usize, usize)]) -> Vec<u8> {
+    let selected = |line: usize| {
+        ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&line))
+    };
+    let diff = ContentDiff::by_line([target, source]);
+    // Lines are copied straight from the inputs into this one buffer.
+    let mut result = Vec::with_capacity(target.len().max(source.len()));
+    // 1-indexed number of the next line of `source`.
+    let mut source_line = 1;
+    for hunk in diff.hunks() {
+        let [removed, added] = [hunk.contents[0], hunk.contents[1]];
+        match hunk.kind {
+            DiffHunkKind::Matching => {
+                result.extend_from_slice(removed);
+                source_line += line_count(added);
+            }
+            DiffHunkKind::Different => {
+                let added_lines = || added.split_inclusive(|b| *b == b'\n');
+                let first = source_line;
+                source_line += line_count(added);
+                let apply = if added.is_empty() {
+                    selected(first - 1) || selected(first)
+                } else {
+                    (first..source_line).any(selected)
+                };
+                if !apply {
+                    result.extend_from_slice(removed);
+                    continue;
+                }
+                for (line, content) in (first..).zip(added_lines()) {
+                    if selected(line) {
+                        result.extend_from_slice(content);
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+/// The ones of `ranges` that take none of the changes from `target` to
+/// `source`, by the same rule as [`copy_lines`]: a range takes a change
+/// when it selects a line the change adds, or a line next to a deletion.
+pub fn unchanged_ranges(
+    target: &[u8],
+    source: &[u8],
+    ranges: &[(usize, usize)],
+) -> Vec<(usize, usize)> {
+    // The source lines each change can be selected by, as inclusive ranges.
+    let mut changes = Vec::new();
+    // 1-indexed number of the next line of `source`.
+    let mut source_line = 1;
+    for hunk in ContentDiff::by_line([target, source]).hunks() {
+        let added = line_count(hunk.contents[1]);
+        if hunk.kind == DiffHunkKind::Different {
+            changes.push(if added == 0 {
+                (source_line - 1, source_line)
+            } else {
+                (source_line, source_line + added - 1)
+            });
+        }
+        source_line += added;
+    }
+    ranges
+        .iter()
+        .copied()
+        .filter(|(start, end)| {
+            !changes
+                .iter()
+                .any(|(first, last)| start <= last && first <= end)
+        })
+        .collect()
+}
+
+/// How one file's changes are divided between the two commits.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SplitFile {
+    pub path: String,
+    pub selected_insertions: usize,
+    pub selected_deletions: usize,
+    pub remaining_insertions: usize,
+    pub remaining_deletions: usize,
+}
+
+/// Splits the change from `parent` to `content` at `ranges`: the file as the
+/// first commit has it, and the lines each commit changes. This is the part
+/// of a split that needs no repo, so it can run anywhere the file contents
+/// are at hand.
+pub fn split_file(
+    path: &str,
+    parent: &[u8],
+    content: &[u8],
+    ranges: &[(usize, usize)],
+) -> (Vec<u8>, SplitFile) {
+    let selected = copy_lines(parent, content, ranges);
+    let (selected_insertions, selected_deletions) = count_changed_lines(parent, &selected);
+    let (remaining_insertions, remaining_deletions) = count_changed_lines(&selected, content);
+    let file = SplitFile {
+        path: path.to_string(),
+        selected_insertions,
+        selected_deletions,
+        remaining_insertions,
+        remaining_deletions,
+    };
+    (selected, file)
+}
+
+/// Splits the deletion of the file `parent` at `ranges`, line numbers in
+/// `parent`: the file as the first commit has it, without the selected
+/// lines, and the lines each commit deletes. The first commit deletes the
+/// whole file when all its lines are selected, which leaves it empty here.
+pub fn split_deletion(
+    path: &str,
+    parent: &[u8],
+    ranges: &[(usize, usize)],
+) -> (Vec<u8>, SplitFile) {
+    let mut kept = Vec::with_capacity(parent.len());
+    let (mut selected_deletions, mut remaining_deletions) = (0, 0);
+    for (line, content) in (1..).zip(parent.split_inclusive(|b| *b == b'\n')) {
+        if ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&line))
+        {
+            selected_deletions += 1;
+        } else {
+            kept.extend_from_slice(content);
+            remaining_deletions += 1;
+        }
+    }
+    let file = SplitFile {
+        path: path.to_string(),
+        selected_insertions: 0,
+        selected_deletions,
+        remaining_insertions: 0,
+        remaining_deletions,
+    };
+    (kept, file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools as _;
+
+    #[test]
+    fn test_selection_groups_by_file() {
+        let range = |spec| LineRange::parse(spec).unwrap();
+        let selection: Selection = [range("b.txt:5-6"), range("a.txt:3-4"), range("b.txt:1-2")]
+            .into_iter()
+            .collect();
+        assert_eq!(selection.len(), 2);
+        assert_eq!(
+            selection.files().collect_vec(),
+            [("a.txt", &[(3, 4)][..]), ("b.txt", &[(1, 2), (5, 6)][..])]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_ranges_are_merged() {
+        assert_eq!(coalesce_ranges([(10, 20), (5, 15)]), [(5, 20)]);
+        assert_eq!(coalesce_ranges([(1, 2), (3, 4), (6, 6)]), [(1, 4), (6, 6)]);
+        assert_eq!(coalesce_ranges([(4, 8), (5, 6)]), [(4, 8)]);
+        let range = |spec| LineRange::parse(spec).unwrap();
+        let selection: Selection = [range("a.txt:5-15"), range("a.txt:10-20")]
+            .into_iter()
+            .collect();
+        assert_eq!(selection.files().collect_vec(), [("a.txt", &[(5, 20)][..])]);
+    }
+
+    #[test]
+    fn test_mode_selection() {
+        let mut selection = Selection::new();
+        selection.add_mode("run.sh");
+        assert!(selection.has_mode("run.sh"));
+        assert!(!selection.has_mode("other.sh"));
+        assert_eq!(selection.files().collect_vec(), [("run.sh", &[][..])]);
+    }
+
+    #[test]
+    fn test_copy_lines() {
+        let target = b"a\nb\nc\nd\n";
+        let source = b"a\nB\nc\nnew\nd\n";
+        // Only the replacement of line 2.
+        assert_eq!(copy_lines(target, source, &[(2, 2)]), b"a\nB\nc\nd\n");
+        // Only the insertion at line 4.
+        assert_eq!(copy_lines(target, source, &[(4, 4)]), b"a\nb\nc\nnew\nd\n");
+        // The whole file.
+        assert_eq!(copy_lines(target, source, &[(1, 5)]), source);
+        // A deletion is copied with a line next to it.
+        assert_eq!(copy_lines(b"a\nx\nb\n", b"a\nb\n", &[(2, 2)]), b"a\nb\n");
+        assert_eq!(
+            copy_lines(b"a\nx\nb\nc\n", b"a\nb\nc\n", &[(3, 3)]),
+            b"a\nx\nb\nc\n"
+        );
+        // Into an empty file.
+        assert_eq!(
+            copy_lines(b"", b"one\ntwo\nthree\n", &[(2, 3)]),
+            b"two\nthree\n"
+        );
+    }
+
+    #[test]
+    fn test_split_added_file() {
+        let (selected, file) = split_file("new.txt", b"", b"1\n2\n3\n4\n", &[(2, 3)]);
+        assert_eq!(selected, b"2\n3\n");
+        assert_eq!(
+            (file.selected_insertions, file.remaining_insertions),
+            (2, 2)
+        );
+        assert_eq!((file.selected_deletions, file.remaining_deletions), (0, 0));
+    }
+
+    #[test]
+    fn test_split_deletion() {
+        let (kept, file) = split_deletion("a.txt", b"1\n2\n3\n4\n", &[(2, 3)]);
+        assert_eq!(kept, b"1\n4\n");
+        assert_eq!((file.selected_deletions, file.remaining_deletions), (2, 2));
+        let (kept, file) = split_deletion("a.txt", b"1\n2\n", &[(1, 2)]);
+        assert!(kept.is_empty());
+        assert_eq!((file.selected_deletions, file.remaining_deletions), (2, 0));
+    }
+
+    #[test]
+    fn test_unchanged_ranges() {
+        let target = b"a\nb\nc\nd\ne\n";
+        let source = b"a\nB\nc\nd\n";
+        // Line 2 is replaced and e deleted after line 4.
+        assert_eq!(
+            unchanged_ranges(target, source, &[(1, 1), (2, 3), (3, 3), (4, 4)]),
+            [(1, 1), (3, 3)]
+        );
+        assert_eq!(unchanged_ranges(target, target, &[(1, 5)]), [(1, 5)]);
+    }
+
+    #[test]
+    fn test_split_file() {
+        let (selected, file) = split_file("a.txt", b"1\n2\n3\n", b"one\n2\nthree\n", &[(3, 3)]);
+        assert_eq!(selected, b"1\n2\nthree\n");
+        assert_eq!(
+            file,
+            SplitFile {
+                path: "a.txt".to_string(),
+                selected_insertions: 1,
+                selected_deletions: 1,
+                remaining_insertions: 1,
+                remaining_deletions: 1,
+            }
+        );
+    }
+}
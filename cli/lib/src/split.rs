@@ -0,0 +1,795 @@
+//! Splitting the changes a revision makes to selected lines into a commit
+//! of their own, before the rest.
+//!
+//! The ranges are line numbers in the revision's version of each file. The
+//! first commit gets the parts of the revision's diff that produce the
+//! selected lines, the way [`copy_lines`](crate::selection::copy_lines)
+//! would copy them; the revision keeps its change id and description and
+//! the remaining changes.
+
+use anyhow::{Context, Result, bail};
+use futures::TryStreamExt as _;
+use itertools::Itertools;
+use jj_lib::backend::{CommitId, CopyId, CopyRecord, MergedTreeId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::merge::{Merge, MergedTreeValue};
+use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
+use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::store::Store;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+
+use crate::LineRange;
+use crate::fingerprint::fingerprint_file;
+use crate::format::{commit_summary, did_you_mean};
+use crate::selection::{
+    Selection, SplitFile, line_count, split_deletion, split_file, unchanged_ranges,
+};
+use crate::tree_diff::{changed_paths, commit_file_contents, read_file};
+
+/// A split worked out by [`plan_split`], not yet made.
+pub struct SplitPlan {
+    /// The revision to split.
+    pub commit: Commit,
+    /// The tree of the new commit: the revision's parent with the selected
+    /// changes.
+    pub selected_tree_id: MergedTreeId,
+    pub files: Vec<SplitFile>,
+    /// The selected ranges that take none of the revision's changes, which
+    /// were most likely given by mistake.
+    pub unchanged: Vec<LineRange>,
+    /// The commit that would be left without changes, if either would.
+    pub empty: Option<EmptyCommit>,
+}
+
+/// The commit of a split left without changes: the new one if nothing that
+/// changed is selected, the revision if all of it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyCommit {
+    Selected,
+    Remaining,
+}
+
+impl SplitPlan {
+    /// Fails if the split would leave either commit without changes.
+    pub fn check_not_empty(&self) -> Result<()> {
+        match self.empty {
+            None => Ok(()),
+            Some(EmptyCommit::Selected) => bail!(
+                "The selected lines aren't changed in {}, so the new commit would be empty",
+                commit_summary(&self.commit)
+            ),
+            Some(EmptyCommit::Remaining) => bail!(
+                "The selected lines are all of the changes in {}, so it would be left empty; \
+                 there is nothing to split",
+                commit_summary(&self.commit)
+            ),
+        }
+    }
+}
+
+/// The two commits a split made of the revision.
+pub struct SplitOutcome {
+    /// The new commit with the selected changes, on the revision's parent.
+    pub selected: Commit,
+    /// The revision rewritten onto `selected`, with the rest of its changes.
+    pub remaining: Commit,
+}
+
+/// Works out the tree of the first commit, calling `on_file` with each path
+/// as it's done. A selection that leaves either commit without changes is
+/// planned too, and marked in [`SplitPlan::empty`]. Files are read, sliced
+/// and written on as many threads as there are CPUs; see
+/// [`plan_split_with_jobs`].
+pub async fn plan_split(
+    repo: &dyn Repo,
+    commit: &Commit,
+    selection: &Selection,
+    on_file: impl FnMut(&str),
+) -> Result<SplitPlan> {
+    let jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+    plan_split_with_jobs(repo, commit, selection, jobs, on_file).await
+}
+
+/// The selected version of one file, written to the store.
+struct SplitValue {
+    path: RepoPathBuf,
+    /// `None` when the first commit deletes the file.
+    value: Option<TreeValue>,
+    /// The path the revision renamed the file from, which the first commit
+    /// then renames it from.
+    renamed_from: Option<RepoPathBuf>,
+    file: SplitFile,
+    unchanged: Vec<(usize, usize)>,
+}
+
+/// The revision being split, and the trees it's split between.
+#[derive(Clone, Copy)]
+struct Revision<'a> {
+    store: &'a Arc<Store>,
+    commit: &'a Commit,
+    parent_tree: &'a MergedTree,
+    tree: &'a MergedTree,
+}
+
+/// Reads `path_str` in both trees, applies the selected lines and writes
+/// the result. A file the revision renamed from `source` is read from there
+/// in the parent. The file keeps the parent's executable bit unless `mode`
+/// selects the revision's.
+async fn split_path(
+    revision: Revision<'_>,
+    path_str: &str,
+    source: Option<&RepoPathBuf>,
+    ranges: &[(usize, usize)],
+    mode: bool,
+) -> Result<SplitValue> {
+    let Revision {
+        store,
+        commit,
+        parent_tree,
+        tree,
+    } = revision;
+    let path = RepoPathBuf::from_internal_string(path_str)
+        .with_context(|| format!("Invalid path {path_str}"))?;
+    let value = tree.path_value(&path)?;
+    let parent_path = source.unwrap_or(&path);
+    let parent_value = parent_tree.path_value(parent_path)?;
+    // Splitting the materialized conflict markers as lines would leave
+    // commits with half a conflict in them.
+    if !value.is_resolved() {
+        bail!(
+            "{path_str} has unresolved conflicts in {}; resolve them before splitting it",
+            commit_summary(commit)
+        );
+    }
+    if !parent_value.is_resolved() {
+        bail!(
+            "{} has unresolved conflicts in the parent of {}; resolve them before splitting it",
+            parent_path.as_internal_file_string(),
+            commit_summary(commit)
+        );
+    }
+    if let Some(kind) = whole_entry_kind(&value).or(whole_entry_kind(&parent_value)) {
+        return split_whole_entry(commit, path, kind, source, value, parent_value, ranges);
+    }
+    if value.is_absent() && !parent_value.is_absent() {
+        return split_deleted_path(store, commit, path, parent_value, ranges).await;
+    }
+    let executable = match value.as_resolved() {
+        Some(Some(TreeValue::File { executable, .. })) => *executable,
+        Some(None) => bail!("{path_str} doesn't exist in {}", commit_summary(commit)),
+        _ => bail!(
+            "{path_str} is not a regular file in {}",
+            commit_summary(commit)
+        ),
+    };
+    let parent_executable = match parent_value.as_resolved() {
+        Some(Some(TreeValue::File { executable, .. })) => Some(*executable),
+        _ => None,
+    };
+    if mode && parent_executable.is_none_or(|parent| parent == executable) {
+        bail!(
+            "{path_str}'s executable bit isn't changed in {}",
+            commit_summary(commit)
+        );
+    }
+    // A file the revision adds has the mode it's added with.
+    let executable = if mode {
+        executable
+    } else {
+        parent_executable.unwrap_or(executable)
+    };
+    let content = read_file(store, &path, value).await?.unwrap_or_default();
+    // A file the revision adds is split against an empty one, so the first
+    // commit adds only the selected lines.
+    let parent_content = if parent_value.is_absent() {
+        Vec::new()
+    } else {
+        read_file(store, parent_path, parent_value)
+            .await?
+            .with_context(|| {
+                format!(
+                    "{} is not a regular file in the parent",
+                    parent_path.as_internal_file_string()
+                )
+            })?
+    };
+
+    let lines = line_count(&content);
+    if let Some((start, end)) = ranges.iter().find(|(_, end)| *end > lines) {
+        bail!(
+            "Range {path_str}:{start}-{end} is past the end of the file, which has {lines} line(s) in {}",
+            commit_summary(commit)
+        );
+    }
+    let (selected, file) = split_file(path_str, &parent_content, &content, ranges);
+    let unchanged = unchanged_ranges(&parent_content, &content, ranges);
+    let id = store.write_file(&path, &mut selected.as_slice()).await?;
+    Ok(SplitValue {
+        path,
+        value: Some(TreeValue::File {
+            id,
+            executable,
+            copy_id: CopyId::placeholder(),
+        }),
+        renamed_from: source.cloned(),
+        file,
+        unchanged,
+    })
+}
+
+/// What to call `value` if it's an entry that is selected as a whole
+/// rather than by lines: a symlink or a submodule.
+fn whole_entry_kind(value: &MergedTreeValue) -> Option<&'static str> {
+    match value.as_resolved() {
+        Some(Some(TreeValue::Symlink(_))) => Some("symlink"),
+        Some(Some(TreeValue::GitSubmodule(_))) => Some("submodule"),
+        _ => None,
+    }
+}
+
+/// [`split_path`] for a symlink or submodule, or a path that is one on
+/// either side. Its target isn't split into lines: the entry counts as the
+/// one line `1`, and selecting it moves the whole change to the first
+/// commit.
+fn split_whole_entry(
+    commit: &Commit,
+    path: RepoPathBuf,
+    kind: &str,
+    source: Option<&RepoPathBuf>,
+    value: MergedTreeValue,
+    parent_value: MergedTreeValue,
+    ranges: &[(usize, usize)],
+) -> Result<SplitValue> {
+    let path_str = path.as_internal_file_string().to_string();
+    if let Some((start, end)) = ranges.iter().find(|(_, end)| *end > 1) {
+        bail!(
+            "Range {path_str}:{start}-{end} is past the end of {kind} {path_str} in {}; select the whole {kind} as {path_str}:1-1",
+            commit_summary(commit)
+        );
+    }
+    let file = SplitFile {
+        path: path_str,
+        selected_insertions: usize::from(value.is_present()),
+        selected_deletions: usize::from(parent_value.is_present()),
+        remaining_insertions: 0,
+        remaining_deletions: 0,
+    };
+    Ok(SplitValue {
+        path,
+        value: value.into_resolved().ok().flatten(),
+        renamed_from: source.cloned(),
+        file,
+        unchanged: Vec::new(),
+    })
+}
+
+/// [`split_path`] for a file the revision deletes. The ranges are lines of
+/// the parent's version, whose deletion moves to the first commit.
+async fn split_deleted_path(
+    store: &Arc<Store>,
+    commit: &Commit,
+    path: RepoPathBuf,
+    parent_value: MergedTreeValue,
+    ranges: &[(usize, usize)],
+) -> Result<SplitValue> {
+    let path_str = path.as_internal_file_string().to_string();
+    let Some(Some(TreeValue::File { executable, .. })) = parent_value.as_resolved() else {
+        bail!("{path_str} is not a regular file in the parent");
+    };
+    let executable = *executable;
+    let parent_content = read_file(store, &path, parent_value.clone())
+        .await?
+        .unwrap_or_default();
+    let lines = line_count(&parent_content);
+    // An empty file has no lines to select, so its deletion is selected as
+    // a whole, as `path:1-1`.
+    if lines == 0 {
+        return split_whole_entry(
+            commit,
+            path,
+            "empty file",
+            None,
+            Merge::absent(),
+            parent_value,
+            ranges,
+        );
+    }
+    if let Some((start, end)) = ranges.iter().find(|(_, end)| *end > lines) {
+        bail!(
+            "Range {path_str}:{start}-{end} is past the end of the file, which has {lines} line(s) before {} deletes it",
+            commit_summary(commit)
+        );
+    }
+    let (kept, file) = split_deletion(&path_str, &parent_content, ranges);
+    let value = if file.remaining_deletions == 0 {
+        None
+    } else {
+        let id = store.write_file(&path, &mut kept.as_slice()).await?;
+        Some(TreeValue::File {
+            id,
+            executable,
+            copy_id: CopyId::placeholder(),
+        })
+    };
+    Ok(SplitValue {
+        path,
+        value,
+        renamed_from: None,
+        file,
+        unchanged: Vec::new(),
+    })
+}
+
+/// Runs `work` on each of `items` on up to `jobs` threads, calling `on_done`
+/// on this thread as each finishes. The results are in the order of
+/// `items`.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    work: impl Fn(&T) -> R + Sync,
+    mut on_done: impl FnMut(&T),
+) -> Vec<R> {
+    if jobs <= 1 || items.len() <= 1 {
+        return items
+            .iter()
+            .map(|item| {
+                let result = work(item);
+                on_done(item);
+                result
+            })
+            .collect();
+    }
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    if sender.send((index, work(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+        for (index, result) in receiver {
+            on_done(&items[index]);
+            results[index] = Some(result);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("every item is worked on"))
+            .collect()
+    })
+}
+
+/// The `(source, target)` paths of the files the revision renames, as the
+/// backend's copy records have them.
+async fn find_renames(
+    repo: &dyn Repo,
+    commit: &Commit,
+    parent_tree: &MergedTree,
+    tree: &MergedTree,
+) -> Result<Vec<(RepoPathBuf, RepoPathBuf)>> {
+    let records: Vec<CopyRecord> = repo
+        .store()
+        .get_copy_records(None, &commit.parent_ids()[0], commit.id())?
+        .try_collect()
+        .await?;
+    let mut renames = Vec::new();
+    for record in records {
+        if record.source != record.target
+            && tree.path_value(&record.source)?.is_absent()
+            && parent_tree.path_value(&record.target)?.is_absent()
+        {
+            renames.push((record.source, record.target));
+        }
+    }
+    Ok(renames)
+}
+
+/// `selection` with the ranges of renamed files given by their old path
+/// moved to the new one. The ranges are lines of the revision's version
+/// either way.
+fn follow_renames(selection: &Selection, renames: &[(RepoPathBuf, RepoPathBuf)]) -> Selection {
+    let mut followed = Selection::new();
+    for (path, ranges) in selection.files() {
+        let target = renames
+            .iter()
+            .find(|(source, _)| source.as_internal_file_string() == path)
+            .map_or(path, |(_, target)| target.as_internal_file_string());
+        for (start, end) in ranges {
+            followed.add(&LineRange {
+                path: target.to_string(),
+                start: *start,
+                end: *end,
+            });
+        }
+        if selection.has_mode(path) {
+            followed.add_mode(target);
+        }
+    }
+    followed
+}
+
+/// [`plan_split`] on up to `jobs` threads. Reading, slicing and writing
+/// each file is independent of the others; only building the tree from
+/// them at the end isn't.
+pub async fn plan_split_with_jobs(
+    repo: &dyn Repo,
+    commit: &Commit,
+    selection: &Selection,
+    jobs: usize,
+    mut on_file: impl FnMut(&str),
+) -> Result<SplitPlan> {
+    if commit.parent_ids().len() != 1 {
+        bail!("Can't split merge commit {}", commit_summary(commit));
+    }
+    let store = repo.store();
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree_async().await?;
+    let renames = find_renames(repo, commit, &parent_tree, &tree).await?;
+    let selection = follow_renames(selection, &renames);
+    let files = selection.files().collect_vec();
+    let changed = changed_paths(&parent_tree, &tree)
+        .await?
+        .into_iter()
+        .map(|change| change.path.as_internal_file_string().to_string())
+        .collect_vec();
+    for (path_str, _) in &files {
+        if changed.iter().any(|path| path == path_str) {
+            continue;
+        }
+        let path = RepoPathBuf::from_internal_string(*path_str)
+            .with_context(|| format!("Invalid path {path_str}"))?;
+        let problem = if tree.path_value(&path)?.is_absent() {
+            "doesn't exist in"
+        } else {
+            "isn't changed in"
+        };
+        bail!(
+            "{path_str} {problem} {}{}",
+            commit_summary(commit),
+            did_you_mean(path_str, &changed)
+        );
+    }
+    let revision = Revision {
+        store,
+        commit,
+        parent_tree: &parent_tree,
+        tree: &tree,
+    };
+    let values = parallel_map(
+        &files,
+        jobs,
+        |(path_str, ranges)| {
+            let source = renames
+                .iter()
+                .find(|(_, target)| target.as_internal_file_string() == *path_str)
+                .map(|(source, _)| source);
+            futures::executor::block_on(split_path(
+                revision,
+                path_str,
+                source,
+                ranges,
+                selection.has_mode(path_str),
+            ))
+        },
+        |(path_str, _)| on_file(path_str),
+    );
+
+    let mut builder = MergedTreeBuilder::new(parent_tree.id());
+    let mut files = Vec::new();
+    let mut unchanged = Vec::new();
+    for value in values {
+        let SplitValue {
+            path,
+            value,
+            renamed_from,
+            file,
+            unchanged: ranges,
+        } = value?;
+        if let Some(source) = renamed_from {
+            builder.set_or_remove(source, Merge::absent());
+        }
+        unchanged.extend(ranges.into_iter().map(|(start, end)| LineRange {
+            path: file.path.clone(),
+            start,
+            end,
+        }));
+        builder.set_or_remove(path, Merge::resolved(value));
+        files.push(file);
+    }
+    let selected_tree_id = builder.write_tree(store)?;
+    let empty = if selected_tree_id == parent_tree.id() {
+        Some(EmptyCommit::Selected)
+    } else if selected_tree_id == *commit.tree_id() {
+        Some(EmptyCommit::Remaining)
+    } else {
+        None
+    };
+    Ok(SplitPlan {
+        commit: commit.clone(),
+        selected_tree_id,
+        files,
+        unchanged,
+        empty,
+    })
+}
+
+/// Creates the commit with the selected changes, described with
+/// `description` or the revision's description, and rewrites the revision
+/// onto it. The revision's descendants are left for the caller to rebase,
+/// as after any rewrite in `repo`.
+pub fn split(
+    repo: &mut MutableRepo,
+    plan: &SplitPlan,
+    description: Option<&str>,
+) -> Result<SplitOutcome> {
+    let commit = &plan.commit;
+    let selected = repo
+        .new_commit(commit.parent_ids().to_vec(), plan.selected_tree_id.clone())
+        .set_description(description.unwrap_or(commit.description()))
+        .write()?;
+    let remaining = repo
+        .rewrite_commit(commit)
+        .set_parents(vec![selected.id().clone()])
+        .write()?;
+    Ok(SplitOutcome {
+        selected,
+        remaining,
+    })
+}
+
+/// The commits made by [`SplitPlanBuilder::execute`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitCommitIds {
+    /// The new commit with the selected changes.
+    pub selected: CommitId,
+    /// The rewritten revision.
+    pub remaining: CommitId,
+}
+
+/// Builds up what to split out of a revision from line ranges, hunks and
+/// whole files, and then makes the split in a transaction of its own.
+///
+/// ```no_run
+/// # async fn example(
+/// #     repo: std::sync::Arc<jj_lib::repo::ReadonlyRepo>,
+/// #     commit: jj_lib::commit::Commit,
+/// # ) -> anyhow::Result<()> {
+/// use jjka_lib::{LineRange, SplitPlanBuilder};
+///
+/// let ids = SplitPlanBuilder::new(repo, commit)
+///     .range(LineRange::parse("src/lib.rs:10-20")?)
+///     .hunk("3f2a9c0d1e4b5a6c")
+///     .file("Cargo.toml")
+///     .description("Extract a helper")
+///     .execute()
+///     .await?;
+/// println!("{} {}", ids.selected, ids.remaining);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SplitPlanBuilder {
+    repo: Arc<ReadonlyRepo>,
+    commit: Commit,
+    ranges: Vec<LineRange>,
+    hunk_ids: Vec<String>,
+    files: Vec<String>,
+    modes: Vec<String>,
+    description: Option<String>,
+    jobs: Option<usize>,
+    allow_empty: bool,
+}
+
+impl SplitPlanBuilder {
+    /// Starts an empty selection of `commit`'s changes in `repo`.
+    pub fn new(repo: Arc<ReadonlyRepo>, commit: Commit) -> Self {
+        SplitPlanBuilder {
+            repo,
+            commit,
+            ranges: Vec::new(),
+            hunk_ids: Vec::new(),
+            files: Vec::new(),
+            modes: Vec::new(),
+            description: None,
+            jobs: None,
+            allow_empty: false,
+        }
+    }
+
+    /// Selects lines, numbered as in the revision's version of the file.
+    pub fn range(mut self, range: LineRange) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Selects the hunk with this id: the content fingerprint `jjka hunks`
+    /// lists for each block of changed lines.
+    pub fn hunk(mut self, id: impl Into<String>) -> Self {
+        self.hunk_ids.push(id.into());
+        self
+    }
+
+    /// Selects all of the revision's changes to a file, its executable bit
+    /// included, or the whole deletion of a file the revision deletes.
+    pub fn file(mut self, path: impl Into<String>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Selects the change to a file's executable bit, which otherwise
+    /// stays in the revision.
+    pub fn mode(mut self, path: impl Into<String>) -> Self {
+        self.modes.push(path.into());
+        self
+    }
+
+    /// Describes the new commit; it gets the revision's description
+    /// otherwise.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Works on up to `jobs` files at once; as many as there are CPUs
+    /// otherwise.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Makes the split even if the new commit or the revision is left
+    /// without changes, as a placeholder.
+    pub fn allow_empty(mut self) -> Self {
+        self.allow_empty = true;
+        self
+    }
+
+    /// Whether the revision changes the executable bit of the file at
+    /// `path`.
+    fn mode_changed(&self, path: &RepoPath) -> Result<bool> {
+        let executable = |tree: MergedTree| -> Result<Option<bool>> {
+            Ok(match tree.path_value(path)?.into_resolved() {
+                Ok(Some(TreeValue::File { executable, .. })) => Some(executable),
+                _ => None,
+            })
+        };
+        let before = executable(self.commit.parent_tree(self.repo.as_ref())?)?;
+        let after = executable(self.commit.tree()?)?;
+        Ok(before.is_some() && after.is_some() && before != after)
+    }
+
+    /// Checks the selection against the revision and works out the split.
+    /// Fails if a hunk id or file isn't among the revision's changes, a
+    /// range is past the end of its file, or, unless [`Self::allow_empty`],
+    /// either commit would be left without changes.
+    pub async fn plan(&self) -> Result<SplitPlan> {
+        if self.ranges.is_empty()
+            && self.hunk_ids.is_empty()
+            && self.files.is_empty()
+            && self.modes.is_empty()
+        {
+            bail!(
+                "Nothing is selected to split out of {}",
+                commit_summary(&self.commit)
+            );
+        }
+        let mut selection: Selection = self.ranges.iter().collect();
+        for path in &self.modes {
+            selection.add_mode(path);
+        }
+        if !self.hunk_ids.is_empty() || !self.files.is_empty() {
+            let contents = commit_file_contents(self.repo.as_ref(), &self.commit).await?;
+            for path in &self.files {
+                let Some(file) = contents
+                    .iter()
+                    .find(|file| file.path.as_internal_file_string() == path)
+                else {
+                    let changed = contents
+                        .iter()
+                        .map(|file| file.path.as_internal_file_string())
+                        .collect_vec();
+                    bail!(
+                        "{path} isn't changed in {}{}",
+                        commit_summary(&self.commit),
+                        did_you_mean(path, &changed)
+                    );
+                };
+                // A deletion is selected by the parent's lines, all of which
+                // move the whole deletion, or as line 1 of an empty file.
+                let deleted = self.commit.tree()?.path_value(&file.path)?.is_absent();
+                let lines = if deleted {
+                    line_count(&file.before).max(1)
+                } else {
+                    line_count(&file.after)
+                };
+                if lines == 0 {
+                    bail!(
+                        "{path} has no lines left in {}, so there are none to select",
+                        commit_summary(&self.commit)
+                    );
+                }
+                selection.add(&LineRange {
+                    path: path.clone(),
+                    start: 1,
+                    end: lines,
+                });
+                if self.mode_changed(&file.path)? {
+                    selection.add_mode(path);
+                }
+            }
+            let hunks = contents
+                .iter()
+                .flat_map(|file| {
+                    fingerprint_file(
+                        file.path.as_internal_file_string(),
+                        &file.before,
+                        &file.after,
+                    )
+                })
+                .collect_vec();
+            for id in &self.hunk_ids {
+                let matching = hunks
+                    .iter()
+                    .filter(|hunk| hunk.fingerprint.content == *id)
+                    .collect_vec();
+                let [hunk] = matching.as_slice() else {
+                    if matching.is_empty() {
+                        bail!("No hunk {id} in {}", commit_summary(&self.commit));
+                    }
+                    bail!(
+                        "Hunk id {id} matches {} identical hunks in {}; select them by range instead",
+                        matching.len(),
+                        commit_summary(&self.commit)
+                    );
+                };
+                selection.add(&LineRange {
+                    path: hunk.path.clone(),
+                    start: hunk.start,
+                    end: hunk.end,
+                });
+            }
+        }
+        let plan = match self.jobs {
+            Some(jobs) => {
+                plan_split_with_jobs(self.repo.as_ref(), &self.commit, &selection, jobs, |_| {})
+                    .await?
+            }
+            None => plan_split(self.repo.as_ref(), &self.commit, &selection, |_| {}).await?,
+        };
+        if !self.allow_empty {
+            plan.check_not_empty()?;
+        }
+        Ok(plan)
+    }
+
+    /// Plans the split and makes it in one transaction, rebasing the
+    /// revision's descendants. If the revision is checked out in a
+    /// workspace, that workspace's working copy is left to be updated by
+    /// the caller.
+    pub async fn execute(self) -> Result<SplitCommitIds> {
+        let plan = self.plan().await?;
+        let mut tx = self.repo.start_transaction();
+        let outcome = split(tx.repo_mut(), &plan, self.description.as_deref())?;
+        tx.repo_mut().rebase_descendants()?;
+        tx.commit(format!(
+            "split lines out of commit {}",
+            commit_summary(&self.commit)
+        ))?;
+        Ok(SplitCommitIds {
+            selected: outcome.selected.id().clone(),
+            remaining: outcome.remaining.id().clone(),
+        })
+    }
+}
@@ -0,0 +1,76 @@
+//! Walking the file-level changes between two trees.
+
+use anyhow::Result;
+use futures::StreamExt;
+use jj_lib::commit::Commit;
+use jj_lib::conflicts::{MaterializedTreeValue, materialize_tree_value};
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::merge::MergedTreeValue;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::store::Store;
+
+/// One changed path with its tree values on both sides.
+pub struct ChangedPath {
+    pub path: RepoPathBuf,
+    pub before: MergedTreeValue,
+    pub after: MergedTreeValue,
+}
+
+/// Lists changed paths between `from` and `to`, sorted by path.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn changed_paths(from: &MergedTree, to: &MergedTree) -> Result<Vec<ChangedPath>> {
+    let mut stream = from.diff_stream(to, &EverythingMatcher);
+    let mut changes = Vec::new();
+    while let Some(entry) = stream.next().await {
+        let values = entry.values?;
+        changes.push(ChangedPath {
+            path: entry.path,
+            before: values.before,
+            after: values.after,
+        });
+    }
+    tracing::trace!(paths = changes.len(), "diffed trees");
+    Ok(changes)
+}
+
+/// Reads the content of a resolved regular file. Returns `None` for absent
+/// paths, symlinks, submodules and conflicts.
+pub async fn read_file(
+    store: &Store,
+    path: &RepoPath,
+    value: MergedTreeValue,
+) -> Result<Option<Vec<u8>>> {
+    match materialize_tree_value(store, path, value).await? {
+        MaterializedTreeValue::File(mut file) => Ok(Some(file.read_all(path).await?)),
+        _ => Ok(None),
+    }
+}
+
+/// Old and new content of a path changed by a commit. Paths that are absent
+/// or aren't regular files on one side have empty content there.
+pub struct FileContents {
+    pub path: RepoPathBuf,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// Reads both sides of every path `commit` changes relative to its parents.
+#[tracing::instrument(level = "debug", skip_all, fields(commit = %commit.id().hex()))]
+pub async fn commit_file_contents(repo: &dyn Repo, commit: &Commit) -> Result<Vec<FileContents>> {
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree_async().await?;
+    let mut files = Vec::new();
+    for change in changed_paths(&parent_tree, &tree).await? {
+        let before = read_file(repo.store(), &change.path, change.before).await?;
+        let after = read_file(repo.store(), &change.path, change.after).await?;
+        files.push(FileContents {
+            path: change.path,
+            before: before.unwrap_or_default(),
+            after: after.unwrap_or_default(),
+        });
+    }
+    Ok(files)
+}
@@ -0,0 +1,350 @@
+//! Line-based unified diff hunks for display.
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::diff::{ContentDiff, DiffHunkKind};
+
+/// Lines of context kept around each change, like `diff -u`.
+pub const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// The line without its trailing newline.
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-indexed first line in the old file (0 if the hunk is empty there).
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    /// The `@@ -a,b +c,d @@` header.
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+}
+
+fn lines(content: &[u8]) -> impl Iterator<Item = String> + '_ {
+    content.split_inclusive(|b| *b == b'\n').map(|line| {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        String::from_utf8_lossy(line).into_owned()
+    })
+}
+
+/// Diffs two file contents into hunks with `context` lines around changes.
+#[tracing::instrument(level = "trace", skip_all)]
+pub fn unified_hunks(before: &[u8], after: &[u8], context: usize) -> Vec<Hunk> {
+    let diff = ContentDiff::by_line([before, after]);
+    let mut all_lines = Vec::new();
+    for hunk in diff.hunks() {
+        match hunk.kind {
+            DiffHunkKind::Matching => {
+                all_lines.extend(lines(hunk.contents[0]).map(|text| DiffLine {
+                    kind: LineKind::Context,
+                    text,
+                }));
+            }
+            DiffHunkKind::Different => {
+                all_lines.extend(lines(hunk.contents[0]).map(|text| DiffLine {
+                    kind: LineKind::Removed,
+                    text,
+                }));
+                all_lines.extend(lines(hunk.contents[1]).map(|text| DiffLine {
+                    kind: LineKind::Added,
+                    text,
+                }));
+            }
+        }
+    }
+
+    // Group changed lines whose context regions touch into the same hunk.
+    let changed: Vec<usize> = all_lines
+        .iter()
+        .positions(|line| line.kind != LineKind::Context)
+        .collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(all_lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let (mut old_line, mut new_line, mut cursor) = (1, 1, 0);
+    for (start, end) in ranges {
+        for line in &all_lines[cursor..start] {
+            advance(&mut old_line, &mut new_line, line.kind);
+        }
+        let lines = all_lines[start..end].to_vec();
+        let old_len = lines.iter().filter(|l| l.kind != LineKind::Added).count();
+        let new_len = lines.iter().filter(|l| l.kind != LineKind::Removed).count();
+        hunks.push(Hunk {
+            old_start: if old_len == 0 { old_line - 1 } else { old_line },
+            old_len,
+            new_start: if new_len == 0 { new_line - 1 } else { new_line },
+            new_len,
+            lines,
+        });
+        for line in &all_lines[start..end] {
+            advance(&mut old_line, &mut new_line, line.kind);
+        }
+        cursor = end;
+    }
+    hunks
+}
+
+/// A run of a changed line's text, and whether it differs from the other
+/// side of the change.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub text: String,
+    pub changed: bool,
+}
+
+fn push_segment(line: &mut Vec<Segment>, text: &str, changed: bool) {
+    if text.is_empty() {
+        return;
+    }
+    match line.last_mut() {
+        Some(last) if last.changed == changed => last.text.push_str(text),
+        _ => line.push(Segment {
+            text: text.to_string(),
+            changed,
+        }),
+    }
+}
+
+/// The segments of each of several lines.
+type LineSegments = Vec<Vec<Segment>>;
+
+/// Splits the pieces of one side of a change into the segments of each line.
+fn split_lines(pieces: &[(String, bool)], line_count: usize) -> LineSegments {
+    let mut lines = vec![Vec::new()];
+    for (text, changed) in pieces {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            push_segment(lines.last_mut().unwrap(), part, *changed);
+        }
+    }
+    lines.resize(line_count, Vec::new());
+    lines
+}
+
+/// Diffs a run of removed lines against the added lines that replace them
+/// word by word. Returns `None` if they have no words in common, since
+/// marking everything as changed wouldn't help.
+fn refine(removed: &[&str], added: &[&str]) -> Option<(LineSegments, LineSegments)> {
+    let before = removed.join("\n");
+    let after = added.join("\n");
+    let diff = ContentDiff::by_word([before.as_bytes(), after.as_bytes()]);
+    let mut similar = false;
+    let (mut old, mut new) = (Vec::new(), Vec::new());
+    for hunk in diff.hunks() {
+        let text = |side: usize| String::from_utf8_lossy(hunk.contents[side]).into_owned();
+        match hunk.kind {
+            DiffHunkKind::Matching => {
+                similar |= hunk.contents[0].iter().any(u8::is_ascii_alphanumeric);
+                old.push((text(0), false));
+                new.push((text(0), false));
+            }
+            DiffHunkKind::Different => {
+                old.push((text(0), true));
+                new.push((text(1), true));
+            }
+        }
+    }
+    similar.then(|| {
+        (
+            split_lines(&old, removed.len()),
+            split_lines(&new, added.len()),
+        )
+    })
+}
+
+/// The segments of each of the hunk's lines. Removed lines followed by added
+/// lines are compared word by word, so the changed words stand out from
+/// the rest of the line; other lines are a single unchanged segment.
+pub fn word_segments(hunk: &Hunk) -> LineSegments {
+    let whole = |line: &DiffLine| {
+        let mut segments = Vec::new();
+        push_segment(&mut segments, &line.text, false);
+        segments
+    };
+    let lines = &hunk.lines;
+    let mut segments = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].kind != LineKind::Removed {
+            segments.push(whole(&lines[i]));
+            i += 1;
+            continue;
+        }
+        let removed_end = i + lines[i..]
+            .iter()
+            .take_while(|line| line.kind == LineKind::Removed)
+            .count();
+        let added_end = removed_end
+            + lines[removed_end..]
+                .iter()
+                .take_while(|line| line.kind == LineKind::Added)
+                .count();
+        let text = |range: std::ops::Range<usize>| {
+            lines[range]
+                .iter()
+                .map(|line| line.text.as_str())
+                .collect_vec()
+        };
+        match refine(&text(i..removed_end), &text(removed_end..added_end)) {
+            Some((old, new)) if removed_end < added_end => {
+                segments.extend(old);
+                segments.extend(new);
+            }
+            _ => segments.extend(lines[i..added_end].iter().map(whole)),
+        }
+        i = added_end;
+    }
+    segments
+}
+
+fn advance(old_line: &mut usize, new_line: &mut usize, kind: LineKind) {
+    match kind {
+        LineKind::Context => {
+            *old_line += 1;
+            *new_line += 1;
+        }
+        LineKind::Removed => *old_line += 1,
+        LineKind::Added => *new_line += 1,
+    }
+}
+
+/// Counts inserted and deleted lines between two versions of a file.
+pub fn count_changed_lines(before: &[u8], after: &[u8]) -> (usize, usize) {
+    let count = |content: &[u8]| content.split_inclusive(|b| *b == b'\n').count();
+    let diff = ContentDiff::by_line([before, after]);
+    diff.hunks()
+        .filter(|hunk| hunk.kind == DiffHunkKind::Different)
+        .fold((0, 0), |(insertions, deletions), hunk| {
+            (
+                insertions + count(hunk.contents[1]),
+                deletions + count(hunk.contents[0]),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(hunks: &[Hunk]) -> String {
+        let mut out = String::new();
+        for hunk in hunks {
+            out.push_str(&hunk.header());
+            out.push('\n');
+            for line in &hunk.lines {
+                let sign = match line.kind {
+                    LineKind::Context => ' ',
+                    LineKind::Removed => '-',
+                    LineKind::Added => '+',
+                };
+                out.push(sign);
+                out.push_str(&line.text);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_unified_hunks_single_change() {
+        let hunks = unified_hunks(b"a\nb\nc\nd\ne\n", b"a\nb\nC\nd\ne\n", 1);
+        assert_eq!(render(&hunks), "@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n");
+    }
+
+    #[test]
+    fn test_unified_hunks_splits_distant_changes() {
+        let before = b"1\n2\n3\n4\n5\n6\n7\n8\n";
+        let after = b"one\n2\n3\n4\n5\n6\n7\neight\n";
+        let hunks = unified_hunks(before, after, 1);
+        assert_eq!(
+            render(&hunks),
+            "@@ -1,2 +1,2 @@\n-1\n+one\n 2\n@@ -7,2 +7,2 @@\n 7\n-8\n+eight\n"
+        );
+    }
+
+    #[test]
+    fn test_word_segments() {
+        let hunks = unified_hunks(
+            b"let x = foo(1);\nkeep\n",
+            b"let x = bar(1, 2);\nkeep\nnew line\n",
+            0,
+        );
+        let segments: Vec<String> = word_segments(&hunks[0])
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|segment| {
+                        if segment.changed {
+                            format!("[{}]", segment.text)
+                        } else {
+                            segment.text.clone()
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(segments, ["let x = [foo](1);", "let x = [bar](1[, 2]);"]);
+        // An added line with nothing removed before it isn't refined.
+        assert_eq!(
+            word_segments(&hunks[1]),
+            [vec![Segment {
+                text: "new line".to_string(),
+                changed: false
+            }]]
+        );
+        // Neither are lines with no words in common.
+        let hunks = unified_hunks(b"alpha\n", b"beta\n", 0);
+        assert!(
+            word_segments(&hunks[0])
+                .iter()
+                .flatten()
+                .all(|segment| !segment.changed)
+        );
+    }
+
+    #[test]
+    fn test_unified_hunks_new_file() {
+        let hunks = unified_hunks(b"", b"x\ny\n", DEFAULT_CONTEXT);
+        assert_eq!(render(&hunks), "@@ -0,0 +1,2 @@\n+x\n+y\n");
+    }
+
+    #[test]
+    fn test_count_changed_lines() {
+        assert_eq!(count_changed_lines(b"a\nb\nc\n", b"a\nB\nc\nd\n"), (2, 1));
+        assert_eq!(count_changed_lines(b"", b"a\nb"), (2, 0));
+        assert_eq!(count_changed_lines(b"a\n", b"a\n"), (0, 0));
+    }
+}
@@ -0,0 +1,96 @@
+//! `jjka apply-plan`: carry out a split planned by `hunksplit --dry-run
+//! --emit-plan`, after it's been reviewed or edited.
+
+use anyhow::{Result, anyhow};
+use itertools::Itertools as _;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use std::path::Path;
+
+use crate::LineRange;
+use crate::exit_code::Failure;
+use crate::fingerprint::{RemapStatus, remap_selectors};
+use crate::format::{OutputFormat, commit_summary};
+use crate::hunks;
+use crate::split::{PlanFile, apply_split, check_empty, plan_split_with_modes, print_split};
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// Finds the plan's ranges in `commit`, a rewrite of the commit it was made
+/// for, reporting where they went. Refuses if any range's lines are gone.
+async fn remap_ranges(
+    helper: &WorkspaceHelper,
+    plan_file: &PlanFile,
+    commit: &Commit,
+    ranges: &[LineRange],
+) -> Result<Vec<LineRange>> {
+    let old_id = &plan_file.commit_id[..plan_file.commit_id.len().min(12)];
+    if plan_file.hunks.is_empty() {
+        return Err(Failure::Precondition.error(anyhow!(
+            "{} has changed since the plan was made (it was commit {old_id}), and the plan \
+             has no hunk fingerprints to find the ranges again; make a new plan",
+            commit_summary(commit)
+        )));
+    }
+    let new_hunks = hunks::commit_hunks(helper.repo().as_ref(), commit).await?;
+    let remapped = remap_selectors(ranges, &plan_file.hunks, &new_hunks);
+    status!(
+        "{} has changed since the plan was made (it was commit {old_id}); remapping the ranges",
+        commit_summary(commit)
+    );
+    for line in hunks::remap_report(&remapped) {
+        status!("{line}");
+    }
+    if remapped
+        .iter()
+        .any(|selector| selector.status == RemapStatus::Lost)
+    {
+        return Err(Failure::Precondition.error(anyhow!(
+            "Some of the planned lines are no longer changed in {}; make a new plan",
+            commit_summary(commit)
+        )));
+    }
+    Ok(remapped
+        .into_iter()
+        .flat_map(|selector| selector.new)
+        .collect())
+}
+
+pub async fn apply_plan_command(
+    path: &Path,
+    policy: PolicyArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let plan_file = PlanFile::read(path)?;
+    let mut ranges: Vec<LineRange> = plan_file
+        .ranges
+        .iter()
+        .map(|range| LineRange::parse(range))
+        .try_collect()?;
+
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    // If the change has become divergent, the planned commit may still be
+    // one of its commits.
+    let commit = match helper
+        .evaluate_revset(&format!("change_id({})", plan_file.change_id))?
+        .into_iter()
+        .find(|commit| commit.id().hex() == plan_file.commit_id)
+    {
+        Some(commit) => commit,
+        None => helper.resolve_single(&plan_file.change_id)?,
+    };
+    if commit.id().hex() != plan_file.commit_id {
+        ranges = remap_ranges(&helper, &plan_file, &commit, &ranges).await?;
+    }
+    let plan =
+        plan_split_with_modes(helper.repo().as_ref(), &commit, &ranges, &plan_file.modes).await?;
+    check_empty(&plan, plan_file.allow_empty)?;
+    let (selected, remaining) =
+        apply_split(&mut helper, &plan, plan_file.message.as_deref()).await?;
+    if format == OutputFormat::Text {
+        print_split(&commit, &selected, &remaining);
+    }
+    Ok(())
+}
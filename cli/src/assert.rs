@@ -0,0 +1,474 @@
+//! `jjka assert`: check invariants of the repo from scripts and CI.
+//!
+//! Assertions are small boolean expressions over revsets, files and
+//! descriptions:
+//!
+//! ```text
+//! expr  := or
+//! or    := and ("||" and)*
+//! and   := unary ("&&" unary)*
+//! unary := "!" unary | "(" expr ")" | call [("==" | "!=" | "<" | "<=" | ">" | ">=") NUMBER]
+//! call  := NAME "(" [arg ("," arg)*] ")"
+//! ```
+//!
+//! Arguments are taken verbatim up to the next top-level comma, so revsets
+//! can be written unquoted; quote them with `"` or `'` to include commas.
+
+use anyhow::{Result, bail};
+use itertools::Itertools as _;
+use regex::Regex;
+use serde_json::json;
+use std::fmt;
+
+use crate::format::{OutputFormat, commit_summary};
+use crate::workspace::WorkspaceHelper;
+
+/// How many offending commits to list for a failed assertion.
+const MAX_DETAILS: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn apply(self, left: usize, right: usize) -> bool {
+        match self {
+            CmpOp::Eq => left == right,
+            CmpOp::Ne => left != right,
+            CmpOp::Lt => left < right,
+            CmpOp::Le => left <= right,
+            CmpOp::Gt => left > right,
+            CmpOp::Ge => left >= right,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Expr {
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// `revset-is-empty(REVSET)`
+    IsEmpty(String),
+    /// `count(REVSET) OP N`
+    Count(String, CmpOp, usize),
+    /// `file-exists(PATH[, REVISION])`
+    FileExists(String, String),
+    /// `description-matches(REVSET, REGEX)`
+    DescriptionMatches(String, String),
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Not(expr) => write!(f, "!{expr}"),
+            Expr::And(left, right) => write!(f, "({left} && {right})"),
+            Expr::Or(left, right) => write!(f, "({left} || {right})"),
+            Expr::IsEmpty(revset) => write!(f, "revset-is-empty({revset})"),
+            Expr::Count(revset, op, n) => write!(f, "count({revset}) {} {n}", op.as_str()),
+            Expr::FileExists(path, revision) => write!(f, "file-exists({path}, {revision})"),
+            Expr::DescriptionMatches(revset, regex) => {
+                write!(f, "description-matches({revset}, {regex:?})")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if the input continues with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "Invalid assertion \"{}\": {message} at column {}",
+            self.input,
+            self.pos + 1
+        )
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.parse_or()?;
+            if !self.eat(")") {
+                return self.error("expected \")\"");
+            }
+            return Ok(expr);
+        }
+        self.parse_call()
+    }
+
+    fn parse_call(&mut self) -> Result<Expr> {
+        self.skip_whitespace();
+        let name_len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(self.rest().len());
+        if name_len == 0 {
+            return self.error("expected a function name");
+        }
+        let name = &self.rest()[..name_len];
+        let start = self.pos;
+        self.pos += name_len;
+        if !self.eat("(") {
+            return self.error("expected \"(\"");
+        }
+        let args = self.parse_args()?;
+        let input = self.input;
+        let arity_error = |expected: &str| {
+            bail!(
+                "Invalid assertion \"{input}\": {name}() takes {expected}, got {}",
+                args.len()
+            )
+        };
+        match name {
+            "revset-is-empty" | "is-empty" => match args.as_slice() {
+                [revset] => Ok(Expr::IsEmpty(revset.clone())),
+                _ => arity_error("a revset"),
+            },
+            "count" => {
+                let [revset] = args.as_slice() else {
+                    return arity_error("a revset");
+                };
+                let revset = revset.clone();
+                let Some(op) = self.parse_cmp_op() else {
+                    return self.error("count() must be compared with a number");
+                };
+                self.skip_whitespace();
+                let digits = self
+                    .rest()
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(self.rest().len());
+                let Ok(n) = self.rest()[..digits].parse() else {
+                    return self.error("expected a number");
+                };
+                self.pos += digits;
+                Ok(Expr::Count(revset, op, n))
+            }
+            "file-exists" => match args.as_slice() {
+                [path] => Ok(Expr::FileExists(path.clone(), "@".to_string())),
+                [path, revision] => Ok(Expr::FileExists(path.clone(), revision.clone())),
+                _ => arity_error("a path and an optional revision"),
+            },
+            "description-matches" => match args.as_slice() {
+                [revset, regex] => Ok(Expr::DescriptionMatches(revset.clone(), regex.clone())),
+                _ => arity_error("a revset and a regex"),
+            },
+            _ => {
+                self.pos = start;
+                self.error(&format!("unknown function {name}()"))
+            }
+        }
+    }
+
+    fn parse_cmp_op(&mut self) -> Option<CmpOp> {
+        // Two-character operators first, so "<=" isn't read as "<".
+        [
+            ("==", CmpOp::Eq),
+            ("!=", CmpOp::Ne),
+            ("<=", CmpOp::Le),
+            (">=", CmpOp::Ge),
+            ("<", CmpOp::Lt),
+            (">", CmpOp::Gt),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token))
+        .map(|(_, op)| op)
+    }
+
+    /// Parses the arguments after the opening parenthesis of a call, up to
+    /// and including the closing one.
+    fn parse_args(&mut self) -> Result<Vec<String>> {
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok(args);
+        }
+        loop {
+            self.skip_whitespace();
+            args.push(self.parse_arg()?);
+            if self.eat(")") {
+                return Ok(args);
+            }
+            if !self.eat(",") {
+                return self.error("expected \",\" or \")\"");
+            }
+        }
+    }
+
+    fn parse_arg(&mut self) -> Result<String> {
+        let rest = self.rest();
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let Some(len) = rest[1..].find(quote) else {
+                return self.error("unterminated string");
+            };
+            self.pos += len + 2;
+            return Ok(rest[1..=len].to_string());
+        }
+        // Take everything up to a comma or closing parenthesis outside of
+        // nested parentheses, e.g. "conflicts() & ::@".
+        let mut depth = 0;
+        for (index, c) in rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ',' | ')' if depth == 0 => {
+                    let arg = rest[..index].trim();
+                    if arg.is_empty() {
+                        return self.error("expected an argument");
+                    }
+                    self.pos += index;
+                    return Ok(arg.to_string());
+                }
+                _ => {}
+            }
+        }
+        self.pos = self.input.len();
+        self.error("expected \")\"")
+    }
+}
+
+fn parse(input: &str) -> Result<Expr> {
+    let mut parser = Parser { input, pos: 0 };
+    let expr = parser.parse_or()?;
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        return parser.error("unexpected input");
+    }
+    Ok(expr)
+}
+
+/// Whether an expression holds, and if not, why.
+struct Outcome {
+    holds: bool,
+    details: Vec<String>,
+}
+
+impl Outcome {
+    fn new(holds: bool, details: Vec<String>) -> Self {
+        Outcome { holds, details }
+    }
+}
+
+fn commit_details(commits: &[jj_lib::commit::Commit]) -> Vec<String> {
+    let mut details = commits
+        .iter()
+        .take(MAX_DETAILS)
+        .map(commit_summary)
+        .collect_vec();
+    if commits.len() > MAX_DETAILS {
+        details.push(format!("... and {} more", commits.len() - MAX_DETAILS));
+    }
+    details
+}
+
+fn evaluate(helper: &WorkspaceHelper, expr: &Expr) -> Result<Outcome> {
+    Ok(match expr {
+        Expr::Not(expr) => Outcome::new(!evaluate(helper, expr)?.holds, vec![]),
+        Expr::And(left, right) => {
+            let left = evaluate(helper, left)?;
+            let right = evaluate(helper, right)?;
+            let details = [left.details, right.details].concat();
+            Outcome::new(left.holds && right.holds, details)
+        }
+        Expr::Or(left, right) => {
+            let left = evaluate(helper, left)?;
+            if left.holds {
+                return Ok(left);
+            }
+            let right = evaluate(helper, right)?;
+            Outcome::new(right.holds, [left.details, right.details].concat())
+        }
+        Expr::IsEmpty(revset) => {
+            let commits = helper.evaluate_revset(revset)?;
+            Outcome::new(commits.is_empty(), commit_details(&commits))
+        }
+        Expr::Count(revset, op, n) => {
+            let count = helper.evaluate_revset(revset)?.len();
+            Outcome::new(
+                op.apply(count, *n),
+                vec![format!("\"{revset}\" has {count} revision(s)")],
+            )
+        }
+        Expr::FileExists(path, revision) => {
+            let commit = helper.resolve_single(revision)?;
+            let repo_path = helper.parse_file_path(path)?;
+            let exists = commit.tree()?.path_value(&repo_path)?.is_present();
+            Outcome::new(
+                exists,
+                vec![format!(
+                    "{path} doesn't exist in {}",
+                    commit_summary(&commit)
+                )],
+            )
+        }
+        Expr::DescriptionMatches(revset, regex) => {
+            let regex = match Regex::new(regex) {
+                Ok(regex) => regex,
+                Err(err) => bail!("Invalid regex \"{regex}\": {err}"),
+            };
+            let mismatched = helper
+                .evaluate_revset(revset)?
+                .into_iter()
+                .filter(|commit| !regex.is_match(commit.description()))
+                .collect_vec();
+            Outcome::new(mismatched.is_empty(), commit_details(&mismatched))
+        }
+    })
+}
+
+pub async fn assert_command(assertions: Vec<String>, format: OutputFormat) -> Result<()> {
+    // Parse everything first so a typo in the last assertion doesn't go
+    // unnoticed behind an earlier failure.
+    let exprs = assertions
+        .iter()
+        .map(|assertion| parse(assertion))
+        .collect::<Result<Vec<_>>>()?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+
+    let mut failed = 0;
+    let mut results = Vec::new();
+    for (assertion, expr) in assertions.iter().zip(&exprs) {
+        let outcome = evaluate(&helper, expr)?;
+        if !outcome.holds {
+            failed += 1;
+        }
+        match format {
+            OutputFormat::Text if outcome.holds => println!("ok      {assertion}"),
+            OutputFormat::Text => {
+                println!("FAILED  {assertion}");
+                for detail in &outcome.details {
+                    println!("          {detail}");
+                }
+            }
+            OutputFormat::Json => results.push(json!({
+                "assertion": assertion,
+                "holds": outcome.holds,
+                "details": outcome.details,
+            })),
+        }
+    }
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    if failed > 0 {
+        bail!("{failed} of {} assertion(s) failed", assertions.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("revset-is-empty(conflicts())").unwrap(),
+            Expr::IsEmpty("conflicts()".to_string())
+        );
+        assert_eq!(
+            parse("!file-exists('a, b.txt') || count(trunk()..@ & ~empty()) <= 3").unwrap(),
+            Expr::Or(
+                Box::new(Expr::Not(Box::new(Expr::FileExists(
+                    "a, b.txt".to_string(),
+                    "@".to_string()
+                )))),
+                Box::new(Expr::Count(
+                    "trunk()..@ & ~empty()".to_string(),
+                    CmpOp::Le,
+                    3
+                )),
+            )
+        );
+        assert_eq!(
+            parse("(is-empty(a) || is-empty(b)) && description-matches(@-, \"^[A-Z]\")")
+                .unwrap()
+                .to_string(),
+            "((revset-is-empty(a) || revset-is-empty(b)) && description-matches(@-, \"^[A-Z]\"))"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| parse(input).unwrap_err().to_string();
+        assert_eq!(
+            error("revset-is-empty(conflicts()"),
+            "Invalid assertion \"revset-is-empty(conflicts()\": expected \")\" at column 28"
+        );
+        assert_eq!(
+            error("frobnicate(@)"),
+            "Invalid assertion \"frobnicate(@)\": unknown function frobnicate() at column 1"
+        );
+        assert_eq!(
+            error("count(@)"),
+            "Invalid assertion \"count(@)\": count() must be compared with a number at column 9"
+        );
+        assert_eq!(
+            error("file-exists()"),
+            "Invalid assertion \"file-exists()\": file-exists() takes a path and an optional \
+             revision, got 0"
+        );
+        assert_eq!(
+            error("is-empty(@) extra"),
+            "Invalid assertion \"is-empty(@) extra\": unexpected input at column 13"
+        );
+    }
+}
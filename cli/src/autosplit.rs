@@ -0,0 +1,661 @@
+//! `jjka autosplit`: split a revision into the commits a strategy script
+//! chooses, so teams can encode their own conventions for what goes
+//! together.
+//!
+//! The script is a shell command line like a hook's. It gets the revision
+//! and its hunks as JSON on stdin, with the lines each hunk removes and
+//! adds, and prints the commits to make:
+//!
+//! ```json
+//! {"commits": [{"description": "Update docs", "ranges": ["README.md:1-10"]}]}
+//! ```
+//!
+//! The commits are made in order below the revision, each with the lines of
+//! its ranges; whatever no commit takes stays in the revision, described
+//! with the optional top-level `description` or its own.
+//!
+//! `--codeowners` is a built-in strategy instead: it makes a commit for
+//! each set of owners the revision's CODEOWNERS file gives its files, so
+//! each commit can be reviewed by a single team. `--generated` is another:
+//! it moves the human-written changes into a commit below, leaving the
+//! revision with the changes to lockfiles and generated code, which match
+//! `jjka.autosplit.generated`. Without any of these, the buckets configured
+//! in `[jjka.autosplit.rules]` are the strategy.
+
+use anyhow::{Context, Result, anyhow, bail};
+use itertools::Itertools as _;
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jjka_lib::unified_diff::{LineKind, unified_hunks};
+use jjka_lib::{Selection, SplitFile};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+
+use crate::LineRange;
+use crate::checks;
+use crate::codeowners::CodeOwners;
+use crate::describe::glob_match;
+use crate::exit_code::{Failure, ResultExt as _};
+use crate::failpoint;
+use crate::fingerprint::fingerprint_file;
+use crate::format::{OutputFormat, commit_summary, subject};
+use crate::perf;
+use crate::serve::CommitInfo;
+use crate::sigpipe;
+use crate::tree_diff::commit_file_contents;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// A hunk as the script sees it.
+#[derive(Serialize)]
+struct ScriptHunk {
+    /// The `path:start-end` range to select the hunk with.
+    range: String,
+    path: String,
+    start: usize,
+    end: usize,
+    /// The content fingerprint `jjka hunks` lists.
+    id: String,
+    removed: Vec<String>,
+    added: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct Bucket {
+    description: String,
+    ranges: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct Strategy {
+    commits: Vec<Bucket>,
+    /// The revision's new description, once the commits are taken out.
+    #[serde(default)]
+    description: Option<String>,
+}
+
+async fn script_hunks(helper: &WorkspaceHelper, commit: &Commit) -> Result<Vec<ScriptHunk>> {
+    let mut hunks = Vec::new();
+    for file in commit_file_contents(helper.repo().as_ref(), commit).await? {
+        let path = file.path.as_internal_file_string();
+        // Both split the diff into the same blocks of changed lines.
+        let fingerprints = fingerprint_file(path, &file.before, &file.after);
+        let diffs = unified_hunks(&file.before, &file.after, 0);
+        for (hunk, diff) in fingerprints.into_iter().zip(diffs) {
+            let lines = |kind| {
+                diff.lines
+                    .iter()
+                    .filter(|line| line.kind == kind)
+                    .map(|line| line.text.clone())
+                    .collect()
+            };
+            hunks.push(ScriptHunk {
+                range: format!("{}:{}-{}", hunk.path, hunk.start, hunk.end),
+                removed: lines(LineKind::Removed),
+                added: lines(LineKind::Added),
+                path: hunk.path,
+                start: hunk.start,
+                end: hunk.end,
+                id: hunk.fingerprint.content,
+            });
+        }
+    }
+    Ok(hunks)
+}
+
+/// Runs the strategy from the workspace root and parses what it prints.
+fn run_script(helper: &WorkspaceHelper, script: &str, input: &[u8]) -> Result<Strategy> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .current_dir(helper.workspace_root())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start the strategy script {script:?}"))?;
+    // A script that doesn't read all of its input closes the pipe early.
+    let _ = sigpipe::write_to_child(child.stdin.take().unwrap(), input);
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("The strategy script exited with {}", output.status);
+    }
+    serde_json::from_slice(&output.stdout)
+        .context("The strategy script didn't print a valid list of commits")
+        .failure(Failure::Usage)
+}
+
+/// Where the commits to make come from.
+pub enum Source {
+    /// A strategy script, run as a shell command line.
+    Script(String),
+    /// The revision's CODEOWNERS file.
+    CodeOwners,
+    /// The patterns of generated files in `jjka.autosplit.generated`.
+    Generated,
+    /// The buckets in `[jjka.autosplit.rules]`.
+    Rules,
+}
+
+/// The files `--generated` separates when `jjka.autosplit.generated` isn't
+/// set.
+const GENERATED: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    "*.pb.go",
+    "*_pb2.py",
+    "*_pb2.pyi",
+];
+
+/// `description` with `owners` after its subject.
+fn owned_description(description: &str, owners: &str) -> String {
+    let (subject, body) = description.split_once('\n').unwrap_or((description, ""));
+    let subject = match subject.trim_end() {
+        "" => "Changes",
+        subject => subject,
+    };
+    format!("{subject} ({owners})\n{body}")
+}
+
+/// Makes a commit for each group `group_of` puts hunks in, in the order of
+/// its first hunk and described by `describe`. Hunks in no group stay in
+/// the revision; if there are none, the last group's hunks stay in it, and
+/// it gets that group's description.
+fn grouped_strategy<'a>(
+    hunks: impl IntoIterator<Item = &'a ScriptHunk>,
+    group_of: impl Fn(&ScriptHunk) -> Option<String>,
+    describe: impl Fn(&str) -> String,
+) -> Strategy {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    let mut ungrouped = false;
+    for hunk in hunks {
+        let Some(key) = group_of(hunk) else {
+            ungrouped = true;
+            continue;
+        };
+        match groups.iter_mut().find(|(group, _)| *group == key) {
+            Some((_, ranges)) => ranges.push(hunk.range.clone()),
+            None => groups.push((key, vec![hunk.range.clone()])),
+        }
+    }
+    let mut commits: Vec<Bucket> = groups
+        .into_iter()
+        .map(|(key, ranges)| Bucket {
+            description: describe(&key),
+            ranges,
+        })
+        .collect();
+    let description = if ungrouped {
+        None
+    } else {
+        commits.pop().map(|bucket| bucket.description)
+    };
+    Strategy {
+        commits,
+        description,
+    }
+}
+
+/// The `--codeowners` strategy: a commit for each set of owners, in the
+/// order of their first files, with the unowned changes left in the
+/// revision. If nothing is unowned, the last owners' changes stay in it.
+fn codeowners_strategy(description: &str, hunks: &[ScriptHunk], owners: &CodeOwners) -> Strategy {
+    grouped_strategy(
+        hunks,
+        |hunk| {
+            let owners = owners.owners(&hunk.path);
+            (!owners.is_empty()).then(|| owners.join(" "))
+        },
+        |owners| owned_description(description, owners),
+    )
+}
+
+/// A bucket of `[jjka.autosplit.rules]`: the paths that go in it and how
+/// to describe its commit.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct SplitRule {
+    paths: Vec<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+const DEFAULT_RULE_MESSAGE: &str = "{subject} ({name})";
+
+/// Fills in the `message` template of the rule `name` for a revision with
+/// `description`.
+fn rule_message(name: &str, rule: &SplitRule, description: &str) -> Result<String> {
+    let (subject, body) = description.split_once('\n').unwrap_or((description, ""));
+    let subject = match subject.trim_end() {
+        "" => "Changes",
+        subject => subject,
+    };
+    let template = rule.message.as_deref().unwrap_or(DEFAULT_RULE_MESSAGE);
+    let placeholder = Regex::new(r"\{([a-z]*)\}").unwrap();
+    let mut unknown = None;
+    let rendered =
+        placeholder.replace_all(template, |captures: &regex::Captures| match &captures[1] {
+            "name" => name.to_string(),
+            "subject" => subject.to_string(),
+            "body" => body.trim().to_string(),
+            other => {
+                unknown.get_or_insert_with(|| other.to_string());
+                String::new()
+            }
+        });
+    if let Some(unknown) = unknown {
+        return Err(Failure::Usage.error(anyhow!(
+            "Unknown placeholder {{{unknown}}} in jjka.autosplit.rules.{name}.message"
+        )));
+    }
+    Ok(format!("{}\n", rendered.trim_end()))
+}
+
+/// The strategy of `[jjka.autosplit.rules]`, tried in name order: a commit
+/// for each rule with the changes to the paths it's the first to match, in
+/// the order of the rules.
+fn rules_strategy(
+    description: &str,
+    hunks: &[ScriptHunk],
+    rules: &[(String, SplitRule)],
+) -> Result<Strategy> {
+    let messages: Vec<String> = rules
+        .iter()
+        .map(|(name, rule)| rule_message(name, rule, description))
+        .try_collect()?;
+    let rule_of = |hunk: &ScriptHunk| {
+        rules
+            .iter()
+            .position(|(_, rule)| rule.paths.iter().any(|glob| glob_match(glob, &hunk.path)))
+    };
+    let hunks = hunks
+        .iter()
+        .sorted_by_key(|hunk| rule_of(hunk).unwrap_or(usize::MAX));
+    Ok(grouped_strategy(
+        hunks,
+        |hunk| rule_of(hunk).map(|index| rules[index].0.clone()),
+        |name| {
+            let index = rules.iter().position(|(rule, _)| rule == name).unwrap();
+            messages[index].clone()
+        },
+    ))
+}
+
+/// The `--generated` strategy: the changes to files that match none of
+/// `patterns` go in a commit with the revision's description, and the
+/// revision keeps the rest. Returns `None` unless both kinds are changed.
+fn generated_strategy(
+    description: &str,
+    hunks: &[ScriptHunk],
+    patterns: &[String],
+) -> Option<Strategy> {
+    let (generated, written): (Vec<&ScriptHunk>, Vec<&ScriptHunk>) = hunks
+        .iter()
+        .partition(|hunk| patterns.iter().any(|glob| glob_match(glob, &hunk.path)));
+    if generated.is_empty() || written.is_empty() {
+        return None;
+    }
+    let subject = description.lines().next().unwrap_or("").trim_end();
+    let remaining = match subject {
+        "" => "Update generated files\n".to_string(),
+        subject => format!("{subject} (generated files)\n"),
+    };
+    Some(Strategy {
+        commits: vec![Bucket {
+            description: description.to_string(),
+            ranges: written.iter().map(|hunk| hunk.range.clone()).collect(),
+        }],
+        description: Some(remaining),
+    })
+}
+
+fn parse_ranges(bucket: &Bucket) -> Result<Vec<LineRange>> {
+    bucket
+        .ranges
+        .iter()
+        .map(|range| LineRange::parse(range))
+        .try_collect()
+        .with_context(|| format!("Invalid range for {:?}", bucket.description))
+        .failure(Failure::Usage)
+}
+
+pub async fn autosplit_command(
+    revision: String,
+    source: Source,
+    dry_run: bool,
+    policy: PolicyArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let commit = helper.resolve_single(&revision)?;
+
+    let hunks = script_hunks(&helper, &commit).await?;
+    let strategy = match source {
+        Source::Script(script) => {
+            let input = json!({
+                "commit": CommitInfo::new(&commit),
+                "description": commit.description(),
+                "hunks": hunks,
+            });
+            run_script(&helper, &script, &serde_json::to_vec(&input)?)?
+        }
+        Source::CodeOwners => {
+            let Some((location, owners)) =
+                CodeOwners::load(helper.repo().as_ref(), &commit).await?
+            else {
+                return Err(Failure::Precondition.error(anyhow!(
+                    "{} has no CODEOWNERS file to split by",
+                    commit_summary(&commit)
+                )));
+            };
+            let strategy = codeowners_strategy(commit.description(), &hunks, &owners);
+            if strategy.commits.is_empty() {
+                status!(
+                    "The changes in {} all have the same owners in {location}",
+                    commit_summary(&commit)
+                );
+                return Ok(());
+            }
+            strategy
+        }
+        Source::Generated => {
+            let patterns = helper
+                .settings()
+                .get::<Vec<String>>("jjka.autosplit.generated")
+                .optional()?
+                .unwrap_or_else(|| GENERATED.iter().map(|glob| glob.to_string()).collect());
+            let Some(strategy) = generated_strategy(commit.description(), &hunks, &patterns) else {
+                status!(
+                    "{} doesn't change both generated and other files",
+                    commit_summary(&commit)
+                );
+                return Ok(());
+            };
+            strategy
+        }
+        Source::Rules => {
+            let Some(rules) = helper
+                .settings()
+                .get::<BTreeMap<String, SplitRule>>("jjka.autosplit.rules")
+                .optional()?
+            else {
+                return Err(Failure::Usage.error(anyhow!(
+                    "Pass --script, --codeowners or --generated, or configure \
+                     [jjka.autosplit.rules]"
+                )));
+            };
+            let rules: Vec<_> = rules.into_iter().collect();
+            let strategy = rules_strategy(commit.description(), &hunks, &rules)?;
+            if strategy.commits.is_empty() {
+                status!(
+                    "jjka.autosplit.rules find nothing to split out of {}",
+                    commit_summary(&commit)
+                );
+                return Ok(());
+            }
+            strategy
+        }
+    };
+    if strategy.commits.is_empty() {
+        status!(
+            "The strategy chose no commits to split out of {}",
+            commit_summary(&commit)
+        );
+        return Ok(());
+    }
+
+    // The remaining commit keeps the revision's tree, so every bucket's
+    // ranges stay numbered as in the revision.
+    let mut tx = helper.start_transaction();
+    let mut current = commit.clone();
+    let mut created = Vec::new();
+    let mut planned: Vec<Vec<SplitFile>> = Vec::new();
+    for bucket in &strategy.commits {
+        let selection: Selection = parse_ranges(bucket)?.iter().collect();
+        let plan = jjka_lib::plan_split_with_jobs(
+            tx.repo(),
+            &current,
+            &selection,
+            perf::jobs(),
+            |_path| {},
+        )
+        .await
+        .and_then(|plan| plan.check_not_empty().map(|()| plan))
+        .with_context(|| format!("Can't make the commit {:?}", bucket.description))?;
+        let outcome = jjka_lib::split(tx.repo_mut(), &plan, Some(&bucket.description))?;
+        failpoint::hit("split")?;
+        tx.repo_mut().rebase_descendants()?;
+        planned.push(plan.files);
+        created.push(outcome.selected);
+        current = outcome.remaining;
+    }
+    if let Some(description) = &strategy.description {
+        current = tx
+            .repo_mut()
+            .rewrite_commit(&current)
+            .set_description(description)
+            .write()?;
+    }
+
+    if dry_run {
+        if format == OutputFormat::Json {
+            let commits: Vec<_> = strategy
+                .commits
+                .iter()
+                .zip(&planned)
+                .map(|(bucket, files)| {
+                    json!({ "description": bucket.description, "ranges": bucket.ranges, "files": files })
+                })
+                .collect();
+            let report = json!({
+                "commit": CommitInfo::new(&commit),
+                "commits": commits,
+                "remaining": current.description(),
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("Would split {} into:", commit_summary(&commit));
+            for (selected, files) in created.iter().zip(&planned) {
+                let files = files
+                    .iter()
+                    .map(|file| {
+                        format!(
+                            "{} +{} -{}",
+                            file.path, file.selected_insertions, file.selected_deletions
+                        )
+                    })
+                    .join(", ");
+                println!("  {}  ({files})", subject(selected));
+            }
+            println!("  {}  (the rest)", subject(&current));
+            status!("This is a dry run; nothing was changed.");
+        }
+        return Ok(());
+    }
+
+    let produced: Vec<&Commit> = created.iter().chain([&current]).collect();
+    checks::check_commits(&helper, &produced)?;
+    helper
+        .finish_transaction(tx, &format!("autosplit commit {}", commit_summary(&commit)))
+        .await?;
+    if format == OutputFormat::Text {
+        println!("Split {} into:", commit_summary(&commit));
+        for selected in &created {
+            println!("  {}", commit_summary(selected));
+        }
+        println!("  {}", commit_summary(&current));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_strategy() {
+        let strategy: Strategy = serde_json::from_str(
+            r#"{"commits": [{"description": "Docs", "ranges": ["README.md:1-2"]}], "description": "Code"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            strategy,
+            Strategy {
+                commits: vec![Bucket {
+                    description: "Docs".to_string(),
+                    ranges: vec!["README.md:1-2".to_string()],
+                }],
+                description: Some("Code".to_string()),
+            }
+        );
+        // A typo shouldn't silently leave everything in the revision.
+        assert!(serde_json::from_str::<Strategy>(r#"{"comits": []}"#).is_err());
+        let err = parse_ranges(&Bucket {
+            description: "Docs".to_string(),
+            ranges: vec!["README.md".to_string()],
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid range for \"Docs\"");
+    }
+
+    #[test]
+    fn test_codeowners_strategy() {
+        let hunk = |range: &str| ScriptHunk {
+            range: range.to_string(),
+            path: range.split(':').next().unwrap().to_string(),
+            start: 1,
+            end: 1,
+            id: String::new(),
+            removed: Vec::new(),
+            added: Vec::new(),
+        };
+        let owners = CodeOwners::parse("*.rs @core\n/docs/ @docs\n");
+        let hunks = [
+            hunk("docs/a.md:1-1"),
+            hunk("src/lib.rs:1-2"),
+            hunk("docs/b.md:3-4"),
+        ];
+        let strategy = codeowners_strategy("Add x\n\nWhy.\n", &hunks, &owners);
+        assert_eq!(
+            strategy,
+            Strategy {
+                commits: vec![Bucket {
+                    description: "Add x (@docs)\n\nWhy.\n".to_string(),
+                    ranges: vec!["docs/a.md:1-1".to_string(), "docs/b.md:3-4".to_string()],
+                }],
+                description: Some("Add x (@core)\n\nWhy.\n".to_string()),
+            }
+        );
+
+        // Unowned changes stay in the revision, which keeps its description.
+        let strategy = codeowners_strategy(
+            "Add x",
+            &[hunk("src/lib.rs:1-2"), hunk("README:1-1")],
+            &owners,
+        );
+        assert_eq!(strategy.commits.len(), 1);
+        assert_eq!(strategy.commits[0].description, "Add x (@core)\n");
+        assert_eq!(strategy.description, None);
+    }
+
+    #[test]
+    fn test_rules_strategy() {
+        let hunk = |range: &str| ScriptHunk {
+            range: range.to_string(),
+            path: range.split(':').next().unwrap().to_string(),
+            start: 1,
+            end: 1,
+            id: String::new(),
+            removed: Vec::new(),
+            added: Vec::new(),
+        };
+        let rule = |paths: &[&str], message: Option<&str>| SplitRule {
+            paths: paths.iter().map(|glob| glob.to_string()).collect(),
+            message: message.map(str::to_string),
+        };
+        let rules = vec![
+            (
+                "docs".to_string(),
+                rule(&["*.md"], Some("docs: {subject}\n\n{body}")),
+            ),
+            ("tests".to_string(), rule(&["tests/**"], None)),
+        ];
+        let hunks = [
+            hunk("tests/a.rs:1-2"),
+            hunk("src/lib.rs:1-1"),
+            hunk("README.md:3-4"),
+        ];
+        let strategy = rules_strategy("Add x\n\nWhy.\n", &hunks, &rules).unwrap();
+        assert_eq!(
+            strategy,
+            Strategy {
+                commits: vec![
+                    Bucket {
+                        description: "docs: Add x\n\nWhy.\n".to_string(),
+                        ranges: vec!["README.md:3-4".to_string()],
+                    },
+                    Bucket {
+                        description: "Add x (tests)\n".to_string(),
+                        ranges: vec!["tests/a.rs:1-2".to_string()],
+                    },
+                ],
+                description: None,
+            }
+        );
+
+        let bad = vec![(
+            "docs".to_string(),
+            rule(&["*.md"], Some("{kind}: {subject}")),
+        )];
+        let err = rules_strategy("Add x", &hunks, &bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown placeholder {kind} in jjka.autosplit.rules.docs.message"
+        );
+    }
+
+    #[test]
+    fn test_generated_strategy() {
+        let hunk = |range: &str| ScriptHunk {
+            range: range.to_string(),
+            path: range.split(':').next().unwrap().to_string(),
+            start: 1,
+            end: 1,
+            id: String::new(),
+            removed: Vec::new(),
+            added: Vec::new(),
+        };
+        let patterns: Vec<String> = GENERATED.iter().map(|glob| glob.to_string()).collect();
+        let hunks = [
+            hunk("Cargo.lock:1-9"),
+            hunk("api/user.pb.go:1-3"),
+            hunk("src/lib.rs:2-3"),
+        ];
+        let strategy = generated_strategy("Bump serde\n\nFor the fix.\n", &hunks, &patterns);
+        assert_eq!(
+            strategy,
+            Some(Strategy {
+                commits: vec![Bucket {
+                    description: "Bump serde\n\nFor the fix.\n".to_string(),
+                    ranges: vec!["src/lib.rs:2-3".to_string()],
+                }],
+                description: Some("Bump serde (generated files)\n".to_string()),
+            })
+        );
+        assert_eq!(generated_strategy("", &hunks[..2], &patterns), None);
+        assert_eq!(generated_strategy("", &hunks[2..], &patterns), None);
+    }
+}
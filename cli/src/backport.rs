@@ -0,0 +1,393 @@
+//! `jjka backport`: cherry-pick commits onto release bookmarks.
+//!
+//! Each target bookmark gets its own chain of cherry-picks and a bookmark
+//! on top, all in one operation, or just the selected lines of a revision
+//! with `--lines`. With `--pr`, each chain is pushed and gets a review into
+//! its target's branch on the remote's forge.
+
+use anyhow::{Result, anyhow, bail};
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::op_store::RefTarget;
+use jj_lib::ref_name::RefName;
+use jj_lib::repo::Repo;
+use jj_lib::transaction::Transaction;
+use jjka_lib::EmptyCommit;
+
+use crate::exit_code::Failure;
+use crate::format::{commit_summary, short_change_id, subject};
+use crate::patch_id::patch_id;
+use crate::progress::Progress;
+use crate::remote::push_bookmarks;
+use crate::split;
+use crate::stack::Ci;
+use crate::trailer::add_trailer;
+use crate::ui::status;
+use crate::workspace::{self, WorkspaceHelper};
+
+/// The trailer that records the patch id of a backport's source.
+const PATCH_ID_TRAILER: &str = "Backport-Patch-Id";
+
+/// Appends the `(backported from ...)` marker linking back to the source
+/// change, and a trailer with the source's patch id, so a range-diff can
+/// later tell whether the copy still matches it. A copy of only some of
+/// the source's lines never will, so it gets no trailer.
+fn backport_description(
+    description: &str,
+    source_change_id: &str,
+    source_patch_id: Option<&str>,
+) -> String {
+    let description = description.trim_end();
+    let marker = format!("(backported from {source_change_id})");
+    let description = if description.is_empty() {
+        format!("{marker}\n")
+    } else {
+        format!("{description}\n\n{marker}\n")
+    };
+    match source_patch_id {
+        Some(patch_id) => add_trailer(&description, PATCH_ID_TRAILER, patch_id),
+        None => description,
+    }
+}
+
+/// The title of the review of a backport onto `onto` of the commits with
+/// `subjects`.
+fn review_title(onto: &str, subjects: &[&str]) -> String {
+    match subjects {
+        [subject] => format!("[{onto}] {subject}"),
+        _ => format!("[{onto}] Backport {} commits", subjects.len()),
+    }
+}
+
+/// The description of the review of `backported` commits: the source of
+/// each, and which ones need their conflicts resolved.
+fn review_description(backported: &[Backported]) -> String {
+    let mut lines = vec!["Backports:".to_string()];
+    for entry in backported {
+        let conflicted = if entry.conflicted {
+            " (conflicted)"
+        } else {
+            ""
+        };
+        lines.push(format!("- {}{conflicted}", commit_summary(&entry.source)));
+    }
+    lines.join("\n")
+}
+
+struct Backported {
+    source: Commit,
+    new_commit: Commit,
+    conflicted: bool,
+    /// Whether the copy's patch id is the source's, or `None` if only some
+    /// of the source's lines were picked, so they can't be compared.
+    patch_ids_match: Option<bool>,
+}
+
+/// The backport onto one target bookmark.
+struct Target {
+    onto: String,
+    bookmark: String,
+    backported: Vec<Backported>,
+}
+
+/// The change each source makes: the tree before it and after it, which is
+/// only the `lines` selected of it if there are any.
+async fn source_changes(
+    helper: &WorkspaceHelper,
+    sources: &[Commit],
+    lines: &[String],
+) -> Result<Vec<(MergedTree, MergedTree)>> {
+    let repo = helper.repo().as_ref();
+    if lines.is_empty() {
+        let mut changes = Vec::new();
+        for source in sources {
+            changes.push((source.parent_tree(repo)?, source.tree_async().await?));
+        }
+        return Ok(changes);
+    }
+    let [source] = sources else {
+        return Err(Failure::Usage.error(anyhow!(
+            "--lines selects lines of one revision, but there are {} to backport",
+            sources.len()
+        )));
+    };
+    let ranges = workspace::parse_ranges(lines)?;
+    let plan = split::plan_split_with_modes(repo, source, &ranges, &[]).await?;
+    split::check_unchanged(&plan, true)?;
+    if plan.empty == Some(EmptyCommit::Selected) {
+        return Err(Failure::Usage.error(anyhow!(
+            "The selected lines aren't changed in {}, so there is nothing to backport",
+            commit_summary(source)
+        )));
+    }
+    let selected_tree = repo.store().get_root_tree(&plan.selected_tree_id)?;
+    Ok(vec![(source.parent_tree(repo)?, selected_tree)])
+}
+
+/// Cherry-picks `sources`, with `changes`, onto `onto_id` as a chain in
+/// `tx`, skipping those already in it.
+async fn backport_onto(
+    tx: &mut Transaction,
+    sources: &[Commit],
+    changes: &[(MergedTree, MergedTree)],
+    onto_id: &CommitId,
+    partial: bool,
+) -> Result<Vec<Backported>> {
+    let repo = tx.base_repo().clone();
+    let mut dest = repo.store().get_commit(onto_id)?;
+    let mut backported = Vec::new();
+    let mut progress = Progress::new("backport", Some(sources.len()));
+    for (source, (before, after)) in sources.iter().zip(changes) {
+        progress.start_item(&commit_summary(source));
+        if repo.index().is_ancestor(source.id(), onto_id) {
+            continue;
+        }
+        let source_patch_id = if partial {
+            None
+        } else {
+            Some(patch_id(repo.as_ref(), source).await?)
+        };
+        let new_tree = dest
+            .tree_async()
+            .await?
+            .merge(before.clone(), after.clone())
+            .await?;
+        let new_commit = tx
+            .repo_mut()
+            .new_commit(vec![dest.id().clone()], new_tree.id())
+            .set_author(source.author().clone())
+            .set_description(backport_description(
+                source.description(),
+                &source.change_id().reverse_hex(),
+                source_patch_id.as_deref(),
+            ))
+            .write()?;
+        let patch_ids_match = match &source_patch_id {
+            Some(source_patch_id) => {
+                Some(patch_id(tx.repo(), &new_commit).await? == *source_patch_id)
+            }
+            None => None,
+        };
+        dest = new_commit.clone();
+        backported.push(Backported {
+            source: source.clone(),
+            conflicted: new_commit.has_conflict()?,
+            new_commit,
+            patch_ids_match,
+        });
+    }
+    progress.finish();
+    Ok(backported)
+}
+
+fn print_target(target: &Target) {
+    status!(
+        "Backported {} commit(s) onto {}:",
+        target.backported.len(),
+        target.onto
+    );
+    for entry in &target.backported {
+        let mut notes = Vec::new();
+        if entry.conflicted {
+            notes.push("conflicted");
+        }
+        match entry.patch_ids_match {
+            Some(true) => {}
+            Some(false) => notes.push("patch-id differs"),
+            None => notes.push("patch-id not checked"),
+        }
+        let notes = if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", notes.join(", "))
+        };
+        println!(
+            "  {} -> {}{notes}",
+            commit_summary(&entry.source),
+            commit_summary(&entry.new_commit)
+        );
+    }
+    let last = &target.backported.last().unwrap().new_commit;
+    println!(
+        "Bookmark {} points to {}",
+        target.bookmark,
+        commit_summary(last)
+    );
+    let conflicted = target
+        .backported
+        .iter()
+        .filter(|entry| entry.conflicted)
+        .count();
+    if conflicted > 0 {
+        eprintln!(
+            "Warning: {conflicted} commit(s) backported onto {} have conflicts to resolve",
+            target.onto
+        );
+    }
+}
+
+/// The flags of `backport` besides the revisions and targets.
+pub struct BackportOptions {
+    /// Line ranges of the one revision to backport, to pick only them.
+    pub lines: Vec<String>,
+    pub bookmark: Option<String>,
+    pub push: bool,
+    /// Open a review of each backport, which pushes it too.
+    pub pr: bool,
+    pub remote: String,
+}
+
+pub async fn backport_command(
+    revset: String,
+    onto: Vec<String>,
+    options: BackportOptions,
+) -> Result<()> {
+    let BackportOptions {
+        lines,
+        bookmark,
+        push,
+        pr,
+        remote,
+    } = options;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+
+    let sources = helper.evaluate_revset(&revset)?;
+    if sources.is_empty() {
+        bail!("Revset \"{revset}\" didn't resolve to any revisions");
+    }
+    if bookmark.is_some() && onto.len() > 1 {
+        return Err(Failure::Usage.error(anyhow!(
+            "-b names one bookmark, but there are {} targets; leave it out to get \
+             backport/<onto>/<change-id> for each",
+            onto.len()
+        )));
+    }
+
+    let repo = helper.repo().clone();
+    let mut onto_ids = Vec::new();
+    for name in &onto {
+        let onto_target = repo.view().get_local_bookmark(RefName::new(name));
+        let Some(onto_id) = onto_target.as_normal() else {
+            bail!("Bookmark {name} doesn't exist or is conflicted");
+        };
+        onto_ids.push(onto_id.clone());
+    }
+    let changes = source_changes(&helper, &sources, &lines).await?;
+    // Find out which forge to open reviews on before changing anything.
+    let ci = if pr {
+        Some(Ci::new(&helper, &remote)?)
+    } else {
+        None
+    };
+
+    let mut tx = helper.start_transaction();
+    let mut targets = Vec::new();
+    for (name, onto_id) in onto.iter().zip(&onto_ids) {
+        for commit in &sources {
+            if repo.index().is_ancestor(commit.id(), onto_id) {
+                status!("Skipping {} (already in {name})", commit_summary(commit));
+            }
+        }
+        let backported =
+            backport_onto(&mut tx, &sources, &changes, onto_id, !lines.is_empty()).await?;
+        let Some(last) = backported.last() else {
+            status!("Nothing to backport onto {name}");
+            continue;
+        };
+        let bookmark = bookmark
+            .clone()
+            .unwrap_or_else(|| format!("backport/{name}/{}", short_change_id(&last.source)));
+        tx.repo_mut().set_local_bookmark_target(
+            RefName::new(&bookmark),
+            RefTarget::normal(last.new_commit.id().clone()),
+        );
+        targets.push(Target {
+            onto: name.clone(),
+            bookmark,
+            backported,
+        });
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+    helper
+        .finish_transaction(tx, &format!("backport {revset} onto {}", onto.join(", ")))
+        .await?;
+    for target in &targets {
+        print_target(target);
+    }
+
+    if push || pr {
+        let bookmarks: Vec<String> = targets
+            .iter()
+            .map(|target| target.bookmark.clone())
+            .collect();
+        push_bookmarks(&mut helper, &remote, &bookmarks).await?;
+        status!("Pushed {} to {remote}", bookmarks.join(", "));
+    }
+    if let Some(ci) = ci {
+        for target in &targets {
+            let subjects: Vec<&str> = target
+                .backported
+                .iter()
+                .map(|entry| subject(&entry.source))
+                .collect();
+            let (review, created) = ci.open_review(
+                &target.bookmark,
+                &target.onto,
+                &review_title(&target.onto, &subjects),
+                &review_description(&target.backported),
+            )?;
+            let action = if created { "opened" } else { "already open" };
+            println!(
+                "{} into {} ({action}) {}",
+                review.label, target.onto, review.url
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backport_description_appends_marker() {
+        assert_eq!(
+            backport_description("Fix crash\n\nDetails here.\n", "abc", Some("1f2e")),
+            "Fix crash\n\nDetails here.\n\n(backported from abc)\n\nBackport-Patch-Id: 1f2e\n"
+        );
+    }
+
+    #[test]
+    fn test_review_title() {
+        assert_eq!(
+            review_title("release-1.4", &["Fix crash"]),
+            "[release-1.4] Fix crash"
+        );
+        assert_eq!(
+            review_title("release-1.4", &["Fix crash", "Fix leak"]),
+            "[release-1.4] Backport 2 commits"
+        );
+    }
+
+    #[test]
+    fn test_backport_description_empty() {
+        assert_eq!(
+            backport_description("", "abc", Some("1f2e")),
+            "(backported from abc)\n\nBackport-Patch-Id: 1f2e\n"
+        );
+    }
+
+    #[test]
+    fn test_backport_description_without_patch_id() {
+        assert_eq!(
+            backport_description("Fix crash\n", "abc", None),
+            "Fix crash\n\n(backported from abc)\n"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! `jjka backup`: move unpushed work between machines in a single file.
+//!
+//! A backup is a `# jjka backup v1` line, a JSON manifest line, and a git
+//! bundle with the commits. The bundle's refs are named after the head
+//! commits, and restoring fetches them into jj's `refs/jj/keep/` namespace so
+//! git never collects them. Change ids travel in the commits' `change-id`
+//! headers.
+
+use anyhow::{Context, Result, bail};
+use clap::Subcommand;
+use jj_lib::backend::CommitId;
+use jj_lib::git;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_store::{OperationId, RefTarget};
+use jj_lib::ref_name::RefName;
+use jj_lib::repo::Repo;
+use jj_lib::settings::GitSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::format::format_timestamp;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+const HEADER: &str = "# jjka backup v1";
+const BUNDLE_REF_PREFIX: &str = "refs/jjka/backup/";
+
+#[derive(Subcommand, Debug)]
+pub enum BackupCommand {
+    /// Restore the commits and bookmarks in a backup file
+    Restore {
+        /// The backup file
+        file: PathBuf,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// The operation the backup was taken at.
+    operation: String,
+    operation_description: String,
+    operation_time: String,
+    /// Number of commits in the bundle.
+    commits: usize,
+    heads: Vec<String>,
+    /// Local bookmarks pointing into the backed-up commits.
+    bookmarks: BTreeMap<String, String>,
+}
+
+/// Runs git on the repo's backing git store.
+fn git_command(helper: &WorkspaceHelper) -> Result<Command> {
+    let backend =
+        git::get_git_backend(helper.repo().store()).context("Backups need a git-backed repo")?;
+    let settings = GitSettings::from_settings(helper.settings())?;
+    let mut command = Command::new(&settings.executable_path);
+    command.arg("--git-dir").arg(backend.git_repo_path());
+    Ok(command)
+}
+
+fn run_git(mut command: Command) -> Result<()> {
+    let output = command.output().context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+async fn create(helper: &WorkspaceHelper, output: &Path, revset: &str) -> Result<()> {
+    let commits = helper.evaluate_revset(revset)?;
+    if commits.is_empty() {
+        status!("Nothing to back up");
+        return Ok(());
+    }
+    let heads = helper.evaluate_revset(&format!("heads({revset})"))?;
+    // Leave out history the other side is expected to have already, such as
+    // trunk. Restoring needs those commits to be present.
+    let bases = helper.evaluate_revset(&format!("heads(::({revset}) ~ ({revset}) ~ root())"))?;
+    let ids: Vec<CommitId> = commits.iter().map(|commit| commit.id().clone()).collect();
+    let view = helper.repo().view();
+    let bookmarks: BTreeMap<String, String> = view
+        .local_bookmarks()
+        .filter_map(|(name, target)| {
+            let id = target.as_normal()?;
+            ids.contains(id)
+                .then(|| (name.as_str().to_string(), id.hex()))
+        })
+        .collect();
+
+    for head in &heads {
+        let mut command = git_command(helper)?;
+        command.args([
+            "update-ref",
+            &format!("{BUNDLE_REF_PREFIX}{}", head.id().hex()),
+            &head.id().hex(),
+        ]);
+        run_git(command)?;
+    }
+    let bundle = tempfile::NamedTempFile::new()?;
+    let mut command = git_command(helper)?;
+    command
+        .args(["bundle", "create", "--quiet"])
+        .arg(bundle.path());
+    for head in &heads {
+        command.arg(format!("{BUNDLE_REF_PREFIX}{}", head.id().hex()));
+    }
+    for base in &bases {
+        command.arg(format!("^{}", base.id().hex()));
+    }
+    let result = run_git(command);
+    for head in &heads {
+        let mut command = git_command(helper)?;
+        command.args([
+            "update-ref",
+            "-d",
+            &format!("{BUNDLE_REF_PREFIX}{}", head.id().hex()),
+        ]);
+        run_git(command)?;
+    }
+    result?;
+
+    let operation = helper.repo().operation();
+    let manifest = Manifest {
+        operation: operation.id().hex(),
+        operation_description: operation.metadata().description.clone(),
+        operation_time: format_timestamp(&operation.metadata().time.end),
+        commits: commits.len(),
+        heads: heads.iter().map(|head| head.id().hex()).collect(),
+        bookmarks,
+    };
+    let mut file = std::fs::File::create(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    writeln!(file, "{HEADER}")?;
+    writeln!(file, "{}", serde_json::to_string(&manifest)?)?;
+    file.write_all(&std::fs::read(bundle.path())?)?;
+    println!(
+        "Backed up {} commit(s) and {} bookmark(s) to {}",
+        manifest.commits,
+        manifest.bookmarks.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Reads a backup file into its manifest and bundle.
+fn read_backup(path: &Path) -> Result<(Manifest, Vec<u8>)> {
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    if header.trim_end() != HEADER {
+        bail!("{} is not a jjka backup", path.display());
+    }
+    let mut manifest = String::new();
+    reader.read_line(&mut manifest)?;
+    let manifest: Manifest = serde_json::from_str(&manifest)
+        .with_context(|| format!("Invalid manifest in {}", path.display()))?;
+    if OperationId::try_from_hex(&manifest.operation).is_none() {
+        bail!("Invalid operation id in {}", path.display());
+    }
+    let mut bundle = Vec::new();
+    reader.read_to_end(&mut bundle)?;
+    Ok((manifest, bundle))
+}
+
+async fn restore(file: PathBuf, policy: PolicyArgs) -> Result<()> {
+    let (manifest, bundle_content) = read_backup(&file)?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+
+    let bundle = tempfile::NamedTempFile::new()?;
+    std::fs::write(bundle.path(), bundle_content)?;
+    let mut command = git_command(&helper)?;
+    command
+        .args(["fetch", "--quiet", "--no-write-fetch-head"])
+        .arg(bundle.path())
+        .arg(format!("+{BUNDLE_REF_PREFIX}*:refs/jj/keep/*"));
+    run_git(command).context(
+        "Failed to fetch the commits from the backup; is its base history (such as trunk) fetched?",
+    )?;
+
+    let repo = helper.repo().clone();
+    let heads = manifest
+        .heads
+        .iter()
+        .map(|hex| {
+            let id = CommitId::try_from_hex(hex).context("Invalid commit id in backup")?;
+            Ok(repo.store().get_commit(&id)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut tx = helper.start_transaction();
+    tx.repo_mut().add_heads(&heads)?;
+    let mut restored_bookmarks = 0;
+    for (name, hex) in &manifest.bookmarks {
+        let id = CommitId::try_from_hex(hex).context("Invalid commit id in backup")?;
+        let ref_name = RefName::new(name);
+        let existing = tx.repo().view().get_local_bookmark(ref_name);
+        if existing.is_absent() {
+            tx.repo_mut()
+                .set_local_bookmark_target(ref_name, RefTarget::normal(id));
+            restored_bookmarks += 1;
+        } else if existing.as_normal() != Some(&id) {
+            status!("Skipped bookmark {name}: it already points elsewhere");
+        }
+    }
+    helper
+        .finish_transaction(tx, &format!("restore backup from {}", file.display()))
+        .await?;
+
+    println!(
+        "Restored {} commit(s) and {restored_bookmarks} bookmark(s) from operation {} ({}, {})",
+        manifest.commits,
+        manifest.operation.get(..12).unwrap_or(&manifest.operation),
+        manifest.operation_description,
+        manifest.operation_time
+    );
+    Ok(())
+}
+
+pub async fn backup_command(
+    command: Option<BackupCommand>,
+    output: Option<PathBuf>,
+    revset: String,
+) -> Result<()> {
+    match command {
+        Some(BackupCommand::Restore { file, policy }) => restore(file, policy).await,
+        None => {
+            let output = output.context("Pass -o FILE to write a backup")?;
+            let mut helper = WorkspaceHelper::load()?;
+            // Include what's in the working copy right now.
+            helper.snapshot_working_copy()?;
+            create(&helper, &output, &revset).await
+        }
+    }
+}
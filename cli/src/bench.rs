@@ -0,0 +1,170 @@
+//! `jjka bench`: time the steps most commands are made of on this repo,
+//! to see which of them is slow where.
+//!
+//! Each step runs once, on one thread and without the hunk cache, so the
+//! numbers add up to what a command doing the same work costs: loading the
+//! repo, snapshotting the working copy, evaluating the revset, reading the
+//! changed files of its revisions, diffing them, and committing an empty
+//! transaction.
+
+use anyhow::Result;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::format::OutputFormat;
+use crate::fsmonitor::{self, Backend};
+use crate::tree_diff::{changed_paths, read_file};
+use crate::unified_diff::{DEFAULT_CONTEXT, unified_hunks};
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Serialize, Debug)]
+struct Phase {
+    name: &'static str,
+    seconds: f64,
+    /// What the step worked on, like the number of files it read.
+    detail: String,
+}
+
+#[derive(Default)]
+struct Phases(Vec<Phase>);
+
+impl Phases {
+    fn push(&mut self, name: &'static str, elapsed: Duration, detail: String) {
+        self.0.push(Phase {
+            name,
+            seconds: elapsed.as_secs_f64(),
+            detail,
+        });
+    }
+
+    fn total(&self) -> f64 {
+        self.0.iter().map(|phase| phase.seconds).sum()
+    }
+}
+
+fn print_text(phases: &Phases) {
+    let width = phases
+        .0
+        .iter()
+        .map(|phase| phase.name.len())
+        .max()
+        .unwrap_or(0);
+    for phase in &phases.0 {
+        println!(
+            "{:<width$}  {:>8.3}s  {}",
+            phase.name, phase.seconds, phase.detail
+        );
+    }
+    println!("{:<width$}  {:>8.3}s", "total", phases.total());
+}
+
+pub async fn bench_command(revisions: String, commit: bool, format: OutputFormat) -> Result<()> {
+    let mut phases = Phases::default();
+
+    let started = Instant::now();
+    let mut helper = WorkspaceHelper::load()?;
+    phases.push(
+        "load",
+        started.elapsed(),
+        format!("at operation {}", &helper.repo().op_id().hex()[..12]),
+    );
+
+    let started = Instant::now();
+    let old_wc_commit_id = helper.wc_commit_id().cloned();
+    helper.snapshot_working_copy()?;
+    let changed = helper.wc_commit_id() != old_wc_commit_id.as_ref();
+    let backend = match fsmonitor::backend(helper.settings())? {
+        Backend::None => "no fsmonitor",
+        Backend::Watchman => "watchman",
+    };
+    phases.push(
+        "snapshot",
+        started.elapsed(),
+        format!(
+            "{}, {backend}",
+            if changed {
+                "recorded changes"
+            } else {
+                "no changes"
+            }
+        ),
+    );
+
+    let started = Instant::now();
+    let commits = helper.evaluate_revset(&revisions)?;
+    phases.push(
+        "revset",
+        started.elapsed(),
+        format!("{} revision(s) in {revisions}", commits.len()),
+    );
+
+    let started = Instant::now();
+    let repo = helper.repo().clone();
+    let mut contents = Vec::new();
+    let mut bytes = 0;
+    for commit in &commits {
+        let parent_tree = commit.parent_tree(repo.as_ref())?;
+        let tree = commit.tree_async().await?;
+        for change in changed_paths(&parent_tree, &tree).await? {
+            let before = read_file(repo.store(), &change.path, change.before)
+                .await?
+                .unwrap_or_default();
+            let after = read_file(repo.store(), &change.path, change.after)
+                .await?
+                .unwrap_or_default();
+            bytes += before.len() + after.len();
+            contents.push((before, after));
+        }
+    }
+    phases.push(
+        "read",
+        started.elapsed(),
+        format!("{} file version(s), {bytes} bytes", contents.len() * 2),
+    );
+
+    let started = Instant::now();
+    let hunks: usize = contents
+        .iter()
+        .filter(|(before, after)| !before.contains(&0) && !after.contains(&0))
+        .map(|(before, after)| unified_hunks(before, after, DEFAULT_CONTEXT).len())
+        .sum();
+    phases.push("diff", started.elapsed(), format!("{hunks} hunk(s)"));
+
+    if commit {
+        let started = Instant::now();
+        let tx = helper.start_transaction();
+        helper.finish_transaction_unreported(tx, "jjka bench")?;
+        phases.push(
+            "commit",
+            started.elapsed(),
+            "an empty operation".to_string(),
+        );
+    }
+
+    if format == OutputFormat::Json {
+        let report = serde_json::json!({
+            "phases": phases.0,
+            "total_seconds": phases.total(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_text(&phases);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total() {
+        let mut phases = Phases::default();
+        phases.push("load", Duration::from_millis(250), String::new());
+        phases.push("diff", Duration::from_millis(500), "3 hunk(s)".to_string());
+        assert_eq!(phases.total(), 0.75);
+        assert_eq!(phases.0[1].detail, "3 hunk(s)");
+    }
+}
@@ -0,0 +1,148 @@
+//! `jjka bisect`: find the first bad change by running a command.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::bisect::{BisectionResult, Bisector, Evaluation, NextStep};
+use jj_lib::repo::Repo;
+use std::process::{Command, ExitStatus};
+
+use crate::format::commit_summary;
+use crate::interrupt;
+use crate::progress::{self, Progress};
+use crate::scratch::ScratchWorkspace;
+use crate::ui::status;
+use crate::workspace::WorkspaceHelper;
+
+/// Exit code meaning "this revision can't be tested", as in `git bisect run`.
+const SKIP_EXIT_CODE: i32 = 125;
+
+fn evaluation(status: ExitStatus) -> Result<Evaluation> {
+    match status.code() {
+        Some(0) => Ok(Evaluation::Good),
+        Some(SKIP_EXIT_CODE) => Ok(Evaluation::Skip),
+        Some(_) => Ok(Evaluation::Bad),
+        None => bail!("Command was terminated by a signal ({status})"),
+    }
+}
+
+/// Tests commits in `scratch` until the bisector is done.
+fn run_steps(
+    helper: &mut WorkspaceHelper,
+    scratch: &mut ScratchWorkspace,
+    bisector: &mut Bisector<'_>,
+    command: &[String],
+) -> Result<BisectionResult> {
+    // The number of steps depends on the outcomes and on skips.
+    let mut progress = Progress::new("bisect", None);
+    loop {
+        if interrupt::requested() {
+            bail!("Interrupted");
+        }
+        let commit = match bisector.next_step()? {
+            NextStep::Evaluate(commit) => commit,
+            NextStep::Done(result) => {
+                progress.finish();
+                return Ok(result);
+            }
+        };
+        status!("Testing {}", commit_summary(&commit));
+        progress.start_item(&commit_summary(&commit));
+        scratch.check_out(helper, &commit)?;
+        let status = progress::suspend(|| {
+            Command::new(&command[0])
+                .args(&command[1..])
+                .current_dir(scratch.root())
+                .status()
+        })
+        .with_context(|| format!("Failed to run {}", command[0]))?;
+        let evaluation = evaluation(status)?;
+        status!(
+            "  {}",
+            match evaluation {
+                Evaluation::Good => "good",
+                Evaluation::Bad => "bad",
+                Evaluation::Skip => "skipped",
+            }
+        );
+        bisector.mark(commit.id().clone(), evaluation);
+    }
+}
+
+pub async fn bisect_command(good: String, bad: String, command: Vec<String>) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+
+    let range = helper.resolve_revset(&format!("({good})..({bad})"))?;
+    // Evaluate against the repo as loaded; checking out midpoints only adds
+    // commits outside the range.
+    let repo = helper.repo().clone();
+    let mut bisector = Bisector::new(repo.as_ref(), range)?;
+
+    // A ctrl-C stops the search, and also reaches the command, but the
+    // scratch workspace is forgotten before it takes effect.
+    let _deferred = interrupt::Deferred::new();
+    let mut scratch = ScratchWorkspace::create(&mut helper, "jjka-bisect")?;
+    let result = run_steps(&mut helper, &mut scratch, &mut bisector, &command);
+    let removed = scratch.remove(&mut helper);
+    let result = result?;
+    removed?;
+
+    let mut found = match result {
+        BisectionResult::Found(commits) => commits,
+        BisectionResult::Indeterminate => Vec::new(),
+    };
+    // The bisector reports the earliest commit marked bad, but a skipped
+    // commit between it and the good ones may be the real culprit.
+    let index = repo.index();
+    let ambiguous: Vec<_> = bisector
+        .skipped_commits()
+        .iter()
+        .filter(|skipped| {
+            found
+                .iter()
+                .any(|commit| index.is_ancestor(skipped, commit.id()))
+                && !bisector
+                    .good_commits()
+                    .iter()
+                    .any(|good| index.is_ancestor(skipped, good))
+        })
+        .map(|id| repo.store().get_commit(id))
+        .collect::<Result<_, _>>()?;
+    if found.is_empty() || !ambiguous.is_empty() {
+        found.extend(ambiguous);
+        if !found.is_empty() {
+            println!("The first bad change is one of:");
+            for commit in &found {
+                println!("  {}", commit_summary(commit));
+            }
+        }
+        bail!("Could not determine the first bad change because it is in a skipped range");
+    }
+    for commit in found {
+        println!(
+            "The first bad change is {}",
+            commit.change_id().reverse_hex()
+        );
+        println!("{}", commit_summary(&commit));
+        for line in commit.description().lines().skip(1) {
+            println!("    {line}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_evaluation_from_exit_code() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let exit = |code: i32| ExitStatus::from_raw(code << 8);
+        assert!(matches!(evaluation(exit(0)).unwrap(), Evaluation::Good));
+        assert!(matches!(evaluation(exit(1)).unwrap(), Evaluation::Bad));
+        assert!(matches!(evaluation(exit(125)).unwrap(), Evaluation::Skip));
+        assert!(evaluation(ExitStatus::from_raw(9)).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! An on-disk cache of each revision's hunks and diffs, in
+//! `.jj/repo/jjka/cache`.
+//!
+//! Entries are keyed by commit id, which pins down both the commit's tree
+//! and its parents, so they never go stale. `jjka prefetch` fills the cache
+//! ahead of time, and [`crate::hunks::commit_hunks`] and
+//! [`crate::hunks::commit_diffs`] read and fill it as they go. Failing to
+//! read or write an entry only costs the recomputation.
+//!
+//! The cache lives under `.jj/repo` rather than directly in `.jj`, because
+//! every workspace of a repo shares that directory: a revision prefetched
+//! in one workspace is cached for all of them.
+
+use anyhow::{Context, Result};
+use jj_lib::backend::CommitId;
+use jj_lib::object_id::ObjectId as _;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::fingerprint::FingerprintedHunk;
+use crate::hunks::FileHunks;
+use crate::state_file;
+
+/// Bumped whenever the format of the entries or the diffing changes.
+const HUNKS_DIR: &str = "hunks-v1";
+/// Like [`HUNKS_DIR`], for the diffs with context lines.
+const DIFFS_DIR: &str = "diffs-v1";
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Puts the cache under the repo at `repo_path`, the `.jj/repo` directory.
+pub fn init(repo_path: &Path) {
+    let _ = DIR.set(repo_path.join("jjka").join("cache"));
+}
+
+fn hunks_path(dir: &Path, commit_id: &CommitId) -> PathBuf {
+    dir.join(HUNKS_DIR)
+        .join(format!("{}.json", commit_id.hex()))
+}
+
+/// The diffs depend on how much context they keep, so that's part of the
+/// key, after the commit id.
+fn diffs_path(dir: &Path, commit_id: &CommitId, context: usize) -> PathBuf {
+    dir.join(DIFFS_DIR)
+        .join(format!("{}-u{context}.json", commit_id.hex()))
+}
+
+fn read_entry<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = std::fs::read(path).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+fn write_entry(path: &Path, value: &impl Serialize) -> Result<()> {
+    state_file::write(path, &serde_json::to_vec(value)?)
+}
+
+fn load_from(dir: &Path, commit_id: &CommitId) -> Option<Vec<FingerprintedHunk>> {
+    read_entry(&hunks_path(dir, commit_id))
+}
+
+fn save_to(dir: &Path, commit_id: &CommitId, hunks: &[FingerprintedHunk]) -> Result<()> {
+    write_entry(&hunks_path(dir, commit_id), &hunks)
+}
+
+pub fn load_hunks(commit_id: &CommitId) -> Option<Vec<FingerprintedHunk>> {
+    load_from(DIR.get()?, commit_id)
+}
+
+pub fn save_hunks(commit_id: &CommitId, hunks: &[FingerprintedHunk]) {
+    if let Some(dir) = DIR.get() {
+        let _ = save_to(dir, commit_id, hunks);
+    }
+}
+
+pub fn load_diffs(commit_id: &CommitId, context: usize) -> Option<Vec<FileHunks>> {
+    read_entry(&diffs_path(DIR.get()?, commit_id, context))
+}
+
+pub fn save_diffs(commit_id: &CommitId, context: usize, diffs: &[FileHunks]) {
+    if let Some(dir) = DIR.get() {
+        let _ = write_entry(&diffs_path(dir, commit_id, context), &diffs);
+    }
+}
+
+pub fn is_cached(commit_id: &CommitId) -> bool {
+    DIR.get()
+        .is_some_and(|dir| hunks_path(dir, commit_id).is_file())
+}
+
+/// Deletes the whole cache, returning how many revisions it had entries for.
+pub fn clear() -> Result<usize> {
+    let Some(dir) = DIR.get() else {
+        return Ok(0);
+    };
+    let mut commits = HashSet::new();
+    for entries_dir in [HUNKS_DIR, DIFFS_DIR] {
+        let entries = match std::fs::read_dir(dir.join(entries_dir)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", dir.display()));
+            }
+        };
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            // Every entry's name starts with the commit id.
+            let hex: String = name.chars().take_while(char::is_ascii_hexdigit).collect();
+            commits.insert(hex);
+        }
+    }
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+    Ok(commits.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::fingerprint_file;
+    use crate::unified_diff::unified_hunks;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let commit_id = CommitId::new(vec![0xab; 20]);
+        assert_eq!(load_from(dir.path(), &commit_id), None);
+
+        let hunks = fingerprint_file("a.txt", b"a\nb\n", b"a\nB\n");
+        save_to(dir.path(), &commit_id, &hunks).unwrap();
+        assert_eq!(load_from(dir.path(), &commit_id), Some(hunks));
+
+        // A corrupt entry is a miss.
+        std::fs::write(hunks_path(dir.path(), &commit_id), "{").unwrap();
+        assert_eq!(load_from(dir.path(), &commit_id), None);
+    }
+
+    #[test]
+    fn test_diffs_are_keyed_by_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let commit_id = CommitId::new(vec![0xcd; 20]);
+        let diffs = vec![FileHunks {
+            path: "a.txt".to_string(),
+            added: false,
+            removed: false,
+            binary: false,
+            hunks: unified_hunks(b"a\nb\n", b"a\nB\n", 3),
+        }];
+        write_entry(&diffs_path(dir.path(), &commit_id, 3), &diffs).unwrap();
+        assert_eq!(
+            read_entry::<Vec<FileHunks>>(&diffs_path(dir.path(), &commit_id, 3)),
+            Some(diffs)
+        );
+        assert_eq!(
+            read_entry::<Vec<FileHunks>>(&diffs_path(dir.path(), &commit_id, 0)),
+            None
+        );
+    }
+}
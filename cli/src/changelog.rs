@@ -0,0 +1,250 @@
+//! `jjka changelog`: release notes from conventional-commit descriptions.
+//!
+//! Each commit whose subject looks like `type(scope)!: description` becomes
+//! an entry under its type's section. Commits marked with `!` or with a
+//! `BREAKING CHANGE:` paragraph are also listed under Breaking changes.
+
+use anyhow::Result;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::forge::{Forge, forge};
+use crate::format::OutputFormat;
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    commit_id: String,
+    change_id: String,
+    #[serde(rename = "type")]
+    commit_type: String,
+    scope: Option<String>,
+    description: String,
+    /// The `BREAKING CHANGE:` text, or the description for a bare `!`.
+    breaking: Option<String>,
+    /// Issue numbers from `#N` references in the description and from
+    /// `Refs:`, `Fixes:` and `Closes:` trailers.
+    issues: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+#[derive(Serialize, Default, Debug)]
+struct Changelog {
+    breaking: Vec<Entry>,
+    features: Vec<Entry>,
+    fixes: Vec<Entry>,
+    /// Other types, and commits that don't follow the convention, with
+    /// `--all`.
+    other: Vec<Entry>,
+}
+
+/// A conventional-commit subject, capturing the type, the scope, the `!`
+/// and the description.
+pub const CONVENTIONAL_SUBJECT: &str = r"^([A-Za-z]+)(?:\(([^)]*)\))?(!)?: (.+)$";
+
+/// Parses a conventional-commit description. Returns `None` if the subject
+/// isn't one.
+fn parse_entry(description: &str) -> Option<Entry> {
+    let subject_re = Regex::new(CONVENTIONAL_SUBJECT).unwrap();
+    let breaking_re = Regex::new(r"(?m)^BREAKING[ -]CHANGE: *(.+(?:\n.+)*)").unwrap();
+    let trailer_re = Regex::new(r"(?mi)^(?:refs|fixes|closes): *(.+)$").unwrap();
+    let issue_re = Regex::new(r"#([0-9]+)").unwrap();
+
+    let subject = description.lines().next()?;
+    let captures = subject_re.captures(subject)?;
+    let text = captures[4].trim().to_string();
+    let breaking = match breaking_re.captures(description) {
+        Some(breaking) => Some(breaking[1].split_whitespace().collect::<Vec<_>>().join(" ")),
+        None => captures.get(3).map(|_| text.clone()),
+    };
+    let mut issues: Vec<String> = issue_re
+        .captures_iter(subject)
+        .map(|issue| issue[1].to_string())
+        .collect();
+    for trailer in trailer_re.captures_iter(description) {
+        for issue in issue_re.captures_iter(&trailer[1]) {
+            if !issues.contains(&issue[1].to_string()) {
+                issues.push(issue[1].to_string());
+            }
+        }
+    }
+    Some(Entry {
+        commit_id: String::new(),
+        change_id: String::new(),
+        commit_type: captures[1].to_lowercase(),
+        scope: captures
+            .get(2)
+            .map(|scope| scope.as_str().to_string())
+            .filter(|scope| !scope.is_empty()),
+        description: text,
+        breaking,
+        issues,
+        url: None,
+    })
+}
+
+fn commit_entry(commit: &Commit, forge: Option<&Forge>) -> Entry {
+    let mut entry = parse_entry(commit.description()).unwrap_or_else(|| Entry {
+        commit_id: String::new(),
+        change_id: String::new(),
+        commit_type: String::new(),
+        scope: None,
+        description: commit
+            .description()
+            .lines()
+            .next()
+            .unwrap_or("(no description)")
+            .to_string(),
+        breaking: None,
+        issues: vec![],
+        url: None,
+    });
+    entry.commit_id = commit.id().hex();
+    entry.change_id = commit.change_id().reverse_hex();
+    entry.url = forge.map(|forge| forge.commit_url(&entry.commit_id));
+    entry
+}
+
+impl Changelog {
+    fn add(&mut self, entry: Entry, all: bool) {
+        if entry.breaking.is_some() {
+            self.breaking.push(entry.clone());
+        }
+        match entry.commit_type.as_str() {
+            "feat" => self.features.push(entry),
+            "fix" => self.fixes.push(entry),
+            _ if all => self.other.push(entry),
+            _ => {}
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.breaking.is_empty()
+            && self.features.is_empty()
+            && self.fixes.is_empty()
+            && self.other.is_empty()
+    }
+}
+
+fn markdown_entry(entry: &Entry, text: &str, forge: Option<&Forge>) -> String {
+    let mut line = String::from("- ");
+    if let Some(scope) = &entry.scope {
+        line.push_str(&format!("**{scope}:** "));
+    }
+    line.push_str(text);
+    let short_id = &entry.commit_id[..12];
+    match &entry.url {
+        Some(url) => line.push_str(&format!(" ([{short_id}]({url}))")),
+        None => line.push_str(&format!(" ({short_id})")),
+    }
+    if !entry.issues.is_empty() {
+        let issues: Vec<String> = entry
+            .issues
+            .iter()
+            .map(|issue| match forge {
+                Some(forge) => format!("[#{issue}]({})", forge.issue_url(issue)),
+                None => format!("#{issue}"),
+            })
+            .collect();
+        line.push_str(&format!(", {}", issues.join(", ")));
+    }
+    line
+}
+
+fn markdown(changelog: &Changelog, title: Option<&str>, forge: Option<&Forge>) -> String {
+    let mut out = String::new();
+    if let Some(title) = title {
+        out.push_str(&format!("## {title}\n\n"));
+    }
+    let sections = [
+        ("Breaking changes", &changelog.breaking, true),
+        ("Features", &changelog.features, false),
+        ("Fixes", &changelog.fixes, false),
+        ("Other changes", &changelog.other, false),
+    ];
+    for (heading, entries, breaking) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("### {heading}\n\n"));
+        for entry in entries {
+            let text = if breaking {
+                entry.breaking.as_deref().unwrap()
+            } else {
+                &entry.description
+            };
+            out.push_str(&markdown_entry(entry, text, forge));
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub async fn changelog_command(
+    revset: String,
+    title: Option<String>,
+    all: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let helper = WorkspaceHelper::load()?;
+    let forge = forge(&helper)?;
+    let mut changelog = Changelog::default();
+    let commits = helper.evaluate_revset(&revset)?;
+    for commit in &commits {
+        changelog.add(commit_entry(commit, forge.as_ref()), all);
+    }
+    match format {
+        OutputFormat::Text if changelog.is_empty() => {
+            println!("No features, fixes or breaking changes in {revset}");
+        }
+        OutputFormat::Text => print!("{}", markdown(&changelog, title.as_deref(), forge.as_ref())),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&changelog)?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entry() {
+        let entry =
+            parse_entry("feat(parser)!: Drop octal literals (#12)\n\nRefs: #7, #12\n").unwrap();
+        assert_eq!(entry.commit_type, "feat");
+        assert_eq!(entry.scope.as_deref(), Some("parser"));
+        assert_eq!(entry.description, "Drop octal literals (#12)");
+        assert_eq!(entry.breaking.as_deref(), Some("Drop octal literals (#12)"));
+        assert_eq!(entry.issues, ["12", "7"]);
+
+        let entry = parse_entry(
+            "fix: Handle empty input\n\nBREAKING CHANGE: parse() now returns\nan Option.\n",
+        )
+        .unwrap();
+        assert_eq!(entry.scope, None);
+        assert_eq!(
+            entry.breaking.as_deref(),
+            Some("parse() now returns an Option.")
+        );
+
+        assert_eq!(parse_entry("Update the README\n"), None);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let forge = Forge::new("https://github.com/owner/repo");
+        let mut entry = parse_entry("fix(lexer): Handle tabs\n\nFixes: #3\n").unwrap();
+        entry.commit_id = "0123456789abcdef0123456789abcdef01234567".to_string();
+        entry.url = Some(forge.commit_url(&entry.commit_id));
+        let mut changelog = Changelog::default();
+        changelog.add(entry, false);
+        assert_eq!(
+            markdown(&changelog, Some("v1.2.0"), Some(&forge)),
+            "## v1.2.0\n\n### Fixes\n\n- **lexer:** Handle tabs ([0123456789ab](https://github.com/owner/repo/commit/0123456789abcdef0123456789abcdef01234567)), [#3](https://github.com/owner/repo/issues/3)\n\n"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Checks run on the commits a split, move, copy or absorb makes, before
+//! they're committed to the repo.
+//!
+//! They're opt-in: `jjka.checks.command` is run in a checkout of each new
+//! commit, and `jjka.checks.enable = true` alone runs the repo's pre-commit
+//! framework with `pre-commit run --all-files`. A failure on any commit
+//! leaves the repo as it was.
+
+use anyhow::{Context, Result, anyhow, bail};
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::git;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use jj_lib::settings::{GitSettings, UserSettings};
+use std::path::Path;
+use std::process::Command;
+
+use crate::exit_code::Failure;
+use crate::format::commit_summary;
+use crate::hooks::HookCommand;
+use crate::ui::status;
+use crate::workspace::WorkspaceHelper;
+
+const PRE_COMMIT: &[&str] = &["pre-commit", "run", "--all-files"];
+
+/// The configured check, if checks are on.
+fn check_command(settings: &UserSettings) -> Result<Option<HookCommand>> {
+    let command = settings
+        .get::<HookCommand>("jjka.checks.command")
+        .optional()?;
+    let enable = settings.get_bool("jjka.checks.enable").optional()?;
+    if !enable.unwrap_or(command.is_some()) {
+        return Ok(None);
+    }
+    Ok(Some(command.unwrap_or_else(|| {
+        HookCommand::Args(PRE_COMMIT.iter().map(|word| word.to_string()).collect())
+    })))
+}
+
+/// Checks out `commit` at `path` as a worktree of the git store, so tools
+/// like pre-commit that need a git repo work in it.
+fn add_worktree(git: &impl Fn() -> Command, path: &Path, commit: &Commit) -> Result<()> {
+    let output = git()
+        .args(["worktree", "add", "--detach", "--quiet"])
+        .arg(path)
+        .arg(commit.id().hex())
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git worktree add failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Runs the configured check on each of `commits`, reporting each one that
+/// fails, and fails if any did.
+pub fn check_commits(helper: &WorkspaceHelper, commits: &[&Commit]) -> Result<()> {
+    let Some(check) = check_command(helper.settings())? else {
+        return Ok(());
+    };
+    let backend = git::get_git_backend(helper.repo().store())
+        .context("jjka.checks needs a git-backed repo")?;
+    let executable = GitSettings::from_settings(helper.settings())?.executable_path;
+    let git = || {
+        let mut command = Command::new(&executable);
+        command.arg("--git-dir").arg(backend.git_repo_path());
+        command
+    };
+
+    let mut failed = 0;
+    for commit in commits {
+        let summary = commit_summary(commit);
+        if commit.has_conflict()? {
+            eprintln!("Warning: Not checking {summary}, which has conflicts");
+            continue;
+        }
+        status!("Checking {summary}");
+        let dir = tempfile::Builder::new().prefix("jjka-check-").tempdir()?;
+        let worktree = dir.path().join("tree");
+        add_worktree(&git, &worktree, commit)?;
+        let mut command = check.to_command().context("jjka.checks.command is empty")?;
+        let result = command
+            .current_dir(&worktree)
+            .env("JJKA_COMMIT", commit.id().hex())
+            .env("JJKA_CHANGE_ID", commit.change_id().reverse_hex())
+            // Keep stdout for the command's own output, like `serve` responses.
+            .stdout(std::io::stderr())
+            .status();
+        let _ = git()
+            .args(["worktree", "remove", "--force"])
+            .arg(&worktree)
+            .output();
+        let status = result.context("Failed to start jjka.checks.command")?;
+        if !status.success() {
+            eprintln!("Check failed on {summary} ({status})");
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(Failure::Precondition.error(anyhow!(
+            "Checks failed on {failed} commit(s); nothing was changed"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+
+    fn check(toml: &str) -> Option<HookCommand> {
+        let mut config = StackedConfig::with_defaults();
+        config.add_layer(ConfigLayer::parse(ConfigSource::User, toml).unwrap());
+        check_command(&UserSettings::from_config(config).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_check_command() {
+        assert_eq!(check(""), None);
+        assert_eq!(
+            check("jjka.checks.enable = true"),
+            Some(HookCommand::Args(vec![
+                "pre-commit".into(),
+                "run".into(),
+                "--all-files".into()
+            ]))
+        );
+        assert_eq!(
+            check("jjka.checks.command = 'cargo check'"),
+            Some(HookCommand::Shell("cargo check".into()))
+        );
+        assert_eq!(
+            check("jjka.checks = { command = 'make lint', enable = false }"),
+            None
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Reading CODEOWNERS files, for `autosplit --codeowners`.
+//!
+//! The file is looked for where GitHub and GitLab do, in the revision being
+//! split. Each line is a pattern followed by owners; the last pattern that
+//! matches a path names its owners, and a pattern without owners leaves the
+//! path unowned. Patterns work like in `.gitignore`: one with a `/` at the
+//! start or in the middle is relative to the root, any other matches at
+//! any depth, and one naming a directory covers everything in it. GitLab's
+//! `[Section]` headers are skipped, so their rules all apply as one list.
+
+use anyhow::{Context, Result};
+use jj_lib::commit::Commit;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPathBuf;
+use jjka_lib::tree_diff::read_file;
+
+use crate::describe::glob_match_path;
+
+/// Where CODEOWNERS files live, in the order they're looked for.
+const LOCATIONS: &[&str] = &[
+    ".github/CODEOWNERS",
+    "CODEOWNERS",
+    "docs/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+struct Rule {
+    pattern: String,
+    owners: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+/// Whether the CODEOWNERS `pattern` matches the file at `path`.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let glob = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{pattern}"),
+    };
+    if !dir_only && glob_match_path(&glob, path) {
+        return true;
+    }
+    // `docs/*` only covers the files right in docs, as on GitHub.
+    let last = glob.rsplit('/').next().unwrap_or(&glob);
+    !last.contains('*') && glob_match_path(&format!("{glob}/**"), path)
+}
+
+impl CodeOwners {
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter(|line| !line.starts_with('[') && !line.starts_with("^["))
+            .filter_map(|line| {
+                let mut words = line
+                    .split_whitespace()
+                    .take_while(|word| !word.starts_with('#'));
+                let pattern = words.next()?.to_string();
+                let owners = words.map(str::to_string).collect();
+                Some(Rule { pattern, owners })
+            })
+            .collect();
+        CodeOwners { rules }
+    }
+
+    /// The owners of the file at `path`, or none if it's unowned.
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| pattern_matches(&rule.pattern, path))
+            .map_or(&[], |rule| rule.owners.as_slice())
+    }
+
+    /// The CODEOWNERS file of `commit` and where it is, if it has one.
+    pub async fn load(repo: &dyn Repo, commit: &Commit) -> Result<Option<(&'static str, Self)>> {
+        let tree = commit.tree_async().await?;
+        for location in LOCATIONS {
+            let path = RepoPathBuf::from_internal_string(*location)?;
+            let value = tree.path_value(&path)?;
+            if value.is_absent() {
+                continue;
+            }
+            let content = read_file(repo.store(), &path, value)
+                .await?
+                .with_context(|| format!("{location} isn't a regular file"))?;
+            return Ok(Some((
+                location,
+                CodeOwners::parse(&String::from_utf8_lossy(&content)),
+            )));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owners() {
+        let owners = CodeOwners::parse(
+            "# Everything else\n\
+             *       @org/core\n\
+             *.md    @org/docs  # prose\n\
+             [Frontend]\n\
+             web/    @org/web @alice\n\
+             /build/ @org/infra\n\
+             docs/*  @org/writers\n\
+             web/vendor/\n\
+             /Cargo.toml @org/infra\n",
+        );
+        let of = |path| owners.owners(path).join(" ");
+        assert_eq!(of("src/main.rs"), "@org/core");
+        assert_eq!(of("notes/README.md"), "@org/docs");
+        assert_eq!(of("web/app.ts"), "@org/web @alice");
+        assert_eq!(of("web/README.md"), "@org/web @alice");
+        assert_eq!(of("web/vendor/lib.js"), "");
+        assert_eq!(of("build/ci/run.sh"), "@org/infra");
+        assert_eq!(of("tools/build/run.sh"), "@org/core");
+        assert_eq!(of("docs/intro.md"), "@org/writers");
+        assert_eq!(of("docs/api/intro.md"), "@org/docs");
+        assert_eq!(of("Cargo.toml"), "@org/infra");
+        assert_eq!(of("lib/Cargo.toml"), "@org/core");
+    }
+}
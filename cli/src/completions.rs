@@ -0,0 +1,526 @@
+//! `jjka completions`: shell completion scripts that call back into jjka.
+//!
+//! The scripts pass the words typed so far to the hidden `jjka __complete`,
+//! which walks the clap definition to see what the last word is: a
+//! subcommand, a flag, or the value of an argument. Revisions complete to
+//! bookmarks and the change ids of recent mutable commits, and line ranges
+//! to the files the revision changes and then their hunks. Candidates are
+//! printed one per line, with a tab before the description.
+
+use anyhow::Result;
+use clap::{Arg, Command, CommandFactory, ValueEnum};
+use itertools::Itertools;
+use jj_lib::repo::Repo as _;
+use std::collections::HashMap;
+
+use crate::Cli;
+use crate::format::{short_change_id, subject};
+use crate::hunks::commit_hunks;
+use crate::workspace::WorkspaceHelper;
+
+/// Arguments whose values are revsets.
+const REVISION_ARGS: &[&str] = &[
+    "revision",
+    "revisions",
+    "revset",
+    "onto",
+    "good",
+    "bad",
+    "remap_from",
+    "from",
+    "to",
+];
+
+/// Arguments whose values are `path:start-end` line ranges.
+const RANGE_ARGS: &[&str] = &["ranges", "selectors", "lines"];
+
+/// How many recent mutable commits to offer change ids for.
+const RECENT_COMMITS: usize = 50;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const BASH: &str = r#"_jjka() {
+    local cur words cword
+    if declare -F _get_comp_words_by_ref >/dev/null; then
+        _get_comp_words_by_ref -n =: cur words cword
+    else
+        cur="${COMP_WORDS[COMP_CWORD]}"
+        words=("${COMP_WORDS[@]}")
+        cword=$COMP_CWORD
+    fi
+    local IFS=$'\n'
+    COMPREPLY=($(jjka __complete -- "${words[@]:1:cword}" 2>/dev/null | cut -f1))
+    if declare -F __ltrim_colon_completions >/dev/null; then
+        __ltrim_colon_completions "$cur"
+    fi
+}
+complete -o nosort -o default -F _jjka jjka
+"#;
+
+const ZSH: &str = r#"_jjka() {
+    local -a candidates
+    candidates=("${(@f)$(jjka __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    candidates=("${(@)candidates:#}")
+    if (( ${#candidates} )); then
+        candidates=("${(@)candidates//:/\\:}")
+        candidates=("${(@)candidates/$'\t'/:}")
+        _describe -V jjka candidates
+    else
+        _files
+    fi
+}
+compdef _jjka jjka
+"#;
+
+const FISH: &str = r#"complete -c jjka -f -a '(jjka __complete -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null)'
+"#;
+
+pub fn completions_command(shell: Shell) -> Result<()> {
+    print!(
+        "{}",
+        match shell {
+            Shell::Bash => BASH,
+            Shell::Zsh => ZSH,
+            Shell::Fish => FISH,
+        }
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Candidate {
+    value: String,
+    description: Option<String>,
+}
+
+impl Candidate {
+    fn new(value: impl Into<String>, description: Option<String>) -> Self {
+        Candidate {
+            value: value.into(),
+            description,
+        }
+    }
+}
+
+/// What the word being completed is.
+#[derive(Debug, PartialEq, Eq)]
+enum Position {
+    Subcommand,
+    Flag,
+    /// The value of the argument with this id.
+    Value(String),
+    Nothing,
+}
+
+/// Where the walk over the typed words ended up.
+struct Context<'a> {
+    command: &'a Command,
+    /// The subcommand names, like `megamerge add`.
+    path: Vec<String>,
+    /// The values given for arguments so far, by id.
+    values: HashMap<String, String>,
+    position: Position,
+    /// Text of the last word that is kept in front of the completions, like
+    /// `--revision=`.
+    prefix: String,
+    current: String,
+}
+
+fn find_long<'a>(command: &'a Command, long: &str) -> Option<&'a Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+}
+
+fn find_short(command: &Command, short: char) -> Option<&Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_short() == Some(short))
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+fn walk<'a>(root: &'a Command, words: &[String]) -> Context<'a> {
+    let (current, typed) = match words.split_last() {
+        Some((current, typed)) => (current.clone(), typed),
+        None => (String::new(), &[][..]),
+    };
+    let mut command = root;
+    let mut path = Vec::new();
+    let mut values = HashMap::new();
+    let mut positionals = 0;
+    let mut pending: Option<&Arg> = None;
+    let mut only_positionals = false;
+    for word in typed {
+        if let Some(arg) = pending.take() {
+            values.insert(arg.get_id().to_string(), word.clone());
+            continue;
+        }
+        if !only_positionals && word == "--" {
+            only_positionals = true;
+        } else if let Some(long) = word.strip_prefix("--").filter(|_| !only_positionals) {
+            match long.split_once('=') {
+                Some((long, value)) => {
+                    if let Some(arg) = find_long(command, long) {
+                        values.insert(arg.get_id().to_string(), value.to_string());
+                    }
+                }
+                None => pending = find_long(command, long).filter(|arg| takes_value(arg)),
+            }
+        } else if let Some(shorts) = word
+            .strip_prefix('-')
+            .filter(|shorts| !only_positionals && !shorts.is_empty())
+        {
+            // Flags can be grouped, and the last one can take the rest of
+            // the word or the next word as its value.
+            for (i, short) in shorts.char_indices() {
+                let Some(arg) = find_short(command, short) else {
+                    break;
+                };
+                if takes_value(arg) {
+                    let rest = &shorts[i + short.len_utf8()..];
+                    if rest.is_empty() {
+                        pending = Some(arg);
+                    } else {
+                        values.insert(arg.get_id().to_string(), rest.to_string());
+                    }
+                    break;
+                }
+            }
+        } else if let Some(subcommand) = (positionals == 0)
+            .then(|| command.find_subcommand(word))
+            .flatten()
+        {
+            command = subcommand;
+            path.push(subcommand.get_name().to_string());
+        } else {
+            if let Some(arg) = positional(command, positionals) {
+                values.insert(arg.get_id().to_string(), word.clone());
+            }
+            positionals += 1;
+        }
+    }
+
+    let mut prefix = String::new();
+    let mut current = current;
+    let position = if let Some(arg) = pending {
+        Position::Value(arg.get_id().to_string())
+    } else if let Some((long, value)) = current
+        .strip_prefix("--")
+        .and_then(|rest| rest.split_once('='))
+        .filter(|_| !only_positionals)
+    {
+        let position = find_long(command, long).map_or(Position::Nothing, |arg| {
+            Position::Value(arg.get_id().to_string())
+        });
+        prefix = format!("--{long}=");
+        current = value.to_string();
+        position
+    } else if current.starts_with('-') && !only_positionals {
+        Position::Flag
+    } else if positionals == 0 && command.has_subcommands() {
+        Position::Subcommand
+    } else {
+        positional(command, positionals).map_or(Position::Nothing, |arg| {
+            Position::Value(arg.get_id().to_string())
+        })
+    };
+    Context {
+        command,
+        path,
+        values,
+        position,
+        prefix,
+        current,
+    }
+}
+
+/// The positional argument the `index`th positional word goes to. The last
+/// one takes all the rest if it takes several values.
+fn positional(command: &Command, index: usize) -> Option<&Arg> {
+    let positionals = command
+        .get_positionals()
+        .filter(|arg| !arg.is_hide_set())
+        .collect_vec();
+    positionals.get(index).copied().or_else(|| {
+        positionals.last().copied().filter(|arg| {
+            arg.get_num_args()
+                .is_some_and(|range| range.max_values() > 1)
+        })
+    })
+}
+
+fn static_candidates(context: &Context) -> Vec<Candidate> {
+    match &context.position {
+        Position::Subcommand => context
+            .command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(|subcommand| {
+                Candidate::new(
+                    subcommand.get_name(),
+                    subcommand.get_about().map(|about| about.to_string()),
+                )
+            })
+            .collect(),
+        Position::Flag => context
+            .command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| {
+                let long = arg.get_long()?;
+                Some(Candidate::new(
+                    format!("--{long}"),
+                    arg.get_help().map(|help| help.to_string()),
+                ))
+            })
+            .collect(),
+        Position::Value(id) => context
+            .command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .map(|arg| {
+                arg.get_possible_values()
+                    .iter()
+                    .filter(|value| !value.is_hide_set())
+                    .map(|value| {
+                        Candidate::new(
+                            value.get_name(),
+                            value.get_help().map(|help| help.to_string()),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Position::Nothing => Vec::new(),
+    }
+}
+
+/// The revision that line ranges for `id` are written against.
+fn range_revision(context: &Context, id: &str) -> String {
+    if context.path == ["revert-lines"] {
+        // Its ranges are in the working copy; the revision is the source.
+        return "@".to_string();
+    }
+    let revision_id = if id == "selectors" {
+        "remap_from"
+    } else if context.path == ["move"] {
+        "from"
+    } else if context.path == ["backport"] {
+        "revset"
+    } else {
+        "revision"
+    };
+    if let Some(value) = context.values.get(revision_id) {
+        return value.clone();
+    }
+    context
+        .command
+        .get_arguments()
+        .find(|arg| arg.get_id() == revision_id)
+        .and_then(|arg| arg.get_default_values().first())
+        .map_or("@".to_string(), |value| value.to_string_lossy().to_string())
+}
+
+fn bookmark_candidates(helper: &WorkspaceHelper) -> Result<Vec<Candidate>> {
+    let repo = helper.repo();
+    let mut candidates = Vec::new();
+    for (name, target) in repo.view().local_bookmarks() {
+        let description = match target.as_normal() {
+            Some(id) => Some(subject(&repo.store().get_commit(id)?).to_string()),
+            None => None,
+        };
+        candidates.push(Candidate::new(name.as_str(), description));
+    }
+    Ok(candidates)
+}
+
+fn revision_candidates(helper: &WorkspaceHelper) -> Result<Vec<Candidate>> {
+    let mut candidates = bookmark_candidates(helper)?;
+    for commit in helper.evaluate_revset(&format!("latest(mutable(), {RECENT_COMMITS})"))? {
+        candidates.push(Candidate::new(
+            short_change_id(&commit),
+            Some(subject(&commit).to_string()),
+        ));
+    }
+    Ok(candidates)
+}
+
+/// Changed files of `revision` as `path:`, or the hunks of a file once the
+/// word names only that file.
+async fn range_candidates(
+    helper: &WorkspaceHelper,
+    revision: &str,
+    current: &str,
+) -> Result<Vec<Candidate>> {
+    let commit = helper.resolve_single(revision)?;
+    let hunks = commit_hunks(helper.repo().as_ref(), &commit).await?;
+    let by_path = hunks.iter().into_group_map_by(|hunk| hunk.path.as_str());
+    let matching = by_path
+        .keys()
+        .filter(|path| path.starts_with(current) || current.starts_with(&format!("{path}:")))
+        .sorted()
+        .collect_vec();
+    if let [path] = matching.as_slice() {
+        return Ok(by_path[*path]
+            .iter()
+            .map(|hunk| {
+                Candidate::new(
+                    format!("{}:{}-{}", hunk.path, hunk.start, hunk.end),
+                    Some(format!("+{} -{}", hunk.added, hunk.removed)),
+                )
+            })
+            .collect());
+    }
+    Ok(matching
+        .into_iter()
+        .map(|path| {
+            Candidate::new(
+                format!("{path}:"),
+                Some(format!("{} hunk(s)", by_path[*path].len())),
+            )
+        })
+        .collect())
+}
+
+async fn dynamic_candidates(context: &Context<'_>, current: &str) -> Result<Vec<Candidate>> {
+    let Position::Value(id) = &context.position else {
+        return Ok(Vec::new());
+    };
+    let id = id.as_str();
+    if !REVISION_ARGS.contains(&id)
+        && !RANGE_ARGS.contains(&id)
+        && id != "bookmark"
+        && id != "paths"
+    {
+        return Ok(Vec::new());
+    }
+    let helper = WorkspaceHelper::load()?;
+    if REVISION_ARGS.contains(&id) {
+        revision_candidates(&helper)
+    } else if id == "bookmark" {
+        bookmark_candidates(&helper)
+    } else {
+        let revision = range_revision(context, id);
+        let mut candidates = range_candidates(&helper, &revision, current).await?;
+        if id == "paths" {
+            // Plain paths, without line ranges.
+            candidates = candidates
+                .into_iter()
+                .filter_map(|candidate| {
+                    let path = candidate.value.strip_suffix(':')?.to_string();
+                    Some(Candidate::new(path, None))
+                })
+                .collect();
+        }
+        Ok(candidates)
+    }
+}
+
+/// Splits a revset being typed into the expression so far and the symbol
+/// being completed, like `trunk()..` and `feat`.
+fn split_revset(current: &str) -> (&str, &str) {
+    let start = current
+        .rfind([':', '.', '|', '&', '~', '(', ',', ' '])
+        .map_or(0, |i| i + 1);
+    current.split_at(start)
+}
+
+pub async fn complete_command(words: Vec<String>) -> Result<()> {
+    let mut root = Cli::command();
+    root.build();
+    let context = walk(&root, &words);
+    let (head, symbol) = match &context.position {
+        Position::Value(id) if REVISION_ARGS.contains(&id.as_str()) => {
+            split_revset(&context.current)
+        }
+        _ => ("", context.current.as_str()),
+    };
+    let mut candidates = static_candidates(&context);
+    // Outside a repo, or with a revision that doesn't resolve, there's
+    // nothing more to offer.
+    candidates.extend(
+        dynamic_candidates(&context, symbol)
+            .await
+            .unwrap_or_default(),
+    );
+    for candidate in candidates
+        .iter()
+        .unique_by(|candidate| candidate.value.clone())
+    {
+        if !candidate.value.starts_with(symbol) {
+            continue;
+        }
+        let value = format!("{}{head}{}", context.prefix, candidate.value);
+        match &candidate.description {
+            Some(description) => println!("{value}\t{}", description.lines().next().unwrap_or("")),
+            None => println!("{value}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        let mut words: Vec<String> = line.split(' ').map(str::to_string).collect();
+        if line.is_empty() {
+            words = vec![String::new()];
+        }
+        words
+    }
+
+    #[test]
+    fn test_walk_positions() {
+        let mut root = Cli::command();
+        root.build();
+        let position = |line: &str| walk(&root, &words(line)).position;
+        let value = |id: &str| Position::Value(id.to_string());
+        assert_eq!(position("hu"), Position::Subcommand);
+        assert_eq!(position("megamerge "), Position::Subcommand);
+        assert_eq!(position("megamerge add "), value("revision"));
+        assert_eq!(position("hunksplit -r "), value("revision"));
+        assert_eq!(position("hunksplit -r @- "), value("ranges"));
+        assert_eq!(position("hunksplit a.txt:1-2 "), value("ranges"));
+        assert_eq!(position("hunksplit --rev"), Position::Flag);
+        assert_eq!(position("lint --format "), value("format"));
+        assert_eq!(position("serve --socket "), value("socket"));
+        assert_eq!(position("--json tidy -r"), Position::Flag);
+
+        let context = walk(&root, &words("copy-hunks --revision=tru"));
+        assert_eq!(context.position, value("revision"));
+        assert_eq!(context.prefix, "--revision=");
+        assert_eq!(context.current, "tru");
+        assert_eq!(
+            range_revision(&walk(&root, &words("copy-hunks -r x ")), "ranges"),
+            "x"
+        );
+        assert_eq!(
+            range_revision(&walk(&root, &words("hunksplit ")), "ranges"),
+            "@"
+        );
+        assert_eq!(position("move --to "), value("to"));
+        assert_eq!(
+            range_revision(
+                &walk(&root, &words("backport x --onto y --lines ")),
+                "lines"
+            ),
+            "x"
+        );
+        assert_eq!(
+            range_revision(&walk(&root, &words("move --from x --to y ")), "ranges"),
+            "x"
+        );
+        assert_eq!(split_revset("trunk()..fe"), ("trunk()..", "fe"));
+        assert_eq!(split_revset("main"), ("", "main"));
+    }
+}
@@ -0,0 +1,208 @@
+//! `jjka conflicts`: list conflicted paths across a revset.
+
+use anyhow::Result;
+use jj_lib::backend::TreeValue;
+use jj_lib::commit::Commit;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::revset::{RevsetExpression, RevsetIteratorExt};
+use serde::Serialize;
+
+use crate::format::{OutputFormat, commit_summary, print_nul_terminated};
+use crate::progress::Progress;
+use crate::serve::CommitInfo;
+use crate::template::Template;
+use crate::workspace::WorkspaceHelper;
+
+/// How far back to look for the commit that introduced a conflict side.
+const MAX_ANCESTORS_SEARCHED: usize = 1000;
+
+/// Finds the nearest ancestor of `commit` (inclusive) whose tree has `term`
+/// at `path` while its parents' tree doesn't.
+fn find_introducing_commit(
+    repo: &dyn Repo,
+    commit: &Commit,
+    path: &RepoPath,
+    term: &TreeValue,
+) -> Result<Option<Commit>> {
+    let ancestors = RevsetExpression::commits(vec![commit.id().clone()])
+        .ancestors()
+        .evaluate(repo)?;
+    for candidate in ancestors
+        .iter()
+        .commits(repo.store())
+        .take(MAX_ANCESTORS_SEARCHED)
+    {
+        let candidate = candidate?;
+        if candidate.id() == repo.store().root_commit_id() {
+            break;
+        }
+        let value = candidate.tree()?.path_value(path)?;
+        if value.as_resolved() != Some(&Some(term.clone())) {
+            continue;
+        }
+        let parent_value = candidate.parent_tree(repo)?.path_value(path)?;
+        if parent_value.as_resolved() != Some(&Some(term.clone())) {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// One side or base of a conflict.
+#[derive(Serialize)]
+struct Term {
+    /// The path doesn't exist on this side.
+    absent: bool,
+    /// The nearest ancestor that introduced this side's version, if it was
+    /// found.
+    introduced_by: Option<CommitInfo>,
+    #[serde(skip)]
+    introduced_by_summary: Option<String>,
+}
+
+impl Term {
+    fn describe(&self) -> &str {
+        if self.absent {
+            "(absent)"
+        } else {
+            self.introduced_by_summary
+                .as_deref()
+                .unwrap_or("(not found in ancestors)")
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ConflictedPath {
+    path: String,
+    sides: usize,
+    /// Whether the parents are already conflicted at this path.
+    inherited: bool,
+    adds: Vec<Term>,
+    removes: Vec<Term>,
+}
+
+#[derive(Serialize)]
+struct ConflictedCommit {
+    #[serde(flatten)]
+    commit: CommitInfo,
+    paths: Vec<ConflictedPath>,
+    #[serde(skip)]
+    summary: String,
+}
+
+fn describe_term(
+    repo: &dyn Repo,
+    commit: &Commit,
+    path: &RepoPath,
+    term: &Option<TreeValue>,
+) -> Result<Term> {
+    let Some(term) = term else {
+        return Ok(Term {
+            absent: true,
+            introduced_by: None,
+            introduced_by_summary: None,
+        });
+    };
+    let introduced_by = find_introducing_commit(repo, commit, path, term)?;
+    Ok(Term {
+        absent: false,
+        introduced_by: introduced_by.as_ref().map(CommitInfo::new),
+        introduced_by_summary: introduced_by.as_ref().map(commit_summary),
+    })
+}
+
+fn print_text(conflicted: &[ConflictedCommit]) {
+    for commit in conflicted {
+        println!("{}", commit.summary);
+        for path in &commit.paths {
+            println!(
+                "  {}  {}-sided conflict ({})",
+                path.path,
+                path.sides,
+                if path.inherited { "inherited" } else { "new" }
+            );
+            for (index, add) in path.adds.iter().enumerate() {
+                println!("    side {}: {}", index + 1, add.describe());
+                if let Some(remove) = path.removes.get(index) {
+                    println!("    base {}: {}", index + 1, remove.describe());
+                }
+            }
+        }
+    }
+    let conflicted_paths: usize = conflicted.iter().map(|commit| commit.paths.len()).sum();
+    if conflicted.is_empty() {
+        println!("No conflicts");
+    } else {
+        println!(
+            "{conflicted_paths} conflicted path(s) in {} revision(s)",
+            conflicted.len()
+        );
+    }
+}
+
+pub async fn conflicts_command(
+    revset: String,
+    null: bool,
+    template: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let template = template.as_deref().map(Template::parse).transpose()?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    let repo = helper.repo().as_ref();
+
+    let mut conflicted = Vec::new();
+    let commits = helper.evaluate_revset(&revset)?;
+    let mut progress = Progress::new("conflicts", Some(commits.len()));
+    for commit in commits {
+        progress.start_item(&commit_summary(&commit));
+        if !commit.has_conflict()? {
+            continue;
+        }
+        let parent_tree = commit.parent_tree(repo)?;
+        let mut paths = Vec::new();
+        for (path, value) in commit.tree_async().await?.conflicts() {
+            let value = value?;
+            let inherited = !parent_tree.path_value(&path)?.is_resolved();
+            paths.push(ConflictedPath {
+                path: path.as_internal_file_string().to_string(),
+                sides: value.num_sides(),
+                inherited,
+                adds: value
+                    .adds()
+                    .map(|add| describe_term(repo, &commit, &path, add))
+                    .collect::<Result<_>>()?,
+                removes: value
+                    .removes()
+                    .map(|remove| describe_term(repo, &commit, &path, remove))
+                    .collect::<Result<_>>()?,
+            });
+        }
+        conflicted.push(ConflictedCommit {
+            commit: CommitInfo::new(&commit),
+            paths,
+            summary: match &template {
+                Some(template) => template.render(repo, &commit)?,
+                None => commit_summary(&commit),
+            },
+        });
+    }
+    progress.finish();
+
+    match format {
+        OutputFormat::Text if null => print_nul_terminated(
+            conflicted
+                .iter()
+                .flat_map(|commit| &commit.paths)
+                .map(|path| path.path.as_str()),
+        )?,
+        OutputFormat::Text => print_text(&conflicted),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "commits": conflicted }))?
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! `jjka copy-hunks`: bring selected lines of a file as they are in another
+//! revision into the working copy, leaving that revision alone.
+//!
+//! The ranges are line numbers in the other revision's version of the file.
+//! The working-copy file is diffed against that version, and only the parts
+//! of the diff that produce selected lines are applied.
+
+use anyhow::Result;
+use jjka_lib::selection::copy_lines;
+
+use crate::format::commit_summary;
+use crate::line_edit::{LinesOf, edit_working_copy_lines};
+use crate::ui::status;
+use crate::workspace::{self, PolicyArgs, WorkspaceHelper};
+
+pub async fn copy_hunks_command(
+    revision: String,
+    ranges: Vec<String>,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let ranges = workspace::parse_ranges(&ranges)?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let source = helper.resolve_single(&revision)?;
+    let changed_files = edit_working_copy_lines(
+        &mut helper,
+        &source,
+        &ranges,
+        LinesOf::Revision,
+        copy_lines,
+        &format!("copy lines from commit {}", commit_summary(&source)),
+    )
+    .await?;
+    if changed_files.is_empty() {
+        status!("Nothing to copy: the selected lines are the same in the working copy");
+        return Ok(());
+    }
+    println!(
+        "Copied lines from {} into the working copy:",
+        commit_summary(&source)
+    );
+    for file in &changed_files {
+        println!("  {file}");
+    }
+    Ok(())
+}
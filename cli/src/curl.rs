@@ -0,0 +1,76 @@
+//! Forge API requests through `curl`.
+//!
+//! Going through curl means TLS, proxies and certificates work the way they
+//! do for the user's other tools. Credentials are handed to curl as config
+//! on stdin rather than as arguments, so they don't show up in the process
+//! list.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::process::{Command, Stdio};
+
+use crate::sigpipe;
+
+pub struct Response {
+    /// Whether the server answered with a 2xx status.
+    pub ok: bool,
+    pub body: Vec<u8>,
+    /// What curl reported about a failed request.
+    pub error: String,
+}
+
+/// Quotes a value for a curl config file.
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Sends `method url` with the curl `config` lines, like headers with
+/// tokens, and `body` as JSON.
+pub fn request(
+    method: &str,
+    url: &str,
+    config: &str,
+    body: Option<&impl Serialize>,
+) -> Result<Response> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail-with-body"])
+        .args(["--request", method, "--config", "-"]);
+    let body_file = match body {
+        Some(body) => {
+            let mut file = tempfile::NamedTempFile::new()?;
+            serde_json::to_writer(&mut file, body)?;
+            command
+                .args(["--header", "Content-Type: application/json"])
+                .arg("--data-binary")
+                .arg(format!("@{}", file.path().display()));
+            Some(file)
+        }
+        None => None,
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl")?;
+    sigpipe::write_to_child(child.stdin.take().unwrap(), config.as_bytes())?;
+    let output = child.wait_with_output()?;
+    drop(body_file);
+    Ok(Response {
+        ok: output.status.success(),
+        body: output.stdout,
+        error: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+}
@@ -0,0 +1,371 @@
+//! `jjka describe`: edit a commit description, starting empty descriptions
+//! from a conventional-commit template.
+//!
+//! The template's `{type}` and `{scope}` are guessed from the paths the
+//! commit touches, and `{issue}` from the names of bookmarks on the commit
+//! or its descendants. Lines starting with `JJ:` and trailers left without a
+//! value are removed after editing, so the scaffolding only stays if it's
+//! filled in.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::object_id::ObjectId as _;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::format::commit_summary;
+use crate::tree_diff::changed_paths;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+const DEFAULT_TEMPLATE: &str = "\
+{type}{scope}:
+
+JJ: Describe the change above. Empty trailers below are removed.
+BREAKING CHANGE:
+Refs: {issue}
+";
+
+const DEFAULT_ISSUE_PATTERN: &str = "[A-Z][A-Z0-9]+-[0-9]+";
+
+/// Commit types tried in order when `jjka.describe.types` isn't set. A type
+/// applies if every touched path matches one of its globs.
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("docs", &["*.md", "*.rst", "*.txt", "docs/**", "doc/**"]),
+    (
+        "test",
+        &["tests/**", "test/**", "*_test.*", "*_tests.*", "test_*"],
+    ),
+    (
+        "ci",
+        &[".github/**", ".gitlab-ci.yml", ".gitlab/**", "ci/**"],
+    ),
+    (
+        "build",
+        &[
+            "Cargo.toml",
+            "Cargo.lock",
+            "package.json",
+            "package-lock.json",
+            "Makefile",
+            "*.cmake",
+        ],
+    ),
+];
+
+/// Leading directories that say nothing about what part of the project a
+/// path belongs to.
+const GENERIC_DIRS: &[&str] = &["src", "lib", "crates", "packages", "cmd", "pkg"];
+
+/// The editor command from `ui.editor`, `$VISUAL` or `$EDITOR`.
+pub fn editor_command(helper: &WorkspaceHelper) -> Result<Vec<String>> {
+    let editor = match helper.settings().get_string("ui.editor").optional()? {
+        Some(editor) => editor,
+        None => std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string()),
+    };
+    Ok(editor.split_whitespace().map(str::to_string).collect())
+}
+
+/// Lets the user edit `description` in their editor.
+pub fn edit_description(editor: &[String], description: &str) -> Result<String> {
+    let file = tempfile::Builder::new()
+        .prefix("jjka-description-")
+        .suffix(".txt")
+        .tempfile()?;
+    std::fs::write(file.path(), description)?;
+    let status = Command::new(&editor[0])
+        .args(&editor[1..])
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("Failed to run editor {}", editor[0]))?;
+    if !status.success() {
+        bail!("Editor {} failed ({status})", editor[0]);
+    }
+    Ok(std::fs::read_to_string(file.path())?)
+}
+
+/// Matches all of `path` against a glob where `*` stays within a path
+/// component and `**` spans any number of them.
+pub fn glob_match_path(glob: &str, path: &str) -> bool {
+    fn matches(glob: &[u8], path: &[u8]) -> bool {
+        match glob {
+            [] => path.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                matches(rest, path)
+                    || path
+                        .iter()
+                        .position(|b| *b == b'/')
+                        .is_some_and(|slash| matches(glob, &path[slash + 1..]))
+            }
+            [b'*', b'*'] => true,
+            [b'*', rest @ ..] => (0..=path.len())
+                .take_while(|&i| i == 0 || path[i - 1] != b'/')
+                .any(|i| matches(rest, &path[i..])),
+            [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+        }
+    }
+    matches(glob.as_bytes(), path.as_bytes())
+}
+
+/// Like [`glob_match_path`], but globs without a `/` match the file name
+/// anywhere, like in `.gitignore`.
+pub fn glob_match(glob: &str, path: &str) -> bool {
+    if glob.contains('/') {
+        glob_match_path(glob, path)
+    } else {
+        glob_match_path(glob, path.rsplit('/').next().unwrap_or(path))
+    }
+}
+
+/// A type or scope with the globs of the paths it applies to.
+type Rule = (String, Vec<String>);
+
+/// The first of `rules` whose globs cover all of `paths`.
+fn matching_rule<'a>(rules: &'a [Rule], paths: &[String]) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|(_, globs)| {
+            paths
+                .iter()
+                .all(|path| globs.iter().any(|glob| glob_match(glob, path)))
+        })
+        .map(|(name, _)| name.as_str())
+}
+
+/// The part of the project all `paths` are in: the first directory after
+/// generic ones like `src/`, or the file stem if that's all there is.
+fn guess_scope(paths: &[String]) -> Option<String> {
+    let mut scope = None;
+    for path in paths {
+        let components: Vec<&str> = path.split('/').collect();
+        let skip = components
+            .iter()
+            .take(components.len() - 1)
+            .take_while(|component| GENERIC_DIRS.contains(component))
+            .count();
+        let rest = &components[skip..];
+        let candidate = match rest {
+            [file] => file.split('.').next().unwrap_or(file),
+            [dir, ..] => dir,
+            [] => return None,
+        };
+        match scope {
+            None => scope = Some(candidate),
+            Some(scope) if scope == candidate => {}
+            Some(_) => return None,
+        }
+    }
+    scope.filter(|scope| !scope.is_empty()).map(str::to_string)
+}
+
+/// Rules from a `name = [globs]` table in the config, in name order.
+fn config_rules(helper: &WorkspaceHelper, key: &'static str) -> Result<Option<Vec<Rule>>> {
+    let rules: Option<BTreeMap<String, Vec<String>>> = helper.settings().get(key).optional()?;
+    Ok(rules.map(|rules| rules.into_iter().collect()))
+}
+
+/// The issue key in the name of a bookmark on `commit` or one of its
+/// descendants, matching `jjka.describe.issue-pattern`.
+pub fn bookmark_issue(helper: &WorkspaceHelper, commit: &Commit) -> Result<Option<String>> {
+    let pattern = helper
+        .settings()
+        .get_string("jjka.describe.issue-pattern")
+        .optional()?
+        .unwrap_or_else(|| DEFAULT_ISSUE_PATTERN.to_string());
+    let pattern = Regex::new(&pattern).context("Invalid jjka.describe.issue-pattern")?;
+    let descendants = helper.evaluate_revset_ids(&format!("{}::", commit.id().hex()))?;
+    Ok(helper
+        .repo()
+        .view()
+        .local_bookmarks()
+        .filter(|(_, target)| target.added_ids().any(|id| descendants.contains(id)))
+        .find_map(|(name, _)| pattern.find(name.as_str()).map(|m| m.as_str().to_string())))
+}
+
+/// Fills in `template` for `commit`.
+async fn render_template(
+    helper: &WorkspaceHelper,
+    commit: &Commit,
+    template: &str,
+) -> Result<String> {
+    let repo = helper.repo().clone();
+    let parent_tree = commit.parent_tree(repo.as_ref())?;
+    let tree = commit.tree_async().await?;
+    let paths: Vec<String> = changed_paths(&parent_tree, &tree)
+        .await?
+        .into_iter()
+        .map(|change| change.path.as_internal_file_string().to_string())
+        .collect();
+
+    let types = match config_rules(helper, "jjka.describe.types")? {
+        Some(types) => types,
+        None => DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| {
+                (
+                    name.to_string(),
+                    globs.iter().map(|glob| glob.to_string()).collect(),
+                )
+            })
+            .collect(),
+    };
+    let default_type = helper
+        .settings()
+        .get_string("jjka.describe.default-type")
+        .optional()?
+        .unwrap_or_else(|| "feat".to_string());
+    let commit_type = if paths.is_empty() {
+        default_type.as_str()
+    } else {
+        matching_rule(&types, &paths).unwrap_or(&default_type)
+    };
+    let scope = match config_rules(helper, "jjka.describe.scopes")? {
+        Some(scopes) => matching_rule(&scopes, &paths).map(str::to_string),
+        None => guess_scope(&paths),
+    };
+
+    let issue = bookmark_issue(helper, commit)?.unwrap_or_default();
+
+    let placeholder = Regex::new(r"\{([a-z]*)\}").unwrap();
+    let mut unknown = None;
+    let rendered =
+        placeholder.replace_all(template, |captures: &regex::Captures| match &captures[1] {
+            "type" => commit_type.to_string(),
+            "scope" => scope
+                .as_ref()
+                .map(|scope| format!("({scope})"))
+                .unwrap_or_default(),
+            "issue" => issue.clone(),
+            other => {
+                unknown.get_or_insert_with(|| other.to_string());
+                String::new()
+            }
+        });
+    if let Some(unknown) = unknown {
+        bail!("Unknown placeholder {{{unknown}}} in jjka.describe.template");
+    }
+    Ok(rendered.into_owned())
+}
+
+/// The text to open the editor with for `commit`: its description, or the
+/// template if it has none.
+pub async fn initial_description(helper: &WorkspaceHelper, commit: &Commit) -> Result<String> {
+    if !commit.description().is_empty() {
+        return Ok(commit.description().to_string());
+    }
+    let template = helper
+        .settings()
+        .get_string("jjka.describe.template")
+        .optional()?
+        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+    render_template(helper, commit, &template).await
+}
+
+/// Removes `JJ:` lines, trailers without a value after the subject, and
+/// trailing whitespace. Fails if only an unfilled `type(scope):` subject is
+/// left.
+pub fn clean_description(text: &str) -> Result<String> {
+    let empty_trailer = Regex::new(r"^[A-Za-z][A-Za-z -]*:\s*$").unwrap();
+    let mut lines: Vec<&str> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.starts_with("JJ:") || (index > 0 && empty_trailer.is_match(line)) {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    if lines.first().is_some_and(|subject| subject.ends_with(':')) {
+        bail!("The subject line is unfinished; the description was not changed");
+    }
+    if lines.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+pub async fn describe_command(revision: String, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let commit = helper.resolve_single(&revision)?;
+    let initial = initial_description(&helper, &commit).await?;
+    let editor = editor_command(&helper)?;
+    let description = clean_description(&edit_description(&editor, &initial)?)?;
+    if description == commit.description() {
+        status!("Nothing changed: the description is the same");
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    let new_commit = tx
+        .repo_mut()
+        .rewrite_commit(&commit)
+        .set_description(description)
+        .write()?;
+    helper
+        .finish_transaction(tx, &format!("describe commit {}", commit.id().hex()))
+        .await?;
+    println!("Described {}", commit_summary(&new_commit));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.md", "README.md"));
+        assert!(glob_match("*.md", "docs/guide/intro.md"));
+        assert!(glob_match("docs/**", "docs/guide/intro.md"));
+        assert!(!glob_match("docs/**", "src/docs.rs"));
+        assert!(glob_match("src/*.rs", "src/main.rs"));
+        assert!(!glob_match("src/*.rs", "src/parser/mod.rs"));
+        assert!(glob_match("src/**/mod.rs", "src/mod.rs"));
+        assert!(glob_match("src/**/mod.rs", "src/parser/ast/mod.rs"));
+    }
+
+    #[test]
+    fn test_guess_scope() {
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            guess_scope(&paths(&["src/parser/lex.rs", "src/parser/ast.rs"])).as_deref(),
+            Some("parser")
+        );
+        assert_eq!(
+            guess_scope(&paths(&["crates/cli/src/main.rs"])).as_deref(),
+            Some("cli")
+        );
+        assert_eq!(
+            guess_scope(&paths(&["src/main.rs"])).as_deref(),
+            Some("main")
+        );
+        assert_eq!(guess_scope(&paths(&["src/main.rs", "docs/a.md"])), None);
+    }
+
+    #[test]
+    fn test_clean_description() {
+        assert_eq!(
+            clean_description(
+                "fix(parser): Handle unary minus\n\nJJ: a comment\nBREAKING CHANGE: \nRefs: \n"
+            )
+            .unwrap(),
+            "fix(parser): Handle unary minus\n"
+        );
+        assert_eq!(
+            clean_description("feat: Add x\n\nBody\n\nRefs: ABC-1\n").unwrap(),
+            "feat: Add x\n\nBody\n\nRefs: ABC-1\n"
+        );
+        assert!(clean_description("feat(parser): \n\nRefs: \n").is_err());
+    }
+}
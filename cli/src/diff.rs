@@ -0,0 +1,229 @@
+//! `jjka diff`: show a revision's changes with the changed words of each
+//! line highlighted.
+//!
+//! Lines are diffed the way jj does, and each run of removed lines is then
+//! compared word by word with the added lines that replace it, so a change
+//! in the middle of a long line is easy to spot. With `--lines`, only the
+//! changes that make up those lines are shown, which is what splitting them
+//! out would move into the new commit.
+
+use anyhow::Result;
+use itertools::Itertools;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPathBuf;
+use serde::Serialize;
+
+use crate::format::{OutputFormat, commit_summary, use_color};
+use crate::highlight::{Language, Theme, language_for_path, tokenize};
+use crate::hunks::{commit_diffs, tree_diffs};
+use crate::pager;
+use crate::split::plan_split;
+use crate::unified_diff::{Hunk, LineKind, Segment, word_segments};
+use crate::workspace::{self, WorkspaceHelper};
+
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const REVERSE: &str = "\x1b[7m";
+const NO_REVERSE: &str = "\x1b[27m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Serialize)]
+struct WordLine {
+    kind: LineKind,
+    text: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Serialize)]
+struct WordHunk {
+    #[serde(skip)]
+    header: String,
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+    lines: Vec<WordLine>,
+}
+
+impl WordHunk {
+    fn new(hunk: Hunk) -> Self {
+        let segments = word_segments(&hunk);
+        WordHunk {
+            header: hunk.header(),
+            old_start: hunk.old_start,
+            old_len: hunk.old_len,
+            new_start: hunk.new_start,
+            new_len: hunk.new_len,
+            lines: hunk
+                .lines
+                .into_iter()
+                .zip(segments)
+                .map(|(line, segments)| WordLine {
+                    kind: line.kind,
+                    text: line.text,
+                    segments,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FileDiff {
+    path: String,
+    added: bool,
+    removed: bool,
+    binary: bool,
+    hunks: Vec<WordHunk>,
+}
+
+/// A line's text with its syntax highlighted, its changed words in reverse
+/// video, and everything else in `line_style`.
+fn paint_line(line: &WordLine, line_style: &str, theme: &Theme, language: &Language) -> String {
+    let mut out = String::new();
+    let mut segments = line.segments.iter();
+    let mut segment = segments.next();
+    let mut segment_start = 0;
+    for (kind, range) in tokenize(language, &line.text) {
+        let mut pos = range.start;
+        while pos < range.end {
+            let Some(current) = segment else {
+                break;
+            };
+            let segment_end = segment_start + current.text.len();
+            let end = segment_end.min(range.end);
+            out.push_str(line_style);
+            if current.changed {
+                out.push_str(REVERSE);
+            }
+            out.push_str(theme.color(kind).unwrap_or(""));
+            out.push_str(&line.text[pos..end]);
+            out.push_str(RESET);
+            pos = end;
+            if end == segment_end {
+                segment = segments.next();
+                segment_start = segment_end;
+            }
+        }
+    }
+    out
+}
+
+/// Prints `file` as a unified diff; with `git_headers`, laid out exactly
+/// like `git diff` for a diff pager to parse.
+fn print_file(file: &FileDiff, color: bool, theme: Option<&Theme>, git_headers: bool) {
+    let style = |code: &'static str| if color { code } else { "" };
+    let language = theme.filter(|_| color).zip(language_for_path(&file.path));
+    let reset = style(RESET);
+    let old = if file.added {
+        "/dev/null".to_string()
+    } else {
+        format!("a/{}", file.path)
+    };
+    let new = if file.removed {
+        "/dev/null".to_string()
+    } else {
+        format!("b/{}", file.path)
+    };
+    if git_headers {
+        println!("diff --git a/{0} b/{0}", file.path);
+        if file.binary {
+            println!("Binary files {old} and {new} differ");
+            return;
+        }
+    }
+    println!("{}--- {old}{reset}", style(BOLD));
+    println!("{}+++ {new}{reset}", style(BOLD));
+    if file.binary {
+        println!("Binary file {} changed", file.path);
+        return;
+    }
+    for hunk in &file.hunks {
+        println!("{}{}{reset}", style(CYAN), hunk.header);
+        for line in &hunk.lines {
+            let (sign, line_style) = match line.kind {
+                LineKind::Context => (' ', ""),
+                LineKind::Removed => ('-', style(RED)),
+                LineKind::Added => ('+', style(GREEN)),
+            };
+            let mut out = format!("{line_style}{sign}");
+            if let Some((theme, language)) = language {
+                out.push_str(RESET);
+                out.push_str(&paint_line(line, line_style, theme, language));
+                println!("{out}");
+                continue;
+            }
+            for segment in &line.segments {
+                if color && segment.changed {
+                    out.push_str(&format!("{REVERSE}{}{NO_REVERSE}", segment.text));
+                } else {
+                    out.push_str(&segment.text);
+                }
+            }
+            if !line_style.is_empty() {
+                out.push_str(reset);
+            }
+            println!("{out}");
+        }
+    }
+}
+
+pub async fn diff_command(
+    revision: String,
+    paths: Vec<String>,
+    lines: Vec<String>,
+    context: usize,
+    format: OutputFormat,
+) -> Result<()> {
+    let helper = WorkspaceHelper::load()?;
+    let repo = helper.repo().clone();
+    let commit = helper.resolve_single(&revision)?;
+    let diffs = if lines.is_empty() {
+        commit_diffs(repo.as_ref(), &commit, context).await?
+    } else {
+        let ranges = workspace::parse_ranges(&lines)?;
+        let plan = plan_split(repo.as_ref(), &commit, &ranges).await?;
+        let from = commit.parent_tree(repo.as_ref())?;
+        let to = repo.store().get_root_tree(&plan.selected_tree_id)?;
+        tree_diffs(repo.as_ref(), &from, &to, context).await?
+    };
+    let prefixes: Vec<RepoPathBuf> = paths
+        .iter()
+        .map(|path| helper.parse_file_path(path))
+        .try_collect()?;
+
+    let files: Vec<FileDiff> = diffs
+        .into_iter()
+        .filter(|diff| {
+            prefixes.is_empty()
+                || RepoPathBuf::from_internal_string(&diff.path)
+                    .is_ok_and(|path| prefixes.iter().any(|prefix| path.starts_with(prefix)))
+        })
+        .map(|diff| FileDiff {
+            path: diff.path,
+            added: diff.added,
+            removed: diff.removed,
+            binary: diff.binary,
+            hunks: diff.hunks.into_iter().map(WordHunk::new).collect(),
+        })
+        .collect();
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&files)?);
+        return Ok(());
+    }
+    // A diff pager like delta colors the diff itself.
+    let git_headers = pager::renders_diffs();
+    let color = !git_headers && use_color(helper.settings())?;
+    if files.is_empty() {
+        println!("No changes in {}", commit_summary(&commit));
+        return Ok(());
+    }
+    let theme = Theme::from_settings(helper.settings())?;
+    for file in &files {
+        print_file(file, color, theme.as_ref(), git_headers);
+    }
+    Ok(())
+}
@@ -0,0 +1,212 @@
+//! `hunksplit --tool`: picking the changes to split out in a diff editor.
+//!
+//! Editors are configured like for `jj split -i`: `--tool` or
+//! `ui.diff-editor` names an entry in `[merge-tools]`, whose `edit-args` may
+//! use `$left` and `$right` and default to `["$left", "$right"]`. The files
+//! the revision changes are written to `$left` as its parent has them and
+//! to `$right` as the new commit would have them; what the editor leaves in
+//! `$right` becomes the new commit, and the rest stays in the revision.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::backend::{CopyId, MergedTreeId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::merge::{Merge, MergedTreeValue};
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::settings::UserSettings;
+use jj_lib::store::Store;
+use jjka_lib::tree_diff::{changed_paths, read_file};
+use jjka_lib::{EmptyCommit, SplitPlan};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::process::Command;
+
+use crate::format::commit_summary;
+use crate::resolve::interpolate;
+use crate::ui::status;
+
+pub struct DiffEditor {
+    name: String,
+    program: String,
+    edit_args: Vec<String>,
+}
+
+impl DiffEditor {
+    pub fn load(settings: &UserSettings, name: Option<String>) -> Result<Self> {
+        let name = match name {
+            Some(name) => name,
+            None => settings
+                .get_string("ui.diff-editor")
+                .optional()?
+                .context("No diff editor configured; set ui.diff-editor or pass --tool")?,
+        };
+        if name.starts_with(':') {
+            bail!("jjka has no built-in diff editor like {name}; name one from [merge-tools]");
+        }
+        let program = settings
+            .get_string(["merge-tools", &name, "program"])
+            .optional()?
+            .unwrap_or_else(|| name.clone());
+        let edit_args = settings
+            .get::<Vec<String>>(["merge-tools", &name, "edit-args"])
+            .optional()?
+            .unwrap_or_else(|| vec!["$left".to_string(), "$right".to_string()]);
+        Ok(DiffEditor {
+            name,
+            program,
+            edit_args,
+        })
+    }
+}
+
+/// Writes `content` at `path` under `dir`, making its directories.
+fn write_file(dir: &Path, path: &str, content: &[u8], executable: bool) -> Result<()> {
+    let file_path = dir.join(path);
+    std::fs::create_dir_all(file_path.parent().unwrap())?;
+    std::fs::write(&file_path, content)?;
+    #[cfg(unix)]
+    if executable {
+        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    let _ = executable;
+    Ok(())
+}
+
+/// Whether the editor left the file at `path` executable. Without Unix
+/// permissions that can't be edited, so it stays as it was.
+#[cfg(unix)]
+fn is_executable(path: &Path, _was_executable: bool) -> Result<bool> {
+    Ok(std::fs::metadata(path)?.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path, was_executable: bool) -> Result<bool> {
+    Ok(was_executable)
+}
+
+/// The content and executable bit of a regular file, or `None` if it's
+/// absent.
+async fn read_entry(
+    store: &Store,
+    path: &RepoPath,
+    value: MergedTreeValue,
+) -> Result<Option<(Vec<u8>, bool)>> {
+    let executable = matches!(
+        value.as_resolved(),
+        Some(Some(TreeValue::File {
+            executable: true,
+            ..
+        }))
+    );
+    Ok(read_file(store, path, value)
+        .await?
+        .map(|content| (content, executable)))
+}
+
+/// Lets the user edit the split of `commit` in `editor`, starting from the
+/// new commit having `initial_tree_id`. Only regular files can be edited;
+/// other entries keep what `initial_tree_id` has.
+pub async fn edit_split(
+    repo: &dyn Repo,
+    commit: &Commit,
+    initial_tree_id: &MergedTreeId,
+    editor: &DiffEditor,
+) -> Result<SplitPlan> {
+    let store = repo.store();
+    let parent_tree = commit.parent_tree(repo)?;
+    let initial_tree = store.get_root_tree(initial_tree_id)?;
+    let dir = tempfile::Builder::new().prefix("jjka-split-").tempdir()?;
+    let (left, right) = (dir.path().join("left"), dir.path().join("right"));
+    std::fs::create_dir(&left)?;
+    std::fs::create_dir(&right)?;
+
+    let mut editable = Vec::new();
+    for change in changed_paths(&parent_tree, &commit.tree_async().await?).await? {
+        let path = change.path;
+        let path_str = path.as_internal_file_string();
+        let is_file = |value: &MergedTreeValue| {
+            matches!(
+                value.as_resolved(),
+                Some(None | Some(TreeValue::File { .. }))
+            )
+        };
+        if !is_file(&change.before) || !is_file(&change.after) {
+            status!("Skipping {path_str}: not a regular file on both sides");
+            continue;
+        }
+        if let Some((content, executable)) = read_entry(store, &path, change.before).await? {
+            write_file(&left, path_str, &content, executable)?;
+        }
+        let initial = read_entry(store, &path, initial_tree.path_value(&path)?).await?;
+        if let Some((content, executable)) = &initial {
+            write_file(&right, path_str, content, *executable)?;
+        }
+        editable.push((path, initial));
+    }
+    if editable.is_empty() {
+        bail!(
+            "{} changes no regular files to edit",
+            commit_summary(commit)
+        );
+    }
+
+    let vars = [
+        ("left", left.to_string_lossy().into_owned()),
+        ("right", right.to_string_lossy().into_owned()),
+    ];
+    let status = Command::new(&editor.program)
+        .args(interpolate(&editor.edit_args, &vars))
+        .status()
+        .with_context(|| format!("Failed to run diff editor {}", editor.program))?;
+    if !status.success() {
+        bail!(
+            "Diff editor {} failed ({status}); nothing was split",
+            editor.name
+        );
+    }
+
+    let mut builder = MergedTreeBuilder::new(initial_tree_id.clone());
+    for (path, initial) in editable {
+        let file_path = right.join(path.as_internal_file_string());
+        let edited = match std::fs::read(&file_path) {
+            Ok(content) => {
+                let was_executable = initial.as_ref().is_some_and(|(_, executable)| *executable);
+                Some((content, is_executable(&file_path, was_executable)?))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", file_path.display()));
+            }
+        };
+        if edited == initial {
+            continue;
+        }
+        let value = match edited {
+            Some((content, executable)) => Merge::normal(TreeValue::File {
+                id: store.write_file(&path, &mut content.as_slice()).await?,
+                executable,
+                copy_id: CopyId::placeholder(),
+            }),
+            None => Merge::absent(),
+        };
+        builder.set_or_remove(path, value);
+    }
+    let selected_tree_id = builder.write_tree(store)?;
+    let empty = if selected_tree_id == parent_tree.id() {
+        Some(EmptyCommit::Selected)
+    } else if selected_tree_id == *commit.tree_id() {
+        Some(EmptyCommit::Remaining)
+    } else {
+        None
+    };
+    Ok(SplitPlan {
+        commit: commit.clone(),
+        selected_tree_id,
+        files: Vec::new(),
+        unchanged: Vec::new(),
+        empty,
+    })
+}
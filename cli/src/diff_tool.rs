@@ -0,0 +1,111 @@
+//! Rendering previews with an external diff program, like difftastic.
+//!
+//! Tools are configured like for `jj diff`: `ui.diff-formatter` names an
+//! entry in `[merge-tools]`, whose `diff-args` may use `$left`, `$right` and
+//! `$path` and default to `["$left", "$right"]`. jj's built-in formatters,
+//! like `:git`, keep jjka's own rendering. The tool runs once per file, on
+//! temp files named after it so it can pick a syntax from the extension.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::settings::UserSettings;
+use std::process::{Command, Stdio};
+
+use crate::resolve::interpolate;
+
+pub struct DiffTool {
+    pub name: String,
+    program: String,
+    diff_args: Vec<String>,
+}
+
+impl DiffTool {
+    /// The tool `ui.diff-formatter` names, if it isn't a built-in one.
+    pub fn load(settings: &UserSettings) -> Result<Option<Self>> {
+        let Some(name) = settings.get_string("ui.diff-formatter").optional()? else {
+            return Ok(None);
+        };
+        if name.starts_with(':') {
+            return Ok(None);
+        }
+        let program = settings
+            .get_string(["merge-tools", &name, "program"])
+            .optional()?
+            .unwrap_or_else(|| name.clone());
+        let diff_args = settings
+            .get::<Vec<String>>(["merge-tools", &name, "diff-args"])
+            .optional()?
+            .unwrap_or_else(|| vec!["$left".to_string(), "$right".to_string()]);
+        Ok(Some(DiffTool {
+            name,
+            program,
+            diff_args,
+        }))
+    }
+
+    /// The tool's output for `path` changing from `before` to `after`.
+    pub fn render(&self, path: &str, before: &[u8], after: &[u8]) -> Result<Vec<u8>> {
+        let dir = tempfile::Builder::new().prefix("jjka-diff-").tempdir()?;
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        let write = |label: &str, content: &[u8]| -> Result<String> {
+            let file_path = dir.path().join(label);
+            std::fs::create_dir(&file_path)?;
+            let file_path = file_path.join(file_name);
+            std::fs::write(&file_path, content)?;
+            Ok(file_path.to_string_lossy().into_owned())
+        };
+        let vars = [
+            ("left", write("left", before)?),
+            ("right", write("right", after)?),
+            ("path", path.to_string()),
+        ];
+        let output = Command::new(&self.program)
+            .args(interpolate(&self.diff_args, &vars))
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run diff tool {}", self.program))?;
+        // Like diff(1), tools may exit with 1 when the files differ.
+        if !output.status.success() && output.status.code() != Some(1) {
+            bail!(
+                "Diff tool {} failed ({}): {}",
+                self.name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let tool = DiffTool {
+            name: "cat".to_string(),
+            program: "sh".to_string(),
+            diff_args: ["-c", r#"echo "$3"; basename "$1"; cat "$1" "$2""#, "sh"]
+                .into_iter()
+                .chain(["$left", "$right", "$path"])
+                .map(str::to_string)
+                .collect(),
+        };
+        let output = tool.render("src/lib.rs", b"old\n", b"new\n").unwrap();
+        assert_eq!(output, b"src/lib.rs\nlib.rs\nold\nnew\n");
+
+        // Exiting with 1 says the files differ.
+        let differing = DiffTool {
+            program: "false".to_string(),
+            diff_args: Vec::new(),
+            ..tool
+        };
+        assert!(differing.render("a.txt", b"", b"").is_ok());
+        let missing = DiffTool {
+            program: "jjka-no-such-tool".to_string(),
+            ..differing
+        };
+        assert!(missing.render("a.txt", b"", b"").is_err());
+    }
+}
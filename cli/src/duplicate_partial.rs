@@ -0,0 +1,81 @@
+//! `jjka duplicate-partial`: copy selected lines of a revision's changes as
+//! a new commit on another revision, like `jj duplicate` for a few hunks.
+//!
+//! The ranges are line numbers in the source revision, like `hunksplit`'s.
+//! The new commit gets the source's author and description, and the source
+//! is left as it is.
+
+use anyhow::{Result, anyhow};
+use jj_lib::repo::Repo as _;
+use jjka_lib::EmptyCommit;
+
+use crate::checks;
+use crate::exit_code::Failure;
+use crate::format::commit_summary;
+use crate::split;
+use crate::workspace::{self, PolicyArgs, WorkspaceHelper};
+
+pub async fn duplicate_partial_command(
+    revision: String,
+    onto: String,
+    ranges: Vec<String>,
+    message: Option<String>,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let parsed_ranges = workspace::parse_ranges(&ranges)?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let source = helper.resolve_single(&revision)?;
+    let destination = helper.resolve_single(&onto)?;
+
+    let plan =
+        split::plan_split_with_modes(helper.repo().as_ref(), &source, &parsed_ranges, &[]).await?;
+    split::check_unchanged(&plan, true)?;
+    if plan.empty == Some(EmptyCommit::Selected) {
+        return Err(Failure::Usage.error(anyhow!(
+            "The selected lines aren't changed in {}, so there is nothing to copy",
+            commit_summary(&source)
+        )));
+    }
+    let repo = helper.repo().clone();
+    let selected_tree = repo.store().get_root_tree(&plan.selected_tree_id)?;
+    let new_tree = destination
+        .tree_async()
+        .await?
+        .merge(source.parent_tree(repo.as_ref())?, selected_tree)
+        .await?;
+
+    let mut tx = helper.start_transaction();
+    let new_commit = tx
+        .repo_mut()
+        .new_commit(vec![destination.id().clone()], new_tree.id())
+        .set_author(source.author().clone())
+        .set_description(message.unwrap_or_else(|| source.description().to_string()))
+        .write()?;
+    checks::check_commits(&helper, &[&new_commit])?;
+    helper
+        .finish_transaction(
+            tx,
+            &format!(
+                "duplicate lines of commit {} onto commit {}",
+                commit_summary(&source),
+                commit_summary(&destination)
+            ),
+        )
+        .await?;
+
+    println!(
+        "Duplicated lines of {} as {}",
+        commit_summary(&source),
+        commit_summary(&new_commit)
+    );
+    if new_commit.has_conflict()? {
+        eprintln!(
+            "Warning: {} has conflicts; the lines don't apply cleanly onto {}",
+            commit_summary(&new_commit),
+            commit_summary(&destination)
+        );
+    }
+    Ok(())
+}
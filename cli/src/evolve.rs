@@ -0,0 +1,209 @@
+//! `jjka evolve`: clean up after a change was rewritten in two places.
+//!
+//! When a change is rewritten while something still builds on its old
+//! version, such as after a concurrent operation or importing a branch that
+//! was rewritten elsewhere, both versions stay visible and the change id
+//! becomes divergent. The copy with the newest committer date is taken to
+//! be the successor, and the commits on top of the other copies are orphans
+//! to rebase onto it.
+
+use anyhow::Result;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use jj_lib::rewrite::rebase_commit;
+use std::collections::HashSet;
+
+use crate::format::{commit_summary, short_change_id};
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// What to do with the older copies of a divergent change.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DivergentAction {
+    /// Only move their descendants onto the newest copy
+    #[default]
+    Keep,
+    /// Abandon them
+    Abandon,
+    /// Squash their changes into the newest copy
+    Merge,
+}
+
+/// A divergent change's visible copies, oldest first.
+struct Divergent {
+    copies: Vec<Commit>,
+}
+
+impl Divergent {
+    fn newest(&self) -> &Commit {
+        self.copies.last().unwrap()
+    }
+
+    fn older(&self) -> &[Commit] {
+        &self.copies[..self.copies.len() - 1]
+    }
+}
+
+/// The divergent changes with a copy in `commits`, parents first.
+fn find_divergent(repo: &dyn Repo, commits: &[Commit]) -> Result<Vec<Divergent>> {
+    let mut seen = HashSet::new();
+    let mut divergent = Vec::new();
+    for commit in commits {
+        if !seen.insert(commit.change_id().clone()) {
+            continue;
+        }
+        let ids = repo
+            .resolve_change_id(commit.change_id())
+            .unwrap_or_default();
+        if ids.len() < 2 {
+            continue;
+        }
+        let mut copies = ids
+            .iter()
+            .map(|id| repo.store().get_commit(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        copies.sort_by_key(|copy| (copy.committer().timestamp.timestamp, copy.id().clone()));
+        divergent.push(Divergent { copies });
+    }
+    Ok(divergent)
+}
+
+/// `parent_ids` with `old` replaced by `new`, keeping each parent once.
+fn replace_parent(parent_ids: &[CommitId], old: &CommitId, new: &CommitId) -> Vec<CommitId> {
+    let mut parents = Vec::new();
+    for id in parent_ids {
+        let id = if id == old { new } else { id };
+        if !parents.contains(id) {
+            parents.push(id.clone());
+        }
+    }
+    parents
+}
+
+pub async fn evolve_command(
+    revset: String,
+    divergent_action: DivergentAction,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let commits = helper.evaluate_revset(&revset)?;
+    let in_revset: HashSet<&CommitId> = commits.iter().map(|commit| commit.id()).collect();
+    let divergent = find_divergent(helper.repo().as_ref(), &commits)?;
+    if divergent.is_empty() {
+        status!("Nothing to evolve: no divergent changes in {revset}");
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    let mut kept = 0;
+    for change in &divergent {
+        println!(
+            "Divergent change {} has {} copies; the newest is {}",
+            short_change_id(change.newest()),
+            change.copies.len(),
+            commit_summary(change.newest())
+        );
+        let mut actions = Vec::new();
+        let mut target = change.newest().clone();
+        for old in change.older() {
+            // Copies outside the revset, and ones an earlier change's
+            // repair already rewrote, are left for another run.
+            if !in_revset.contains(old.id())
+                || old.is_hidden(tx.repo())
+                || target.is_hidden(tx.repo())
+                || tx.repo().index().is_ancestor(old.id(), target.id())
+            {
+                continue;
+            }
+            match divergent_action {
+                DivergentAction::Keep => {
+                    let children =
+                        helper.evaluate_revset(&format!("children({})", old.id().hex()))?;
+                    for child in children {
+                        let parents = replace_parent(child.parent_ids(), old.id(), target.id());
+                        actions.push(format!(
+                            "Rebased orphan {} onto {}",
+                            commit_summary(&child),
+                            commit_summary(&target)
+                        ));
+                        rebase_commit(tx.repo_mut(), child, parents).await?;
+                    }
+                    kept += 1;
+                }
+                DivergentAction::Abandon => {
+                    tx.repo_mut()
+                        .set_rewritten_commit(old.id().clone(), target.id().clone());
+                    actions.push(format!(
+                        "Abandoned {}, keeping {}",
+                        commit_summary(old),
+                        commit_summary(&target)
+                    ));
+                }
+                DivergentAction::Merge => {
+                    let tree = target
+                        .tree_async()
+                        .await?
+                        .merge(old.parent_tree(tx.repo())?, old.tree_async().await?)
+                        .await?;
+                    // The newest description wins unless it's empty.
+                    let description = if target.description().trim().is_empty() {
+                        old.description()
+                    } else {
+                        target.description()
+                    }
+                    .to_string();
+                    let merged = tx
+                        .repo_mut()
+                        .rewrite_commit(&target)
+                        .set_tree_id(tree.id())
+                        .set_description(description)
+                        .write()?;
+                    tx.repo_mut()
+                        .set_rewritten_commit(old.id().clone(), merged.id().clone());
+                    actions.push(format!(
+                        "Merged {} into {}",
+                        commit_summary(old),
+                        commit_summary(&merged)
+                    ));
+                    target = merged;
+                }
+            }
+        }
+        tx.repo_mut().rebase_descendants()?;
+        for action in &actions {
+            println!("  {action}");
+        }
+    }
+    if tx.repo().has_changes() {
+        helper
+            .finish_transaction(tx, &format!("evolve {revset}"))
+            .await?;
+    }
+    if kept > 0 {
+        println!(
+            "Kept {kept} older copies; run with --divergent abandon or --divergent merge to resolve them"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_parent() {
+        let id = |byte| CommitId::new(vec![byte]);
+        assert_eq!(replace_parent(&[id(1)], &id(1), &id(2)), [id(2)]);
+        assert_eq!(
+            replace_parent(&[id(3), id(1)], &id(1), &id(2)),
+            [id(3), id(2)]
+        );
+        // A merge of both copies ends up with only the newest.
+        assert_eq!(replace_parent(&[id(1), id(2)], &id(1), &id(2)), [id(2)]);
+    }
+}
@@ -0,0 +1,135 @@
+//! Exit codes that tell kinds of failure apart, for wrappers and CI.
+//!
+//! Errors are classified by tagging them with a [`Failure`] where they're
+//! raised, or by the jj-lib or system error at the bottom of their chain.
+//! Anything else exits with [`FAILED`]. The codes are part of the CLI and
+//! documented in the README, so they must not change.
+
+use anyhow::Error;
+use jj_lib::backend::BackendError;
+use jj_lib::op_heads_store::OpHeadsStoreError;
+use jj_lib::op_store::OpStoreError;
+use jj_lib::revset::{RevsetEvaluationError, RevsetParseError, RevsetResolutionError};
+use jjka_lib::RangeParseError;
+use std::fmt;
+
+/// The command failed, e.g. a check like `assert` didn't hold.
+pub const FAILED: u8 = 1;
+/// The command did what was asked, but left commits with new conflicts.
+pub const CONFLICTS: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// Invalid arguments, such as a malformed line range or revset (2, the
+    /// same as clap's usage errors).
+    Usage,
+    /// The repo isn't in a state the command can work on: there is no repo,
+    /// the working copy is stale, or a commit is frozen (3).
+    Precondition,
+    /// The storage backend or the operating system failed (70).
+    Internal,
+}
+
+impl Failure {
+    pub fn code(self) -> u8 {
+        match self {
+            Failure::Usage => 2,
+            Failure::Precondition => 3,
+            Failure::Internal => 70,
+        }
+    }
+
+    /// Tags `error` with this kind of failure.
+    pub fn error(self, error: impl Into<Error>) -> Error {
+        Error::new(Classified {
+            failure: self,
+            error: error.into(),
+        })
+    }
+}
+
+/// An error tagged with a [`Failure`]. It shows as the error it wraps, so the
+/// message and its causes read the same.
+#[derive(Debug)]
+struct Classified {
+    failure: Failure,
+    error: Error,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for Classified {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Tags the error of a result with a [`Failure`].
+pub trait ResultExt<T> {
+    fn failure(self, failure: Failure) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn failure(self, failure: Failure) -> anyhow::Result<T> {
+        self.map_err(|error| failure.error(error))
+    }
+}
+
+fn classify(error: &Error) -> Option<Failure> {
+    // A tag takes precedence over what's under it.
+    if let Some(classified) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<Classified>())
+    {
+        return Some(classified.failure);
+    }
+    error.chain().find_map(|cause| {
+        if cause.is::<RevsetParseError>()
+            || cause.is::<RevsetResolutionError>()
+            || cause.is::<RangeParseError>()
+        {
+            Some(Failure::Usage)
+        } else if cause.is::<BackendError>()
+            || cause.is::<OpStoreError>()
+            || cause.is::<OpHeadsStoreError>()
+            || cause.is::<RevsetEvaluationError>()
+            || cause.is::<std::io::Error>()
+        {
+            Some(Failure::Internal)
+        } else {
+            None
+        }
+    })
+}
+
+/// The exit code for a command that failed with `error`.
+pub fn for_error(error: &Error) -> u8 {
+    classify(error).map_or(FAILED, Failure::code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context as _, anyhow};
+
+    #[test]
+    fn test_for_error() {
+        assert_eq!(for_error(&anyhow!("Assertion failed")), FAILED);
+        assert_eq!(for_error(&Failure::Usage.error(anyhow!("Bad range"))), 2);
+
+        let tagged: anyhow::Result<()> = Err(anyhow!("frozen")).failure(Failure::Precondition);
+        let error = tagged.context("Can't rewrite").unwrap_err();
+        assert_eq!(for_error(&error), 3);
+        assert_eq!(format!("{error:#}"), "Can't rewrite: frozen");
+
+        let io_error = std::io::Error::other("disk full");
+        assert_eq!(
+            for_error(&Error::new(io_error).context("Failed to write")),
+            70
+        );
+    }
+}
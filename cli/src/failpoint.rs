@@ -0,0 +1,56 @@
+//! Failures injected at named points of a command, for tests that check a
+//! failure midway leaves nothing half done.
+//!
+//! Debug builds read `JJKA_FAILPOINTS`, a comma-separated list of points:
+//! `split` fails there with an error, and `commit=interrupt` delivers a
+//! ctrl-C instead, on Unix where that's a signal. Release builds ignore it.
+
+use anyhow::{Result, bail};
+
+/// What to do at the point `name`, if `points` lists it.
+fn action<'a>(points: &'a str, name: &str) -> Option<&'a str> {
+    points.split(',').find_map(|point| {
+        let (point, action) = point
+            .trim()
+            .split_once('=')
+            .unwrap_or((point.trim(), "error"));
+        (point == name).then_some(action)
+    })
+}
+
+/// Fails, or interrupts the process, if `JJKA_FAILPOINTS` names `name`.
+pub fn hit(name: &str) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        return Ok(());
+    }
+    let Ok(points) = std::env::var("JJKA_FAILPOINTS") else {
+        return Ok(());
+    };
+    match action(&points, name) {
+        None => Ok(()),
+        #[cfg(unix)]
+        Some("interrupt") => {
+            unsafe {
+                libc::raise(libc::SIGINT);
+            }
+            Ok(())
+        }
+        Some(_) => bail!("Injected failure at {name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action() {
+        assert_eq!(action("split", "split"), Some("error"));
+        assert_eq!(
+            action("split, commit=interrupt", "commit"),
+            Some("interrupt")
+        );
+        assert_eq!(action("splits", "split"), None);
+        assert_eq!(action("", "split"), None);
+    }
+}
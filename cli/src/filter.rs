@@ -0,0 +1,327 @@
+//! `jjka filter`: remove paths or leaked secrets from history, like
+//! `git filter-repo`.
+//!
+//! Every revision in the revset gets its own tree filtered, instead of its
+//! diff rebased, so a removed file can't come back through a conflict.
+//! Descendants outside the revset are rebased onto the filtered commits.
+//! The rewritten commits keep their change ids.
+
+use anyhow::{Context, Result, bail};
+use futures::StreamExt as _;
+use jj_lib::backend::{CommitId, FileId, MergedTreeId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::matchers::{EverythingMatcher, Matcher, PrefixMatcher};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::store::Store;
+use regex::bytes::{NoExpand, Regex};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncReadExt as _;
+
+use crate::format::{OutputFormat, commit_summary, short_commit_id};
+use crate::impact;
+use crate::perf;
+use crate::progress::Progress;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// What `--replace-text` rules replace their match with by default.
+const REMOVED: &str = "***REMOVED***";
+
+/// A `--replace-text` rule.
+#[derive(Debug)]
+struct Replacement {
+    pattern: Regex,
+    replacement: Vec<u8>,
+    /// Whether `$1` and the like in the replacement refer to groups.
+    expand: bool,
+}
+
+/// Parses `--replace-text` rules, one per line in `git filter-repo`'s
+/// format: `TEXT`, `TEXT==>REPLACEMENT`, or the same with a `literal:` or
+/// `regex:` prefix.
+fn parse_replacements(rules: &str) -> Result<Vec<Replacement>> {
+    let mut replacements = Vec::new();
+    for line in rules.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (pattern, replacement) = line.rsplit_once("==>").unwrap_or((line, REMOVED));
+        let (pattern, expand) = match pattern.strip_prefix("regex:") {
+            Some(pattern) => (
+                Regex::new(pattern).with_context(|| format!("Invalid replacement {line:?}"))?,
+                true,
+            ),
+            None => {
+                let literal = pattern.strip_prefix("literal:").unwrap_or(pattern);
+                (Regex::new(&regex::escape(literal))?, false)
+            }
+        };
+        replacements.push(Replacement {
+            pattern,
+            replacement: replacement.as_bytes().to_vec(),
+            expand,
+        });
+    }
+    Ok(replacements)
+}
+
+struct Filter {
+    removed: Vec<RepoPathBuf>,
+    replacements: Vec<Replacement>,
+}
+
+impl Filter {
+    fn removes(&self, path: &RepoPath) -> bool {
+        self.removed.iter().any(|removed| path.starts_with(removed))
+    }
+
+    /// `content` with the replacements made, or `None` if none matched.
+    fn replace(&self, content: &[u8]) -> Option<Vec<u8>> {
+        let mut content = Cow::Borrowed(content);
+        for rule in &self.replacements {
+            let replaced = if rule.expand {
+                rule.pattern
+                    .replace_all(&content, rule.replacement.as_slice())
+            } else {
+                rule.pattern
+                    .replace_all(&content, NoExpand(&rule.replacement))
+            };
+            if let Cow::Owned(replaced) = replaced {
+                content = Cow::Owned(replaced);
+            }
+        }
+        match content {
+            Cow::Owned(content) => Some(content),
+            Cow::Borrowed(_) => None,
+        }
+    }
+}
+
+/// The filtered version of each file version seen so far, or `None` if no
+/// replacement matched it.
+type FileCache = Mutex<HashMap<FileId, Option<FileId>>>;
+
+async fn replace_file(
+    store: &Store,
+    path: &RepoPath,
+    id: &FileId,
+    filter: &Filter,
+    cache: &FileCache,
+) -> Result<Option<FileId>> {
+    let cached = cache.lock().unwrap().get(id).cloned();
+    if let Some(cached) = cached {
+        return Ok(cached);
+    }
+    let mut content = Vec::new();
+    store
+        .read_file(path, id)
+        .await?
+        .read_to_end(&mut content)
+        .await
+        .with_context(|| format!("Failed to read {}", path.as_internal_file_string()))?;
+    let new_id = match filter.replace(&content) {
+        Some(replaced) => Some(store.write_file(path, &mut replaced.as_slice()).await?),
+        None => None,
+    };
+    cache.lock().unwrap().insert(id.clone(), new_id.clone());
+    Ok(new_id)
+}
+
+async fn filter_tree(
+    repo: &ReadonlyRepo,
+    commit: &Commit,
+    filter: &Filter,
+    cache: &FileCache,
+) -> Result<MergedTreeId> {
+    let tree = commit.tree_async().await?;
+    // Without replacements, only the removed paths need visiting.
+    let entries = {
+        let matcher: Box<dyn Matcher> = if filter.replacements.is_empty() {
+            Box::new(PrefixMatcher::new(&filter.removed))
+        } else {
+            Box::new(EverythingMatcher)
+        };
+        tree.entries_matching(matcher.as_ref()).collect::<Vec<_>>()
+    };
+    let mut builder = MergedTreeBuilder::new(tree.id());
+    for (path, value) in entries {
+        if filter.removes(&path) {
+            builder.set_or_remove(path, Merge::absent());
+            continue;
+        }
+        // Conflicted files are left alone, having no single content.
+        let value = value?;
+        let Some(Some(TreeValue::File {
+            id,
+            executable,
+            copy_id,
+        })) = value.as_resolved()
+        else {
+            continue;
+        };
+        if let Some(new_id) = replace_file(repo.store(), &path, id, filter, cache).await? {
+            let new_value = TreeValue::File {
+                id: new_id,
+                executable: *executable,
+                copy_id: copy_id.clone(),
+            };
+            builder.set_or_remove(path, Merge::normal(new_value));
+        }
+    }
+    Ok(builder.write_tree(repo.store())?)
+}
+
+#[derive(Serialize)]
+struct Rewrite {
+    change_id: String,
+    old_commit_id: String,
+    new_commit_id: String,
+}
+
+pub async fn filter_command(
+    remove_paths: Vec<String>,
+    replace_text: Option<&Path>,
+    revset: String,
+    format: OutputFormat,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let removed: Vec<RepoPathBuf> = remove_paths
+        .iter()
+        .map(|path| helper.parse_file_path(path))
+        .collect::<Result<_>>()?;
+    if removed.iter().any(|path| path.is_root()) {
+        bail!("Refusing to remove every file; --remove-path must name a file or directory");
+    }
+    let replacements = match replace_text {
+        Some(path) => parse_replacements(
+            &std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        )?,
+        None => Vec::new(),
+    };
+    let filter = Arc::new(Filter {
+        removed,
+        replacements,
+    });
+
+    let repo = helper.repo().clone();
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let commits = helper
+        .evaluate_revset(&revset)?
+        .into_iter()
+        .filter(|commit| *commit.id() != root_commit_id)
+        .collect::<Vec<_>>();
+    let cache: Arc<FileCache> = Arc::default();
+    let mut progress = Progress::new("filter", Some(commits.len()));
+    let mut results = futures::stream::iter(commits)
+        .map(|commit| {
+            progress.start_item(&commit_summary(&commit));
+            let repo = repo.clone();
+            let filter = filter.clone();
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                let tree_id = filter_tree(&repo, &commit, &filter, &cache).await;
+                (commit, tree_id)
+            })
+        })
+        .buffered(perf::jobs());
+    let mut trees: HashMap<CommitId, MergedTreeId> = HashMap::new();
+    let mut roots = Vec::new();
+    while let Some(result) = results.next().await {
+        let (commit, tree_id) = result?;
+        let tree_id = tree_id?;
+        if tree_id != *commit.tree_id() {
+            roots.push(commit.id().clone());
+        }
+        trees.insert(commit.id().clone(), tree_id);
+    }
+    drop(results);
+    progress.finish();
+    if roots.is_empty() {
+        status!("Nothing to filter: no revision in {revset} has the paths or text");
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    let mut rewrites: Vec<(Commit, Commit)> = Vec::new();
+    tx.repo_mut()
+        .transform_descendants(roots, async |rewriter| {
+            let old_commit = rewriter.old_commit().clone();
+            // Revisions in the revset keep their filtered snapshot; the
+            // rest are rebased onto them.
+            let new_commit = match trees.get(old_commit.id()) {
+                Some(tree_id) => rewriter.reparent().set_tree_id(tree_id.clone()).write()?,
+                None => rewriter.rebase().await?.write()?,
+            };
+            rewrites.push((old_commit, new_commit));
+            Ok(())
+        })?;
+    helper
+        .finish_transaction(tx, &format!("filter {revset}"))
+        .await?;
+
+    match format {
+        OutputFormat::Text => {
+            status!("Rewrote {} commit(s):", rewrites.len());
+            for (old_commit, new_commit) in &rewrites {
+                println!(
+                    "  {} -> {}",
+                    short_commit_id(old_commit),
+                    commit_summary(new_commit)
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let rewrites = rewrites
+                .iter()
+                .map(|(old_commit, new_commit)| Rewrite {
+                    change_id: new_commit.change_id().reverse_hex(),
+                    old_commit_id: old_commit.id().hex(),
+                    new_commit_id: new_commit.id().hex(),
+                })
+                .collect::<Vec<_>>();
+            let output = serde_json::json!({
+                "commits": rewrites,
+                "operations": impact::take().await?,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replacements() {
+        let filter = Filter {
+            removed: Vec::new(),
+            replacements: parse_replacements(
+                "hunter2\n\
+                 \n\
+                 literal:a.b==>[dot]\n\
+                 regex:token=(\\w)\\w*==>token=${1}xxx\n",
+            )
+            .unwrap(),
+        };
+        assert_eq!(
+            filter.replace(b"pw=hunter2 a.b axb token=secret\n"),
+            Some(b"pw=***REMOVED*** [dot] axb token=sxxx\n".to_vec())
+        );
+        assert_eq!(filter.replace(b"nothing here\n"), None);
+        assert!(parse_replacements("regex:(==>x").is_err());
+    }
+}
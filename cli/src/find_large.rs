@@ -0,0 +1,246 @@
+//! `jjka find-large`: find big files in history.
+//!
+//! Each revision is diffed against its parents, so every file version is
+//! attributed to the revision that introduced it. Sizes come from the git
+//! object headers when the repo is git-backed, so blobs aren't read.
+
+use anyhow::{Context, Result};
+use futures::StreamExt as _;
+use itertools::Itertools;
+use jj_lib::backend::{FileId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::store::Store;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use tokio::io::AsyncReadExt as _;
+
+use crate::format::{OutputFormat, commit_summary, subject};
+use crate::perf;
+use crate::progress::Progress;
+use crate::tree_diff::changed_paths;
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Serialize)]
+struct LargeFile {
+    path: String,
+    size: u64,
+    /// The revision that introduced this version.
+    commit_id: String,
+    change_id: String,
+    subject: String,
+    #[serde(skip)]
+    summary: String,
+}
+
+#[derive(Serialize)]
+struct PathCost {
+    path: String,
+    /// Large versions of the path.
+    versions: usize,
+    total_size: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    threshold: u64,
+    files: Vec<LargeFile>,
+    paths: Vec<PathCost>,
+}
+
+/// Parses a size like `500K`, `1.5M` or `2GiB`; suffixes are powers of 1024.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size {input:?}"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(format!("Invalid size unit {unit:?}; expected K, M or G")),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// The file versions `commit` adds relative to its parents.
+async fn new_files(repo: &ReadonlyRepo, commit: &Commit) -> Result<Vec<(RepoPathBuf, FileId)>> {
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree_async().await?;
+    Ok(changed_paths(&parent_tree, &tree)
+        .await?
+        .into_iter()
+        .filter_map(|change| match change.after.into_resolved() {
+            Ok(Some(TreeValue::File { id, .. })) => Some((change.path, id)),
+            _ => None,
+        })
+        .collect())
+}
+
+async fn file_size(
+    store: &Store,
+    git_repo: Option<&gix::Repository>,
+    path: &RepoPathBuf,
+    id: &FileId,
+) -> Result<u64> {
+    if let Some(git_repo) = git_repo {
+        let oid = gix::ObjectId::try_from(id.as_bytes())?;
+        return Ok(git_repo.find_header(oid)?.size());
+    }
+    let mut content = Vec::new();
+    store
+        .read_file(path, id)
+        .await?
+        .read_to_end(&mut content)
+        .await
+        .with_context(|| format!("Failed to read {}", path.as_internal_file_string()))?;
+    Ok(content.len() as u64)
+}
+
+fn print_report(report: &Report, revset: &str) {
+    println!(
+        "Files of at least {} in {revset}:",
+        format_size(report.threshold)
+    );
+    for file in &report.files {
+        println!(
+            "  {:>10}  {}  {}",
+            format_size(file.size),
+            file.path,
+            file.summary
+        );
+    }
+    println!();
+    println!("Size of the large versions by path:");
+    for path in &report.paths {
+        println!(
+            "  {:>10}  {} ({} version(s))",
+            format_size(path.total_size),
+            path.path,
+            path.versions
+        );
+    }
+    println!();
+    println!("Removing them from history (run in the git repo, then `jj git import`):");
+    println!(
+        "  git filter-repo --invert-paths {}",
+        report
+            .paths
+            .iter()
+            .map(|path| format!("--path '{}'", path.path))
+            .join(" ")
+    );
+}
+
+pub async fn find_large_command(
+    revset: String,
+    threshold: u64,
+    format: OutputFormat,
+) -> Result<()> {
+    let helper = WorkspaceHelper::load()?;
+    let repo = helper.repo().clone();
+    let git_repo = jj_lib::git::get_git_repo(repo.store()).ok();
+    // Oldest first, so each version is attributed to the first revision
+    // that has it.
+    let commits = helper.evaluate_revset(&revset)?;
+    let mut progress = Progress::new("find-large", Some(commits.len()));
+    let mut results = futures::stream::iter(commits)
+        .map(|commit| {
+            progress.start_item(&commit_summary(&commit));
+            let repo = repo.clone();
+            tokio::spawn(async move {
+                let files = new_files(&repo, &commit).await;
+                (commit, files)
+            })
+        })
+        .buffered(perf::jobs());
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    while let Some(result) = results.next().await {
+        let (commit, new) = result?;
+        for (path, id) in new? {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            let size = file_size(repo.store(), git_repo.as_ref(), &path, &id).await?;
+            if size >= threshold {
+                files.push(LargeFile {
+                    path: path.as_internal_file_string().to_string(),
+                    size,
+                    commit_id: commit.id().hex(),
+                    change_id: commit.change_id().reverse_hex(),
+                    subject: subject(&commit).to_string(),
+                    summary: commit_summary(&commit),
+                });
+            }
+        }
+    }
+    drop(results);
+    progress.finish();
+
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    let mut by_path: BTreeMap<&str, PathCost> = BTreeMap::new();
+    for file in &files {
+        let cost = by_path.entry(&file.path).or_insert_with(|| PathCost {
+            path: file.path.clone(),
+            versions: 0,
+            total_size: 0,
+        });
+        cost.versions += 1;
+        cost.total_size += file.size;
+    }
+    let mut paths = by_path.into_values().collect_vec();
+    paths.sort_by_key(|path| std::cmp::Reverse(path.total_size));
+    let report = Report {
+        threshold,
+        files,
+        paths,
+    };
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text if report.files.is_empty() => println!(
+            "No files of at least {} in {revset}",
+            format_size(threshold)
+        ),
+        OutputFormat::Text => print_report(&report, &revset),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("1.5k"), Ok(1536));
+        assert_eq!(parse_size("2MiB"), Ok(2 << 20));
+        assert!(parse_size("3 parsecs").is_err());
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 << 30), "3.0 GiB");
+    }
+}
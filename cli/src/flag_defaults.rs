@@ -0,0 +1,171 @@
+//! Defaults for command options from the `[jjka]` config table.
+//!
+//! `jjka.<command>.<option>` sets an option whenever it isn't passed on the
+//! command line, e.g. `jjka.diff.context = 5` or `jjka.log-search.revisions =
+//! "trunk()..@"`. Subcommands nest, as in `jjka.snapshot.save.keep = true`.
+//! The values are handed to clap as if they had been typed, so they're
+//! checked the same way as the command line.
+
+use crate::exit_code::Failure;
+use crate::workspace;
+use anyhow::{Result, anyhow, bail};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use jj_lib::config::{ConfigGetResultExt as _, ConfigValue};
+use jj_lib::settings::UserSettings;
+use std::ffi::OsString;
+
+/// The arguments a config value stands for; none for a flag set to false.
+fn value_args(long: &str, action: &ArgAction, value: &ConfigValue) -> Result<Vec<String>> {
+    let scalar = |value: &ConfigValue| -> Result<String> {
+        Ok(match value {
+            ConfigValue::String(text) => text.value().clone(),
+            ConfigValue::Integer(number) => number.value().to_string(),
+            ConfigValue::Float(number) => number.value().to_string(),
+            ConfigValue::Boolean(flag) => flag.value().to_string(),
+            _ => bail!("expected a string, number or boolean"),
+        })
+    };
+    Ok(match (action, value) {
+        (ArgAction::SetTrue, ConfigValue::Boolean(flag)) => {
+            if *flag.value() {
+                vec![format!("--{long}")]
+            } else {
+                Vec::new()
+            }
+        }
+        (ArgAction::SetTrue, _) => bail!("expected true or false"),
+        (ArgAction::Append, ConfigValue::Array(values)) => values
+            .iter()
+            .map(|value| Ok(format!("--{long}={}", scalar(value)?)))
+            .collect::<Result<_>>()?,
+        (_, value) => vec![format!("--{long}={}", scalar(value)?)],
+    })
+}
+
+/// The arguments to add for the options of `command`, the subcommand at
+/// `path`, that the config sets and the command line doesn't.
+fn config_args(
+    settings: &UserSettings,
+    command: &Command,
+    path: &[&str],
+    matches: &ArgMatches,
+) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    for arg in command.get_arguments() {
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        if arg.is_positional()
+            || arg.is_global_set()
+            || matches!(
+                matches.value_source(arg.get_id().as_str()),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        {
+            continue;
+        }
+        let name: Vec<&str> = ["jjka"]
+            .into_iter()
+            .chain(path.iter().copied())
+            .chain([long])
+            .collect();
+        let key = name.join(".");
+        let Some(value) = settings.get_value(name.as_slice()).optional()? else {
+            continue;
+        };
+        args.extend(
+            value_args(long, arg.get_action(), &value)
+                .map_err(|err| anyhow!("Invalid {key} in the config: {err}"))?,
+        );
+    }
+    Ok(args)
+}
+
+/// Parses the command line, filling in options the config has defaults for.
+pub fn parse(command: Command) -> Result<ArgMatches> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let matches = command.clone().get_matches_from(&argv);
+
+    let mut path = Vec::new();
+    let mut leaf = (&command, &matches);
+    while let Some((name, sub_matches)) = leaf.1.subcommand() {
+        // Plugins have no options of ours to fill in.
+        let Some(sub_command) = leaf.0.find_subcommand(name) else {
+            return Ok(matches);
+        };
+        path.push(name);
+        leaf = (sub_command, sub_matches);
+    }
+    // A config that doesn't load is reported by the command itself, if it
+    // needs the config at all.
+    let Ok(settings) = workspace::load_settings() else {
+        return Ok(matches);
+    };
+    if path.is_empty() {
+        return Ok(matches);
+    }
+    let args =
+        config_args(&settings, leaf.0, &path, leaf.1).map_err(|err| Failure::Usage.error(err))?;
+    if args.is_empty() {
+        return Ok(matches);
+    }
+
+    // Put the defaults right after the subcommand's name, so they come
+    // before `--` and trailing arguments.
+    let mut position = 1;
+    for name in &path {
+        position += argv[position..]
+            .iter()
+            .position(|arg| arg == name)
+            .expect("clap matched the subcommand")
+            + 1;
+    }
+    let mut argv = argv;
+    argv.splice(position..position, args.iter().map(OsString::from));
+    command.try_get_matches_from(&argv).map_err(|err| {
+        Failure::Usage.error(anyhow!(
+            "Invalid option from the jjka.{} config table: {}",
+            path.join("."),
+            err.render()
+                .to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_args() {
+        let value = |toml: &str| toml.parse::<ConfigValue>().unwrap();
+        assert_eq!(
+            value_args("keep", &ArgAction::SetTrue, &value("true")).unwrap(),
+            ["--keep"]
+        );
+        assert!(
+            value_args("keep", &ArgAction::SetTrue, &value("false"))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(value_args("keep", &ArgAction::SetTrue, &value("\"yes\"")).is_err());
+        assert_eq!(
+            value_args("context", &ArgAction::Set, &value("5")).unwrap(),
+            ["--context=5"]
+        );
+        assert_eq!(
+            value_args(
+                "lines",
+                &ArgAction::Append,
+                &value("[\"a:1-2\", \"b:3-4\"]")
+            )
+            .unwrap(),
+            ["--lines=a:1-2", "--lines=b:3-4"]
+        );
+    }
+}
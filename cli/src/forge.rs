@@ -0,0 +1,150 @@
+//! Links to commits and issues on the forge hosting the repo.
+//!
+//! The web URL is `jjka.forge.url` if set, otherwise derived from the URL
+//! of the `origin` git remote. GitLab URLs get GitLab's `/-/` paths.
+
+use anyhow::Result;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::git;
+use jj_lib::repo::Repo;
+use serde::Deserialize;
+
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Forge {
+    /// The repo's web page, without a trailing slash.
+    pub url: String,
+    is_gitlab: bool,
+}
+
+impl Forge {
+    pub fn new(url: &str) -> Self {
+        let url = url.trim_end_matches('/').to_string();
+        let is_gitlab = url.contains("gitlab");
+        Forge { url, is_gitlab }
+    }
+
+    pub fn commit_url(&self, hex: &str) -> String {
+        if self.is_gitlab {
+            format!("{}/-/commit/{hex}", self.url)
+        } else {
+            format!("{}/commit/{hex}", self.url)
+        }
+    }
+
+    pub fn issue_url(&self, number: &str) -> String {
+        if self.is_gitlab {
+            format!("{}/-/issues/{number}", self.url)
+        } else {
+            format!("{}/issues/{number}", self.url)
+        }
+    }
+}
+
+/// The https URL of the web page for a git remote URL like
+/// `git@github.com:owner/repo.git`.
+fn web_url(remote_url: &str) -> Option<String> {
+    let without_scheme = match remote_url.split_once("://") {
+        Some(("http" | "https" | "ssh" | "git", rest)) => rest,
+        Some(_) => return None,
+        // scp-like: `[user@]host:path`
+        None => remote_url,
+    };
+    let without_user = without_scheme
+        .split_once('@')
+        .map_or(without_scheme, |(_, rest)| rest);
+    let (host, path) = match without_user.split_once('/') {
+        Some((host, path)) if !host.contains(':') || remote_url.contains("://") => (host, path),
+        _ => without_user.split_once(':')?,
+    };
+    // Drop a port, which is for git rather than the web server.
+    let host = host.split(':').next().unwrap();
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some(format!("https://{host}/{path}"))
+}
+
+/// How to reach the forge behind a git remote, from
+/// `[jjka.forge.remotes.<remote>]`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct RemoteConfig {
+    /// `gitlab` or `gerrit`.
+    pub kind: Option<String>,
+    pub url: Option<String>,
+    /// The user name for forges with HTTP basic auth.
+    pub user: Option<String>,
+    /// The environment variable holding the API token.
+    pub token_env: Option<String>,
+}
+
+impl RemoteConfig {
+    /// `kind`, or the forge whose name is in `url`.
+    pub fn kind_for<'a>(&'a self, url: &str) -> Option<&'a str> {
+        self.kind.as_deref().or_else(|| {
+            ["gitlab", "github"]
+                .into_iter()
+                .find(|kind| url.contains(kind))
+        })
+    }
+}
+
+pub fn remote_config(helper: &WorkspaceHelper, remote: &str) -> Result<RemoteConfig> {
+    Ok(helper
+        .settings()
+        .get(["jjka", "forge", "remotes", remote])
+        .optional()?
+        .unwrap_or_default())
+}
+
+/// The forge configured for the repo, if there is one.
+pub fn forge(helper: &WorkspaceHelper) -> Result<Option<Forge>> {
+    if let Some(url) = helper.settings().get_string("jjka.forge.url").optional()? {
+        return Ok(Some(Forge::new(&url)));
+    }
+    Ok(remote_web_url(helper, "origin")?.map(|url| Forge::new(&url)))
+}
+
+/// The web page of the repo at git remote `remote`, if its URL looks like a
+/// forge's.
+pub fn remote_web_url(helper: &WorkspaceHelper, remote: &str) -> Result<Option<String>> {
+    let Ok(git_repo) = git::get_git_repo(helper.repo().store()) else {
+        return Ok(None);
+    };
+    let Ok(remote) = git_repo.find_remote(remote) else {
+        return Ok(None);
+    };
+    let url = remote
+        .url(gix::remote::Direction::Fetch)
+        .map(|url| url.to_bstring().to_string());
+    Ok(url.as_deref().and_then(web_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_url() {
+        for remote in [
+            "git@github.com:tjorvi/jujutsuka.git",
+            "https://github.com/tjorvi/jujutsuka.git",
+            "https://github.com/tjorvi/jujutsuka",
+            "ssh://git@github.com:22/tjorvi/jujutsuka.git",
+        ] {
+            assert_eq!(
+                web_url(remote).as_deref(),
+                Some("https://github.com/tjorvi/jujutsuka"),
+                "{remote}"
+            );
+        }
+        assert_eq!(web_url("/srv/git/repo.git"), None);
+        assert_eq!(
+            Forge::new("https://gitlab.com/group/project/").commit_url("abc"),
+            "https://gitlab.com/group/project/-/commit/abc"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Helpers for printing commits the way jj shows them.
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, FixedOffset};
+use jj_lib::backend::Timestamp;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::settings::UserSettings;
+use std::io::{IsTerminal as _, Write as _};
+use std::sync::OnceLock;
+
+pub use jjka_lib::format::{commit_summary, short_change_id, short_commit_id, subject};
+
+/// Output format for commands that can also emit machine-readable data.
+#[derive(clap::ValueEnum, serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// When to color output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When stdout is a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                (std::io::stdout().is_terminal() || crate::pager::is_active())
+                    && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// The `--color` passed on the command line, if any.
+static COLOR: OnceLock<ColorChoice> = OnceLock::new();
+
+pub fn init_color(choice: Option<ColorChoice>) {
+    if let Some(choice) = choice {
+        let _ = COLOR.set(choice);
+    }
+}
+
+/// Whether to color what goes to stdout, from `--color` or else jj's
+/// `ui.color`.
+pub fn use_color(settings: &UserSettings) -> Result<bool> {
+    let choice = match COLOR.get() {
+        Some(choice) => *choice,
+        None => match settings.get_string("ui.color").optional()?.as_deref() {
+            None | Some("auto") => ColorChoice::Auto,
+            // jj's `debug` also shows the labels; jjka has none to show.
+            Some("always" | "debug") => ColorChoice::Always,
+            Some("never") => ColorChoice::Never,
+            Some(other) => bail!("Invalid ui.color {other:?}; expected auto, always or never"),
+        },
+    };
+    Ok(choice.enabled())
+}
+
+/// Converts a jj timestamp to a date-time in its recorded time zone.
+pub fn to_datetime(timestamp: &Timestamp) -> Option<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(timestamp.tz_offset * 60)?;
+    DateTime::from_timestamp_millis(timestamp.timestamp.0).map(|dt| dt.with_timezone(&offset))
+}
+
+/// Formats a timestamp like jj's default `YYYY-MM-DD HH:MM:SS`.
+pub fn format_timestamp(timestamp: &Timestamp) -> String {
+    match to_datetime(timestamp) {
+        Some(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => "(invalid timestamp)".to_string(),
+    }
+}
+
+/// Prints each distinct path once, in order, followed by a NUL byte, for
+/// `xargs -0` and other tools that can't trust newlines in paths.
+pub fn print_nul_terminated<'a>(paths: impl IntoIterator<Item = &'a str>) -> std::io::Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    let mut stdout = std::io::stdout().lock();
+    for path in paths {
+        if seen.insert(path) {
+            stdout.write_all(path.as_bytes())?;
+            stdout.write_all(b"\0")?;
+        }
+    }
+    stdout.flush()
+}
@@ -0,0 +1,385 @@
+//! `jjka from-git`: translate git habits into jj and jjka commands.
+
+use anyhow::{Context, Result, bail};
+use std::io::{BufRead, Write};
+use std::process::Command;
+
+struct Suggestion {
+    /// Command line, with `<placeholders>` for values to ask for.
+    command: &'static str,
+    note: &'static str,
+}
+
+struct Intent {
+    git: &'static str,
+    /// Lowercase phrases that select this intent.
+    keys: &'static [&'static str],
+    explanation: &'static str,
+    suggestions: &'static [Suggestion],
+}
+
+const INTENTS: &[Intent] = &[
+    Intent {
+        git: "git add -p",
+        keys: &["add -p", "add --patch", "stage", "partial commit"],
+        explanation: "There is no staging area: the working copy is a commit. Split the lines \
+                      you want into their own commit instead.",
+        suggestions: &[
+            Suggestion {
+                command: "jjka hunksplit <path:start-end>",
+                note: "split exact line ranges into a new commit",
+            },
+            Suggestion {
+                command: "jj split -i",
+                note: "pick hunks interactively",
+            },
+        ],
+    },
+    Intent {
+        git: "git commit",
+        keys: &["commit -m", "commit"],
+        explanation: "Your changes are already recorded in the working-copy commit. Describe \
+                      it and start a new one on top.",
+        suggestions: &[Suggestion {
+            command: "jj commit -m <message>",
+            note: "describe @ and start a new working-copy commit",
+        }],
+    },
+    Intent {
+        git: "git commit --amend",
+        keys: &["commit --amend", "amend", "fixup"],
+        explanation: "Move the working-copy changes into the commit below it, or just edit \
+                      the message.",
+        suggestions: &[
+            Suggestion {
+                command: "jj squash",
+                note: "move the changes in @ into @-",
+            },
+            Suggestion {
+                command: "jj describe -r @- -m <message>",
+                note: "rewrite the message of @-",
+            },
+        ],
+    },
+    Intent {
+        git: "git stash",
+        keys: &["stash"],
+        explanation: "Start a new commit on the parent; your changes stay in the old \
+                      working-copy commit, so nothing needs stashing.",
+        suggestions: &[
+            Suggestion {
+                command: "jj new @-",
+                note: "set the changes aside and start fresh",
+            },
+            Suggestion {
+                command: "jj edit <change>",
+                note: "go back to them later (like git stash pop)",
+            },
+        ],
+    },
+    Intent {
+        git: "git rebase -i",
+        keys: &[
+            "rebase -i",
+            "rebase --interactive",
+            "reorder",
+            "squash commits",
+        ],
+        explanation: "Rewrite the stack directly; descendants are rebased automatically.",
+        suggestions: &[
+            Suggestion {
+                command: "jjka tidy --fix",
+                note: "squash fixup!/squash! commits and drop empty ones",
+            },
+            Suggestion {
+                command: "jj rebase -r <change> --insert-before <other>",
+                note: "move one commit",
+            },
+            Suggestion {
+                command: "jj squash --from <change> --into <other>",
+                note: "fold one commit into another",
+            },
+        ],
+    },
+    Intent {
+        git: "git pull --rebase",
+        keys: &["pull", "fetch", "rebase onto main", "update"],
+        explanation: "Fetch, then rebase your stacks onto the new trunk in one go.",
+        suggestions: &[Suggestion {
+            command: "jjka sync",
+            note: "fetch and restack all your mutable stacks",
+        }],
+    },
+    Intent {
+        git: "git push",
+        keys: &["push"],
+        explanation: "Branches are bookmarks; push the ones that moved.",
+        suggestions: &[Suggestion {
+            command: "jj git push",
+            note: "push tracked bookmarks that changed",
+        }],
+    },
+    Intent {
+        git: "git checkout -b / git branch",
+        keys: &["checkout -b", "branch", "switch -c"],
+        explanation: "Commits don't need a branch; create a bookmark when you want to push.",
+        suggestions: &[Suggestion {
+            command: "jj bookmark create <name> -r @",
+            note: "name the working-copy commit",
+        }],
+    },
+    Intent {
+        git: "git checkout / git switch",
+        keys: &["checkout", "switch"],
+        explanation: "Start a new commit on top of the revision you want to work on.",
+        suggestions: &[Suggestion {
+            command: "jj new <revision>",
+            note: "work on top of a revision",
+        }],
+    },
+    Intent {
+        git: "git cherry-pick",
+        keys: &["cherry-pick", "cherry pick", "backport"],
+        explanation: "Copy commits onto a release bookmark, keeping a link to the original.",
+        suggestions: &[
+            Suggestion {
+                command: "jjka backport <revisions> --onto <bookmark>",
+                note: "copy commits onto a release bookmark",
+            },
+            Suggestion {
+                command: "jj duplicate <revision> -d <destination>",
+                note: "copy a commit anywhere",
+            },
+        ],
+    },
+    Intent {
+        git: "git mergetool",
+        keys: &["mergetool", "merge conflict", "conflict"],
+        explanation: "Conflicts are stored in commits, so they can be resolved later and in \
+                      any order.",
+        suggestions: &[
+            Suggestion {
+                command: "jjka resolve",
+                note: "run your merge tool on the conflicts in @",
+            },
+            Suggestion {
+                command: "jjka conflicts",
+                note: "list conflicts across the stack",
+            },
+        ],
+    },
+    Intent {
+        git: "git bisect",
+        keys: &["bisect"],
+        explanation: "Bisect in a temporary workspace without touching your working copy.",
+        suggestions: &[Suggestion {
+            command: "jjka bisect --good <revision> -- sh -c <test command>",
+            note: "find the first bad change automatically",
+        }],
+    },
+    Intent {
+        git: "git reset --hard / git reflog",
+        keys: &["reset", "reflog", "undo"],
+        explanation: "Every command is an operation you can undo.",
+        suggestions: &[
+            Suggestion {
+                command: "jj undo",
+                note: "undo the last operation",
+            },
+            Suggestion {
+                command: "jj op log",
+                note: "see all operations",
+            },
+        ],
+    },
+    Intent {
+        git: "git log",
+        keys: &["log", "history"],
+        explanation: "The default log shows your stacks on top of trunk.",
+        suggestions: &[Suggestion {
+            command: "jj log",
+            note: "show the commit graph",
+        }],
+    },
+];
+
+/// The intent matching a free-form description like "I want git add -p". The
+/// longest matching key wins, so "checkout -b" beats "checkout".
+fn find_intent(query: &str) -> Option<&'static Intent> {
+    let query = query.to_lowercase();
+    INTENTS
+        .iter()
+        .filter_map(|intent| {
+            let key = intent
+                .keys
+                .iter()
+                .filter(|key| query.contains(*key))
+                .max_by_key(|key| key.len())?;
+            Some((key.len(), intent))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, intent)| intent)
+}
+
+/// The `<placeholders>` in a suggested command line, in order.
+fn placeholders(command: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = command;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        found.push(&rest[start..=start + len]);
+        rest = &rest[start + len + 1..];
+    }
+    found
+}
+
+/// The argv for a suggestion, with each placeholder replaced by its value.
+fn command_args(command: &str, values: &[String]) -> Vec<String> {
+    let mut values = values.iter();
+    let mut args = Vec::new();
+    let mut rest = command;
+    while !rest.is_empty() {
+        rest = rest.trim_start();
+        if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            args.push(values.next().cloned().unwrap_or_default());
+            rest = &rest[end..];
+        } else {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            args.push(rest[..end].to_string());
+            rest = &rest[end..];
+        }
+    }
+    args
+}
+
+fn prompt(input: &mut dyn BufRead, question: &str) -> Result<String> {
+    print!("{question}");
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+fn print_intent(intent: &Intent) {
+    println!("Instead of `{}`:", intent.git);
+    println!("  {}", intent.explanation);
+    for (index, suggestion) in intent.suggestions.iter().enumerate() {
+        println!(
+            "  {}. {:<44} # {}",
+            index + 1,
+            suggestion.command,
+            suggestion.note
+        );
+    }
+}
+
+fn run_suggestion(input: &mut dyn BufRead, suggestion: &Suggestion) -> Result<()> {
+    let mut values = Vec::new();
+    for placeholder in placeholders(suggestion.command) {
+        values.push(prompt(input, &format!("{placeholder}: "))?);
+    }
+    let args = command_args(suggestion.command, &values);
+    println!("Running: {}", args.join(" "));
+    let program = if args[0] == "jjka" {
+        std::env::current_exe().context("Failed to find the jjka executable")?
+    } else {
+        args[0].clone().into()
+    };
+    let status = Command::new(&program)
+        .args(&args[1..])
+        .status()
+        .with_context(|| format!("Failed to run {}", args[0]))?;
+    if !status.success() {
+        bail!("{} failed ({status})", args.join(" "));
+    }
+    Ok(())
+}
+
+pub async fn from_git_command(query: Option<String>, run: bool) -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let interactive = query.is_none();
+    let intent = match query {
+        Some(query) => match find_intent(&query) {
+            Some(intent) => intent,
+            None => bail!(
+                "No git workflow matches \"{query}\"; run `jjka from-git` without arguments to see all"
+            ),
+        },
+        None => {
+            println!("What would you do in git?");
+            for (index, intent) in INTENTS.iter().enumerate() {
+                println!("  {:>2}. {}", index + 1, intent.git);
+            }
+            let answer = prompt(&mut input, "Number or description: ")?;
+            let by_number = answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| INTENTS.get(n.wrapping_sub(1)));
+            match by_number.or_else(|| find_intent(&answer)) {
+                Some(intent) => intent,
+                None => bail!("No git workflow matches \"{answer}\""),
+            }
+        }
+    };
+    print_intent(intent);
+    if !run && !interactive {
+        return Ok(());
+    }
+
+    let suggestion = match intent.suggestions {
+        [suggestion] if run => suggestion,
+        suggestions => {
+            let answer = prompt(&mut input, "Run which one? (number, Enter to skip) ")?;
+            if answer.is_empty() {
+                return Ok(());
+            }
+            match answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| suggestions.get(n.wrapping_sub(1)))
+            {
+                Some(suggestion) => suggestion,
+                None => bail!("No suggestion number {answer}"),
+            }
+        }
+    };
+    run_suggestion(&mut input, suggestion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_intent() {
+        let git = |query| find_intent(query).map(|intent| intent.git);
+        assert_eq!(git("I want to do git add -p"), Some("git add -p"));
+        assert_eq!(git("git rebase -i HEAD~3"), Some("git rebase -i"));
+        assert_eq!(
+            git("git checkout -b topic"),
+            Some("git checkout -b / git branch")
+        );
+        assert_eq!(git("git checkout main"), Some("git checkout / git switch"));
+        assert_eq!(git("Stash my work"), Some("git stash"));
+        assert_eq!(git("git commit --amend"), Some("git commit --amend"));
+        assert_eq!(git("git frobnicate"), None);
+    }
+
+    #[test]
+    fn test_command_args() {
+        let command = "jj describe -r @- -m <message>";
+        assert_eq!(placeholders(command), vec!["<message>"]);
+        assert_eq!(
+            command_args(command, &["Fix the parser".to_string()]),
+            vec!["jj", "describe", "-r", "@-", "-m", "Fix the parser"]
+        );
+        assert_eq!(
+            placeholders("jjka backport <revisions> --onto <bookmark>"),
+            vec!["<revisions>", "<bookmark>"]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Snapshotting with jj's `fsmonitor.backend = "watchman"`: Watchman says
+//! which files changed since the last snapshot, and only those are looked
+//! at, instead of every file in the working copy.
+//!
+//! jj-lib only talks to Watchman when it's built with its `watchman`
+//! feature, which jjka isn't, so jjka asks the `watchman` command itself and
+//! keeps its own clock, with the tree it was taken for, in
+//! `.jj/working_copy/jjka-watchman-clock`. Whenever that answer can't be
+//! trusted (no clock yet, the working copy was changed by something else
+//! since, or Watchman restarted or can't be run), the snapshot scans the
+//! whole working copy as it would without a monitor.
+
+use anyhow::{Context, Result, anyhow, bail};
+use jj_lib::backend::MergedTreeId;
+use jj_lib::fsmonitor::FsmonitorSettings;
+use jj_lib::local_working_copy::{TreeState, TreeStateSettings};
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::settings::UserSettings;
+use jj_lib::store::Store;
+use jj_lib::working_copy::SnapshotOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use crate::exit_code::Failure;
+use crate::sigpipe;
+
+const CLOCK_FILE: &str = "jjka-watchman-clock";
+
+/// The filesystem monitors `fsmonitor.backend` can name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    None,
+    Watchman,
+}
+
+pub fn backend(settings: &UserSettings) -> Result<Backend> {
+    match settings.get_string("fsmonitor.backend")?.as_str() {
+        "none" => Ok(Backend::None),
+        "watchman" => Ok(Backend::Watchman),
+        other => Err(Failure::Usage.error(anyhow!(
+            "Unknown fsmonitor.backend \"{other}\"; expected \"none\" or \"watchman\""
+        ))),
+    }
+}
+
+/// Where Watchman's answer to the last query left off.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct SavedClock {
+    clock: String,
+    /// The working copy's tree after that snapshot, in hex, so a change made
+    /// to it without jjka is noticed.
+    tree_ids: Vec<String>,
+}
+
+fn tree_hexes(tree_id: &MergedTreeId) -> Vec<String> {
+    tree_id.to_merge().iter().map(|id| id.hex()).collect()
+}
+
+/// Runs one `watchman -j` command.
+fn watchman(request: &Value) -> Result<Value> {
+    let mut child = Command::new("watchman")
+        .args(["-j", "--no-pretty"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run watchman")?;
+    sigpipe::write_to_child(child.stdin.take().unwrap(), &serde_json::to_vec(request)?)?;
+    let output = child.wait_with_output()?;
+    let response: Value =
+        serde_json::from_slice(&output.stdout).context("watchman printed invalid JSON")?;
+    if let Some(error) = response.get("error").and_then(Value::as_str) {
+        bail!("watchman failed: {error}");
+    }
+    if !output.status.success() {
+        bail!("watchman exited with {}", output.status);
+    }
+    Ok(response)
+}
+
+fn string_field<'a>(response: &'a Value, field: &str) -> Result<&'a str> {
+    response[field]
+        .as_str()
+        .with_context(|| format!("watchman's response has no {field}"))
+}
+
+/// Asks Watchman for the files under `root` changed since `since`. Returns
+/// the new clock, and the changed paths unless every file has to be looked
+/// at.
+fn query(root: &Path, since: Option<&str>) -> Result<(String, Option<Vec<PathBuf>>)> {
+    let watch = watchman(&json!(["watch-project", root]))?;
+    let watch_root = string_field(&watch, "watch")?;
+    let Some(since) = since else {
+        let clock = watchman(&json!(["clock", watch_root]))?;
+        return Ok((string_field(&clock, "clock")?.to_string(), None));
+    };
+    let mut request = json!({
+        "since": since,
+        "fields": ["name"],
+        "empty_on_fresh_instance": true,
+        // Like jj, leave out the repo's own directories.
+        "expression": ["not", ["anyof",
+            ["name", [".git", ".jj"], "wholename"],
+            ["dirname", ".git"],
+            ["dirname", ".jj"],
+        ]],
+    });
+    if let Some(relative_path) = watch.get("relative_path").and_then(Value::as_str) {
+        request["relative_root"] = json!(relative_path);
+    }
+    let result = watchman(&json!(["query", watch_root, request]))?;
+    let clock = string_field(&result, "clock")?.to_string();
+    if result["is_fresh_instance"].as_bool().unwrap_or(false) {
+        return Ok((clock, None));
+    }
+    let files = result["files"]
+        .as_array()
+        .context("watchman's response has no files")?
+        .iter()
+        .filter_map(Value::as_str)
+        .map(PathBuf::from)
+        .collect();
+    Ok((clock, Some(files)))
+}
+
+/// Snapshots the working copy at `workspace_root`, whose tree is
+/// `old_tree_id`, looking only at the files Watchman reports as changed
+/// where it can. Must be called with the working copy locked. Returns the
+/// new tree.
+pub fn snapshot_with_watchman(
+    workspace_root: &Path,
+    old_tree_id: &MergedTreeId,
+    store: &Arc<Store>,
+    settings: &UserSettings,
+    options: &SnapshotOptions,
+) -> Result<MergedTreeId> {
+    let state_path = workspace_root.join(".jj").join("working_copy");
+    let clock_path = state_path.join(CLOCK_FILE);
+    let saved: Option<SavedClock> = std::fs::read(&clock_path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .filter(|saved: &SavedClock| saved.tree_ids == tree_hexes(old_tree_id));
+
+    let mut tree_state_settings = TreeStateSettings::try_from_user_settings(settings)?;
+    tree_state_settings.fsmonitor_settings = FsmonitorSettings::None;
+    let clock = match query(
+        workspace_root,
+        saved.as_ref().map(|saved| saved.clock.as_str()),
+    ) {
+        Ok((clock, changed_files)) => {
+            if let Some(changed_files) = changed_files {
+                tracing::debug!(count = changed_files.len(), "watchman reported changes");
+                // jj-lib's way of being handed the changed files.
+                tree_state_settings.fsmonitor_settings = FsmonitorSettings::Test { changed_files };
+            }
+            Some(clock)
+        }
+        Err(err) => {
+            tracing::warn!("Scanning the whole working copy: {err:#}");
+            None
+        }
+    };
+    let mut tree_state = TreeState::load(
+        store.clone(),
+        workspace_root.to_path_buf(),
+        state_path,
+        &tree_state_settings,
+    )?;
+
+    tree_state.snapshot(options)?;
+    tree_state.save()?;
+    let tree_id = tree_state.current_tree_id().clone();
+    match clock {
+        Some(clock) => {
+            let saved = SavedClock {
+                clock,
+                tree_ids: tree_hexes(&tree_id),
+            };
+            // Without it the next snapshot only scans everything again.
+            let _ = std::fs::write(&clock_path, serde_json::to_vec(&saved)?);
+        }
+        None => {
+            let _ = std::fs::remove_file(&clock_path);
+        }
+    }
+    Ok(tree_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+
+    fn settings(toml: &str) -> UserSettings {
+        let mut config = StackedConfig::with_defaults();
+        config.add_layer(ConfigLayer::parse(ConfigSource::User, toml).unwrap());
+        UserSettings::from_config(config).unwrap()
+    }
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(backend(&settings("")).unwrap(), Backend::None);
+        assert_eq!(
+            backend(&settings("fsmonitor.backend = \"watchman\"")).unwrap(),
+            Backend::Watchman
+        );
+        let err = backend(&settings("fsmonitor.backend = \"inotify\"")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown fsmonitor.backend \"inotify\"; expected \"none\" or \"watchman\""
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! `jjka gerrit`: review a stack on Gerrit.
+//!
+//! `push` gives each commit of the stack a `Change-Id:` trailer made from
+//! its jj change id, so a rewritten commit uploads as a new patch set of
+//! the same Gerrit change, and pushes the stack to `refs/for/<branch>`.
+//! `status` asks the server what each change's review is at.
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Subcommand;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::curl;
+use crate::exit_code::Failure;
+use crate::forge::{remote_config, remote_web_url};
+use crate::format::commit_summary;
+use crate::remote::{push_commit, trunk_branch};
+use crate::submit::stack;
+use crate::trailer::{add_trailer, trailer_value};
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper, current_commit};
+
+#[derive(Subcommand, Debug)]
+pub enum GerritCommand {
+    /// Add Change-Id trailers to the stack and push it for review
+    Push {
+        /// The commits to push, which must form a line
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// The remote Gerrit is at
+        #[arg(long, default_value = "origin")]
+        remote: String,
+
+        /// The branch the changes are for [default: the remote's branch at
+        /// trunk()]
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// Show the Change-Ids without adding them or pushing
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Show the review state of the stack's changes
+    Status {
+        /// The commits to look up
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// The remote Gerrit is at
+        #[arg(long, default_value = "origin")]
+        remote: String,
+    },
+}
+
+/// The Change-Id for `commit` if it doesn't have one: the change id's hex
+/// digits padded to Gerrit's forty, after the "I" Gerrit expects.
+fn new_change_id(commit: &Commit) -> String {
+    format!("I{}6a6a6964", commit.change_id().hex())
+}
+
+/// The Change-Id `commit` goes to Gerrit with.
+fn change_id(commit: &Commit) -> String {
+    trailer_value(commit.description(), "Change-Id")
+        .map_or_else(|| new_change_id(commit), str::to_string)
+}
+
+async fn push(
+    revisions: String,
+    remote: String,
+    branch: Option<String>,
+    dry_run: bool,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+
+    let commits = stack(&helper, &revisions)?;
+    let Some(head) = commits.last() else {
+        status!("Nothing to push in {revisions}");
+        return Ok(());
+    };
+    let Some(branch) = branch.map_or_else(|| trunk_branch(&helper, &remote), |b| Ok(Some(b)))?
+    else {
+        return Err(Failure::Usage.error(anyhow!(
+            "trunk() isn't at a branch of {remote}; pass --branch with the branch to push for"
+        )));
+    };
+    let missing: Vec<&Commit> = commits
+        .iter()
+        .filter(|commit| trailer_value(commit.description(), "Change-Id").is_none())
+        .collect();
+
+    if dry_run {
+        for commit in &commits {
+            let new = if missing.contains(&commit) {
+                " (new)"
+            } else {
+                ""
+            };
+            println!(
+                "Would push {} as {}{new}",
+                commit_summary(commit),
+                change_id(commit)
+            );
+        }
+        println!("to refs/for/{branch} on {remote}");
+        return Ok(());
+    }
+
+    let mut head = head.clone();
+    if !missing.is_empty() {
+        let mut tx = helper.start_transaction();
+        for commit in &missing {
+            let current = current_commit(tx.repo(), commit.change_id())?;
+            let description =
+                add_trailer(current.description(), "Change-Id", &new_change_id(commit));
+            tx.repo_mut()
+                .rewrite_commit(&current)
+                .set_description(description)
+                .write()?;
+            tx.repo_mut().rebase_descendants()?;
+        }
+        head = current_commit(tx.repo(), head.change_id())?;
+        helper
+            .finish_transaction(tx, &format!("add Change-Id trailers to {revisions}"))
+            .await?;
+        status!("Added Change-Id trailers to {} commit(s)", missing.len());
+    }
+    push_commit(&helper, &remote, head.id(), &format!("refs/for/{branch}"))?;
+    println!(
+        "Pushed {} commit(s) to refs/for/{branch} on {remote}",
+        commits.len()
+    );
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct ChangeInfo {
+    #[serde(rename = "_number")]
+    number: u64,
+    project: String,
+    status: String,
+    #[serde(default)]
+    current_revision: Option<String>,
+    #[serde(default)]
+    revisions: BTreeMap<String, RevisionInfo>,
+    #[serde(default)]
+    labels: BTreeMap<String, LabelInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RevisionInfo {
+    #[serde(rename = "_number")]
+    number: u64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct LabelInfo {
+    #[serde(default)]
+    all: Vec<ApprovalInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApprovalInfo {
+    #[serde(default)]
+    value: i32,
+}
+
+/// Each label's strongest vote, like "Code-Review+2 Verified-1": the
+/// lowest if anyone voted against, since that blocks submitting.
+fn votes(labels: &BTreeMap<String, LabelInfo>) -> String {
+    labels
+        .iter()
+        .filter_map(|(name, label)| {
+            let values = label.all.iter().map(|approval| approval.value);
+            let vote = match values.clone().min() {
+                Some(min) if min < 0 => min,
+                _ => values.max().filter(|&max| max > 0)?,
+            };
+            Some(format!("{name}{vote:+}"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct Gerrit {
+    /// The server's URL, without a trailing slash.
+    url: String,
+    /// curl config lines with the HTTP credentials, if there are any.
+    auth: Option<String>,
+}
+
+impl Gerrit {
+    fn new(helper: &WorkspaceHelper, remote: &str) -> Result<Self> {
+        let config = remote_config(helper, remote)?;
+        if let Some(kind) = config.kind.as_deref().filter(|&kind| kind != "gerrit") {
+            bail!("jjka.forge.remotes.{remote}.kind is \"{kind}\", not \"gerrit\"");
+        }
+        // A remote's path is the project's, below the server's URL.
+        let derived = remote_web_url(helper, remote)?
+            .map(|url| url.splitn(4, '/').take(3).collect::<Vec<_>>().join("/"));
+        let Some(url) = config.url.or(derived) else {
+            return Err(Failure::Precondition.error(anyhow!(
+                "Can't tell the URL of the Gerrit server of remote {remote}; set \
+                 jjka.forge.remotes.{remote}.url"
+            )));
+        };
+        let token_env = config
+            .token_env
+            .as_deref()
+            .unwrap_or("GERRIT_HTTP_PASSWORD");
+        let auth = match (config.user, std::env::var(token_env)) {
+            (Some(user), Ok(password)) => Some(format!(
+                "user = {}\n",
+                curl::quote(&format!("{user}:{password}"))
+            )),
+            _ => None,
+        };
+        Ok(Gerrit {
+            url: url.trim_end_matches('/').to_string(),
+            auth,
+        })
+    }
+
+    /// The change with the Change-Id `id`, if the server has one.
+    fn change(&self, id: &str) -> Result<Option<ChangeInfo>> {
+        // Authenticated requests go to /a/.
+        let prefix = if self.auth.is_some() { "/a" } else { "" };
+        let url = format!(
+            "{}{prefix}/changes/?q=change:{id}&o=CURRENT_REVISION&o=DETAILED_LABELS",
+            self.url
+        );
+        let response = curl::request("GET", &url, self.auth.as_deref().unwrap_or(""), None::<&()>)
+            .context("Failed to query Gerrit")?;
+        if !response.ok {
+            let body = String::from_utf8_lossy(&response.body);
+            let message = if body.trim().is_empty() {
+                response.error
+            } else {
+                body.trim().to_string()
+            };
+            bail!("Gerrit GET {url} failed: {message}");
+        }
+        // Gerrit guards its JSON against being run as a script.
+        let body = response
+            .body
+            .strip_prefix(b")]}'")
+            .unwrap_or(&response.body);
+        let changes: Vec<ChangeInfo> = serde_json::from_slice(body)
+            .with_context(|| format!("Gerrit GET {url} returned unexpected JSON"))?;
+        Ok(changes.into_iter().next())
+    }
+}
+
+async fn review_status(revisions: String, remote: String) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    let commits = stack(&helper, &revisions)?;
+    let gerrit = Gerrit::new(&helper, &remote)?;
+
+    println!("Review state on {}:", gerrit.url);
+    for commit in &commits {
+        let summary = commit_summary(commit);
+        let Some(id) = trailer_value(commit.description(), "Change-Id") else {
+            println!("  {summary}: no Change-Id; run `jjka gerrit push`");
+            continue;
+        };
+        let Some(change) = gerrit.change(id)? else {
+            println!("  {summary}: not uploaded yet");
+            continue;
+        };
+        let patch_set = change
+            .current_revision
+            .as_ref()
+            .and_then(|revision| change.revisions.get(revision))
+            .map_or(0, |revision| revision.number);
+        let outdated = if change.current_revision.as_deref() == Some(&commit.id().hex()) {
+            ""
+        } else {
+            ", outdated"
+        };
+        let votes = match votes(&change.labels) {
+            votes if votes.is_empty() => "no votes".to_string(),
+            votes => votes,
+        };
+        println!(
+            "  {summary} -> {} {}, patch set {patch_set}{outdated}, {votes} {}/c/{}/+/{}",
+            change.number, change.status, gerrit.url, change.project, change.number
+        );
+    }
+    Ok(())
+}
+
+pub async fn gerrit_command(command: GerritCommand) -> Result<()> {
+    match command {
+        GerritCommand::Push {
+            revisions,
+            remote,
+            branch,
+            dry_run,
+            policy,
+        } => push(revisions, remote, branch, dry_run, policy).await,
+        GerritCommand::Status { revisions, remote } => review_status(revisions, remote).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_votes() {
+        let label = |values: &[i32]| LabelInfo {
+            all: values.iter().map(|&value| ApprovalInfo { value }).collect(),
+        };
+        let labels = BTreeMap::from([
+            ("Code-Review".to_string(), label(&[2, -1, 0])),
+            ("Verified".to_string(), label(&[1])),
+            ("Quality".to_string(), label(&[0])),
+        ]);
+        assert_eq!(votes(&labels), "Code-Review-1 Verified+1");
+        assert_eq!(votes(&BTreeMap::new()), "");
+    }
+}
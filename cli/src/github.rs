@@ -0,0 +1,235 @@
+//! A small client for the GitHub REST API, for `jjka stack`, `jjka land` and
+//! `jjka backport`.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::curl;
+
+/// A check run on a commit, as GitHub's Checks API reports it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CheckRun {
+    /// "queued", "in_progress" or "completed".
+    pub status: String,
+    /// How a completed run ended, like "success" or "failure".
+    #[serde(default)]
+    pub conclusion: Option<String>,
+}
+
+/// The API URL of the repo whose web page is `repo_url`, like
+/// `https://github.com/owner/repo`. GitHub Enterprise serves the API under
+/// `/api/v3` of its own host.
+fn repo_api_url(repo_url: &str) -> Result<String> {
+    let (scheme, rest) = repo_url
+        .split_once("://")
+        .ok_or_else(|| anyhow!("{repo_url} isn't a GitHub repo URL"))?;
+    let (host, path) = rest
+        .trim_end_matches('/')
+        .split_once('/')
+        .ok_or_else(|| anyhow!("{repo_url} doesn't name a GitHub repo"))?;
+    let path = path.trim_end_matches(".git");
+    Ok(match host {
+        "github.com" | "www.github.com" => format!("https://api.github.com/repos/{path}"),
+        _ => format!("{scheme}://{host}/api/v3/repos/{path}"),
+    })
+}
+
+/// An open pull request, as `land` looks it up and `backport` opens it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PullRequest {
+    pub number: u64,
+    pub html_url: String,
+}
+
+/// A review of a pull request.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PullRequestReview {
+    pub user: ReviewUser,
+    /// "APPROVED", "CHANGES_REQUESTED", "COMMENTED" or "DISMISSED".
+    pub state: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReviewUser {
+    pub login: String,
+}
+
+/// Whether `reviews`, oldest first, approve a pull request: someone's
+/// latest verdict is an approval and nobody's is a change request.
+/// Comments don't change a reviewer's verdict.
+fn approved(reviews: &[PullRequestReview]) -> bool {
+    let mut verdicts = HashMap::new();
+    for review in reviews {
+        if review.state != "COMMENTED" {
+            verdicts.insert(review.user.login.as_str(), review.state.as_str());
+        }
+    }
+    verdicts.values().any(|state| *state == "APPROVED")
+        && !verdicts.values().any(|state| *state == "CHANGES_REQUESTED")
+}
+
+pub struct GitHub {
+    /// The repo's API URL, without a trailing slash.
+    api: String,
+    /// The repo's owner, which qualifies branch names in searches.
+    owner: String,
+    /// Public repos can be read without one.
+    token: Option<String>,
+}
+
+impl GitHub {
+    pub fn new(repo_url: &str, token: Option<String>) -> Result<Self> {
+        let api = repo_api_url(repo_url)?;
+        let owner = api.rsplit('/').nth(1).unwrap_or_default().to_string();
+        Ok(GitHub { api, owner, token })
+    }
+
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<T> {
+        let url = format!("{}{path}", self.api);
+        let mut config = format!(
+            "header = {}\n",
+            curl::quote("Accept: application/vnd.github+json")
+        );
+        if let Some(token) = &self.token {
+            config += &format!(
+                "header = {}\n",
+                curl::quote(&format!("Authorization: Bearer {token}"))
+            );
+        }
+        let response =
+            curl::request(method, &url, &config, body).context("Failed to call the GitHub API")?;
+        if !response.ok {
+            let message = serde_json::from_slice::<serde_json::Value>(&response.body)
+                .ok()
+                .and_then(|json| Some(json.get("message")?.as_str()?.to_string()))
+                .unwrap_or(response.error);
+            bail!("GitHub {method} {url} failed: {message}");
+        }
+        serde_json::from_slice(&response.body)
+            .with_context(|| format!("GitHub {method} {url} returned unexpected JSON"))
+    }
+
+    /// The check runs on the commit `sha`.
+    pub fn check_runs(&self, sha: &str) -> Result<Vec<CheckRun>> {
+        #[derive(Deserialize)]
+        struct CheckRuns {
+            check_runs: Vec<CheckRun>,
+        }
+        let runs: CheckRuns = self.request(
+            "GET",
+            &format!("/commits/{sha}/check-runs?per_page=100"),
+            None::<&()>,
+        )?;
+        Ok(runs.check_runs)
+    }
+
+    /// The open pull request from `branch` of the repo itself, if there is
+    /// one.
+    pub fn find_open(&self, branch: &str) -> Result<Option<PullRequest>> {
+        let requests: Vec<PullRequest> = self.request(
+            "GET",
+            &format!("/pulls?state=open&head={}:{branch}", self.owner),
+            None::<&()>,
+        )?;
+        Ok(requests.into_iter().next())
+    }
+
+    /// Opens a pull request from `head`, a branch of the repo itself, into
+    /// `base`.
+    pub fn create(&self, head: &str, base: &str, title: &str, body: &str) -> Result<PullRequest> {
+        #[derive(Serialize)]
+        struct Create<'a> {
+            head: &'a str,
+            base: &'a str,
+            title: &'a str,
+            body: &'a str,
+        }
+        self.request(
+            "POST",
+            "/pulls",
+            Some(&Create {
+                head,
+                base,
+                title,
+                body,
+            }),
+        )
+    }
+
+    /// Whether the reviews of pull request `number` approve it.
+    pub fn approved(&self, number: u64) -> Result<bool> {
+        let reviews: Vec<PullRequestReview> = self.request(
+            "GET",
+            &format!("/pulls/{number}/reviews?per_page=100"),
+            None::<&()>,
+        )?;
+        Ok(approved(&reviews))
+    }
+
+    /// Merges pull request `number`, as long as its head is still `sha`.
+    pub fn merge(&self, number: u64, sha: &str) -> Result<()> {
+        #[derive(Serialize)]
+        struct Merge<'a> {
+            sha: &'a str,
+        }
+        let _: serde_json::Value = self.request(
+            "PUT",
+            &format!("/pulls/{number}/merge"),
+            Some(&Merge { sha }),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_api_url() {
+        assert_eq!(
+            repo_api_url("https://github.com/tjorvi/jujutsuka").unwrap(),
+            "https://api.github.com/repos/tjorvi/jujutsuka"
+        );
+        assert_eq!(
+            repo_api_url("https://github.example.com/team/app.git").unwrap(),
+            "https://github.example.com/api/v3/repos/team/app"
+        );
+        assert!(repo_api_url("github.com/owner").is_err());
+    }
+
+    #[test]
+    fn test_approved() {
+        let review = |login: &str, state: &str| PullRequestReview {
+            user: ReviewUser {
+                login: login.to_string(),
+            },
+            state: state.to_string(),
+        };
+        assert!(!approved(&[]));
+        assert!(approved(&[
+            review("ana", "APPROVED"),
+            review("ana", "COMMENTED")
+        ]));
+        assert!(!approved(&[
+            review("ana", "APPROVED"),
+            review("bo", "CHANGES_REQUESTED")
+        ]));
+        assert!(approved(&[
+            review("bo", "CHANGES_REQUESTED"),
+            review("ana", "APPROVED"),
+            review("bo", "APPROVED")
+        ]));
+        assert!(!approved(&[
+            review("ana", "APPROVED"),
+            review("ana", "DISMISSED")
+        ]));
+    }
+}
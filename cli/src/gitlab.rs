@@ -0,0 +1,206 @@
+//! A small client for the GitLab REST API, for `jjka submit`, `jjka land` and
+//! `jjka backport`.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::curl;
+
+/// An open merge request, with the fields `submit` compares and prints.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MergeRequest {
+    pub iid: u64,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub target_branch: String,
+    pub web_url: String,
+}
+
+/// What a merge request should say and where it should merge to.
+#[derive(Serialize, Debug)]
+pub struct MergeRequestFields<'a> {
+    pub target_branch: &'a str,
+    pub title: &'a str,
+    pub description: &'a str,
+}
+
+impl MergeRequestFields<'_> {
+    /// Whether `request` already says this.
+    pub fn matches(&self, request: &MergeRequest) -> bool {
+        request.target_branch == self.target_branch
+            && request.title == self.title
+            && request.description.as_deref().unwrap_or("") == self.description
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, as GitLab wants
+/// for a project path or branch name in a URL.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The API URL of the project whose web page is `project_url`, like
+/// `https://gitlab.com/group/app`.
+fn project_api_url(project_url: &str) -> Result<String> {
+    let (scheme, rest) = project_url
+        .split_once("://")
+        .ok_or_else(|| anyhow!("{project_url} isn't a GitLab project URL"))?;
+    let (host, path) = rest
+        .trim_end_matches('/')
+        .split_once('/')
+        .ok_or_else(|| anyhow!("{project_url} doesn't name a GitLab project"))?;
+    Ok(format!(
+        "{scheme}://{host}/api/v4/projects/{}",
+        encode(path.trim_end_matches(".git"))
+    ))
+}
+
+pub struct GitLab {
+    /// The project's API URL, without a trailing slash.
+    api: String,
+    token: String,
+}
+
+impl GitLab {
+    pub fn new(project_url: &str, token: String) -> Result<Self> {
+        Ok(GitLab {
+            api: project_api_url(project_url)?,
+            token,
+        })
+    }
+
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<T> {
+        let url = format!("{}{path}", self.api);
+        let config = format!(
+            "header = {}\n",
+            curl::quote(&format!("PRIVATE-TOKEN: {}", self.token))
+        );
+        let response =
+            curl::request(method, &url, &config, body).context("Failed to call the GitLab API")?;
+        if !response.ok {
+            let message = serde_json::from_slice::<serde_json::Value>(&response.body)
+                .ok()
+                .and_then(|json| {
+                    let message = json.get("message").or_else(|| json.get("error"))?;
+                    Some(match message.as_str() {
+                        Some(message) => message.to_string(),
+                        None => message.to_string(),
+                    })
+                })
+                .unwrap_or(response.error);
+            bail!("GitLab {method} {url} failed: {message}");
+        }
+        serde_json::from_slice(&response.body)
+            .with_context(|| format!("GitLab {method} {url} returned unexpected JSON"))
+    }
+
+    /// The open merge request from `source_branch`, if there is one.
+    pub fn find_open(&self, source_branch: &str) -> Result<Option<MergeRequest>> {
+        let requests: Vec<MergeRequest> = self.request(
+            "GET",
+            &format!(
+                "/merge_requests?state=opened&source_branch={}",
+                encode(source_branch)
+            ),
+            None::<&()>,
+        )?;
+        Ok(requests.into_iter().next())
+    }
+
+    pub fn create(&self, source_branch: &str, fields: &MergeRequestFields) -> Result<MergeRequest> {
+        #[derive(Serialize)]
+        struct Create<'a> {
+            source_branch: &'a str,
+            #[serde(flatten)]
+            fields: &'a MergeRequestFields<'a>,
+        }
+        self.request(
+            "POST",
+            "/merge_requests",
+            Some(&Create {
+                source_branch,
+                fields,
+            }),
+        )
+    }
+
+    pub fn update(&self, iid: u64, fields: &MergeRequestFields) -> Result<MergeRequest> {
+        self.request("PUT", &format!("/merge_requests/{iid}"), Some(fields))
+    }
+
+    /// Whether merge request `iid` has the approvals it needs.
+    pub fn approved(&self, iid: u64) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct Approvals {
+            approved: bool,
+        }
+        let approvals: Approvals = self.request(
+            "GET",
+            &format!("/merge_requests/{iid}/approvals"),
+            None::<&()>,
+        )?;
+        Ok(approvals.approved)
+    }
+
+    /// Merges merge request `iid`, as long as its head is still `sha`.
+    pub fn merge(&self, iid: u64, sha: &str) -> Result<MergeRequest> {
+        #[derive(Serialize)]
+        struct Merge<'a> {
+            sha: &'a str,
+        }
+        self.request(
+            "PUT",
+            &format!("/merge_requests/{iid}/merge"),
+            Some(&Merge { sha }),
+        )
+    }
+
+    /// The status of the newest pipeline for the commit `sha`, like
+    /// "success" or "running", if one ran.
+    pub fn pipeline_status(&self, sha: &str) -> Result<Option<String>> {
+        #[derive(Deserialize)]
+        struct Pipeline {
+            status: String,
+        }
+        let pipelines: Vec<Pipeline> = self.request(
+            "GET",
+            &format!("/pipelines?sha={sha}&order_by=id&sort=desc&per_page=1"),
+            None::<&()>,
+        )?;
+        Ok(pipelines.into_iter().next().map(|pipeline| pipeline.status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_api_url() {
+        assert_eq!(
+            project_api_url("https://gitlab.com/group/sub/app").unwrap(),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fapp"
+        );
+        assert_eq!(
+            project_api_url("http://gitlab.internal:8080/team/app.git/").unwrap(),
+            "http://gitlab.internal:8080/api/v4/projects/team%2Fapp"
+        );
+        assert!(project_api_url("https://gitlab.com").is_err());
+        assert_eq!(encode("jjka/abc"), "jjka%2Fabc");
+    }
+}
@@ -0,0 +1,216 @@
+//! `jjka grep`: search the files of every revision in a revset.
+//!
+//! The revisions' trees are walked up to `--jobs` at a time, and every
+//! distinct file version is then read and searched once, however many
+//! revisions share it.
+
+use anyhow::{Result, bail};
+use futures::StreamExt as _;
+use jj_lib::backend::{FileId, TreeValue};
+use jj_lib::matchers::PrefixMatcher;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPathBuf;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt as _;
+
+use crate::format::{OutputFormat, print_nul_terminated, short_change_id};
+use crate::perf;
+use crate::progress::Progress;
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LineMatch {
+    line: usize,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct Match<'a> {
+    commit_id: String,
+    change_id: String,
+    path: &'a str,
+    line: usize,
+    text: &'a str,
+}
+
+/// The lines of `content` that match, or none if it looks binary.
+fn search(regex: &Regex, content: &[u8]) -> Vec<LineMatch> {
+    if content.contains(&0) {
+        return Vec::new();
+    }
+    content
+        .split_inclusive(|b| *b == b'\n')
+        .zip(1..)
+        .filter_map(|(line, number)| {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line));
+            regex.is_match(&line).then(|| LineMatch {
+                line: number,
+                text: line.into_owned(),
+            })
+        })
+        .collect()
+}
+
+pub async fn grep_command(
+    pattern: String,
+    revset: String,
+    paths: Vec<String>,
+    ignore_case: bool,
+    fixed_strings: bool,
+    null: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let regex = RegexBuilder::new(&if fixed_strings {
+        regex::escape(&pattern)
+    } else {
+        pattern.clone()
+    })
+    .case_insensitive(ignore_case)
+    .build()?;
+    let helper = WorkspaceHelper::load()?;
+    let repo = helper.repo().clone();
+    let mut prefixes: Vec<RepoPathBuf> = paths
+        .iter()
+        .map(|path| helper.parse_file_path(path))
+        .collect::<Result<_>>()?;
+    if prefixes.is_empty() {
+        prefixes.push(RepoPathBuf::root());
+    }
+    let matcher = Arc::new(PrefixMatcher::new(&prefixes));
+
+    // Newest first, like `jj log`.
+    let mut commits = helper.evaluate_revset(&revset)?;
+    commits.reverse();
+    let mut walks = futures::stream::iter(commits.clone())
+        .map(|commit| {
+            let matcher = matcher.clone();
+            tokio::spawn(async move {
+                let tree = commit.tree_async().await?;
+                let mut files = Vec::new();
+                for (path, value) in tree.entries_matching(matcher.as_ref()) {
+                    // Conflicted files have no single content to search.
+                    if let Some(Some(TreeValue::File { id, .. })) = value?.as_resolved() {
+                        files.push((path, id.clone()));
+                    }
+                }
+                anyhow::Ok(files)
+            })
+        })
+        .buffered(perf::jobs());
+    let mut files: Vec<Vec<(RepoPathBuf, FileId)>> = Vec::new();
+    let mut to_search: HashMap<FileId, RepoPathBuf> = HashMap::new();
+    while let Some(result) = walks.next().await {
+        let commit_files = result??;
+        for (path, id) in &commit_files {
+            to_search.entry(id.clone()).or_insert_with(|| path.clone());
+        }
+        files.push(commit_files);
+    }
+
+    let mut progress = Progress::new("grep", Some(to_search.len()));
+    let regex = Arc::new(regex);
+    let mut results = futures::stream::iter(to_search)
+        .map(|(id, path)| {
+            progress.start_item(path.as_internal_file_string());
+            let store = repo.store().clone();
+            let regex = regex.clone();
+            tokio::spawn(async move {
+                let mut content = Vec::new();
+                store
+                    .read_file(&path, &id)
+                    .await?
+                    .read_to_end(&mut content)
+                    .await?;
+                anyhow::Ok((id, search(&regex, &content)))
+            })
+        })
+        .buffer_unordered(perf::jobs());
+    let mut matches: HashMap<FileId, Vec<LineMatch>> = HashMap::new();
+    while let Some(result) = results.next().await {
+        let (id, lines) = result??;
+        if !lines.is_empty() {
+            matches.insert(id, lines);
+        }
+    }
+    drop(results);
+    progress.finish();
+
+    let mut found = Vec::new();
+    for (commit, commit_files) in commits.iter().zip(&files) {
+        for (path, id) in commit_files {
+            for line in matches.get(id).into_iter().flatten() {
+                found.push((commit, path, line));
+            }
+        }
+    }
+    match format {
+        OutputFormat::Text if null => {
+            print_nul_terminated(
+                found
+                    .iter()
+                    .map(|(_, path, _)| path.as_internal_file_string()),
+            )?;
+        }
+        OutputFormat::Text => {
+            for (commit, path, line) in &found {
+                println!(
+                    "{} {}:{}:{}",
+                    short_change_id(commit),
+                    path.as_internal_file_string(),
+                    line.line,
+                    line.text
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let found: Vec<Match> = found
+                .iter()
+                .map(|(commit, path, line)| Match {
+                    commit_id: commit.id().hex(),
+                    change_id: commit.change_id().reverse_hex(),
+                    path: path.as_internal_file_string(),
+                    line: line.line,
+                    text: &line.text,
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&found)?);
+        }
+    }
+    if found.is_empty() {
+        bail!("No matches for {pattern} in {revset}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let regex = Regex::new("fo+|^$").unwrap();
+        assert_eq!(
+            search(&regex, b"foo\r\nbar\n\nxfoo\n"),
+            [
+                LineMatch {
+                    line: 1,
+                    text: "foo".to_string()
+                },
+                LineMatch {
+                    line: 3,
+                    text: String::new()
+                },
+                LineMatch {
+                    line: 4,
+                    text: "xfoo".to_string()
+                },
+            ]
+        );
+        assert_eq!(search(&regex, b"foo\0"), []);
+    }
+}
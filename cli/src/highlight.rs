@@ -0,0 +1,478 @@
+//! Syntax highlighting for diff lines.
+//!
+//! A small lexer recognizes the keywords, strings, comments and numbers of
+//! common languages, picked by file extension. Diffs show lines out of
+//! context, so each line is highlighted on its own: a block comment or
+//! string that spans lines is only recognized on the line it starts.
+//!
+//! The colors come from the `jjka.diff.theme` setting: `dark` (the default),
+//! `light` or `none`.
+//!
+//! syntect and tree-sitter would know more languages, but neither crate is
+//! available to this offline build. Callers only see [`language_for_path`]
+//! and [`Theme`], so either could replace the lexer behind them.
+
+use anyhow::{Result, bail};
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::settings::UserSettings;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// `'` starts a string only when it closes right after one character or
+    /// escape, as in Rust where it also starts lifetimes.
+    short_single_quotes: bool,
+}
+
+const C_QUOTES: &[char] = &['"', '\''];
+const SCRIPT_QUOTES: &[char] = &['"', '\'', '`'];
+
+static LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: C_QUOTES,
+        short_single_quotes: true,
+    },
+    Language {
+        extensions: &["py", "pyi"],
+        keywords: &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+            "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+            "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+            "return", "try", "while", "with", "yield",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_QUOTES,
+        short_single_quotes: false,
+    },
+    Language {
+        extensions: &["js", "jsx", "mjs", "cjs", "ts", "tsx"],
+        keywords: &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "from",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "interface",
+            "let",
+            "new",
+            "null",
+            "return",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "type",
+            "typeof",
+            "undefined",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: SCRIPT_QUOTES,
+        short_single_quotes: false,
+    },
+    Language {
+        extensions: &["go"],
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "fallthrough",
+            "false",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: SCRIPT_QUOTES,
+        short_single_quotes: false,
+    },
+    Language {
+        extensions: &[
+            "c", "h", "cc", "cpp", "cxx", "hh", "hpp", "java", "kt", "cs", "swift",
+        ],
+        keywords: &[
+            "auto",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "enum",
+            "extends",
+            "false",
+            "final",
+            "for",
+            "fun",
+            "func",
+            "if",
+            "implements",
+            "import",
+            "include",
+            "inline",
+            "namespace",
+            "new",
+            "null",
+            "nullptr",
+            "package",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typedef",
+            "union",
+            "using",
+            "val",
+            "var",
+            "virtual",
+            "void",
+            "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: C_QUOTES,
+        short_single_quotes: false,
+    },
+    Language {
+        extensions: &["sh", "bash", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "until", "while",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_QUOTES,
+        short_single_quotes: false,
+    },
+    Language {
+        extensions: &["rb"],
+        keywords: &[
+            "begin", "class", "def", "do", "else", "elsif", "end", "ensure", "false", "if",
+            "module", "nil", "require", "rescue", "return", "self", "true", "unless", "until",
+            "when", "while", "yield",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_QUOTES,
+        short_single_quotes: false,
+    },
+    Language {
+        extensions: &["toml", "yaml", "yml", "ini", "cfg"],
+        keywords: &["false", "true", "null"],
+        line_comments: &["#", ";"],
+        block_comment: None,
+        quotes: C_QUOTES,
+        short_single_quotes: false,
+    },
+];
+
+/// The language of a file, by its extension.
+pub fn language_for_path(path: &str) -> Option<&'static Language> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let (_, extension) = file_name.rsplit_once('.')?;
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension))
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The end of a string starting with `quote` at `start`, past the closing
+/// quote, or the end of the line if it isn't closed.
+fn string_end(line: &str, start: usize, quote: char) -> usize {
+    let mut chars = line[start + 1..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return start + 1 + offset + c.len_utf8();
+        }
+    }
+    line.len()
+}
+
+/// Whether a `'` at `start` opens a one-character literal like `'a'` or
+/// `'\n'` rather than a lifetime.
+fn is_char_literal(line: &str, start: usize) -> bool {
+    let rest = &line[start + 1..];
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some('\\') => true,
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
+
+/// Splits `line` into consecutive tokens that cover all of it.
+pub fn tokenize(language: &Language, line: &str) -> Vec<(TokenKind, Range<usize>)> {
+    let mut tokens: Vec<(TokenKind, Range<usize>)> = Vec::new();
+    let mut push = |kind: TokenKind, range: Range<usize>| match tokens.last_mut() {
+        Some((last_kind, last)) if *last_kind == kind && last.end == range.start => {
+            last.end = range.end;
+        }
+        _ => tokens.push((kind, range)),
+    };
+    let mut pos = 0;
+    while pos < line.len() {
+        let rest = &line[pos..];
+        let c = rest.chars().next().unwrap();
+        if language
+            .line_comments
+            .iter()
+            .any(|prefix| rest.starts_with(prefix))
+        {
+            push(TokenKind::Comment, pos..line.len());
+            break;
+        }
+        if let Some((open, close)) = language.block_comment
+            && rest.starts_with(open)
+        {
+            let end = rest[open.len()..]
+                .find(close)
+                .map_or(line.len(), |offset| pos + open.len() + offset + close.len());
+            push(TokenKind::Comment, pos..end);
+            pos = end;
+            continue;
+        }
+        let is_string = language.quotes.contains(&c)
+            && !(c == '\'' && language.short_single_quotes && !is_char_literal(line, pos));
+        let end = if is_string {
+            let end = string_end(line, pos, c);
+            push(TokenKind::String, pos..end);
+            end
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !is_ident_char(c) && c != '.')
+                .map_or(line.len(), |offset| pos + offset);
+            push(TokenKind::Number, pos..end);
+            end
+        } else if is_ident_char(c) {
+            let end = rest
+                .find(|c: char| !is_ident_char(c))
+                .map_or(line.len(), |offset| pos + offset);
+            let kind = if language.keywords.contains(&&line[pos..end]) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Plain
+            };
+            push(kind, pos..end);
+            end
+        } else {
+            push(TokenKind::Plain, pos..pos + c.len_utf8());
+            pos + c.len_utf8()
+        };
+        pos = end;
+    }
+    tokens
+}
+
+/// The ANSI colors of each kind of token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    keyword: &'static str,
+    string: &'static str,
+    comment: &'static str,
+    number: &'static str,
+}
+
+const DARK: Theme = Theme {
+    keyword: "\x1b[35m",
+    string: "\x1b[33m",
+    comment: "\x1b[90m",
+    number: "\x1b[36m",
+};
+
+const LIGHT: Theme = Theme {
+    keyword: "\x1b[34m",
+    string: "\x1b[35m",
+    comment: "\x1b[2m",
+    number: "\x1b[36m",
+};
+
+impl Theme {
+    /// The theme named by `jjka.diff.theme`, or `None` to not highlight.
+    pub fn from_settings(settings: &UserSettings) -> Result<Option<Theme>> {
+        let name = settings
+            .get_string("jjka.diff.theme")
+            .optional()?
+            .unwrap_or_else(|| "dark".to_string());
+        Ok(match name.as_str() {
+            "dark" => Some(DARK),
+            "light" => Some(LIGHT),
+            "none" => None,
+            _ => bail!("Unknown jjka.diff.theme {name:?}; expected dark, light or none"),
+        })
+    }
+
+    pub fn color(&self, kind: TokenKind) -> Option<&'static str> {
+        match kind {
+            TokenKind::Plain => None,
+            TokenKind::Keyword => Some(self.keyword),
+            TokenKind::String => Some(self.string),
+            TokenKind::Comment => Some(self.comment),
+            TokenKind::Number => Some(self.number),
+        }
+    }
+
+    /// `line` with its tokens colored. Outside of tokens, and after each,
+    /// the text is drawn in `base`, the style of the whole line.
+    pub fn paint(&self, language: &Language, line: &str, base: &str) -> String {
+        let mut out = String::new();
+        for (kind, range) in tokenize(language, line) {
+            match self.color(kind) {
+                Some(color) => {
+                    out.push_str(color);
+                    out.push_str(&line[range]);
+                    out.push_str("\x1b[0m");
+                    out.push_str(base);
+                }
+                None => out.push_str(&line[range]),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(path: &str, line: &str) -> Vec<(TokenKind, String)> {
+        let language = language_for_path(path).unwrap();
+        tokenize(language, line)
+            .into_iter()
+            .filter(|(kind, _)| *kind != TokenKind::Plain)
+            .map(|(kind, range)| (kind, line[range].to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_rust() {
+        assert_eq!(
+            kinds(
+                "src/lib.rs",
+                r#"fn f<'a>(s: &'a str) -> char { let x = "a\"b"; 'c' } // 42"#
+            ),
+            [
+                (TokenKind::Keyword, "fn".to_string()),
+                (TokenKind::Keyword, "let".to_string()),
+                (TokenKind::String, r#""a\"b""#.to_string()),
+                (TokenKind::String, "'c'".to_string()),
+                (TokenKind::Comment, "// 42".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds("a.rs", "x /* y */ 0x1f"),
+            [
+                (TokenKind::Comment, "/* y */".to_string()),
+                (TokenKind::Number, "0x1f".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_python() {
+        assert_eq!(
+            kinds("a.py", "def f(): return 'it''s'  # done"),
+            [
+                (TokenKind::Keyword, "def".to_string()),
+                (TokenKind::Keyword, "return".to_string()),
+                (TokenKind::String, "'it''s'".to_string()),
+                (TokenKind::Comment, "# done".to_string()),
+            ]
+        );
+        assert_eq!(language_for_path("Makefile"), None);
+        assert_eq!(language_for_path("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_paint_restores_base_style() {
+        let language = language_for_path("a.rs").unwrap();
+        assert_eq!(
+            DARK.paint(language, "let x", "\x1b[32m"),
+            "\x1b[35mlet\x1b[0m\x1b[32m x"
+        );
+    }
+}
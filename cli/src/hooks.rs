@@ -0,0 +1,176 @@
+//! Commands from `[jjka.hooks]` run around every transaction a command
+//! commits.
+//!
+//! `pre` runs before the transaction is committed and can reject it by
+//! exiting with an error; `post` runs after. Both get a JSON payload on
+//! stdin with the jjka command, the operation and what it rewrote,
+//! abandoned and which bookmarks it moved, like `--json`'s summary.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::repo::Repo;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::impact::Impact;
+use crate::sigpipe;
+use crate::workspace::WorkspaceHelper;
+
+static COMMAND: OnceLock<String> = OnceLock::new();
+
+/// Records the name of the jjka command being run, for the payload.
+pub fn init(command: String) {
+    let _ = COMMAND.set(command);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    Pre,
+    Post,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::Pre => "pre",
+            Hook::Post => "post",
+        }
+    }
+
+    fn config_key(self) -> &'static str {
+        match self {
+            Hook::Pre => "jjka.hooks.pre",
+            Hook::Post => "jjka.hooks.post",
+        }
+    }
+}
+
+/// A hook is either a shell command line or a program and its arguments.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum HookCommand {
+    Shell(String),
+    Args(Vec<String>),
+}
+
+impl HookCommand {
+    pub fn to_command(&self) -> Option<Command> {
+        match self {
+            HookCommand::Shell(line) => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(line);
+                Some(command)
+            }
+            HookCommand::Args(args) => {
+                let (program, args) = args.split_first()?;
+                let mut command = Command::new(program);
+                command.args(args);
+                Some(command)
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    hook: &'static str,
+    command: &'a str,
+    #[serde(flatten)]
+    impact: &'a Impact,
+}
+
+/// Runs the configured `hook`, if any, from the workspace root, first
+/// computing the diffstats in the payload from `repo`. Fails if the hook
+/// can't be started or exits with an error.
+pub async fn run_hook(
+    helper: &WorkspaceHelper,
+    hook: Hook,
+    impact: &mut Impact,
+    repo: &dyn Repo,
+) -> Result<()> {
+    let key = hook.config_key();
+    let Some(config) = helper.settings().get::<HookCommand>(key).optional()? else {
+        return Ok(());
+    };
+    let Some(mut command) = config.to_command() else {
+        return Ok(());
+    };
+    impact.compute_diffstats(repo).await?;
+    let jjka_command = COMMAND.get().map_or("", String::as_str);
+    let payload = serde_json::to_vec(&Payload {
+        hook: hook.name(),
+        command: jjka_command,
+        impact,
+    })?;
+    run(
+        &mut command,
+        helper.workspace_root(),
+        hook,
+        jjka_command,
+        &payload,
+    )
+    .with_context(|| format!("The {key} hook failed"))
+}
+
+fn run(
+    command: &mut Command,
+    root: &Path,
+    hook: Hook,
+    jjka_command: &str,
+    payload: &[u8],
+) -> Result<()> {
+    let mut child = command
+        .current_dir(root)
+        .env("JJKA_HOOK", hook.name())
+        .env("JJKA_COMMAND", jjka_command)
+        .stdin(Stdio::piped())
+        // Keep stdout for the command's own output, like `serve` responses.
+        .stdout(std::io::stderr())
+        .spawn()
+        .context("Failed to start it")?;
+    // A hook that doesn't read its input closes the pipe early, which is
+    // fine.
+    let _ = sigpipe::write_to_child(child.stdin.take().unwrap(), payload);
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("It exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_hook_command() {
+        let shell: HookCommand = serde_json::from_str(r#""grep -q pre""#).unwrap();
+        let args: HookCommand = serde_json::from_str(r#"["sh", "-c", "exit 3"]"#).unwrap();
+        assert_eq!(
+            args,
+            HookCommand::Args(vec!["sh".into(), "-c".into(), "exit 3".into()])
+        );
+        let root = std::env::temp_dir();
+        let payload = br#"{"hook": "pre"}"#;
+        run(
+            &mut shell.to_command().unwrap(),
+            &root,
+            Hook::Pre,
+            "tidy",
+            payload,
+        )
+        .unwrap();
+        let err = run(
+            &mut args.to_command().unwrap(),
+            &root,
+            Hook::Pre,
+            "tidy",
+            payload,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("exit status: 3"), "{err}");
+        assert!(HookCommand::Args(vec![]).to_command().is_none());
+    }
+}
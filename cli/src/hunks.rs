@@ -0,0 +1,209 @@
+//! `jjka hunks`: list a revision's hunks with their fingerprints, or remap
+//! line-range selectors from an older version of the change.
+
+use anyhow::Result;
+use jj_lib::commit::Commit;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::repo::Repo;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::LineRange;
+use crate::cache;
+use crate::fingerprint::{
+    FingerprintedHunk, RemapStatus, RemappedSelector, fingerprint_file, remap_selectors,
+};
+use crate::format::{OutputFormat, commit_summary};
+use crate::serve::CommitInfo;
+use crate::tree_diff::{changed_paths, commit_file_contents, read_file};
+use crate::unified_diff::{Hunk, unified_hunks};
+use crate::workspace::WorkspaceHelper;
+
+/// The hunks `commit` changes relative to its parents, from the cache if
+/// they're in it.
+pub async fn commit_hunks(repo: &dyn Repo, commit: &Commit) -> Result<Vec<FingerprintedHunk>> {
+    if let Some(hunks) = cache::load_hunks(commit.id()) {
+        return Ok(hunks);
+    }
+    let mut hunks = Vec::new();
+    for file in commit_file_contents(repo, commit).await? {
+        hunks.extend(fingerprint_file(
+            file.path.as_internal_file_string(),
+            &file.before,
+            &file.after,
+        ));
+    }
+    cache::save_hunks(commit.id(), &hunks);
+    Ok(hunks)
+}
+
+/// One file's changes in a revision, as unified hunks.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileHunks {
+    pub path: String,
+    pub added: bool,
+    pub removed: bool,
+    /// Binary files get no hunks.
+    pub binary: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+/// The unified diff of `commit` against its parents with `context` lines
+/// around each change, from the cache if it's in it.
+pub async fn commit_diffs(
+    repo: &dyn Repo,
+    commit: &Commit,
+    context: usize,
+) -> Result<Vec<FileHunks>> {
+    if let Some(diffs) = cache::load_diffs(commit.id(), context) {
+        return Ok(diffs);
+    }
+    let parent_tree = commit.parent_tree(repo)?;
+    let diffs = tree_diffs(repo, &parent_tree, &commit.tree_async().await?, context).await?;
+    cache::save_diffs(commit.id(), context, &diffs);
+    Ok(diffs)
+}
+
+/// The unified diff from `from` to `to`, computed afresh.
+pub async fn tree_diffs(
+    repo: &dyn Repo,
+    from: &MergedTree,
+    to: &MergedTree,
+    context: usize,
+) -> Result<Vec<FileHunks>> {
+    let mut diffs = Vec::new();
+    for change in changed_paths(from, to).await? {
+        let added = change.before.is_absent();
+        let removed = change.after.is_absent();
+        let before = read_file(repo.store(), &change.path, change.before)
+            .await?
+            .unwrap_or_default();
+        let after = read_file(repo.store(), &change.path, change.after)
+            .await?
+            .unwrap_or_default();
+        let binary = before.contains(&0) || after.contains(&0);
+        diffs.push(FileHunks {
+            path: change.path.as_internal_file_string().to_string(),
+            added,
+            removed,
+            binary,
+            hunks: if binary {
+                Vec::new()
+            } else {
+                unified_hunks(&before, &after, context)
+            },
+        });
+    }
+    Ok(diffs)
+}
+
+fn format_range(range: &LineRange) -> String {
+    format!("{}:{}-{}", range.path, range.start, range.end)
+}
+
+pub async fn hunks_command(
+    revision: String,
+    remap_from: Option<String>,
+    selectors: Vec<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    let repo = helper.repo().clone();
+    let commit = helper.resolve_single(&revision)?;
+    let new_hunks = commit_hunks(repo.as_ref(), &commit).await?;
+
+    let Some(remap_from) = remap_from else {
+        if format == OutputFormat::Json {
+            let report = json!({ "commit": CommitInfo::new(&commit), "hunks": new_hunks });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        for hunk in &new_hunks {
+            println!(
+                "{}:{}-{}  +{} -{}  {}",
+                hunk.path, hunk.start, hunk.end, hunk.added, hunk.removed, hunk.fingerprint.content
+            );
+        }
+        return Ok(());
+    };
+
+    let old_commit = helper.resolve_single(&remap_from)?;
+    let old_hunks = commit_hunks(repo.as_ref(), &old_commit).await?;
+    let selectors = if selectors.is_empty() {
+        old_hunks
+            .iter()
+            .map(|hunk| LineRange {
+                path: hunk.path.clone(),
+                start: hunk.start,
+                end: hunk.end,
+            })
+            .collect()
+    } else {
+        selectors
+            .iter()
+            .map(|s| LineRange::parse(s))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let remapped = remap_selectors(&selectors, &old_hunks, &new_hunks);
+    if format == OutputFormat::Json {
+        let selectors: Vec<_> = remapped
+            .iter()
+            .map(|selector| {
+                json!({
+                    "old": format_range(&selector.old),
+                    "new": selector.new.iter().map(format_range).collect::<Vec<_>>(),
+                    "status": selector.status.as_str(),
+                })
+            })
+            .collect();
+        let report = json!({
+            "from": CommitInfo::new(&old_commit),
+            "to": CommitInfo::new(&commit),
+            "selectors": selectors,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("Remapping selectors from {}", commit_summary(&old_commit));
+    println!("                      to {}", commit_summary(&commit));
+    for line in remap_report(&remapped) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Describes where each selector went, then how many were unchanged,
+/// relocated, fuzzy or lost.
+pub fn remap_report(remapped: &[RemappedSelector]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut counts = [0; 4];
+    for selector in remapped {
+        counts[selector.status as usize] += 1;
+        let new = selector.new.iter().map(format_range).collect::<Vec<_>>();
+        if new.is_empty() {
+            lines.push(format!(
+                "  {} ({})",
+                format_range(&selector.old),
+                selector.status.as_str()
+            ));
+        } else {
+            lines.push(format!(
+                "  {} -> {} ({})",
+                format_range(&selector.old),
+                new.join(" "),
+                selector.status.as_str()
+            ));
+        }
+    }
+    lines.push(format!(
+        "{} unchanged, {} relocated, {} fuzzy, {} lost",
+        counts[RemapStatus::Unchanged as usize],
+        counts[RemapStatus::Relocated as usize],
+        counts[RemapStatus::Fuzzy as usize],
+        counts[RemapStatus::Lost as usize]
+    ));
+    lines
+}
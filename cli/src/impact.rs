@@ -0,0 +1,301 @@
+//! Summaries of what a command's transactions did to the repo.
+//!
+//! Every transaction committed through
+//! [`WorkspaceHelper::finish_transaction`](crate::workspace::WorkspaceHelper::finish_transaction)
+//! is recorded here, and `main` prints them all once the command is done:
+//! rewritten commits with their diffstat before and after, abandoned commits
+//! and moved bookmarks.
+//!
+//! The diffstats mean diffing every file a rewritten commit changes, twice,
+//! so they're only computed when a summary or hook payload includes them.
+
+use anyhow::Result;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_store::RefTarget;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::format::{OutputFormat, commit_summary, short_commit_id, subject};
+use crate::tree_diff::commit_file_contents;
+use crate::ui::{self, status};
+use crate::unified_diff::count_changed_lines;
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static RECORDED: Mutex<Vec<Impact>> = Mutex::new(Vec::new());
+static PRODUCED_CONFLICTS: AtomicBool = AtomicBool::new(false);
+
+pub fn init(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffStat {
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl DiffStat {
+    pub async fn of_commit(repo: &dyn Repo, commit: &Commit) -> Result<Self> {
+        let mut stat = DiffStat::default();
+        for file in commit_file_contents(repo, commit).await? {
+            let (insertions, deletions) = count_changed_lines(&file.before, &file.after);
+            stat.files += 1;
+            stat.insertions += insertions;
+            stat.deletions += deletions;
+        }
+        Ok(stat)
+    }
+
+    pub fn format(&self) -> String {
+        format!(
+            "{} file(s) +{} -{}",
+            self.files, self.insertions, self.deletions
+        )
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RewrittenCommit {
+    change_id: String,
+    old_commit_id: String,
+    new_commit_id: String,
+    subject: String,
+    /// Left out until [`Impact::compute_diffstats`].
+    old_diffstat: Option<DiffStat>,
+    new_diffstat: Option<DiffStat>,
+    /// The new commit has conflicts and the old one didn't.
+    new_conflict: bool,
+    #[serde(skip)]
+    summary: String,
+    #[serde(skip)]
+    old_short_id: String,
+    #[serde(skip)]
+    old: Commit,
+    #[serde(skip)]
+    new: Commit,
+}
+
+#[derive(Serialize, Debug)]
+struct AbandonedCommit {
+    change_id: String,
+    commit_id: String,
+    subject: String,
+    #[serde(skip)]
+    summary: String,
+}
+
+#[derive(Serialize, Debug)]
+struct MovedBookmark {
+    name: String,
+    /// Commit ids the bookmark pointed to; more than one if conflicted and
+    /// none if it didn't exist.
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Impact {
+    operation: String,
+    rewritten: Vec<RewrittenCommit>,
+    abandoned: Vec<AbandonedCommit>,
+    bookmarks: Vec<MovedBookmark>,
+    /// The repo after the transaction, for computing the diffstats when
+    /// the impact is reported.
+    #[serde(skip)]
+    repo: Option<Arc<ReadonlyRepo>>,
+}
+
+fn target_ids(target: &RefTarget) -> Vec<String> {
+    target.added_ids().map(|id| id.hex()).collect()
+}
+
+impl Impact {
+    /// Compares `new_repo` to `old_repo`, where `hidden` are the commits
+    /// visible in the old repo but not in the new one.
+    pub async fn compute(
+        old_repo: &dyn Repo,
+        new_repo: &dyn Repo,
+        hidden: &[CommitId],
+        operation: &str,
+    ) -> Result<Self> {
+        let mut impact = Impact {
+            operation: operation.to_string(),
+            rewritten: Vec::new(),
+            abandoned: Vec::new(),
+            bookmarks: Vec::new(),
+            repo: None,
+        };
+        for id in hidden {
+            let old = old_repo.store().get_commit(id)?;
+            let change_id = old.change_id().reverse_hex();
+            let new_ids = new_repo
+                .resolve_change_id(old.change_id())
+                .unwrap_or_default();
+            if new_ids.is_empty() {
+                impact.abandoned.push(AbandonedCommit {
+                    change_id,
+                    commit_id: id.hex(),
+                    subject: subject(&old).to_string(),
+                    summary: commit_summary(&old),
+                });
+                continue;
+            }
+            for new_id in new_ids {
+                let new = new_repo.store().get_commit(&new_id)?;
+                impact.rewritten.push(RewrittenCommit {
+                    change_id: change_id.clone(),
+                    old_commit_id: id.hex(),
+                    new_commit_id: new_id.hex(),
+                    subject: subject(&new).to_string(),
+                    old_diffstat: None,
+                    new_diffstat: None,
+                    new_conflict: new.has_conflict()? && !old.has_conflict()?,
+                    summary: commit_summary(&new),
+                    old_short_id: short_commit_id(&old),
+                    old: old.clone(),
+                    new,
+                });
+            }
+        }
+
+        let names: BTreeSet<_> = old_repo
+            .view()
+            .local_bookmarks()
+            .chain(new_repo.view().local_bookmarks())
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        for name in names {
+            let old = old_repo.view().get_local_bookmark(&name);
+            let new = new_repo.view().get_local_bookmark(&name);
+            if old != new {
+                impact.bookmarks.push(MovedBookmark {
+                    name: name.as_str().to_string(),
+                    old: target_ids(old),
+                    new: target_ids(new),
+                });
+            }
+        }
+        Ok(impact)
+    }
+
+    /// Computes the diffstats of the rewritten commits before and after,
+    /// unless that's been done already. `repo` has to contain both.
+    pub async fn compute_diffstats(&mut self, repo: &dyn Repo) -> Result<()> {
+        for commit in &mut self.rewritten {
+            if commit.new_diffstat.is_some() {
+                continue;
+            }
+            commit.old_diffstat = Some(DiffStat::of_commit(repo, &commit.old).await?);
+            commit.new_diffstat = Some(DiffStat::of_commit(repo, &commit.new).await?);
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rewritten.is_empty() && self.abandoned.is_empty() && self.bookmarks.is_empty()
+    }
+
+    fn print_text(&self) {
+        status!("Impact of \"{}\":", self.operation);
+        for commit in &self.rewritten {
+            let mut notes = Vec::new();
+            if let (Some(old_diffstat), Some(new_diffstat)) =
+                (&commit.old_diffstat, &commit.new_diffstat)
+            {
+                notes.push(new_diffstat.format());
+                if new_diffstat != old_diffstat {
+                    notes.push(format!("was {}", old_diffstat.format()));
+                }
+            }
+            if commit.new_conflict {
+                notes.push("new conflict".to_string());
+            }
+            status!(
+                "  Rewrote {} (was {})  {}",
+                commit.summary,
+                commit.old_short_id,
+                notes.join(", ")
+            );
+        }
+        for commit in &self.abandoned {
+            status!("  Abandoned {}", commit.summary);
+        }
+        let short = |ids: &[String]| match ids {
+            [] => "(none)".to_string(),
+            ids => ids
+                .iter()
+                .map(|id| &id[..12])
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        for bookmark in &self.bookmarks {
+            status!(
+                "  Moved bookmark {} from {} to {}",
+                bookmark.name,
+                short(&bookmark.old),
+                short(&bookmark.new)
+            );
+        }
+    }
+}
+
+/// Remembers the impact of a transaction committed as `repo` for
+/// [`report`].
+pub fn record(mut impact: Impact, repo: Arc<ReadonlyRepo>) {
+    if impact.rewritten.iter().any(|commit| commit.new_conflict) {
+        PRODUCED_CONFLICTS.store(true, Ordering::Relaxed);
+    }
+    if !impact.is_empty() {
+        impact.repo = Some(repo);
+        RECORDED.lock().unwrap().push(impact);
+    }
+}
+
+/// Whether a transaction of this command left a commit with new conflicts.
+pub fn produced_conflicts() -> bool {
+    PRODUCED_CONFLICTS.load(Ordering::Relaxed)
+}
+
+/// Removes and returns the impact recorded so far, for commands that
+/// report it themselves instead of at the end.
+pub async fn take() -> Result<Vec<Impact>> {
+    let mut recorded = std::mem::take(&mut *RECORDED.lock().unwrap());
+    for impact in &mut recorded {
+        if let Some(repo) = impact.repo.take() {
+            impact.compute_diffstats(repo.as_ref()).await?;
+        }
+    }
+    Ok(recorded)
+}
+
+/// Prints the impact of all transactions recorded so far. With `--json`,
+/// prints them as one JSON object on a single line.
+pub async fn report() -> Result<()> {
+    let format = FORMAT.get().copied().unwrap_or_default();
+    if format == OutputFormat::Text && ui::is_quiet() {
+        RECORDED.lock().unwrap().clear();
+        return Ok(());
+    }
+    let recorded = take().await?;
+    if recorded.is_empty() {
+        return Ok(());
+    }
+    match format {
+        OutputFormat::Text => {
+            for impact in &recorded {
+                impact.print_text();
+            }
+        }
+        OutputFormat::Json => {
+            let report = serde_json::json!({ "operations": recorded });
+            println!("{}", serde_json::to_string(&report)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Holding off ctrl-C while an operation is published.
+//!
+//! Nothing a transaction does is visible until it's committed, so an
+//! interrupt before then loses nothing. Committing exports git refs,
+//! writes the operation and checks out the new working-copy commit, and
+//! stopping between those steps would leave git, jj and the files on disk
+//! disagreeing; a ctrl-C there takes effect once they're all done.
+//!
+//! Ctrl-C is only held off on Unix, where it's a signal.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn record_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether a ctrl-C came while deferred, for long loops that should stop
+/// early and clean up.
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Defers ctrl-C until dropped, then delivers it if one came. Nested
+/// deferrals leave it to the outermost one.
+pub struct Deferred {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl Deferred {
+    #[cfg(unix)]
+    pub fn new() -> Self {
+        let handler = record_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let previous = unsafe { libc::signal(libc::SIGINT, handler) };
+        if previous != handler {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+        Deferred { previous }
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> Self {
+        Deferred {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+        // While paging, ctrl-C is the pager's and stays ignored here.
+        if self.previous == libc::SIG_DFL && INTERRUPTED.swap(false, Ordering::SeqCst) {
+            unsafe {
+                libc::raise(libc::SIGINT);
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! `jjka land`: merge the bottom commit of a stack into trunk.
+//!
+//! The commit has to sit right on top of trunk(), after fetching. When the
+//! remote's forge is known, like for `stack`, its open merge request or
+//! pull request also has to be approved and its CI green. It then lands by
+//! pushing it to trunk's branch, or with `--merge` by having the forge
+//! merge it, after which the rest of the stack is rebased onto the new
+//! trunk. A landed commit the forge squashed or rebased is abandoned.
+
+use anyhow::{Result, anyhow, bail};
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_store::RefTarget;
+use jj_lib::ref_name::{RefName, RemoteName};
+use jj_lib::repo::Repo as _;
+use jj_lib::rewrite::rebase_commit;
+
+use crate::exit_code::Failure;
+use crate::forge::{remote_config, remote_web_url};
+use crate::format::{commit_summary, short_change_id};
+use crate::remote::{fetch, push_bookmarks, trunk_branch};
+use crate::stack::{Ci, CiStatus, Review};
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// Fails unless `commit` is the bottom of a stack on `trunk`.
+fn check_bottom(helper: &WorkspaceHelper, commit: &Commit, trunk: &Commit) -> Result<()> {
+    let summary = commit_summary(commit);
+    let index = helper.repo().index();
+    if index.is_ancestor(commit.id(), trunk.id()) {
+        return Err(Failure::Usage.error(anyhow!("{summary} is already in trunk()")));
+    }
+    if commit.parent_ids() == [trunk.id().clone()] {
+        if commit.has_conflict()? {
+            return Err(Failure::Precondition.error(anyhow!(
+                "{summary} has conflicts; resolve them before landing it"
+            )));
+        }
+        return Ok(());
+    }
+    let below = helper.evaluate_revset(&format!("trunk()..{}-", commit.id().hex()))?;
+    if !below.is_empty() && index.is_ancestor(trunk.id(), commit.id()) {
+        return Err(Failure::Precondition.error(anyhow!(
+            "{summary} isn't the bottom of its stack; land the {} commit(s) below it first",
+            below.len()
+        )));
+    }
+    Err(Failure::Precondition.error(anyhow!(
+        "{summary} isn't on top of trunk(), which has moved; run `jjka sync` first"
+    )))
+}
+
+/// Whether the forge `remote` is on is known, in which case landing needs
+/// an approved review and green CI.
+fn has_forge(helper: &WorkspaceHelper, remote: &str) -> Result<bool> {
+    let config = remote_config(helper, remote)?;
+    let url = config.url.clone().or(remote_web_url(helper, remote)?);
+    Ok(config.kind.is_some() || url.is_some_and(|url| config.kind_for(&url).is_some()))
+}
+
+/// Finds the review of `commit` from a branch of `remote` and fails unless
+/// it's approved and CI passed on the commit.
+fn check_review(
+    helper: &WorkspaceHelper,
+    ci: &Ci,
+    commit: &Commit,
+    remote: &str,
+    target: &str,
+) -> Result<Review> {
+    let summary = commit_summary(commit);
+    let branch = helper
+        .repo()
+        .view()
+        .remote_bookmarks(RemoteName::new(remote))
+        .find(|(name, remote_ref)| {
+            name.as_str() != target && remote_ref.target.as_normal() == Some(commit.id())
+        })
+        .map(|(name, _)| name.as_str().to_string());
+    let Some(branch) = branch else {
+        return Err(Failure::Precondition.error(anyhow!(
+            "{summary} isn't pushed to {remote}, so it has no review; submit it first"
+        )));
+    };
+    let Some(review) = ci.find_review(&branch)? else {
+        return Err(
+            Failure::Precondition.error(anyhow!("Nothing is open for review from {branch}"))
+        );
+    };
+    if !ci.approved(&review)? {
+        return Err(Failure::Precondition.error(anyhow!(
+            "{} for {summary} isn't approved yet: {}",
+            review.label,
+            review.url
+        )));
+    }
+    let ci_status = ci.status(commit)?;
+    if ci_status != CiStatus::Passed {
+        return Err(Failure::Precondition.error(anyhow!(
+            "CI hasn't passed on {summary} ({})",
+            ci_status.label()
+        )));
+    }
+    Ok(review)
+}
+
+/// Rebases the children of `landed` onto `trunk`, and abandons `landed` if
+/// the forge merged a copy of it, unless a branch still keeps it immutable.
+/// Returns how many commits were rebased.
+async fn restack(helper: &mut WorkspaceHelper, landed: &Commit, trunk: &Commit) -> Result<usize> {
+    let children =
+        helper.evaluate_revset(&format!("children({}) & mutable()", landed.id().hex()))?;
+    let abandon = !helper.repo().index().is_ancestor(landed.id(), trunk.id())
+        && !helper
+            .evaluate_revset(&format!("{} & mutable()", landed.id().hex()))?
+            .is_empty();
+    if children.is_empty() && !abandon {
+        return Ok(0);
+    }
+    let mut tx = helper.start_transaction();
+    for child in &children {
+        let parents: Vec<CommitId> = child
+            .parent_ids()
+            .iter()
+            .map(|id| {
+                if id == landed.id() {
+                    trunk.id().clone()
+                } else {
+                    id.clone()
+                }
+            })
+            .collect();
+        rebase_commit(tx.repo_mut(), child.clone(), parents).await?;
+    }
+    if abandon {
+        tx.repo_mut().record_abandoned_commit(landed);
+    }
+    let rebased = children.len() + tx.repo_mut().rebase_descendants()?;
+    helper
+        .finish_transaction(
+            tx,
+            &format!(
+                "restack onto trunk after landing {}",
+                short_change_id(landed)
+            ),
+        )
+        .await?;
+    Ok(rebased)
+}
+
+pub async fn land_command(
+    revision: String,
+    remote: String,
+    merge: bool,
+    dry_run: bool,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    fetch(&mut helper, &remote).await?;
+
+    let commit = helper.resolve_single(&revision)?;
+    let trunk = helper.resolve_single("trunk()")?;
+    check_bottom(&helper, &commit, &trunk)?;
+    let summary = commit_summary(&commit);
+    let Some(target) = trunk_branch(&helper, &remote)? else {
+        return Err(Failure::Usage.error(anyhow!(
+            "trunk() isn't at a branch of {remote}, so there's nowhere to land {summary}"
+        )));
+    };
+
+    let forge = if has_forge(&helper, &remote)? {
+        let ci = Ci::new(&helper, &remote)?;
+        let review = check_review(&helper, &ci, &commit, &remote, &target)?;
+        status!("{} is approved and CI passed", review.label);
+        Some((ci, review))
+    } else if merge {
+        return Err(Failure::Usage.error(anyhow!(
+            "Can't tell which forge remote {remote} is on to merge with; set \
+             jjka.forge.remotes.{remote}.kind to \"github\" or \"gitlab\""
+        )));
+    } else {
+        status!("Not checking review or CI: can't tell which forge {remote} is on");
+        None
+    };
+
+    let sha = commit.id().hex();
+    match forge {
+        Some((ci, review)) if merge => {
+            if dry_run {
+                println!(
+                    "Would merge {} ({}) into {target}",
+                    review.label, review.url
+                );
+                return Ok(());
+            }
+            match ci {
+                Ci::GitHub(github) => github.merge(review.number, &sha)?,
+                Ci::GitLab(gitlab) => {
+                    gitlab.merge(review.number, &sha)?;
+                }
+            }
+            status!("Merged {} into {target}", review.label);
+            fetch(&mut helper, &remote).await?;
+            let new_trunk = helper.resolve_single("trunk()")?;
+            if new_trunk.id() == trunk.id() {
+                bail!(
+                    "{} was merged, but {target} on {remote} hasn't moved yet; \
+                     run `jjka sync` once it has",
+                    review.label
+                );
+            }
+            let rebased = restack(&mut helper, &commit, &new_trunk).await?;
+            println!("Landed {summary} on {target}");
+            if rebased > 0 {
+                println!("Rebased {rebased} commit(s) onto trunk");
+            }
+        }
+        _ => {
+            if dry_run {
+                println!("Would push {summary} to {target} on {remote}");
+                return Ok(());
+            }
+            let mut tx = helper.start_transaction();
+            tx.repo_mut().set_local_bookmark_target(
+                RefName::new(&target),
+                RefTarget::normal(commit.id().clone()),
+            );
+            helper
+                .finish_transaction(
+                    tx,
+                    &format!("set bookmark {target} to land {}", short_change_id(&commit)),
+                )
+                .await?;
+            push_bookmarks(&mut helper, &remote, std::slice::from_ref(&target)).await?;
+            println!("Landed {summary} on {target}");
+        }
+    }
+    Ok(())
+}
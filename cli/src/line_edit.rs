@@ -0,0 +1,146 @@
+//! Rewriting line ranges of working-copy files from another revision, for
+//! `copy-hunks` and `revert-lines`.
+
+use anyhow::{Context, Result, bail};
+use itertools::Itertools;
+use jj_lib::backend::{CopyId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::merge::{Merge, MergedTreeValue};
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jjka_lib::selection::line_count;
+
+use crate::LineRange;
+use crate::format::commit_summary;
+use crate::tree_diff::read_file;
+use crate::unified_diff::count_changed_lines;
+use crate::workspace::WorkspaceHelper;
+
+/// Which version of a file the line numbers of the ranges refer to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LinesOf {
+    WorkingCopy,
+    Revision,
+}
+
+/// Computes a file's new working-copy content from its current content,
+/// its content in the other revision and the selected ranges.
+pub type EditLines = fn(&[u8], &[u8], &[(usize, usize)]) -> Vec<u8>;
+
+/// Replaces each file named in `ranges` in the working copy with
+/// `edit(working-copy content, content in other, ranges of that file)`.
+/// Returns a `path +added -removed` line per file that changed, and leaves
+/// the working copy alone if none did.
+pub async fn edit_working_copy_lines(
+    helper: &mut WorkspaceHelper,
+    other: &Commit,
+    ranges: &[LineRange],
+    lines_of: LinesOf,
+    edit: EditLines,
+    description: &str,
+) -> Result<Vec<String>> {
+    let wc_id = helper
+        .wc_commit_id()
+        .context("This workspace has no working-copy commit")?
+        .clone();
+    let repo = helper.repo().clone();
+    let store = repo.store();
+    let wc = store.get_commit(&wc_id)?;
+    let other_tree = other.tree_async().await?;
+    let wc_tree = wc.tree_async().await?;
+
+    let mut builder = MergedTreeBuilder::new(wc.tree_id().clone());
+    let mut changed_files = Vec::new();
+    let by_path = ranges.iter().into_group_map_by(|range| range.path.as_str());
+    for (path_str, ranges) in by_path.into_iter().sorted_by_key(|(path, _)| *path) {
+        let path = RepoPathBuf::from_internal_string(path_str)
+            .with_context(|| format!("Invalid path {path_str}"))?;
+        let other_value = other_tree.path_value(&path)?;
+        let wc_value = wc_tree.path_value(&path)?;
+        if !wc_value.is_resolved() {
+            bail!("{path_str} has conflicts in the working copy; resolve them first");
+        }
+        let (numbered_value, numbered_name) = match lines_of {
+            LinesOf::WorkingCopy => (&wc_value, "the working copy".to_string()),
+            LinesOf::Revision => (&other_value, commit_summary(other)),
+        };
+        if numbered_value.is_absent() {
+            bail!("{path_str} doesn't exist in {numbered_name}");
+        }
+        let other_content = read_content(repo.as_ref(), &path, other_value.clone())
+            .await?
+            .with_context(|| {
+                format!(
+                    "{path_str} is not a regular file in {}",
+                    commit_summary(other)
+                )
+            })?;
+        let wc_content = read_content(repo.as_ref(), &path, wc_value.clone())
+            .await?
+            .with_context(|| format!("{path_str} is not a regular file in the working copy"))?;
+
+        let numbered_lines = line_count(match lines_of {
+            LinesOf::WorkingCopy => &wc_content,
+            LinesOf::Revision => &other_content,
+        });
+        if let Some(range) = ranges.iter().find(|range| range.end > numbered_lines) {
+            bail!(
+                "Range {path_str}:{}-{} is past the end of the file, which has {numbered_lines} line(s) in {numbered_name}",
+                range.start,
+                range.end
+            );
+        }
+        let ranges = ranges
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect_vec();
+        let new_content = edit(&wc_content, &other_content, &ranges);
+        if new_content == wc_content {
+            continue;
+        }
+        let executable = match wc_value.into_resolved() {
+            Ok(Some(TreeValue::File { executable, .. })) => executable,
+            _ => match other_value.into_resolved() {
+                Ok(Some(TreeValue::File { executable, .. })) => executable,
+                _ => false,
+            },
+        };
+        let id = store.write_file(&path, &mut new_content.as_slice()).await?;
+        builder.set_or_remove(
+            path,
+            Merge::normal(TreeValue::File {
+                id,
+                executable,
+                copy_id: CopyId::placeholder(),
+            }),
+        );
+        let (added, removed) = count_changed_lines(&wc_content, &new_content);
+        changed_files.push(format!("{path_str} +{added} -{removed}"));
+    }
+    if changed_files.is_empty() {
+        return Ok(changed_files);
+    }
+
+    let tree_id = builder.write_tree(store)?;
+    let mut tx = helper.start_transaction();
+    tx.repo_mut()
+        .rewrite_commit(&wc)
+        .set_tree_id(tree_id)
+        .write()?;
+    helper.finish_transaction(tx, description).await?;
+    Ok(changed_files)
+}
+
+/// A regular file's content, empty if the path is absent, `None` for other
+/// kinds of files.
+async fn read_content(
+    repo: &dyn Repo,
+    path: &RepoPath,
+    value: MergedTreeValue,
+) -> Result<Option<Vec<u8>>> {
+    if value.is_absent() {
+        return Ok(Some(Vec::new()));
+    }
+    read_file(repo.store(), path, value).await
+}
@@ -0,0 +1,177 @@
+//! `jjka link-issue`: mention an issue in every commit of a stack.
+//!
+//! The issue key comes from the command line, from the name of a bookmark
+//! on the stack (like `PROJ-123-unary-minus`, matched with
+//! `jjka.describe.issue-pattern`), or from a prompt. Each description gets
+//! it as a trailer, or as a prefix of the subject with `--style prefix`.
+//! Descriptions that already mention the key are left alone, so running it
+//! again after adding commits only touches the new ones.
+
+use anyhow::{Result, anyhow, bail};
+use clap::ValueEnum;
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use std::io::{BufRead as _, IsTerminal as _, Write as _};
+
+use crate::describe::bookmark_issue;
+use crate::exit_code::Failure;
+use crate::format::commit_summary;
+use crate::trailer::add_trailer;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper, current_commit};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueStyle {
+    /// A `Refs: PROJ-123` trailer (the trailer is `jjka.issue.trailer`)
+    Trailer,
+    /// A subject like `PROJ-123: Fix the lexer` (the format is
+    /// `jjka.issue.prefix`)
+    Prefix,
+}
+
+/// `description` mentioning `issue` the way `style` says.
+fn link(description: &str, issue: &str, style: IssueStyle, format: &str) -> String {
+    match style {
+        IssueStyle::Trailer => add_trailer(description, format, issue),
+        IssueStyle::Prefix => format!("{}{description}", format.replace("{issue}", issue)),
+    }
+}
+
+/// Asks for the issue key, if someone is there to answer.
+fn prompt_issue() -> Result<Option<String>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    eprint!("Issue key: ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let issue = line.trim();
+    Ok((!issue.is_empty()).then(|| issue.to_string()))
+}
+
+pub async fn link_issue_command(
+    issue: Option<String>,
+    revisions: String,
+    style: Option<IssueStyle>,
+    dry_run: bool,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+
+    let repo = helper.repo().clone();
+    let mut commits: Vec<Commit> = Vec::new();
+    for commit in helper.evaluate_revset(&revisions)? {
+        if commit.description().trim().is_empty() {
+            if !commit.is_empty(repo.as_ref())? {
+                eprintln!(
+                    "Warning: Skipping {}, which has no description yet",
+                    commit_summary(&commit)
+                );
+            }
+            continue;
+        }
+        commits.push(commit);
+    }
+    let Some(bottom) = commits.first() else {
+        status!("No described commits in {revisions}");
+        return Ok(());
+    };
+    let issue = match issue {
+        Some(issue) => issue,
+        None => match bookmark_issue(&helper, bottom)? {
+            Some(issue) => issue,
+            None => prompt_issue()?.ok_or_else(|| {
+                Failure::Usage.error(anyhow!(
+                    "No bookmark on {revisions} names an issue; pass the issue key"
+                ))
+            })?,
+        },
+    };
+
+    let settings = helper.settings();
+    let style = match style {
+        Some(style) => style,
+        None => match settings
+            .get_string("jjka.issue.style")
+            .optional()?
+            .as_deref()
+        {
+            None | Some("trailer") => IssueStyle::Trailer,
+            Some("prefix") => IssueStyle::Prefix,
+            Some(other) => {
+                bail!("jjka.issue.style is \"{other}\"; it can be \"trailer\" or \"prefix\"")
+            }
+        },
+    };
+    let format = match style {
+        IssueStyle::Trailer => settings
+            .get_string("jjka.issue.trailer")
+            .optional()?
+            .unwrap_or_else(|| "Refs".to_string()),
+        IssueStyle::Prefix => settings
+            .get_string("jjka.issue.prefix")
+            .optional()?
+            .unwrap_or_else(|| "{issue}: ".to_string()),
+    };
+
+    let missing: Vec<&Commit> = commits
+        .iter()
+        .filter(|commit| !commit.description().contains(issue.as_str()))
+        .collect();
+    if missing.is_empty() {
+        status!("Every commit in {revisions} already mentions {issue}");
+        return Ok(());
+    }
+    if dry_run {
+        for commit in &missing {
+            println!("Would link {} to {issue}", commit_summary(commit));
+        }
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    let mut linked = Vec::new();
+    for commit in &missing {
+        let current = current_commit(tx.repo(), commit.change_id())?;
+        let description = link(current.description(), &issue, style, &format);
+        linked.push(
+            tx.repo_mut()
+                .rewrite_commit(&current)
+                .set_description(description)
+                .write()?,
+        );
+        tx.repo_mut().rebase_descendants()?;
+    }
+    helper
+        .finish_transaction(tx, &format!("link {revisions} to {issue}"))
+        .await?;
+    for commit in &linked {
+        println!("Linked {} to {issue}", commit_summary(commit));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link() {
+        assert_eq!(
+            link("Fix lexer\n", "PROJ-12", IssueStyle::Trailer, "Refs"),
+            "Fix lexer\n\nRefs: PROJ-12\n"
+        );
+        assert_eq!(
+            link(
+                "Fix lexer\n\nTabs.\n",
+                "PROJ-12",
+                IssueStyle::Prefix,
+                "[{issue}] "
+            ),
+            "[PROJ-12] Fix lexer\n\nTabs.\n"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! `jjka lint`: check commit descriptions against the rules in
+//! `[jjka.lint]`, failing if any commit breaks one, for CI and push guards.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::object_id::ObjectId as _;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::changelog::CONVENTIONAL_SUBJECT;
+use crate::format::{OutputFormat, commit_summary, subject};
+use crate::workspace::WorkspaceHelper;
+
+const DEFAULT_MAX_SUBJECT_LENGTH: usize = 72;
+const DEFAULT_TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+const DEFAULT_WIP_PATTERN: &str = r"(?i)^(wip\b|fixup!|squash!|amend!)|\bDO NOT (MERGE|SUBMIT)\b";
+
+struct Rules {
+    /// 0 turns the check off.
+    max_subject_length: usize,
+    conventional: bool,
+    types: Vec<String>,
+    required_trailers: Vec<String>,
+    wip: Option<Regex>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            max_subject_length: DEFAULT_MAX_SUBJECT_LENGTH,
+            conventional: false,
+            types: DEFAULT_TYPES.iter().map(|t| t.to_string()).collect(),
+            required_trailers: vec![],
+            wip: Some(Regex::new(DEFAULT_WIP_PATTERN).unwrap()),
+        }
+    }
+}
+
+impl Rules {
+    fn from_config(helper: &WorkspaceHelper) -> Result<Self> {
+        let settings = helper.settings();
+        let mut rules = Rules::default();
+        if let Some(length) = settings
+            .get::<usize>("jjka.lint.max-subject-length")
+            .optional()?
+        {
+            rules.max_subject_length = length;
+        }
+        if let Some(conventional) = settings.get_bool("jjka.lint.conventional").optional()? {
+            rules.conventional = conventional;
+        }
+        if let Some(types) = settings.get("jjka.lint.types").optional()? {
+            rules.types = types;
+        }
+        if let Some(trailers) = settings.get("jjka.lint.required-trailers").optional()? {
+            rules.required_trailers = trailers;
+        }
+        if let Some(pattern) = settings.get_string("jjka.lint.wip-pattern").optional()? {
+            rules.wip = if pattern.is_empty() {
+                None
+            } else {
+                Some(
+                    Regex::new(&pattern)
+                        .with_context(|| format!("Invalid jjka.lint.wip-pattern {pattern:?}"))?,
+                )
+            };
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct Problem {
+    rule: &'static str,
+    message: String,
+}
+
+fn problem(rule: &'static str, message: String) -> Problem {
+    Problem { rule, message }
+}
+
+fn lint_description(rules: &Rules, description: &str) -> Vec<Problem> {
+    let Some(subject) = description.lines().next().filter(|s| !s.trim().is_empty()) else {
+        return vec![problem("empty", "The description is empty".to_string())];
+    };
+    let mut problems = vec![];
+    let length = subject.chars().count();
+    if rules.max_subject_length > 0 && length > rules.max_subject_length {
+        problems.push(problem(
+            "subject-length",
+            format!(
+                "The subject is {length} characters long; the limit is {}",
+                rules.max_subject_length
+            ),
+        ));
+    }
+    if rules.conventional {
+        match Regex::new(CONVENTIONAL_SUBJECT).unwrap().captures(subject) {
+            None => problems.push(problem(
+                "conventional",
+                "The subject doesn't look like `type(scope): description`".to_string(),
+            )),
+            Some(captures) if !rules.types.iter().any(|t| *t == captures[1]) => {
+                problems.push(problem(
+                    "conventional",
+                    format!(
+                        "Unknown type {:?}; expected one of {}",
+                        &captures[1],
+                        rules.types.join(", ")
+                    ),
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    for trailer in &rules.required_trailers {
+        let trailer_re = Regex::new(&format!(r"(?m)^{}: *\S", regex::escape(trailer))).unwrap();
+        if !trailer_re.is_match(description) {
+            problems.push(problem(
+                "trailer",
+                format!("The {trailer}: trailer is missing"),
+            ));
+        }
+    }
+    if let Some(found) = rules.wip.as_ref().and_then(|wip| wip.find(description)) {
+        problems.push(problem(
+            "wip",
+            format!(
+                "The description is marked as unfinished: {:?}",
+                found.as_str()
+            ),
+        ));
+    }
+    problems
+}
+
+#[derive(Serialize)]
+struct LintedCommit {
+    commit_id: String,
+    change_id: String,
+    subject: String,
+    problems: Vec<Problem>,
+}
+
+pub async fn lint_command(revset: String, format: OutputFormat) -> Result<()> {
+    let helper = WorkspaceHelper::load()?;
+    let rules = Rules::from_config(&helper)?;
+    let repo = helper.repo().clone();
+    let mut checked = 0;
+    let mut linted = vec![];
+    for commit in helper.evaluate_revset(&revset)? {
+        // The working-copy commit usually has nothing in it yet.
+        if Some(commit.id()) == helper.wc_commit_id()
+            && commit.description().is_empty()
+            && commit.is_empty(repo.as_ref())?
+        {
+            continue;
+        }
+        checked += 1;
+        let problems = lint_description(&rules, commit.description());
+        if problems.is_empty() {
+            continue;
+        }
+        if format == OutputFormat::Text {
+            println!("{}", commit_summary(&commit));
+            for problem in &problems {
+                println!("  {}: {}", problem.rule, problem.message);
+            }
+        }
+        linted.push(LintedCommit {
+            commit_id: commit.id().hex(),
+            change_id: commit.change_id().reverse_hex(),
+            subject: subject(&commit).to_string(),
+            problems,
+        });
+    }
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&linted)?);
+    }
+    if !linted.is_empty() {
+        let count: usize = linted.iter().map(|commit| commit.problems.len()).sum();
+        bail!(
+            "Found {count} problem(s) in {} of {checked} commit(s)",
+            linted.len()
+        );
+    }
+    if format == OutputFormat::Text {
+        println!("Checked {checked} commit(s): no problems");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(description: &str, rules: &Rules) -> Vec<&'static str> {
+        lint_description(rules, description)
+            .into_iter()
+            .map(|problem| problem.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_description() {
+        let defaults = Rules::default();
+        assert_eq!(rules("Fix the parser\n", &defaults), Vec::<&str>::new());
+        assert_eq!(rules("", &defaults), ["empty"]);
+        assert_eq!(rules(&"x".repeat(73), &defaults), ["subject-length"]);
+        assert_eq!(rules("WIP: parser\n", &defaults), ["wip"]);
+        assert_eq!(rules("fixup! Fix the parser\n", &defaults), ["wip"]);
+        assert_eq!(
+            rules("Fix the parser\n\nDO NOT MERGE\n", &defaults),
+            ["wip"]
+        );
+        assert_eq!(rules("Wipe the cache\n", &defaults), Vec::<&str>::new());
+
+        let strict = Rules {
+            conventional: true,
+            required_trailers: vec!["Signed-off-by".to_string()],
+            ..Rules::default()
+        };
+        assert_eq!(
+            rules(
+                "fix(parser): Handle tabs\n\nSigned-off-by: A <a@example.com>\n",
+                &strict
+            ),
+            Vec::<&str>::new()
+        );
+        assert_eq!(rules("Handle tabs\n", &strict), ["conventional", "trailer"]);
+        assert_eq!(
+            rules("fixes: Handle tabs\n\nSigned-off-by: A\n", &strict),
+            ["conventional"]
+        );
+    }
+}
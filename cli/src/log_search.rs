@@ -0,0 +1,236 @@
+//! `jjka log-search`: find the revisions that added or removed a string,
+//! like `git log -S` and `-G`.
+//!
+//! `-S` looks for revisions that change how often the string occurs in a
+//! file, so moving a line doesn't count; `-G` for revisions whose added or
+//! removed lines match a regular expression. Each revision is diffed
+//! against its parents, up to `--jobs` at a time.
+
+use anyhow::{Result, bail};
+use futures::StreamExt as _;
+use jj_lib::commit::Commit;
+use jj_lib::diff::{ContentDiff, DiffHunkKind};
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPathBuf;
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::format::{OutputFormat, commit_summary, print_nul_terminated, subject};
+use crate::perf;
+use crate::progress::Progress;
+use crate::template::Template;
+use crate::tree_diff::{changed_paths, read_file};
+use crate::workspace::WorkspaceHelper;
+
+/// The options that say what `log-search` looks for.
+#[derive(clap::Args, Clone, Debug)]
+pub struct PickaxeArgs {
+    /// Find revisions that change the number of occurrences of STRING
+    #[arg(short = 'S', value_name = "STRING")]
+    string: Option<String>,
+
+    /// Find revisions whose added or removed lines match REGEX
+    #[arg(short = 'G', value_name = "REGEX")]
+    regex: Option<String>,
+
+    /// Treat the -S string as a regular expression
+    #[arg(long, requires = "string")]
+    pickaxe_regex: bool,
+}
+
+/// What to look for in each revision's changes.
+#[derive(Debug)]
+enum Pickaxe {
+    /// Changes in the number of matches.
+    Occurrences(Regex),
+    /// Added or removed lines that match.
+    Lines(Regex),
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct FileMatch {
+    path: String,
+    /// Matches (for `-G`, matching lines) the revision adds.
+    added: usize,
+    /// Matches the revision removes.
+    removed: usize,
+}
+
+#[derive(Serialize)]
+struct RevisionMatch {
+    commit_id: String,
+    change_id: String,
+    subject: String,
+    files: Vec<FileMatch>,
+}
+
+impl Pickaxe {
+    /// How many matches the change from `before` to `after` adds and
+    /// removes, or `None` if it doesn't touch any.
+    fn check(&self, before: &[u8], after: &[u8]) -> Option<(usize, usize)> {
+        let (added, removed) = match self {
+            Pickaxe::Occurrences(regex) => {
+                let before = regex.find_iter(before).count();
+                let after = regex.find_iter(after).count();
+                (after.saturating_sub(before), before.saturating_sub(after))
+            }
+            Pickaxe::Lines(regex) => {
+                let matching = |content: &[u8]| {
+                    content
+                        .split_inclusive(|b| *b == b'\n')
+                        .filter(|line| regex.is_match(line))
+                        .count()
+                };
+                let diff = ContentDiff::by_line([before, after]);
+                diff.hunks()
+                    .filter(|hunk| hunk.kind == DiffHunkKind::Different)
+                    .fold((0, 0), |(added, removed), hunk| {
+                        (
+                            added + matching(hunk.contents[1]),
+                            removed + matching(hunk.contents[0]),
+                        )
+                    })
+            }
+        };
+        (added > 0 || removed > 0).then_some((added, removed))
+    }
+}
+
+async fn search_commit(
+    repo: &ReadonlyRepo,
+    commit: &Commit,
+    pickaxe: &Pickaxe,
+    prefixes: &[RepoPathBuf],
+) -> Result<Vec<FileMatch>> {
+    if commit.id() == repo.store().root_commit_id() {
+        return Ok(Vec::new());
+    }
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree_async().await?;
+    let mut files = Vec::new();
+    for change in changed_paths(&parent_tree, &tree).await? {
+        if !prefixes.is_empty()
+            && !prefixes
+                .iter()
+                .any(|prefix| change.path.starts_with(prefix))
+        {
+            continue;
+        }
+        let before = read_file(repo.store(), &change.path, change.before).await?;
+        let after = read_file(repo.store(), &change.path, change.after).await?;
+        if let Some((added, removed)) = pickaxe.check(
+            before.as_deref().unwrap_or_default(),
+            after.as_deref().unwrap_or_default(),
+        ) {
+            files.push(FileMatch {
+                path: change.path.as_internal_file_string().to_string(),
+                added,
+                removed,
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// `string` is `-S`, matched literally unless `pickaxe_regex`; `regex` is
+/// `-G`. Exactly one of them is set.
+pub async fn log_search_command(
+    args: PickaxeArgs,
+    revset: String,
+    paths: Vec<String>,
+    null: bool,
+    template: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let template = template.as_deref().map(Template::parse).transpose()?;
+    let pickaxe = match (args.string, args.regex) {
+        (Some(string), None) if args.pickaxe_regex => Pickaxe::Occurrences(Regex::new(&string)?),
+        (Some(string), None) => Pickaxe::Occurrences(Regex::new(&regex::escape(&string))?),
+        (None, Some(regex)) => Pickaxe::Lines(Regex::new(&regex)?),
+        _ => bail!("Expected exactly one of -S and -G"),
+    };
+    let helper = WorkspaceHelper::load()?;
+    let repo = helper.repo().clone();
+    let prefixes: Arc<Vec<RepoPathBuf>> = Arc::new(
+        paths
+            .iter()
+            .map(|path| helper.parse_file_path(path))
+            .collect::<Result<_>>()?,
+    );
+    let pickaxe = Arc::new(pickaxe);
+    // Newest first, like `jj log`.
+    let mut commits = helper.evaluate_revset(&revset)?;
+    commits.reverse();
+    let mut progress = Progress::new("log-search", Some(commits.len()));
+    let mut results = futures::stream::iter(commits)
+        .map(|commit| {
+            progress.start_item(&commit_summary(&commit));
+            let repo = repo.clone();
+            let pickaxe = pickaxe.clone();
+            let prefixes = prefixes.clone();
+            tokio::spawn(async move {
+                let files = search_commit(&repo, &commit, &pickaxe, &prefixes).await;
+                (commit, files)
+            })
+        })
+        .buffered(perf::jobs());
+    let mut found = Vec::new();
+    while let Some(result) = results.next().await {
+        let (commit, files) = result?;
+        let files = files?;
+        if files.is_empty() {
+            continue;
+        }
+        if format == OutputFormat::Text && !null {
+            match &template {
+                Some(template) => print!("{}", template.render_line(repo.as_ref(), &commit)?),
+                None => println!("{}", commit_summary(&commit)),
+            }
+            for file in &files {
+                println!("  {}: +{} -{}", file.path, file.added, file.removed);
+            }
+        }
+        found.push(RevisionMatch {
+            commit_id: commit.id().hex(),
+            change_id: commit.change_id().reverse_hex(),
+            subject: subject(&commit).to_string(),
+            files,
+        });
+    }
+    drop(results);
+    progress.finish();
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&found)?),
+        OutputFormat::Text if null => print_nul_terminated(
+            found
+                .iter()
+                .flat_map(|revision| &revision.files)
+                .map(|file| file.path.as_str()),
+        )?,
+        OutputFormat::Text => {}
+    }
+    if found.is_empty() {
+        bail!("No revisions in {revset} add or remove matches");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pickaxe_check() {
+        let occurrences = Pickaxe::Occurrences(Regex::new(&regex::escape("foo()")).unwrap());
+        assert_eq!(occurrences.check(b"a\n", b"foo()\nfoo()\n"), Some((2, 0)));
+        // Moving a line doesn't change how often it occurs.
+        assert_eq!(occurrences.check(b"foo()\nb\n", b"b\nfoo()\n"), None);
+
+        let lines = Pickaxe::Lines(Regex::new("fo+").unwrap());
+        assert_eq!(lines.check(b"foo()\nb\n", b"b\nfoo()\n"), Some((1, 1)));
+        assert_eq!(lines.check(b"foo\nb\n", b"foo\nc\n"), None);
+    }
+}
@@ -0,0 +1,316 @@
+//! Diagnostic logging with `tracing`, for troubleshooting slow operations.
+//!
+//! Nothing but warnings is logged unless `-v` (debug events and spans of
+//! jjka and jjka-lib) or `-vv` (everything, including jj-lib's) is passed, or
+//! `JJKA_LOG` is set to a filter such as `debug` or `jjka=trace,jj_lib=info`.
+//! Each span logs how long it took when it closes, so a log shows where the
+//! time went: loading the repo, computing diffs, committing the transaction.
+//!
+//! jjka only needs a small part of what a full subscriber offers, so this
+//! module has its own, writing one line per event to stderr.
+
+use anyhow::{Result, anyhow, bail};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::str::FromStr as _;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+
+/// One `target=level` part of a filter. A directive without a target applies
+/// to everything no other directive matches.
+#[derive(Debug, PartialEq, Eq)]
+struct Directive {
+    target: Option<String>,
+    level: LevelFilter,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Filter {
+    directives: Vec<Directive>,
+}
+
+impl Filter {
+    /// The filter for `-v` passed `verbose` times.
+    fn for_verbosity(verbose: u8) -> Filter {
+        let spec = match verbose {
+            0 => "warn",
+            1 => "warn,jjka=debug,jjka_lib=debug",
+            _ => "debug,jjka=trace,jjka_lib=trace",
+        };
+        Filter::parse(spec).unwrap()
+    }
+
+    fn parse(spec: &str) -> Result<Filter> {
+        let mut directives = Vec::new();
+        for part in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            let (target, level) = match part.split_once('=') {
+                Some((target, level)) => (Some(target.to_string()), level),
+                None => (None, part),
+            };
+            let level = LevelFilter::from_str(level).map_err(|_| {
+                anyhow!("Invalid log level {level:?} in JJKA_LOG; expected off, error, warn, info, debug or trace")
+            })?;
+            directives.push(Directive { target, level });
+        }
+        if directives.is_empty() {
+            bail!("JJKA_LOG is empty; set it to a level such as debug, or unset it");
+        }
+        Ok(Filter { directives })
+    }
+
+    /// The level for `target` from the directive with the longest matching
+    /// target.
+    fn level(&self, target: &str) -> LevelFilter {
+        let matches = |prefix: &str| {
+            target == prefix
+                || target
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with("::"))
+        };
+        self.directives
+            .iter()
+            .filter(|directive| directive.target.as_deref().is_none_or(matches))
+            .max_by_key(|directive| {
+                directive
+                    .target
+                    .as_ref()
+                    .map_or(0, |target| target.len() + 1)
+            })
+            .map_or(LevelFilter::OFF, |directive| directive.level)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level(metadata.target())
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|directive| directive.level)
+            .max()
+            .unwrap_or(LevelFilter::OFF)
+    }
+}
+
+/// Collects the fields of a span or event as ` name=value` pairs, keeping an
+/// event's message apart.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl tracing::field::Visit for Fields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.rest, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.rest, " {}={value:?}", field.name());
+        }
+    }
+}
+
+struct Span {
+    metadata: &'static Metadata<'static>,
+    fields: String,
+    started: Instant,
+    refs: usize,
+}
+
+struct Logger {
+    filter: Filter,
+    started: Instant,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, Span>>,
+}
+
+thread_local! {
+    /// The spans entered on this thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Logger {
+    fn write_line(&self, metadata: &Metadata<'_>, message: &str) {
+        let context = ENTERED.with(|entered| {
+            let spans = self.spans.lock().unwrap();
+            entered
+                .borrow()
+                .iter()
+                .filter_map(|id| spans.get(id))
+                .map(|span| format!("{}: ", span.metadata.name()))
+                .collect::<String>()
+        });
+        let _ = writeln!(
+            std::io::stderr().lock(),
+            "{:>8.3}s {:>5} {}: {context}{message}",
+            self.started.elapsed().as_secs_f64(),
+            metadata.level(),
+            metadata.target(),
+        );
+    }
+}
+
+impl Subscriber for Logger {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.filter.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.max_level())
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        self.spans.lock().unwrap().insert(
+            id,
+            Span {
+                metadata: attributes.metadata(),
+                fields: fields.rest,
+                started: Instant::now(),
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            span.fields.push_str(&fields.rest);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.write_line(
+            event.metadata(),
+            &format!("{}{}", fields.message, fields.rest),
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(index) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(index);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            span.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let closed = {
+            let mut spans = self.spans.lock().unwrap();
+            match spans.get_mut(&span.into_u64()) {
+                Some(data) if data.refs > 1 => {
+                    data.refs -= 1;
+                    None
+                }
+                Some(_) => spans.remove(&span.into_u64()),
+                None => None,
+            }
+        };
+        let Some(closed) = closed else {
+            return false;
+        };
+        self.write_line(
+            closed.metadata,
+            &format!(
+                "{}{} took {:.1?}",
+                closed.metadata.name(),
+                closed.fields,
+                closed.started.elapsed()
+            ),
+        );
+        true
+    }
+}
+
+/// Installs the logger for the rest of the process. `JJKA_LOG`, if set,
+/// takes precedence over `-v`.
+pub fn init(verbose: u8) -> Result<()> {
+    let filter = match std::env::var("JJKA_LOG") {
+        Ok(spec) => Filter::parse(&spec)?,
+        Err(_) => Filter::for_verbosity(verbose),
+    };
+    let logger = Logger {
+        filter,
+        started: Instant::now(),
+        // Span ids must not be zero.
+        next_id: AtomicU64::new(1),
+        spans: Mutex::new(HashMap::new()),
+    };
+    tracing::subscriber::set_global_default(logger)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_levels() {
+        let filter = Filter::parse("info,jjka=trace,jjka::tree_diff=off").unwrap();
+        assert_eq!(filter.level("jj_lib::repo"), LevelFilter::INFO);
+        assert_eq!(filter.level("jjka"), LevelFilter::TRACE);
+        assert_eq!(filter.level("jjka::workspace"), LevelFilter::TRACE);
+        assert_eq!(filter.level("jjka::tree_diff"), LevelFilter::OFF);
+        assert_eq!(filter.level("jjkafoo"), LevelFilter::INFO);
+        assert_eq!(filter.max_level(), LevelFilter::TRACE);
+
+        assert_eq!(
+            Filter::for_verbosity(1).level("jjka::diff"),
+            LevelFilter::DEBUG
+        );
+        assert_eq!(
+            Filter::for_verbosity(1).level("jjka_lib::tree_diff"),
+            LevelFilter::DEBUG
+        );
+        assert_eq!(Filter::for_verbosity(1).level("jj_lib"), LevelFilter::WARN);
+        assert!(Filter::parse("jjka=loud").is_err());
+        assert!(Filter::parse("").is_err());
+    }
+}
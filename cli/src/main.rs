@@ -1,15 +1,145 @@
-use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
+use anyhow::Result;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use itertools::Itertools;
-use jj_lib::config::StackedConfig;
-use jj_lib::repo::StoreFactories;
-use jj_lib::settings::UserSettings;
-use jj_lib::workspace::{Workspace, default_working_copy_factories};
-use std::collections::HashSet;
+use jj_lib::repo::Repo as _;
+use jj_lib::repo_path::RepoPathBuf;
+use std::ffi::OsString;
+use std::io::Write as _;
+use format::{ColorChoice, OutputFormat};
+use jjka_lib::{LineIndex, LineRange, coalesce_ranges, fingerprint, tree_diff, unified_diff};
+use workspace::{PolicyArgs, WorkspaceHelper};
+
+mod apply_plan;
+mod assert;
+mod autosplit;
+mod backport;
+mod backup;
+mod bench;
+mod bisect;
+mod cache;
+mod changelog;
+mod checks;
+mod codeowners;
+mod completions;
+mod conflicts;
+mod copy_hunks;
+mod curl;
+mod describe;
+mod diff;
+mod diff_editor;
+mod diff_tool;
+mod duplicate_partial;
+mod evolve;
+mod exit_code;
+mod failpoint;
+mod filter;
+mod find_large;
+mod flag_defaults;
+mod forge;
+mod format;
+mod from_git;
+mod fsmonitor;
+mod gerrit;
+mod github;
+mod gitlab;
+mod grep;
+mod highlight;
+mod hooks;
+mod hunks;
+mod impact;
+mod interrupt;
+mod land;
+mod line_edit;
+mod link_issue;
+mod lint;
+mod log_search;
+mod logging;
+mod mcp;
+mod megamerge;
+mod move_hunks;
+mod op_gc;
+mod pager;
+mod patch_id;
+mod perf;
+mod plugin;
+mod prefetch;
+mod progress;
+mod remote;
+mod resolve;
+mod revert_lines;
+mod review;
+mod rpc;
+mod scratch;
+mod serve;
+mod sign;
+mod sigpipe;
+mod snapshot;
+mod split;
+mod split_renames;
+mod stack;
+mod state_file;
+mod stats;
+mod submit;
+mod sync;
+mod template;
+mod terminal;
+mod tidy;
+mod trailer;
+mod ui;
+mod verify;
+mod verify_signatures;
+mod watch;
+mod web;
+mod workspace;
+mod workspaces;
 
 #[derive(Parser, Debug)]
 #[command(name = "jjka", version, about = "Jujutsu utilities", long_about = None)]
 struct Cli {
+    /// Report progress of long-running commands on stderr (defaults to a
+    /// bar when stderr is a terminal)
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    progress: Option<progress::ProgressFormat>,
+
+    /// Output format: `json` prints one JSON document for commands that
+    /// report data, and the summary of changes made to the repo as JSON
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    /// The same as --format json
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Work on at most N things at once (defaults to jjka.perf.max-threads,
+    /// or the number of CPUs)
+    #[arg(
+        short = 'j',
+        long,
+        global = true,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    jobs: Option<u16>,
+
+    /// Log what jjka is doing to stderr, with timings; -vv also logs jj-lib
+    /// (JJKA_LOG sets a filter instead, e.g. `jjka=trace,jj_lib=info`)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only print results and errors: leave out messages about what the
+    /// command is doing, the summary of changes and progress bars
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Don't send long reports through the pager from ui.pager
+    #[arg(long, global = true)]
+    no_pager: bool,
+
+    /// When to color the output (defaults to ui.color, or auto: when stdout
+    /// is a terminal and NO_COLOR isn't set)
+    #[arg(long, global = true, value_enum, value_name = "WHEN")]
+    color: Option<ColorChoice>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -22,11 +152,19 @@ enum Commands {
     ///   jjka hunksplit src/main.rs:10-20
     ///   jjka hunksplit src/main.rs:10-20 src/lib.rs:5-15
     ///   jjka hunksplit --revision @- src/main.rs:10-20
+    ///   jjka hunksplit --dry-run --emit-plan plan.json src/main.rs:10-20
+    ///   jjka hunksplit --mode run.sh
+    ///   jjka hunksplit --tool meld
     Hunksplit {
         /// Line ranges to include in the new commit (format: path:start-end)
-        #[arg(required = true)]
+        #[arg(required_unless_present_any = ["modes", "interactive", "tool"])]
         ranges: Vec<String>,
 
+        /// Move the change to this file's executable bit to the new commit
+        /// too; it stays in the revision otherwise
+        #[arg(long = "mode", value_name = "PATH")]
+        modes: Vec<String>,
+
         /// The revision to split (defaults to @, the working copy)
         #[arg(short = 'r', long, default_value = "@")]
         revision: String,
@@ -34,204 +172,1805 @@ enum Commands {
         /// Message for the new commit (the one with the selected changes)
         #[arg(short = 'm', long)]
         message: Option<String>,
+
+        /// Pick the changes for the new commit in the diff editor from
+        /// ui.diff-editor, starting from the ranges' selection if any
+        #[arg(short = 'i', long, conflicts_with_all = ["dry_run", "modes"])]
+        interactive: bool,
+
+        /// The diff editor to use instead of ui.diff-editor (implies
+        /// --interactive)
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["dry_run", "modes"])]
+        tool: Option<String>,
+
+        /// Preview the split without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Write the split to FILE as JSON, for `jjka apply-plan`
+        #[arg(long, value_name = "FILE", requires = "dry_run")]
+        emit_plan: Option<std::path::PathBuf>,
+
+        /// Split even if some ranges select only unchanged lines
+        #[arg(long)]
+        force: bool,
+
+        /// Split even if the new commit or the revision is left with no
+        /// changes, keeping it as an empty placeholder
+        #[arg(long)]
+        allow_empty: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
     },
-}
 
-#[derive(Debug, Clone)]
-struct LineRange {
-    path: String,
-    start: usize, // 1-indexed, inclusive
-    end: usize,   // 1-indexed, inclusive
-}
+    /// Split a revision as planned by `hunksplit --dry-run --emit-plan`
+    ///
+    /// The plan can be reviewed and edited first: its ranges and message are
+    /// used as they are in the file. If the revision has been rewritten since
+    /// the plan was made, the ranges follow its hunks' fingerprints, and the
+    /// plan is refused if any of them got lost.
+    ///
+    /// Examples:
+    ///   jjka apply-plan plan.json
+    ApplyPlan {
+        /// The plan file
+        plan: std::path::PathBuf,
 
-impl LineRange {
-    fn parse(s: &str) -> Result<Self> {
-        let parts: Vec<&str> = s.rsplitn(2, ':').collect();
-        if parts.len() != 2 {
-            bail!("Invalid range format. Expected path:start-end, got: {}", s);
-        }
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
 
-        let (range_str, path_str) = (parts[0], parts[1]);
-        let range_parts: Vec<&str> = range_str.split('-').collect();
-        if range_parts.len() != 2 {
-            bail!(
-                "Invalid range format. Expected start-end, got: {}",
-                range_str
-            );
-        }
+    /// Check that the repo is in the expected state
+    ///
+    /// Evaluates each assertion and exits non-zero if any is false, listing
+    /// the offending revisions. Assertions combine revset-is-empty(REVSET),
+    /// count(REVSET) OP N, file-exists(PATH[, REV]) and
+    /// description-matches(REVSET, REGEX) with !, && and ||.
+    ///
+    /// Examples:
+    ///   jjka assert 'revset-is-empty(conflicts())'
+    ///   jjka assert 'count(trunk()..@) <= 10' 'file-exists(CHANGELOG.md)'
+    ///   jjka assert 'description-matches(trunk()..@, "^[a-z]+: ")'
+    Assert {
+        /// The assertions to check
+        #[arg(required = true)]
+        assertions: Vec<String>,
+    },
 
-        let start = range_parts[0]
-            .parse::<usize>()
-            .context("Failed to parse start line number")?;
-        let end = range_parts[1]
-            .parse::<usize>()
-            .context("Failed to parse end line number")?;
+    /// Split a revision into the commits a strategy chooses
+    ///
+    /// The script, a shell command line run from the workspace root, gets
+    /// the revision and its hunks as JSON on stdin and prints the commits to
+    /// make, each a description and line ranges. They're made in order
+    /// below the revision, which keeps whatever they don't take. With
+    /// --codeowners, each commit gets the files of one set of owners in the
+    /// revision's CODEOWNERS file instead, and with --generated, the
+    /// changes to lockfiles and generated code stay in the revision on top
+    /// of the rest. Without any of these, the path globs of the buckets in
+    /// [jjka.autosplit.rules] choose the commits.
+    ///
+    /// Examples:
+    ///   jjka autosplit --script ./split-by-directory.py
+    ///   jjka autosplit -r @- --script 'python3 tools/split.py' --dry-run
+    ///   jjka autosplit --codeowners
+    ///   jjka autosplit -r @- --generated
+    ///   jjka autosplit -r @-
+    Autosplit {
+        /// The revision to split
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
 
-        if start < 1 || end < 1 {
-            bail!("Line numbers must be >= 1");
-        }
+        /// The strategy script [default: the buckets in
+        /// jjka.autosplit.rules]
+        #[arg(long, value_name = "COMMAND")]
+        script: Option<String>,
 
-        if start > end {
-            bail!("Start line must be <= end line");
-        }
+        /// Make a commit per set of owners in the revision's CODEOWNERS file
+        /// instead of running a script
+        #[arg(long, conflicts_with_all = ["script", "generated"])]
+        codeowners: bool,
 
-        Ok(LineRange {
-            path: path_str.to_string(),
-            start,
-            end,
-        })
-    }
-}
+        /// Move the changes to files other than lockfiles and generated code
+        /// (jjka.autosplit.generated) into a commit below
+        #[arg(long, conflicts_with = "script")]
+        generated: bool,
 
-fn extract_lines_from_content(content: &[u8], ranges: &[LineRange], file_path: &str) -> Vec<u8> {
-    // Find all ranges that apply to this file
-    let applicable_ranges: Vec<_> = ranges
-        .iter()
-        .filter(|r| r.path == file_path)
-        .sorted_by_key(|r| r.start)
-        .collect();
-
-    if applicable_ranges.is_empty() {
-        return Vec::new();
-    }
+        /// Show the commits the script chose without making them
+        #[arg(long)]
+        dry_run: bool,
 
-    let content_str = String::from_utf8_lossy(content);
-    let lines: Vec<&str> = content_str.lines().collect();
-    let mut result_lines = Vec::new();
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
 
-    for range in applicable_ranges {
-        // Convert to 0-indexed
-        let start_idx = range.start.saturating_sub(1);
-        let end_idx = range.end.min(lines.len());
+    /// Cherry-pick commits onto one or more release bookmarks
+    ///
+    /// Each new commit is annotated with "(backported from <change-id>)" and
+    /// a Backport-Patch-Id trailer with the source commit's patch id, which
+    /// its own patch id is compared against. Every target
+    /// gets its own chain of commits and bookmark, and the commits that
+    /// conflict are reported per target.
+    ///
+    /// Examples:
+    ///   jjka backport fix-crash --onto release-1.2
+    ///   jjka backport 'trunk()..fixes' --onto release-1.2 --push
+    ///   jjka backport fix-crash --onto release-1.4,release-1.5 --pr
+    ///   jjka backport fix-crash --onto release-1.5 --lines src/parser.rs:10-20
+    Backport {
+        /// The revisions to backport
+        revset: String,
+
+        /// The bookmarks to cherry-pick onto, repeated or comma-separated
+        #[arg(long, required = true, value_delimiter = ',')]
+        onto: Vec<String>,
+
+        /// Backport only these lines of the revision (format: path:start-end)
+        #[arg(long, num_args = 1..)]
+        lines: Vec<String>,
+
+        /// Bookmark to set on the backported commits (defaults to backport/<onto>/<change-id>)
+        #[arg(short = 'b', long)]
+        bookmark: Option<String>,
+
+        /// Push the resulting bookmarks
+        #[arg(long)]
+        push: bool,
+
+        /// Push the resulting bookmarks and open a review of each into its
+        /// target on the remote's forge
+        #[arg(long)]
+        pr: bool,
+
+        /// The remote to push to
+        #[arg(long, default_value = "origin")]
+        remote: String,
+    },
 
-        if start_idx < lines.len() {
-            for line in &lines[start_idx..end_idx] {
-                result_lines.push(*line);
-            }
-        }
-    }
+    /// Write a portable backup of unpushed work, or restore one
+    ///
+    /// Writes the commits in REVSET, with the bookmarks pointing at them and
+    /// the id of the current operation, to a single file. `backup restore`
+    /// adds them back to any repo that has their base history, such as a
+    /// clone on another machine.
+    ///
+    /// Examples:
+    ///   jjka backup -o work.jjka-backup
+    ///   jjka backup -o feature.jjka-backup -r 'trunk()..feature'
+    ///   jjka backup restore work.jjka-backup
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Backup {
+        #[command(subcommand)]
+        command: Option<backup::BackupCommand>,
+
+        /// The file to write
+        #[arg(short = 'o', long, required = true)]
+        output: Option<std::path::PathBuf>,
+
+        /// The commits to back up
+        #[arg(short = 'r', long, default_value = "mutable()")]
+        revisions: String,
+    },
 
-    result_lines.join("\n").into_bytes()
+    /// Time loading, snapshotting, diffing and committing on this repo
+    ///
+    /// Runs each step once and prints how long it took, to find what makes
+    /// jjka slow on a particular repo. The files of every revision in the
+    /// revset are read and diffed without the cache. The commit step writes
+    /// an empty operation; pass --no-commit to leave the operation log alone.
+    ///
+    /// Examples:
+    ///   jjka bench
+    ///   jjka bench -r 'mutable()' --no-commit
+    ///   jjka --json bench
+    Bench {
+        /// The revisions to read and diff
+        #[arg(short = 'r', long, alias = "revset", default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Skip timing a transaction commit
+        #[arg(long)]
+        no_commit: bool,
+    },
+
+    /// Find the first bad change by running a command on midpoints
+    ///
+    /// Each tested revision is checked out in a temporary workspace and the
+    /// command is run there. Exit code 0 means good, 125 means the revision
+    /// can't be tested, anything else means bad.
+    ///
+    /// Examples:
+    ///   jjka bisect --good v1.0 --bad @ -- cargo test
+    ///   jjka bisect --good 'trunk()' -- ./check.sh
+    Bisect {
+        /// A revision known to be good
+        #[arg(long)]
+        good: String,
+
+        /// A revision known to be bad
+        #[arg(long, default_value = "@")]
+        bad: String,
+
+        /// The command to run
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Generate release notes from conventional-commit descriptions
+    ///
+    /// Groups commits whose subject looks like `type(scope)!: description`
+    /// into Breaking changes, Features and Fixes, as Markdown. Commits and
+    /// issues are linked on the forge at jjka.forge.url, or the one the
+    /// `origin` remote points at.
+    ///
+    /// Examples:
+    ///   jjka changelog
+    ///   jjka changelog -r 'v1.1.0..v1.2.0' --title v1.2.0
+    ///   jjka changelog --all --format json
+    Changelog {
+        /// The revisions to include
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// A heading for the release
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Also list other types of changes and non-conventional commits
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Print a shell completion script
+    ///
+    /// The script calls back into jjka, so revisions complete to bookmarks
+    /// and recent change ids, and line ranges to the files the revision
+    /// changes and then their hunks.
+    ///
+    /// Examples:
+    ///   source <(jjka completions bash)
+    ///   jjka completions zsh > ~/.zfunc/_jjka
+    ///   jjka completions fish > ~/.config/fish/completions/jjka.fish
+    Completions {
+        /// The shell to print the script for
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+
+    /// Complete the words typed so far; used by the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        words: Vec<String>,
+    },
+
+    /// List conflicted paths in each revision
+    ///
+    /// For every side and base of a conflict, shows the nearest ancestor
+    /// that introduced that version of the file. A conflict is "new" if the
+    /// revision's parents don't have it and "inherited" otherwise.
+    ///
+    /// Examples:
+    ///   jjka conflicts
+    ///   jjka conflicts -r 'conflicts() & mutable()'
+    ///   jjka conflicts -z -r @ | xargs -0 $EDITOR
+    Conflicts {
+        /// The revisions to inspect
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Only print the paths, each once and followed by a NUL byte
+        #[arg(short = 'z')]
+        null: bool,
+
+        /// Format each revision with a jj template instead, e.g.
+        /// 'change_id.short(8) ++ " " ++ description.first_line()'
+        #[arg(short = 'T', long)]
+        template: Option<String>,
+    },
+
+    /// Copy selected lines of another revision into the working copy
+    ///
+    /// The ranges are line numbers in REV's version of each file. Only the
+    /// parts of the difference between the working copy and REV that make
+    /// up those lines are applied to the working copy; REV is not changed.
+    ///
+    /// Examples:
+    ///   jjka copy-hunks -r feature src/main.rs:10-20
+    ///   jjka copy-hunks -r @-- src/lib.rs:5-15 src/lib.rs:40-42
+    CopyHunks {
+        /// The revision to copy lines from
+        #[arg(short = 'r', long)]
+        revision: String,
+
+        /// Line ranges in REV to copy (format: path:start-end)
+        #[arg(required = true)]
+        ranges: Vec<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Edit a revision's description, starting from a template
+    ///
+    /// Opens the editor like `jj describe`. An empty description starts from
+    /// jjka.describe.template, a conventional-commit subject with the type
+    /// and scope guessed from the touched paths, a BREAKING CHANGE section
+    /// and an issue trailer taken from the bookmark name.
+    ///
+    /// Examples:
+    ///   jjka describe
+    ///   jjka describe -r @-
+    Describe {
+        /// The revision to describe
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Show a revision's changes with the changed words highlighted
+    ///
+    /// Removed lines are compared word by word with the lines that replace
+    /// them, and the words that differ are shown in reverse video. With
+    /// --lines, only the changes that make up those lines are shown, which
+    /// is what splitting them out of the revision would move.
+    ///
+    /// Examples:
+    ///   jjka diff
+    ///   jjka diff -r @- src/
+    ///   jjka diff --lines src/main.rs:10-20
+    Diff {
+        /// The revision to show
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        /// Only show these files or directories
+        paths: Vec<String>,
+
+        /// Only show the changes behind these line ranges (format: path:start-end)
+        #[arg(long, value_name = "RANGE")]
+        lines: Vec<String>,
+
+        /// Lines of context around each change
+        #[arg(long, default_value_t = unified_diff::DEFAULT_CONTEXT)]
+        context: usize,
+    },
+
+    /// Copy selected lines of a revision as a new commit on another revision
+    ///
+    /// The ranges are line numbers in REV's version of each file, like
+    /// hunksplit's. The changes that make up those lines are applied on top
+    /// of ONTO in a new commit with REV's author and description; REV is
+    /// not changed.
+    ///
+    /// Examples:
+    ///   jjka duplicate-partial -r feature --onto release-1.2 src/main.rs:10-20
+    ///   jjka duplicate-partial --onto other-branch -m 'Fix the lexer' src/lexer.rs:5-15
+    DuplicatePartial {
+        /// The revision to copy lines from
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        /// The revision to put the new commit on
+        #[arg(long)]
+        onto: String,
+
+        /// Line ranges in REV to copy (format: path:start-end)
+        #[arg(required = true)]
+        ranges: Vec<String>,
+
+        /// Message for the new commit (defaults to REV's description)
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Repair divergent changes and the orphans left on their old copies
+    ///
+    /// Finds changes in the revset with more than one visible copy, such as
+    /// after a concurrent rewrite, and takes the copy with the newest
+    /// committer date as the successor. Commits on top of the older copies
+    /// are rebased onto it; with --divergent, the older copies are also
+    /// abandoned or squashed into it. Prints each action taken.
+    ///
+    /// Examples:
+    ///   jjka evolve
+    ///   jjka evolve --divergent abandon
+    ///   jjka evolve -r 'mine() & mutable()' --divergent merge
+    Evolve {
+        /// The revisions to repair
+        #[arg(short = 'r', long, default_value = "mutable()")]
+        revisions: String,
+
+        /// What to do with the older copies of divergent changes
+        #[arg(long, value_enum, default_value_t)]
+        divergent: evolve::DivergentAction,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Remove paths or leaked secrets from history
+    ///
+    /// Rewrites every revision in the revset without the --remove-path
+    /// files and directories, and with the text listed in the
+    /// --replace-text file replaced. Descendants are rebased, change ids are
+    /// kept, and the old and new commit id of each rewritten revision is
+    /// printed.
+    ///
+    /// Examples:
+    ///   jjka filter --remove-path secrets.env
+    ///   jjka filter --replace-text leaked.txt -r 'trunk()..'
+    #[command(group(clap::ArgGroup::new("filters").required(true).multiple(true).args(["remove_paths", "replace_text"])))]
+    Filter {
+        /// A file or directory to remove
+        #[arg(long = "remove-path", value_name = "PATH")]
+        remove_paths: Vec<String>,
+
+        /// A file of text to replace, one `TEXT[==>REPLACEMENT]` or
+        /// `regex:PATTERN[==>REPLACEMENT]` per line
+        #[arg(long, value_name = "FILE")]
+        replace_text: Option<std::path::PathBuf>,
+
+        /// The revisions to rewrite
+        #[arg(short = 'r', long, default_value = "all()")]
+        revisions: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Find large files in history
+    ///
+    /// Lists every version of a file of at least --threshold that a
+    /// revision in the revset introduced, the total size of those versions
+    /// for each path, and a command to filter them out of history.
+    ///
+    /// Examples:
+    ///   jjka find-large
+    ///   jjka find-large --threshold 500K -r '::trunk()'
+    FindLarge {
+        /// The revisions to scan
+        #[arg(short = 'r', long, default_value = "all()")]
+        revisions: String,
+
+        /// The smallest size to report, like 500K or 2M
+        #[arg(long, default_value = "1M", value_parser = find_large::parse_size)]
+        threshold: u64,
+    },
+
+    /// Find the jj or jjka way to do something you know from git
+    ///
+    /// Describe a git command or workflow to see what to use instead, or run
+    /// without arguments to pick from a list. Offers to run the suggestion,
+    /// asking for any values it needs.
+    ///
+    /// Examples:
+    ///   jjka from-git
+    ///   jjka from-git "git add -p"
+    ///   jjka from-git --run stash
+    FromGit {
+        /// The git command or workflow, e.g. "rebase -i" or "stash"
+        query: Option<String>,
+
+        /// Run the suggested command
+        #[arg(long)]
+        run: bool,
+    },
+
+    /// Push a stack to Gerrit for review and see how its review is going
+    ///
+    /// `push` adds a Change-Id trailer, made from the change id, to each
+    /// commit that lacks one, then pushes the stack to refs/for/<branch>;
+    /// pushing again after a rewrite uploads new patch sets of the same
+    /// changes. `status` shows each change's number, state and votes.
+    ///
+    /// Examples:
+    ///   jjka gerrit push
+    ///   jjka gerrit push -r 'trunk()..feature' --branch stable-2.1
+    ///   jjka gerrit status
+    Gerrit {
+        #[command(subcommand)]
+        command: gerrit::GerritCommand,
+    },
+
+    /// Search the files of each revision in a revset
+    ///
+    /// Prints the change id, path, line number and line of every match,
+    /// newest revision first. PATTERN is a regular expression. Exits with an
+    /// error if nothing matches, like grep.
+    ///
+    /// Examples:
+    ///   jjka grep 'TODO' -r 'trunk()..@'
+    ///   jjka grep -F 'unwrap()' -r 'mutable()' src/
+    ///   jjka grep -z 'FIXME' | xargs -0 $EDITOR
+    Grep {
+        /// The regular expression to search for
+        pattern: String,
+
+        /// The revisions to search
+        #[arg(short = 'r', long, default_value = "@")]
+        revisions: String,
+
+        /// Only search these files or directories
+        paths: Vec<String>,
+
+        /// Match case-insensitively
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+
+        /// Treat PATTERN as a literal string
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+
+        /// Only print the paths, each once and followed by a NUL byte
+        #[arg(short = 'z')]
+        null: bool,
+    },
+
+    /// List hunks with their fingerprints, or remap selectors after a rewrite
+    ///
+    /// With --remap-from, each selector written against the older revision is
+    /// located in the newer one by hunk content and reported as unchanged,
+    /// relocated, fuzzy (its surroundings changed) or lost.
+    ///
+    /// Examples:
+    ///   jjka hunks -r @-
+    ///   jjka hunks -r @ --remap-from 3f2a9c1b src/main.rs:10-20
+    Hunks {
+        /// The revision whose hunks to list
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        /// An older version of the change to remap selectors from
+        #[arg(long)]
+        remap_from: Option<String>,
+
+        /// Line ranges written against --remap-from (defaults to all its hunks)
+        #[arg(requires = "remap_from")]
+        selectors: Vec<String>,
+    },
+
+    /// Land the bottom commit of a stack on trunk
+    ///
+    /// Fetches, then checks that the revision is right on top of trunk()
+    /// and, if the remote's forge is known, that its merge request or pull
+    /// request is approved and CI passed on it. Then pushes it to trunk's
+    /// branch, or with --merge has the forge merge it and rebases the rest
+    /// of the stack onto the new trunk.
+    ///
+    /// Examples:
+    ///   jjka land
+    ///   jjka land -r 'trunk()+' --merge
+    ///   jjka land --remote upstream --dry-run
+    Land {
+        /// The commit to land
+        #[arg(short = 'r', long, default_value = "roots(trunk()..@)")]
+        revision: String,
+
+        /// The remote to land on
+        #[arg(long, default_value = "origin")]
+        remote: String,
+
+        /// Have the forge merge the merge request or pull request instead of
+        /// pushing the commit
+        #[arg(long)]
+        merge: bool,
+
+        /// Run the checks and show how the commit would land
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Mention an issue in every commit of a stack
+    ///
+    /// The issue key is ISSUE, the key in the name of a bookmark on the
+    /// stack (matched with jjka.describe.issue-pattern), or asked for. It is
+    /// added as a trailer, or as a subject prefix with --style prefix.
+    /// Commits that already mention it are left alone.
+    ///
+    /// Examples:
+    ///   jjka link-issue
+    ///   jjka link-issue PROJ-123 -r 'trunk()..feature'
+    ///   jjka link-issue --style prefix
+    LinkIssue {
+        /// The issue key [default: from a bookmark name, or asked for]
+        issue: Option<String>,
+
+        /// The commits to link to the issue
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Add the key as a trailer or a subject prefix [default:
+        /// jjka.issue.style, or trailer]
+        #[arg(long, value_enum)]
+        style: Option<link_issue::IssueStyle>,
+
+        /// Show the commits that would be linked without rewriting them
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Check commit descriptions against the rules in [jjka.lint]
+    ///
+    /// Flags empty descriptions, long subjects and WIP markers, and if
+    /// configured, subjects that aren't conventional commits and missing
+    /// trailers. Exits with an error if any commit has a problem. An empty
+    /// working-copy commit without a description is skipped.
+    ///
+    /// Examples:
+    ///   jjka lint
+    ///   jjka lint -r 'remote_bookmarks()..@'
+    ///   jjka lint -r 'trunk()..' --format json
+    Lint {
+        /// The revisions to check
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+    },
+
+    /// Find the revisions that added or removed a string
+    ///
+    /// With -S, lists revisions that change how many times the string
+    /// occurs in a file; with -G, revisions whose added or removed lines
+    /// match a regular expression. Revisions are diffed against their
+    /// parents and listed newest first. Exits with an error if none match.
+    ///
+    /// Examples:
+    ///   jjka log-search -S 'parse_config'
+    ///   jjka log-search -G 'fn \w+_legacy' -r 'trunk()..@' src/
+    ///   jjka log-search -S 'OldName' -z | xargs -0 grep -l OldName
+    #[command(group(clap::ArgGroup::new("pickaxe").required(true).args(["string", "regex"])))]
+    LogSearch {
+        #[command(flatten)]
+        pickaxe: log_search::PickaxeArgs,
+
+        /// The revisions to search
+        #[arg(short = 'r', long, default_value = "::@")]
+        revisions: String,
+
+        /// Only look at changes to these files or directories
+        paths: Vec<String>,
+
+        /// Only print the paths, each once and followed by a NUL byte
+        #[arg(short = 'z')]
+        null: bool,
+
+        /// Format each revision with a jj template instead, e.g.
+        /// 'change_id.short(8) ++ " " ++ description.first_line()'
+        #[arg(short = 'T', long)]
+        template: Option<String>,
+    },
+
+    /// Serve jjka's tools to coding agents over the Model Context Protocol
+    ///
+    /// Speaks MCP on stdin and stdout, offering the tools list_hunks,
+    /// split_revision, absorb and describe, so an agent can inspect and tidy
+    /// up its commits with structured arguments. Configure it in the agent as
+    /// a stdio server running `jjka mcp` in the workspace.
+    ///
+    /// Examples:
+    ///   jjka mcp
+    Mcp,
+
+    /// Maintain a merge of several features to work on top of
+    ///
+    /// `create` merges the given heads into a new commit with the working
+    /// copy on top, and `add` and `remove` change which features it merges.
+    /// `update` rebases the merge onto each feature's newest commit, so work
+    /// stacked on a feature shows up in the megamerge.
+    ///
+    /// Examples:
+    ///   jjka megamerge create parser-fix lexer-rewrite
+    ///   jjka megamerge add docs-update
+    ///   jjka megamerge update
+    Megamerge {
+        #[command(subcommand)]
+        command: megamerge::MegamergeCommand,
+    },
+
+    /// Move selected lines of one revision's changes into another
+    ///
+    /// The ranges are line numbers in FROM's version of each file, like
+    /// hunksplit's. The changes that make up those lines are taken out of
+    /// FROM and applied to TO, which may be any other mutable revision, and
+    /// the commits between and after them are rebased in one operation.
+    ///
+    /// Examples:
+    ///   jjka move --to @- src/main.rs:10-20
+    ///   jjka move --from feature --to 'description("Add parser")' src/lib.rs:5-15
+    Move {
+        /// The revision to take the lines from
+        #[arg(long, default_value = "@")]
+        from: String,
+
+        /// The revision to move the lines into
+        #[arg(long)]
+        to: String,
+
+        /// Line ranges in FROM to move (format: path:start-end)
+        #[arg(required = true)]
+        ranges: Vec<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Prune old operations and collect the commits only they reference
+    ///
+    /// Keeps every operation that is among the --keep newest or newer than
+    /// --keep-days, and drops the history before that, along with commits
+    /// no remaining operation can reach. Saved snapshots are kept. Other
+    /// workspaces may need `jj workspace update-stale` afterwards.
+    ///
+    /// Examples:
+    ///   jjka op-gc --dry-run
+    ///   jjka op-gc --keep-days 30 --keep 100
+    OpGc {
+        /// Keep operations newer than this many days
+        #[arg(long, default_value_t = 30)]
+        keep_days: u32,
+
+        /// Keep at least this many of the newest operations
+        #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+        keep: u32,
+
+        /// Report what would be pruned without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Diff a revset ahead of time so later commands respond instantly
+    ///
+    /// Computes the hunks of every revision in the revset, in parallel, and
+    /// caches them in `.jj/repo/jjka/cache`, where `hunks`, shell
+    /// completions and the MCP server find them. Revisions are cached by
+    /// commit id, so entries never go stale; --clear deletes them all.
+    ///
+    /// Examples:
+    ///   jjka prefetch
+    ///   jjka prefetch -r 'mutable()'
+    ///   jjka prefetch --clear
+    Prefetch {
+        /// The revisions to prefetch
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Delete the cache instead
+        #[arg(long, conflicts_with = "revisions")]
+        clear: bool,
+    },
+
+    /// Answer JSON-RPC requests from editor plugins
+    ///
+    /// Reads JSON-RPC 2.0 requests, one per line, from stdin or a unix
+    /// socket and writes one response line per request. The methods are
+    /// `status`, `previewSplit`, `applySplit` and `annotate`.
+    ///
+    /// Examples:
+    ///   jjka serve
+    ///   jjka serve --socket /tmp/jjka.sock
+    Serve {
+        /// Listen on this unix socket instead of using stdin and stdout
+        #[arg(long, value_name = "PATH")]
+        socket: Option<std::path::PathBuf>,
+    },
+
+    /// Sign revisions with the configured GPG or SSH key
+    ///
+    /// Rewrites every unsigned revision in the revset with a signature from
+    /// jj's signing backend (`signing.backend` and `signing.key`), whoever
+    /// authored it, and rebases their descendants.
+    ///
+    /// Examples:
+    ///   jjka sign
+    ///   jjka sign -r 'mutable()' --key ~/.ssh/id_ed25519.pub
+    Sign {
+        /// The revisions to sign
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// The key to sign with, instead of signing.key
+        #[arg(long)]
+        key: Option<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Save and restore named snapshots of the working-copy changes
+    ///
+    /// Like `git stash`: `save` records the changes in @ as a hidden commit
+    /// and resets @ to its parent, `restore` applies them to @ again, on top
+    /// of whatever it contains by then.
+    ///
+    /// Examples:
+    ///   jjka snapshot save wip-parser
+    ///   jjka snapshot list
+    ///   jjka snapshot restore wip-parser --drop
+    Snapshot {
+        #[command(subcommand)]
+        command: snapshot::SnapshotCommand,
+    },
+
+    /// Split renamed or copied files out from the edits made to them
+    ///
+    /// Finds files the revision renames or copies and also modifies, and
+    /// splits it into a commit with only the renames and copies, followed by
+    /// the revision's other changes. The commit with the edits keeps the
+    /// change id and description.
+    ///
+    /// Examples:
+    ///   jjka split-renames
+    ///   jjka split-renames -r @- --edits-first
+    ///   jjka split-renames --dry-run
+    SplitRenames {
+        /// The revision to split
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        /// Put the edits first and the renames on top
+        #[arg(long)]
+        edits_first: bool,
+
+        /// List the renames and copies without splitting
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Squash selected lines of a revision into its parent
+    ///
+    /// The ranges are line numbers in REV's version of each file, like
+    /// hunksplit's. The changes that make up those lines move into REV's
+    /// parent and the rest stay in REV, like `jjka move --to REV-`.
+    ///
+    /// Examples:
+    ///   jjka squash-ranges src/main.rs:10-20
+    ///   jjka squash-ranges -r @- src/lib.rs:5-15 src/lib.rs:40-42
+    SquashRanges {
+        /// The revision to take the lines from
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        /// Line ranges in REV to squash (format: path:start-end)
+        #[arg(required = true)]
+        ranges: Vec<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// List a stack with the CI status of each pushed commit
+    ///
+    /// Shows each commit, newest first, with the remote branch that points
+    /// at it and whether CI passed, failed or is still running there, from
+    /// GitHub's check runs or GitLab's pipelines. The bottom commits that
+    /// passed are reported as safe to land.
+    ///
+    /// Examples:
+    ///   jjka stack
+    ///   jjka stack -r 'trunk()..feature' --remote upstream
+    ///   jjka stack --format json
+    Stack {
+        /// The commits to list
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// The remote whose branches and CI to look at
+        #[arg(long, default_value = "origin")]
+        remote: String,
+
+        /// Format each revision with a jj template instead, e.g.
+        /// 'change_id.short(8) ++ " " ++ description.first_line()'
+        #[arg(short = 'T', long)]
+        template: Option<String>,
+    },
+
+    /// Report line churn per file and directory
+    ///
+    /// Examples:
+    ///   jjka stats
+    ///   jjka stats -r 'trunk()..@' --top 5
+    ///   jjka stats -r 'author(alice)' --format json
+    Stats {
+        /// The revisions to analyze
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Number of paths in the "most churned" table
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Resolve conflicts with an external merge tool
+    ///
+    /// Runs the merge tool named by ui.merge-editor (or --tool) on each
+    /// 2-sided file conflict, configured under [merge-tools] like for
+    /// `jj resolve`, and records the results in the revision.
+    ///
+    /// Examples:
+    ///   jjka resolve
+    ///   jjka resolve -r @- src/parser.rs
+    ///   jjka resolve --tool meld
+    Resolve {
+        /// The revision to resolve conflicts in
+        #[arg(short = 'r', long, default_value = "@")]
+        revision: String,
+
+        /// Only resolve these paths (defaults to all conflicted paths)
+        paths: Vec<String>,
+
+        /// The merge tool to use, instead of ui.merge-editor
+        #[arg(long)]
+        tool: Option<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Step through a stack commit by commit in a full-screen view
+    ///
+    /// Shows each commit's description and diff. Keys: j/k and space to
+    /// scroll, n/p for the next/previous commit, r to mark it reviewed, e to
+    /// edit its description, s to split it with `jj split`, q to quit.
+    /// Review marks are kept until the commit's diff changes.
+    ///
+    /// Examples:
+    ///   jjka review
+    ///   jjka review -r 'main..feature'
+    Review {
+        /// The revisions to review
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Restore line ranges of the working copy to another revision
+    ///
+    /// The line-level version of `jj restore`: the ranges are line numbers
+    /// in the working-copy files, and the lines in them go back to what REV
+    /// has there. The rest of each file is left alone.
+    ///
+    /// Examples:
+    ///   jjka revert-lines -r @- src/main.rs:10-20
+    ///   jjka revert-lines -r trunk() src/lib.rs:5-15 src/lib.rs:40-42
+    RevertLines {
+        /// The revision to restore the lines from
+        #[arg(short = 'r', long)]
+        revision: String,
+
+        /// Line ranges in the working copy to restore (format: path:start-end)
+        #[arg(required = true)]
+        ranges: Vec<String>,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Push a stack as chained GitLab merge requests
+    ///
+    /// Pushes each commit of the stack to a `jjka/<change-id>` branch and
+    /// opens a merge request from it into the branch of the commit below;
+    /// the bottom one merges into trunk's branch. Run it again after
+    /// rewriting the stack to force-push the branches and update the merge
+    /// requests. Each remote's forge is set in `[jjka.forge.remotes.<name>]`.
+    ///
+    /// Examples:
+    ///   jjka submit
+    ///   jjka submit -r 'trunk()..feature' --target develop
+    ///   jjka submit --dry-run
+    Submit {
+        /// The commits to submit, which must form a line
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// The remote to push to
+        #[arg(long, default_value = "origin")]
+        remote: String,
+
+        /// The branch the bottom merge request merges into [default: the
+        /// remote's branch at trunk()]
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Show the branches and merge requests without pushing
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Fetch from the remote and rebase your stacks onto the new trunk
+    ///
+    /// Fetches all branches, then rebases the roots of every stack in
+    /// `trunk()..REVSET` onto trunk() in one transaction, and lists the
+    /// commits that became conflicted or empty.
+    ///
+    /// Examples:
+    ///   jjka sync
+    ///   jjka sync --remote upstream
+    ///   jjka sync -r 'bookmarks(glob:"feature/*")'
+    Sync {
+        /// The remote to fetch from
+        #[arg(long, default_value = "origin")]
+        remote: String,
+
+        /// The commits whose stacks to rebase
+        #[arg(short = 'r', long, default_value = "mine() & mutable()")]
+        revisions: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Clean up a messy stack
+    ///
+    /// Finds empty commits, "fixup!"/"squash!" commits and commits whose
+    /// changes already landed on trunk. With --fix, abandons the empty and
+    /// merged commits and squashes each fixup into the commit it names.
+    ///
+    /// Examples:
+    ///   jjka tidy
+    ///   jjka tidy --fix
+    ///   jjka tidy -r 'mutable() & ::@' --fix
+    Tidy {
+        /// The revisions to tidy
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Apply the changes instead of only listing them
+        #[arg(long)]
+        fix: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Check the store for missing or corrupt objects
+    ///
+    /// Reads every commit in REVSET and all trees, files and symlinks they
+    /// reach. In git-backed repos each object is also re-hashed and compared
+    /// with its id. Exits non-zero if anything is wrong.
+    ///
+    /// Examples:
+    ///   jjka verify
+    ///   jjka verify -r 'mutable()'
+    Verify {
+        /// The commits to check
+        #[arg(short = 'r', long, default_value = "all()")]
+        revisions: String,
+    },
+
+    /// Check that revisions are validly signed
+    ///
+    /// Verifies the signature of every revision in the revset with jj's
+    /// signing backends and lists each with its status: good, UNKNOWN (a
+    /// key that isn't trusted), BAD, UNSIGNED or ERROR. Exits non-zero
+    /// unless they are all good.
+    ///
+    /// Examples:
+    ///   jjka verify-signatures
+    ///   jjka verify-signatures -r 'trunk()..main' --allow-unknown
+    VerifySignatures {
+        /// The revisions to check
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+
+        /// Accept signatures by keys that aren't trusted
+        #[arg(long)]
+        allow_unknown: bool,
+
+        /// Format each revision with a jj template instead, e.g.
+        /// 'change_id.short(8) ++ " " ++ description.first_line()'
+        #[arg(short = 'T', long)]
+        template: Option<String>,
+    },
+
+    /// Snapshot the working copy whenever files change
+    ///
+    /// Prints each snapshot that changed something with its diffstat and
+    /// the totals since the watch started. With --exec, runs a shell command
+    /// after each snapshot with $JJKA_COMMIT_ID and $JJKA_CHANGE_ID set.
+    ///
+    /// Examples:
+    ///   jjka watch
+    ///   jjka watch --exec 'cargo check --quiet'
+    ///   jjka watch --poll --interval 2000
+    Watch {
+        /// Milliseconds without changes before snapshotting, or between
+        /// snapshots with --poll
+        #[arg(long, default_value_t = 500)]
+        interval: u64,
+
+        /// Snapshot every --interval instead of waiting for file system
+        /// events
+        #[arg(long)]
+        poll: bool,
+
+        /// A shell command to run after each snapshot
+        #[arg(long, value_name = "COMMAND")]
+        exec: Option<String>,
+    },
+
+    /// Serve a read-only web UI for browsing the repo
+    ///
+    /// Pages: / (stack), /commit/<id> (diff), /oplog (operation log).
+    /// JSON: /api/log, /api/commit/<id>, /api/oplog.
+    ///
+    /// Examples:
+    ///   jjka web
+    ///   jjka web --port 9000 -r 'mutable()'
+    ///   jjka web --bind 0.0.0.0
+    Web {
+        /// Port to listen on (0 picks a free port)
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to listen on (use 0.0.0.0 to share on the LAN)
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+
+        /// The revisions shown in the stack view
+        #[arg(short = 'r', long, default_value = "trunk()..@")]
+        revisions: String,
+    },
+
+    /// Add, list and forget workspaces
+    ///
+    /// Each workspace is a separate working copy of the same repo with its
+    /// own working-copy commit, handy for running a build in one while
+    /// editing in another. `add` creates it next to this one unless given
+    /// --destination.
+    ///
+    /// Examples:
+    ///   jjka workspace add build
+    ///   jjka workspace add review --destination /tmp/review -r main
+    ///   jjka workspace list
+    ///   jjka workspace forget build
+    Workspace {
+        #[command(subcommand)]
+        command: workspaces::WorkspaceCommand,
+    },
+
+    /// Any other command runs `jjka-<command>` from PATH
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
 }
 
-fn extract_complement_lines(content: &[u8], ranges: &[LineRange], file_path: &str) -> Vec<u8> {
-    // Find all ranges that apply to this file
-    let applicable_ranges: Vec<_> = ranges
-        .iter()
-        .filter(|r| r.path == file_path)
-        .sorted_by_key(|r| r.start)
-        .collect();
-
-    if applicable_ranges.is_empty() {
-        return content.to_vec();
-    }
+/// The 1-indexed lines that `ranges` select in a file of `line_count` lines
+/// at `file_path`, in order. Overlapping ranges are merged, so each line
+/// comes once.
+fn selected_lines<'a>(
+    ranges: &'a [LineRange],
+    file_path: &'a str,
+    line_count: usize,
+) -> impl Iterator<Item = usize> + Clone + 'a {
+    coalesce_ranges(ranges.iter().filter(|r| r.path == file_path).map(|r| (r.start, r.end)))
+        .into_iter()
+        .flat_map(move |(start, end)| start.max(1)..=end.min(line_count))
+}
 
-    let content_str = String::from_utf8_lossy(content);
-    let lines: Vec<&str> = content_str.lines().collect();
-    let mut excluded_lines = HashSet::new();
+/// The 1-indexed lines of the file at `file_path` that no range selects.
+fn unselected_lines<'a>(
+    ranges: &'a [LineRange],
+    file_path: &'a str,
+    line_count: usize,
+) -> impl Iterator<Item = usize> + Clone + 'a {
+    (1..=line_count).filter(move |line| {
+        !ranges
+            .iter()
+            .any(|r| r.path == file_path && (r.start..=r.end).contains(line))
+    })
+}
 
-    // Mark all lines that should be excluded
-    for range in &applicable_ranges {
-        let start_idx = range.start.saturating_sub(1);
-        let end_idx = range.end.min(lines.len());
+/// Makes `bytes` valid UTF-8, copying them only if they aren't already.
+fn into_utf8_lossy(bytes: Vec<u8>) -> Vec<u8> {
+    match String::from_utf8(bytes) {
+        Ok(text) => text.into_bytes(),
+        Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned().into_bytes(),
+    }
+}
 
-        for i in start_idx..end_idx {
-            excluded_lines.insert(i);
+/// Joins lines sliced out of `content` with newlines into one buffer sized
+/// up front, as valid UTF-8.
+fn join_lines(content: &[u8], index: &LineIndex, lines: impl Iterator<Item = usize> + Clone) -> Vec<u8> {
+    let len: usize = lines
+        .clone()
+        .enumerate()
+        .map(|(i, line)| index.slice(content, line).len() + usize::from(i > 0))
+        .sum();
+    let mut result = Vec::with_capacity(len);
+    for (i, line) in lines.enumerate() {
+        if i > 0 {
+            result.push(b'\n');
         }
+        result.extend_from_slice(index.slice(content, line));
     }
+    into_utf8_lossy(result)
+}
 
-    // Collect lines that are not excluded
-    let result_lines: Vec<_> = lines
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| !excluded_lines.contains(i))
-        .map(|(_, line)| *line)
-        .collect();
+fn extract_lines_from_content(content: &[u8], ranges: &[LineRange], file_path: &str) -> Vec<u8> {
+    let index = LineIndex::new(content);
+    join_lines(content, &index, selected_lines(ranges, file_path, index.len()))
+}
 
-    result_lines.join("\n").into_bytes()
+fn extract_complement_lines(content: &[u8], ranges: &[LineRange], file_path: &str) -> Vec<u8> {
+    if !ranges.iter().any(|r| r.path == file_path) {
+        return content.to_vec();
+    }
+    let index = LineIndex::new(content);
+    join_lines(content, &index, unselected_lines(ranges, file_path, index.len()))
+}
+
+/// The flags of `hunksplit` that say what to do with the ranges.
+struct HunksplitOptions {
+    modes: Vec<String>,
+    message: Option<String>,
+    interactive: bool,
+    tool: Option<String>,
+    dry_run: bool,
+    emit_plan: Option<std::path::PathBuf>,
+    force: bool,
+    allow_empty: bool,
 }
 
 async fn hunksplit_command(
     ranges: Vec<String>,
-    _revision: String,
-    _message: Option<String>,
+    revision: String,
+    options: HunksplitOptions,
+    policy: PolicyArgs,
+    format: OutputFormat,
 ) -> Result<()> {
-    // Parse line ranges
-    let mut parsed_ranges = Vec::new();
-    for range_str in &ranges {
-        parsed_ranges.push(LineRange::parse(range_str)?);
+    let HunksplitOptions {
+        modes,
+        message,
+        interactive,
+        tool,
+        dry_run,
+        emit_plan,
+        force,
+        allow_empty,
+    } = options;
+    let parsed_ranges = workspace::parse_ranges(&ranges)?;
+    let modes = workspace::parse_paths(&modes)?;
+
+    // The ranges are checked before the repo is loaded, so a mistyped one
+    // fails right away. A plain dry run of the working copy reads its files
+    // from disk and doesn't load the repo at all.
+    let previews_working_copy =
+        dry_run && emit_plan.is_none() && modes.is_empty() && revision == "@";
+    let loaded = if previews_working_copy {
+        None
+    } else {
+        let mut workspace = WorkspaceHelper::load()?;
+        workspace.set_policy_args(&policy);
+        workspace.snapshot_working_copy()?;
+        let commit = workspace.resolve_single(&revision)?;
+        let mut plan = split::plan_split_with_modes(
+            workspace.repo().as_ref(),
+            &commit,
+            &parsed_ranges,
+            &modes,
+        )
+        .await?;
+        split::check_unchanged(&plan, force)?;
+        if !dry_run {
+            if interactive || tool.is_some() {
+                let editor = diff_editor::DiffEditor::load(workspace.settings(), tool)?;
+                // Without ranges, the editor starts from all of the changes.
+                let initial = if parsed_ranges.is_empty() {
+                    commit.tree_id()
+                } else {
+                    &plan.selected_tree_id
+                };
+                plan = diff_editor::edit_split(workspace.repo().as_ref(), &commit, initial, &editor)
+                    .await?;
+            }
+            split::check_empty(&plan, allow_empty)?;
+            let (selected, remaining) =
+                split::apply_split(&mut workspace, &plan, message.as_deref()).await?;
+            if format == OutputFormat::Text {
+                split::print_split(&commit, &selected, &remaining);
+            }
+            return Ok(());
+        }
+        if let Some(path) = &emit_plan {
+            split::check_empty(&plan, allow_empty)?;
+            let hunks = hunks::commit_hunks(workspace.repo().as_ref(), &commit).await?;
+            let ranges = parsed_ranges.iter().map(LineRange::to_string).collect();
+            split::PlanFile::new(plan, ranges, modes.clone(), allow_empty, message, hunks)
+                .write(path)?;
+            ui::status!("Wrote the plan to {}; apply it with `jjka apply-plan`", path.display());
+        }
+        Some((workspace, commit))
+    };
+
+    // The revision's version of each file mentioned in the ranges, or `None`
+    // if it isn't a file there.
+    let paths = parsed_ranges.iter().map(|r| r.path.as_str()).unique().sorted();
+    let mut affected_files = Vec::new();
+    let source = if let Some((workspace, commit)) = &loaded {
+        let tree = commit.tree_async().await?;
+        for file_path_str in paths {
+            let path = RepoPathBuf::from_internal_string(file_path_str)?;
+            let value = tree.path_value(&path)?;
+            let content = tree_diff::read_file(workspace.repo().store(), &path, value).await?;
+            affected_files.push((file_path_str, content));
+        }
+        format::commit_summary(commit)
+    } else {
+        let root = workspace::find_root()?;
+        for file_path_str in paths {
+            affected_files.push((file_path_str, std::fs::read(root.join(file_path_str)).ok()));
+        }
+        format!("the working copy at {}", root.display())
+    };
+
+    if format == OutputFormat::Json {
+        let files: Vec<_> = affected_files
+            .iter()
+            .map(|(file_path_str, content)| {
+                let Some(content) = content else {
+                    return serde_json::json!({ "path": file_path_str, "found": false });
+                };
+                let selected_content = extract_lines_from_content(content, &parsed_ranges, file_path_str);
+                let remaining_content = extract_complement_lines(content, &parsed_ranges, file_path_str);
+                serde_json::json!({
+                    "path": file_path_str,
+                    "found": true,
+                    "selected": String::from_utf8_lossy(&selected_content),
+                    "remaining": String::from_utf8_lossy(&remaining_content),
+                })
+            })
+            .collect();
+        let ranges: Vec<_> = parsed_ranges
+            .iter()
+            .map(|range| serde_json::json!({ "path": range.path, "start": range.start, "end": range.end }))
+            .collect();
+        let preview = serde_json::json!({ "ranges": ranges, "modes": modes, "files": files });
+        println!("{}", serde_json::to_string_pretty(&preview)?);
+        return Ok(());
     }
 
-    // Find the workspace
-    let cwd = std::env::current_dir().context("Failed to get current directory")?;
-
-    // Create default user settings with StackedConfig
-    let stacked_config = StackedConfig::empty();
-    let settings = UserSettings::from_config(stacked_config)?;
-
-    // Load workspace with default factories
-    let store_factories = StoreFactories::default();
-    let working_copy_factories = default_working_copy_factories();
-
-    let workspace = Workspace::load(&settings, &cwd, &store_factories, &working_copy_factories)
-        .context("Failed to load workspace")?;
-
-    let _repo = workspace.repo_loader().load_at_head()
-        .context("Failed to load repository")?;
-
-    println!("Successfully loaded repository");
+    ui::status!("Previewing the split of {source}");
     println!("\nParsed line ranges:");
     for range in &parsed_ranges {
         println!("  {} lines {}-{}", range.path, range.start, range.end);
     }
 
-    // Collect all files mentioned in ranges
-    let affected_files: HashSet<_> = parsed_ranges.iter().map(|r| r.path.as_str()).collect();
-
+    let settings = workspace::load_settings()?;
+    let diff_tool = diff_tool::DiffTool::load(&settings)?;
+    let theme = if format::use_color(&settings)? {
+        highlight::Theme::from_settings(&settings)?
+    } else {
+        None
+    };
     println!("\nAffected files:");
-    for file_path_str in affected_files {
+    for (file_path_str, content) in &affected_files {
         println!("  - {}", file_path_str);
+        let language = theme.as_ref().zip(highlight::language_for_path(file_path_str));
 
-        // Try to read the file from the working copy
-        let wc_path = workspace.workspace_root().join(file_path_str);
-        if let Ok(content) = std::fs::read(&wc_path) {
-            let selected_content = extract_lines_from_content(&content, &parsed_ranges, file_path_str);
-            let remaining_content = extract_complement_lines(&content, &parsed_ranges, file_path_str);
+        if let Some(content) = content {
+            let selected_content = extract_lines_from_content(content, &parsed_ranges, file_path_str);
+            let selected_index = LineIndex::new(&selected_content);
+            let mut remaining = extract_complement_lines(content, &parsed_ranges, file_path_str);
 
             println!("    Selected: {} bytes ({} lines)",
                 selected_content.len(),
-                String::from_utf8_lossy(&selected_content).lines().count()
+                selected_index.len()
             );
             println!("    Remaining: {} bytes ({} lines)",
-                remaining_content.len(),
-                String::from_utf8_lossy(&remaining_content).lines().count()
+                remaining.len(),
+                String::from_utf8_lossy(&remaining).lines().count()
             );
 
+            if let Some(tool) = &diff_tool {
+                // The selected lines show up as added to what remains.
+                // Joining the lines drops the file's last newline.
+                if content.ends_with(b"\n") && !remaining.is_empty() {
+                    remaining.push(b'\n');
+                }
+                println!("\n    Selected changes ({}):", tool.name);
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&tool.render(file_path_str, &remaining, content)?)?;
+                stdout.flush()?;
+                continue;
+            }
+
             println!("\n    Selected content:");
-            for line in String::from_utf8_lossy(&selected_content).lines() {
-                println!("      {}", line);
+            // The preview is valid UTF-8 already, so this borrows each line.
+            for line in 1..=selected_index.len() {
+                let line = String::from_utf8_lossy(selected_index.slice(&selected_content, line));
+                match language {
+                    Some((theme, language)) => println!("      {}", theme.paint(language, &line, "")),
+                    None => println!("      {line}"),
+                }
             }
         } else {
-            println!("    (file not found in working copy)");
+            println!("    (not a file in {source})");
+        }
+    }
+
+    if !modes.is_empty() {
+        println!("\nExecutable bit changes to move:");
+        for path in &modes {
+            println!("  - {path}");
         }
     }
 
-    println!("\nNote: This is a preview. Actual commit splitting is not yet implemented.");
-    println!("The jj_lib API for commit creation needs to be properly integrated.");
+    ui::status!("\nNote: This is a dry run; nothing was changed.");
 
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// The subcommand path, like `megamerge add`.
+fn command_name(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        names.push(name);
+        matches = sub_matches;
+    }
+    names.join(" ")
+}
 
-    match cli.command {
+fn main() -> std::process::ExitCode {
+    let result = (|| {
+        let matches = flag_defaults::parse(Cli::command())?;
+        let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        sigpipe::init();
+        logging::init(cli.verbose)?;
+        hooks::init(command_name(&matches));
+        let jobs = perf::init(cli.jobs.map(usize::from))?;
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(jobs)
+            .max_blocking_threads(jobs)
+            .enable_all()
+            .build()?
+            .block_on(run_cli(cli))
+    })();
+    match result {
+        Ok(()) if impact::produced_conflicts() => exit_code::CONFLICTS.into(),
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            exit_code::for_error(&err).into()
+        }
+    }
+}
+
+async fn run_cli(cli: Cli) -> Result<()> {
+    ui::init(cli.quiet);
+    format::init_color(cli.color);
+    progress::init(cli.progress);
+    let format = if cli.json {
+        OutputFormat::Json
+    } else {
+        cli.format
+    };
+    impact::init(format);
+    if !cli.no_pager && cli.command.paginates() {
+        pager::start(matches!(cli.command, Commands::Diff { .. }))?;
+    }
+
+    let result = run(cli.command, format).await;
+    // Wait for the user to quit the pager before printing anything after it.
+    pager::finish();
+    // Report what was changed even if a later step failed.
+    impact::report().await?;
+    result
+}
+
+impl Commands {
+    /// Whether the command prints a report that can be long enough to page.
+    fn paginates(&self) -> bool {
+        matches!(
+            self,
+            Commands::Changelog { .. }
+                | Commands::Conflicts { .. }
+                | Commands::Diff { .. }
+                | Commands::FindLarge { .. }
+                | Commands::Grep { .. }
+                | Commands::Hunks { .. }
+                | Commands::LogSearch { .. }
+                | Commands::Stats { .. }
+                | Commands::VerifySignatures { .. }
+        )
+    }
+}
+
+async fn run(command: Commands, format: OutputFormat) -> Result<()> {
+    match command {
         Commands::Hunksplit {
             ranges,
+            modes,
+            revision,
+            message,
+            interactive,
+            tool,
+            dry_run,
+            emit_plan,
+            force,
+            allow_empty,
+            policy,
+        } => {
+            let options = HunksplitOptions {
+                modes,
+                message,
+                interactive,
+                tool,
+                dry_run,
+                emit_plan,
+                force,
+                allow_empty,
+            };
+            hunksplit_command(ranges, revision, options, policy, format).await?
+        }
+        Commands::ApplyPlan { plan, policy } => {
+            apply_plan::apply_plan_command(&plan, policy, format).await?
+        }
+        Commands::Assert { assertions } => assert::assert_command(assertions, format).await?,
+        Commands::Autosplit {
+            revision,
+            script,
+            codeowners,
+            generated,
+            dry_run,
+            policy,
+        } => {
+            let source = match script {
+                Some(script) => autosplit::Source::Script(script),
+                None if codeowners => autosplit::Source::CodeOwners,
+                None if generated => autosplit::Source::Generated,
+                None => autosplit::Source::Rules,
+            };
+            autosplit::autosplit_command(revision, source, dry_run, policy, format).await?
+        }
+        Commands::Backport {
+            revset,
+            onto,
+            lines,
+            bookmark,
+            push,
+            pr,
+            remote,
+        } => {
+            let options = backport::BackportOptions {
+                lines,
+                bookmark,
+                push,
+                pr,
+                remote,
+            };
+            backport::backport_command(revset, onto, options).await?
+        }
+        Commands::Backup {
+            command,
+            output,
+            revisions,
+        } => backup::backup_command(command, output, revisions).await?,
+        Commands::Bench {
+            revisions,
+            no_commit,
+        } => bench::bench_command(revisions, !no_commit, format).await?,
+        Commands::Bisect { good, bad, command } => {
+            bisect::bisect_command(good, bad, command).await?
+        }
+        Commands::Changelog {
+            revisions,
+            title,
+            all,
+        } => changelog::changelog_command(revisions, title, all, format).await?,
+        Commands::Completions { shell } => completions::completions_command(shell)?,
+        Commands::Complete { words } => completions::complete_command(words).await?,
+        Commands::Conflicts {
+            revisions,
+            null,
+            template,
+        } => conflicts::conflicts_command(revisions, null, template, format).await?,
+        Commands::CopyHunks {
+            revision,
+            ranges,
+            policy,
+        } => copy_hunks::copy_hunks_command(revision, ranges, policy).await?,
+        Commands::Describe { revision, policy } => {
+            describe::describe_command(revision, policy).await?
+        }
+        Commands::Diff {
             revision,
+            paths,
+            lines,
+            context,
+        } => diff::diff_command(revision, paths, lines, context, format).await?,
+        Commands::DuplicatePartial {
+            revision,
+            onto,
+            ranges,
             message,
-        } => hunksplit_command(ranges, revision, message).await?,
+            policy,
+        } => {
+            duplicate_partial::duplicate_partial_command(revision, onto, ranges, message, policy)
+                .await?
+        }
+        Commands::Evolve {
+            revisions,
+            divergent,
+            policy,
+        } => evolve::evolve_command(revisions, divergent, policy).await?,
+        Commands::Filter {
+            remove_paths,
+            replace_text,
+            revisions,
+            policy,
+        } => {
+            filter::filter_command(
+                remove_paths,
+                replace_text.as_deref(),
+                revisions,
+                format,
+                policy,
+            )
+            .await?
+        }
+        Commands::FindLarge {
+            revisions,
+            threshold,
+        } => find_large::find_large_command(revisions, threshold, format).await?,
+        Commands::FromGit { query, run } => from_git::from_git_command(query, run).await?,
+        Commands::Gerrit { command } => gerrit::gerrit_command(command).await?,
+        Commands::Grep {
+            pattern,
+            revisions,
+            paths,
+            ignore_case,
+            fixed_strings,
+            null,
+        } => {
+            grep::grep_command(pattern, revisions, paths, ignore_case, fixed_strings, null, format)
+                .await?
+        }
+        Commands::Hunks {
+            revision,
+            remap_from,
+            selectors,
+        } => hunks::hunks_command(revision, remap_from, selectors, format).await?,
+        Commands::Resolve {
+            revision,
+            paths,
+            tool,
+            policy,
+        } => resolve::resolve_command(revision, paths, tool, policy).await?,
+        Commands::Review { revisions, policy } => {
+            review::review_command(revisions, policy).await?
+        }
+        Commands::Land {
+            revision,
+            remote,
+            merge,
+            dry_run,
+            policy,
+        } => land::land_command(revision, remote, merge, dry_run, policy).await?,
+        Commands::LinkIssue {
+            issue,
+            revisions,
+            style,
+            dry_run,
+            policy,
+        } => link_issue::link_issue_command(issue, revisions, style, dry_run, policy).await?,
+        Commands::Lint { revisions } => lint::lint_command(revisions, format).await?,
+        Commands::LogSearch {
+            pickaxe,
+            revisions,
+            paths,
+            null,
+            template,
+        } => {
+            log_search::log_search_command(pickaxe, revisions, paths, null, template, format)
+                .await?
+        }
+        Commands::Mcp => mcp::mcp_command().await?,
+        Commands::Megamerge { command } => megamerge::megamerge_command(command).await?,
+        Commands::Move {
+            from,
+            to,
+            ranges,
+            policy,
+        } => move_hunks::move_command(from, to, ranges, policy).await?,
+        Commands::OpGc {
+            keep_days,
+            keep,
+            dry_run,
+        } => op_gc::op_gc_command(keep_days, keep, dry_run).await?,
+        Commands::Prefetch { revisions, clear } => {
+            prefetch::prefetch_command(revisions, clear).await?
+        }
+        Commands::RevertLines {
+            revision,
+            ranges,
+            policy,
+        } => revert_lines::revert_lines_command(revision, ranges, policy).await?,
+        Commands::Serve { socket } => serve::serve_command(socket).await?,
+        Commands::Sign {
+            revisions,
+            key,
+            policy,
+        } => sign::sign_command(revisions, key, policy).await?,
+        Commands::Snapshot { command } => snapshot::snapshot_command(command, format).await?,
+        Commands::SplitRenames {
+            revision,
+            edits_first,
+            dry_run,
+            policy,
+        } => {
+            split_renames::split_renames_command(revision, edits_first, dry_run, policy).await?
+        }
+        Commands::SquashRanges {
+            revision,
+            ranges,
+            policy,
+        } => move_hunks::squash_ranges_command(revision, ranges, policy).await?,
+        Commands::Stack {
+            revisions,
+            remote,
+            template,
+        } => stack::stack_command(revisions, remote, template, format).await?,
+        Commands::Stats {
+            revisions,
+            top,
+        } => stats::stats_command(revisions, top, format).await?,
+        Commands::Submit {
+            revisions,
+            remote,
+            target,
+            dry_run,
+            policy,
+        } => submit::submit_command(revisions, remote, target, dry_run, policy).await?,
+        Commands::Sync {
+            remote,
+            revisions,
+            policy,
+        } => sync::sync_command(remote, revisions, policy).await?,
+        Commands::Tidy {
+            revisions,
+            fix,
+            policy,
+        } => tidy::tidy_command(revisions, fix, policy).await?,
+        Commands::Verify { revisions } => verify::verify_command(revisions).await?,
+        Commands::VerifySignatures {
+            revisions,
+            allow_unknown,
+            template,
+        } => {
+            verify_signatures::verify_signatures_command(revisions, allow_unknown, template, format)
+                .await?
+        }
+        Commands::Watch {
+            interval,
+            poll,
+            exec,
+        } => watch::watch_command(interval, poll, exec).await?,
+        Commands::Web {
+            port,
+            bind,
+            revisions,
+        } => web::web_command(bind, port, revisions).await?,
+        Commands::Workspace { command } => workspaces::workspace_command(command, format).await?,
+        Commands::External(args) => plugin::run_plugin(args, format)?,
     }
 
     Ok(())
@@ -241,49 +1980,6 @@ async fn main() -> Result<()> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_line_range_parse_valid() {
-        let range = LineRange::parse("src/main.rs:10-20").unwrap();
-        assert_eq!(range.path, "src/main.rs");
-        assert_eq!(range.start, 10);
-        assert_eq!(range.end, 20);
-    }
-
-    #[test]
-    fn test_line_range_parse_with_colons_in_path() {
-        let range = LineRange::parse("C:/Users/test/file.rs:5-15").unwrap();
-        assert_eq!(range.path, "C:/Users/test/file.rs");
-        assert_eq!(range.start, 5);
-        assert_eq!(range.end, 15);
-    }
-
-    #[test]
-    fn test_line_range_parse_single_line() {
-        let range = LineRange::parse("test.txt:42-42").unwrap();
-        assert_eq!(range.path, "test.txt");
-        assert_eq!(range.start, 42);
-        assert_eq!(range.end, 42);
-    }
-
-    #[test]
-    fn test_line_range_parse_invalid_format() {
-        assert!(LineRange::parse("src/main.rs").is_err());
-        assert!(LineRange::parse("src/main.rs:10").is_err());
-        assert!(LineRange::parse("src/main.rs:10-20-30").is_err());
-    }
-
-    #[test]
-    fn test_line_range_parse_invalid_numbers() {
-        assert!(LineRange::parse("src/main.rs:abc-def").is_err());
-        assert!(LineRange::parse("src/main.rs:10-abc").is_err());
-        assert!(LineRange::parse("src/main.rs:0-10").is_err());
-    }
-
-    #[test]
-    fn test_line_range_parse_inverted_range() {
-        assert!(LineRange::parse("src/main.rs:20-10").is_err());
-    }
-
     #[test]
     fn test_extract_lines_simple() {
         let content = b"line 1\nline 2\nline 3\nline 4\nline 5";
@@ -299,6 +1995,19 @@ mod tests {
         assert_eq!(result_str, "line 2\nline 3\nline 4");
     }
 
+    #[test]
+    fn test_extract_lines_overlapping_ranges() {
+        let content = b"1\n2\n3\n4\n5\n6";
+        let range = |start, end| LineRange {
+            path: "test.txt".to_string(),
+            start,
+            end,
+        };
+        let ranges = vec![range(2, 4), range(3, 5)];
+        assert_eq!(extract_lines_from_content(content, &ranges, "test.txt"), b"2\n3\n4\n5");
+        assert_eq!(extract_complement_lines(content, &ranges, "test.txt"), b"1\n6");
+    }
+
     #[test]
     fn test_extract_lines_multiple_ranges() {
         let content = b"line 1\nline 2\nline 3\nline 4\nline 5\nline 6";
@@ -445,4 +2154,18 @@ mod tests {
             assert!(!remaining_lines.contains(line));
         }
     }
+    #[test]
+    fn test_extraction_replaces_only_invalid_utf8() {
+        let content = b"caf\xc3\xa9\nbad \xff\nok";
+        let ranges = vec![LineRange {
+            path: "test.txt".to_string(),
+            start: 1,
+            end: 2,
+        }];
+        assert_eq!(
+            extract_lines_from_content(content, &ranges, "test.txt"),
+            "caf\u{e9}\nbad \u{fffd}".as_bytes()
+        );
+        assert_eq!(extract_complement_lines(content, &ranges, "test.txt"), b"ok");
+    }
 }
@@ -0,0 +1,293 @@
+//! `jjka mcp`: a Model Context Protocol server on stdin and stdout, so
+//! coding agents can inspect and tidy up their commits with jjka's tools.
+//!
+//! Tool failures, like an unknown revision, are returned as results with
+//! `isError` set, as MCP asks, so the agent sees the message and can retry.
+
+use anyhow::Result;
+use jj_lib::commit::Commit;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::BufReader;
+
+use crate::checks;
+use crate::describe::{clean_description, initial_description};
+use crate::format::commit_summary;
+use crate::hunks::commit_hunks;
+use crate::impact;
+use crate::rpc::{self, INVALID_PARAMS, METHOD_NOT_FOUND, RpcError};
+use crate::serve::{CommitInfo, SplitParams, apply_split_request, default_revision, load};
+
+/// Protocol versions this server speaks, newest first.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const INSTRUCTIONS: &str = "Tools for keeping jj commits tidy. Revisions are jj revsets such as \
+    `@` for the working-copy commit or a change id. Line ranges are `path:start-end` with \
+    1-based, inclusive line numbers in the revision's version of the file, as listed by \
+    list_hunks.";
+
+fn revision_schema() -> Value {
+    json!({
+        "type": "string",
+        "description": "A revset resolving to one revision",
+        "default": "@",
+    })
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "list_hunks",
+            "description": "List the changed line ranges of a revision, with the lines added and removed in each.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "revision": revision_schema() },
+            },
+        },
+        {
+            "name": "split_revision",
+            "description": "Move the changes that make up the given line ranges of a revision into a new commit before it. The revision keeps its change id, description and the other changes.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "revision": revision_schema(),
+                    "ranges": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Line ranges like src/lib.rs:10-20",
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "Description of the new commit; defaults to the revision's",
+                    },
+                },
+                "required": ["ranges"],
+            },
+        },
+        {
+            "name": "absorb",
+            "description": "Move each change in a revision into the mutable ancestor that last changed the same lines, like `jj absorb`. Changes that can't be attributed to one ancestor stay put.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "revision": revision_schema(),
+                    "into": {
+                        "type": "string",
+                        "description": "Revset of revisions that may receive changes",
+                        "default": "mutable()",
+                    },
+                },
+            },
+        },
+        {
+            "name": "describe",
+            "description": "Set the description of a revision. Without a message, returns the current description and a suggested conventional-commit template instead.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "revision": revision_schema(),
+                    "message": { "type": "string", "description": "The new description" },
+                },
+            },
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+struct RevisionParams {
+    #[serde(default = "default_revision")]
+    revision: String,
+}
+
+fn default_into() -> String {
+    "mutable()".to_string()
+}
+
+#[derive(Deserialize)]
+struct AbsorbParams {
+    #[serde(default = "default_revision")]
+    revision: String,
+    #[serde(default = "default_into")]
+    into: String,
+}
+
+#[derive(Deserialize)]
+struct DescribeParams {
+    #[serde(default = "default_revision")]
+    revision: String,
+    message: Option<String>,
+}
+
+async fn list_hunks(params: RevisionParams) -> Result<Value> {
+    let helper = load()?;
+    let commit = helper.resolve_single(&params.revision)?;
+    let hunks: Vec<Value> = commit_hunks(helper.repo().as_ref(), &commit)
+        .await?
+        .into_iter()
+        .map(|hunk| {
+            json!({
+                "range": format!("{}:{}-{}", hunk.path, hunk.start, hunk.end),
+                "added": hunk.added,
+                "removed": hunk.removed,
+                "fingerprint": hunk.fingerprint.content,
+            })
+        })
+        .collect();
+    Ok(json!({ "commit": CommitInfo::new(&commit), "hunks": hunks }))
+}
+
+async fn absorb(params: AbsorbParams) -> Result<Value> {
+    let mut helper = load()?;
+    let commit = helper.resolve_single(&params.revision)?;
+    let destinations =
+        helper.resolve_revset(&format!("({}) & ::({})-", params.into, params.revision))?;
+    let mut tx = helper.start_transaction();
+    let outcome = jjka_lib::absorb(tx.repo_mut(), &commit, &destinations).await?;
+    let skipped: Vec<Value> = outcome
+        .skipped
+        .iter()
+        .map(|(path, reason)| json!({ "path": path, "reason": reason }))
+        .collect();
+    if outcome.destinations.is_empty() {
+        return Ok(
+            json!({ "absorbed_into": [], "source": CommitInfo::new(&commit), "skipped": skipped }),
+        );
+    }
+    let produced: Vec<&Commit> = outcome
+        .destinations
+        .iter()
+        .chain(outcome.source.as_ref())
+        .collect();
+    checks::check_commits(&helper, &produced)?;
+    helper
+        .finish_transaction(
+            tx,
+            &format!("absorb changes from commit {}", commit_summary(&commit)),
+        )
+        .await?;
+    let absorbed_into: Vec<CommitInfo> = outcome.destinations.iter().map(CommitInfo::new).collect();
+    Ok(json!({
+        "absorbed_into": absorbed_into,
+        // None if everything was absorbed and the revision was abandoned.
+        "source": outcome.source.as_ref().map(CommitInfo::new),
+        "skipped": skipped,
+        "operations": impact::take().await?,
+    }))
+}
+
+async fn describe(params: DescribeParams) -> Result<Value> {
+    let mut helper = load()?;
+    let commit = helper.resolve_single(&params.revision)?;
+    let Some(message) = params.message else {
+        return Ok(json!({
+            "commit": CommitInfo::new(&commit),
+            "description": commit.description(),
+            "suggested": initial_description(&helper, &commit).await?,
+        }));
+    };
+    let description = clean_description(&message)?;
+    if description == commit.description() {
+        return Ok(json!({ "commit": CommitInfo::new(&commit), "changed": false }));
+    }
+    let mut tx = helper.start_transaction();
+    let new_commit = tx
+        .repo_mut()
+        .rewrite_commit(&commit)
+        .set_description(description)
+        .write()?;
+    helper
+        .finish_transaction(tx, &format!("describe commit {}", commit_summary(&commit)))
+        .await?;
+    Ok(json!({
+        "commit": CommitInfo::new(&new_commit),
+        "changed": true,
+        "operations": impact::take().await?,
+    }))
+}
+
+#[derive(Deserialize)]
+struct CallParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+async fn call_tool(params: CallParams) -> Result<Value, RpcError> {
+    let arguments = params.arguments;
+    let result = match params.name.as_str() {
+        "list_hunks" => list_hunks(rpc::params(arguments)?).await,
+        "split_revision" => apply_split_request(rpc::params::<SplitParams>(arguments)?).await,
+        "absorb" => absorb(rpc::params(arguments)?).await,
+        "describe" => describe(rpc::params(arguments)?).await,
+        name => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown tool {name:?}"),
+            ));
+        }
+    };
+    Ok(match result {
+        Ok(result) => json!({
+            "content": [{ "type": "text", "text": serde_json::to_string_pretty(&result).unwrap() }],
+            "structuredContent": result,
+            "isError": false,
+        }),
+        Err(err) => json!({
+            "content": [{ "type": "text", "text": format!("{err:#}") }],
+            "isError": true,
+        }),
+    })
+}
+
+fn initialize(params: Value) -> Value {
+    let requested = params["protocolVersion"].as_str();
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .find(|version| Some(**version) == requested)
+        .unwrap_or(&PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "jjka", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": INSTRUCTIONS,
+    })
+}
+
+async fn dispatch(method: String, params: Value) -> Result<Value, RpcError> {
+    match method.as_str() {
+        "initialize" => Ok(initialize(params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => call_tool(rpc::params(params)?).await,
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {method:?}"),
+        )),
+    }
+}
+
+pub async fn mcp_command() -> Result<()> {
+    // Fail early if we aren't in a repo.
+    load()?;
+    rpc::serve(
+        dispatch,
+        BufReader::new(tokio::io::stdin()),
+        tokio::io::stdout(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initialize_version() {
+        let version = |requested: Value| {
+            initialize(json!({ "protocolVersion": requested }))["protocolVersion"].clone()
+        };
+        assert_eq!(version(json!("2025-03-26")), "2025-03-26");
+        assert_eq!(version(json!("1999-01-01")), PROTOCOL_VERSIONS[0]);
+        assert_eq!(version(Value::Null), PROTOCOL_VERSIONS[0]);
+    }
+}
@@ -0,0 +1,266 @@
+//! `jjka megamerge`: keep a merge of several in-flight features to work on
+//! top of.
+//!
+//! The merge is tracked by change id in `.jj/repo/jjka/megamerge`, so
+//! rewrites don't lose it, and its features are its parents. Each update
+//! follows a feature to the newest commit stacked on it outside the
+//! megamerge, and rebases the merge onto those heads, keeping whatever the
+//! merge commit itself changes.
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Subcommand;
+use itertools::Itertools as _;
+use jj_lib::backend::{ChangeId, CommitId};
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::rewrite::{merge_commit_trees, rebase_commit};
+use std::path::{Path, PathBuf};
+
+use crate::format::commit_summary;
+use crate::state_file;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper, current_commit};
+
+#[derive(Subcommand, Debug)]
+pub enum MegamergeCommand {
+    /// Merge the features into a new commit and start working on top of it
+    Create {
+        /// The feature heads to merge
+        #[arg(required = true)]
+        revisions: Vec<String>,
+
+        /// The description of the merge commit
+        #[arg(short = 'm', long, default_value = "megamerge")]
+        message: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Add a feature to the megamerge
+    Add {
+        /// The feature head to add
+        revision: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Take a feature out of the megamerge
+    Remove {
+        /// A commit of the feature to remove
+        revision: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Rebase the megamerge onto the features' current heads
+    Update {
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+}
+
+fn state_path(helper: &WorkspaceHelper) -> PathBuf {
+    helper.repo_path().join("jjka").join("megamerge")
+}
+
+/// Reads the state file, a `merge <change id>` line.
+fn load_state(path: &Path) -> Result<Option<ChangeId>> {
+    let Some(content) = state_file::read(path)? else {
+        return Ok(None);
+    };
+    let line = content.trim_end();
+    let hex = line
+        .strip_prefix("merge ")
+        .ok_or_else(|| anyhow!("Invalid line in {}: {line}", path.display()))?;
+    let id = ChangeId::try_from_hex(hex)
+        .ok_or_else(|| anyhow!("Invalid change id in {}: {hex}", path.display()))?;
+    Ok(Some(id))
+}
+
+fn save_state(path: &Path, merge: &ChangeId) -> Result<()> {
+    state_file::write(path, format!("merge {}\n", merge.hex()).as_bytes())
+}
+
+/// The megamerge commit, whose parents are the features.
+fn existing_merge(helper: &WorkspaceHelper) -> Result<Commit> {
+    let merge = load_state(&state_path(helper))?
+        .context("There is no megamerge; create one with `jjka megamerge create`")?;
+    current_commit(helper.repo().as_ref(), &merge)
+        .context("The megamerge commit is gone; create a new one with `jjka megamerge create`")
+}
+
+fn print_features(features: &[Commit]) {
+    for feature in features {
+        println!("  {}", commit_summary(feature));
+    }
+}
+
+async fn create(revisions: Vec<String>, message: String, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let path = state_path(&helper);
+    if let Some(merge) = load_state(&path)?
+        && let Ok(merge) = current_commit(helper.repo().as_ref(), &merge)
+    {
+        bail!(
+            "There already is a megamerge at {}; change it with `add` and `remove`",
+            commit_summary(&merge)
+        );
+    }
+    let mut features: Vec<Commit> = Vec::new();
+    for revision in &revisions {
+        let commit = helper.resolve_single(revision)?;
+        if features.iter().any(|f| f.change_id() == commit.change_id()) {
+            bail!("{} is given more than once", commit_summary(&commit));
+        }
+        features.push(commit);
+    }
+
+    let mut tx = helper.start_transaction();
+    let tree = merge_commit_trees(tx.repo(), &features).await?;
+    let parent_ids: Vec<CommitId> = features.iter().map(|f| f.id().clone()).collect();
+    let merge = tx
+        .repo_mut()
+        .new_commit(parent_ids, tree.id())
+        .set_description(format!("{message}\n"))
+        .write()?;
+    tx.repo_mut()
+        .check_out(helper.workspace_name().to_owned(), &merge)?;
+    helper
+        .finish_transaction(
+            tx,
+            &format!("create megamerge of {} features", features.len()),
+        )
+        .await?;
+    save_state(&path, merge.change_id())?;
+
+    println!(
+        "Created megamerge {} of {} feature(s):",
+        commit_summary(&merge),
+        features.len()
+    );
+    print_features(&features);
+    Ok(())
+}
+
+/// Moves each of `features` to its newest commit outside the megamerge and
+/// rebases the merge onto them.
+async fn rebuild(
+    helper: &mut WorkspaceHelper,
+    merge: Commit,
+    features: Vec<Commit>,
+    action: &str,
+) -> Result<()> {
+    let mut heads: Vec<Commit> = Vec::new();
+    for commit in features {
+        let candidates = helper.evaluate_revset(&format!(
+            "heads({}:: ~ {}::)",
+            commit.id().hex(),
+            merge.id().hex()
+        ))?;
+        let head = match <[_; 1]>::try_from(candidates) {
+            Ok([head]) => head,
+            Err(candidates) => {
+                if !candidates.is_empty() {
+                    status!(
+                        "Feature {} has {} heads; leaving it where it is",
+                        commit_summary(&commit),
+                        candidates.len()
+                    );
+                }
+                commit
+            }
+        };
+        heads.push(head);
+    }
+
+    let head_ids: Vec<CommitId> = heads.iter().map(|head| head.id().clone()).collect();
+    if merge.parent_ids() == head_ids.as_slice() {
+        status!("Megamerge {} is up to date", commit_summary(&merge));
+        return Ok(());
+    }
+    let mut tx = helper.start_transaction();
+    let new_merge = rebase_commit(tx.repo_mut(), merge, head_ids).await?;
+    helper.finish_transaction(tx, action).await?;
+
+    println!(
+        "Updated megamerge {} of {} feature(s):",
+        commit_summary(&new_merge),
+        heads.len()
+    );
+    print_features(&heads);
+    if new_merge.has_conflict()? {
+        status!("The megamerge has conflicts; see `jjka conflicts -r @-`");
+    }
+    Ok(())
+}
+
+async fn add(revision: String, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let merge = existing_merge(&helper)?;
+    let mut features: Vec<Commit> = merge.parents().try_collect()?;
+    let commit = helper.resolve_single(&revision)?;
+    if commit.change_id() == merge.change_id()
+        || features.iter().any(|f| f.change_id() == commit.change_id())
+    {
+        bail!("{} is already in the megamerge", commit_summary(&commit));
+    }
+    let action = format!("add {} to megamerge", commit.id().hex());
+    features.push(commit);
+    rebuild(&mut helper, merge, features, &action).await
+}
+
+async fn remove(revision: String, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let merge = existing_merge(&helper)?;
+    let mut features: Vec<Commit> = merge.parents().try_collect()?;
+    let commit = helper.resolve_single(&revision)?;
+    let count = features.len();
+    features.retain(|feature| feature.change_id() != commit.change_id());
+    if features.len() == count {
+        bail!(
+            "{} is not a feature of the megamerge",
+            commit_summary(&commit)
+        );
+    }
+    if features.is_empty() {
+        bail!("Can't remove the last feature of the megamerge");
+    }
+    rebuild(
+        &mut helper,
+        merge,
+        features,
+        &format!("remove {} from megamerge", commit.id().hex()),
+    )
+    .await
+}
+
+async fn update(policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let merge = existing_merge(&helper)?;
+    let features = merge.parents().try_collect()?;
+    rebuild(&mut helper, merge, features, "update megamerge").await
+}
+
+pub async fn megamerge_command(command: MegamergeCommand) -> Result<()> {
+    match command {
+        MegamergeCommand::Create {
+            revisions,
+            message,
+            policy,
+        } => create(revisions, message, policy).await,
+        MegamergeCommand::Add { revision, policy } => add(revision, policy).await,
+        MegamergeCommand::Remove { revision, policy } => remove(revision, policy).await,
+        MegamergeCommand::Update { policy } => update(policy).await,
+    }
+}
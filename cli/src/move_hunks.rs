@@ -0,0 +1,191 @@
+//! `jjka move`: move selected lines of one revision's changes into another.
+//!
+//! The ranges are line numbers in the source revision, like `hunksplit`'s.
+//! The selected changes are taken out of the source and applied to the
+//! destination, which can be anywhere in the mutable graph: an ancestor, a
+//! descendant or an unrelated revision. The commits between them, and the
+//! descendants of both, are rebased in the same operation.
+//!
+//! `jjka squash-ranges` is the common case of moving lines into the
+//! revision's parent, the surgical version of `jj squash`.
+
+use anyhow::{Result, anyhow};
+use jj_lib::commit::Commit;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::repo::{MutableRepo, Repo as _};
+use jjka_lib::EmptyCommit;
+
+use crate::LineRange;
+use crate::checks;
+use crate::exit_code::Failure;
+use crate::format::commit_summary;
+use crate::split;
+use crate::workspace::{self, PolicyArgs, WorkspaceHelper, current_commit};
+
+/// Takes the changes from `source`'s parents to `selected_tree` out of
+/// `source` and applies them to `destination`, rebasing descendants after
+/// each rewrite so that commits between the two give them up or keep them.
+/// Returns the rewritten source and destination.
+async fn move_changes(
+    repo: &mut MutableRepo,
+    source: &Commit,
+    destination: &Commit,
+    selected_tree: &MergedTree,
+) -> Result<(Commit, Commit)> {
+    let parent_tree = source.parent_tree(repo)?;
+    let source_tree = source
+        .tree_async()
+        .await?
+        .merge(selected_tree.clone(), parent_tree.clone())
+        .await?;
+    repo.rewrite_commit(source)
+        .set_tree_id(source_tree.id())
+        .write()?;
+    repo.rebase_descendants()?;
+
+    // Rebasing took the changes out of the destination too if it's a
+    // descendant of the source.
+    let destination = current_commit(repo, destination.change_id())?;
+    let destination_tree = destination
+        .tree_async()
+        .await?
+        .merge(parent_tree, selected_tree.clone())
+        .await?;
+    let destination = repo
+        .rewrite_commit(&destination)
+        .set_tree_id(destination_tree.id())
+        .write()?;
+    repo.rebase_descendants()?;
+    Ok((current_commit(repo, source.change_id())?, destination))
+}
+
+/// The source and destination after moving lines between them.
+struct Moved {
+    source: Commit,
+    destination: Commit,
+    /// Whether the source was left without changes.
+    emptied: bool,
+}
+
+/// Moves the changes that make up the `ranges` of `source` into
+/// `destination` in one operation described by `description`.
+async fn move_lines(
+    helper: &mut WorkspaceHelper,
+    source: &Commit,
+    destination: &Commit,
+    ranges: &[LineRange],
+    description: &str,
+) -> Result<Moved> {
+    if destination.id() == helper.repo().store().root_commit_id() {
+        return Err(Failure::Usage.error(anyhow!(
+            "The root commit can't be changed, so the lines can't move into it"
+        )));
+    }
+    let plan = split::plan_split_with_modes(helper.repo().as_ref(), source, ranges, &[]).await?;
+    split::check_unchanged(&plan, true)?;
+    if plan.empty == Some(EmptyCommit::Selected) {
+        return Err(Failure::Usage.error(anyhow!(
+            "The selected lines aren't changed in {}, so there is nothing to move",
+            commit_summary(source)
+        )));
+    }
+    let selected_tree = helper
+        .repo()
+        .store()
+        .get_root_tree(&plan.selected_tree_id)?;
+
+    let mut tx = helper.start_transaction();
+    let (moved_source, moved_destination) =
+        move_changes(tx.repo_mut(), source, destination, &selected_tree).await?;
+    checks::check_commits(helper, &[&moved_source, &moved_destination])?;
+    helper.finish_transaction(tx, description).await?;
+    Ok(Moved {
+        source: moved_source,
+        destination: moved_destination,
+        emptied: plan.empty == Some(EmptyCommit::Remaining),
+    })
+}
+
+pub async fn move_command(
+    from: String,
+    to: String,
+    ranges: Vec<String>,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let parsed_ranges = workspace::parse_ranges(&ranges)?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let source = helper.resolve_single(&from)?;
+    let destination = helper.resolve_single(&to)?;
+    if source.id() == destination.id() {
+        return Err(Failure::Usage.error(anyhow!(
+            "--from and --to are both {}; there is nowhere to move the lines",
+            commit_summary(&source)
+        )));
+    }
+
+    let description = format!(
+        "move lines from commit {} to commit {}",
+        commit_summary(&source),
+        commit_summary(&destination)
+    );
+    let moved = move_lines(
+        &mut helper,
+        &source,
+        &destination,
+        &parsed_ranges,
+        &description,
+    )
+    .await?;
+    println!(
+        "Moved lines from {} to {}",
+        commit_summary(&moved.source),
+        commit_summary(&moved.destination)
+    );
+    if moved.emptied {
+        println!("{} is now empty", commit_summary(&moved.source));
+    }
+    if moved.destination.has_conflict()? {
+        eprintln!(
+            "Warning: {} has conflicts after the move",
+            commit_summary(&moved.destination)
+        );
+    }
+    Ok(())
+}
+
+pub async fn squash_ranges_command(
+    revision: String,
+    ranges: Vec<String>,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let parsed_ranges = workspace::parse_ranges(&ranges)?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let child = helper.resolve_single(&revision)?;
+    let [parent_id] = child.parent_ids() else {
+        return Err(Failure::Usage.error(anyhow!(
+            "{} is a merge; squash-ranges needs a revision with one parent, or use \
+             `jjka move --to` to pick one",
+            commit_summary(&child)
+        )));
+    };
+    let parent = helper.repo().store().get_commit(parent_id)?;
+
+    let description = format!(
+        "squash lines of commit {} into its parent",
+        commit_summary(&child)
+    );
+    let moved = move_lines(&mut helper, &child, &parent, &parsed_ranges, &description).await?;
+    println!(
+        "Squashed lines of {} into {}",
+        commit_summary(&moved.source),
+        commit_summary(&moved.destination)
+    );
+    if moved.emptied {
+        println!("{} is now empty", commit_summary(&moved.source));
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! `jjka op-gc`: prune old operations and collect the commits only they
+//! kept alive.
+//!
+//! An operation is kept if it's among the `--keep` newest or newer than
+//! `--keep-days`. Pruning starts at the newest operation that is neither:
+//! everything since then is reparented onto the root operation, like
+//! `jj op abandon ..<op>`, and the op store and backend are then
+//! garbage-collected. Commits kept by `jjka snapshot` survive even though
+//! no operation refers to them.
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use jj_lib::backend::{CommitId, MillisSinceEpoch};
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_walk;
+use jj_lib::operation::Operation;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::revset::RevsetExpression;
+use jj_lib::transaction::Transaction;
+use std::slice;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::format::format_timestamp;
+use crate::snapshot;
+use crate::ui::status;
+use crate::workspace::WorkspaceHelper;
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// The commit heads recorded by any of `ops`.
+fn view_heads(ops: &[Operation]) -> Result<Vec<CommitId>> {
+    let mut heads = Vec::new();
+    for op in ops {
+        heads.extend(op.view()?.heads().iter().cloned());
+    }
+    Ok(heads.into_iter().unique().collect())
+}
+
+/// A transaction that makes the snapshot commits reachable. It's never
+/// committed, so they stay hidden, but its index has them.
+fn with_snapshots(repo: &Arc<ReadonlyRepo>, snapshot_ids: &[CommitId]) -> Result<Transaction> {
+    let snapshots: Vec<_> = snapshot_ids
+        .iter()
+        .map(|id| repo.store().get_commit(id))
+        .try_collect()?;
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().add_heads(&snapshots)?;
+    Ok(tx)
+}
+
+/// How many commits reachable from `old_heads` aren't from `new_heads`.
+fn count_unreachable(
+    repo: &dyn Repo,
+    old_heads: Vec<CommitId>,
+    new_heads: Vec<CommitId>,
+) -> Result<usize> {
+    let unreachable: Vec<CommitId> = RevsetExpression::commits(old_heads)
+        .ancestors()
+        .minus(&RevsetExpression::commits(new_heads).ancestors())
+        .evaluate(repo)?
+        .iter()
+        .try_collect()?;
+    Ok(unreachable.len())
+}
+
+fn operation_summary(op: &Operation) -> String {
+    format!(
+        "{} {} ({})",
+        &op.id().hex()[..12],
+        op.metadata().description,
+        format_timestamp(&op.metadata().time.end)
+    )
+}
+
+pub async fn op_gc_command(keep_days: u32, keep: u32, dry_run: bool) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    let repo = helper.repo().clone();
+    let loader = repo.loader();
+    let root_op_id = repo.op_store().root_operation_id().clone();
+    let head_op = repo.operation().clone();
+
+    let ops: Vec<Operation> = op_walk::walk_ancestors(slice::from_ref(&head_op))
+        .filter_ok(|op| *op.id() != root_op_id)
+        .try_collect()?;
+    let now = MillisSinceEpoch(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis() as i64,
+    );
+    let cutoff = now.0 - i64::from(keep_days) * MILLIS_PER_DAY;
+    let Some(boundary) = ops
+        .iter()
+        .enumerate()
+        .find(|(index, op)| *index >= keep as usize && op.metadata().time.end.timestamp.0 < cutoff)
+        .map(|(_, op)| op.clone())
+    else {
+        status!(
+            "Nothing to prune: all {} operation(s) are within the retention policy",
+            ops.len()
+        );
+        return Ok(());
+    };
+
+    let pruned: Vec<Operation> = op_walk::walk_ancestors(slice::from_ref(&boundary))
+        .filter_ok(|op| *op.id() != root_op_id)
+        .try_collect()?;
+    let kept: Vec<Operation> =
+        op_walk::walk_ancestors_range(slice::from_ref(&head_op), slice::from_ref(&boundary))
+            .try_collect()?;
+    let snapshot_ids = snapshot::snapshot_commit_ids(&helper)?;
+    let mut kept_heads = view_heads(&kept)?;
+    kept_heads.extend(snapshot_ids.iter().cloned());
+    kept_heads.push(repo.store().root_commit_id().clone());
+    let tx = with_snapshots(&repo, &snapshot_ids)?;
+    let unreachable = count_unreachable(tx.repo(), view_heads(&ops)?, kept_heads)?;
+
+    if dry_run {
+        println!(
+            "Would prune {} operation(s), keeping {}, and make {unreachable} commit(s) unreachable",
+            pruned.len(),
+            kept.len()
+        );
+        println!("Newest pruned operation: {}", operation_summary(&boundary));
+        return Ok(());
+    }
+
+    let stats = op_walk::reparent_range(
+        repo.op_store().as_ref(),
+        slice::from_ref(&boundary),
+        slice::from_ref(&head_op),
+        &loader.root_operation(),
+    )?;
+    let [new_head_id] = <[_; 1]>::try_from(stats.new_head_ids).unwrap();
+    loader
+        .op_heads_store()
+        .update_op_heads(slice::from_ref(head_op.id()), &new_head_id)?;
+    helper.remap_working_copy_operation(head_op.id(), &new_head_id)?;
+
+    let new_repo = loader.load_at(&loader.load_operation(&new_head_id)?)?;
+    let tx = with_snapshots(&new_repo, &snapshot_ids)?;
+    let keep_newer = SystemTime::now();
+    new_repo
+        .store()
+        .gc(tx.repo().index(), keep_newer)
+        .context("Failed to collect unreachable commits")?;
+    let op_heads = loader.op_heads_store().get_op_heads()?;
+    new_repo
+        .op_store()
+        .gc(&op_heads, keep_newer)
+        .context("Failed to collect pruned operations")?;
+
+    println!(
+        "Pruned {} operation(s), keeping {}; {unreachable} commit(s) became unreachable",
+        pruned.len(),
+        kept.len()
+    );
+    println!("Newest pruned operation: {}", operation_summary(&boundary));
+    Ok(())
+}
@@ -0,0 +1,259 @@
+//! Paging long output, like jj does.
+//!
+//! When stdout is a terminal, commands that print reports send them through
+//! the pager from `ui.pager` unless `ui.paginate` is `"never"` or
+//! `--no-pager` is passed. The pager's stdin replaces file descriptor 1, so
+//! everything that prints to stdout goes through it.
+//!
+//! While paging, Ctrl-C is left to the pager, and jjka exits quietly with
+//! SIGPIPE when the pager is quit before the output is finished (see
+//! [`crate::sigpipe`]).
+//!
+//! `jjka.diff.pager` takes over for `jjka diff`, for pagers like delta that
+//! render diffs themselves: they get a plain git-style diff to work on.
+//!
+//! Handing stdout to the pager takes Unix file descriptors, so elsewhere
+//! output isn't paged.
+
+use anyhow::{Result, bail};
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::settings::UserSettings;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+#[cfg(unix)]
+use std::io::{IsTerminal as _, Write as _};
+#[cfg(unix)]
+use std::os::fd::AsRawFd as _;
+#[cfg(unix)]
+use std::process::{Child, Command, Stdio};
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What jj's built-in pager is replaced with, since jjka doesn't have one.
+const DEFAULT_PAGER: &[&str] = &["less", "-FRX"];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static RENDERS_DIFFS: AtomicBool = AtomicBool::new(false);
+#[cfg(unix)]
+static PAGER: Mutex<Option<Pager>> = Mutex::new(None);
+
+#[cfg(unix)]
+struct Pager {
+    child: Child,
+    /// A duplicate of the original stdout, put back when paging ends.
+    saved_stdout: libc::c_int,
+}
+
+/// `ui.pager` as jj accepts it: a command line, its words, or a table with
+/// the words under `command`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PagerSetting {
+    CommandLine(String),
+    Words(Vec<String>),
+    Table {
+        command: Vec<String>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PagerCommand {
+    words: Vec<String>,
+    env: BTreeMap<String, String>,
+    /// It's `jjka.diff.pager`, which colors and lays out diffs itself.
+    renders_diffs: bool,
+}
+
+/// The pager command to run, or `None` if output shouldn't be paged. With
+/// `diff`, `jjka.diff.pager` is used if set.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn pager_command(settings: &UserSettings, diff: bool) -> Result<Option<PagerCommand>> {
+    let paginate = settings
+        .get_string("ui.paginate")
+        .optional()?
+        .unwrap_or_else(|| "auto".to_string());
+    match paginate.as_str() {
+        "auto" => {}
+        "never" => return Ok(None),
+        _ => bail!("Invalid ui.paginate \"{paginate}\"; expected \"auto\" or \"never\""),
+    }
+    let diff_pager = if diff {
+        settings.get::<PagerSetting>("jjka.diff.pager").optional()?
+    } else {
+        None
+    };
+    let renders_diffs = diff_pager.is_some();
+    let setting = match diff_pager {
+        Some(setting) => Some(setting),
+        None => settings.get::<PagerSetting>("ui.pager").optional()?,
+    };
+    let (words, env) = match setting {
+        Some(PagerSetting::CommandLine(line)) => (
+            line.split_whitespace().map(str::to_string).collect(),
+            BTreeMap::new(),
+        ),
+        Some(PagerSetting::Words(words)) => (words, BTreeMap::new()),
+        Some(PagerSetting::Table { command, env }) => (command, env),
+        None => (Vec::new(), BTreeMap::new()),
+    };
+    if words.is_empty() || words == [":builtin"] {
+        let words = DEFAULT_PAGER.iter().map(|word| word.to_string()).collect();
+        return Ok(Some(PagerCommand {
+            words,
+            env,
+            renders_diffs: false,
+        }));
+    }
+    Ok(Some(PagerCommand {
+        words,
+        env,
+        renders_diffs,
+    }))
+}
+
+/// Starts paging stdout if it's a terminal and the settings allow it, with
+/// the diff pager if `diff`. A pager that can't be started is warned about
+/// and skipped.
+#[cfg(unix)]
+pub fn start(diff: bool) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        return Ok(());
+    }
+    let settings = crate::workspace::load_settings()?;
+    let Some(command) = pager_command(&settings, diff)? else {
+        return Ok(());
+    };
+    let mut pager = Command::new(&command.words[0]);
+    pager
+        .args(&command.words[1..])
+        .envs(&command.env)
+        .stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() && !command.env.contains_key("LESS") {
+        pager.env("LESS", "FRX");
+    }
+    let mut child = match pager.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("Warning: Failed to start pager {}: {err}", command.words[0]);
+            return Ok(());
+        }
+    };
+    let stdin = child.stdin.take().unwrap();
+    std::io::stdout().flush()?;
+    unsafe {
+        let saved_stdout = libc::dup(libc::STDOUT_FILENO);
+        if saved_stdout < 0 || libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            bail!(
+                "Failed to redirect stdout to the pager: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        *PAGER.lock().unwrap() = Some(Pager {
+            child,
+            saved_stdout,
+        });
+    }
+    ACTIVE.store(true, Ordering::Relaxed);
+    RENDERS_DIFFS.store(command.renders_diffs, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Output isn't paged here, as stdout can't be handed to the pager.
+#[cfg(not(unix))]
+pub fn start(_diff: bool) -> Result<()> {
+    Ok(())
+}
+
+/// Whether output is going to a pager, which counts as a terminal for
+/// deciding to use color.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Whether output is going to a diff pager, which wants diffs without
+/// color and with git's headers.
+pub fn renders_diffs() -> bool {
+    RENDERS_DIFFS.load(Ordering::Relaxed)
+}
+
+/// Ends paging: closes the pager's input and waits for the user to quit it.
+#[cfg(unix)]
+pub fn finish() {
+    let Some(mut pager) = PAGER.lock().unwrap().take() else {
+        return;
+    };
+    let _ = std::io::stdout().flush();
+    unsafe {
+        libc::dup2(pager.saved_stdout, libc::STDOUT_FILENO);
+        libc::close(pager.saved_stdout);
+    }
+    let _ = pager.child.wait();
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+    ACTIVE.store(false, Ordering::Relaxed);
+    RENDERS_DIFFS.store(false, Ordering::Relaxed);
+}
+
+#[cfg(not(unix))]
+pub fn finish() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+
+    fn settings(toml: &str) -> UserSettings {
+        let mut config = StackedConfig::with_defaults();
+        config.add_layer(ConfigLayer::parse(ConfigSource::User, toml).unwrap());
+        UserSettings::from_config(config).unwrap()
+    }
+
+    #[test]
+    fn test_pager_command() {
+        let words = |toml| {
+            pager_command(&settings(toml), false)
+                .unwrap()
+                .map(|command| command.words)
+        };
+        assert_eq!(
+            words(""),
+            Some(vec!["less".to_string(), "-FRX".to_string()])
+        );
+        assert_eq!(
+            words("ui.pager = \"most -s\""),
+            Some(vec!["most".to_string(), "-s".to_string()])
+        );
+        assert_eq!(
+            words("ui.pager = [\"bat\", \"--plain\"]"),
+            Some(vec!["bat".to_string(), "--plain".to_string()])
+        );
+        assert_eq!(words("ui.paginate = \"never\""), None);
+        assert!(pager_command(&settings("ui.paginate = \"sometimes\""), false).is_err());
+
+        let command = pager_command(
+            &settings("ui.pager = { command = [\"delta\"], env = { DELTA_PAGER = \"less\" } }"),
+            false,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(command.words, ["delta"]);
+        assert_eq!(command.env["DELTA_PAGER"], "less");
+
+        let both = settings("ui.pager = \"less\"\njjka.diff.pager = \"delta\"");
+        let command = pager_command(&both, true).unwrap().unwrap();
+        assert_eq!(
+            (command.words, command.renders_diffs),
+            (vec!["delta".to_string()], true)
+        );
+        let command = pager_command(&both, false).unwrap().unwrap();
+        assert_eq!(
+            (command.words, command.renders_diffs),
+            (vec!["less".to_string()], false)
+        );
+    }
+}
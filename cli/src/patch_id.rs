@@ -0,0 +1,91 @@
+//! Parent-independent identifiers for the change a commit introduces.
+//!
+//! Like `git patch-id`, the id only depends on the changed paths and the
+//! added/removed lines with whitespace stripped, so a cherry-picked commit
+//! that applied cleanly has the same patch id as its source.
+
+use anyhow::Result;
+use jj_lib::commit::Commit;
+use jj_lib::diff::{ContentDiff, DiffHunkKind};
+use jj_lib::repo::Repo;
+use sha1::{Digest, Sha1};
+
+use crate::tree_diff::{changed_paths, read_file};
+
+fn hash_line(hasher: &mut Sha1, sign: u8, line: &[u8]) {
+    hasher.update([sign]);
+    hasher.update(
+        line.iter()
+            .copied()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect::<Vec<_>>(),
+    );
+    hasher.update(b"\n");
+}
+
+fn hash_file_diff(hasher: &mut Sha1, before: &[u8], after: &[u8]) {
+    let diff = ContentDiff::by_line([before, after]);
+    for hunk in diff.hunks() {
+        if hunk.kind == DiffHunkKind::Matching {
+            continue;
+        }
+        for line in hunk.contents[0].split_inclusive(|b| *b == b'\n') {
+            hash_line(hasher, b'-', line);
+        }
+        for line in hunk.contents[1].split_inclusive(|b| *b == b'\n') {
+            hash_line(hasher, b'+', line);
+        }
+    }
+}
+
+/// Computes the patch id of `commit` relative to its parents.
+pub async fn patch_id(repo: &dyn Repo, commit: &Commit) -> Result<String> {
+    let store = repo.store();
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree()?;
+    let mut hasher = Sha1::new();
+    for change in changed_paths(&parent_tree, &tree).await? {
+        hasher.update(change.path.as_internal_file_string().as_bytes());
+        hasher.update(b"\0");
+        let before = read_file(store, &change.path, change.before).await?;
+        let after = read_file(store, &change.path, change.after).await?;
+        hash_file_diff(
+            &mut hasher,
+            before.as_deref().unwrap_or_default(),
+            after.as_deref().unwrap_or_default(),
+        );
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_diff_hash(before: &str, after: &str) -> String {
+        let mut hasher = Sha1::new();
+        hash_file_diff(&mut hasher, before.as_bytes(), after.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    #[test]
+    fn test_patch_id_ignores_context() {
+        let a = file_diff_hash("a\nb\nc\n", "a\nB\nc\n");
+        let b = file_diff_hash("x\ny\nb\nz\n", "x\ny\nB\nz\n");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_patch_id_ignores_whitespace() {
+        let a = file_diff_hash("fn f() {}\n", "fn f() { g(); }\n");
+        let b = file_diff_hash("fn f() {}\n", "fn  f() {   g(); }\n");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_patch_id_differs_for_different_changes() {
+        let a = file_diff_hash("a\n", "b\n");
+        let b = file_diff_hash("a\n", "c\n");
+        assert_ne!(a, b);
+    }
+}
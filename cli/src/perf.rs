@@ -0,0 +1,171 @@
+//! How much of the machine jjka may use: the number of worker threads and,
+//! on Linux, CPU and IO priority.
+//!
+//! Configured under `[jjka.perf]`:
+//!
+//! ```toml
+//! [jjka.perf]
+//! max-threads = 4
+//! nice = 10
+//! io-priority = "idle"
+//! ```
+
+use anyhow::{Result, bail};
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::settings::UserSettings;
+use std::sync::OnceLock;
+
+static JOBS: OnceLock<usize> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IoPriority {
+    /// Only get disk time when nothing else wants it.
+    Idle,
+    /// The lowest level of the normal best-effort class.
+    Low,
+    Normal,
+}
+
+impl IoPriority {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "idle" => Ok(IoPriority::Idle),
+            "low" => Ok(IoPriority::Low),
+            "normal" => Ok(IoPriority::Normal),
+            _ => bail!(
+                "Invalid jjka.perf.io-priority \"{value}\"; expected \"idle\", \"low\" or \"normal\""
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PerfSettings {
+    jobs: usize,
+    nice: Option<i32>,
+    io_priority: IoPriority,
+}
+
+impl PerfSettings {
+    /// `--jobs` wins over `jjka.perf.max-threads`, which defaults to the
+    /// number of CPUs.
+    fn load(settings: &UserSettings, jobs_arg: Option<usize>) -> Result<Self> {
+        let max_threads = settings.get::<usize>("jjka.perf.max-threads").optional()?;
+        let jobs = match jobs_arg.or(max_threads) {
+            Some(0) => bail!("jjka.perf.max-threads must be at least 1"),
+            Some(jobs) => jobs,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let nice = settings.get::<i32>("jjka.perf.nice").optional()?;
+        if let Some(nice) = nice
+            && !(0..=19).contains(&nice)
+        {
+            bail!("jjka.perf.nice must be between 0 and 19, got {nice}");
+        }
+        let io_priority = match settings.get_string("jjka.perf.io-priority").optional()? {
+            Some(value) => IoPriority::parse(&value)?,
+            None => IoPriority::Normal,
+        };
+        Ok(PerfSettings {
+            jobs,
+            nice,
+            io_priority,
+        })
+    }
+
+    /// Lowers the priority of this process. Child processes, like the
+    /// commands run by `bisect`, inherit it.
+    #[cfg(target_os = "linux")]
+    fn apply_priority(&self) -> Result<()> {
+        if let Some(nice) = self.nice {
+            // SAFETY: setpriority only changes the scheduling priority.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                bail!(
+                    "Failed to set niceness to {nice}: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+        let priority = match self.io_priority {
+            IoPriority::Idle => 3 << IOPRIO_CLASS_SHIFT,
+            IoPriority::Low => (2 << IOPRIO_CLASS_SHIFT) | 7,
+            IoPriority::Normal => return Ok(()),
+        };
+        // SAFETY: ioprio_set only changes the IO scheduling class.
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) } != 0 {
+            bail!(
+                "Failed to set IO priority: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+
+    /// Priorities are only hints, so elsewhere they're ignored.
+    #[cfg(not(target_os = "linux"))]
+    fn apply_priority(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads `[jjka.perf]`, lowers the process priority if configured and
+/// returns the number of worker threads to use.
+pub fn init(jobs_arg: Option<usize>) -> Result<usize> {
+    let settings = crate::workspace::load_settings()?;
+    let perf = PerfSettings::load(&settings, jobs_arg)?;
+    perf.apply_priority()?;
+    JOBS.get_or_init(|| perf.jobs);
+    Ok(perf.jobs)
+}
+
+/// How many things heavy operations should work on at once.
+pub fn jobs() -> usize {
+    JOBS.get().copied().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+
+    fn settings(toml: &str) -> UserSettings {
+        let mut config = StackedConfig::with_defaults();
+        config.add_layer(ConfigLayer::parse(ConfigSource::User, toml).unwrap());
+        UserSettings::from_config(config).unwrap()
+    }
+
+    #[test]
+    fn test_load_perf_settings() {
+        let config = settings("jjka.perf.max-threads = 3\njjka.perf.nice = 5\n");
+        assert_eq!(
+            PerfSettings::load(&config, None).unwrap(),
+            PerfSettings {
+                jobs: 3,
+                nice: Some(5),
+                io_priority: IoPriority::Normal
+            }
+        );
+        assert_eq!(PerfSettings::load(&config, Some(1)).unwrap().jobs, 1);
+
+        let config = settings("jjka.perf.io-priority = \"idle\"\n");
+        assert_eq!(
+            PerfSettings::load(&config, None).unwrap().io_priority,
+            IoPriority::Idle
+        );
+        let error = |toml| {
+            PerfSettings::load(&settings(toml), None)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("jjka.perf.nice = 25"),
+            "jjka.perf.nice must be between 0 and 19, got 25"
+        );
+        assert_eq!(
+            error("jjka.perf.io-priority = \"fast\""),
+            "Invalid jjka.perf.io-priority \"fast\"; expected \"idle\", \"low\" or \"normal\""
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! External subcommands: `jjka foo` runs `jjka-foo` from `PATH`, the way
+//! git runs `git-foo`, so jjka can be extended without forking it.
+//!
+//! The plugin replaces the jjka process and gets its arguments after the
+//! name. `JJKA_CONTEXT` holds JSON describing where it was run: the
+//! workspace, if any, at the operation jjka loaded, and the output flags.
+//! The most used fields are also set as plain variables (`JJKA_BINARY`,
+//! `JJKA_WORKSPACE_ROOT`, `JJKA_REPO_PATH` and `JJKA_OPERATION_ID`).
+
+use anyhow::{Context, Result, anyhow};
+use jj_lib::object_id::ObjectId as _;
+use serde::Serialize;
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt as _;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::exit_code::Failure;
+use crate::format::{OutputFormat, use_color};
+use crate::ui;
+use crate::workspace::{self, WorkspaceHelper};
+
+/// Bumped when a field changes meaning or goes away.
+const CONTEXT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct WorkspaceContext {
+    root: PathBuf,
+    repo_path: PathBuf,
+    name: String,
+    operation_id: String,
+    /// The working-copy commit as of that operation; jjka doesn't snapshot
+    /// the working copy before running a plugin.
+    working_copy_commit_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PluginContext {
+    version: u32,
+    /// The jjka binary, for plugins that call back into it.
+    binary: PathBuf,
+    /// None outside a jj workspace.
+    workspace: Option<WorkspaceContext>,
+    format: OutputFormat,
+    color: bool,
+    quiet: bool,
+}
+
+fn workspace_context(helper: &WorkspaceHelper) -> WorkspaceContext {
+    WorkspaceContext {
+        root: helper.workspace_root().to_path_buf(),
+        repo_path: helper.repo_path().to_path_buf(),
+        name: helper.workspace_name().as_str().to_string(),
+        operation_id: helper.repo().op_id().hex(),
+        working_copy_commit_id: helper.wc_commit_id().map(|id| id.hex()),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Without Unix permissions, the `.exe` suffix is what makes a program.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The first executable called `program` in the directories of `path`.
+fn find_program(program: &str, path: &std::ffi::OsStr) -> Option<PathBuf> {
+    let file_name = format!("{program}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(path)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Runs the plugin for the unknown subcommand `args[0]` with the rest of
+/// `args`. Only returns if the plugin can't be found or started.
+pub fn run_plugin(args: Vec<OsString>, format: OutputFormat) -> Result<()> {
+    let (name, args) = args.split_first().expect("clap passes the subcommand name");
+    let name = name.to_string_lossy();
+    let program_name = format!("jjka-{name}");
+    let program = std::env::var_os("PATH")
+        .and_then(|path| find_program(&program_name, &path))
+        .ok_or_else(|| {
+            Failure::Usage.error(anyhow!(
+                "Unknown command '{name}', and there is no {program_name} on PATH; see `jjka --help`"
+            ))
+        })?;
+
+    // Plugins work outside a workspace too, so one that doesn't load is only
+    // left out of the context.
+    let helper = WorkspaceHelper::load().ok();
+    let settings = match &helper {
+        Some(helper) => Some(helper.settings().clone()),
+        None => workspace::load_settings().ok(),
+    };
+    let context = PluginContext {
+        version: CONTEXT_VERSION,
+        binary: std::env::current_exe().context("Failed to find the jjka binary")?,
+        workspace: helper.as_ref().map(workspace_context),
+        format,
+        color: settings
+            .as_ref()
+            .is_some_and(|settings| use_color(settings).unwrap_or(false)),
+        quiet: ui::is_quiet(),
+    };
+
+    let mut command = Command::new(&program);
+    command
+        .args(args)
+        .env("JJKA_CONTEXT", serde_json::to_string(&context)?)
+        .env("JJKA_BINARY", &context.binary);
+    if let Some(workspace) = &context.workspace {
+        command
+            .env("JJKA_WORKSPACE_ROOT", &workspace.root)
+            .env("JJKA_REPO_PATH", &workspace.repo_path)
+            .env("JJKA_OPERATION_ID", &workspace.operation_id);
+    }
+    tracing::debug!(program = %program.display(), "running plugin");
+    let err = exec(command);
+    Err(anyhow!(err).context(format!("Failed to run {}", program.display())))
+}
+
+/// Replaces this process with `command`, returning only if that fails.
+#[cfg(unix)]
+fn exec(mut command: Command) -> std::io::Error {
+    command.exec()
+}
+
+/// Processes can't be replaced here, so the plugin runs as a child and
+/// jjka exits with its exit code.
+#[cfg(not(unix))]
+fn exec(mut command: Command) -> std::io::Error {
+    match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(err) => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_program() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, mode: u32| {
+            let path = dir.join("jjka-demo");
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+            path
+        };
+        // Not executable, so the search goes on to the next directory.
+        write(first.path(), 0o644);
+        let found = write(second.path(), 0o755);
+        let path = std::env::join_paths([first.path(), second.path()]).unwrap();
+        assert_eq!(find_program("jjka-demo", &path), Some(found));
+        assert_eq!(find_program("jjka-other", &path), None);
+    }
+}
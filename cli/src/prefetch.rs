@@ -0,0 +1,59 @@
+//! `jjka prefetch`: diff a revset ahead of time, so later commands find its
+//! hunks and diffs in the cache.
+//!
+//! Each revision is diffed on a blocking thread of its own, up to `--jobs`
+//! at a time, which also pulls its trees and files into the OS page cache.
+
+use anyhow::Result;
+use futures::StreamExt as _;
+
+use crate::cache;
+use crate::format::commit_summary;
+use crate::hunks::{commit_diffs, commit_hunks};
+use crate::perf;
+use crate::progress::Progress;
+use crate::unified_diff::DEFAULT_CONTEXT;
+use crate::workspace::WorkspaceHelper;
+
+pub async fn prefetch_command(revset: String, clear: bool) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    if clear {
+        let removed = cache::clear()?;
+        println!("Removed the cached hunks of {removed} revision(s)");
+        return Ok(());
+    }
+    helper.snapshot_working_copy()?;
+    let repo = helper.repo().clone();
+    let (cached, commits): (Vec<_>, Vec<_>) = helper
+        .evaluate_revset(&revset)?
+        .into_iter()
+        .partition(|commit| cache::is_cached(commit.id()));
+
+    let mut progress = Progress::new("prefetch", Some(commits.len()));
+    let mut results = futures::stream::iter(commits)
+        .map(|commit| {
+            progress.start_item(&commit_summary(&commit));
+            let repo = repo.clone();
+            tokio::task::spawn_blocking(move || {
+                futures::executor::block_on(async {
+                    // The diff `jjka diff` and `jjka web` show by default.
+                    commit_diffs(repo.as_ref(), &commit, DEFAULT_CONTEXT).await?;
+                    commit_hunks(repo.as_ref(), &commit).await
+                })
+            })
+        })
+        .buffer_unordered(perf::jobs());
+    let mut fetched = 0;
+    let mut hunk_count = 0;
+    while let Some(result) = results.next().await {
+        hunk_count += result??.len();
+        fetched += 1;
+    }
+    drop(results);
+    progress.finish();
+    println!(
+        "Cached {hunk_count} hunk(s) of {fetched} revision(s) in {revset}; {} were already cached",
+        cached.len()
+    );
+    Ok(())
+}
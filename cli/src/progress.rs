@@ -0,0 +1,238 @@
+//! Progress of long-running commands, on stderr.
+//!
+//! When stderr is a terminal, a phase that runs for more than a moment draws
+//! a progress bar with its counter, which is cleared when the phase ends.
+//! With `--progress json`, long-running commands instead write one JSON
+//! object per line as they go, for tools that wrap jjka. Human-readable
+//! output still goes to stdout, so a wrapper can show its own progress bar
+//! and still display the result.
+
+use serde::Serialize;
+use std::io::{IsTerminal as _, Write};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// Newline-delimited JSON events on stderr
+    Json,
+    /// A progress bar on stderr, even if it isn't a terminal
+    Bar,
+    /// No progress reporting
+    None,
+}
+
+static FORMAT: OnceLock<ProgressFormat> = OnceLock::new();
+/// Whether a bar is on the last line of stderr, to be cleared before
+/// anything else is written there.
+static ON_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// How long a phase runs before its bar is drawn, so quick commands don't
+/// flicker.
+const BAR_DELAY: Duration = Duration::from_millis(500);
+/// The least time between redraws of a bar.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sets the progress format for the rest of the process. Without a format,
+/// bars are drawn if stderr is a terminal and `--quiet` isn't passed.
+pub fn init(format: Option<ProgressFormat>) {
+    let format = format.unwrap_or(
+        if std::io::stderr().is_terminal() && !crate::ui::is_quiet() {
+            ProgressFormat::Bar
+        } else {
+            ProgressFormat::None
+        },
+    );
+    let _ = FORMAT.set(format);
+}
+
+fn format() -> ProgressFormat {
+    FORMAT.get().copied().unwrap_or(ProgressFormat::None)
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    phase: &'a str,
+    current: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<usize>,
+    message: &'a str,
+}
+
+/// Progress through one phase of a command, such as rewriting each commit
+/// of a stack.
+///
+/// An event is emitted as each item starts, with `current` counting the
+/// items started before it, and a final one with `current == total` from
+/// [`Progress::finish`]. `total` is left out when it isn't known up front.
+pub struct Progress {
+    phase: &'static str,
+    current: usize,
+    total: Option<usize>,
+    started: Instant,
+    /// When the bar was last drawn, if it's on screen.
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(phase: &'static str, total: Option<usize>) -> Self {
+        Progress {
+            phase,
+            current: 0,
+            total,
+            started: Instant::now(),
+            drawn: None,
+        }
+    }
+
+    /// Reports that work on the next item starts.
+    pub fn start_item(&mut self, message: &str) {
+        match format() {
+            ProgressFormat::Json => emit(self.phase, self.current, self.total, message),
+            ProgressFormat::Bar => self.draw(message),
+            ProgressFormat::None => {}
+        }
+        self.current += 1;
+    }
+
+    pub fn finish(mut self) {
+        self.clear();
+        emit(self.phase, self.current, Some(self.current), "done");
+    }
+
+    fn draw(&mut self, message: &str) {
+        let now = Instant::now();
+        let due = match self.drawn {
+            // Redraw straight away a bar that other output cleared.
+            Some(_) if !ON_SCREEN.load(Ordering::Relaxed) => true,
+            Some(drawn) => now - drawn >= REDRAW_INTERVAL,
+            None => now - self.started >= BAR_DELAY,
+        };
+        // The pager owns the screen while it's open.
+        if !due || crate::pager::is_active() {
+            return;
+        }
+        let line = render_bar(
+            self.phase,
+            self.current,
+            self.total,
+            message,
+            stderr_width(),
+        );
+        let _ = write!(std::io::stderr().lock(), "\r\x1b[K{line}");
+        ON_SCREEN.store(true, Ordering::Relaxed);
+        self.drawn = Some(now);
+    }
+
+    fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            clear_line();
+        }
+    }
+}
+
+/// Runs `f`, which writes to stderr or runs a child that may, with the bar
+/// cleared so the output doesn't run into it. The bar comes back with the
+/// next item.
+pub fn suspend<T>(f: impl FnOnce() -> T) -> T {
+    clear_line();
+    f()
+}
+
+fn clear_line() {
+    if ON_SCREEN.swap(false, Ordering::Relaxed) {
+        let _ = write!(std::io::stderr().lock(), "\r\x1b[K");
+    }
+}
+
+impl Drop for Progress {
+    /// Clears the bar of a phase that ends early, e.g. with an error.
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// One line of progress such as `stats [=====>    ] 5/10 message`, cut to
+/// `width` columns.
+fn render_bar(
+    phase: &str,
+    current: usize,
+    total: Option<usize>,
+    message: &str,
+    width: usize,
+) -> String {
+    const BAR_WIDTH: usize = 20;
+    let mut line = match total {
+        Some(total) if total > 0 => {
+            let filled = (current.min(total) * BAR_WIDTH) / total;
+            let mut bar = "=".repeat(filled);
+            if filled < BAR_WIDTH {
+                bar.push('>');
+                bar.push_str(&" ".repeat(BAR_WIDTH - filled - 1));
+            }
+            format!("{phase} [{bar}] {current}/{total}")
+        }
+        _ => format!("{phase} {current}"),
+    };
+    if !message.is_empty() {
+        line.push(' ');
+        line.push_str(message);
+    }
+    // Stay one column short so the line never wraps.
+    line.chars().take(width.saturating_sub(1)).collect()
+}
+
+#[cfg(unix)]
+fn stderr_width() -> usize {
+    // SAFETY: TIOCGWINSZ fills in the winsize struct.
+    let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+    let ok = unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 {
+        size.ws_col as usize
+    } else {
+        80
+    }
+}
+
+#[cfg(not(unix))]
+fn stderr_width() -> usize {
+    80
+}
+
+fn emit(phase: &str, current: usize, total: Option<usize>, message: &str) {
+    if format() != ProgressFormat::Json {
+        return;
+    }
+    let event = Event {
+        phase,
+        current,
+        total,
+        message,
+    };
+    let line = serde_json::to_string(&event).expect("progress events serialize");
+    // Progress is best effort; a closed stderr must not fail the command.
+    let _ = writeln!(std::io::stderr().lock(), "{line}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_bar() {
+        assert_eq!(
+            render_bar("stats", 5, Some(10), "abc", 80),
+            "stats [==========>         ] 5/10 abc"
+        );
+        assert_eq!(
+            render_bar("stats", 10, Some(10), "", 80),
+            "stats [====================] 10/10"
+        );
+        assert_eq!(
+            render_bar("bisect", 3, None, "testing", 80),
+            "bisect 3 testing"
+        );
+        assert_eq!(render_bar("bisect", 3, None, "testing", 10), "bisect 3 ");
+    }
+}
@@ -0,0 +1,124 @@
+//! Talking to git remotes.
+
+use anyhow::{Result, bail};
+use itertools::Itertools;
+use jj_lib::backend::CommitId;
+use jj_lib::git::{self, GitBranchPushTargets, GitFetch, GitRefUpdate, RemoteCallbacks};
+use jj_lib::ref_name::GitRefNameBuf;
+use jj_lib::ref_name::{RefName, RemoteName};
+use jj_lib::refs::BookmarkPushUpdate;
+use jj_lib::settings::GitSettings;
+use jj_lib::str_util::StringPattern;
+
+use crate::workspace::WorkspaceHelper;
+
+/// Fetches all branches from `remote` and imports them as remote bookmarks.
+pub async fn fetch(helper: &mut WorkspaceHelper, remote: &str) -> Result<()> {
+    let remote_name = RemoteName::new(remote);
+    let git_settings = GitSettings::from_settings(helper.settings())?;
+    let mut tx = helper.start_transaction();
+    let mut git_fetch = GitFetch::new(tx.repo_mut(), &git_settings)?;
+    let refspecs = git::expand_fetch_refspecs(remote_name, vec![StringPattern::everything()])?;
+    git_fetch.fetch(
+        remote_name,
+        refspecs,
+        RemoteCallbacks::default(),
+        None,
+        None,
+    )?;
+    git_fetch.import_refs()?;
+    helper
+        .finish_transaction(tx, &format!("fetch from git remote {remote}"))
+        .await
+}
+
+/// Pushes local bookmarks to `remote`, updating their remote-tracking state.
+pub async fn push_bookmarks(
+    helper: &mut WorkspaceHelper,
+    remote: &str,
+    names: &[String],
+) -> Result<()> {
+    let remote_name = RemoteName::new(remote);
+    let view = helper.repo().view();
+    let branch_updates = names
+        .iter()
+        .map(|name| {
+            let ref_name = RefName::new(name);
+            let Some(new_target) = view.get_local_bookmark(ref_name).as_normal() else {
+                bail!("Bookmark {name} is absent or conflicted");
+            };
+            let remote_ref = view.get_remote_bookmark(ref_name.to_remote_symbol(remote_name));
+            let update = BookmarkPushUpdate {
+                old_target: remote_ref.target.as_normal().cloned(),
+                new_target: Some(new_target.clone()),
+            };
+            Ok((ref_name.to_owned(), update))
+        })
+        .try_collect()?;
+
+    let git_settings = GitSettings::from_settings(helper.settings())?;
+    let mut tx = helper.start_transaction();
+    let stats = git::push_branches(
+        tx.repo_mut(),
+        &git_settings,
+        remote_name,
+        &GitBranchPushTargets { branch_updates },
+        RemoteCallbacks::default(),
+    )?;
+    check_pushed(&stats, remote)?;
+    helper
+        .finish_transaction(tx, &format!("push {} to {remote}", names.join(", ")))
+        .await
+}
+
+fn check_pushed(stats: &git::GitPushStats, remote: &str) -> Result<()> {
+    if !stats.all_ok() {
+        let rejected = stats
+            .rejected
+            .iter()
+            .chain(&stats.remote_rejected)
+            .map(|(name, reason)| match reason {
+                Some(reason) => format!("{} ({reason})", name.as_str()),
+                None => name.as_str().to_string(),
+            })
+            .join(", ");
+        bail!("Push to {remote} was rejected: {rejected}");
+    }
+    Ok(())
+}
+
+/// Pushes `commit` to the ref `git_ref` of `remote`, which isn't expected
+/// to exist there, like Gerrit's `refs/for/<branch>`. Nothing in the repo
+/// changes.
+pub fn push_commit(
+    helper: &WorkspaceHelper,
+    remote: &str,
+    commit: &CommitId,
+    git_ref: &str,
+) -> Result<()> {
+    let git_settings = GitSettings::from_settings(helper.settings())?;
+    let update = GitRefUpdate {
+        qualified_name: GitRefNameBuf::from(git_ref),
+        expected_current_target: None,
+        new_target: Some(commit.clone()),
+    };
+    let stats = git::push_updates(
+        helper.repo().as_ref(),
+        &git_settings,
+        RemoteName::new(remote),
+        &[update],
+        RemoteCallbacks::default(),
+    )?;
+    check_pushed(&stats, remote)
+}
+
+/// The name of `remote`'s branch that trunk() is at.
+pub fn trunk_branch(helper: &WorkspaceHelper, remote: &str) -> Result<Option<String>> {
+    let trunk = helper.resolve_single("trunk()")?;
+    Ok(helper
+        .repo()
+        .view()
+        .remote_bookmarks(RemoteName::new(remote))
+        .find(|(_, remote_ref)| remote_ref.target.as_normal() == Some(trunk.id()))
+        .map(|(name, _)| name.as_str().to_string()))
+}
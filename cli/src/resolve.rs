@@ -0,0 +1,290 @@
+//! `jjka resolve`: resolve file conflicts with an external 3-way merge tool.
+//!
+//! Tools are configured like for `jj resolve`: `ui.merge-editor` names an
+//! entry in `[merge-tools]`, whose `merge-args` may use `$base`, `$left`,
+//! `$right`, `$output`, `$path` and `$marker_length`.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::backend::{CopyId, FileId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::conflicts::{
+    ConflictMarkerStyle, ConflictMaterializeOptions, MaterializedFileConflictValue,
+    choose_materialized_conflict_marker_len, materialize_merge_result_to_bytes,
+    try_materialize_file_conflict_value, update_from_content,
+};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::settings::UserSettings;
+use jj_lib::store::Store;
+use std::process::Command;
+
+use crate::format::commit_summary;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+struct MergeTool {
+    name: String,
+    program: String,
+    merge_args: Vec<String>,
+    /// The tool edits a file with conflict markers in `$output` instead of
+    /// writing the merge result from scratch, so unresolved hunks survive.
+    edits_conflict_markers: bool,
+}
+
+impl MergeTool {
+    fn load(settings: &UserSettings, name: Option<String>) -> Result<Self> {
+        let name = match name {
+            Some(name) => name,
+            None => settings
+                .get_string("ui.merge-editor")
+                .optional()?
+                .context("No merge tool configured; set ui.merge-editor or pass --tool")?,
+        };
+        let program = settings
+            .get_string(["merge-tools", &name, "program"])
+            .optional()?
+            .unwrap_or_else(|| name.clone());
+        let Some(merge_args) = settings
+            .get::<Vec<String>>(["merge-tools", &name, "merge-args"])
+            .optional()?
+        else {
+            bail!(
+                "merge-tools.{name}.merge-args is not set, so {name} can't be used as a merge tool"
+            );
+        };
+        let edits_conflict_markers = settings
+            .get_bool(["merge-tools", &name, "merge-tool-edits-conflict-markers"])
+            .optional()?
+            .unwrap_or(false);
+        Ok(MergeTool {
+            name,
+            program,
+            merge_args,
+            edits_conflict_markers,
+        })
+    }
+}
+
+/// Replaces `$name` variables in each argument.
+pub fn interpolate(args: &[String], vars: &[(&str, String)]) -> Vec<String> {
+    args.iter()
+        .map(|arg| {
+            vars.iter().fold(arg.clone(), |arg, (name, value)| {
+                arg.replace(&format!("${name}"), value)
+            })
+        })
+        .collect()
+}
+
+/// Runs the tool on one conflicted file and returns the new file ids.
+async fn run_merge_tool(
+    tool: &MergeTool,
+    store: &Store,
+    path: &RepoPath,
+    file: &MaterializedFileConflictValue,
+) -> Result<Merge<Option<FileId>>> {
+    let dir = tempfile::Builder::new().prefix("jjka-resolve-").tempdir()?;
+    // Keep the file name so tools can pick a syntax from the extension.
+    let file_name = path
+        .components()
+        .next_back()
+        .map_or("file", |name| name.as_internal_str());
+    let write = |label: &str, content: &[u8]| -> Result<String> {
+        let file_path = dir.path().join(format!("{label}_{file_name}"));
+        std::fs::write(&file_path, content)?;
+        Ok(file_path.to_string_lossy().into_owned())
+    };
+
+    let marker_len = choose_materialized_conflict_marker_len(&file.contents);
+    let initial_output = if tool.edits_conflict_markers {
+        let options = ConflictMaterializeOptions {
+            marker_style: ConflictMarkerStyle::Git,
+            marker_len: Some(marker_len),
+            merge: store.merge_options().clone(),
+        };
+        materialize_merge_result_to_bytes(&file.contents, &options).to_vec()
+    } else {
+        Vec::new()
+    };
+    let vars = [
+        (
+            "base",
+            write("base", &file.contents.get_remove(0).unwrap()[..])?,
+        ),
+        (
+            "left",
+            write("left", &file.contents.get_add(0).unwrap()[..])?,
+        ),
+        (
+            "right",
+            write("right", &file.contents.get_add(1).unwrap()[..])?,
+        ),
+        ("output", write("output", &initial_output)?),
+        ("path", path.as_internal_file_string().to_string()),
+        ("marker_length", marker_len.to_string()),
+    ];
+    let status = Command::new(&tool.program)
+        .args(interpolate(&tool.merge_args, &vars))
+        .status()
+        .with_context(|| format!("Failed to run merge tool {}", tool.program))?;
+    if !status.success() {
+        bail!("Merge tool {} failed ({status})", tool.name);
+    }
+
+    let output = std::fs::read(&vars[3].1)?;
+    if output.is_empty() || output == initial_output {
+        bail!(
+            "Merge tool {} didn't write a resolution for {}",
+            tool.name,
+            path.as_internal_file_string()
+        );
+    }
+    if tool.edits_conflict_markers {
+        Ok(update_from_content(&file.unsimplified_ids, store, path, &output, marker_len).await?)
+    } else {
+        Ok(Merge::normal(
+            store.write_file(path, &mut &output[..]).await?,
+        ))
+    }
+}
+
+/// The conflicted paths to resolve, in tree order. Explicitly requested paths
+/// must be conflicted.
+async fn paths_to_resolve(
+    helper: &WorkspaceHelper,
+    commit: &Commit,
+    paths: &[String],
+) -> Result<Vec<RepoPathBuf>> {
+    let conflicted: Vec<RepoPathBuf> = commit
+        .tree_async()
+        .await?
+        .conflicts()
+        .map(|(path, _)| path)
+        .collect();
+    if paths.is_empty() {
+        return Ok(conflicted);
+    }
+    let mut selected = Vec::new();
+    for input in paths {
+        let path = helper.parse_file_path(input)?;
+        if !conflicted.contains(&path) {
+            bail!("{input} is not conflicted in {}", commit_summary(commit));
+        }
+        selected.push(path);
+    }
+    Ok(selected)
+}
+
+pub async fn resolve_command(
+    revision: String,
+    paths: Vec<String>,
+    tool: Option<String>,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let repo = helper.repo().clone();
+    let store = repo.store();
+    let commit = helper.resolve_single(&revision)?;
+
+    let paths = paths_to_resolve(&helper, &commit, &paths).await?;
+    if paths.is_empty() {
+        status!("No conflicts in {}", commit_summary(&commit));
+        return Ok(());
+    }
+    let tool = MergeTool::load(helper.settings(), tool)?;
+
+    let tree = commit.tree_async().await?;
+    let mut builder = MergedTreeBuilder::new(commit.tree_id().clone());
+    let mut resolved = 0;
+    for path in &paths {
+        let path_str = path.as_internal_file_string();
+        let value = tree.path_value(path)?;
+        let Some(file) = try_materialize_file_conflict_value(store, path, &value).await? else {
+            status!("Skipping {path_str}: not a conflict between regular files");
+            continue;
+        };
+        if file.contents.num_sides() != 2 {
+            status!(
+                "Skipping {path_str}: {}-sided conflicts can't be resolved with a 3-way merge tool",
+                file.contents.num_sides()
+            );
+            continue;
+        }
+        let executable = file.executable.unwrap_or(false);
+        let copy_id = file.copy_id.clone().unwrap_or_else(CopyId::placeholder);
+        let new_ids = run_merge_tool(&tool, store, path, &file).await?;
+        if new_ids.is_resolved() {
+            println!("Resolved {path_str}");
+            resolved += 1;
+        } else {
+            println!("Partially resolved {path_str}");
+        }
+        builder.set_or_remove(
+            path.clone(),
+            new_ids.map(|id| {
+                id.as_ref().map(|id| TreeValue::File {
+                    id: id.clone(),
+                    executable,
+                    copy_id: copy_id.clone(),
+                })
+            }),
+        );
+    }
+    let new_tree_id = builder.write_tree(store)?;
+    if new_tree_id == *commit.tree_id() {
+        status!("Nothing changed");
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    let new_commit = tx
+        .repo_mut()
+        .rewrite_commit(&commit)
+        .set_tree_id(new_tree_id)
+        .write()?;
+    helper
+        .finish_transaction(
+            tx,
+            &format!("resolve conflicts in commit {}", commit.id().hex()),
+        )
+        .await?;
+    let remaining = new_commit.tree_async().await?.conflicts().count();
+    println!(
+        "Resolved {resolved} of {} path(s) in {}",
+        paths.len(),
+        commit_summary(&new_commit)
+    );
+    if remaining > 0 {
+        println!("{remaining} conflicted path(s) remain");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        let args = [
+            "--out=$output".to_string(),
+            "$left".to_string(),
+            "$$base".to_string(),
+        ];
+        let vars = [
+            ("left", "/tmp/l".to_string()),
+            ("base", "/tmp/b".to_string()),
+            ("output", "/tmp/o".to_string()),
+        ];
+        assert_eq!(
+            interpolate(&args, &vars),
+            vec!["--out=/tmp/o", "/tmp/l", "$/tmp/b"]
+        );
+    }
+}
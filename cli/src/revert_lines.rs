@@ -0,0 +1,127 @@
+//! `jjka revert-lines`: restore line ranges of working-copy files to their
+//! content in another revision, the line-level version of `jj restore`.
+//!
+//! The ranges are line numbers in the working copy. Where the diff from
+//! the working copy to the revision touches a selected line, that line is
+//! put back the way the revision has it; the rest of the file is kept.
+
+use anyhow::Result;
+use itertools::Itertools;
+use jj_lib::diff::{ContentDiff, DiffHunkKind};
+use jjka_lib::selection::line_count;
+
+use crate::format::commit_summary;
+use crate::line_edit::{LinesOf, edit_working_copy_lines};
+use crate::ui::status;
+use crate::workspace::{self, PolicyArgs, WorkspaceHelper};
+
+/// Undoes the changes from `source` to `target` on the lines of `target`
+/// in `ranges`. A selected line of a changed block is dropped, and the
+/// block's lines from `source` go where its first selected line was; a
+/// line `target` lacks is restored when a line next to it is selected.
+fn restore_lines(target: &[u8], source: &[u8], ranges: &[(usize, usize)]) -> Vec<u8> {
+    let selected = |line: usize| {
+        ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&line))
+    };
+    let diff = ContentDiff::by_line([target, source]);
+    let mut result = Vec::new();
+    // 1-indexed number of the next line of `target`.
+    let mut target_line = 1;
+    for hunk in diff.hunks() {
+        let [current, original] = [hunk.contents[0], hunk.contents[1]];
+        match hunk.kind {
+            DiffHunkKind::Matching => {
+                result.extend_from_slice(current);
+                target_line += line_count(current);
+            }
+            DiffHunkKind::Different => {
+                let current_lines = current.split_inclusive(|b| *b == b'\n').collect_vec();
+                let first = target_line;
+                target_line += current_lines.len();
+                if current_lines.is_empty() {
+                    if selected(first - 1) || selected(first) {
+                        result.extend_from_slice(original);
+                    }
+                    continue;
+                }
+                let mut restored = false;
+                for (line, content) in (first..).zip(current_lines) {
+                    if !selected(line) {
+                        result.extend_from_slice(content);
+                    } else if !restored {
+                        result.extend_from_slice(original);
+                        restored = true;
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+pub async fn revert_lines_command(
+    revision: String,
+    ranges: Vec<String>,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let ranges = workspace::parse_ranges(&ranges)?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let source = helper.resolve_single(&revision)?;
+    let changed_files = edit_working_copy_lines(
+        &mut helper,
+        &source,
+        &ranges,
+        LinesOf::WorkingCopy,
+        restore_lines,
+        &format!("revert lines to commit {}", commit_summary(&source)),
+    )
+    .await?;
+    if changed_files.is_empty() {
+        status!(
+            "Nothing to revert: the selected lines are the same in {}",
+            commit_summary(&source)
+        );
+        return Ok(());
+    }
+    println!(
+        "Reverted lines in the working copy to {}:",
+        commit_summary(&source)
+    );
+    for file in &changed_files {
+        println!("  {file}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_lines() {
+        let target = b"a\nX\nY\nd\n";
+        let source = b"a\nb\nc\nd\n";
+        assert_eq!(restore_lines(target, source, &[(2, 3)]), source);
+        assert_eq!(restore_lines(target, source, &[(1, 1)]), target);
+        // Part of a changed block brings back the whole original block.
+        assert_eq!(restore_lines(target, source, &[(2, 2)]), b"a\nb\nc\nY\nd\n");
+        // Lines the working copy added are dropped.
+        assert_eq!(
+            restore_lines(b"a\nextra\nb\n", b"a\nb\n", &[(2, 2)]),
+            b"a\nb\n"
+        );
+        // Lines the working copy deleted come back next to a selected line.
+        assert_eq!(
+            restore_lines(b"a\nc\n", b"a\nb\nc\n", &[(1, 1)]),
+            b"a\nb\nc\n"
+        );
+        assert_eq!(
+            restore_lines(b"a\nc\nd\n", b"a\nb\nc\nd\n", &[(3, 3)]),
+            b"a\nc\nd\n"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! `jjka review`: a full-screen walk through a stack, one commit at a time.
+//!
+//! Review marks are keyed by change id and patch id, so they survive
+//! rebases and description edits but not changes to the diff itself. They're
+//! kept in `.jj/repo/jjka/reviewed`, one key per line.
+
+use anyhow::{Context, Result};
+use jj_lib::backend::ChangeId;
+use jj_lib::commit::Commit;
+use jj_lib::repo::Repo;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::describe::{clean_description, edit_description, editor_command, initial_description};
+use crate::diff_tool::DiffTool;
+use crate::format::{commit_summary, format_timestamp};
+use crate::highlight::{Language, Theme, language_for_path};
+use crate::patch_id::patch_id;
+use crate::state_file;
+use crate::terminal::{Key, RawTerminal};
+use crate::tree_diff::commit_file_contents;
+use crate::unified_diff::{DEFAULT_CONTEXT, LineKind, unified_hunks};
+use crate::workspace::{PolicyArgs, WorkspaceHelper, current_commit};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+    Title,
+    Plain,
+    File,
+    HunkHeader,
+    /// Diff lines, with the language to highlight them as.
+    Context(Option<&'static Language>),
+    Added(Option<&'static Language>),
+    Removed(Option<&'static Language>),
+    /// Output of the `ui.diff-formatter` tool, with its own colors.
+    External,
+    Status,
+}
+
+struct ReviewItem {
+    change_id: ChangeId,
+    title: String,
+    /// `<change id>:<patch id>`, the key for review marks.
+    key: String,
+    lines: Vec<(Style, String)>,
+}
+
+async fn review_item(
+    repo: &dyn Repo,
+    commit: &Commit,
+    diff_tool: Option<&DiffTool>,
+) -> Result<ReviewItem> {
+    let key = format!(
+        "{}:{}",
+        commit.change_id().reverse_hex(),
+        patch_id(repo, commit).await?
+    );
+    let author = commit.author();
+    let mut lines = vec![(
+        Style::Plain,
+        format!(
+            "Author: {} <{}>  {}",
+            author.name,
+            author.email,
+            format_timestamp(&author.timestamp)
+        ),
+    )];
+    lines.push((Style::Plain, String::new()));
+    for line in commit.description().lines() {
+        lines.push((Style::Plain, format!("    {line}")));
+    }
+    for file in commit_file_contents(repo, commit).await? {
+        lines.push((Style::Plain, String::new()));
+        let path = file.path.as_internal_file_string();
+        let language = language_for_path(path);
+        lines.push((Style::File, path.to_string()));
+        if let Some(tool) = diff_tool {
+            let output = tool.render(path, &file.before, &file.after)?;
+            for line in String::from_utf8_lossy(&output).lines() {
+                lines.push((Style::External, line.to_string()));
+            }
+            continue;
+        }
+        for hunk in unified_hunks(&file.before, &file.after, DEFAULT_CONTEXT) {
+            lines.push((Style::HunkHeader, hunk.header()));
+            for line in hunk.lines {
+                let (style, sign) = match line.kind {
+                    LineKind::Context => (Style::Context(language), ' '),
+                    LineKind::Removed => (Style::Removed(language), '-'),
+                    LineKind::Added => (Style::Added(language), '+'),
+                };
+                lines.push((style, format!("{sign}{}", line.text)));
+            }
+        }
+    }
+    Ok(ReviewItem {
+        change_id: commit.change_id().clone(),
+        title: commit_summary(commit),
+        key,
+        lines,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    None,
+    Quit,
+    Describe,
+    Split,
+}
+
+struct ReviewState {
+    index: usize,
+    scroll: usize,
+    reviewed: BTreeSet<String>,
+}
+
+impl ReviewState {
+    /// Applies a key press; `page` is the number of body lines on screen.
+    fn handle_key(&mut self, items: &[ReviewItem], key: Key, page: usize) -> Action {
+        let max_scroll = items[self.index].lines.len().saturating_sub(page);
+        match key {
+            Key::Char('q') | Key::Escape => return Action::Quit,
+            Key::Char('j') | Key::Down | Key::Enter => self.scroll += 1,
+            Key::Char('k') | Key::Up => self.scroll = self.scroll.saturating_sub(1),
+            Key::Char(' ') | Key::PageDown => self.scroll += page,
+            Key::Char('b') | Key::PageUp => self.scroll = self.scroll.saturating_sub(page),
+            Key::Char('g') | Key::Home => self.scroll = 0,
+            Key::Char('G') | Key::End => self.scroll = max_scroll,
+            Key::Char('n') => self.go_to(items, self.index + 1),
+            Key::Char('p') => self.go_to(items, self.index.saturating_sub(1)),
+            Key::Char('r') => {
+                let key = &items[self.index].key;
+                if !self.reviewed.remove(key) {
+                    self.reviewed.insert(key.clone());
+                    // Move on to the next commit once this one is done.
+                    self.go_to(items, self.index + 1);
+                }
+            }
+            Key::Char('e') => return Action::Describe,
+            Key::Char('s') => return Action::Split,
+            _ => {}
+        }
+        self.scroll = self.scroll.min(max_scroll);
+        Action::None
+    }
+
+    fn go_to(&mut self, items: &[ReviewItem], index: usize) {
+        if index < items.len() && index != self.index {
+            self.index = index;
+            self.scroll = 0;
+        }
+    }
+
+    /// The lines to show on a `rows` x `cols` screen.
+    fn screen(&self, items: &[ReviewItem], rows: usize, cols: usize) -> Vec<(Style, String)> {
+        let item = &items[self.index];
+        let mark = if self.reviewed.contains(&item.key) {
+            "[reviewed] "
+        } else {
+            ""
+        };
+        let mut screen = vec![(
+            Style::Title,
+            format!("[{}/{}] {mark}{}", self.index + 1, items.len(), item.title),
+        )];
+        let page = rows.saturating_sub(2);
+        screen.extend(item.lines.iter().skip(self.scroll).take(page).cloned());
+        screen.resize(rows.saturating_sub(1), (Style::Plain, String::new()));
+        let reviewed = items
+            .iter()
+            .filter(|item| self.reviewed.contains(&item.key))
+            .count();
+        screen.push((
+            Style::Status,
+            format!(
+                "{reviewed}/{} reviewed  n/p commit  j/k/space scroll  r mark  e describe  s split  q quit",
+                items.len()
+            ),
+        ));
+        for (_, line) in &mut screen {
+            truncate_columns(line, cols);
+        }
+        screen
+    }
+}
+
+/// Cuts `line` down to `cols` characters, not counting the escape sequences
+/// a diff tool colors its output with.
+fn truncate_columns(line: &mut String, cols: usize) {
+    let mut shown = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if c == '\x1b' && chars.next_if(|&(_, c)| c == '[').is_some() {
+            // A CSI sequence ends with a byte in @..~.
+            while chars.next_if(|&(_, c)| !('@'..='~').contains(&c)).is_some() {}
+            chars.next();
+            continue;
+        }
+        if shown == cols {
+            line.truncate(index);
+            return;
+        }
+        shown += 1;
+    }
+}
+
+fn draw(screen: &[(Style, String)], theme: Option<&Theme>) -> Result<()> {
+    let mut out = String::from("\x1b[H");
+    for (row, (style, line)) in screen.iter().enumerate() {
+        let (color, language) = match *style {
+            Style::Title => ("\x1b[1;7m", None),
+            Style::Plain => ("", None),
+            Style::File => ("\x1b[1m", None),
+            Style::HunkHeader => ("\x1b[36m", None),
+            Style::Context(language) => ("", language),
+            Style::Added(language) => ("\x1b[32m", language),
+            Style::Removed(language) => ("\x1b[31m", language),
+            Style::External => ("", None),
+            Style::Status => ("\x1b[7m", None),
+        };
+        let line = match (theme, language, line.chars().next()) {
+            (Some(theme), Some(language), Some(sign)) => format!(
+                "{sign}{}",
+                theme.paint(language, &line[sign.len_utf8()..], color)
+            ),
+            _ => line.clone(),
+        };
+        out.push_str(&format!("\x1b[{};1H\x1b[2K{color}{line}\x1b[0m", row + 1));
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(out.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn reviewed_path(helper: &WorkspaceHelper) -> PathBuf {
+    helper.repo_path().join("jjka").join("reviewed")
+}
+
+fn load_reviewed(path: &Path) -> Result<BTreeSet<String>> {
+    let content = state_file::read(path)?.unwrap_or_default();
+    Ok(content.lines().map(str::to_string).collect())
+}
+
+fn save_reviewed(path: &Path, reviewed: &BTreeSet<String>) -> Result<()> {
+    let content: String = reviewed.iter().map(|key| format!("{key}\n")).collect();
+    state_file::write(path, content.as_bytes())
+}
+
+async fn load_items(
+    helper: &WorkspaceHelper,
+    revset: &str,
+    diff_tool: Option<&DiffTool>,
+) -> Result<Vec<ReviewItem>> {
+    let repo = helper.repo().clone();
+    let mut items = Vec::new();
+    for commit in helper.evaluate_revset(revset)? {
+        items.push(review_item(repo.as_ref(), &commit, diff_tool).await?);
+    }
+    Ok(items)
+}
+
+pub async fn review_command(revset: String, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let diff_tool = DiffTool::load(helper.settings())?;
+    let mut items = load_items(&helper, &revset, diff_tool.as_ref()).await?;
+    if items.is_empty() {
+        println!("No revisions to review");
+        return Ok(());
+    }
+    let reviewed_path = reviewed_path(&helper);
+    let mut state = ReviewState {
+        index: 0,
+        scroll: 0,
+        reviewed: load_reviewed(&reviewed_path)?,
+    };
+
+    let theme = Theme::from_settings(helper.settings())?;
+    let terminal = RawTerminal::enter()?;
+    loop {
+        let (rows, cols) = terminal.size();
+        draw(&state.screen(&items, rows, cols), theme.as_ref())?;
+        let page = rows.saturating_sub(2);
+        let change_id = items[state.index].change_id.clone();
+        match state.handle_key(&items, terminal.read_key()?, page) {
+            Action::None => continue,
+            Action::Quit => break,
+            Action::Describe => {
+                let commit = current_commit(helper.repo().as_ref(), &change_id)?;
+                let initial = initial_description(&helper, &commit).await?;
+                let editor = editor_command(&helper)?;
+                let edited = terminal.suspend(|| edit_description(&editor, &initial))??;
+                // An unfinished template counts as cancelling the edit.
+                let Ok(description) = clean_description(&edited) else {
+                    continue;
+                };
+                if description == commit.description() {
+                    continue;
+                }
+                let mut tx = helper.start_transaction();
+                tx.repo_mut()
+                    .rewrite_commit(&commit)
+                    .set_description(description)
+                    .write()?;
+                helper
+                    .finish_transaction(tx, &format!("describe commit {}", commit_summary(&commit)))
+                    .await?;
+            }
+            Action::Split => {
+                let status = terminal.suspend(|| {
+                    Command::new("jj")
+                        .args(["split", "-r", &change_id.reverse_hex()])
+                        .status()
+                })?;
+                status.context("Failed to run jj split")?;
+                // jj changed the repo behind our back.
+                helper = WorkspaceHelper::load()?;
+                helper.set_policy_args(&policy);
+            }
+        }
+        items = load_items(&helper, &revset, diff_tool.as_ref()).await?;
+        if items.is_empty() {
+            break;
+        }
+        // Stay on the same change after it was rewritten.
+        state.index = items
+            .iter()
+            .position(|item| item.change_id == change_id)
+            .unwrap_or(state.index.min(items.len() - 1));
+        state.scroll = 0;
+    }
+    drop(terminal);
+
+    save_reviewed(&reviewed_path, &state.reviewed)?;
+    let reviewed = items
+        .iter()
+        .filter(|item| state.reviewed.contains(&item.key))
+        .count();
+    println!("Reviewed {reviewed} of {} revision(s)", items.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u8, lines: usize) -> ReviewItem {
+        ReviewItem {
+            change_id: ChangeId::new(vec![id]),
+            title: format!("commit {id}"),
+            key: format!("{id}:patch"),
+            lines: (0..lines)
+                .map(|n| (Style::Plain, format!("line {n}")))
+                .collect(),
+        }
+    }
+
+    fn state() -> ReviewState {
+        ReviewState {
+            index: 0,
+            scroll: 0,
+            reviewed: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let items = [item(1, 30), item(2, 5)];
+        let mut state = state();
+        assert_eq!(state.handle_key(&items, Key::Char('j'), 10), Action::None);
+        assert_eq!(state.scroll, 1);
+        state.handle_key(&items, Key::PageDown, 10);
+        assert_eq!(state.scroll, 11);
+        state.handle_key(&items, Key::End, 10);
+        assert_eq!(state.scroll, 20);
+        state.handle_key(&items, Key::Char(' '), 10);
+        assert_eq!(state.scroll, 20);
+
+        // Marking a commit reviewed moves on to the next one.
+        state.handle_key(&items, Key::Char('r'), 10);
+        assert_eq!((state.index, state.scroll), (1, 0));
+        assert!(state.reviewed.contains("1:patch"));
+        state.handle_key(&items, Key::Char('n'), 10);
+        assert_eq!(state.index, 1);
+        state.handle_key(&items, Key::Char('p'), 10);
+        state.handle_key(&items, Key::Char('r'), 10);
+        assert!(state.reviewed.is_empty());
+
+        assert_eq!(
+            state.handle_key(&items, Key::Char('e'), 10),
+            Action::Describe
+        );
+        assert_eq!(state.handle_key(&items, Key::Char('s'), 10), Action::Split);
+        assert_eq!(state.handle_key(&items, Key::Char('q'), 10), Action::Quit);
+    }
+
+    #[test]
+    fn test_screen() {
+        let items = [item(1, 30), item(2, 5)];
+        let mut state = state();
+        state.reviewed.insert("2:patch".to_string());
+        state.scroll = 3;
+        let screen = state.screen(&items, 6, 12);
+        let text: Vec<_> = screen.iter().map(|(_, line)| line.as_str()).collect();
+        assert_eq!(
+            text,
+            [
+                "[1/2] commit",
+                "line 3",
+                "line 4",
+                "line 5",
+                "line 6",
+                "1/2 reviewed"
+            ]
+        );
+        assert_eq!(screen[0].0, Style::Title);
+        assert_eq!(screen[5].0, Style::Status);
+
+        state.handle_key(&items, Key::Char('n'), 4);
+        let screen = state.screen(&items, 8, 80);
+        assert_eq!(screen[0].1, "[2/2] [reviewed] commit 2");
+        // Short commits are padded out to the full screen.
+        assert_eq!(screen.len(), 8);
+        assert_eq!(screen[6].1, "");
+    }
+
+    #[test]
+    fn test_truncate_columns() {
+        let mut line = "\x1b[1;31mremoved\x1b[0m line".to_string();
+        truncate_columns(&mut line, 5);
+        assert_eq!(line, "\x1b[1;31mremov");
+        let mut line = "\x1b[32mshort\x1b[0m".to_string();
+        truncate_columns(&mut line, 5);
+        assert_eq!(line, "\x1b[32mshort\x1b[0m");
+    }
+
+    #[test]
+    fn test_reviewed_marks_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jjka").join("reviewed");
+        assert!(load_reviewed(&path).unwrap().is_empty());
+        let marks = BTreeSet::from(["abc:123".to_string(), "def:456".to_string()]);
+        save_reviewed(&path, &marks).unwrap();
+        assert_eq!(load_reviewed(&path).unwrap(), marks);
+    }
+}
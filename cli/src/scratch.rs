@@ -0,0 +1,71 @@
+//! Temporary workspaces for running commands against other revisions
+//! without touching the user's working copy.
+
+use anyhow::{Context, Result};
+use jj_lib::commit::Commit;
+use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::workspace::Workspace;
+use std::path::Path;
+use tempfile::TempDir;
+
+use crate::format::short_commit_id;
+use crate::workspace::WorkspaceHelper;
+
+/// A workspace in a temporary directory. Call [`ScratchWorkspace::remove`]
+/// when done so it doesn't linger in `jj workspace list`.
+pub struct ScratchWorkspace {
+    workspace: Workspace,
+    name: WorkspaceNameBuf,
+    // Dropped after `workspace`, removing the files.
+    _dir: TempDir,
+}
+
+impl ScratchWorkspace {
+    /// Creates a workspace named `<prefix>-<pid>`.
+    pub fn create(helper: &mut WorkspaceHelper, prefix: &str) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("{prefix}-"))
+            .tempdir()
+            .context("Failed to create a scratch directory")?;
+        let name = format!("{prefix}-{}", std::process::id());
+        let workspace = helper.add_workspace(dir.path(), &name)?;
+        Ok(ScratchWorkspace {
+            workspace,
+            name: WorkspaceNameBuf::from(name),
+            _dir: dir,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        self.workspace.workspace_root()
+    }
+
+    /// Updates the files to `commit` by starting a new working-copy commit on
+    /// top of it, like `jj new`.
+    pub fn check_out(&mut self, helper: &mut WorkspaceHelper, commit: &Commit) -> Result<()> {
+        let mut tx = helper.start_transaction();
+        let wc_commit = tx.repo_mut().check_out(self.name.clone(), commit)?;
+        helper.finish_transaction_unreported(
+            tx,
+            &format!(
+                "check out {} in workspace {}",
+                short_commit_id(commit),
+                self.name.as_symbol()
+            ),
+        )?;
+        self.workspace
+            .check_out(helper.repo().op_id().clone(), None, &wc_commit)
+            .with_context(|| format!("Failed to check out {}", short_commit_id(commit)))?;
+        Ok(())
+    }
+
+    /// Forgets the workspace, abandoning its working-copy commit if empty.
+    pub fn remove(self, helper: &mut WorkspaceHelper) -> Result<()> {
+        let mut tx = helper.start_transaction();
+        tx.repo_mut().remove_wc_commit(&self.name)?;
+        helper.finish_transaction_unreported(
+            tx,
+            &format!("forget workspace {}", self.name.as_symbol()),
+        )
+    }
+}
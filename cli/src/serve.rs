@@ -0,0 +1,206 @@
+//! `jjka serve`: answer JSON-RPC 2.0 requests from editor plugins.
+//!
+//! Requests and responses are JSON objects, one per line, on stdin and
+//! stdout or on a unix socket. Every request loads the repo at its latest
+//! operation and snapshots the working copy first, so plugins never see
+//! stale state.
+
+use anyhow::{Context, Result, bail};
+use itertools::Itertools;
+use jj_lib::annotate::FileAnnotator;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use tokio::io::BufReader;
+use tokio::net::UnixListener;
+
+use crate::LineRange;
+use crate::format::subject;
+use crate::impact;
+use crate::rpc::{self, METHOD_NOT_FOUND, RpcError};
+use crate::split::{apply_split, plan_split};
+use crate::tree_diff::{changed_paths, read_file};
+use crate::unified_diff::count_changed_lines;
+use crate::workspace::WorkspaceHelper;
+
+pub fn default_revision() -> String {
+    "@".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct SplitParams {
+    #[serde(default = "default_revision")]
+    revision: String,
+    ranges: Vec<String>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnnotateParams {
+    #[serde(default = "default_revision")]
+    revision: String,
+    path: String,
+}
+
+#[derive(Serialize)]
+pub struct CommitInfo {
+    commit_id: String,
+    change_id: String,
+    subject: String,
+}
+
+impl CommitInfo {
+    pub fn new(commit: &Commit) -> Self {
+        CommitInfo {
+            commit_id: commit.id().hex(),
+            change_id: commit.change_id().reverse_hex(),
+            subject: subject(commit).to_string(),
+        }
+    }
+}
+
+fn parse_ranges(ranges: &[String]) -> Result<Vec<LineRange>> {
+    ranges
+        .iter()
+        .map(|range| LineRange::parse(range))
+        .try_collect()
+        .map_err(Into::into)
+}
+
+/// The workspace at the latest operation, with the working copy
+/// snapshotted.
+pub fn load() -> Result<WorkspaceHelper> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    Ok(helper)
+}
+
+/// The working-copy commit and the files it changes.
+async fn status() -> Result<Value> {
+    let helper = load()?;
+    let repo = helper.repo().clone();
+    let wc_id = helper
+        .wc_commit_id()
+        .context("This workspace has no working-copy commit")?;
+    let wc = repo.store().get_commit(wc_id)?;
+    let mut files = Vec::new();
+    let parent_tree = wc.parent_tree(repo.as_ref())?;
+    for change in changed_paths(&parent_tree, &wc.tree_async().await?).await? {
+        let before = read_file(repo.store(), &change.path, change.before).await?;
+        let after = read_file(repo.store(), &change.path, change.after).await?;
+        let (insertions, deletions) =
+            count_changed_lines(&before.unwrap_or_default(), &after.unwrap_or_default());
+        files.push(json!({
+            "path": change.path.as_internal_file_string(),
+            "insertions": insertions,
+            "deletions": deletions,
+        }));
+    }
+    let parents: Vec<CommitInfo> = wc
+        .parents()
+        .map_ok(|parent| CommitInfo::new(&parent))
+        .try_collect()?;
+    Ok(json!({
+        "workspace_root": helper.workspace_root(),
+        "operation": repo.op_id().hex(),
+        "working_copy": CommitInfo::new(&wc),
+        "parents": parents,
+        "files": files,
+    }))
+}
+
+async fn preview_split(params: SplitParams) -> Result<Value> {
+    let ranges = parse_ranges(&params.ranges)?;
+    let helper = load()?;
+    let commit = helper.resolve_single(&params.revision)?;
+    let plan = plan_split(helper.repo().as_ref(), &commit, &ranges).await?;
+    Ok(json!({
+        "commit": CommitInfo::new(&commit),
+        "files": plan.files,
+    }))
+}
+
+/// Splits the revision, returning both commits and the impact.
+pub async fn apply_split_request(params: SplitParams) -> Result<Value> {
+    let ranges = parse_ranges(&params.ranges)?;
+    let mut helper = load()?;
+    let commit = helper.resolve_single(&params.revision)?;
+    let plan = plan_split(helper.repo().as_ref(), &commit, &ranges).await?;
+    let (selected, remaining) = apply_split(&mut helper, &plan, params.message.as_deref()).await?;
+    Ok(json!({
+        "selected": CommitInfo::new(&selected),
+        "remaining": CommitInfo::new(&remaining),
+        "files": plan.files,
+        "operations": impact::take().await?,
+    }))
+}
+
+/// The commit that last changed each line of a file.
+async fn annotate(params: AnnotateParams) -> Result<Value> {
+    let helper = load()?;
+    let commit = helper.resolve_single(&params.revision)?;
+    let path = helper.parse_file_path(&params.path)?;
+    let repo = helper.repo().clone();
+    let mut annotator = FileAnnotator::from_commit(&commit, &path)?;
+    annotator.compute(repo.as_ref(), &helper.resolve_revset("all()")?)?;
+    let annotation = annotator.to_annotation();
+    let mut lines = Vec::new();
+    for (number, (origin, _)) in (1..).zip(annotation.line_origins()) {
+        let origin = origin.unwrap_or_else(|origin| origin);
+        let origin_commit = repo.store().get_commit(&origin.commit_id)?;
+        lines.push(json!({
+            "line": number,
+            "commit_id": origin.commit_id.hex(),
+            "change_id": origin_commit.change_id().reverse_hex(),
+            "subject": subject(&origin_commit),
+            "origin_line": origin.line_number + 1,
+        }));
+    }
+    Ok(json!({ "commit": CommitInfo::new(&commit), "lines": lines }))
+}
+
+async fn dispatch(method: String, params: Value) -> Result<Value, RpcError> {
+    match method.as_str() {
+        "status" => Ok(status().await?),
+        "previewSplit" => Ok(preview_split(rpc::params(params)?).await?),
+        "applySplit" => Ok(apply_split_request(rpc::params(params)?).await?),
+        "annotate" => Ok(annotate(rpc::params(params)?).await?),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {method:?}"),
+        )),
+    }
+}
+
+pub async fn serve_command(socket: Option<PathBuf>) -> Result<()> {
+    // Fail early if we aren't in a repo.
+    WorkspaceHelper::load()?;
+    let Some(socket) = socket else {
+        return rpc::serve(
+            dispatch,
+            BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await;
+    };
+    if socket.exists() {
+        bail!(
+            "{} already exists; remove it if no server is using it",
+            socket.display()
+        );
+    }
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    println!("Listening on {}", socket.display());
+    // One client at a time, like `web`.
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (reader, writer) = stream.into_split();
+        if let Err(err) = rpc::serve(dispatch, BufReader::new(reader), writer).await {
+            eprintln!("Error handling client: {err:#}");
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! `jjka sign`: sign the commits of a revset with the configured GPG or SSH
+//! key, rebasing their descendants.
+//!
+//! The signing backend and key come from jj's `signing.backend` and
+//! `signing.key` settings. Revisions that are already signed are left alone,
+//! unless they have to be rewritten anyway because an ancestor was signed.
+//!
+//! Every other command's rewrites go through [`resign_rewritten`], so a
+//! signature isn't lost without a word when jj doesn't re-sign a commit
+//! itself, as it does only for the user's own.
+
+use anyhow::{Result, anyhow, bail};
+use itertools::Itertools as _;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::repo::Repo;
+use jj_lib::settings::UserSettings;
+use jj_lib::signing::SignBehavior;
+use jj_lib::transaction::Transaction;
+use std::collections::HashSet;
+
+use crate::PolicyArgs;
+use crate::exit_code::Failure;
+use crate::format::{commit_summary, short_commit_id};
+use crate::ui::status;
+use crate::workspace::WorkspaceHelper;
+
+pub async fn sign_command(revset: String, key: Option<String>, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    if !helper.repo().store().signer().can_sign() {
+        return Err(Failure::Precondition.error(anyhow!(
+            "No signing backend is configured; set signing.backend to \"gpg\" or \"ssh\", and \
+             signing.key to the key to sign with"
+        )));
+    }
+    helper.snapshot_working_copy()?;
+
+    let root_commit_id = helper.repo().store().root_commit_id().clone();
+    let commits = helper.evaluate_revset(&revset)?;
+    if commits.iter().any(|commit| *commit.id() == root_commit_id) {
+        bail!("The root commit can't be signed");
+    }
+    let selected: HashSet<CommitId> = commits.iter().map(|commit| commit.id().clone()).collect();
+    let roots: Vec<CommitId> = commits
+        .iter()
+        .filter(|commit| !commit.is_signed())
+        .map(|commit| commit.id().clone())
+        .collect();
+    if roots.is_empty() {
+        status!("Nothing to sign: every revision in {revset} is already signed");
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    let mut signed: Vec<(Commit, Commit)> = Vec::new();
+    tx.repo_mut()
+        .transform_descendants(roots, async |rewriter| {
+            let old_commit = rewriter.old_commit().clone();
+            if selected.contains(old_commit.id()) {
+                let mut builder = rewriter.reparent().set_sign_behavior(SignBehavior::Force);
+                if let Some(key) = &key {
+                    builder = builder.set_sign_key(key.clone());
+                }
+                signed.push((old_commit, builder.write()?));
+            } else {
+                rewriter.rebase().await?.write()?;
+            }
+            Ok(())
+        })?;
+    helper
+        .finish_transaction(tx, &format!("sign {revset}"))
+        .await?;
+
+    status!("Signed {} commit(s):", signed.len());
+    for (old_commit, new_commit) in &signed {
+        println!(
+            "  {} -> {}",
+            short_commit_id(old_commit),
+            commit_summary(new_commit)
+        );
+    }
+    Ok(())
+}
+
+/// Deals with the commits `tx` rewrote from signed ones among `hidden`, the
+/// commits of `old_repo` it hides, that were left unsigned: re-signs them
+/// if `jjka.signing.resign` is set, and warns about them otherwise. Nothing
+/// is done if `signing.behavior` is "drop". Returns whether it rewrote any.
+pub fn resign_rewritten(
+    settings: &UserSettings,
+    old_repo: &dyn Repo,
+    tx: &mut Transaction,
+    hidden: &[CommitId],
+) -> Result<bool> {
+    if settings.sign_settings().behavior == SignBehavior::Drop {
+        return Ok(false);
+    }
+    let mut signed_changes = HashSet::new();
+    for id in hidden {
+        let old_commit = old_repo.store().get_commit(id)?;
+        if old_commit.is_signed() {
+            signed_changes.insert(old_commit.change_id().clone());
+        }
+    }
+    let mut dropped = Vec::new();
+    for change_id in &signed_changes {
+        for id in tx.repo().resolve_change_id(change_id).unwrap_or_default() {
+            let commit = tx.repo().store().get_commit(&id)?;
+            if !commit.is_signed() {
+                dropped.push(commit);
+            }
+        }
+    }
+    if dropped.is_empty() {
+        return Ok(false);
+    }
+
+    let resign = settings
+        .get_bool("jjka.signing.resign")
+        .optional()?
+        .unwrap_or(false);
+    let can_sign = tx.repo().store().signer().can_sign();
+    if !resign || !can_sign {
+        let how = if can_sign {
+            "set jjka.signing.resign = true to re-sign them, or sign them with `jjka sign`"
+        } else {
+            "configure signing.backend and sign them with `jjka sign`"
+        };
+        eprintln!(
+            "Warning: Rewriting dropped the signatures of {} commit(s): {}; {how}",
+            dropped.len(),
+            dropped.iter().map(commit_summary).join(", ")
+        );
+        return Ok(false);
+    }
+    // Signing a commit rewrites its descendants, which are visited too.
+    let roots = dropped.iter().map(|commit| commit.id().clone()).collect();
+    let mut resigned = 0;
+    tx.repo_mut()
+        .transform_descendants(roots, async |rewriter| {
+            if signed_changes.contains(rewriter.old_commit().change_id()) {
+                rewriter
+                    .reparent()
+                    .set_sign_behavior(SignBehavior::Force)
+                    .write()?;
+                resigned += 1;
+            } else {
+                rewriter.rebase().await?.write()?;
+            }
+            Ok(())
+        })?;
+    // Everything was visited, but the rewrites are still recorded.
+    tx.repo_mut().rebase_descendants()?;
+    status!("Re-signed {resigned} rewritten commit(s)");
+    Ok(true)
+}
@@ -0,0 +1,68 @@
+//! Stopping quietly when whatever reads stdout goes away.
+//!
+//! Rust ignores SIGPIPE, so printing to a pipe whose reader has exited
+//! fails instead, and `println!` panics on the failure: `jjka diff | head -1`
+//! would end with "failed printing to stdout". jjka puts SIGPIPE back to
+//! its default at startup and is stopped by it like other Unix tools.
+//!
+//! Programs jjka runs, like hooks and strategy scripts, may exit without
+//! reading all of their input. Writing it blocks SIGPIPE on the writing
+//! thread, so a closed pipe there is an error to handle, not the end of
+//! jjka.
+
+use std::io::{self, Write};
+
+#[cfg(unix)]
+pub fn init() {
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn init() {}
+
+/// Writes all of `bytes` to `pipe`, the stdin of a program jjka runs. If
+/// the program has closed it, this fails with `BrokenPipe`.
+#[cfg(unix)]
+pub fn write_to_child(mut pipe: impl Write, bytes: &[u8]) -> io::Result<()> {
+    unsafe {
+        let mut sigpipe = std::mem::zeroed();
+        libc::sigemptyset(&mut sigpipe);
+        libc::sigaddset(&mut sigpipe, libc::SIGPIPE);
+        let mut previous = std::mem::zeroed();
+        libc::pthread_sigmask(libc::SIG_BLOCK, &sigpipe, &mut previous);
+        let result = pipe.write_all(bytes);
+        // The failed write left a SIGPIPE pending on this thread, which
+        // would be delivered once it's unblocked.
+        let mut pending = std::mem::zeroed();
+        libc::sigpending(&mut pending);
+        if libc::sigismember(&pending, libc::SIGPIPE) == 1 {
+            let mut signal = 0;
+            libc::sigwait(&sigpipe, &mut signal);
+        }
+        libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+        result
+    }
+}
+
+#[cfg(not(unix))]
+pub fn write_to_child(mut pipe: impl Write, bytes: &[u8]) -> io::Result<()> {
+    pipe.write_all(bytes)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_write_to_child_that_exited() {
+        init();
+        let mut child = Command::new("true").stdin(Stdio::piped()).spawn().unwrap();
+        let stdin = child.stdin.take().unwrap();
+        child.wait().unwrap();
+        let err = write_to_child(stdin, &[b'x'; 1 << 20]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,263 @@
+//! `jjka snapshot`: named stashes of the working-copy changes.
+//!
+//! A snapshot is a hidden commit with the working-copy commit's parents and
+//! tree, so its diff is exactly the changes that were in `@`. The names are
+//! kept in `.jj/repo/jjka/snapshots`, one `<name> <commit id>` per line.
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Subcommand;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::format::{OutputFormat, commit_summary, format_timestamp};
+use crate::impact::DiffStat;
+use crate::serve::CommitInfo;
+use crate::state_file;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotCommand {
+    /// Save the working-copy changes and reset the working copy
+    Save {
+        /// The name to save the snapshot as
+        name: String,
+
+        /// Leave the changes in the working copy
+        #[arg(long)]
+        keep: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// List saved snapshots
+    List,
+
+    /// Apply a snapshot's changes to the working copy
+    Restore {
+        /// The snapshot to restore
+        name: String,
+
+        /// Forget the snapshot after restoring it
+        #[arg(long)]
+        drop: bool,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+    },
+
+    /// Forget a snapshot
+    Drop {
+        /// The snapshot to forget
+        name: String,
+    },
+}
+
+fn snapshots_path(helper: &WorkspaceHelper) -> PathBuf {
+    helper.repo_path().join("jjka").join("snapshots")
+}
+
+/// The saved snapshots, oldest first.
+fn load_snapshots(path: &Path) -> Result<Vec<(String, CommitId)>> {
+    let Some(content) = state_file::read(path)? else {
+        return Ok(vec![]);
+    };
+    content
+        .lines()
+        .map(|line| {
+            let (name, hex) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Invalid line in {}: {line}", path.display()))?;
+            let id = CommitId::try_from_hex(hex)
+                .ok_or_else(|| anyhow!("Invalid commit id in {}: {hex}", path.display()))?;
+            Ok((name.to_string(), id))
+        })
+        .collect()
+}
+
+fn save_snapshots(path: &Path, snapshots: &[(String, CommitId)]) -> Result<()> {
+    let content: String = snapshots
+        .iter()
+        .map(|(name, id)| format!("{name} {}\n", id.hex()))
+        .collect();
+    state_file::write(path, content.as_bytes())
+}
+
+/// The commits of all saved snapshots. They're hidden, so anything that
+/// collects unreachable commits has to keep these alive itself.
+pub fn snapshot_commit_ids(helper: &WorkspaceHelper) -> Result<Vec<CommitId>> {
+    let snapshots = load_snapshots(&snapshots_path(helper))?;
+    Ok(snapshots.into_iter().map(|(_, id)| id).collect())
+}
+
+fn find_snapshot(helper: &WorkspaceHelper, name: &str) -> Result<Commit> {
+    let snapshots = load_snapshots(&snapshots_path(helper))?;
+    let Some((_, id)) = snapshots.iter().find(|(n, _)| n == name) else {
+        bail!("No snapshot named \"{name}\"; see `jjka snapshot list`");
+    };
+    Ok(helper.repo().store().get_commit(id)?)
+}
+
+fn wc_commit(helper: &WorkspaceHelper) -> Result<Commit> {
+    let id = helper
+        .wc_commit_id()
+        .context("This workspace has no working-copy commit")?;
+    Ok(helper.repo().store().get_commit(id)?)
+}
+
+async fn save(name: String, keep: bool, policy: PolicyArgs) -> Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("Snapshot names can't be empty or contain whitespace");
+    }
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let path = snapshots_path(&helper);
+    let mut snapshots = load_snapshots(&path)?;
+    if snapshots.iter().any(|(n, _)| *n == name) {
+        bail!("Snapshot \"{name}\" already exists; drop it first");
+    }
+    let wc = wc_commit(&helper)?;
+    let repo = helper.repo().clone();
+    if wc.is_empty(repo.as_ref())? {
+        bail!("Nothing to snapshot: the working copy has no changes");
+    }
+
+    let mut tx = helper.start_transaction();
+    let snapshot = tx
+        .repo_mut()
+        .new_commit(wc.parent_ids().to_vec(), wc.tree_id().clone())
+        .set_description(format!("jjka snapshot {name}\n"))
+        .detach()
+        .write_hidden()?;
+    if !keep {
+        let parent_tree = wc.parent_tree(repo.as_ref())?;
+        tx.repo_mut()
+            .rewrite_commit(&wc)
+            .set_tree_id(parent_tree.id())
+            .write()?;
+        helper
+            .finish_transaction(tx, &format!("save snapshot {name}"))
+            .await?;
+    }
+    snapshots.push((name.clone(), snapshot.id().clone()));
+    save_snapshots(&path, &snapshots)?;
+    let stat = DiffStat::of_commit(repo.as_ref(), &snapshot).await?;
+    println!("Saved snapshot {name} ({})", stat.format());
+    if !keep {
+        status!("Reset the working copy to its parent");
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SnapshotEntry {
+    name: String,
+    commit_id: String,
+    time: String,
+    diffstat: DiffStat,
+    /// The parent the changes were made on, `None` for the root.
+    base: Option<CommitInfo>,
+    #[serde(skip)]
+    base_summary: String,
+}
+
+async fn list(format: OutputFormat) -> Result<()> {
+    let helper = WorkspaceHelper::load()?;
+    let snapshots = load_snapshots(&snapshots_path(&helper))?;
+    let repo = helper.repo().clone();
+    let mut entries = Vec::new();
+    for (name, id) in &snapshots {
+        let snapshot = repo.store().get_commit(id)?;
+        let base = snapshot.parents().next().transpose()?;
+        entries.push(SnapshotEntry {
+            name: name.clone(),
+            commit_id: id.hex(),
+            time: format_timestamp(&snapshot.committer().timestamp),
+            diffstat: DiffStat::of_commit(repo.as_ref(), &snapshot).await?,
+            base: base.as_ref().map(CommitInfo::new),
+            base_summary: base.as_ref().map_or("root".to_string(), commit_summary),
+        });
+    }
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        status!("No snapshots");
+        return Ok(());
+    }
+    let width = entries.iter().map(|entry| entry.name.len()).max().unwrap();
+    for entry in &entries {
+        println!(
+            "{:<width$}  {}  {}  on {}",
+            entry.name,
+            entry.time,
+            entry.diffstat.format(),
+            entry.base_summary
+        );
+    }
+    Ok(())
+}
+
+async fn restore(name: String, drop: bool, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let snapshot = find_snapshot(&helper, &name)?;
+    let wc = wc_commit(&helper)?;
+    let repo = helper.repo().clone();
+
+    let new_tree = wc
+        .tree_async()
+        .await?
+        .merge(
+            snapshot.parent_tree(repo.as_ref())?,
+            snapshot.tree_async().await?,
+        )
+        .await?;
+    let mut tx = helper.start_transaction();
+    let new_wc = tx
+        .repo_mut()
+        .rewrite_commit(&wc)
+        .set_tree_id(new_tree.id())
+        .write()?;
+    helper
+        .finish_transaction(tx, &format!("restore snapshot {name}"))
+        .await?;
+    println!("Restored snapshot {name} into {}", commit_summary(&new_wc));
+    if !wc.has_conflict()? && new_wc.has_conflict()? {
+        status!("The working copy now has conflicts; see `jjka conflicts -r @`");
+    }
+    if drop {
+        drop_snapshot(&helper, &name)?;
+    }
+    Ok(())
+}
+
+fn drop_snapshot(helper: &WorkspaceHelper, name: &str) -> Result<()> {
+    let path = snapshots_path(helper);
+    let mut snapshots = load_snapshots(&path)?;
+    let count = snapshots.len();
+    snapshots.retain(|(n, _)| n != name);
+    if snapshots.len() == count {
+        bail!("No snapshot named \"{name}\"; see `jjka snapshot list`");
+    }
+    save_snapshots(&path, &snapshots)?;
+    println!("Dropped snapshot {name}");
+    Ok(())
+}
+
+pub async fn snapshot_command(command: SnapshotCommand, format: OutputFormat) -> Result<()> {
+    match command {
+        SnapshotCommand::Save { name, keep, policy } => save(name, keep, policy).await,
+        SnapshotCommand::List => list(format).await,
+        SnapshotCommand::Restore { name, drop, policy } => restore(name, drop, policy).await,
+        SnapshotCommand::Drop { name } => drop_snapshot(&WorkspaceHelper::load()?, &name),
+    }
+}
@@ -0,0 +1,205 @@
+//! Splitting revisions for `hunksplit`, `apply-plan`, `serve` and `mcp`,
+//! on top of [`jjka_lib::split`], in a transaction of the workspace.
+
+use anyhow::{Context, Result};
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use jjka_lib::fingerprint::FingerprintedHunk;
+use jjka_lib::{Selection, SplitFile, SplitPlan};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::LineRange;
+use crate::checks;
+use crate::exit_code::{Failure, ResultExt as _};
+use crate::failpoint;
+use crate::format::commit_summary;
+use crate::perf;
+use crate::progress::Progress;
+use crate::workspace::WorkspaceHelper;
+
+/// Works out the split of `commit` for `ranges` on `--jobs` threads,
+/// reporting each file as progress. Fails if either commit would be left
+/// empty.
+pub async fn plan_split(
+    repo: &dyn Repo,
+    commit: &Commit,
+    ranges: &[LineRange],
+) -> Result<SplitPlan> {
+    let plan = plan_split_with_modes(repo, commit, ranges, &[]).await?;
+    plan.check_not_empty()?;
+    Ok(plan)
+}
+
+/// [`plan_split`] that also moves the executable bit changes of the files
+/// at `modes`, leaving empty commits for [`check_empty`] to refuse.
+pub async fn plan_split_with_modes(
+    repo: &dyn Repo,
+    commit: &Commit,
+    ranges: &[LineRange],
+    modes: &[String],
+) -> Result<SplitPlan> {
+    let mut selection: Selection = ranges.iter().collect();
+    for path in modes {
+        selection.add_mode(path);
+    }
+    let mut progress = Progress::new("split", Some(selection.len()));
+    let plan = jjka_lib::plan_split_with_jobs(repo, commit, &selection, perf::jobs(), |path| {
+        progress.start_item(path);
+    })
+    .await?;
+    progress.finish();
+    Ok(plan)
+}
+
+/// Splits the revision as planned, described with `description` or the
+/// revision's description. Returns both commits.
+pub async fn apply_split(
+    helper: &mut WorkspaceHelper,
+    plan: &SplitPlan,
+    description: Option<&str>,
+) -> Result<(Commit, Commit)> {
+    let mut tx = helper.start_transaction();
+    let outcome = jjka_lib::split(tx.repo_mut(), plan, description)?;
+    failpoint::hit("split")?;
+    checks::check_commits(helper, &[&outcome.selected, &outcome.remaining])?;
+    helper
+        .finish_transaction(
+            tx,
+            &format!("split lines out of commit {}", commit_summary(&plan.commit)),
+        )
+        .await?;
+    Ok((outcome.selected, outcome.remaining))
+}
+
+/// The version of [`PlanFile`]'s format, bumped when it changes
+/// incompatibly.
+const PLAN_VERSION: u32 = 1;
+
+/// A split written by `hunksplit --dry-run --emit-plan`, to be reviewed or
+/// edited and then carried out by `apply-plan`.
+///
+/// The plan names the exact commit it was made for, as line numbers only
+/// mean something in one version of a file. If the revision has been
+/// rewritten in the meantime, the ranges are carried over by the
+/// fingerprints of its hunks, and the plan is refused if any got lost.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlanFile {
+    pub version: u32,
+    pub change_id: String,
+    pub commit_id: String,
+    /// `path:start-end` ranges, as passed to `hunksplit` but with the paths
+    /// made repo-relative like [`PlanFile::modes`].
+    pub ranges: Vec<String>,
+    /// The files whose executable bit change moves too, as passed to
+    /// `hunksplit --mode`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modes: Vec<String>,
+    /// Whether the split may leave either commit empty, as with
+    /// `hunksplit --allow-empty`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_empty: bool,
+    /// The description of the new commit; the revision's when missing.
+    #[serde(default)]
+    pub message: Option<String>,
+    /// What the split does to each file, for reviewers. It's worked out
+    /// again when the plan is applied, so editing it has no effect.
+    #[serde(default)]
+    pub files: Vec<SplitFile>,
+    /// The revision's hunks when the plan was made, for finding the ranges
+    /// again after a rewrite.
+    #[serde(default)]
+    pub hunks: Vec<FingerprintedHunk>,
+}
+
+impl PlanFile {
+    pub fn new(
+        plan: SplitPlan,
+        ranges: Vec<String>,
+        modes: Vec<String>,
+        allow_empty: bool,
+        message: Option<String>,
+        hunks: Vec<FingerprintedHunk>,
+    ) -> Self {
+        PlanFile {
+            version: PLAN_VERSION,
+            change_id: plan.commit.change_id().reverse_hex(),
+            commit_id: plan.commit.id().hex(),
+            ranges,
+            modes,
+            allow_empty,
+            message,
+            files: plan.files,
+            hunks,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write the plan to {}", path.display()))
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the plan {}", path.display()))?;
+        let plan: PlanFile = serde_json::from_str(&json)
+            .with_context(|| format!("{} is not a valid plan", path.display()))
+            .failure(Failure::Usage)?;
+        if plan.version != PLAN_VERSION {
+            return Err(Failure::Usage.error(anyhow::anyhow!(
+                "{} is a version {} plan; this jjka only reads version {PLAN_VERSION}",
+                path.display(),
+                plan.version
+            )));
+        }
+        Ok(plan)
+    }
+}
+
+/// Refuses a plan with ranges that select only unchanged lines, or with
+/// `force` just warns about them.
+pub fn check_unchanged(plan: &SplitPlan, force: bool) -> Result<()> {
+    if plan.unchanged.is_empty() {
+        return Ok(());
+    }
+    let ranges = plan
+        .unchanged
+        .iter()
+        .map(|range| format!("{}:{}-{}", range.path, range.start, range.end))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let message = format!(
+        "{ranges} select only lines unchanged in {}",
+        commit_summary(&plan.commit)
+    );
+    if !force {
+        return Err(
+            Failure::Usage.error(anyhow::anyhow!("{message}; pass --force to split anyway"))
+        );
+    }
+    eprintln!("Warning: {message}");
+    Ok(())
+}
+
+/// Refuses a plan that leaves either commit without changes, unless
+/// `allow_empty`.
+pub fn check_empty(plan: &SplitPlan, allow_empty: bool) -> Result<()> {
+    if allow_empty {
+        return Ok(());
+    }
+    plan.check_not_empty().map_err(|err| {
+        Failure::Usage.error(anyhow::anyhow!(
+            "{err}; pass --allow-empty to make the empty commit anyway"
+        ))
+    })
+}
+
+/// Prints the commits a split of `commit` created.
+pub fn print_split(commit: &Commit, selected: &Commit, remaining: &Commit) {
+    println!("Split {} into:", commit_summary(commit));
+    println!("  {}", commit_summary(selected));
+    println!("  {}", commit_summary(remaining));
+}
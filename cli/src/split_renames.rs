@@ -0,0 +1,252 @@
+//! `jjka split-renames`: separate renames and copies from the edits made to
+//! the moved files, so each half diffs cleanly.
+//!
+//! Renames are found with the backend's copy records, which for git means
+//! git's rename detection. A pure rename is what that detection is best at,
+//! so the rename commit keeps `blame` and `log --follow` working across it.
+
+use anyhow::{Result, bail};
+use futures::TryStreamExt as _;
+use jj_lib::backend::{CopyRecord, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
+use jj_lib::repo::Repo;
+
+use crate::format::commit_summary;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// A copy record from the revision, whether its source is gone, and
+/// whether the target's content differs from the source's.
+struct Move {
+    record: CopyRecord,
+    is_rename: bool,
+    is_modified: bool,
+}
+
+async fn find_moves(repo: &dyn Repo, commit: &Commit) -> Result<Vec<Move>> {
+    let parent_id = &commit.parent_ids()[0];
+    let records: Vec<CopyRecord> = repo
+        .store()
+        .get_copy_records(None, parent_id, commit.id())?
+        .try_collect()
+        .await?;
+    let tree = commit.tree_async().await?;
+    let mut moves = Vec::new();
+    for record in records {
+        if record.source == record.target {
+            continue;
+        }
+        let is_rename = tree.path_value(&record.source)?.is_absent();
+        let target = tree.path_value(&record.target)?;
+        let is_modified = target
+            .as_resolved()
+            .and_then(|value| value.as_ref())
+            .is_none_or(|value| match value {
+                TreeValue::File { id, .. } => *id != record.source_file,
+                _ => true,
+            });
+        moves.push(Move {
+            record,
+            is_rename,
+            is_modified,
+        });
+    }
+    Ok(moves)
+}
+
+/// `parent_tree` with only the moves applied, each target getting its
+/// source's unmodified content.
+fn apply_moves(repo: &dyn Repo, parent_tree: &MergedTree, moves: &[Move]) -> Result<MergedTree> {
+    let mut builder = MergedTreeBuilder::new(parent_tree.id());
+    for entry in moves {
+        let source = parent_tree.path_value(&entry.record.source)?;
+        builder.set_or_remove(entry.record.target.clone(), source);
+        if entry.is_rename {
+            builder.set_or_remove(entry.record.source.clone(), Merge::absent());
+        }
+    }
+    let tree_id = builder.write_tree(repo.store())?;
+    Ok(repo.store().get_root_tree(&tree_id)?)
+}
+
+/// `tree` with each rename undone, keeping the target's content at the
+/// source path. Copies are left as they are.
+fn undo_renames(repo: &dyn Repo, tree: &MergedTree, moves: &[Move]) -> Result<MergedTree> {
+    let mut builder = MergedTreeBuilder::new(tree.id());
+    for entry in moves.iter().filter(|entry| entry.is_rename) {
+        let target = tree.path_value(&entry.record.target)?;
+        builder.set_or_remove(entry.record.source.clone(), target);
+        builder.set_or_remove(entry.record.target.clone(), Merge::absent());
+    }
+    let tree_id = builder.write_tree(repo.store())?;
+    Ok(repo.store().get_root_tree(&tree_id)?)
+}
+
+fn moves_description(moves: &[Move]) -> String {
+    let verb = |entry: &Move| if entry.is_rename { "Rename" } else { "Copy" };
+    match moves {
+        [entry] => format!(
+            "{} {} to {}\n",
+            verb(entry),
+            entry.record.source.as_internal_file_string(),
+            entry.record.target.as_internal_file_string()
+        ),
+        _ => {
+            let renames = moves.iter().filter(|entry| entry.is_rename).count();
+            let files = |n: usize| {
+                if n == 1 {
+                    "1 file".to_string()
+                } else {
+                    format!("{n} files")
+                }
+            };
+            let subject = match (renames, moves.len() - renames) {
+                (n, 0) => format!("Rename {}", files(n)),
+                (0, n) => format!("Copy {}", files(n)),
+                (r, c) => format!("Rename {} and copy {}", files(r), files(c)),
+            };
+            let mut description = format!("{subject}\n\n");
+            for entry in moves {
+                description.push_str(&format!(
+                    "{} -> {}\n",
+                    entry.record.source.as_internal_file_string(),
+                    entry.record.target.as_internal_file_string()
+                ));
+            }
+            description
+        }
+    }
+}
+
+pub async fn split_renames_command(
+    revision: String,
+    edits_first: bool,
+    dry_run: bool,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+    let commit = helper.resolve_single(&revision)?;
+    if commit.parent_ids().len() != 1 {
+        bail!("Can't split merge commit {}", commit_summary(&commit));
+    }
+
+    let repo = helper.repo().clone();
+    let moves = find_moves(repo.as_ref(), &commit).await?;
+    if !moves.iter().any(|entry| entry.is_modified) {
+        status!(
+            "Nothing to split: no renamed or copied file in {} was also modified",
+            commit_summary(&commit)
+        );
+        return Ok(());
+    }
+    if dry_run {
+        println!("Would split {}:", commit_summary(&commit));
+        for entry in &moves {
+            println!(
+                "  {} {} -> {}{}",
+                if entry.is_rename { "Rename" } else { "Copy" },
+                entry.record.source.as_internal_file_string(),
+                entry.record.target.as_internal_file_string(),
+                if entry.is_modified { " (modified)" } else { "" }
+            );
+        }
+        return Ok(());
+    }
+
+    let parent_tree = commit.parent_tree(repo.as_ref())?;
+    let tree = commit.tree_async().await?;
+    let description = moves_description(&moves);
+    let mut tx = helper.start_transaction();
+    // The commit with the edits keeps the change id and description, so the
+    // change stays recognizable; the renames get a new commit.
+    let (first, second) = if edits_first {
+        let edits_tree = undo_renames(repo.as_ref(), &tree, &moves)?;
+        let edits = tx
+            .repo_mut()
+            .rewrite_commit(&commit)
+            .set_tree_id(edits_tree.id())
+            .write()?;
+        let renames = tx
+            .repo_mut()
+            .new_commit(vec![edits.id().clone()], tree.id())
+            .set_description(description)
+            .write()?;
+        // Descendants go on top of the second half.
+        tx.repo_mut()
+            .set_rewritten_commit(commit.id().clone(), renames.id().clone());
+        (edits, renames)
+    } else {
+        let renames_tree = apply_moves(repo.as_ref(), &parent_tree, &moves)?;
+        let renames = tx
+            .repo_mut()
+            .new_commit(commit.parent_ids().to_vec(), renames_tree.id())
+            .set_description(description)
+            .write()?;
+        let edits = tx
+            .repo_mut()
+            .rewrite_commit(&commit)
+            .set_parents(vec![renames.id().clone()])
+            .write()?;
+        (renames, edits)
+    };
+    helper
+        .finish_transaction(
+            tx,
+            &format!("split renames out of commit {}", commit_summary(&commit)),
+        )
+        .await?;
+
+    println!("Split {} into:", commit_summary(&commit));
+    println!("  {}", commit_summary(&first));
+    println!("  {}", commit_summary(&second));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::backend::{CommitId, FileId};
+    use jj_lib::repo_path::RepoPathBuf;
+
+    fn entry(source: &str, target: &str, is_rename: bool) -> Move {
+        Move {
+            record: CopyRecord {
+                target: RepoPathBuf::from_internal_string(target).unwrap(),
+                target_commit: CommitId::new(vec![0]),
+                source: RepoPathBuf::from_internal_string(source).unwrap(),
+                source_file: FileId::new(vec![0]),
+                source_commit: CommitId::new(vec![0]),
+            },
+            is_rename,
+            is_modified: true,
+        }
+    }
+
+    #[test]
+    fn test_moves_description() {
+        assert_eq!(
+            moves_description(&[entry("src/a.rs", "src/b.rs", true)]),
+            "Rename src/a.rs to src/b.rs\n"
+        );
+        assert_eq!(
+            moves_description(&[entry("a", "b", false)]),
+            "Copy a to b\n"
+        );
+        assert_eq!(
+            moves_description(&[entry("a", "b", true), entry("c", "d", false)]),
+            "Rename 1 file and copy 1 file\n\na -> b\nc -> d\n"
+        );
+        assert_eq!(
+            moves_description(&[
+                entry("a", "b", true),
+                entry("c", "d", true),
+                entry("e", "f", false)
+            ]),
+            "Rename 2 files and copy 1 file\n\na -> b\nc -> d\ne -> f\n"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! `jjka stack`: list a stack with the CI status of each pushed commit.
+//!
+//! A commit counts as pushed when a branch of the remote points at it, like
+//! the `jjka/<change-id>` branches `submit` pushes. Its status comes from
+//! GitHub's check runs or from GitLab's newest pipeline for the commit,
+//! depending on the remote's forge. The bottom commits that passed are
+//! the ones that are safe to land.
+
+use anyhow::{Result, anyhow, bail};
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::ref_name::RemoteName;
+use serde::Serialize;
+
+use crate::exit_code::Failure;
+use crate::forge::{remote_config, remote_web_url};
+use crate::format::{OutputFormat, commit_summary, subject};
+use crate::github::{CheckRun, GitHub, PullRequest};
+use crate::gitlab::{GitLab, MergeRequest, MergeRequestFields};
+use crate::submit::gitlab;
+use crate::template::Template;
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CiStatus {
+    Passed,
+    Failed,
+    Pending,
+    /// Pushed, but no CI ran for it.
+    None,
+    NotPushed,
+}
+
+impl CiStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            CiStatus::Passed => "passed",
+            CiStatus::Failed => "failed",
+            CiStatus::Pending => "pending",
+            CiStatus::None => "no CI",
+            CiStatus::NotPushed => "not pushed",
+        }
+    }
+
+    /// The status of a GitLab pipeline that is `status`.
+    fn from_pipeline(status: &str) -> Self {
+        match status {
+            "success" => CiStatus::Passed,
+            "failed" | "canceled" => CiStatus::Failed,
+            "skipped" => CiStatus::None,
+            // created, waiting_for_resource, preparing, pending, running,
+            // manual and scheduled.
+            _ => CiStatus::Pending,
+        }
+    }
+
+    /// The status of a commit with GitHub check `runs`: failed if any
+    /// failed, otherwise pending until they're all done.
+    fn from_check_runs(runs: &[CheckRun]) -> Self {
+        if runs.is_empty() {
+            return CiStatus::None;
+        }
+        let failed = runs.iter().any(|run| {
+            matches!(
+                run.conclusion.as_deref(),
+                Some("failure" | "cancelled" | "timed_out" | "action_required" | "startup_failure")
+            )
+        });
+        if failed {
+            CiStatus::Failed
+        } else if runs.iter().any(|run| run.status != "completed") {
+            CiStatus::Pending
+        } else {
+            CiStatus::Passed
+        }
+    }
+}
+
+/// The merge request or pull request a branch is reviewed in.
+pub struct Review {
+    /// Like "!12" on GitLab or "#12" on GitHub.
+    pub label: String,
+    pub number: u64,
+    pub url: String,
+}
+
+impl Review {
+    fn github(request: PullRequest) -> Self {
+        Review {
+            label: format!("#{}", request.number),
+            number: request.number,
+            url: request.html_url,
+        }
+    }
+
+    fn gitlab(request: MergeRequest) -> Self {
+        Review {
+            label: format!("!{}", request.iid),
+            number: request.iid,
+            url: request.web_url,
+        }
+    }
+}
+
+/// The forge a remote is on, for reading CI status and, in `land` and
+/// `backport`, reviews.
+pub enum Ci {
+    GitHub(GitHub),
+    GitLab(GitLab),
+}
+
+impl Ci {
+    pub fn new(helper: &WorkspaceHelper, remote: &str) -> Result<Self> {
+        let config = remote_config(helper, remote)?;
+        let Some(url) = config.url.clone().or(remote_web_url(helper, remote)?) else {
+            return Err(Failure::Precondition.error(anyhow!(
+                "Can't tell the web URL of remote {remote}; set jjka.forge.remotes.{remote}.url"
+            )));
+        };
+        match config.kind_for(&url) {
+            Some("github") => {
+                let token_env = config.token_env.as_deref().unwrap_or("GITHUB_TOKEN");
+                Ok(Ci::GitHub(GitHub::new(
+                    &url,
+                    std::env::var(token_env).ok(),
+                )?))
+            }
+            Some("gitlab") => Ok(Ci::GitLab(gitlab(helper, remote)?)),
+            Some(kind) => {
+                bail!("Remote {remote} is on {kind}, which jjka can't get CI status from")
+            }
+            None => Err(Failure::Precondition.error(anyhow!(
+                "Can't tell which forge remote {remote} is on; set \
+                 jjka.forge.remotes.{remote}.kind to \"github\" or \"gitlab\""
+            ))),
+        }
+    }
+
+    pub fn status(&self, commit: &Commit) -> Result<CiStatus> {
+        let sha = commit.id().hex();
+        Ok(match self {
+            Ci::GitHub(github) => CiStatus::from_check_runs(&github.check_runs(&sha)?),
+            Ci::GitLab(gitlab) => gitlab
+                .pipeline_status(&sha)?
+                .map_or(CiStatus::None, |status| CiStatus::from_pipeline(&status)),
+        })
+    }
+
+    /// The open review from `branch`, if there is one.
+    pub fn find_review(&self, branch: &str) -> Result<Option<Review>> {
+        Ok(match self {
+            Ci::GitHub(github) => github.find_open(branch)?.map(Review::github),
+            Ci::GitLab(gitlab) => gitlab.find_open(branch)?.map(Review::gitlab),
+        })
+    }
+
+    /// Whether `review` is approved.
+    pub fn approved(&self, review: &Review) -> Result<bool> {
+        match self {
+            Ci::GitHub(github) => github.approved(review.number),
+            Ci::GitLab(gitlab) => gitlab.approved(review.number),
+        }
+    }
+
+    /// Opens a review of `branch` into `target`, unless one is already
+    /// open from it. Returns the review and whether it's new.
+    pub fn open_review(
+        &self,
+        branch: &str,
+        target: &str,
+        title: &str,
+        description: &str,
+    ) -> Result<(Review, bool)> {
+        if let Some(review) = self.find_review(branch)? {
+            return Ok((review, false));
+        }
+        let review = match self {
+            Ci::GitHub(github) => {
+                Review::github(github.create(branch, target, title, description)?)
+            }
+            Ci::GitLab(gitlab) => {
+                let fields = MergeRequestFields {
+                    target_branch: target,
+                    title,
+                    description,
+                };
+                Review::gitlab(gitlab.create(branch, &fields)?)
+            }
+        };
+        Ok((review, true))
+    }
+}
+
+#[derive(Serialize)]
+struct StackEntry {
+    commit_id: String,
+    change_id: String,
+    subject: String,
+    branch: Option<String>,
+    ci: CiStatus,
+}
+
+/// How many commits from the bottom of `entries`, which are newest first,
+/// passed CI.
+fn safe_to_land(entries: &[StackEntry]) -> usize {
+    entries
+        .iter()
+        .rev()
+        .take_while(|entry| entry.ci == CiStatus::Passed)
+        .count()
+}
+
+pub async fn stack_command(
+    revisions: String,
+    remote: String,
+    template: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let template = template.as_deref().map(Template::parse).transpose()?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    let repo = helper.repo().clone();
+
+    let mut commits = Vec::new();
+    for commit in helper.evaluate_revset(&revisions)? {
+        if commit.description().is_empty() && commit.is_empty(repo.as_ref())? {
+            continue;
+        }
+        commits.push(commit);
+    }
+    commits.reverse();
+    let remote_name = RemoteName::new(&remote);
+    let branch_of = |commit: &Commit| {
+        repo.view()
+            .remote_bookmarks(remote_name)
+            .find(|(_, remote_ref)| remote_ref.target.as_normal() == Some(commit.id()))
+            .map(|(name, _)| name.as_str().to_string())
+    };
+    let ci = if commits.iter().any(|commit| branch_of(commit).is_some()) {
+        Some(Ci::new(&helper, &remote)?)
+    } else {
+        None
+    };
+
+    let mut entries = Vec::new();
+    let mut summaries = Vec::new();
+    for commit in &commits {
+        let branch = branch_of(commit);
+        let status = match (&ci, &branch) {
+            (Some(ci), Some(_)) => ci.status(commit)?,
+            _ => CiStatus::NotPushed,
+        };
+        summaries.push(match &template {
+            Some(template) => template.render(repo.as_ref(), commit)?,
+            None => commit_summary(commit),
+        });
+        entries.push(StackEntry {
+            commit_id: commit.id().hex(),
+            change_id: commit.change_id().reverse_hex(),
+            subject: subject(commit).to_string(),
+            branch,
+            ci: status,
+        });
+    }
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    println!("Stack {revisions} on {remote}:");
+    let width = summaries.iter().map(String::len).max().unwrap_or(0);
+    for (entry, summary) in entries.iter().zip(&summaries) {
+        let branch = entry.branch.as_deref().unwrap_or("-");
+        println!("  {summary:width$}  {branch}  {}", entry.ci.label());
+    }
+    match safe_to_land(&entries) {
+        0 if entries.is_empty() => {}
+        0 => println!("Nothing is safe to land yet"),
+        count => println!("Safe to land: the bottom {count} commit(s)"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(status: &str, conclusion: Option<&str>) -> CheckRun {
+        CheckRun {
+            status: status.to_string(),
+            conclusion: conclusion.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_from_check_runs() {
+        let passed = run("completed", Some("success"));
+        let skipped = run("completed", Some("skipped"));
+        let running = run("in_progress", None);
+        let failed = run("completed", Some("failure"));
+        assert_eq!(CiStatus::from_check_runs(&[]), CiStatus::None);
+        assert_eq!(
+            CiStatus::from_check_runs(&[passed.clone(), skipped]),
+            CiStatus::Passed
+        );
+        assert_eq!(
+            CiStatus::from_check_runs(&[passed.clone(), running.clone()]),
+            CiStatus::Pending
+        );
+        assert_eq!(
+            CiStatus::from_check_runs(&[running, failed, passed]),
+            CiStatus::Failed
+        );
+        assert_eq!(CiStatus::from_pipeline("running"), CiStatus::Pending);
+    }
+}
@@ -0,0 +1,51 @@
+//! The small files jjka keeps its own state in, under `.jj/repo/jjka`, such
+//! as saved snapshots and reviewed commits.
+
+use anyhow::{Context, Result};
+use std::io::Write as _;
+use std::path::Path;
+
+/// Reads the file at `path`, or `None` if there isn't one yet.
+pub fn read(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Replaces the file at `path` with `content`, creating its directory. The
+/// content is written aside and renamed into place, so a concurrent reader,
+/// or one after a crash, never sees half of it.
+pub fn write(path: &Path, content: &[u8]) -> Result<()> {
+    let write = || -> Result<()> {
+        let dir = path.parent().unwrap();
+        std::fs::create_dir_all(dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(content)?;
+        file.persist(path)?;
+        Ok(())
+    };
+    write().with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jjka").join("state");
+        assert_eq!(read(&path).unwrap(), None);
+
+        write(&path, b"one\n").unwrap();
+        write(&path, b"two\n").unwrap();
+        assert_eq!(read(&path).unwrap().as_deref(), Some("two\n"));
+        // Nothing is left beside it.
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+}
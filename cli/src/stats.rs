@@ -0,0 +1,245 @@
+//! `jjka stats`: per-path churn over a set of revisions.
+
+use anyhow::Result;
+use futures::StreamExt as _;
+use jj_lib::commit::Commit;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::format::{OutputFormat, commit_summary};
+use crate::perf;
+use crate::progress::Progress;
+use crate::tree_diff::{changed_paths, read_file};
+use crate::unified_diff::count_changed_lines;
+use crate::workspace::WorkspaceHelper;
+
+/// Lines added and removed in one file by one commit.
+struct FileChange {
+    path: String,
+    insertions: usize,
+    deletions: usize,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+struct PathStats {
+    path: String,
+    insertions: usize,
+    deletions: usize,
+    commits: usize,
+}
+
+impl PathStats {
+    fn churn(&self) -> usize {
+        self.insertions + self.deletions
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+struct ChurnReport {
+    commits: usize,
+    insertions: usize,
+    deletions: usize,
+    files: Vec<PathStats>,
+    directories: Vec<PathStats>,
+    most_churned: Vec<PathStats>,
+}
+
+#[derive(Default)]
+struct ChurnStats {
+    commits: usize,
+    files: BTreeMap<String, PathStats>,
+    directories: BTreeMap<String, PathStats>,
+}
+
+/// Directories containing `path`, innermost first, not including the root.
+fn parent_dirs(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').rev().map(|(i, _)| &path[..i])
+}
+
+fn add_to(map: &mut BTreeMap<String, PathStats>, path: &str, change: &FileChange) {
+    let stats = map.entry(path.to_string()).or_insert_with(|| PathStats {
+        path: path.to_string(),
+        ..Default::default()
+    });
+    stats.insertions += change.insertions;
+    stats.deletions += change.deletions;
+}
+
+impl ChurnStats {
+    fn add_commit(&mut self, changes: &[FileChange]) {
+        self.commits += 1;
+        let mut touched_dirs = BTreeSet::new();
+        for change in changes {
+            add_to(&mut self.files, &change.path, change);
+            self.files.get_mut(&change.path).unwrap().commits += 1;
+            for dir in parent_dirs(&change.path) {
+                add_to(&mut self.directories, dir, change);
+                touched_dirs.insert(dir);
+            }
+        }
+        for dir in touched_dirs {
+            self.directories.get_mut(dir).unwrap().commits += 1;
+        }
+    }
+
+    fn into_report(self, top: usize) -> ChurnReport {
+        let files: Vec<_> = self.files.into_values().collect();
+        let mut most_churned = files.clone();
+        // Stable sort keeps ties in path order.
+        most_churned.sort_by_key(|stats| std::cmp::Reverse(stats.churn()));
+        most_churned.truncate(top);
+        ChurnReport {
+            commits: self.commits,
+            insertions: files.iter().map(|f| f.insertions).sum(),
+            deletions: files.iter().map(|f| f.deletions).sum(),
+            files,
+            directories: self.directories.into_values().collect(),
+            most_churned,
+        }
+    }
+}
+
+fn print_table(title: &str, rows: &[PathStats]) {
+    println!("{title}:");
+    if rows.is_empty() {
+        println!("  (none)");
+        return;
+    }
+    let width = rows
+        .iter()
+        .map(|row| format!("+{} -{}", row.insertions, row.deletions).len())
+        .max()
+        .unwrap_or_default();
+    for row in rows {
+        let counts = format!("+{} -{}", row.insertions, row.deletions);
+        println!(
+            "  {counts:<width$}  {:>3} commit(s)  {}",
+            row.commits, row.path
+        );
+    }
+}
+
+fn print_report(report: &ChurnReport, top: usize) {
+    println!(
+        "{} commit(s), {} file(s) changed, {} insertion(s)(+), {} deletion(s)(-)",
+        report.commits,
+        report.files.len(),
+        report.insertions,
+        report.deletions
+    );
+    println!();
+    print_table("Files", &report.files);
+    println!();
+    print_table("Directories", &report.directories);
+    println!();
+    print_table(
+        &format!("Most churned paths (top {top})"),
+        &report.most_churned,
+    );
+}
+
+/// The lines each file changes in `commit`, or `None` for the root commit,
+/// which has no changes worth counting.
+async fn commit_changes(repo: &ReadonlyRepo, commit: &Commit) -> Result<Option<Vec<FileChange>>> {
+    if commit.id() == repo.store().root_commit_id() {
+        return Ok(None);
+    }
+    let parent_tree = commit.parent_tree(repo)?;
+    let tree = commit.tree_async().await?;
+    let mut changes = Vec::new();
+    for change in changed_paths(&parent_tree, &tree).await? {
+        let before = read_file(repo.store(), &change.path, change.before).await?;
+        let after = read_file(repo.store(), &change.path, change.after).await?;
+        let (insertions, deletions) = count_changed_lines(
+            before.as_deref().unwrap_or_default(),
+            after.as_deref().unwrap_or_default(),
+        );
+        changes.push(FileChange {
+            path: change.path.as_internal_file_string().to_string(),
+            insertions,
+            deletions,
+        });
+    }
+    Ok(Some(changes))
+}
+
+pub async fn stats_command(revset: String, top: usize, format: OutputFormat) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+
+    let repo = helper.repo().clone();
+    let mut stats = ChurnStats::default();
+    let commits = helper.evaluate_revset(&revset)?;
+    let mut progress = Progress::new("stats", Some(commits.len()));
+    // Diffing is the expensive part, so run up to --jobs commits at once.
+    let mut results = futures::stream::iter(commits)
+        .map(|commit| {
+            progress.start_item(&commit_summary(&commit));
+            let repo = repo.clone();
+            tokio::spawn(async move { commit_changes(repo.as_ref(), &commit).await })
+        })
+        .buffered(perf::jobs());
+    while let Some(result) = results.next().await {
+        if let Some(changes) = result?? {
+            stats.add_commit(&changes);
+        }
+    }
+    drop(results);
+    progress.finish();
+
+    let report = stats.into_report(top);
+    match format {
+        OutputFormat::Text => print_report(&report, top),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, insertions: usize, deletions: usize) -> FileChange {
+        FileChange {
+            path: path.to_string(),
+            insertions,
+            deletions,
+        }
+    }
+
+    #[test]
+    fn test_parent_dirs() {
+        assert_eq!(
+            parent_dirs("src/cmd/stats.rs").collect::<Vec<_>>(),
+            vec!["src/cmd", "src"]
+        );
+        assert_eq!(parent_dirs("README.md").count(), 0);
+    }
+
+    #[test]
+    fn test_churn_stats_aggregates_directories() {
+        let mut stats = ChurnStats::default();
+        stats.add_commit(&[change("src/a.rs", 3, 1), change("src/b.rs", 2, 0)]);
+        stats.add_commit(&[change("src/a.rs", 1, 1), change("README.md", 5, 0)]);
+        let report = stats.into_report(2);
+
+        assert_eq!(report.commits, 2);
+        assert_eq!((report.insertions, report.deletions), (11, 2));
+        assert_eq!(
+            report.directories,
+            vec![PathStats {
+                path: "src".to_string(),
+                insertions: 6,
+                deletions: 2,
+                commits: 2,
+            }]
+        );
+        let top: Vec<_> = report
+            .most_churned
+            .iter()
+            .map(|s| (s.path.as_str(), s.commits))
+            .collect();
+        assert_eq!(top, vec![("src/a.rs", 2), ("README.md", 1)]);
+    }
+}
@@ -0,0 +1,230 @@
+//! `jjka submit`: push a stack as chained GitLab merge requests.
+//!
+//! Each commit gets a `jjka/<change-id>` branch, and a merge request from
+//! it into the branch of the commit below, so reviewers see one commit per
+//! merge request; the bottom one goes into trunk's branch. Running it again
+//! after rewriting the stack force-pushes the branches and updates the
+//! merge requests' targets, titles and descriptions.
+//!
+//! Which forge a remote is on is configured per remote:
+//!
+//! ```toml
+//! [jjka.forge.remotes.origin]
+//! kind = "gitlab"
+//! url = "https://gitlab.example.com/group/app"
+//! token-env = "GITLAB_TOKEN"
+//! ```
+//!
+//! All of these are optional for a remote whose URL is on a host with
+//! "gitlab" in its name.
+
+use anyhow::{Result, anyhow, bail};
+use jj_lib::commit::Commit;
+use jj_lib::op_store::RefTarget;
+use jj_lib::ref_name::{RefName, RemoteName};
+
+use crate::exit_code::Failure;
+use crate::forge::{remote_config, remote_web_url};
+use crate::format::{commit_summary, short_change_id, subject};
+use crate::gitlab::{GitLab, MergeRequestFields};
+use crate::remote::{push_bookmarks, trunk_branch};
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper};
+
+/// The branch `submit` pushes `commit` to.
+fn branch_name(commit: &Commit) -> String {
+    format!("jjka/{}", short_change_id(commit))
+}
+
+/// The merge request's description: the commit's description after the
+/// subject line, and a pointer to the merge request it builds on.
+fn merge_request_description(description: &str, depends_on: Option<u64>) -> String {
+    let body = description
+        .split_once('\n')
+        .map_or("", |(_, body)| body)
+        .trim();
+    match (body.is_empty(), depends_on) {
+        (_, None) => body.to_string(),
+        (true, Some(iid)) => format!("Depends on !{iid}"),
+        (false, Some(iid)) => format!("{body}\n\nDepends on !{iid}"),
+    }
+}
+
+/// The commits of `revset` from the bottom up, without empty undescribed
+/// commits like a fresh working-copy commit. Fails unless they form a
+/// line, each on top of the one before.
+pub fn stack(helper: &WorkspaceHelper, revset: &str) -> Result<Vec<Commit>> {
+    let repo = helper.repo().as_ref();
+    let mut commits = Vec::new();
+    for commit in helper.evaluate_revset(revset)? {
+        if commit.description().is_empty() && commit.is_empty(repo)? {
+            continue;
+        }
+        if commit.parent_ids().len() != 1 {
+            return Err(Failure::Usage.error(anyhow!(
+                "{} is a merge; submit needs a linear stack",
+                commit_summary(&commit)
+            )));
+        }
+        if commit.description().trim().is_empty() {
+            return Err(Failure::Usage.error(anyhow!(
+                "{} has no description to title its merge request; describe it first",
+                commit_summary(&commit)
+            )));
+        }
+        commits.push(commit);
+    }
+    for pair in commits.windows(2) {
+        if pair[1].parent_ids()[0] != *pair[0].id() {
+            return Err(Failure::Usage.error(anyhow!(
+                "{} isn't on top of {}; submit needs a linear stack",
+                commit_summary(&pair[1]),
+                commit_summary(&pair[0])
+            )));
+        }
+    }
+    Ok(commits)
+}
+
+/// The GitLab project `remote` is on, with the access token from the
+/// environment.
+pub fn gitlab(helper: &WorkspaceHelper, remote: &str) -> Result<GitLab> {
+    let config = remote_config(helper, remote)?;
+    let Some(url) = config.url.clone().or(remote_web_url(helper, remote)?) else {
+        return Err(Failure::Precondition.error(anyhow!(
+            "Can't tell the web URL of remote {remote}; set jjka.forge.remotes.{remote}.url"
+        )));
+    };
+    match config.kind_for(&url) {
+        Some("gitlab") => {}
+        Some(kind) => bail!("Remote {remote} is on {kind}; submit only supports GitLab"),
+        None => {
+            return Err(Failure::Precondition.error(anyhow!(
+                "Can't tell which forge remote {remote} is on; set \
+                 jjka.forge.remotes.{remote}.kind = \"gitlab\" for a GitLab server"
+            )));
+        }
+    }
+    let token_env = config.token_env.as_deref().unwrap_or("GITLAB_TOKEN");
+    let Ok(token) = std::env::var(token_env) else {
+        return Err(Failure::Precondition.error(anyhow!(
+            "Set {token_env} to a GitLab access token with the api scope"
+        )));
+    };
+    GitLab::new(&url, token)
+}
+
+pub async fn submit_command(
+    revset: String,
+    remote: String,
+    target: Option<String>,
+    dry_run: bool,
+    policy: PolicyArgs,
+) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+
+    let commits = stack(&helper, &revset)?;
+    if commits.is_empty() {
+        status!("Nothing to submit in {revset}");
+        return Ok(());
+    }
+    let Some(target) = target.map_or_else(|| trunk_branch(&helper, &remote), |t| Ok(Some(t)))?
+    else {
+        return Err(Failure::Usage.error(anyhow!(
+            "trunk() isn't at a branch of {remote}; pass --target with the branch to merge into"
+        )));
+    };
+    let branches: Vec<String> = commits.iter().map(branch_name).collect();
+
+    if dry_run {
+        let mut into = target.as_str();
+        for (commit, branch) in commits.iter().zip(&branches) {
+            println!(
+                "Would push {} to {branch} with a merge request into {into}",
+                commit_summary(commit)
+            );
+            into = branch;
+        }
+        return Ok(());
+    }
+    let gitlab = gitlab(&helper, &remote)?;
+
+    let repo = helper.repo().clone();
+    let remote_name = RemoteName::new(&remote);
+    let outdated: Vec<String> = commits
+        .iter()
+        .zip(&branches)
+        .filter(|(commit, branch)| {
+            let remote_ref = repo
+                .view()
+                .get_remote_bookmark(RefName::new(branch.as_str()).to_remote_symbol(remote_name));
+            remote_ref.target.as_normal() != Some(commit.id())
+        })
+        .map(|(_, branch)| branch.clone())
+        .collect();
+    if !outdated.is_empty() {
+        let mut tx = helper.start_transaction();
+        for (commit, branch) in commits.iter().zip(&branches) {
+            tx.repo_mut().set_local_bookmark_target(
+                RefName::new(branch),
+                RefTarget::normal(commit.id().clone()),
+            );
+        }
+        helper
+            .finish_transaction(tx, &format!("set bookmarks to submit {revset}"))
+            .await?;
+        push_bookmarks(&mut helper, &remote, &outdated).await?;
+        status!("Pushed {} branch(es) to {remote}", outdated.len());
+    }
+
+    println!("Merge requests for {revset}:");
+    let mut into = target.clone();
+    let mut depends_on = None;
+    for (commit, branch) in commits.iter().zip(&branches) {
+        let title = subject(commit);
+        let description = merge_request_description(commit.description(), depends_on);
+        let fields = MergeRequestFields {
+            target_branch: &into,
+            title,
+            description: &description,
+        };
+        let (request, action) = match gitlab.find_open(branch)? {
+            Some(request) if fields.matches(&request) => (request, "up to date"),
+            Some(request) => (gitlab.update(request.iid, &fields)?, "updated"),
+            None => (gitlab.create(branch, &fields)?, "created"),
+        };
+        println!(
+            "  {} -> !{} ({action}) {}",
+            commit_summary(commit),
+            request.iid,
+            request.web_url
+        );
+        depends_on = Some(request.iid);
+        into = branch.clone();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_request_description() {
+        assert_eq!(merge_request_description("Add parser\n", None), "");
+        assert_eq!(
+            merge_request_description("Add parser\n\nWith tests.\n", None),
+            "With tests."
+        );
+        assert_eq!(
+            merge_request_description("Add parser\n\nWith tests.\n", Some(12)),
+            "With tests.\n\nDepends on !12"
+        );
+        assert_eq!(
+            merge_request_description("Add parser", Some(12)),
+            "Depends on !12"
+        );
+    }
+}
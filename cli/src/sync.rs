@@ -0,0 +1,94 @@
+//! `jjka sync`: fetch from the remote and restack onto the new trunk.
+
+use anyhow::Result;
+use jj_lib::backend::ChangeId;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::rewrite::rebase_commit;
+
+use crate::format::commit_summary;
+use crate::remote::fetch;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper, current_commit};
+
+/// State of a stack commit before the rebase, to tell what the rebase did.
+struct StackCommit {
+    change_id: ChangeId,
+    was_conflicted: bool,
+    was_empty: bool,
+}
+
+pub async fn sync_command(remote: String, revset: String, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+
+    fetch(&mut helper, &remote).await?;
+    let trunk = helper.resolve_single("trunk()")?;
+    status!("Trunk is at {}", commit_summary(&trunk));
+
+    // Roots of the stacks, which by construction fork from trunk's history.
+    let roots_revset = format!("roots(trunk()..({revset}))");
+    for root in helper.evaluate_revset(&format!("{roots_revset} & immutable()"))? {
+        status!("Skipping stack on immutable {}", commit_summary(&root));
+    }
+    let roots: Vec<_> = helper
+        .evaluate_revset(&format!("{roots_revset} & mutable()"))?
+        .into_iter()
+        .filter(|root| root.parent_ids() != [trunk.id().clone()])
+        .collect();
+    if roots.is_empty() {
+        status!("Nothing to rebase");
+        return Ok(());
+    }
+
+    let repo = helper.repo().clone();
+    let root_ids = roots.iter().map(|root| root.id().hex()).collect::<Vec<_>>();
+    let mut stack = Vec::new();
+    for commit in helper.evaluate_revset(&format!("({})::", root_ids.join(" | ")))? {
+        stack.push(StackCommit {
+            change_id: commit.change_id().clone(),
+            was_conflicted: commit.has_conflict()?,
+            was_empty: commit.is_empty(repo.as_ref())?,
+        });
+    }
+
+    let mut tx = helper.start_transaction();
+    for root in &roots {
+        rebase_commit(tx.repo_mut(), root.clone(), vec![trunk.id().clone()]).await?;
+    }
+    tx.repo_mut().rebase_descendants()?;
+    let mut conflicted: Vec<Commit> = Vec::new();
+    let mut emptied: Vec<Commit> = Vec::new();
+    for entry in &stack {
+        let commit = current_commit(tx.repo(), &entry.change_id)?;
+        if !entry.was_conflicted && commit.has_conflict()? {
+            conflicted.push(commit.clone());
+        }
+        if !entry.was_empty && commit.is_empty(tx.repo())? {
+            emptied.push(commit);
+        }
+    }
+    helper
+        .finish_transaction(tx, &format!("sync {revset} onto trunk from {remote}"))
+        .await?;
+
+    println!(
+        "Rebased {} commit(s) in {} stack(s) onto trunk",
+        stack.len(),
+        roots.len()
+    );
+    if !conflicted.is_empty() {
+        println!("New conflicts:");
+        for commit in &conflicted {
+            println!("  {}", commit_summary(commit));
+        }
+    }
+    if !emptied.is_empty() {
+        println!("Became empty (their changes are probably already on trunk):");
+        for commit in &emptied {
+            println!("  {}", commit_summary(commit));
+        }
+    }
+    Ok(())
+}
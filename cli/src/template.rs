@@ -0,0 +1,919 @@
+//! `-T/--template`: formatting revisions with jj's template language.
+//!
+//! jj's template engine lives in jj-cli rather than jj-lib, so this is an
+//! implementation of the part of the language that formats a single commit:
+//! string literals, `++`, the `!`, `&&` and `||` operators, the commit
+//! keywords, their common methods, and the functions `if`, `concat`,
+//! `separate`, `surround`, `coalesce` and `label`. Templates are type checked
+//! when they're parsed, so a mistake is reported before any output.
+
+use anyhow::{Result, anyhow, bail};
+use jj_lib::backend::Timestamp;
+use jj_lib::commit::Commit;
+use jj_lib::hex_util;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+
+use crate::exit_code::Failure;
+use crate::format::{format_timestamp, to_datetime};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    String(String),
+    Integer(i64),
+    Keyword(String),
+    Call(String, Vec<Expr>),
+    Method(Box<Expr>, String, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Concat(Vec<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    String,
+    Boolean,
+    Integer,
+    /// A commit or change id, printed in full.
+    Id,
+    Signature,
+    Timestamp,
+    /// A list of strings, printed separated by spaces.
+    List,
+}
+
+impl Type {
+    fn name(self) -> &'static str {
+        match self {
+            Type::String => "String",
+            Type::Boolean => "Boolean",
+            Type::Integer => "Integer",
+            Type::Id => "CommitId or ChangeId",
+            Type::Signature => "Signature",
+            Type::Timestamp => "Timestamp",
+            Type::List => "List",
+        }
+    }
+}
+
+/// Which id a [`Value::Id`] is. Change ids are shown in jj's reverse hex,
+/// with `z`-`k` for the digits.
+#[derive(Clone, Copy, Debug)]
+enum IdKind {
+    Commit,
+    Change,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    String(String),
+    Boolean(bool),
+    Integer(i64),
+    Id(IdKind, Vec<u8>),
+    Signature(jj_lib::backend::Signature),
+    Timestamp(Timestamp),
+    List(Vec<String>),
+}
+
+impl Value {
+    fn render(&self) -> String {
+        match self {
+            Value::String(text) => text.clone(),
+            Value::Id(IdKind::Commit, bytes) => hex_util::encode_hex(bytes),
+            Value::Id(IdKind::Change, bytes) => hex_util::encode_reverse_hex(bytes),
+            Value::Boolean(value) => value.to_string(),
+            Value::Integer(value) => value.to_string(),
+            Value::Signature(signature) => format!("{} <{}>", signature.name, signature.email),
+            Value::Timestamp(timestamp) => format_timestamp(timestamp),
+            Value::List(items) => items.join(" "),
+        }
+    }
+
+    /// Whether the value counts as true in `if()` and the logical operators,
+    /// which also accept strings and lists and test them for being
+    /// non-empty.
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Boolean(value) => *value,
+            Value::String(text) => !text.is_empty(),
+            Value::List(items) => !items.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+/// A parsed and type-checked template.
+#[derive(Debug)]
+pub struct Template {
+    expr: Expr,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+        };
+        let expr = parser
+            .template()
+            .and_then(|expr| {
+                parser.skip_whitespace();
+                if parser.position < source.len() {
+                    return Err(parser.error("expected `++` or the end of the template"));
+                }
+                type_of(&expr)?;
+                Ok(expr)
+            })
+            .map_err(|err| {
+                Failure::Usage.error(anyhow!("Failed to parse template {source:?}: {err}"))
+            })?;
+        Ok(Template { expr })
+    }
+
+    /// Renders the template for `commit`.
+    pub fn render(&self, repo: &dyn Repo, commit: &Commit) -> Result<String> {
+        Ok(eval(&self.expr, &Context { repo, commit })?.render())
+    }
+
+    /// Renders the template as a line of output, adding the newline unless
+    /// the template ends with one.
+    pub fn render_line(&self, repo: &dyn Repo, commit: &Commit) -> Result<String> {
+        let mut line = self.render(repo, commit)?;
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        Ok(line)
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{token}`")))
+        }
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!(
+            "{message} at column {}",
+            self.source[..self.position].chars().count() + 1
+        )
+    }
+
+    /// `expression ("++" expression)*`
+    fn template(&mut self) -> Result<Expr> {
+        let mut parts = vec![self.or()?];
+        while self.eat("++") {
+            parts.push(self.or()?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            Expr::Concat(parts)
+        })
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let mut expr = self.primary()?;
+        while self.eat(".") {
+            let name = self.identifier()?;
+            let args = self
+                .arguments()?
+                .ok_or_else(|| self.error("expected `(`"))?;
+            expr = Expr::Method(Box::new(expr), name, args);
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        match rest.chars().next() {
+            Some('"') => self.string_literal(),
+            Some('\'') => {
+                let end = rest[1..]
+                    .find('\'')
+                    .ok_or_else(|| self.error("unterminated string"))?;
+                let text = rest[1..=end].to_string();
+                self.position += end + 2;
+                Ok(Expr::String(text))
+            }
+            Some('(') => {
+                self.position += 1;
+                let expr = self.template()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let digits =
+                    rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                let value = rest[..digits]
+                    .parse()
+                    .map_err(|_| self.error("integer is too large"))?;
+                self.position += digits;
+                Ok(Expr::Integer(value))
+            }
+            _ => {
+                let name = self.identifier()?;
+                Ok(match self.arguments()? {
+                    Some(args) => Expr::Call(name, args),
+                    None => Expr::Keyword(name),
+                })
+            }
+        }
+    }
+
+    fn string_literal(&mut self) -> Result<Expr> {
+        self.position += 1;
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += offset + 1;
+                    return Ok(Expr::String(text));
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => text.push('\n'),
+                    Some((_, 't')) => text.push('\t'),
+                    Some((_, 'r')) => text.push('\r'),
+                    Some((_, '0')) => text.push('\0'),
+                    Some((_, escaped @ ('"' | '\\'))) => text.push(escaped),
+                    _ => {
+                        self.position += offset;
+                        return Err(self.error("invalid escape sequence"));
+                    }
+                },
+                c => text.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                .len();
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.error("expected a keyword, function, string or `(`"));
+        }
+        self.position += len;
+        Ok(rest[..len].to_string())
+    }
+
+    /// A parenthesized argument list, if the next token opens one.
+    fn arguments(&mut self) -> Result<Option<Vec<Expr>>> {
+        if !self.eat("(") {
+            return Ok(None);
+        }
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok(Some(args));
+        }
+        loop {
+            args.push(self.template()?);
+            if self.eat(")") {
+                return Ok(Some(args));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+fn keyword_type(name: &str) -> Option<Type> {
+    Some(match name {
+        "description" => Type::String,
+        "commit_id" | "change_id" => Type::Id,
+        "author" | "committer" => Type::Signature,
+        "bookmarks" | "local_bookmarks" | "remote_bookmarks" | "tags" => Type::List,
+        "empty" | "conflict" | "root" => Type::Boolean,
+        _ => return None,
+    })
+}
+
+fn check_args(what: &str, args: &[Expr], expected: &[Type], optional: usize) -> Result<()> {
+    let required = expected.len() - optional;
+    if args.len() < required || args.len() > expected.len() {
+        let count = if optional == 0 {
+            required.to_string()
+        } else {
+            format!("{required} to {}", expected.len())
+        };
+        bail!("{what} expects {count} argument(s), got {}", args.len());
+    }
+    for (arg, expected) in args.iter().zip(expected) {
+        // Anything can be printed, so anything can be passed as a string.
+        let actual = type_of(arg)?;
+        if actual != *expected && *expected != Type::String {
+            bail!(
+                "{what}: expected {} argument, got {}",
+                expected.name(),
+                actual.name()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn method_type(ty: Type, name: &str, args: &[Expr]) -> Result<Type> {
+    let what = format!("{}.{name}()", ty.name());
+    let (result, expected, optional): (Type, &[Type], usize) = match (ty, name) {
+        (Type::String, "len") | (Type::List, "len") => (Type::Integer, &[], 0),
+        (Type::String, "contains" | "starts_with" | "ends_with") => {
+            (Type::Boolean, &[Type::String], 0)
+        }
+        (Type::String, "first_line" | "upper" | "lower" | "trim") => (Type::String, &[], 0),
+        (Type::String, "lines") => (Type::List, &[], 0),
+        (Type::Id, "short") => (Type::String, &[Type::Integer], 1),
+        (Type::Id, "normal_hex") => (Type::String, &[], 0),
+        (Type::Signature, "name" | "email") => (Type::String, &[], 0),
+        (Type::Signature, "timestamp") => (Type::Timestamp, &[], 0),
+        (Type::Timestamp, "format") => (Type::String, &[Type::String], 0),
+        (Type::List, "join") => (Type::String, &[Type::String], 0),
+        (Type::List, "contains") => (Type::Boolean, &[Type::String], 0),
+        _ => bail!("{} has no method {name}()", ty.name()),
+    };
+    check_args(&what, args, expected, optional)?;
+    Ok(result)
+}
+
+fn type_of(expr: &Expr) -> Result<Type> {
+    match expr {
+        Expr::String(_) => Ok(Type::String),
+        Expr::Concat(parts) => {
+            for part in parts {
+                type_of(part)?;
+            }
+            Ok(Type::String)
+        }
+        Expr::Integer(_) => Ok(Type::Integer),
+        Expr::Keyword(name) => keyword_type(name).ok_or_else(|| anyhow!("Unknown keyword {name}")),
+        Expr::Not(operand) => {
+            type_of(operand)?;
+            Ok(Type::Boolean)
+        }
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            type_of(left)?;
+            type_of(right)?;
+            Ok(Type::Boolean)
+        }
+        Expr::Method(object, name, args) => method_type(type_of(object)?, name, args),
+        Expr::Call(name, args) => {
+            let what = format!("{name}()");
+            match name.as_str() {
+                "if" => {
+                    if !(2..=3).contains(&args.len()) {
+                        bail!("if() expects 2 to 3 argument(s), got {}", args.len());
+                    }
+                }
+                "label" => check_args(&what, args, &[Type::String, Type::String], 0)?,
+                "surround" => {
+                    check_args(&what, args, &[Type::String, Type::String, Type::String], 0)?
+                }
+                "separate" if args.is_empty() => {
+                    bail!("separate() expects a separator argument")
+                }
+                "concat" | "separate" | "coalesce" => {}
+                _ => bail!("Unknown function {name}()"),
+            }
+            for arg in args {
+                type_of(arg)?;
+            }
+            Ok(Type::String)
+        }
+    }
+}
+
+struct Context<'a> {
+    repo: &'a dyn Repo,
+    commit: &'a Commit,
+}
+
+fn string(expr: &Expr, context: &Context) -> Result<String> {
+    Ok(eval(expr, context)?.render())
+}
+
+fn integer(expr: &Expr, context: &Context) -> Result<i64> {
+    match eval(expr, context)? {
+        Value::Integer(value) => Ok(value),
+        _ => unreachable!("arguments are type checked"),
+    }
+}
+
+/// The bookmarks pointing to the commit, named like jj does: `main@origin`
+/// for a remote bookmark, `??` after a conflicted one, and `*` after a local
+/// bookmark that a tracked remote bookmark hasn't caught up with.
+/// `bookmarks` leaves out the tracked remote bookmarks that point where the
+/// local one does.
+fn bookmarks(name: &str, context: &Context) -> Vec<String> {
+    let commit_id = context.commit.id();
+    let mut names = Vec::new();
+    for (bookmark, target) in context.repo.view().bookmarks() {
+        let local = target.local_target;
+        if name != "remote_bookmarks" && local.added_ids().any(|id| id == commit_id) {
+            let synced = target
+                .remote_refs
+                .iter()
+                .all(|(_, remote)| !remote.is_tracked() || remote.target == *local);
+            let sigil = if local.has_conflict() {
+                "??"
+            } else if !synced {
+                "*"
+            } else {
+                ""
+            };
+            names.push(format!("{}{sigil}", bookmark.as_str()));
+        }
+        if name == "local_bookmarks" {
+            continue;
+        }
+        for (remote_name, remote) in &target.remote_refs {
+            let synced = remote.is_tracked() && remote.target == *local;
+            if !remote.target.added_ids().any(|id| id == commit_id)
+                || (name == "bookmarks" && synced)
+            {
+                continue;
+            }
+            let sigil = if remote.target.has_conflict() {
+                "??"
+            } else {
+                ""
+            };
+            names.push(format!(
+                "{}@{}{sigil}",
+                bookmark.as_str(),
+                remote_name.as_str()
+            ));
+        }
+    }
+    names
+}
+
+fn keyword(name: &str, context: &Context) -> Result<Value> {
+    let commit = context.commit;
+    let view = context.repo.view();
+    Ok(match name {
+        "description" => Value::String(commit.description().to_string()),
+        "commit_id" => Value::Id(IdKind::Commit, commit.id().to_bytes()),
+        "change_id" => Value::Id(IdKind::Change, commit.change_id().to_bytes()),
+        "author" => Value::Signature(commit.author().clone()),
+        "committer" => Value::Signature(commit.committer().clone()),
+        "bookmarks" | "local_bookmarks" | "remote_bookmarks" => {
+            Value::List(bookmarks(name, context))
+        }
+        "tags" => Value::List(
+            view.local_tags()
+                .filter(|(_, target)| target.added_ids().any(|id| id == commit.id()))
+                .map(|(name, _)| name.as_str().to_string())
+                .collect(),
+        ),
+        "empty" => Value::Boolean(commit.is_empty(context.repo)?),
+        "conflict" => Value::Boolean(commit.has_conflict()?),
+        "root" => Value::Boolean(commit.id() == context.repo.store().root_commit_id()),
+        _ => unreachable!("keywords are type checked"),
+    })
+}
+
+fn method(object: Value, name: &str, args: &[Expr], context: &Context) -> Result<Value> {
+    Ok(match (object, name) {
+        (Value::String(text), "len") => Value::Integer(text.chars().count() as i64),
+        (Value::List(items), "len") => Value::Integer(items.len() as i64),
+        (Value::String(text), "contains") => {
+            Value::Boolean(text.contains(&string(&args[0], context)?))
+        }
+        (Value::String(text), "starts_with") => {
+            Value::Boolean(text.starts_with(&string(&args[0], context)?))
+        }
+        (Value::String(text), "ends_with") => {
+            Value::Boolean(text.ends_with(&string(&args[0], context)?))
+        }
+        (Value::String(text), "first_line") => {
+            Value::String(text.lines().next().unwrap_or_default().to_string())
+        }
+        (Value::String(text), "upper") => Value::String(text.to_uppercase()),
+        (Value::String(text), "lower") => Value::String(text.to_lowercase()),
+        (Value::String(text), "trim") => Value::String(text.trim().to_string()),
+        (Value::String(text), "lines") => Value::List(text.lines().map(str::to_string).collect()),
+        (id @ Value::Id(..), "short") => {
+            let len = match args.first() {
+                Some(arg) => integer(arg, context)?.max(0) as usize,
+                None => 12,
+            };
+            Value::String(id.render().chars().take(len).collect())
+        }
+        (Value::Id(_, bytes), "normal_hex") => Value::String(hex_util::encode_hex(&bytes)),
+        (Value::Signature(signature), "name") => Value::String(signature.name),
+        (Value::Signature(signature), "email") => Value::String(signature.email),
+        (Value::Signature(signature), "timestamp") => Value::Timestamp(signature.timestamp),
+        (Value::Timestamp(timestamp), "format") => {
+            let format = string(&args[0], context)?;
+            let datetime = to_datetime(&timestamp).ok_or_else(|| anyhow!("Invalid timestamp"))?;
+            let mut text = String::new();
+            std::fmt::Write::write_fmt(&mut text, format_args!("{}", datetime.format(&format)))
+                .map_err(|_| anyhow!("Invalid timestamp format {format:?}"))?;
+            Value::String(text)
+        }
+        (Value::List(items), "join") => Value::String(items.join(&string(&args[0], context)?)),
+        (Value::List(items), "contains") => {
+            let item = string(&args[0], context)?;
+            Value::Boolean(items.contains(&item))
+        }
+        _ => unreachable!("methods are type checked"),
+    })
+}
+
+fn eval(expr: &Expr, context: &Context) -> Result<Value> {
+    Ok(match expr {
+        Expr::String(text) => Value::String(text.clone()),
+        Expr::Integer(value) => Value::Integer(*value),
+        Expr::Keyword(name) => keyword(name, context)?,
+        Expr::Not(operand) => Value::Boolean(!eval(operand, context)?.is_true()),
+        Expr::And(left, right) => {
+            Value::Boolean(eval(left, context)?.is_true() && eval(right, context)?.is_true())
+        }
+        Expr::Or(left, right) => {
+            Value::Boolean(eval(left, context)?.is_true() || eval(right, context)?.is_true())
+        }
+        Expr::Concat(parts) => {
+            let mut text = String::new();
+            for part in parts {
+                text.push_str(&string(part, context)?);
+            }
+            Value::String(text)
+        }
+        Expr::Method(object, name, args) => method(eval(object, context)?, name, args, context)?,
+        Expr::Call(name, args) => Value::String(match name.as_str() {
+            "if" => {
+                if eval(&args[0], context)?.is_true() {
+                    string(&args[1], context)?
+                } else if let Some(otherwise) = args.get(2) {
+                    string(otherwise, context)?
+                } else {
+                    String::new()
+                }
+            }
+            "concat" => {
+                let mut text = String::new();
+                for arg in args {
+                    text.push_str(&string(arg, context)?);
+                }
+                text
+            }
+            "separate" => {
+                let separator = string(&args[0], context)?;
+                let mut parts = Vec::new();
+                for arg in &args[1..] {
+                    let part = string(arg, context)?;
+                    if !part.is_empty() {
+                        parts.push(part);
+                    }
+                }
+                parts.join(&separator)
+            }
+            "coalesce" => {
+                let mut text = String::new();
+                for arg in args {
+                    text = string(arg, context)?;
+                    if !text.is_empty() {
+                        break;
+                    }
+                }
+                text
+            }
+            // Output isn't colored, so labels only group their content.
+            "label" => string(&args[1], context)?,
+            "surround" => {
+                let content = string(&args[2], context)?;
+                if content.is_empty() {
+                    content
+                } else {
+                    format!(
+                        "{}{content}{}",
+                        string(&args[0], context)?,
+                        string(&args[1], context)?
+                    )
+                }
+            }
+            _ => unreachable!("functions are type checked"),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::backend::{MillisSinceEpoch, Signature};
+    use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+    use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState};
+    use jj_lib::ref_name::{RefName, RemoteName};
+    use jj_lib::repo::ReadonlyRepo;
+    use jj_lib::settings::UserSettings;
+    use jj_lib::workspace::Workspace;
+    use std::sync::Arc;
+
+    /// An empty commit on the root, with the local bookmark `main`, which
+    /// `main@origin` tracks, `main@upstream` tracking it from the root
+    /// commit, and the untracked `feature@origin`.
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        repo: Arc<ReadonlyRepo>,
+        commit: Commit,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = StackedConfig::with_defaults();
+            config.add_layer(
+                ConfigLayer::parse(
+                    ConfigSource::User,
+                    "user.name = \"Test User\"\nuser.email = \"test.user@example.com\"\n",
+                )
+                .unwrap(),
+            );
+            let settings = UserSettings::from_config(config).unwrap();
+            let (_, repo) = Workspace::init_simple(&settings, dir.path()).unwrap();
+
+            let author = Signature {
+                name: "Ada Lovelace".to_string(),
+                email: "ada@example.com".to_string(),
+                timestamp: Timestamp {
+                    timestamp: MillisSinceEpoch(86_400_000),
+                    tz_offset: 60,
+                },
+            };
+            let mut tx = repo.start_transaction();
+            let root_id = repo.store().root_commit_id().clone();
+            let commit = tx
+                .repo_mut()
+                .new_commit(vec![root_id.clone()], repo.store().empty_merged_tree_id())
+                .set_author(author)
+                .set_description("  Add feature\n\nWith a body.\n")
+                .write()
+                .unwrap();
+            let at_commit = RefTarget::normal(commit.id().clone());
+            let main = RefName::new("main");
+            tx.repo_mut()
+                .set_local_bookmark_target(main, at_commit.clone());
+            for (name, remote, target, state) in [
+                (main, "origin", at_commit.clone(), RemoteRefState::Tracked),
+                (
+                    main,
+                    "upstream",
+                    RefTarget::normal(root_id),
+                    RemoteRefState::Tracked,
+                ),
+                (
+                    RefName::new("feature"),
+                    "origin",
+                    at_commit,
+                    RemoteRefState::New,
+                ),
+            ] {
+                tx.repo_mut().set_remote_bookmark(
+                    name.to_remote_symbol(RemoteName::new(remote)),
+                    RemoteRef { target, state },
+                );
+            }
+            let repo = tx.commit("create test commit").unwrap();
+            Fixture {
+                _dir: dir,
+                repo,
+                commit,
+            }
+        }
+
+        fn render(&self, source: &str) -> String {
+            Template::parse(source)
+                .unwrap()
+                .render(self.repo.as_ref(), &self.commit)
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_render_keywords() {
+        let fixture = Fixture::new();
+        let render = |source| fixture.render(source);
+        assert_eq!(render("description"), "  Add feature\n\nWith a body.\n");
+        assert_eq!(render("commit_id"), fixture.commit.id().hex());
+        assert_eq!(
+            render("change_id"),
+            fixture.commit.change_id().reverse_hex()
+        );
+        assert_eq!(render("author"), "Ada Lovelace <ada@example.com>");
+        assert_eq!(render("committer.name()"), "Test User");
+        assert_eq!(render("bookmarks"), "feature@origin main*");
+        assert_eq!(render("local_bookmarks"), "main*");
+        assert_eq!(render("remote_bookmarks"), "feature@origin main@origin");
+        assert_eq!(render("tags"), "");
+        assert_eq!(render("empty"), "true");
+        assert_eq!(render("conflict"), "false");
+        assert_eq!(render("root"), "false");
+    }
+
+    #[test]
+    fn test_render_methods() {
+        let fixture = Fixture::new();
+        let render = |source| fixture.render(source);
+        let commit_id = fixture.commit.id().hex();
+
+        assert_eq!(render(r#""héllo".len()"#), "5");
+        assert_eq!(render(r#""hello".contains("ell")"#), "true");
+        assert_eq!(render(r#""hello".contains("xyz")"#), "false");
+        assert_eq!(render(r#""hello".starts_with("he")"#), "true");
+        assert_eq!(render(r#""hello".ends_with("he")"#), "false");
+        assert_eq!(render("description.first_line()"), "  Add feature");
+        assert_eq!(render("description.first_line().trim()"), "Add feature");
+        assert_eq!(
+            render(r#""MiXed".upper() ++ "MiXed".lower()"#),
+            "MIXEDmixed"
+        );
+        assert_eq!(
+            render(r#"description.lines().join("|")"#),
+            "  Add feature||With a body."
+        );
+
+        assert_eq!(render("description.lines().len()"), "3");
+        assert_eq!(render(r#"bookmarks.join(", ")"#), "feature@origin, main*");
+        assert_eq!(render(r#"bookmarks.contains("main*")"#), "true");
+        assert_eq!(render(r#"bookmarks.contains("main")"#), "false");
+
+        assert_eq!(render("commit_id.short()"), &commit_id[..12]);
+        assert_eq!(render("commit_id.short(4)"), &commit_id[..4]);
+        assert_eq!(render("commit_id.normal_hex()"), commit_id);
+        assert_eq!(
+            render("change_id.normal_hex()"),
+            fixture.commit.change_id().hex()
+        );
+        assert_eq!(
+            render("change_id.short()"),
+            &fixture.commit.change_id().reverse_hex()[..12]
+        );
+
+        assert_eq!(render("author.name()"), "Ada Lovelace");
+        assert_eq!(render("author.email()"), "ada@example.com");
+        assert_eq!(render("author.timestamp()"), "1970-01-02 01:00:00");
+        assert_eq!(
+            render(r#"author.timestamp().format("%Y-%m-%d %z")"#),
+            "1970-01-02 +0100"
+        );
+    }
+
+    #[test]
+    fn test_render_functions() {
+        let fixture = Fixture::new();
+        let render = |source| fixture.render(source);
+        assert_eq!(render(r#"if(empty, "empty")"#), "empty");
+        assert_eq!(render(r#"if(root, "root")"#), "");
+        assert_eq!(render(r#"if(tags, "tagged", "untagged")"#), "untagged");
+        assert_eq!(render(r#"concat("a", 1, root)"#), "a1false");
+        assert_eq!(render(r#"separate(" ", "a", tags, "b")"#), "a b");
+        assert_eq!(render(r#"surround("[", "]", local_bookmarks)"#), "[main*]");
+        assert_eq!(render(r#"surround("[", "]", tags)"#), "");
+        assert_eq!(render(r#"coalesce(tags, "", "first", "second")"#), "first");
+        assert_eq!(render(r#"coalesce(tags, "")"#), "");
+        assert_eq!(render(r#"label("bookmark", "main")"#), "main");
+    }
+
+    #[test]
+    fn test_render_operators() {
+        let fixture = Fixture::new();
+        let render = |source| fixture.render(source);
+        // `!` binds tighter than `&&`, which binds tighter than `||`, and
+        // `++` is the loosest of all.
+        assert_eq!(render("!root && root || empty"), "true");
+        assert_eq!(render("empty || root && root"), "true");
+        assert_eq!(render("!(empty || root)"), "false");
+        assert_eq!(render(r#""a" ++ tags || "b""#), "atrue");
+        // Methods bind tighter than `!`.
+        assert_eq!(render(r#"!description.contains("feature")"#), "false");
+        // Strings and lists are true when they're non-empty.
+        assert_eq!(render(r#"tags || "" || bookmarks"#), "true");
+        assert_eq!(render(r#"!"""#), "true");
+    }
+
+    #[test]
+    fn test_render_escapes() {
+        let fixture = Fixture::new();
+        let render = |source| fixture.render(source);
+        assert_eq!(
+            render(r#""quote \" backslash \\ tab \t cr \r nul \0 newline \n""#),
+            "quote \" backslash \\ tab \t cr \r nul \0 newline \n"
+        );
+        assert_eq!(render(r#"'raw \n "quoted"'"#), r#"raw \n "quoted""#);
+        assert!(
+            Template::parse(r#""\q""#)
+                .unwrap_err()
+                .to_string()
+                .contains("invalid escape sequence at column 2")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let template = Template::parse(
+            r#"change_id.short(8) ++ " " ++ if(bookmarks, "[" ++ bookmarks.join(",") ++ "]")"#,
+        )
+        .unwrap();
+        assert_eq!(
+            template.expr,
+            Expr::Concat(vec![
+                Expr::Method(
+                    Box::new(Expr::Keyword("change_id".to_string())),
+                    "short".to_string(),
+                    vec![Expr::Integer(8)]
+                ),
+                Expr::String(" ".to_string()),
+                Expr::Call(
+                    "if".to_string(),
+                    vec![
+                        Expr::Keyword("bookmarks".to_string()),
+                        Expr::Concat(vec![
+                            Expr::String("[".to_string()),
+                            Expr::Method(
+                                Box::new(Expr::Keyword("bookmarks".to_string())),
+                                "join".to_string(),
+                                vec![Expr::String(",".to_string())]
+                            ),
+                            Expr::String("]".to_string()),
+                        ]),
+                    ]
+                ),
+            ])
+        );
+        assert_eq!(
+            Template::parse(r#"'raw\n' ++ "tab\t""#).unwrap().expr,
+            Expr::Concat(vec![
+                Expr::String("raw\\n".to_string()),
+                Expr::String("tab\t".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source| Template::parse(source).unwrap_err().to_string();
+        assert!(
+            error("description ++")
+                .contains("expected a keyword, function, string or `(` at column 15")
+        );
+        assert!(error("descriptoin").ends_with("Unknown keyword descriptoin"));
+        assert!(
+            error("commit_id.first_line()")
+                .ends_with("CommitId or ChangeId has no method first_line()")
+        );
+        assert!(error("change_id.short(\"8\")").ends_with("expected Integer argument, got String"));
+        assert!(error("\"open").contains("unterminated string"));
+        assert!(error("description description").contains("expected `++`"));
+    }
+}
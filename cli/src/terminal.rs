@@ -0,0 +1,173 @@
+//! Just enough terminal handling for full-screen views: raw mode, the
+//! alternate screen, key input and the window size.
+//!
+//! Raw mode is set through termios, so the views only work on Unix.
+
+use anyhow::{Result, bail};
+use std::io::{IsTerminal, Read, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+}
+
+#[cfg(unix)]
+mod sys {
+    use anyhow::{Result, bail};
+
+    /// The terminal settings from before raw mode.
+    pub type Settings = libc::termios;
+
+    pub fn read_settings() -> Result<Settings> {
+        // SAFETY: tcgetattr only writes the termios struct.
+        let mut settings = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut settings) } != 0 {
+            bail!("Failed to read terminal settings");
+        }
+        Ok(settings)
+    }
+
+    pub fn set_raw(original: &Settings) -> Result<()> {
+        let mut termios = *original;
+        // SAFETY: cfmakeraw and tcsetattr only read and write the struct.
+        unsafe {
+            libc::cfmakeraw(&mut termios);
+            // Keep output post-processing so "\n" still returns the cursor.
+            termios.c_oflag |= libc::OPOST;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                bail!("Failed to switch the terminal to raw mode");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn restore(original: &Settings) {
+        // SAFETY: restores the settings from `read_settings`.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+        }
+    }
+
+    pub fn window_size() -> Option<(usize, usize)> {
+        // SAFETY: TIOCGWINSZ fills in the winsize struct.
+        let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        (ok && size.ws_row > 0 && size.ws_col > 0)
+            .then_some((size.ws_row as usize, size.ws_col as usize))
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use anyhow::{Result, bail};
+
+    pub type Settings = ();
+
+    pub fn read_settings() -> Result<Settings> {
+        bail!("Full-screen views need a Unix terminal");
+    }
+
+    pub fn set_raw(_original: &Settings) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn restore(_original: &Settings) {}
+
+    pub fn window_size() -> Option<(usize, usize)> {
+        None
+    }
+}
+
+/// Puts the terminal in raw mode on the alternate screen until dropped.
+pub struct RawTerminal {
+    original: sys::Settings,
+}
+
+impl RawTerminal {
+    pub fn enter() -> Result<Self> {
+        if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+            bail!("This command needs an interactive terminal");
+        }
+        let terminal = RawTerminal {
+            original: sys::read_settings()?,
+        };
+        terminal.activate()?;
+        Ok(terminal)
+    }
+
+    fn activate(&self) -> Result<()> {
+        sys::set_raw(&self.original)?;
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+
+    /// Rows and columns of the terminal window.
+    pub fn size(&self) -> (usize, usize) {
+        sys::window_size().unwrap_or((24, 80))
+    }
+
+    pub fn read_key(&self) -> Result<Key> {
+        let mut stdin = std::io::stdin().lock();
+        loop {
+            let mut byte = [0];
+            stdin.read_exact(&mut byte)?;
+            let key = match byte[0] {
+                b'\r' | b'\n' => Key::Enter,
+                0x1b => {
+                    let mut seq = [0; 2];
+                    if stdin.read_exact(&mut seq).is_err() || seq[0] != b'[' {
+                        return Ok(Key::Escape);
+                    }
+                    match seq[1] {
+                        b'A' => Key::Up,
+                        b'B' => Key::Down,
+                        b'H' => Key::Home,
+                        b'F' => Key::End,
+                        b'5' | b'6' => {
+                            let mut tilde = [0];
+                            stdin.read_exact(&mut tilde)?;
+                            if seq[1] == b'5' {
+                                Key::PageUp
+                            } else {
+                                Key::PageDown
+                            }
+                        }
+                        _ => continue,
+                    }
+                }
+                byte if byte.is_ascii() && !byte.is_ascii_control() => Key::Char(byte as char),
+                _ => continue,
+            };
+            return Ok(key);
+        }
+    }
+
+    /// Restores the normal terminal while `f` runs, e.g. to launch an editor.
+    pub fn suspend<T>(&self, f: impl FnOnce() -> T) -> Result<T> {
+        self.restore();
+        let result = f();
+        self.activate()?;
+        Ok(result)
+    }
+
+    fn restore(&self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        sys::restore(&self.original);
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
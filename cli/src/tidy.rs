@@ -0,0 +1,232 @@
+//! `jjka tidy`: find and fix common messes in a stack.
+
+use anyhow::Result;
+use jj_lib::commit::Commit;
+use jj_lib::repo::{MutableRepo, Repo};
+use std::collections::HashMap;
+
+use crate::format::{commit_summary, subject};
+use crate::patch_id::patch_id;
+use crate::progress::Progress;
+use crate::ui::status;
+use crate::workspace::{PolicyArgs, WorkspaceHelper, current_commit};
+
+const FIXUP_PREFIXES: [&str; 2] = ["fixup! ", "squash! "];
+
+/// For a `fixup!`/`squash!` subject, returns the subject it targets
+/// (stripping nested prefixes) and whether the message should be kept.
+fn fixup_target(subject: &str) -> Option<(&str, bool)> {
+    let mut rest = subject;
+    let mut keep_message = false;
+    let mut matched = false;
+    while let Some(prefix) = FIXUP_PREFIXES.iter().find(|p| rest.starts_with(*p)) {
+        keep_message |= *prefix == "squash! ";
+        rest = rest[prefix.len()..].trim_start();
+        matched = true;
+    }
+    (matched && !rest.is_empty()).then_some((rest, keep_message))
+}
+
+/// The target's description with the squashed commit's message appended,
+/// minus its `squash!` subject line.
+fn squashed_description(target: &str, squash: &str) -> String {
+    let body = squash.lines().skip(1).collect::<Vec<_>>().join("\n");
+    let body = body.trim();
+    if body.is_empty() {
+        return target.to_string();
+    }
+    format!("{}\n\n{body}\n", target.trim_end())
+}
+
+struct Fixup {
+    commit: Commit,
+    target: Commit,
+    keep_message: bool,
+}
+
+#[derive(Default)]
+struct TidyPlan {
+    empty: Vec<Commit>,
+    fixups: Vec<Fixup>,
+    /// Commits whose patch matches a commit that landed on trunk.
+    merged: Vec<(Commit, Commit)>,
+}
+
+impl TidyPlan {
+    fn is_empty(&self) -> bool {
+        self.empty.is_empty() && self.fixups.is_empty() && self.merged.is_empty()
+    }
+}
+
+async fn plan(helper: &WorkspaceHelper, revset: &str) -> Result<TidyPlan> {
+    let repo = helper.repo().as_ref();
+    let commits = helper.evaluate_revset(revset)?;
+    let mut plan = TidyPlan::default();
+
+    // Commits that landed on trunk since the stack forked from it.
+    let mut trunk_patch_ids = HashMap::new();
+    for commit in helper.evaluate_revset(&format!("({revset})..trunk()"))? {
+        trunk_patch_ids.insert(patch_id(repo, &commit).await?, commit);
+    }
+
+    for (index, commit) in commits.iter().enumerate() {
+        let is_merge = commit.parent_ids().len() > 1;
+        if !is_merge
+            && !commit.is_empty(repo)?
+            && let Some(trunk_commit) = trunk_patch_ids.get(&patch_id(repo, commit).await?)
+        {
+            plan.merged.push((commit.clone(), trunk_commit.clone()));
+            continue;
+        }
+        if let Some((target_subject, keep_message)) = fixup_target(subject(commit)) {
+            // The nearest ancestor in the stack with that subject.
+            let target = commits[..index].iter().rev().find(|candidate| {
+                fixup_target(subject(candidate)).is_none()
+                    && subject(candidate).starts_with(target_subject)
+                    && repo.index().is_ancestor(candidate.id(), commit.id())
+            });
+            if let Some(target) = target
+                && !plan
+                    .merged
+                    .iter()
+                    .any(|(merged, _)| merged.id() == target.id())
+            {
+                plan.fixups.push(Fixup {
+                    commit: commit.clone(),
+                    target: target.clone(),
+                    keep_message,
+                });
+                continue;
+            }
+        }
+        let is_wc = helper.wc_commit_id() == Some(commit.id());
+        if !is_merge && !is_wc && commit.is_empty(repo)? {
+            plan.empty.push(commit.clone());
+        }
+    }
+    Ok(plan)
+}
+
+fn print_plan(plan: &TidyPlan) {
+    if !plan.empty.is_empty() {
+        println!("Empty commits:");
+        for commit in &plan.empty {
+            println!("  {}", commit_summary(commit));
+        }
+    }
+    if !plan.fixups.is_empty() {
+        println!("Fixups:");
+        for fixup in &plan.fixups {
+            println!(
+                "  {} -> {}",
+                commit_summary(&fixup.commit),
+                commit_summary(&fixup.target)
+            );
+        }
+    }
+    if !plan.merged.is_empty() {
+        println!("Already merged into trunk:");
+        for (commit, trunk_commit) in &plan.merged {
+            println!(
+                "  {} (as {})",
+                commit_summary(commit),
+                commit_summary(trunk_commit)
+            );
+        }
+    }
+}
+
+async fn squash_fixup(repo: &mut MutableRepo, fixup: &Fixup) -> Result<()> {
+    let commit = current_commit(repo, fixup.commit.change_id())?;
+    let target = current_commit(repo, fixup.target.change_id())?;
+    let new_tree = target
+        .tree_async()
+        .await?
+        .merge(commit.parent_tree(repo)?, commit.tree_async().await?)
+        .await?;
+    let description = if fixup.keep_message {
+        squashed_description(target.description(), commit.description())
+    } else {
+        target.description().to_string()
+    };
+    repo.rewrite_commit(&target)
+        .set_tree_id(new_tree.id())
+        .set_description(description)
+        .write()?;
+    repo.record_abandoned_commit(&commit);
+    repo.rebase_descendants()?;
+    Ok(())
+}
+
+pub async fn tidy_command(revset: String, fix: bool, policy: PolicyArgs) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.set_policy_args(&policy);
+    helper.snapshot_working_copy()?;
+
+    let plan = plan(&helper, &revset).await?;
+    if plan.is_empty() {
+        status!("Nothing to tidy");
+        return Ok(());
+    }
+    print_plan(&plan);
+    if !fix {
+        status!("Run with --fix to apply these changes");
+        return Ok(());
+    }
+
+    let mut tx = helper.start_transaction();
+    for commit in plan.empty.iter().chain(plan.merged.iter().map(|(c, _)| c)) {
+        tx.repo_mut().record_abandoned_commit(commit);
+    }
+    tx.repo_mut().rebase_descendants()?;
+    let mut progress = Progress::new("squash", Some(plan.fixups.len()));
+    for fixup in &plan.fixups {
+        progress.start_item(&commit_summary(&fixup.commit));
+        squash_fixup(tx.repo_mut(), fixup).await?;
+    }
+    progress.finish();
+    helper
+        .finish_transaction(tx, &format!("tidy {revset}"))
+        .await?;
+    println!(
+        "Abandoned {} empty commit(s), squashed {} fixup(s), dropped {} merged commit(s)",
+        plan.empty.len(),
+        plan.fixups.len(),
+        plan.merged.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixup_target() {
+        assert_eq!(
+            fixup_target("fixup! Add parser"),
+            Some(("Add parser", false))
+        );
+        assert_eq!(
+            fixup_target("squash! Add parser"),
+            Some(("Add parser", true))
+        );
+        assert_eq!(
+            fixup_target("fixup! squash! Add parser"),
+            Some(("Add parser", true))
+        );
+        assert_eq!(fixup_target("Add parser"), None);
+    }
+
+    #[test]
+    fn test_squashed_description() {
+        assert_eq!(
+            squashed_description("Add parser\n\nDetails.\n", "squash! Add parser\n\nMore.\n"),
+            "Add parser\n\nDetails.\n\nMore.\n"
+        );
+        assert_eq!(
+            squashed_description("Add parser\n", "squash! Add parser\n"),
+            "Add parser\n"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Reading and adding `Key: value` trailers at the end of a description.
+
+use regex::Regex;
+
+/// Whether `line` looks like a `Key: value` trailer.
+fn is_trailer(line: &str) -> bool {
+    let trailer = Regex::new(r"^[A-Za-z][A-Za-z0-9-]*: *\S").unwrap();
+    trailer.is_match(line)
+}
+
+/// The value of the first `key` trailer in the last paragraph of
+/// `description`.
+pub fn trailer_value<'a>(description: &'a str, key: &str) -> Option<&'a str> {
+    let (_, last) = description.trim_end().rsplit_once("\n\n")?;
+    last.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case(key).then(|| value.trim())
+    })
+}
+
+/// `description` with a `key: value` trailer appended to its trailer block,
+/// starting one after the body if it has none.
+pub fn add_trailer(description: &str, key: &str, value: &str) -> String {
+    let description = description.trim_end();
+    let has_trailers = description
+        .rsplit_once("\n\n")
+        .is_some_and(|(_, last)| last.lines().all(is_trailer));
+    let separator = if has_trailers { "\n" } else { "\n\n" };
+    format!("{description}{separator}{key}: {value}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_trailer() {
+        assert_eq!(
+            add_trailer("Fix lexer\n", "Change-Id", "I12"),
+            "Fix lexer\n\nChange-Id: I12\n"
+        );
+        assert_eq!(
+            add_trailer("Fix lexer\n\nSigned-off-by: A <a@b>\n", "Change-Id", "I12"),
+            "Fix lexer\n\nSigned-off-by: A <a@b>\nChange-Id: I12\n"
+        );
+        // The subject alone isn't a trailer block, even if it looks like one.
+        assert_eq!(
+            add_trailer("lexer: handle tabs", "Refs", "ABC-1"),
+            "lexer: handle tabs\n\nRefs: ABC-1\n"
+        );
+        let described = add_trailer("Fix lexer\n\nTabs broke it.\n", "Change-Id", "I12");
+        assert_eq!(described, "Fix lexer\n\nTabs broke it.\n\nChange-Id: I12\n");
+        assert_eq!(trailer_value(&described, "change-id"), Some("I12"));
+        assert_eq!(trailer_value("Change-Id: I12", "Change-Id"), None);
+    }
+}
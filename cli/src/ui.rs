@@ -0,0 +1,29 @@
+//! Keeping stdout for results.
+//!
+//! What a command produces, such as the rewritten commits or a report, goes
+//! to stdout. Messages about what it's doing or why it did nothing go to
+//! stderr with [`status!`], so scripts can read stdout without filtering.
+//! `--quiet` leaves those messages out; warnings and errors are still shown.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn init(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints an informational message to stderr, unless `--quiet` was passed.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::ui::is_quiet() {
+            $crate::progress::suspend(|| eprintln!($($arg)*));
+        }
+    };
+}
+
+pub(crate) use status;
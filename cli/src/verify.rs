@@ -0,0 +1,173 @@
+//! `jjka verify`: check that everything reachable from the repo's commits
+//! can be read back from the store.
+//!
+//! For git-backed repos, every commit, tree and blob is also re-hashed and
+//! compared with its id, which catches bit rot that still decompresses.
+
+use anyhow::{Result, bail};
+use jj_lib::backend::{CommitId, FileId, SymlinkId, TreeId, TreeValue};
+use jj_lib::hex_util::encode_hex;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::store::Store;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt as _;
+
+use crate::progress::Progress;
+use crate::workspace::WorkspaceHelper;
+
+struct Verifier {
+    store: Arc<Store>,
+    git_repo: Option<gix::Repository>,
+    seen_trees: HashSet<TreeId>,
+    seen_files: HashSet<FileId>,
+    seen_symlinks: HashSet<SymlinkId>,
+    commits: usize,
+    problems: Vec<String>,
+}
+
+impl Verifier {
+    /// Re-hashes a git object. Problems reading it are left to the backend
+    /// read that follows, which reports them with more context.
+    fn check_hash(&mut self, kind: &str, id: &[u8], path: &RepoPath) {
+        let Some(git_repo) = &self.git_repo else {
+            return;
+        };
+        let Ok(oid) = gix::ObjectId::try_from(id) else {
+            return;
+        };
+        let Ok(Some(object)) = git_repo.try_find_object(oid) else {
+            return;
+        };
+        let message =
+            match gix::objs::compute_hash(git_repo.object_hash(), object.kind, &object.data) {
+                Ok(actual) if actual == oid => return,
+                Ok(actual) => format!("content hashes to {actual}"),
+                Err(err) => format!("can't be hashed: {err}"),
+            };
+        drop(object);
+        self.problem(kind, id, path, &message);
+    }
+
+    fn problem(&mut self, kind: &str, id: &[u8], path: &RepoPath, message: &str) {
+        let location = if path.is_root() {
+            String::new()
+        } else {
+            format!(" at {}", path.as_internal_file_string())
+        };
+        self.problems
+            .push(format!("{kind} {}{location}: {message}", encode_hex(id)));
+    }
+
+    async fn verify_commit(&mut self, id: &CommitId) {
+        self.commits += 1;
+        if id == self.store.root_commit_id() {
+            return;
+        }
+        self.check_hash("commit", id.as_bytes(), RepoPath::root());
+        let commit = match self.store.get_commit_async(id).await {
+            Ok(commit) => commit,
+            Err(err) => {
+                self.problem("commit", id.as_bytes(), RepoPath::root(), &err.to_string());
+                return;
+            }
+        };
+        for tree_id in commit.tree_id().to_merge().iter() {
+            self.verify_tree(RepoPathBuf::root(), tree_id).await;
+        }
+    }
+
+    async fn verify_tree(&mut self, dir: RepoPathBuf, id: &TreeId) {
+        if !self.seen_trees.insert(id.clone()) {
+            return;
+        }
+        self.check_hash("tree", id.as_bytes(), &dir);
+        let tree = match self.store.get_tree_async(dir.clone(), id).await {
+            Ok(tree) => tree,
+            Err(err) => {
+                self.problem("tree", id.as_bytes(), &dir, &err.to_string());
+                return;
+            }
+        };
+        let entries: Vec<(RepoPathBuf, TreeValue)> = tree
+            .entries_non_recursive()
+            .map(|entry| (dir.join(entry.name()), entry.value().clone()))
+            .collect();
+        for (path, value) in entries {
+            match value {
+                TreeValue::File { id, .. } => self.verify_file(&path, &id).await,
+                TreeValue::Symlink(id) => self.verify_symlink(&path, &id).await,
+                TreeValue::Tree(id) => Box::pin(self.verify_tree(path, &id)).await,
+                // Submodule commits live in another repo.
+                TreeValue::GitSubmodule(_) => {}
+            }
+        }
+    }
+
+    async fn verify_file(&mut self, path: &RepoPath, id: &FileId) {
+        if !self.seen_files.insert(id.clone()) {
+            return;
+        }
+        self.check_hash("file", id.as_bytes(), path);
+        let result = async {
+            let mut reader = self.store.read_file(path, id).await?;
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content).await?;
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            self.problem("file", id.as_bytes(), path, &err.to_string());
+        }
+    }
+
+    async fn verify_symlink(&mut self, path: &RepoPath, id: &SymlinkId) {
+        if !self.seen_symlinks.insert(id.clone()) {
+            return;
+        }
+        self.check_hash("symlink", id.as_bytes(), path);
+        if let Err(err) = self.store.read_symlink(path, id).await {
+            self.problem("symlink", id.as_bytes(), path, &err.to_string());
+        }
+    }
+}
+
+pub async fn verify_command(revset: String) -> Result<()> {
+    let helper = WorkspaceHelper::load()?;
+    let store = helper.repo().store().clone();
+    let git_repo = jj_lib::git::get_git_repo(&store).ok();
+    let ids = helper.evaluate_revset_ids(&revset)?;
+    let mut verifier = Verifier {
+        store,
+        git_repo,
+        seen_trees: HashSet::new(),
+        seen_files: HashSet::new(),
+        seen_symlinks: HashSet::new(),
+        commits: 0,
+        problems: Vec::new(),
+    };
+    let mut progress = Progress::new("verify", Some(ids.len()));
+    for id in &ids {
+        progress.start_item(&id.hex());
+        verifier.verify_commit(id).await;
+    }
+    progress.finish();
+
+    println!(
+        "Checked {} commit(s), {} tree(s), {} file(s) and {} symlink(s)",
+        verifier.commits,
+        verifier.seen_trees.len(),
+        verifier.seen_files.len(),
+        verifier.seen_symlinks.len()
+    );
+    if verifier.problems.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+    for problem in &verifier.problems {
+        println!("  {problem}");
+    }
+    bail!("Found {} problem(s) in the store", verifier.problems.len())
+}
@@ -0,0 +1,164 @@
+//! `jjka verify-signatures`: check that the commits of a revset carry valid
+//! signatures, for CI.
+//!
+//! Signatures are checked by jj's signing backends, so SSH signatures are
+//! only trusted with `signing.backends.ssh.allowed-signers` configured, and
+//! GPG ones by the keyring's trust. Anything short of a good signature fails
+//! the command.
+
+use anyhow::{Result, bail};
+use futures::StreamExt as _;
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use jj_lib::signing::SigStatus;
+use serde::Serialize;
+
+use crate::format::{OutputFormat, commit_summary};
+use crate::perf;
+use crate::progress::Progress;
+use crate::template::Template;
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Status {
+    Good,
+    /// Signed, but by a key that isn't trusted or couldn't be checked.
+    Unknown,
+    Bad,
+    Unsigned,
+    /// The signature couldn't be verified at all, e.g. there's no backend
+    /// for its format.
+    Error,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Good => "good",
+            Status::Unknown => "UNKNOWN",
+            Status::Bad => "BAD",
+            Status::Unsigned => "UNSIGNED",
+            Status::Error => "ERROR",
+        }
+    }
+
+    fn passes(self, allow_unknown: bool) -> bool {
+        match self {
+            Status::Good => true,
+            Status::Unknown => allow_unknown,
+            Status::Bad | Status::Unsigned | Status::Error => false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CommitSignature {
+    change_id: String,
+    commit_id: String,
+    status: Status,
+    /// The key or principal the signature was made with, if known.
+    signer: Option<String>,
+    #[serde(skip)]
+    summary: String,
+}
+
+fn check(commit: &Commit) -> CommitSignature {
+    let (status, signer) = match commit.verification() {
+        Ok(None) => (Status::Unsigned, None),
+        Ok(Some(verification)) => {
+            let status = match verification.status {
+                SigStatus::Good => Status::Good,
+                SigStatus::Unknown => Status::Unknown,
+                SigStatus::Bad => Status::Bad,
+            };
+            (status, verification.display.or(verification.key))
+        }
+        Err(err) => (Status::Error, Some(err.to_string())),
+    };
+    CommitSignature {
+        change_id: commit.change_id().reverse_hex(),
+        commit_id: commit.id().hex(),
+        status,
+        signer,
+        summary: commit_summary(commit),
+    }
+}
+
+pub async fn verify_signatures_command(
+    revset: String,
+    allow_unknown: bool,
+    template: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let template = template.as_deref().map(Template::parse).transpose()?;
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    let root_commit_id = helper.repo().store().root_commit_id().clone();
+    let commits = helper
+        .evaluate_revset(&revset)?
+        .into_iter()
+        .filter(|commit| *commit.id() != root_commit_id)
+        .collect::<Vec<_>>();
+
+    // The backends run gpg or ssh-keygen for every signature.
+    let mut progress = Progress::new("verify-signatures", Some(commits.len()));
+    let mut results = futures::stream::iter(commits)
+        .map(|commit| {
+            progress.start_item(&commit_summary(&commit));
+            tokio::task::spawn_blocking(move || {
+                let signature = check(&commit);
+                (commit, signature)
+            })
+        })
+        .buffered(perf::jobs());
+    let mut signatures = Vec::new();
+    while let Some(result) = results.next().await {
+        let (commit, mut signature) = result?;
+        if let Some(template) = &template {
+            signature.summary = template.render(helper.repo().as_ref(), &commit)?;
+        }
+        signatures.push(signature);
+    }
+    drop(results);
+    progress.finish();
+
+    let failed = signatures
+        .iter()
+        .filter(|signature| !signature.status.passes(allow_unknown))
+        .count();
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&signatures)?),
+        OutputFormat::Text => {
+            for signature in &signatures {
+                print!("{:<9} {}", signature.status.label(), signature.summary);
+                if let Some(signer) = &signature.signer {
+                    print!("  ({signer})");
+                }
+                println!();
+            }
+        }
+    }
+    if failed > 0 {
+        bail!(
+            "{failed} of {} revision(s) in {revset} are not validly signed",
+            signatures.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes() {
+        assert!(Status::Good.passes(false));
+        assert!(!Status::Unknown.passes(false));
+        assert!(Status::Unknown.passes(true));
+        assert!(!Status::Unsigned.passes(true));
+        assert!(!Status::Bad.passes(true));
+    }
+}
@@ -0,0 +1,288 @@
+//! `jjka watch`: snapshot the working copy whenever files change.
+//!
+//! On Linux, changes are noticed through inotify; elsewhere, or when the
+//! tree has more directories than inotify will watch, the working copy is
+//! snapshotted every `--interval` instead. Each snapshot that changed
+//! something is printed with its diffstat and the totals so far.
+
+use anyhow::{Context, Result};
+use jj_lib::backend::CommitId;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::format::{commit_summary, to_datetime};
+use crate::impact::DiffStat;
+use crate::tree_diff::{changed_paths, read_file};
+use crate::unified_diff::count_changed_lines;
+use crate::workspace::WorkspaceHelper;
+
+/// Directories that are never part of the working copy.
+const SKIPPED_DIRS: &[&str] = &[".jj", ".git"];
+
+enum Watcher {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Inotify),
+    Poll,
+}
+
+impl Watcher {
+    fn new(root: &Path, poll: bool) -> Self {
+        #[cfg(target_os = "linux")]
+        if !poll {
+            match inotify::Inotify::new(root) {
+                Ok(inotify) => return Watcher::Inotify(inotify),
+                Err(err) => eprintln!("Warning: Failed to watch for changes ({err}); polling"),
+            }
+        }
+        let _ = (root, poll);
+        Watcher::Poll
+    }
+
+    /// Returns once files have changed and then been left alone for
+    /// `quiet`, or when polling, after `quiet`.
+    fn wait_for_change(&mut self, quiet: Duration) -> Result<()> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Watcher::Inotify(inotify) => {
+                inotify.wait(None)?;
+                while inotify.wait(Some(quiet))? {}
+            }
+            Watcher::Poll => std::thread::sleep(quiet),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::collections::HashMap;
+    use std::ffi::{CString, OsStr};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::SKIPPED_DIRS;
+
+    const MASK: u32 = libc::IN_MODIFY
+        | libc::IN_ATTRIB
+        | libc::IN_CLOSE_WRITE
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO
+        | libc::IN_ONLYDIR
+        | libc::IN_DONT_FOLLOW;
+
+    /// An inotify instance watching every directory of a tree.
+    pub struct Inotify {
+        fd: OwnedFd,
+        dirs: HashMap<i32, PathBuf>,
+    }
+
+    impl Inotify {
+        pub fn new(root: &Path) -> io::Result<Self> {
+            // SAFETY: inotify_init1 only creates a file descriptor.
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: the descriptor was just created and isn't owned elsewhere.
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            let mut inotify = Inotify {
+                fd,
+                dirs: HashMap::new(),
+            };
+            inotify.add_tree(root)?;
+            Ok(inotify)
+        }
+
+        fn add_tree(&mut self, dir: &Path) -> io::Result<()> {
+            let path = CString::new(dir.as_os_str().as_bytes())?;
+            // SAFETY: `path` is a valid C string for the duration of the call.
+            let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), MASK) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.dirs.insert(wd, dir.to_path_buf());
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir()
+                    && !SKIPPED_DIRS
+                        .iter()
+                        .any(|skipped| entry.file_name() == *skipped)
+                {
+                    self.add_tree(&entry.path())?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Waits up to `timeout`, or forever, for events. Returns whether
+        /// there were any. New directories are watched as they appear.
+        pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+            let mut pollfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout_ms = timeout.map_or(-1, |timeout| timeout.as_millis() as libc::c_int);
+            // SAFETY: `pollfd` is a valid array of one element.
+            let ready = unsafe { libc::poll(&mut pollfd, 1, timeout_ms) };
+            if ready < 0 {
+                let err = io::Error::last_os_error();
+                return if err.kind() == io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(err)
+                };
+            }
+            if ready == 0 {
+                return Ok(false);
+            }
+            let mut new_dirs = Vec::new();
+            let mut buffer = [0u8; 16 * 1024];
+            loop {
+                // SAFETY: `buffer` is valid for writes of its length.
+                let len = unsafe {
+                    libc::read(
+                        self.fd.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len(),
+                    )
+                };
+                if len < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    }
+                    return Err(err);
+                }
+                let mut offset = 0;
+                while offset < len as usize {
+                    // SAFETY: the kernel writes whole events, each starting
+                    // with an `inotify_event` header.
+                    let event: libc::inotify_event =
+                        unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr().cast()) };
+                    let name_start = offset + size_of::<libc::inotify_event>();
+                    let name = &buffer[name_start..name_start + event.len as usize];
+                    let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+                    if event.mask & libc::IN_ISDIR != 0
+                        && event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0
+                        && !SKIPPED_DIRS
+                            .iter()
+                            .any(|skipped| name == skipped.as_bytes())
+                        && let Some(parent) = self.dirs.get(&event.wd)
+                    {
+                        new_dirs.push(parent.join(OsStr::from_bytes(name)));
+                    }
+                    if event.mask & libc::IN_IGNORED != 0 {
+                        self.dirs.remove(&event.wd);
+                    }
+                    offset = name_start + event.len as usize;
+                }
+            }
+            for dir in new_dirs {
+                // The directory may be gone already.
+                let _ = self.add_tree(&dir);
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// What the snapshots so far changed, for the running summary.
+#[derive(Default)]
+struct Session {
+    snapshots: usize,
+    paths: BTreeSet<String>,
+    insertions: usize,
+    deletions: usize,
+}
+
+/// Snapshots the working copy at the latest operation. Returns the old and
+/// new working-copy commit if the snapshot changed it.
+fn snapshot(helper: &mut WorkspaceHelper) -> Result<Option<(CommitId, CommitId)>> {
+    let Some(old_id) = helper.wc_commit_id().cloned() else {
+        return Ok(None);
+    };
+    helper.snapshot_working_copy()?;
+    let new_id = helper.wc_commit_id().cloned().unwrap();
+    Ok((new_id != old_id).then_some((old_id, new_id)))
+}
+
+pub async fn watch_command(interval_ms: u64, poll: bool, exec: Option<String>) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    helper.snapshot_working_copy()?;
+    let root = helper.workspace_root().to_path_buf();
+    let mut watcher = Watcher::new(&root, poll);
+    println!("Watching {}; press Ctrl-C to stop", root.display());
+
+    let interval = Duration::from_millis(interval_ms);
+    let mut session = Session::default();
+    loop {
+        watcher.wait_for_change(interval)?;
+        // Load again each time to see what other jj commands did since.
+        let mut helper = WorkspaceHelper::load()?;
+        let (old_id, new_id) = match snapshot(&mut helper) {
+            Ok(Some(ids)) => ids,
+            Ok(None) => continue,
+            Err(err) => {
+                eprintln!("Warning: {err:#}");
+                continue;
+            }
+        };
+        let repo = helper.repo().clone();
+        let old_tree = repo.store().get_commit(&old_id)?.tree_async().await?;
+        let new_commit = repo.store().get_commit(&new_id)?;
+        let new_tree = new_commit.tree_async().await?;
+        let mut stat = DiffStat::default();
+        for change in changed_paths(&old_tree, &new_tree).await? {
+            let before = read_file(repo.store(), &change.path, change.before).await?;
+            let after = read_file(repo.store(), &change.path, change.after).await?;
+            let (insertions, deletions) =
+                count_changed_lines(&before.unwrap_or_default(), &after.unwrap_or_default());
+            stat.files += 1;
+            stat.insertions += insertions;
+            stat.deletions += deletions;
+            session
+                .paths
+                .insert(change.path.as_internal_file_string().to_string());
+        }
+        session.snapshots += 1;
+        session.insertions += stat.insertions;
+        session.deletions += stat.deletions;
+        println!(
+            "{} snapshot {}: {} (session: {} file(s) +{} -{})",
+            to_datetime(&new_commit.committer().timestamp)
+                .map_or(String::new(), |time| time.format("%H:%M:%S").to_string()),
+            session.snapshots,
+            stat.format(),
+            session.paths.len(),
+            session.insertions,
+            session.deletions
+        );
+
+        if let Some(exec) = &exec {
+            let status = Command::new("sh")
+                .arg("-c")
+                .arg(exec)
+                .current_dir(&root)
+                .env("JJKA_COMMIT_ID", new_id.hex())
+                .env("JJKA_CHANGE_ID", new_commit.change_id().reverse_hex())
+                .status()
+                .with_context(|| format!("Failed to run {exec}"))?;
+            if !status.success() {
+                println!(
+                    "  {exec} failed ({status}) on {}",
+                    commit_summary(&new_commit)
+                );
+            }
+        }
+    }
+}
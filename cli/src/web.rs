@@ -0,0 +1,508 @@
+//! `jjka web`: a read-only web UI and JSON API for browsing the repo.
+//!
+//! The repo is reloaded at its latest operation for every request, so the
+//! pages follow along as the repo changes. Nothing is ever written, not even
+//! a working-copy snapshot.
+
+use anyhow::{Context, Result, bail};
+use jj_lib::backend::{CommitId, Signature};
+use jj_lib::commit::Commit;
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_walk;
+use jj_lib::repo::Repo;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+use crate::format::{format_timestamp, subject, to_datetime};
+use crate::hunks::commit_diffs;
+use crate::unified_diff::{DEFAULT_CONTEXT, Hunk, LineKind};
+use crate::workspace::WorkspaceHelper;
+
+/// Number of operations shown in the op log timeline.
+const OPLOG_LIMIT: usize = 100;
+
+/// How long a client has to send its request before it's dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest request line or header accepted, and the most the request
+/// line and headers may add up to. Larger requests get a 431.
+const MAX_HEADER_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 32 * 1024;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+nav a { margin-right: 1em; }
+code, pre { font-family: monospace; }
+table { border-collapse: collapse; }
+td { padding: 0.2em 0.8em 0.2em 0; vertical-align: top; }
+.change-id { color: #a0a; }
+.commit-id { color: #00a; }
+.bookmark { color: #a0a; font-weight: bold; }
+.conflict { color: #c00; font-weight: bold; }
+pre.diff { background: #f8f8f8; padding: 0.5em; }
+.hunk-header { color: #077; }
+.added { background: #dfd; }
+.removed { background: #fdd; }
+";
+
+#[derive(Serialize)]
+struct Person {
+    name: String,
+    email: String,
+    timestamp: String,
+}
+
+impl From<&Signature> for Person {
+    fn from(signature: &Signature) -> Self {
+        Person {
+            name: signature.name.clone(),
+            email: signature.email.clone(),
+            timestamp: to_datetime(&signature.timestamp)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CommitEntry {
+    change_id: String,
+    commit_id: String,
+    parents: Vec<String>,
+    author: Person,
+    committer: Person,
+    description: String,
+    bookmarks: Vec<String>,
+    working_copy: bool,
+    conflict: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Serialize)]
+struct FileDiff {
+    path: String,
+    status: FileStatus,
+    binary: bool,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Serialize)]
+struct CommitDetail {
+    #[serde(flatten)]
+    commit: CommitEntry,
+    files: Vec<FileDiff>,
+}
+
+#[derive(Serialize)]
+struct OperationEntry {
+    id: String,
+    description: String,
+    user: String,
+    start: String,
+    end: String,
+    snapshot: bool,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn html(title: &str, body: String) -> Self {
+        Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body: format!(
+                "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{} - jjka</title>\
+                 <style>{STYLE}</style></head><body>\
+                 <nav><a href=\"/\">Stack</a><a href=\"/oplog\">Operation log</a></nav>\n{body}</body></html>\n",
+                escape_html(title)
+            ),
+        }
+    }
+
+    fn json(value: &impl Serialize) -> Result<Self> {
+        Ok(Response {
+            status: "200 OK",
+            content_type: "application/json",
+            body: serde_json::to_string_pretty(value)? + "\n",
+        })
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{message}\n"),
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn commit_entry(helper: &WorkspaceHelper, commit: &Commit) -> Result<CommitEntry> {
+    let view = helper.repo().view();
+    Ok(CommitEntry {
+        change_id: commit.change_id().reverse_hex(),
+        commit_id: commit.id().hex(),
+        parents: commit.parent_ids().iter().map(|id| id.hex()).collect(),
+        author: commit.author().into(),
+        committer: commit.committer().into(),
+        description: commit.description().to_string(),
+        bookmarks: view
+            .local_bookmarks_for_commit(commit.id())
+            .map(|(name, _)| name.as_str().to_string())
+            .collect(),
+        working_copy: helper.wc_commit_id() == Some(commit.id()),
+        conflict: commit.has_conflict()?,
+    })
+}
+
+async fn file_diffs(helper: &WorkspaceHelper, commit: &Commit) -> Result<Vec<FileDiff>> {
+    let diffs = commit_diffs(helper.repo().as_ref(), commit, DEFAULT_CONTEXT).await?;
+    Ok(diffs
+        .into_iter()
+        .map(|diff| FileDiff {
+            path: diff.path,
+            status: if diff.added {
+                FileStatus::Added
+            } else if diff.removed {
+                FileStatus::Removed
+            } else {
+                FileStatus::Modified
+            },
+            binary: diff.binary,
+            hunks: diff.hunks,
+        })
+        .collect())
+}
+
+/// The stack, newest first like `jj log`.
+fn stack(helper: &WorkspaceHelper, revisions: &str) -> Result<Vec<CommitEntry>> {
+    let mut commits = helper.evaluate_revset(revisions)?;
+    commits.reverse();
+    commits
+        .iter()
+        .map(|commit| commit_entry(helper, commit))
+        .collect()
+}
+
+fn lookup_commit(helper: &WorkspaceHelper, hex: &str) -> Result<Option<Commit>> {
+    let Some(id) = CommitId::try_from_hex(hex) else {
+        return Ok(None);
+    };
+    if !helper.repo().index().has_id(&id) {
+        return Ok(None);
+    }
+    Ok(Some(helper.repo().store().get_commit(&id)?))
+}
+
+fn operations(helper: &WorkspaceHelper) -> Result<Vec<OperationEntry>> {
+    let head = helper.repo().operation().clone();
+    op_walk::walk_ancestors(&[head])
+        .take(OPLOG_LIMIT)
+        .map(|op| {
+            let op = op?;
+            let metadata = op.metadata();
+            Ok(OperationEntry {
+                id: op.id().hex(),
+                description: metadata.description.clone(),
+                user: format!("{}@{}", metadata.username, metadata.hostname),
+                start: format_timestamp(&metadata.time.start),
+                end: format_timestamp(&metadata.time.end),
+                snapshot: metadata.is_snapshot,
+            })
+        })
+        .collect()
+}
+
+fn commit_row(entry: &CommitEntry) -> String {
+    let mut markers = String::new();
+    if entry.working_copy {
+        markers.push_str(" <strong>@</strong>");
+    }
+    for bookmark in &entry.bookmarks {
+        markers.push_str(&format!(
+            " <span class=\"bookmark\">{}</span>",
+            escape_html(bookmark)
+        ));
+    }
+    if entry.conflict {
+        markers.push_str(" <span class=\"conflict\">conflict</span>");
+    }
+    let subject = entry
+        .description
+        .lines()
+        .next()
+        .filter(|line| !line.trim().is_empty())
+        .unwrap_or("(no description set)");
+    format!(
+        "<tr><td><a href=\"/commit/{}\"><code class=\"change-id\">{}</code></a></td>\
+         <td><code class=\"commit-id\">{}</code></td><td>{}</td><td>{}{markers}</td></tr>\n",
+        entry.commit_id,
+        &entry.change_id[..12],
+        &entry.commit_id[..12],
+        escape_html(&entry.author.name),
+        escape_html(subject),
+    )
+}
+
+fn stack_page(entries: &[CommitEntry], revisions: &str) -> Response {
+    let mut body = format!(
+        "<h1>Stack</h1>\n<p>Revisions: <code>{}</code></p>\n",
+        escape_html(revisions)
+    );
+    if entries.is_empty() {
+        body.push_str("<p>(no revisions)</p>\n");
+    } else {
+        body.push_str("<table>\n");
+        for entry in entries {
+            body.push_str(&commit_row(entry));
+        }
+        body.push_str("</table>\n");
+    }
+    Response::html("Stack", body)
+}
+
+fn commit_page(commit: &Commit, detail: &CommitDetail) -> Response {
+    let entry = &detail.commit;
+    let mut body = format!(
+        "<h1>{}</h1>\n<table>\n\
+         <tr><td>Change ID</td><td><code class=\"change-id\">{}</code></td></tr>\n\
+         <tr><td>Commit ID</td><td><code class=\"commit-id\">{}</code></td></tr>\n\
+         <tr><td>Author</td><td>{} &lt;{}&gt; ({})</td></tr>\n",
+        escape_html(subject(commit)),
+        entry.change_id,
+        entry.commit_id,
+        escape_html(&entry.author.name),
+        escape_html(&entry.author.email),
+        format_timestamp(&commit.author().timestamp),
+    );
+    for parent in &entry.parents {
+        body.push_str(&format!(
+            "<tr><td>Parent</td><td><a href=\"/commit/{parent}\"><code class=\"commit-id\">{}</code></a></td></tr>\n",
+            &parent[..12]
+        ));
+    }
+    body.push_str(&format!(
+        "</table>\n<pre>{}</pre>\n",
+        escape_html(&entry.description)
+    ));
+    for file in &detail.files {
+        let status = match file.status {
+            FileStatus::Added => "added",
+            FileStatus::Removed => "removed",
+            FileStatus::Modified => "modified",
+        };
+        body.push_str(&format!(
+            "<h3><code>{}</code> ({status})</h3>\n",
+            escape_html(&file.path)
+        ));
+        if file.binary {
+            body.push_str("<p>(binary file)</p>\n");
+            continue;
+        }
+        body.push_str("<pre class=\"diff\">");
+        for hunk in &file.hunks {
+            body.push_str(&format!(
+                "<span class=\"hunk-header\">{}</span>\n",
+                hunk.header()
+            ));
+            for line in &hunk.lines {
+                let (class, sign) = match line.kind {
+                    LineKind::Context => ("context", ' '),
+                    LineKind::Removed => ("removed", '-'),
+                    LineKind::Added => ("added", '+'),
+                };
+                body.push_str(&format!(
+                    "<span class=\"{class}\">{sign}{}</span>\n",
+                    escape_html(&line.text)
+                ));
+            }
+        }
+        body.push_str("</pre>\n");
+    }
+    Response::html(subject(commit), body)
+}
+
+fn oplog_page(operations: &[OperationEntry]) -> Response {
+    let mut body = String::from("<h1>Operation log</h1>\n<table>\n");
+    for op in operations {
+        body.push_str(&format!(
+            "<tr><td><code class=\"commit-id\">{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            &op.id[..12],
+            op.start,
+            escape_html(&op.user),
+            escape_html(&op.description),
+        ));
+    }
+    body.push_str("</table>\n");
+    if operations.len() == OPLOG_LIMIT {
+        body.push_str(&format!(
+            "<p>(showing the latest {OPLOG_LIMIT} operations)</p>\n"
+        ));
+    }
+    Response::html("Operation log", body)
+}
+
+async fn route(path: &str, revisions: &str) -> Result<Response> {
+    let helper = WorkspaceHelper::load()?;
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let response = match segments.as_slice() {
+        [""] => stack_page(&stack(&helper, revisions)?, revisions),
+        ["oplog"] => oplog_page(&operations(&helper)?),
+        ["commit", hex] | ["api", "commit", hex] => {
+            let Some(commit) = lookup_commit(&helper, hex)? else {
+                return Ok(Response::error("404 Not Found", "No such commit"));
+            };
+            let detail = CommitDetail {
+                commit: commit_entry(&helper, &commit)?,
+                files: file_diffs(&helper, &commit).await?,
+            };
+            if segments[0] == "api" {
+                Response::json(&detail)?
+            } else {
+                commit_page(&commit, &detail)
+            }
+        }
+        ["api", "log"] => Response::json(&stack(&helper, revisions)?)?,
+        ["api", "oplog"] => Response::json(&operations(&helper)?)?,
+        _ => Response::error("404 Not Found", "Not found"),
+    };
+    Ok(response)
+}
+
+/// Reads the request head and returns its method and path (without query).
+/// Reads a line of the request head into `line`, returning its length, or
+/// `MAX_HEADER_LINE + 1` if it's longer than that.
+async fn read_header_line(
+    reader: &mut BufReader<&mut TcpStream>,
+    line: &mut String,
+) -> Result<usize> {
+    let limit = MAX_HEADER_LINE as u64 + 1;
+    Ok((&mut *reader).take(limit).read_line(line).await?)
+}
+
+/// Returns the method and path of the request, or `None` if its head is
+/// too large.
+async fn read_request(reader: &mut BufReader<&mut TcpStream>) -> Result<Option<(String, String)>> {
+    let mut request_line = String::new();
+    let mut size = read_header_line(reader, &mut request_line).await?;
+    if size > MAX_HEADER_LINE {
+        return Ok(None);
+    }
+    let mut header = String::new();
+    loop {
+        let len = read_header_line(reader, &mut header).await?;
+        size += len;
+        if len > MAX_HEADER_LINE || size > MAX_HEADERS {
+            return Ok(None);
+        }
+        if len == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line: {request_line:?}");
+    };
+    let path = target.split('?').next().unwrap_or_default();
+    Ok(Some((method.to_string(), path.to_string())))
+}
+
+async fn handle_connection(mut stream: TcpStream, revisions: Arc<String>) -> Result<()> {
+    let request = timeout(
+        REQUEST_TIMEOUT,
+        read_request(&mut BufReader::new(&mut stream)),
+    )
+    .await
+    .context("Timed out waiting for the request")??;
+    let response = match request {
+        None => Response::error(
+            "431 Request Header Fields Too Large",
+            "The request line or headers are too large",
+        ),
+        Some((method, _)) if method != "GET" => {
+            Response::error("405 Method Not Allowed", "Only GET is supported")
+        }
+        // Loading the repo and diffing block, so they run off the
+        // connection tasks' threads.
+        Some((_, path)) => tokio::task::spawn_blocking(move || {
+            futures::executor::block_on(route(&path, &revisions))
+        })
+        .await?
+        .unwrap_or_else(|err| Response::error("500 Internal Server Error", &format!("{err:#}"))),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+pub async fn web_command(bind: String, port: u16, revisions: String) -> Result<()> {
+    // Fail early if we aren't in a repo or the revset is invalid.
+    WorkspaceHelper::load()?.parse_revset(&revisions)?;
+
+    let listener = TcpListener::bind((bind.as_str(), port))
+        .await
+        .with_context(|| format!("Failed to listen on {bind}:{port}"))?;
+    println!("Serving on http://{}", listener.local_addr()?);
+    // Each connection gets its own task, so a slow client doesn't hold up
+    // the others.
+    let revisions = Arc::new(revisions);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let revisions = revisions.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, revisions).await {
+                eprintln!("Error handling request: {err:#}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<a href=\"x\">Tom & 'Jerry'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,742 @@
+//! Workspace loading, revset resolution and transactions shared by all commands.
+
+use anyhow::{Context, Result, anyhow};
+use itertools::Itertools;
+use jj_lib::backend::{ChangeId, CommitId};
+use jj_lib::commit::Commit;
+use jj_lib::config::{
+    ConfigGetResultExt, ConfigLayer, ConfigResolutionContext, ConfigSource, StackedConfig, resolve,
+};
+use jj_lib::git;
+use jj_lib::gitignore::GitIgnoreFile;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_store::OperationId;
+use jj_lib::ref_name::{WorkspaceName, WorkspaceNameBuf};
+use jj_lib::repo::{ReadonlyRepo, Repo, StoreFactories};
+use jj_lib::repo_path::{RepoPathBuf, RepoPathUiConverter};
+use jj_lib::revset::{
+    self, ResolvedRevsetExpression, RevsetAliasesMap, RevsetDiagnostics, RevsetExpression,
+    RevsetExtensions, RevsetIteratorExt, RevsetParseContext, RevsetResolutionError,
+    RevsetWorkspaceContext, SymbolResolver, UserRevsetExpression,
+};
+use jj_lib::rewrite::RebaseOptions;
+use jj_lib::settings::{HumanByteSize, UserSettings};
+use jj_lib::time_util::DatePatternContext;
+use jj_lib::transaction::Transaction;
+use jj_lib::working_copy::{SnapshotOptions, WorkingCopyFreshness};
+use jj_lib::workspace::{Workspace, default_working_copy_factories, default_working_copy_factory};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::LineRange;
+use crate::cache;
+use crate::exit_code::{Failure, ResultExt as _};
+use crate::failpoint;
+use crate::format;
+use crate::fsmonitor;
+use crate::hooks::{self, Hook};
+use crate::impact::{self, Impact};
+use crate::interrupt;
+use crate::progress::Progress;
+use crate::sign;
+
+const DEFAULT_CONFIG: &str = include_str!("defaults.toml");
+
+/// Walks up from `cwd` to the nearest directory containing `.jj`.
+fn find_workspace_root(cwd: &Path) -> Result<PathBuf> {
+    cwd.ancestors()
+        .find(|dir| dir.join(".jj").is_dir())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            Failure::Precondition.error(anyhow!("There is no jj repo in \"{}\"", cwd.display()))
+        })
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// User config locations, honoring `JJ_CONFIG` the way jj does.
+fn user_config_paths() -> Vec<PathBuf> {
+    if let Some(paths) = std::env::var_os("JJ_CONFIG") {
+        return std::env::split_paths(&paths)
+            .filter(|path| !path.as_os_str().is_empty())
+            .collect();
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")));
+    itertools::chain(
+        config_dir.map(|dir| dir.join("jj").join("config.toml")),
+        home_dir().map(|home| home.join(".jjconfig.toml")),
+    )
+    .collect()
+}
+
+/// Loads jj's layered config: jj-lib defaults, jjka defaults, user files,
+/// repo config, and `JJ_USER`/`JJ_EMAIL` overrides.
+fn load_config(workspace_root: &Path) -> Result<StackedConfig> {
+    let mut config = StackedConfig::with_defaults();
+    config.add_layer(ConfigLayer::parse(ConfigSource::Default, DEFAULT_CONFIG)?);
+
+    for path in user_config_paths() {
+        if path.is_dir() {
+            config.load_dir(ConfigSource::User, &path)?;
+        } else if path.exists() {
+            config.load_file(ConfigSource::User, path)?;
+        }
+    }
+
+    let repo_path = workspace_root.join(".jj").join("repo");
+    let repo_config = repo_path.join("config.toml");
+    if repo_config.exists() {
+        config.load_file(ConfigSource::Repo, repo_config)?;
+    }
+
+    let mut overrides = ConfigLayer::empty(ConfigSource::EnvOverrides);
+    if let Ok(name) = std::env::var("JJ_USER") {
+        overrides.set_value("user.name", name)?;
+    }
+    if let Ok(email) = std::env::var("JJ_EMAIL") {
+        overrides.set_value("user.email", email)?;
+    }
+    config.add_layer(overrides);
+
+    let home = home_dir();
+    let context = ConfigResolutionContext {
+        home_dir: home.as_deref(),
+        repo_path: Some(&repo_path),
+        command: None,
+    };
+    Ok(resolve(&config, &context)?)
+}
+
+/// Writes `input`, a path given on the command line relative to `cwd`, as
+/// the slash-separated path of a file in the workspace at `root`. `./`,
+/// `..` and, on Windows, backslashes are all accepted.
+fn normalize_path(root: &Path, cwd: &Path, input: &str) -> Result<String> {
+    let converter = RepoPathUiConverter::Fs {
+        cwd: cwd.to_path_buf(),
+        base: root.to_path_buf(),
+    };
+    let path = converter
+        .parse_file_path(&from_native_separators(input))
+        .failure(Failure::Usage)?;
+    Ok(path.as_internal_file_string().to_string())
+}
+
+/// Turns the backslashes in `input` into slashes on Windows, where they
+/// separate path components. Elsewhere a backslash is part of a file name.
+fn from_native_separators(input: &str) -> Cow<'_, str> {
+    if cfg!(windows) {
+        Cow::Owned(input.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Parses line ranges given on the command line, with their paths made
+/// repo-relative as [`WorkspaceHelper::parse_file_path`] would, but without
+/// loading the workspace. Paths are taken as they are outside one.
+pub fn parse_ranges(specs: &[String]) -> Result<Vec<LineRange>> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let root = find_workspace_root(&cwd).ok();
+    specs
+        .iter()
+        .map(|spec| {
+            let mut range = LineRange::parse(spec)?;
+            if let Some(root) = &root {
+                range.path = normalize_path(root, &cwd, &range.path)
+                    .with_context(|| format!("Invalid range {spec}"))?;
+            }
+            Ok(range)
+        })
+        .collect()
+}
+
+/// File paths given on the command line, made repo-relative like the paths
+/// of [`parse_ranges`].
+pub fn parse_paths(inputs: &[String]) -> Result<Vec<String>> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let Ok(root) = find_workspace_root(&cwd) else {
+        return Ok(inputs.to_vec());
+    };
+    inputs
+        .iter()
+        .map(|input| normalize_path(&root, &cwd, input))
+        .collect()
+}
+
+/// The root of the workspace containing the current directory, found
+/// without loading the workspace or its repo. For commands that only read
+/// files in the working copy.
+pub fn find_root() -> Result<PathBuf> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    find_workspace_root(&cwd)
+}
+
+/// Settings for the current directory, including the repo config when it's
+/// inside a workspace. For options needed before a command loads the repo.
+pub fn load_settings() -> Result<UserSettings> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let root = find_workspace_root(&cwd).unwrap_or(cwd);
+    Ok(UserSettings::from_config(load_config(&root)?)?)
+}
+
+fn load_revset_aliases(settings: &UserSettings) -> Result<RevsetAliasesMap> {
+    let mut aliases = RevsetAliasesMap::new();
+    for layer in settings.config().layers() {
+        let Ok(Some(table)) = layer.look_up_table("revset-aliases") else {
+            continue;
+        };
+        for (decl, item) in table.iter() {
+            let definition = item
+                .as_str()
+                .with_context(|| format!("Revset alias {decl} must be a string"))?;
+            aliases
+                .insert(decl, definition)
+                .with_context(|| format!("Invalid revset alias {decl}"))?;
+        }
+    }
+    Ok(aliases)
+}
+
+/// Flags for commands that rewrite commits, relaxing jjka's own policies.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct PolicyArgs {
+    /// Allow rewriting immutable revisions, those in `immutable_heads()`
+    /// and their ancestors
+    #[arg(long)]
+    pub ignore_immutable: bool,
+
+    /// Allow rewriting revisions frozen by `jjka.policies.frozen`
+    #[arg(long)]
+    pub ignore_frozen: bool,
+}
+
+/// The visible commit for `change_id`, such as after earlier rewrites in a
+/// transaction.
+pub fn current_commit(repo: &dyn Repo, change_id: &ChangeId) -> Result<Commit> {
+    let ids = repo.resolve_change_id(change_id).unwrap_or_default();
+    let [id] = ids.as_slice() else {
+        return Err(anyhow!(
+            "Change {} is divergent or hidden",
+            change_id.reverse_hex()
+        ));
+    };
+    Ok(repo.store().get_commit(id)?)
+}
+
+/// One indented line per commit, to list the candidates of an ambiguous
+/// revision.
+fn candidate_list(commits: &[Commit]) -> String {
+    commits
+        .iter()
+        .map(|commit| format!("\n  {}", format::commit_summary(commit)))
+        .join("")
+}
+
+/// The error for a change with several visible `commits`, which can only
+/// be told apart by commit id.
+fn divergent_error(commits: &[Commit]) -> anyhow::Error {
+    Failure::Usage.error(anyhow!(
+        "Change {} is divergent: it has {} visible commits; pass the commit id of the one to \
+         use:{}",
+        commits[0].change_id().reverse_hex(),
+        commits.len(),
+        candidate_list(commits)
+    ))
+}
+
+/// A loaded workspace plus everything needed to resolve user revsets and
+/// rewrite commits in it.
+pub struct WorkspaceHelper {
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    revset_aliases: RevsetAliasesMap,
+    revset_extensions: Arc<RevsetExtensions>,
+    path_converter: RepoPathUiConverter,
+    policy: PolicyArgs,
+}
+
+impl WorkspaceHelper {
+    /// Loads the workspace containing the current directory at the latest
+    /// operation.
+    #[tracing::instrument(name = "load_repo")]
+    pub fn load() -> Result<Self> {
+        let cwd = std::env::current_dir().context("Failed to get current directory")?;
+        let workspace_root = find_workspace_root(&cwd)?;
+        let settings = UserSettings::from_config(load_config(&workspace_root)?)?;
+
+        let store_factories = StoreFactories::default();
+        let working_copy_factories = default_working_copy_factories();
+        let workspace = Workspace::load(
+            &settings,
+            &workspace_root,
+            &store_factories,
+            &working_copy_factories,
+        )
+        .context("Failed to load workspace")?;
+        cache::init(workspace.repo_path());
+
+        let repo = workspace
+            .repo_loader()
+            .load_at_head()
+            .context("Failed to load repository")?;
+        tracing::debug!(
+            operation = %repo.op_id().hex(),
+            workspace = %workspace_root.display(),
+            "loaded repo"
+        );
+
+        let revset_aliases = load_revset_aliases(&settings)?;
+        let path_converter = RepoPathUiConverter::Fs {
+            cwd,
+            base: workspace.workspace_root().to_path_buf(),
+        };
+        Ok(WorkspaceHelper {
+            workspace,
+            repo,
+            revset_aliases,
+            revset_extensions: Arc::new(RevsetExtensions::default()),
+            path_converter,
+            policy: PolicyArgs::default(),
+        })
+    }
+
+    pub fn set_policy_args(&mut self, policy: &PolicyArgs) {
+        self.policy = policy.clone();
+    }
+
+    pub fn repo(&self) -> &Arc<ReadonlyRepo> {
+        &self.repo
+    }
+
+    pub fn settings(&self) -> &UserSettings {
+        self.repo.settings()
+    }
+
+    pub fn workspace_root(&self) -> &Path {
+        self.workspace.workspace_root()
+    }
+
+    /// The `.jj/repo` directory, where repo-wide jjka state is kept.
+    pub fn repo_path(&self) -> &Path {
+        self.workspace.repo_path()
+    }
+
+    pub fn workspace_name(&self) -> &WorkspaceName {
+        self.workspace.workspace_name()
+    }
+
+    pub fn wc_commit_id(&self) -> Option<&CommitId> {
+        self.repo
+            .view()
+            .get_wc_commit_id(self.workspace.workspace_name())
+    }
+
+    /// Parses a file path given on the command line, relative to the current
+    /// directory.
+    pub fn parse_file_path(&self, input: &str) -> Result<RepoPathBuf> {
+        Ok(self
+            .path_converter
+            .parse_file_path(&from_native_separators(input))?)
+    }
+
+    pub fn parse_revset(&self, revset_str: &str) -> Result<Arc<UserRevsetExpression>> {
+        let workspace_context = RevsetWorkspaceContext {
+            path_converter: &self.path_converter,
+            workspace_name: self.workspace.workspace_name(),
+        };
+        let context = RevsetParseContext {
+            aliases_map: &self.revset_aliases,
+            local_variables: Default::default(),
+            user_email: self.settings().user_email(),
+            date_pattern_context: DatePatternContext::from(chrono::Local::now()),
+            extensions: &self.revset_extensions,
+            workspace: Some(workspace_context),
+        };
+        let mut diagnostics = RevsetDiagnostics::new();
+        revset::parse(&mut diagnostics, revset_str, &context)
+            .with_context(|| format!("Failed to parse revset: {revset_str}"))
+    }
+
+    /// Parses a revset and resolves its symbols against the current repo.
+    pub fn resolve_revset(&self, revset_str: &str) -> Result<Arc<ResolvedRevsetExpression>> {
+        let expression = self.parse_revset(revset_str)?;
+        let repo = self.repo.as_ref();
+        let symbol_resolver = SymbolResolver::new(repo, self.revset_extensions.symbol_resolvers());
+        expression
+            .resolve_user_expression(repo, &symbol_resolver)
+            .with_context(|| format!("Failed to resolve revset: {revset_str}"))
+    }
+
+    /// Evaluates a revset, returning commits in topological order with
+    /// parents before children.
+    pub fn evaluate_revset(&self, revset_str: &str) -> Result<Vec<Commit>> {
+        let repo = self.repo.as_ref();
+        let revset = self.resolve_revset(revset_str)?.evaluate(repo)?;
+        let mut commits: Vec<Commit> = revset.iter().commits(repo.store()).try_collect()?;
+        commits.reverse();
+        Ok(commits)
+    }
+
+    /// Like [`Self::evaluate_revset`], but only the ids, without reading the
+    /// commits from the store.
+    pub fn evaluate_revset_ids(&self, revset_str: &str) -> Result<Vec<CommitId>> {
+        let repo = self.repo.as_ref();
+        let revset = self.resolve_revset(revset_str)?.evaluate(repo)?;
+        let mut ids: Vec<CommitId> = revset.iter().try_collect()?;
+        ids.reverse();
+        Ok(ids)
+    }
+
+    /// Evaluates a revset that must resolve to exactly one commit. When it
+    /// resolves to more, such as a divergent change id, the error lists
+    /// them so one can be picked by commit id.
+    pub fn resolve_single(&self, revset_str: &str) -> Result<Commit> {
+        let mut commits = match self.evaluate_revset(revset_str) {
+            Ok(commits) => commits,
+            Err(err) => {
+                let divergent = err.chain().find_map(|cause| {
+                    match cause.downcast_ref::<RevsetResolutionError>() {
+                        Some(RevsetResolutionError::DivergentChangeId { targets, .. }) => {
+                            Some(targets)
+                        }
+                        _ => None,
+                    }
+                });
+                let Some(targets) = divergent else {
+                    return Err(err);
+                };
+                let commits: Vec<Commit> = targets
+                    .iter()
+                    .map(|id| self.repo.store().get_commit(id))
+                    .try_collect()?;
+                return Err(divergent_error(&commits));
+            }
+        };
+        match commits.len() {
+            0 => Err(Failure::Usage.error(anyhow!(
+                "Revset \"{revset_str}\" didn't resolve to any revisions"
+            ))),
+            1 => Ok(commits.pop().unwrap()),
+            _ if commits
+                .iter()
+                .all(|commit| commit.change_id() == commits[0].change_id()) =>
+            {
+                Err(divergent_error(&commits))
+            }
+            n => Err(Failure::Usage.error(anyhow!(
+                "Revset \"{revset_str}\" resolved to {n} revisions, expected one:{}",
+                candidate_list(&commits)
+            ))),
+        }
+    }
+
+    /// Records changes in the working copy into the working-copy commit, so
+    /// rewrites never operate on stale contents.
+    #[tracing::instrument(skip_all)]
+    pub fn snapshot_working_copy(&mut self) -> Result<()> {
+        let Some(wc_commit_id) = self.wc_commit_id().cloned() else {
+            return Ok(());
+        };
+        let repo = self.repo.clone();
+        let wc_commit = repo.store().get_commit(&wc_commit_id)?;
+        let max_new_file_size = self
+            .settings()
+            .get_value_with("snapshot.max-new-file-size", |value| {
+                HumanByteSize::try_from(value)
+            })?
+            .0;
+
+        let fsmonitor = fsmonitor::backend(self.settings())?;
+        let settings = self.settings().clone();
+        let workspace_root = self.workspace.workspace_root().to_path_buf();
+
+        let workspace_name = self.workspace.workspace_name().to_owned();
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        match WorkingCopyFreshness::check_stale(locked_ws.locked_wc(), &wc_commit, &repo)? {
+            WorkingCopyFreshness::Fresh => {}
+            WorkingCopyFreshness::Updated(_) => {
+                return Err(Failure::Precondition.error(anyhow!(
+                    "The repo was loaded at an older operation than the working copy"
+                )));
+            }
+            WorkingCopyFreshness::WorkingCopyStale | WorkingCopyFreshness::SiblingOperation => {
+                return Err(Failure::Precondition.error(anyhow!(
+                    "The working copy is stale; run `jj workspace update-stale` first"
+                )));
+            }
+        }
+
+        let options = SnapshotOptions {
+            base_ignores: GitIgnoreFile::empty(),
+            progress: None,
+            start_tracking_matcher: &EverythingMatcher,
+            max_new_file_size,
+        };
+        let new_tree_id = match fsmonitor {
+            fsmonitor::Backend::None => locked_ws.locked_wc().snapshot(&options)?.0,
+            fsmonitor::Backend::Watchman => {
+                let old_tree_id = locked_ws.locked_wc().old_tree_id().clone();
+                fsmonitor::snapshot_with_watchman(
+                    &workspace_root,
+                    &old_tree_id,
+                    repo.store(),
+                    &settings,
+                    &options,
+                )?
+            }
+        };
+        if new_tree_id != *wc_commit.tree_id() {
+            let mut tx = repo.start_transaction();
+            tx.set_is_snapshot(true);
+            let mut_repo = tx.repo_mut();
+            let new_wc_commit = mut_repo
+                .rewrite_commit(&wc_commit)
+                .set_tree_id(new_tree_id)
+                .write()?;
+            mut_repo.set_wc_commit(workspace_name, new_wc_commit.id().clone())?;
+            mut_repo.rebase_descendants()?;
+            self.repo = tx.commit("snapshot working copy")?;
+        }
+        locked_ws.finish(self.repo.op_id().clone())?;
+        Ok(())
+    }
+
+    /// Moves the working copy from operation `old_id` to `new_id` when the
+    /// operation log was rewritten under it. Returns false, leaving it alone,
+    /// if the working copy was at some other operation.
+    pub fn remap_working_copy_operation(
+        &mut self,
+        old_id: &OperationId,
+        new_id: &OperationId,
+    ) -> Result<bool> {
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        if locked_ws.locked_wc().old_operation_id() != old_id {
+            return Ok(false);
+        }
+        locked_ws.finish(new_id.clone())?;
+        Ok(true)
+    }
+
+    /// Adds another workspace for this repo rooted at `root`, which must be
+    /// an empty directory.
+    pub fn add_workspace(&mut self, root: &Path, name: &str) -> Result<Workspace> {
+        let (workspace, repo) = Workspace::init_workspace_with_existing_repo(
+            root,
+            self.workspace.repo_path(),
+            &self.repo,
+            &*default_working_copy_factory(),
+            WorkspaceNameBuf::from(name),
+        )
+        .with_context(|| format!("Failed to add workspace {name}"))?;
+        self.repo = repo;
+        Ok(workspace)
+    }
+
+    pub fn start_transaction(&self) -> Transaction {
+        self.repo.start_transaction()
+    }
+
+    /// Commits that were visible before the transaction but no longer are.
+    fn hidden_commits(&self, tx: &Transaction) -> Result<Vec<CommitId>> {
+        let old_heads =
+            RevsetExpression::commits(self.repo.view().heads().iter().cloned().collect());
+        Ok(old_heads
+            .ancestors()
+            .minus(&RevsetExpression::visible_heads().ancestors())
+            .evaluate(tx.repo())?
+            .iter()
+            .try_collect()?)
+    }
+
+    /// The first of the `hidden` commits that `expression` matches.
+    fn first_protected(
+        &self,
+        expression: &Arc<ResolvedRevsetExpression>,
+        hidden: &[CommitId],
+    ) -> Result<Option<Commit>> {
+        let violations: Vec<CommitId> = expression
+            .intersection(&RevsetExpression::commits(hidden.to_vec()))
+            .evaluate(self.repo.as_ref())?
+            .iter()
+            .try_collect()?;
+        Ok(match violations.first() {
+            Some(id) => Some(self.repo.store().get_commit(id)?),
+            None => None,
+        })
+    }
+
+    /// Fails if any of the `hidden` commits is immutable, like jj does,
+    /// unless `--ignore-immutable` was passed.
+    fn check_immutable(&self, hidden: &[CommitId]) -> Result<()> {
+        if self.policy.ignore_immutable || hidden.is_empty() {
+            return Ok(());
+        }
+        let immutable = self
+            .resolve_revset("immutable_heads()")
+            .context("Invalid revset-aliases.immutable_heads()")?
+            .ancestors();
+        if let Some(commit) = self.first_protected(&immutable, hidden)? {
+            return Err(Failure::Precondition.error(anyhow!(
+                "Commit {} is immutable; pass --ignore-immutable to rewrite it anyway, or \
+                 change revset-aliases.immutable_heads()",
+                format::commit_summary(&commit)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fails if any of the `hidden` commits is matched by the
+    /// `jjka.policies.frozen` revset, unless `--ignore-frozen` was passed.
+    fn check_frozen(&self, hidden: &[CommitId]) -> Result<()> {
+        if self.policy.ignore_frozen || hidden.is_empty() {
+            return Ok(());
+        }
+        let Some(frozen) = self
+            .settings()
+            .get_string("jjka.policies.frozen")
+            .optional()?
+        else {
+            return Ok(());
+        };
+        let frozen_expression = self
+            .resolve_revset(&frozen)
+            .context("Invalid jjka.policies.frozen revset")?;
+        if let Some(commit) = self.first_protected(&frozen_expression, hidden)? {
+            return Err(Failure::Precondition.error(anyhow!(
+                "Commit {} is frozen by jjka.policies.frozen; pass --ignore-frozen to rewrite it anyway",
+                format::commit_summary(&commit)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rebases descendants of rewritten commits, re-signs or warns about
+    /// those that lost their signatures, and enforces policies. Returns the
+    /// commits the transaction hides.
+    fn prepare_transaction(&self, tx: &mut Transaction) -> Result<Vec<CommitId>> {
+        if tx.repo().has_rewrites() {
+            let mut progress = Progress::new("rebase", None);
+            tx.repo_mut().rebase_descendants_with_options(
+                &RebaseOptions::default(),
+                |old_commit, _rebased_commit| {
+                    progress.start_item(&format::commit_summary(&old_commit));
+                },
+            )?;
+            progress.finish();
+        }
+        let mut hidden = self.hidden_commits(tx)?;
+        if sign::resign_rewritten(self.settings(), self.repo.as_ref(), tx, &hidden)? {
+            hidden = self.hidden_commits(tx)?;
+        }
+        self.check_immutable(&hidden)?;
+        self.check_frozen(&hidden)?;
+        Ok(hidden)
+    }
+
+    /// Commits the transaction and records its impact for the summary
+    /// printed at the end of the command. The `jjka.hooks.pre` hook can
+    /// reject the transaction; a failing `jjka.hooks.post` hook is only
+    /// reported, since the changes are already made.
+    #[tracing::instrument(skip_all, fields(description = %description))]
+    pub async fn finish_transaction(
+        &mut self,
+        mut tx: Transaction,
+        description: &str,
+    ) -> Result<()> {
+        let hidden = self.prepare_transaction(&mut tx)?;
+        let mut impact =
+            Impact::compute(self.repo.as_ref(), tx.repo(), &hidden, description).await?;
+        hooks::run_hook(self, Hook::Pre, &mut impact, tx.repo()).await?;
+        self.commit_transaction(tx, description)?;
+        if let Err(err) = hooks::run_hook(self, Hook::Post, &mut impact, self.repo.as_ref()).await {
+            eprintln!("Warning: {err:#}");
+        }
+        impact::record(impact, self.repo.clone());
+        Ok(())
+    }
+
+    /// Like [`Self::finish_transaction`], but leaves the transaction out of
+    /// the impact summary. For bookkeeping that isn't what the command is
+    /// about, such as managing temporary workspaces.
+    pub fn finish_transaction_unreported(
+        &mut self,
+        mut tx: Transaction,
+        description: &str,
+    ) -> Result<()> {
+        self.prepare_transaction(&mut tx)?;
+        self.commit_transaction(tx, description)
+    }
+
+    /// Exports git refs, commits a prepared transaction and updates the
+    /// working copy if its commit changed. Nothing is published if this
+    /// fails before the commit, and a ctrl-C waits until it's all done.
+    #[tracing::instrument(skip_all)]
+    fn commit_transaction(&mut self, mut tx: Transaction, description: &str) -> Result<()> {
+        let _deferred = interrupt::Deferred::new();
+        failpoint::hit("commit")?;
+        if git::get_git_backend(tx.repo().store()).is_ok() {
+            git::export_refs(tx.repo_mut())?;
+        }
+        let old_wc_commit_id = self.wc_commit_id().cloned();
+        self.repo = tx.commit(description)?;
+
+        let new_wc_commit_id = self.wc_commit_id().cloned();
+        if let Some(new_wc_commit_id) = new_wc_commit_id
+            && Some(&new_wc_commit_id) != old_wc_commit_id.as_ref()
+        {
+            let old_tree_id = match &old_wc_commit_id {
+                Some(id) => Some(self.repo.store().get_commit(id)?.tree_id().clone()),
+                None => None,
+            };
+            let new_wc_commit = self.repo.store().get_commit(&new_wc_commit_id)?;
+            self.workspace
+                .check_out(
+                    self.repo.op_id().clone(),
+                    old_tree_id.as_ref(),
+                    &new_wc_commit,
+                )
+                .context("Failed to update the working copy")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        let root = Path::new("/repo");
+        let normalize = |cwd: &str, input| normalize_path(root, Path::new(cwd), input).unwrap();
+        assert_eq!(normalize("/repo", "src/main.rs"), "src/main.rs");
+        assert_eq!(normalize("/repo", "./src/main.rs"), "src/main.rs");
+        assert_eq!(normalize("/repo/src", "main.rs"), "src/main.rs");
+        assert_eq!(normalize("/repo/src", "../README.md"), "README.md");
+        assert!(normalize_path(root, Path::new("/repo"), "../outside.rs").is_err());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_normalize_path_takes_backslashes() {
+        let root = Path::new("C:\\repo");
+        let normalize = |cwd: &str, input| normalize_path(root, Path::new(cwd), input).unwrap();
+        assert_eq!(normalize("C:\\repo", ".\\src\\main.rs"), "src/main.rs");
+        assert_eq!(normalize("C:\\repo\\src", "..\\README.md"), "README.md");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_normalize_path_keeps_backslashes_in_file_names() {
+        let root = Path::new("/repo");
+        let normalize = |cwd: &str, input| normalize_path(root, Path::new(cwd), input).unwrap();
+        assert_eq!(normalize("/repo", "a\\b.rs"), "a\\b.rs");
+        assert_eq!(normalize("/repo/src", "..\\README.md"), "src/..\\README.md");
+    }
+}
@@ -0,0 +1,259 @@
+//! `jjka workspace`: add, list and forget secondary working copies.
+//!
+//! jj doesn't record where a workspace lives, so the paths of the ones added
+//! here are kept in `.jj/repo/jjka/workspaces` for `list` to show.
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Subcommand;
+use jj_lib::ref_name::{WorkspaceName, WorkspaceNameBuf};
+use jj_lib::repo::Repo as _;
+use jj_lib::rewrite::merge_commit_trees;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::format::{OutputFormat, commit_summary};
+use crate::serve::CommitInfo;
+use crate::state_file;
+use crate::workspace::WorkspaceHelper;
+
+#[derive(Subcommand, Debug)]
+pub enum WorkspaceCommand {
+    /// Add a workspace with a new working-copy commit
+    Add {
+        /// The name of the new workspace
+        name: String,
+
+        /// Where to create it [default: a sibling of this workspace named
+        /// after it, like `../repo-NAME`]
+        #[arg(long, value_name = "PATH")]
+        destination: Option<PathBuf>,
+
+        /// The parent of the new working-copy commit [default: the parents
+        /// of this workspace's]
+        #[arg(short = 'r', long)]
+        revision: Option<String>,
+    },
+
+    /// List the workspaces and their working-copy commits
+    List,
+
+    /// Stop tracking workspaces, leaving their files on disk
+    Forget {
+        /// The workspaces to forget [default: this one]
+        names: Vec<String>,
+    },
+}
+
+fn paths_file(helper: &WorkspaceHelper) -> PathBuf {
+    helper.repo_path().join("jjka").join("workspaces")
+}
+
+/// The recorded workspace paths, as `(name, path)` pairs.
+fn load_paths(path: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let Some(content) = state_file::read(path)? else {
+        return Ok(vec![]);
+    };
+    content
+        .lines()
+        .map(|line| {
+            let (name, root) = line
+                .split_once('\t')
+                .ok_or_else(|| anyhow!("Invalid line in {}: {line}", path.display()))?;
+            Ok((name.to_string(), PathBuf::from(root)))
+        })
+        .collect()
+}
+
+fn save_paths(path: &Path, paths: &[(String, PathBuf)]) -> Result<()> {
+    let content: String = paths
+        .iter()
+        .map(|(name, root)| format!("{name}\t{}\n", root.display()))
+        .collect();
+    state_file::write(path, content.as_bytes())
+}
+
+/// `../<this workspace's directory>-<name>`.
+fn default_destination(workspace_root: &Path, name: &str) -> PathBuf {
+    let dir_name = workspace_root
+        .file_name()
+        .map_or("workspace".into(), |dir_name| dir_name.to_string_lossy());
+    workspace_root
+        .parent()
+        .unwrap_or(workspace_root)
+        .join(format!("{dir_name}-{name}"))
+}
+
+async fn add(
+    helper: &mut WorkspaceHelper,
+    name: &str,
+    destination: Option<PathBuf>,
+    revision: Option<String>,
+) -> Result<()> {
+    if helper
+        .repo()
+        .view()
+        .get_wc_commit_id(WorkspaceName::new(name))
+        .is_some()
+    {
+        bail!("Workspace {name} already exists");
+    }
+    let parents = match revision {
+        Some(revision) => vec![helper.resolve_single(&revision)?],
+        None => {
+            let wc_commit_id = helper
+                .wc_commit_id()
+                .context("This workspace has no working-copy commit")?;
+            let wc_commit = helper.repo().store().get_commit(wc_commit_id)?;
+            wc_commit.parents().collect::<Result<_, _>>()?
+        }
+    };
+    let destination = match destination {
+        Some(destination) => std::path::absolute(destination)?,
+        None => default_destination(helper.workspace_root(), name),
+    };
+    if destination.exists() && destination.read_dir()?.next().is_some() {
+        bail!("Destination {} is not empty", destination.display());
+    }
+    std::fs::create_dir_all(&destination)
+        .with_context(|| format!("Failed to create {}", destination.display()))?;
+
+    let mut workspace = helper.add_workspace(&destination, name)?;
+    let tree = merge_commit_trees(helper.repo().as_ref(), &parents).await?;
+    let mut tx = helper.start_transaction();
+    let wc_commit = tx
+        .repo_mut()
+        .new_commit(
+            parents.iter().map(|parent| parent.id().clone()).collect(),
+            tree.id(),
+        )
+        .write()?;
+    tx.repo_mut()
+        .edit(WorkspaceNameBuf::from(name), &wc_commit)?;
+    helper
+        .finish_transaction(tx, &format!("add workspace {name}"))
+        .await?;
+    workspace
+        .check_out(helper.repo().op_id().clone(), None, &wc_commit)
+        .context("Failed to check out the new workspace")?;
+
+    let paths_file = paths_file(helper);
+    let mut paths = load_paths(&paths_file)?;
+    paths.retain(|(existing, _)| existing != name);
+    paths.push((name.to_string(), destination.clone()));
+    save_paths(&paths_file, &paths)?;
+    println!(
+        "Added workspace {name} at {} on top of {}",
+        destination.display(),
+        parents
+            .iter()
+            .map(commit_summary)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct WorkspaceEntry {
+    name: String,
+    current: bool,
+    /// Where the workspace is, if jjka added it or it's this one.
+    path: Option<PathBuf>,
+    working_copy: CommitInfo,
+    #[serde(skip)]
+    summary: String,
+}
+
+fn list(helper: &WorkspaceHelper, format: OutputFormat) -> Result<()> {
+    let paths = load_paths(&paths_file(helper))?;
+    let repo = helper.repo();
+    let mut entries = Vec::new();
+    for (name, commit_id) in repo.view().wc_commit_ids() {
+        let commit = repo.store().get_commit(commit_id)?;
+        let current = **name == *helper.workspace_name();
+        let path = if current {
+            Some(helper.workspace_root().to_path_buf())
+        } else {
+            paths
+                .iter()
+                .find(|(recorded, _)| recorded == name.as_str())
+                .map(|(_, root)| root.clone())
+        };
+        entries.push(WorkspaceEntry {
+            name: name.as_symbol().to_string(),
+            current,
+            path,
+            working_copy: CommitInfo::new(&commit),
+            summary: commit_summary(&commit),
+        });
+    }
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    for entry in &entries {
+        let current = if entry.current { " (current)" } else { "" };
+        println!("{}{current}: {}", entry.name, entry.summary);
+        if let Some(path) = &entry.path {
+            println!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+async fn forget(helper: &mut WorkspaceHelper, names: Vec<String>) -> Result<()> {
+    let names = if names.is_empty() {
+        vec![helper.workspace_name().as_str().to_string()]
+    } else {
+        names
+    };
+    let mut tx = helper.start_transaction();
+    for name in &names {
+        let name = WorkspaceName::new(name);
+        if tx.repo().view().get_wc_commit_id(name).is_none() {
+            bail!("No workspace named {}", name.as_symbol());
+        }
+        tx.repo_mut().remove_wc_commit(name)?;
+    }
+    helper
+        .finish_transaction(tx, &format!("forget workspace {}", names.join(", ")))
+        .await?;
+
+    let paths_file = paths_file(helper);
+    let mut paths = load_paths(&paths_file)?;
+    paths.retain(|(name, _)| !names.contains(name));
+    save_paths(&paths_file, &paths)?;
+    for name in &names {
+        println!("Forgot workspace {name}; its files are left on disk");
+    }
+    Ok(())
+}
+
+pub async fn workspace_command(command: WorkspaceCommand, format: OutputFormat) -> Result<()> {
+    let mut helper = WorkspaceHelper::load()?;
+    match command {
+        WorkspaceCommand::Add {
+            name,
+            destination,
+            revision,
+        } => {
+            helper.snapshot_working_copy()?;
+            add(&mut helper, &name, destination, revision).await
+        }
+        WorkspaceCommand::List => list(&helper, format),
+        WorkspaceCommand::Forget { names } => forget(&mut helper, names).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_destination() {
+        assert_eq!(
+            default_destination(Path::new("/src/project"), "build"),
+            Path::new("/src/project-build")
+        );
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::revset::ResolvedRevsetExpression;
+
+#[tokio::test]
+async fn test_lib_absorb_moves_changes_into_destinations() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\nb\nc\n"))], "Add a");
+    let fix = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("a\nB\nc\n")), ("new.txt", Some("new\n"))],
+        "Fix b",
+    );
+
+    let readonly = repo.repo();
+    let mut tx = readonly.start_transaction();
+    let destinations = ResolvedRevsetExpression::commits(vec![repo.commit(&base).id().clone()]);
+    let outcome = jjka_lib::absorb(tx.repo_mut(), &repo.commit(&fix), &destinations)
+        .await
+        .unwrap();
+    tx.commit("absorb").unwrap();
+
+    let [absorbed] = outcome.destinations.try_into().unwrap();
+    assert_eq!(absorbed.description(), "Add a");
+    assert_eq!(
+        repo.file_content(&absorbed, "a.txt").as_deref(),
+        Some("a\nB\nc\n")
+    );
+    // The new file has no ancestor to go to, so it stays in the fix.
+    let source = outcome.source.unwrap();
+    assert_eq!(source.parent_ids()[0], *absorbed.id());
+    assert_eq!(
+        repo.file_content(&source, "new.txt").as_deref(),
+        Some("new\n")
+    );
+    assert_ne!(source.id().hex(), fix);
+}
+
+#[tokio::test]
+async fn test_lib_absorb_moves_submodule_pointers() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let old = "1111111111111111111111111111111111111111";
+    let new = "2222222222222222222222222222222222222222";
+    let base = repo.create_submodule_commit(&root, "sub", Some(old), &[], "Add sub");
+    let fix = repo.create_submodule_commit(&base, "sub", Some(new), &[], "Bump sub");
+
+    let readonly = repo.repo();
+    let mut tx = readonly.start_transaction();
+    let destinations = ResolvedRevsetExpression::commits(vec![repo.commit(&base).id().clone()]);
+    let outcome = jjka_lib::absorb(tx.repo_mut(), &repo.commit(&fix), &destinations)
+        .await
+        .unwrap();
+    tx.commit("absorb").unwrap();
+
+    assert!(outcome.skipped.is_empty(), "{:?}", outcome.skipped);
+    let [absorbed] = outcome.destinations.try_into().unwrap();
+    assert_eq!(absorbed.description(), "Add sub");
+    assert_eq!(
+        repo.submodule_target(&absorbed, "sub").as_deref(),
+        Some(new)
+    );
+    // The fix keeps its description but no longer changes anything.
+    let source = outcome.source.unwrap();
+    assert_eq!(source.parent_ids()[0], *absorbed.id());
+    assert_eq!(source.tree_id(), absorbed.tree_id());
+}
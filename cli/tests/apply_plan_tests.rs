@@ -0,0 +1,179 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+use serde_json::Value;
+
+/// Plans splitting the last line out of a commit editing the first and last
+/// lines of a.txt, returning the base and the commit.
+fn plan(repo: &TestRepo) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\nthree\n"))], "Edit a");
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--dry-run", "--emit-plan"])
+        .args(["plan.json", "a.txt:3-3"])
+        .assert()
+        .success();
+    (base, commit)
+}
+
+#[test]
+fn test_apply_plan_uses_edited_plan() {
+    let repo = TestRepo::init();
+    let (base, commit) = plan(&repo);
+    let path = repo.root().join("plan.json");
+    let mut plan: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    plan["message"] = "Second line".into();
+    plan["ranges"] = serde_json::json!(["a.txt:1-1"]);
+    std::fs::write(&path, plan.to_string()).unwrap();
+
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"Split \w+ \w+ Edit a into:\n  \w+ \w+ Second line\n")
+                .unwrap(),
+        );
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(selected.description(), "Second line");
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("one\n2\n3\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(remaining.change_id(), repo.commit(&commit).change_id());
+}
+
+#[test]
+fn test_apply_plan_refuses_changed_revision() {
+    let repo = TestRepo::init();
+    plan(&repo);
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .success();
+
+    // The split took the planned lines out of the revision.
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "has changed since the plan was made",
+        ))
+        .stderr(predicate::str::contains("a.txt:3-3 (lost)"))
+        .stderr(predicate::str::contains("no longer changed in"));
+
+    std::fs::write(repo.root().join("plan.json"), "{\"version\": 7}").unwrap();
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("plan.json is not a valid plan"));
+}
+
+#[test]
+fn test_apply_plan_remaps_ranges_after_rewrite() {
+    let repo = TestRepo::init();
+    let (base, commit) = plan(&repo);
+    // A line added at the top moves the planned one down.
+    let amended = repo.amend(&commit, &[("a.txt", Some("0\none\n2\nthree\n"))]);
+
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "has changed since the plan was made",
+        ))
+        .stderr(predicate::str::contains("a.txt:3-3 -> a.txt:4-4"))
+        .stderr(predicate::str::contains("0 lost"));
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("1\n2\nthree\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(remaining.tree_id(), repo.commit(&amended).tree_id());
+}
+
+#[test]
+fn test_apply_plan_keeps_allow_empty() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n"))], "Edit a");
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--dry-run", "--allow-empty"])
+        .args(["--emit-plan", "plan.json", "a.txt:1-1"])
+        .assert()
+        .success();
+    let path = repo.root().join("plan.json");
+    let plan: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(plan["allow_empty"], true);
+
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(remaining.tree_id(), selected.tree_id());
+}
+
+#[test]
+fn test_apply_plan_takes_ranges_planned_in_a_subdirectory() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("src/main.rs", Some("1\n2\n3\n"))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("src/main.rs", Some("one\n2\nthree\n"))],
+        "Edit main",
+    );
+    std::fs::create_dir(repo.root().join("src")).unwrap();
+    repo.jjka()
+        .current_dir(repo.root().join("src"))
+        .args(["hunksplit", "-r", &commit, "--dry-run"])
+        .args(["--emit-plan", "../plan.json", "main.rs:3-3"])
+        .assert()
+        .success();
+    let path = repo.root().join("plan.json");
+    let plan: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(plan["ranges"], serde_json::json!(["src/main.rs:3-3"]));
+
+    repo.jjka()
+        .current_dir(repo.root().join("src"))
+        .args(["apply-plan", "../plan.json"])
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "src/main.rs").as_deref(),
+        Some("1\n2\nthree\n")
+    );
+}
+
+#[test]
+fn test_apply_plan_picks_the_planned_commit_of_a_divergent_change() {
+    let repo = TestRepo::init();
+    let (base, commit) = plan(&repo);
+    repo.create_divergent_copy(&commit, &[("a.txt", Some("ONE\n2\nthree\n"))], "Edit a");
+
+    repo.jjka()
+        .args(["apply-plan", "plan.json"])
+        .assert()
+        .success();
+
+    let selected = repo
+        .children(&repo.commit(&base))
+        .into_iter()
+        .find(|child| repo.file_content(child, "a.txt").as_deref() == Some("1\n2\nthree\n"))
+        .unwrap();
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(remaining.tree_id(), repo.commit(&commit).tree_id());
+}
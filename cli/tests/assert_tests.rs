@@ -0,0 +1,65 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_assert_passes() {
+    let repo = TestRepo::init();
+    let base = repo.root_commit_id();
+    let first = repo.create_commit(&[&base], &[("a.txt", Some("a\n"))], "feat: add a");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "fix: add b");
+    let range = format!("{first}::{second}");
+
+    repo.jjka()
+        .args([
+            "assert",
+            "revset-is-empty(conflicts())",
+            &format!("count({range}) == 2"),
+            &format!("file-exists(b.txt, {second}) && !file-exists(b.txt, {first})"),
+            &format!("description-matches({range}, '^(feat|fix): ')"),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "ok      revset-is-empty(conflicts())",
+        ))
+        .stdout(predicate::str::contains("FAILED").not());
+}
+
+#[test]
+fn test_assert_fails() {
+    let repo = TestRepo::init();
+    let base = repo.root_commit_id();
+    let first = repo.create_commit(&[&base], &[("a.txt", Some("a\n"))], "feat: add a");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Add b");
+
+    repo.jjka()
+        .args([
+            "assert",
+            &format!("description-matches({first}::{second}, '^feat: ')"),
+            &format!("file-exists(a.txt, {second})"),
+            &format!("file-exists(c.txt, {second})"),
+        ])
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::is_match(r"FAILED  description-matches[^\n]*\n +\w+ \w+ Add b\n")
+                .unwrap(),
+        )
+        .stdout(predicate::str::contains("ok      file-exists(a.txt"))
+        .stdout(predicate::str::is_match(r"c.txt doesn't exist in \w+ \w+ Add b").unwrap())
+        .stderr(predicate::str::contains("2 of 3 assertion(s) failed"));
+}
+
+#[test]
+fn test_assert_invalid_expression() {
+    let repo = TestRepo::init();
+
+    repo.jjka()
+        .args(["assert", "revset-is-empty(@)", "frobnicate(@)"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("unknown function frobnicate()"));
+}
@@ -0,0 +1,316 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+use serde_json::Value;
+
+/// A revision changing a.txt and adding b.txt, on a base with a.txt.
+fn setup(repo: &TestRepo) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n"))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("one\n2\n3\n")), ("b.txt", Some("new\n"))],
+        "Everything",
+    );
+    (base, commit)
+}
+
+#[test]
+fn test_autosplit_makes_the_scripts_commits() {
+    let repo = TestRepo::init();
+    let (base, commit) = setup(&repo);
+    let script = r#"cat > ../input.json; echo '{"commits": [{"description": "Add b", "ranges": ["b.txt:1-1"]}], "description": "Edit a"}'"#;
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--script", script])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"Split \w+ \w+ Everything into:\n  \w+ \w+ Add b\n  \w+ \w+ Edit a\n",
+            )
+            .unwrap(),
+        );
+
+    let input: Value =
+        serde_json::from_slice(&std::fs::read(repo.root().join("../input.json")).unwrap()).unwrap();
+    assert_eq!(input["description"], "Everything");
+    let hunks = input["hunks"].as_array().unwrap();
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0]["range"], "a.txt:1-1");
+    assert_eq!(hunks[0]["removed"], serde_json::json!(["1"]));
+    assert_eq!(hunks[0]["added"], serde_json::json!(["one"]));
+    assert_eq!(hunks[1]["range"], "b.txt:1-1");
+
+    let [added] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(added.description(), "Add b");
+    assert_eq!(repo.file_content(&added, "b.txt").as_deref(), Some("new\n"));
+    assert_eq!(
+        repo.file_content(&added, "a.txt").as_deref(),
+        Some("1\n2\n3\n")
+    );
+    let [rest] = repo.children(&added).try_into().unwrap();
+    assert_eq!(rest.description(), "Edit a");
+    assert_eq!(rest.change_id(), repo.commit(&commit).change_id());
+    assert_eq!(rest.tree_id(), repo.commit(&commit).tree_id());
+}
+
+#[test]
+fn test_autosplit_dry_run_and_bad_output() {
+    let repo = TestRepo::init();
+    let (base, commit) = setup(&repo);
+    let script = r#"echo '{"commits": [{"description": "Add b", "ranges": ["b.txt:1-1"]}]}'"#;
+
+    let output = repo
+        .jjka()
+        .args([
+            "--json",
+            "autosplit",
+            "-r",
+            &commit,
+            "--dry-run",
+            "--script",
+            script,
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(report["commits"][0]["files"][0]["path"], "b.txt");
+    assert_eq!(report["commits"][0]["files"][0]["selected_insertions"], 1);
+    assert_eq!(report["remaining"], "Everything");
+    assert_eq!(repo.children(&repo.commit(&base)), [repo.commit(&commit)]);
+
+    repo.jjka()
+        .args([
+            "autosplit",
+            "-r",
+            &commit,
+            "--script",
+            "echo '{\"comits\": []}'",
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The strategy script didn't print a valid list of commits",
+        ));
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--script", "exit 3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("The strategy script exited with"));
+}
+
+#[test]
+fn test_autosplit_by_codeowners() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[(
+            ".github/CODEOWNERS",
+            Some("/web/ @org/web\n*.rs @org/core\n"),
+        )],
+        "Base",
+    );
+    let commit = repo.create_commit(
+        &[&base],
+        &[
+            ("src/lib.rs", Some("fn a() {}\n")),
+            ("web/app.js", Some("a();\n")),
+            ("web/lib.rs", Some("fn b() {}\n")),
+            ("README", Some("Docs\n")),
+        ],
+        "Everything\n\nWith a body.\n",
+    );
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--codeowners"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"  \w+ \w+ Everything \(@org/core\)\n  \w+ \w+ Everything \(@org/web\)\n  \w+ \w+ Everything\n",
+            )
+            .unwrap(),
+        );
+
+    let [core] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        core.description(),
+        "Everything (@org/core)\n\nWith a body.\n"
+    );
+    // The last matching rule wins, so web/lib.rs is core's.
+    assert!(repo.file_content(&core, "src/lib.rs").is_some());
+    assert!(repo.file_content(&core, "web/lib.rs").is_some());
+    assert!(repo.file_content(&core, "web/app.js").is_none());
+    let [web] = repo.children(&core).try_into().unwrap();
+    assert!(repo.file_content(&web, "web/app.js").is_some());
+    assert!(repo.file_content(&web, "README").is_none());
+    let [rest] = repo.children(&web).try_into().unwrap();
+    assert_eq!(rest.description(), "Everything\n\nWith a body.\n");
+    assert_eq!(rest.tree_id(), repo.commit(&commit).tree_id());
+}
+
+#[test]
+fn test_autosplit_by_codeowners_needs_a_file() {
+    let repo = TestRepo::init();
+    let (_, commit) = setup(&repo);
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--codeowners"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "has no CODEOWNERS file to split by",
+        ));
+
+    // Everything owned by someone: the last owners' changes stay put.
+    let owned = repo.create_commit(
+        &[&commit],
+        &[
+            ("CODEOWNERS", Some("* @team\nb.txt @other\n")),
+            ("a.txt", Some("one\ntwo\n3\n")),
+            ("b.txt", Some("newer\n")),
+        ],
+        "More",
+    );
+    repo.jjka()
+        .args(["autosplit", "-r", &owned, "--codeowners", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  More (@team)  (CODEOWNERS +2 -0, a.txt +1 -1)",
+        ))
+        .stdout(predicate::str::contains("  More (@other)  (the rest)"));
+}
+
+#[test]
+fn test_autosplit_separates_generated_files() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("Cargo.lock", Some("v1\n"))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[
+            ("Cargo.lock", Some("v2\n")),
+            ("src/lib.rs", Some("fn a() {}\n")),
+            ("api/user.pb.go", Some("package api\n")),
+        ],
+        "Use the new client",
+    );
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--generated"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"  \w+ \w+ Use the new client\n  \w+ \w+ Use the new client \(generated files\)\n",
+            )
+            .unwrap(),
+        );
+    let [written] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert!(repo.file_content(&written, "src/lib.rs").is_some());
+    assert!(repo.file_content(&written, "api/user.pb.go").is_none());
+    assert_eq!(
+        repo.file_content(&written, "Cargo.lock").as_deref(),
+        Some("v1\n")
+    );
+    let [generated] = repo.children(&written).try_into().unwrap();
+    assert_eq!(generated.tree_id(), repo.commit(&commit).tree_id());
+}
+
+#[test]
+fn test_autosplit_generated_patterns_from_config() {
+    let repo = TestRepo::init();
+    let (_, commit) = setup(&repo);
+    repo.add_config("jjka.autosplit.generated = ['b.*']");
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--generated", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  Everything  (a.txt +1 -1)"))
+        .stdout(predicate::str::contains(
+            "  Everything (generated files)  (the rest)",
+        ));
+
+    // Without the setting, neither file is generated.
+    let other = TestRepo::init();
+    let (_, commit) = setup(&other);
+    other
+        .jjka()
+        .args(["autosplit", "-r", &commit, "--generated"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "doesn't change both generated and other files",
+        ));
+}
+
+#[test]
+fn test_autosplit_by_configured_rules() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("README", Some("a\n"))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[
+            ("src/lib.rs", Some("fn a() {}\n")),
+            ("tests/a.rs", Some("#[test]\nfn a() {}\n")),
+            ("docs/guide.md", Some("# A\n")),
+        ],
+        "Add a\n\nIt's needed.",
+    );
+    repo.add_config(
+        "[jjka.autosplit.rules]\n\
+         docs = { paths = ['*.md'], message = 'docs: {subject}' }\n\
+         tests = { paths = ['tests/**'] }",
+    );
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"  \w+ \w+ docs: Add a\n  \w+ \w+ Add a \(tests\)\n")
+                .unwrap(),
+        );
+    let [docs] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(docs.description(), "docs: Add a\n");
+    assert!(repo.file_content(&docs, "docs/guide.md").is_some());
+    assert!(repo.file_content(&docs, "tests/a.rs").is_none());
+    let [tests] = repo.children(&docs).try_into().unwrap();
+    assert!(repo.file_content(&tests, "tests/a.rs").is_some());
+    assert!(repo.file_content(&tests, "src/lib.rs").is_none());
+    let [rest] = repo.children(&tests).try_into().unwrap();
+    assert_eq!(rest.description(), "Add a\n\nIt's needed.");
+    assert_eq!(rest.tree_id(), repo.commit(&commit).tree_id());
+}
+
+#[test]
+fn test_autosplit_needs_a_strategy_or_rules() {
+    let repo = TestRepo::init();
+    let (_, commit) = setup(&repo);
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Pass --script, --codeowners or --generated, or configure [jjka.autosplit.rules]",
+        ));
+
+    repo.add_config("jjka.autosplit.rules.docs = { paths = ['*.md'] }");
+    repo.jjka()
+        .args(["autosplit", "-r", &commit])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "jjka.autosplit.rules find nothing to split out of",
+        ));
+}
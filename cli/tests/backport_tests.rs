@@ -0,0 +1,266 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt as _;
+use std::process::Command;
+
+/// root -> base (release-1.2) -> feature -> fix
+fn release_repo() -> (TestRepo, String, String) {
+    let repo = TestRepo::init();
+    let base = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("app.txt", Some("one\ntwo\nthree\n"))],
+        "Initial version",
+    );
+    repo.set_bookmark("release-1.2", &base);
+    let feature = repo.create_commit(
+        &[&base],
+        &[("feature.txt", Some("new feature\n"))],
+        "Add feature",
+    );
+    let fix = repo.create_commit(
+        &[&feature],
+        &[("app.txt", Some("one\nTWO\nthree\n"))],
+        "Fix two",
+    );
+    (repo, base, fix)
+}
+
+#[test]
+fn test_backport_cherry_picks_onto_bookmark() {
+    let (repo, base, fix) = release_repo();
+    let fix_commit = repo.commit(&fix);
+
+    repo.jjka()
+        .args([
+            "backport",
+            &fix,
+            "--onto",
+            "release-1.2",
+            "-b",
+            "backport-fix",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Backported 1 commit(s) onto release-1.2",
+        ))
+        .stdout(predicate::str::contains("patch-id differs").not());
+
+    let backported = repo.bookmark_commit("backport-fix").unwrap();
+    assert_eq!(backported.parent_ids()[0].hex(), base);
+    assert_eq!(
+        repo.file_content(&backported, "app.txt").as_deref(),
+        Some("one\nTWO\nthree\n")
+    );
+    // The unrelated feature commit is not part of the backport.
+    assert_eq!(repo.file_content(&backported, "feature.txt"), None);
+    let description = backported.description();
+    let marker = format!(
+        "Fix two\n\n(backported from {})\n\nBackport-Patch-Id: ",
+        fix_commit.change_id().reverse_hex()
+    );
+    assert!(description.starts_with(&marker), "{description}");
+    // The trailer records the source's patch id, which the copy shares.
+    let patch_id = description[marker.len()..].trim_end();
+    assert!(patch_id.len() >= 16 && patch_id.chars().all(|c| c.is_ascii_hexdigit()));
+    // The release bookmark itself stays put.
+    assert_eq!(
+        repo.bookmark_commit("release-1.2").unwrap().id().hex(),
+        base
+    );
+}
+
+#[test]
+fn test_backport_default_bookmark_name() {
+    let (repo, _base, fix) = release_repo();
+    let change_id = repo.commit(&fix).change_id().reverse_hex();
+    let bookmark = format!("backport/release-1.2/{}", &change_id[..12]);
+
+    repo.jjka()
+        .args(["backport", &fix, "--onto", "release-1.2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Bookmark {bookmark} points to"
+        )));
+
+    assert!(repo.bookmark_commit(&bookmark).is_some());
+}
+
+#[test]
+fn test_backport_skips_commits_already_on_target() {
+    let (repo, base, _fix) = release_repo();
+
+    repo.jjka()
+        .args(["backport", &base, "--onto", "release-1.2"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("already in release-1.2"))
+        .stderr(predicate::str::contains("Nothing to backport"));
+}
+
+#[test]
+fn test_backport_unknown_bookmark() {
+    let (repo, _base, fix) = release_repo();
+
+    repo.jjka()
+        .args(["backport", &fix, "--onto", "release-9.9"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Bookmark release-9.9 doesn't exist",
+        ));
+}
+
+#[test]
+fn test_backport_onto_several_targets() {
+    let (repo, base, fix) = release_repo();
+    // release-1.1 changed the line the fix touches.
+    let old = repo.create_commit(
+        &[&base],
+        &[("app.txt", Some("one\nzwei\nthree\n"))],
+        "Translate two",
+    );
+    repo.set_bookmark("release-1.1", &old);
+
+    repo.jjka()
+        .args([
+            "backport",
+            &fix,
+            "--onto",
+            "release-1.1,release-1.2",
+            "-b",
+            "x",
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "-b names one bookmark, but there are 2 targets",
+        ));
+
+    repo.jjka()
+        .args([
+            "backport",
+            &fix,
+            "--onto",
+            "release-1.1",
+            "--onto",
+            "release-1.2",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Backported 1 commit(s) onto release-1.1",
+        ))
+        .stderr(predicate::str::contains(
+            "Backported 1 commit(s) onto release-1.2",
+        ))
+        .stderr(predicate::str::contains(
+            "Warning: 1 commit(s) backported onto release-1.1 have conflicts to resolve",
+        ))
+        .stderr(predicate::str::contains("onto release-1.2 have conflicts").not());
+    let change_id = &repo.commit(&fix).change_id().reverse_hex()[..12];
+    let old_backport = repo
+        .bookmark_commit(&format!("backport/release-1.1/{change_id}"))
+        .unwrap();
+    assert_eq!(old_backport.parent_ids()[0].hex(), old);
+    assert!(old_backport.has_conflict().unwrap());
+    let new_backport = repo
+        .bookmark_commit(&format!("backport/release-1.2/{change_id}"))
+        .unwrap();
+    assert_eq!(new_backport.parent_ids()[0].hex(), base);
+    assert!(!new_backport.has_conflict().unwrap());
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+}
+
+#[test]
+fn test_backport_lines_and_open_a_pull_request() {
+    let (repo, base, _) = release_repo();
+    let fix = repo.create_commit(
+        &[&base],
+        &[("app.txt", Some("ONE\ntwo\nTHREE\n"))],
+        "Fix one and three",
+    );
+    let origin = repo.root().parent().unwrap().join("origin.git");
+    git(repo.root(), &["init", "--bare", origin.to_str().unwrap()]);
+    git(
+        &repo.root().join(".jj/repo/store/git"),
+        &["remote", "add", "origin", origin.to_str().unwrap()],
+    );
+    repo.add_config(
+        "jjka.forge.remotes.origin = { kind = 'github', url = 'https://github.com/org/app' }",
+    );
+    // A fake GitHub with no open pull requests, which keeps what's posted.
+    let forge = tempfile::tempdir().unwrap();
+    let script = r#"#!/bin/sh
+# Read the config jjka writes, so it doesn't hit a closed pipe.
+cat > /dev/null
+for arg; do
+  case "$arg" in @*) cp "${arg#@}" "$FAKE_FORGE/posted" ;; esac
+  url=$arg
+done
+case "$url" in
+  *"/pulls?state=open"*) echo '[]' ;;
+  */pulls) echo '{"number": 9, "html_url": "https://github.com/org/app/pull/9"}' ;;
+  *) exit 22 ;;
+esac
+"#;
+    let curl = forge.path().join("curl");
+    std::fs::write(&curl, script).unwrap();
+    std::fs::set_permissions(&curl, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let path = std::env::join_paths(
+        std::iter::once(forge.path().to_path_buf()).chain(std::env::split_paths(&path)),
+    )
+    .unwrap();
+
+    repo.jjka()
+        .env("PATH", &path)
+        .env("FAKE_FORGE", forge.path())
+        .args([
+            "backport",
+            &fix,
+            "--onto",
+            "release-1.2",
+            "-b",
+            "backport-one",
+            "--lines",
+            "app.txt:1-1",
+            "--pr",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Pushed backport-one to origin"))
+        .stdout(predicate::str::contains(
+            "#9 into release-1.2 (opened) https://github.com/org/app/pull/9",
+        ))
+        .stdout(predicate::str::contains("(patch-id not checked)"));
+    let backported = repo.bookmark_commit("backport-one").unwrap();
+    assert_eq!(
+        repo.file_content(&backported, "app.txt").as_deref(),
+        Some("ONE\ntwo\nthree\n")
+    );
+    // Part of the source's patch can't match its patch id.
+    assert!(
+        !backported.description().contains("Backport-Patch-Id"),
+        "{:?}",
+        backported.description()
+    );
+    let posted: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(forge.path().join("posted")).unwrap())
+            .unwrap();
+    assert_eq!(posted["head"], "backport-one");
+    assert_eq!(posted["base"], "release-1.2");
+    assert_eq!(posted["title"], "[release-1.2] Fix one and three");
+}
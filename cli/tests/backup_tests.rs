@@ -0,0 +1,85 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_backup_and_restore_in_another_repo() {
+    let source = TestRepo::init();
+    let root = source.root_commit_id();
+    let first = source.create_commit(&[&root], &[("a.txt", Some("a\n"))], "First");
+    let second = source.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second");
+    source.set_bookmark("feature", &second);
+    let file = source.root().parent().unwrap().join("work.jjka-backup");
+
+    source
+        .jjka()
+        .args([
+            "backup",
+            "-o",
+            file.to_str().unwrap(),
+            "-r",
+            &format!("{first}::"),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Backed up 2 commit(s) and 1 bookmark(s) to",
+        ));
+
+    let target = TestRepo::init();
+    target
+        .jjka()
+        .args(["backup", "restore", file.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"Restored 2 commit\(s\) and 1 bookmark\(s\) from operation \w{12} \(",
+            )
+            .unwrap(),
+        );
+
+    let restored = target.bookmark_commit("feature").unwrap();
+    let original = source.commit(&second);
+    assert_eq!(restored.id(), original.id());
+    assert_eq!(restored.change_id(), original.change_id());
+    assert_eq!(
+        target.file_content(&restored, "a.txt").as_deref(),
+        Some("a\n")
+    );
+
+    // Restoring again changes nothing.
+    target
+        .jjka()
+        .args(["backup", "restore", file.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("and 0 bookmark(s)"));
+}
+
+#[test]
+fn test_backup_errors() {
+    let repo = TestRepo::init();
+    repo.jjka()
+        .args(["backup"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--output <OUTPUT>"));
+
+    let file = repo.root().parent().unwrap().join("not-a-backup");
+    std::fs::write(&file, "hello\n").unwrap();
+    repo.jjka()
+        .args(["backup", "restore", file.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a jjka backup"));
+
+    let manifest = r#"{"operation":"not hex","operation_description":"","operation_time":"","commits":0,"heads":[],"bookmarks":{}}"#;
+    std::fs::write(&file, format!("# jjka backup v1\n{manifest}\n")).unwrap();
+    repo.jjka()
+        .args(["backup", "restore", file.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid operation id in"));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+use serde_json::Value;
+
+#[test]
+fn test_bench_times_each_step() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("A\nb\n"))], "Edit");
+    let op_before = repo.repo().op_id().clone();
+
+    let output = repo
+        .jjka()
+        .args(["--json", "bench", "-r", &commit])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: Value = serde_json::from_slice(&output).unwrap();
+    let phases = report["phases"].as_array().unwrap();
+    let names: Vec<_> = phases
+        .iter()
+        .map(|phase| phase["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["load", "snapshot", "revset", "read", "diff", "commit"]
+    );
+    assert_eq!(phases[2]["detail"], format!("1 revision(s) in {commit}"));
+    assert_eq!(phases[3]["detail"], "2 file version(s), 6 bytes");
+    assert_eq!(phases[4]["detail"], "1 hunk(s)");
+    assert!(report["total_seconds"].as_f64().unwrap() >= 0.0);
+    // The commit step writes an operation of its own.
+    assert_ne!(repo.repo().op_id(), &op_before);
+}
+
+#[test]
+fn test_bench_no_commit() {
+    let repo = TestRepo::init();
+    let op_before = repo.repo().op_id().clone();
+    repo.jjka()
+        .args(["bench", "--no-commit"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"(?m)^load\s+\d+\.\d{3}s  at operation [0-9a-f]{12}$")
+                .unwrap(),
+        )
+        .stdout(predicate::str::contains("commit").not())
+        .stdout(predicate::str::is_match(r"(?m)^total\s+\d+\.\d{3}s$").unwrap());
+    assert_eq!(repo.repo().op_id(), &op_before);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+/// A linear history where `state.txt` contains "bug" from the third commit on.
+fn buggy_history() -> (TestRepo, Vec<String>) {
+    let repo = TestRepo::init();
+    let mut commits = Vec::new();
+    let mut parent = repo.root_commit_id();
+    for (i, state) in ["ok", "ok", "bug", "bug", "bug"].iter().enumerate() {
+        parent = repo.create_commit(
+            &[&parent],
+            &[("state.txt", Some(state))],
+            &format!("Commit {i}\n\nBody of commit {i}.\n"),
+        );
+        commits.push(parent.clone());
+    }
+    (repo, commits)
+}
+
+const CHECK: &str = "! grep -q bug state.txt";
+
+#[test]
+fn test_bisect_finds_first_bad_change() {
+    let (repo, commits) = buggy_history();
+    let first_bad = repo.commit(&commits[2]);
+
+    repo.jjka()
+        .args(["bisect", "--good", &commits[0], "--bad", &commits[4]])
+        .args(["--", "sh", "-c", CHECK])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "The first bad change is {}",
+            first_bad.change_id().reverse_hex()
+        )))
+        .stdout(predicate::str::contains(
+            "Commit 2\n    \n    Body of commit 2.",
+        ));
+
+    // The scratch workspace is forgotten afterwards.
+    assert_eq!(repo.repo().view().wc_commit_ids().len(), 1);
+}
+
+#[test]
+fn test_bisect_skipped_range_is_indeterminate() {
+    let (repo, commits) = buggy_history();
+
+    repo.jjka()
+        .args(["bisect", "--good", &commits[0], "--bad", &commits[3]])
+        .args(["--", "sh", "-c", "exit 125"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("The first bad change is one of:"))
+        .stdout(predicate::str::contains("Commit 1"))
+        .stdout(predicate::str::contains("Commit 3"))
+        .stderr(predicate::str::contains("in a skipped range"));
+}
+
+#[test]
+fn test_bisect_forgets_scratch_workspace_on_error() {
+    let (repo, commits) = buggy_history();
+
+    repo.jjka()
+        .args(["bisect", "--good", &commits[0], "--bad", &commits[4]])
+        .args(["--", "jjka-no-such-command"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Failed to run jjka-no-such-command",
+        ));
+
+    assert_eq!(repo.repo().view().wc_commit_ids().len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bisect_forgets_scratch_workspace_on_interrupt() {
+    let (repo, commits) = buggy_history();
+
+    // The command interrupts jjka itself, as a ctrl-C would.
+    let status = repo
+        .jjka_std()
+        .args(["bisect", "--good", &commits[0], "--bad", &commits[4]])
+        .args(["--", "sh", "-c", "kill -INT $PPID"])
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    assert_eq!(repo.repo().view().wc_commit_ids().len(), 1);
+}
+
+#[test]
+fn test_bisect_requires_command() {
+    let (repo, commits) = buggy_history();
+
+    repo.jjka()
+        .args(["bisect", "--good", &commits[0]])
+        .assert()
+        .failure();
+}
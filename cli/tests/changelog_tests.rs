@@ -0,0 +1,67 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_changelog_markdown() {
+    let repo = TestRepo::init();
+    repo.add_config("jjka.forge.url = 'https://github.com/owner/repo'");
+    let root = repo.root_commit_id();
+    let feat = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("a\n"))],
+        "feat(cli): Add a flag\n\nRefs: #4\n",
+    );
+    let fix = repo.create_commit(&[&feat], &[("a.txt", Some("b\n"))], "fix: Stop crashing\n");
+    let chore = repo.create_commit(
+        &[&fix],
+        &[("b.txt", Some("b\n"))],
+        "chore!: Drop the old config\n\nBREAKING CHANGE: The old config file is ignored.\n",
+    );
+    let revset = format!("{root}..{chore}");
+
+    repo.jjka()
+        .args(["changelog", "-r", &revset, "--title", "v2.0.0"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!(
+            "## v2.0.0\n\n### Breaking changes\n\n- The old config file is ignored. ([{}](https://github.com/owner/repo/commit/{chore}))\n\n### Features\n\n- **cli:** Add a flag ([{}](https://github.com/owner/repo/commit/{feat})), [#4](https://github.com/owner/repo/issues/4)\n\n### Fixes\n\n- Stop crashing",
+            &chore[..12],
+            &feat[..12],
+        )))
+        .stdout(predicate::str::contains("Other changes").not());
+
+    repo.jjka()
+        .args(["changelog", "-r", &revset, "--all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "### Other changes\n\n- Drop the old config",
+        ));
+}
+
+#[test]
+fn test_changelog_json() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Update the README\n");
+
+    repo.jjka()
+        .args(["changelog", "-r", &commit])
+        .assert()
+        .success()
+        .stdout("No features, fixes or breaking changes in ".to_string() + &commit + "\n");
+
+    let output = repo
+        .jjka()
+        .args(["changelog", "-r", &commit, "--all", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["features"], serde_json::json!([]));
+    assert_eq!(json["other"][0]["commit_id"], commit);
+    assert_eq!(json["other"][0]["description"], "Update the README");
+    assert_eq!(json["other"][0]["type"], "");
+}
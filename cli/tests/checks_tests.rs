@@ -0,0 +1,69 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+
+/// A revision that edits `a.txt` and adds `bad.txt`, on a base. Returns the
+/// base and the revision.
+fn setup(repo: &TestRepo) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n"))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("one\n2\n")), ("bad.txt", Some("x\n"))],
+        "Edit",
+    );
+    (base, commit)
+}
+
+#[test]
+fn test_checks_run_on_each_split_commit() {
+    let repo = TestRepo::init();
+    let (base, commit) = setup(&repo);
+    let log = repo.root().parent().unwrap().join("checked");
+    repo.add_config(&format!(
+        "jjka.checks.command = 'echo \"$JJKA_COMMIT $(git rev-parse HEAD) $(cat a.txt | head -1) $(ls | wc -l)\" >> {}'",
+        log.display()
+    ));
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "-m", "First", "a.txt:1-1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Checking "));
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    let checked = std::fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = checked.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            format!("{} {0} one 1", selected.id().hex()),
+            format!("{} {0} one 2", remaining.id().hex()),
+        ]
+    );
+    // The checkouts are cleaned up.
+    let worktrees = repo.root().join(".jj/repo/store/git/worktrees");
+    assert!(std::fs::read_dir(&worktrees).map_or(true, |mut dir| dir.next().is_none()));
+}
+
+#[test]
+fn test_failing_check_leaves_repo_unchanged() {
+    let repo = TestRepo::init();
+    let (base, commit) = setup(&repo);
+    repo.add_config("jjka.checks.command = ['sh', '-c', 'test ! -e bad.txt']");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "a.txt:1-1"])
+        .assert()
+        .code(3)
+        .stderr(
+            predicate::str::is_match(r"Check failed on \w+ \w+ Edit \(exit status: 1\)").unwrap(),
+        )
+        .stderr(predicate::str::contains(
+            "Checks failed on 1 commit(s); nothing was changed",
+        ));
+    let [child] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(child.id().hex(), commit);
+}
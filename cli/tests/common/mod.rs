@@ -0,0 +1,378 @@
+//! A throwaway jj repository for integration tests, built directly with
+//! jj-lib since the `jj` binary isn't required to run the tests.
+
+#![allow(dead_code)]
+
+use assert_cmd::Command;
+use futures::executor::block_on;
+use jj_lib::backend::{CommitId, CopyId, MergedTreeId, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+use jj_lib::conflicts::{MaterializedTreeValue, materialize_tree_value};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_store::RefTarget;
+use jj_lib::ref_name::RefName;
+use jj_lib::repo::{ReadonlyRepo, Repo, StoreFactories};
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::settings::UserSettings;
+use jj_lib::workspace::{Workspace, default_working_copy_factories};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+pub struct TestRepo {
+    _dir: TempDir,
+    root: PathBuf,
+    config_path: PathBuf,
+}
+
+fn test_settings() -> UserSettings {
+    let mut config = StackedConfig::with_defaults();
+    config.add_layer(
+        ConfigLayer::parse(
+            ConfigSource::User,
+            "user.name = \"Test User\"\nuser.email = \"test.user@example.com\"\n",
+        )
+        .unwrap(),
+    );
+    UserSettings::from_config(config).unwrap()
+}
+
+/// Writes `base_tree_id` with `files` applied (`None` deletes the path).
+fn write_tree(
+    repo: &dyn Repo,
+    base_tree_id: MergedTreeId,
+    files: &[(&str, Option<&str>)],
+    executable: bool,
+) -> MergedTreeId {
+    let store = repo.store();
+    let mut builder = MergedTreeBuilder::new(base_tree_id);
+    for (path, content) in files {
+        let path = RepoPathBuf::from_internal_string(*path).unwrap();
+        let value = content.map(|content| {
+            let id = block_on(store.write_file(&path, &mut content.as_bytes())).unwrap();
+            TreeValue::File {
+                id,
+                executable,
+                copy_id: CopyId::placeholder(),
+            }
+        });
+        builder.set_or_remove(path, Merge::resolved(value));
+    }
+    builder.write_tree(store).unwrap()
+}
+
+impl TestRepo {
+    /// Initializes an empty repo backed by an internal git store.
+    pub fn init() -> Self {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("repo");
+        std::fs::create_dir(&root).unwrap();
+        Workspace::init_internal_git(&test_settings(), &root).unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "").unwrap();
+        TestRepo {
+            _dir: dir,
+            root,
+            config_path,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Appends TOML to the user config seen by `jjka`.
+    pub fn add_config(&self, toml: &str) {
+        let mut config = std::fs::read_to_string(&self.config_path).unwrap();
+        config.push_str(toml);
+        config.push('\n');
+        std::fs::write(&self.config_path, config).unwrap();
+    }
+
+    /// Generates an SSH key pair named `name` next to the repo, returning
+    /// the path of its private half.
+    pub fn ssh_key(&self, name: &str) -> PathBuf {
+        let key = self.config_path.with_file_name(name);
+        let status = std::process::Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", name, "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        key
+    }
+
+    /// A `jjka` command running in the repo with hermetic config.
+    pub fn jjka(&self) -> Command {
+        Command::from_std(self.jjka_std())
+    }
+
+    /// Like [`TestRepo::jjka`], for tests that need to spawn the process.
+    pub fn jjka_std(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin("jjka"));
+        cmd.current_dir(&self.root)
+            .env("JJ_CONFIG", &self.config_path)
+            .env("JJ_USER", "Test User")
+            .env("JJ_EMAIL", "test.user@example.com");
+        cmd
+    }
+
+    pub fn repo(&self) -> Arc<ReadonlyRepo> {
+        let workspace = Workspace::load(
+            &test_settings(),
+            &self.root,
+            &StoreFactories::default(),
+            &default_working_copy_factories(),
+        )
+        .unwrap();
+        workspace.repo_loader().load_at_head().unwrap()
+    }
+
+    pub fn root_commit_id(&self) -> String {
+        self.repo().store().root_commit_id().hex()
+    }
+
+    pub fn wc_commit(&self) -> Commit {
+        let repo = self.repo();
+        let id = repo.view().wc_commit_ids().values().next().unwrap().clone();
+        repo.store().get_commit(&id).unwrap()
+    }
+
+    pub fn commit(&self, hex: &str) -> Commit {
+        let repo = self.repo();
+        let id = CommitId::try_from_hex(hex).unwrap();
+        repo.store().get_commit(&id).unwrap()
+    }
+
+    /// Creates a commit on top of `parents`, applying `files` to the first
+    /// parent's tree (`None` deletes the path). Returns the commit id hex.
+    pub fn create_commit(
+        &self,
+        parents: &[&str],
+        files: &[(&str, Option<&str>)],
+        description: &str,
+    ) -> String {
+        self.create_commit_with_mode(parents, files, false, description)
+    }
+
+    /// Like [`TestRepo::create_commit`], writing `files` with the given
+    /// executable bit.
+    pub fn create_commit_with_mode(
+        &self,
+        parents: &[&str],
+        files: &[(&str, Option<&str>)],
+        executable: bool,
+        description: &str,
+    ) -> String {
+        let repo = self.repo();
+        let parent_ids: Vec<CommitId> = parents
+            .iter()
+            .map(|hex| CommitId::try_from_hex(hex).unwrap())
+            .collect();
+        let base_tree_id = repo
+            .store()
+            .get_commit(&parent_ids[0])
+            .unwrap()
+            .tree_id()
+            .clone();
+        let tree_id = write_tree(repo.as_ref(), base_tree_id, files, executable);
+
+        let mut tx = repo.start_transaction();
+        let commit = tx
+            .repo_mut()
+            .new_commit(parent_ids, tree_id)
+            .set_description(description)
+            .write()
+            .unwrap();
+        tx.commit("create test commit").unwrap();
+        commit.id().hex()
+    }
+
+    /// Creates a commit on `parent` that points the submodule at `path` to
+    /// the commit `target` (`None` removes it) and applies `files`. Returns
+    /// the commit id hex.
+    pub fn create_submodule_commit(
+        &self,
+        parent: &str,
+        path: &str,
+        target: Option<&str>,
+        files: &[(&str, Option<&str>)],
+        description: &str,
+    ) -> String {
+        let repo = self.repo();
+        let parent = self.commit(parent);
+        let base_tree_id = write_tree(repo.as_ref(), parent.tree_id().clone(), files, false);
+        let mut builder = MergedTreeBuilder::new(base_tree_id);
+        let value = target.map(|hex| TreeValue::GitSubmodule(CommitId::try_from_hex(hex).unwrap()));
+        builder.set_or_remove(
+            RepoPathBuf::from_internal_string(path).unwrap(),
+            Merge::resolved(value),
+        );
+        let tree_id = builder.write_tree(repo.store()).unwrap();
+
+        let mut tx = repo.start_transaction();
+        let commit = tx
+            .repo_mut()
+            .new_commit(vec![parent.id().clone()], tree_id)
+            .set_description(description)
+            .write()
+            .unwrap();
+        tx.commit("create test commit").unwrap();
+        commit.id().hex()
+    }
+
+    /// The commit the submodule at `path` points to in the commit's tree.
+    pub fn submodule_target(&self, commit: &Commit, path: &str) -> Option<String> {
+        let path = RepoPathBuf::from_internal_string(path).unwrap();
+        match commit
+            .tree()
+            .unwrap()
+            .path_value(&path)
+            .unwrap()
+            .into_resolved()
+        {
+            Ok(Some(TreeValue::GitSubmodule(id))) => Some(id.hex()),
+            _ => None,
+        }
+    }
+
+    /// Creates a second visible copy of `hex` with the same change id and
+    /// parents, applying `files` to its tree, as a concurrent rewrite would.
+    /// The copy is committed a second later. Returns the copy's id hex.
+    pub fn create_divergent_copy(
+        &self,
+        hex: &str,
+        files: &[(&str, Option<&str>)],
+        description: &str,
+    ) -> String {
+        let repo = self.repo();
+        let original = self.commit(hex);
+        let tree_id = write_tree(repo.as_ref(), original.tree_id().clone(), files, false);
+        let mut committer = original.committer().clone();
+        committer.timestamp.timestamp.0 += 1000;
+
+        let mut tx = repo.start_transaction();
+        let commit = tx
+            .repo_mut()
+            .new_commit(original.parent_ids().to_vec(), tree_id)
+            .set_change_id(original.change_id().clone())
+            .set_committer(committer)
+            .set_description(description)
+            .write()
+            .unwrap();
+        tx.commit("create divergent test commit").unwrap();
+        commit.id().hex()
+    }
+
+    /// Rewrites `hex` in place with `files` applied to its tree, as an amend
+    /// would, keeping its change id. Returns the new commit id hex.
+    pub fn amend(&self, hex: &str, files: &[(&str, Option<&str>)]) -> String {
+        let repo = self.repo();
+        let original = self.commit(hex);
+        let tree_id = write_tree(repo.as_ref(), original.tree_id().clone(), files, false);
+
+        let mut tx = repo.start_transaction();
+        let commit = tx
+            .repo_mut()
+            .rewrite_commit(&original)
+            .set_tree_id(tree_id)
+            .write()
+            .unwrap();
+        tx.repo_mut().rebase_descendants().unwrap();
+        tx.commit("amend test commit").unwrap();
+        commit.id().hex()
+    }
+
+    /// Copies the changes of `source` onto `onto` like `jj duplicate -d`,
+    /// keeping conflicts in the tree. Returns the new commit id hex.
+    pub fn cherry_pick(&self, source: &str, onto: &str, description: &str) -> String {
+        let repo = self.repo();
+        let source = self.commit(source);
+        let onto = self.commit(onto);
+        let tree = block_on(onto.tree().unwrap().merge(
+            source.parent_tree(repo.as_ref()).unwrap(),
+            source.tree().unwrap(),
+        ))
+        .unwrap();
+        let mut tx = repo.start_transaction();
+        let commit = tx
+            .repo_mut()
+            .new_commit(vec![onto.id().clone()], tree.id())
+            .set_description(description)
+            .write()
+            .unwrap();
+        tx.commit("cherry-pick test commit").unwrap();
+        commit.id().hex()
+    }
+
+    pub fn set_bookmark(&self, name: &str, hex: &str) {
+        let repo = self.repo();
+        let mut tx = repo.start_transaction();
+        tx.repo_mut().set_local_bookmark_target(
+            RefName::new(name),
+            RefTarget::normal(CommitId::try_from_hex(hex).unwrap()),
+        );
+        tx.commit("set test bookmark").unwrap();
+    }
+
+    pub fn bookmark_commit(&self, name: &str) -> Option<Commit> {
+        let repo = self.repo();
+        let target = repo.view().get_local_bookmark(RefName::new(name)).clone();
+        target
+            .as_normal()
+            .map(|id| repo.store().get_commit(id).unwrap())
+    }
+
+    /// Whether `path` is an executable file in the commit's tree.
+    pub fn is_executable(&self, commit: &Commit, path: &str) -> bool {
+        let path = RepoPathBuf::from_internal_string(path).unwrap();
+        matches!(
+            commit
+                .tree()
+                .unwrap()
+                .path_value(&path)
+                .unwrap()
+                .into_resolved(),
+            Ok(Some(TreeValue::File {
+                executable: true,
+                ..
+            }))
+        )
+    }
+
+    /// Reads a file from a commit's tree, `None` if absent.
+    pub fn file_content(&self, commit: &Commit, path: &str) -> Option<String> {
+        let repo = self.repo();
+        let path = RepoPathBuf::from_internal_string(path).unwrap();
+        let value = commit.tree().unwrap().path_value(&path).unwrap();
+        match block_on(materialize_tree_value(repo.store(), &path, value)).unwrap() {
+            MaterializedTreeValue::File(mut file) => {
+                Some(String::from_utf8(block_on(file.read_all(&path)).unwrap()).unwrap())
+            }
+            MaterializedTreeValue::Absent => None,
+            _ => panic!("{} is not a regular file", path.as_internal_file_string()),
+        }
+    }
+
+    /// Visible commits that have `parent` as a parent.
+    pub fn children(&self, parent: &Commit) -> Vec<Commit> {
+        let repo = self.repo();
+        let mut children = Vec::new();
+        let mut pending: Vec<CommitId> = repo.view().heads().iter().cloned().collect();
+        let mut seen = std::collections::HashSet::new();
+        while let Some(id) = pending.pop() {
+            if !seen.insert(id.clone()) || id == *repo.store().root_commit_id() {
+                continue;
+            }
+            let commit = repo.store().get_commit(&id).unwrap();
+            if commit.parent_ids().contains(parent.id()) {
+                children.push(commit.clone());
+            }
+            pending.extend(commit.parent_ids().iter().cloned());
+        }
+        children
+    }
+}
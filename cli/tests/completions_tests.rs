@@ -0,0 +1,75 @@
+mod common;
+
+use common::TestRepo;
+
+/// The candidates `jjka __complete` offers for `words`, without
+/// descriptions.
+fn complete(repo: &TestRepo, words: &[&str]) -> Vec<String> {
+    let output = repo
+        .jjka()
+        .arg("__complete")
+        .arg("--")
+        .args(words)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split('\t').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_complete_revisions_and_ranges() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("a\nb\nc\n")), ("b.txt", Some("b\n"))],
+        "Add files",
+    );
+    let edit = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("A\nb\nC\n")), ("b.txt", Some("B\n"))],
+        "Edit files",
+    );
+    repo.set_bookmark("feature", &edit);
+    repo.set_bookmark("fix", &base);
+
+    assert_eq!(complete(&repo, &["hunksplit", "-r", "fe"]), ["feature"]);
+    assert_eq!(
+        complete(&repo, &["copy-hunks", "--revision=trunk()..fi"]),
+        ["--revision=trunk()..fix"]
+    );
+    let change_id = repo.commit(&edit).change_id().reverse_hex();
+    assert!(complete(&repo, &["tidy", "-r", ""]).contains(&change_id[..12].to_string()));
+
+    assert_eq!(
+        complete(&repo, &["hunksplit", "-r", "feature", ""]),
+        ["a.txt:", "b.txt:"]
+    );
+    // Once the word names one file, its hunks are offered.
+    assert_eq!(
+        complete(&repo, &["hunksplit", "-r", "feature", "a"]),
+        ["a.txt:1-1", "a.txt:3-3"]
+    );
+    assert_eq!(
+        complete(&repo, &["copy-hunks", "-r", "fix", "b.txt:"]),
+        ["b.txt:1-1"]
+    );
+
+    assert_eq!(complete(&repo, &["lint", "--format", ""]), ["text", "json"]);
+    assert!(complete(&repo, &["megam"]).contains(&"megamerge".to_string()));
+}
+
+#[test]
+fn test_completion_scripts() {
+    let repo = TestRepo::init();
+    for shell in ["bash", "zsh", "fish"] {
+        let output = repo.jjka().args(["completions", shell]).output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.contains("jjka __complete --"), "{script}");
+    }
+}
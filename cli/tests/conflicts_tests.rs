@@ -0,0 +1,98 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_conflicts_lists_sides_and_origins() {
+    let repo = TestRepo::init();
+    let base = repo.create_commit(&[&repo.root_commit_id()], &[("a.txt", Some("1\n"))], "Base");
+    let left = repo.create_commit(&[&base], &[("a.txt", Some("2\n"))], "Left");
+    let right = repo.create_commit(&[&base], &[("a.txt", Some("3\n"))], "Right");
+    let picked = repo.cherry_pick(&right, &left, "Pick right");
+    let child = repo.create_commit(&[&picked], &[("b.txt", Some("b\n"))], "Child");
+
+    let base_summary = summary(&repo, &base);
+    let left_summary = summary(&repo, &left);
+    repo.jjka()
+        .args(["conflicts", "-r", &format!("::{child}")])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{}\n  a.txt  2-sided conflict (new)\n    side 1: {left_summary}\n    base 1: {base_summary}\n    side 2: (not found in ancestors)\n",
+            summary(&repo, &picked)
+        )))
+        .stdout(predicate::str::contains(format!(
+            "{}\n  a.txt  2-sided conflict (inherited)\n",
+            summary(&repo, &child)
+        )))
+        .stdout(predicate::str::contains("2 conflicted path(s) in 2 revision(s)"));
+}
+
+#[test]
+fn test_conflicts_none() {
+    let repo = TestRepo::init();
+    let commit = repo.create_commit(&[&repo.root_commit_id()], &[("a.txt", Some("a\n"))], "A");
+
+    repo.jjka()
+        .args(["conflicts", "-r", &commit])
+        .assert()
+        .success()
+        .stdout("No conflicts\n");
+}
+
+#[test]
+fn test_conflicts_json() {
+    let repo = TestRepo::init();
+    let base = repo.create_commit(&[&repo.root_commit_id()], &[("a.txt", Some("1\n"))], "Base");
+    let left = repo.create_commit(&[&base], &[("a.txt", Some("2\n"))], "Left");
+    let right = repo.create_commit(&[&base], &[("a.txt", Some("3\n"))], "Right");
+    let picked = repo.cherry_pick(&right, &left, "Pick right");
+
+    let output = repo
+        .jjka()
+        .args(["--format", "json", "conflicts", "-r", &picked])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let commit = &report["commits"][0];
+    assert_eq!(commit["commit_id"], picked);
+    assert_eq!(commit["subject"], "Pick right");
+    let path = &commit["paths"][0];
+    assert_eq!(path["path"], "a.txt");
+    assert_eq!(path["sides"], 2);
+    assert_eq!(path["inherited"], false);
+    assert_eq!(path["adds"][0]["introduced_by"]["commit_id"], left);
+    assert_eq!(path["removes"][0]["introduced_by"]["commit_id"], base);
+    assert_eq!(path["adds"][1]["introduced_by"], serde_json::Value::Null);
+}
+
+#[test]
+fn test_conflicts_nul_terminated_paths() {
+    let repo = TestRepo::init();
+    let files = |content| [("a b.txt", Some(content)), ("c.txt", Some(content))];
+    let base = repo.create_commit(&[&repo.root_commit_id()], &files("1\n"), "Base");
+    let left = repo.create_commit(&[&base], &files("2\n"), "Left");
+    let right = repo.create_commit(&[&base], &files("3\n"), "Right");
+    let picked = repo.cherry_pick(&right, &left, "Pick right");
+    let child = repo.create_commit(&[&picked], &[("d.txt", Some("d\n"))], "Child");
+
+    let output = repo
+        .jjka()
+        .args(["conflicts", "-z", "-r", &format!("{picked}::{child}")])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"a b.txt\0c.txt\0");
+}
+
+fn summary(repo: &TestRepo, hex: &str) -> String {
+    let commit = repo.commit(hex);
+    let description = commit.description().lines().next().unwrap().to_string();
+    format!(
+        "{} {} {description}",
+        &commit.change_id().reverse_hex()[..12],
+        &hex[..12]
+    )
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_copy_hunks_into_working_copy() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("one\ntwo\nthree\n"))], "Base");
+    let other = repo.create_commit(
+        &[&base],
+        &[
+            ("a.txt", Some("one\nTWO\nthree\nfour\n")),
+            ("b.txt", Some("new file\nsecond line\n")),
+        ],
+        "Other",
+    );
+    std::fs::write(repo.root().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    repo.jjka()
+        .args(["copy-hunks", "-r", &other, "a.txt:1-1"])
+        .assert()
+        .success()
+        .stderr("Nothing to copy: the selected lines are the same in the working copy\n");
+
+    repo.jjka()
+        .args(["copy-hunks", "-r", &other, "a.txt:4-4", "b.txt:2-2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Copied lines from"))
+        .stdout(predicate::str::contains("  a.txt +1 -0"))
+        .stdout(predicate::str::contains("  b.txt +1 -0"));
+    assert_eq!(
+        std::fs::read_to_string(repo.root().join("a.txt")).unwrap(),
+        "one\ntwo\nthree\nfour\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo.root().join("b.txt")).unwrap(),
+        "second line\n"
+    );
+    // The source revision is untouched.
+    assert_eq!(
+        repo.file_content(&repo.commit(&other), "a.txt").as_deref(),
+        Some("one\nTWO\nthree\nfour\n")
+    );
+
+    repo.jjka()
+        .args(["copy-hunks", "-r", &base, "b.txt:1-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("b.txt doesn't exist in"));
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+
+/// Configures an editor that saves what it was given to `seen.txt` and
+/// runs `script` on the file.
+fn set_editor(repo: &TestRepo, script: &str) -> std::path::PathBuf {
+    let dir = repo.root().parent().unwrap();
+    let seen = dir.join("seen.txt");
+    let editor = dir.join("editor.sh");
+    std::fs::write(
+        &editor,
+        format!("#!/bin/sh\ncp \"$1\" '{}'\n{script}\n", seen.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+    repo.add_config(&format!("ui.editor = '{}'", editor.display()));
+    seen
+}
+
+#[test]
+fn test_describe_from_template() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(
+        &[&root],
+        &[
+            ("src/parser/lex.rs", Some("lex\n")),
+            ("src/parser/ast.rs", Some("ast\n")),
+        ],
+        "",
+    );
+    repo.set_bookmark("ABC-12-unary-minus", &commit);
+    let seen = set_editor(&repo, "sed -i '1s/$/ Handle unary minus/' \"$1\"");
+
+    repo.jjka()
+        .args(["describe", "-r", &commit])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Described"))
+        .stdout(predicate::str::contains("feat(parser): Handle unary minus"));
+    assert_eq!(
+        std::fs::read_to_string(seen).unwrap(),
+        "feat(parser):\n\nJJ: Describe the change above. Empty trailers below are removed.\nBREAKING CHANGE:\nRefs: ABC-12\n"
+    );
+    let described = repo.bookmark_commit("ABC-12-unary-minus").unwrap();
+    assert_eq!(
+        described.description(),
+        "feat(parser): Handle unary minus\n\nRefs: ABC-12\n"
+    );
+}
+
+#[test]
+fn test_describe_with_configured_template() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("docs/guide.md", Some("guide\n"))], "");
+    repo.set_bookmark("docs", &commit);
+    set_editor(&repo, "true");
+    repo.add_config("[jjka.describe]\ntemplate = \"{type}: {scope}\"");
+
+    repo.jjka()
+        .args(["describe", "-r", &commit])
+        .assert()
+        .success();
+    assert_eq!(
+        repo.bookmark_commit("docs").unwrap().description(),
+        "docs: (docs)\n"
+    );
+
+    let repo = TestRepo::init();
+    set_editor(&repo, "true");
+    repo.jjka()
+        .args(["describe"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("The subject line is unfinished"));
+}
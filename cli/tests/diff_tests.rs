@@ -0,0 +1,265 @@
+mod common;
+
+use common::TestRepo;
+use serde_json::{Value, json};
+
+#[test]
+fn test_diff_highlights_changed_words() {
+    let repo = TestRepo::init();
+    repo.add_config("jjka.diff.theme = \"none\"");
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("a.rs", Some("let total = price * count;\nend\n"))],
+        "Add a",
+    );
+    let edit = repo.create_commit(
+        &[&base],
+        &[("a.rs", Some("let total = price * quantity;\nend\n"))],
+        "Rename count",
+    );
+
+    let output = repo
+        .jjka()
+        .args(["diff", "-r", &edit, "--color", "always"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("\x1b[31m-let total = price * \x1b[7mcount\x1b[27m;\x1b[0m"),
+        "{stdout:?}"
+    );
+    assert!(
+        stdout.contains("\x1b[32m+let total = price * \x1b[7mquantity\x1b[27m;\x1b[0m"),
+        "{stdout:?}"
+    );
+
+    let output = repo
+        .jjka()
+        .args(["diff", "-r", &edit, "--color", "never"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "--- a/a.rs\n+++ b/a.rs\n@@ -1,2 +1,2 @@\n-let total = price * count;\n+let total = price * quantity;\n end\n"
+    );
+}
+
+#[test]
+fn test_diff_highlights_syntax() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.rs", Some("let n = 1;\n"))], "Add a");
+    let edit = repo.create_commit(
+        &[&base],
+        &[
+            ("a.rs", Some("let n = 2; // two\n")),
+            ("a.txt", Some("let\n")),
+        ],
+        "Edit a",
+    );
+
+    let output = repo
+        .jjka()
+        .args(["diff", "-r", &edit, "--color", "always"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // The keyword, number and comment are colored, and the changed words
+    // are still in reverse video.
+    assert!(
+        stdout.contains("\x1b[32m+\x1b[0m\x1b[32m\x1b[35mlet\x1b[0m\x1b[32m n = \x1b[0m"),
+        "{stdout:?}"
+    );
+    assert!(
+        stdout.contains("\x1b[32m\x1b[7m\x1b[36m2\x1b[0m"),
+        "{stdout:?}"
+    );
+    assert!(
+        stdout.contains("\x1b[7m\x1b[90m// two\x1b[0m"),
+        "{stdout:?}"
+    );
+    // Files in unknown languages are only colored by the diff.
+    assert!(stdout.contains("\x1b[32m+let\x1b[0m"), "{stdout:?}");
+
+    repo.add_config("jjka.diff.theme = \"mauve\"");
+    let output = repo
+        .jjka()
+        .args(["diff", "-r", &edit, "--color", "always"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown jjka.diff.theme \"mauve\""));
+}
+
+#[test]
+fn test_diff_lines_previews_split() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\nb\nc\n"))], "Add a");
+    let edit = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("A\nb\nC\n")), ("b.txt", Some("new\n"))],
+        "Edit",
+    );
+
+    let output = repo
+        .jjka()
+        .args([
+            "diff",
+            "-r",
+            &edit,
+            "--lines",
+            "a.txt:3-3",
+            "--format",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let files: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(files.as_array().unwrap().len(), 1);
+    assert_eq!(files[0]["path"], "a.txt");
+    assert_eq!(
+        files[0]["hunks"][0]["lines"],
+        json!([
+            { "kind": "context", "text": "a", "segments": [{ "text": "a", "changed": false }] },
+            { "kind": "context", "text": "b", "segments": [{ "text": "b", "changed": false }] },
+            { "kind": "removed", "text": "c", "segments": [{ "text": "c", "changed": false }] },
+            { "kind": "added", "text": "C", "segments": [{ "text": "C", "changed": false }] },
+        ])
+    );
+
+    let output = repo
+        .jjka()
+        .args(["diff", "-r", &edit, "b.txt", "--color", "never"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "--- /dev/null\n+++ b/b.txt\n@@ -0,0 +1,1 @@\n+new\n"
+    );
+}
+
+#[test]
+fn test_diff_color_follows_ui_color() {
+    let repo = TestRepo::init();
+    repo.add_config("ui.color = \"always\"\njjka.diff.theme = \"none\"");
+    let root = repo.root_commit_id();
+    let edit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+
+    let output = repo.jjka().args(["diff", "-r", &edit]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\x1b[32m+a\x1b[0m"), "{stdout:?}");
+
+    // The global flag wins, wherever it's passed.
+    let output = repo
+        .jjka()
+        .args(["--color", "never", "diff", "-r", &edit])
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8(output.stdout).unwrap().contains('\x1b'));
+
+    let repo = TestRepo::init();
+    repo.add_config("ui.color = \"sometimes\"");
+    repo.jjka()
+        .args(["diff"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid ui.color \"sometimes\""));
+}
+
+/// Runs jjka with `args` on a pseudo-terminal, as pagers only start when
+/// stdout is a terminal.
+fn jjka_on_terminal(repo: &TestRepo, args: &str) {
+    let jjka = repo.jjka_std();
+    let mut script = std::process::Command::new("script");
+    script
+        .args(["-qec"])
+        .arg(format!("{} {args}", jjka.get_program().to_str().unwrap()))
+        .arg("/dev/null")
+        .current_dir(repo.root())
+        .stdin(std::process::Stdio::null());
+    for (key, value) in jjka.get_envs() {
+        if let Some(value) = value {
+            script.env(key, value);
+        }
+    }
+    let output = script.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn test_diff_pager_gets_git_diff() {
+    let repo = TestRepo::init();
+    let out = repo.root().parent().unwrap().join("paged");
+    repo.add_config(&format!(
+        "ui.pager = ['sh', '-c', 'cat > {0}.plain']\njjka.diff.pager = ['sh', '-c', 'cat > {0}.diff']",
+        out.display()
+    ));
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.rs", Some("let n = 1;\n"))], "Add a");
+    let edit = repo.create_commit(
+        &[&base],
+        &[("a.rs", Some("let n = 2;\n")), ("b.bin", Some("\0\u{1}\n"))],
+        "Edit a",
+    );
+
+    jjka_on_terminal(&repo, &format!("diff -r {edit}"));
+    assert_eq!(
+        std::fs::read_to_string(out.with_extension("diff")).unwrap(),
+        "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1,1 +1,1 @@\n-let n = 1;\n+let n = 2;\n\
+         diff --git a/b.bin b/b.bin\nBinary files /dev/null and b/b.bin differ\n"
+    );
+    assert!(!out.with_extension("plain").exists());
+}
+
+#[test]
+fn test_diff_without_diff_pager_keeps_colors() {
+    let repo = TestRepo::init();
+    let out = repo.root().parent().unwrap().join("paged");
+    repo.add_config(&format!(
+        "jjka.diff.theme = 'none'\nui.pager = ['sh', '-c', 'cat > {}']",
+        out.display()
+    ));
+    let root = repo.root_commit_id();
+    let edit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+
+    // Without jjka.diff.pager, diffs go to ui.pager with jjka's own colors.
+    jjka_on_terminal(&repo, &format!("diff -r {edit}"));
+    let paged = std::fs::read_to_string(&out).unwrap();
+    assert!(paged.contains("\x1b[32m+a\x1b[0m"), "{paged:?}");
+    assert!(!paged.contains("diff --git"), "{paged:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_diff_stops_quietly_when_the_pipe_closes() {
+    use std::io::{BufRead as _, BufReader};
+    use std::os::unix::process::ExitStatusExt as _;
+    use std::process::Stdio;
+
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    // More than a pipe holds, so jjka is still writing when the reader goes.
+    let content: String = (1..=20_000).map(|n| format!("line {n}\n")).collect();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some(&content))], "Add a");
+
+    let mut child = repo
+        .jjka_std()
+        .args(["diff", "-r", &commit])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first_line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first_line)
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.signal(), Some(libc::SIGPIPE), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+
+/// A base with a five-line file, a source commit on it changing the first
+/// and last lines, and a destination branch changing it as `destination`
+/// has it. Returns the source and destination commits.
+fn setup(repo: &TestRepo, destination: &str) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n5\n"))], "Base");
+    let source = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("one\n2\n3\n4\nfive\n"))],
+        "Spell out numbers",
+    );
+    let destination = repo.create_commit(&[&base], &[("a.txt", Some(destination))], "Release");
+    (source, destination)
+}
+
+#[test]
+fn test_duplicate_partial_copies_the_selected_lines() {
+    let repo = TestRepo::init();
+    let (source, destination) = setup(&repo, "1\n2\nthree\n4\n5\n");
+
+    repo.jjka()
+        .args([
+            "duplicate-partial",
+            "-r",
+            &source,
+            "--onto",
+            &destination,
+            "a.txt:1-1",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"Duplicated lines of \w+ \w+ Spell out numbers as \w+ \w+ Spell out numbers\n",
+            )
+            .unwrap(),
+        );
+    let [copy] = repo
+        .children(&repo.commit(&destination))
+        .try_into()
+        .unwrap();
+    assert_eq!(copy.description(), "Spell out numbers");
+    assert_eq!(
+        repo.file_content(&copy, "a.txt").as_deref(),
+        Some("one\n2\nthree\n4\n5\n")
+    );
+    // The source is still there, unchanged.
+    repo.jjka()
+        .args([
+            "duplicate-partial",
+            "-r",
+            &source,
+            "--onto",
+            &destination,
+            "a.txt:5-5",
+        ])
+        .assert()
+        .success();
+    assert_eq!(repo.commit(&source).id().hex(), source);
+    assert_eq!(repo.children(&repo.commit(&destination)).len(), 2);
+}
+
+#[test]
+fn test_duplicate_partial_message_and_conflicts() {
+    let repo = TestRepo::init();
+    let (source, destination) = setup(&repo, "uno\n2\n3\n4\n5\n");
+
+    repo.jjka()
+        .args([
+            "duplicate-partial",
+            "-r",
+            &source,
+            "--onto",
+            &destination,
+            "-m",
+            "Backport the first line",
+            "a.txt:1-1",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "has conflicts; the lines don't apply cleanly onto",
+        ));
+    let [copy] = repo
+        .children(&repo.commit(&destination))
+        .try_into()
+        .unwrap();
+    assert_eq!(copy.description(), "Backport the first line");
+    assert!(copy.has_conflict().unwrap());
+
+    repo.jjka()
+        .args([
+            "duplicate-partial",
+            "-r",
+            &source,
+            "--onto",
+            &destination,
+            "a.txt:2-3",
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The selected lines aren't changed in",
+        ));
+}
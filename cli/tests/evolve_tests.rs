@@ -0,0 +1,70 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use predicates::prelude::*;
+
+#[test]
+fn test_evolve_rebases_orphans() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let old = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    let child = repo.create_commit(&[&old], &[("b.txt", Some("b\n"))], "Add b");
+    let new = repo.create_divergent_copy(&old, &[("a.txt", Some("A\n"))], "Add a");
+
+    repo.jjka()
+        .arg("evolve")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("has 2 copies"))
+        .stdout(predicate::str::contains("Rebased orphan"))
+        .stdout(predicate::str::contains("Kept 1 older copies"));
+
+    let [rebased] = repo.children(&repo.commit(&new)).try_into().unwrap();
+    assert_eq!(rebased.change_id(), repo.commit(&child).change_id());
+    assert_eq!(repo.file_content(&rebased, "a.txt").as_deref(), Some("A\n"));
+    assert_eq!(repo.file_content(&rebased, "b.txt").as_deref(), Some("b\n"));
+    // The older copy stays until it's abandoned or merged.
+    assert!(repo.children(&repo.commit(&old)).is_empty());
+    assert!(!repo.commit(&old).is_hidden(repo.repo().as_ref()));
+}
+
+#[test]
+fn test_evolve_merges_divergent_copies() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let old = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("a\n")), ("c.txt", Some("c\n"))],
+        "Add a",
+    );
+    let child = repo.create_commit(&[&old], &[("b.txt", Some("b\n"))], "Add b");
+    // The copies were edited differently: one added d.txt and dropped c.txt.
+    let new = repo.create_divergent_copy(&old, &[("c.txt", None), ("d.txt", Some("d\n"))], "");
+
+    repo.jjka()
+        .args(["evolve", "--divergent", "merge"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Merged"));
+
+    let ids = repo
+        .repo()
+        .resolve_change_id(repo.commit(&new).change_id())
+        .unwrap();
+    let [merged] = ids.try_into().unwrap();
+    let merged = repo.commit(&merged.hex());
+    assert_eq!(merged.description(), "Add a");
+    assert!(!merged.has_conflict().unwrap());
+    assert_eq!(repo.file_content(&merged, "c.txt").as_deref(), Some("c\n"));
+    assert_eq!(repo.file_content(&merged, "d.txt").as_deref(), Some("d\n"));
+    let [rebased] = repo.children(&merged).try_into().unwrap();
+    assert_eq!(rebased.change_id(), repo.commit(&child).change_id());
+
+    repo.jjka()
+        .arg("evolve")
+        .assert()
+        .success()
+        .stderr("Nothing to evolve: no divergent changes in mutable()\n");
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_usage_errors_exit_with_2() {
+    let repo = TestRepo::init();
+    repo.jjka()
+        .args(["hunksplit", "a.txt:10"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with("Error: Invalid range format"));
+    repo.jjka()
+        .args(["stats", "-r", "nonexistent-bookmark"])
+        .assert()
+        .code(2);
+    repo.jjka()
+        .args(["stats", "-r", "(("])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Failed to parse revset"));
+}
+
+#[test]
+fn test_precondition_and_check_failures() {
+    let repo = TestRepo::init();
+    let outside = tempfile::tempdir().unwrap();
+    repo.jjka()
+        .args(["stats"])
+        .current_dir(outside.path())
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("There is no jj repo in"));
+
+    repo.jjka()
+        .args(["assert", "revset-is-empty(root())"])
+        .assert()
+        .code(1);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use serde_json::Value;
+
+#[test]
+fn test_filter_removes_path_and_replaces_text() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(
+        &[&root],
+        &[
+            ("secrets.env", Some("TOKEN=abc123\n")),
+            ("config.toml", Some("password = \"hunter2\"\n")),
+        ],
+        "Add config",
+    );
+    let second = repo.create_commit(
+        &[&first],
+        &[("config.toml", Some("password = \"hunter2\"\nport = 80\n"))],
+        "Set port",
+    );
+    repo.set_bookmark("main", &second);
+    let rules = repo.root().parent().unwrap().join("rules.txt");
+    std::fs::write(&rules, "hunter2==>REDACTED\n").unwrap();
+
+    let output = repo
+        .jjka()
+        .args(["filter", "--remove-path", "secrets.env", "--replace-text"])
+        .arg(&rules)
+        .args(["-r", "::main", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let rewrites = &report["commits"];
+    let old_ids: Vec<&str> = rewrites
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|rewrite| rewrite["old_commit_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(old_ids, [first.as_str(), second.as_str()]);
+
+    let main = repo.bookmark_commit("main").unwrap();
+    assert_eq!(rewrites[1]["new_commit_id"], main.id().hex());
+    assert_eq!(main.change_id(), repo.commit(&second).change_id());
+    assert_eq!(repo.file_content(&main, "secrets.env"), None);
+    assert_eq!(
+        repo.file_content(&main, "config.toml").unwrap(),
+        "password = \"REDACTED\"\nport = 80\n"
+    );
+    let parent = repo.commit(&main.parent_ids()[0].hex());
+    assert_eq!(parent.change_id(), repo.commit(&first).change_id());
+    assert_eq!(repo.file_content(&parent, "secrets.env"), None);
+    assert_eq!(
+        repo.file_content(&parent, "config.toml").unwrap(),
+        "password = \"REDACTED\"\n"
+    );
+}
+
+#[test]
+fn test_filter_nothing_to_do() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    repo.set_bookmark("main", &commit);
+
+    let output = repo.jjka().arg("filter").output().unwrap();
+    assert!(!output.status.success());
+
+    let output = repo
+        .jjka()
+        .args(["filter", "--remove-path", "secrets.env"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("Nothing to filter")
+    );
+    assert_eq!(repo.bookmark_commit("main").unwrap().id().hex(), commit);
+
+    let output = repo
+        .jjka()
+        .args(["filter", "--remove-path", "."])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
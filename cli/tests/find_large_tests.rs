@@ -0,0 +1,74 @@
+mod common;
+
+use common::TestRepo;
+use serde_json::Value;
+
+#[test]
+fn test_find_large_reports_versions_and_paths() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let big = "x".repeat(3000);
+    let bigger = "y".repeat(5000);
+    let first = repo.create_commit(
+        &[&root],
+        &[("assets/blob.bin", Some(&big)), ("small.txt", Some("hi\n"))],
+        "Add blob",
+    );
+    let second = repo.create_commit(
+        &[&first],
+        &[("assets/blob.bin", Some(&bigger))],
+        "Grow blob",
+    );
+    // Restoring an old version doesn't count it twice.
+    let third = repo.create_commit(&[&second], &[("assets/blob.bin", Some(&big))], "Revert");
+    repo.set_bookmark("main", &third);
+
+    let output = repo
+        .jjka()
+        .args(["find-large", "--threshold", "2K", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["threshold"], 2048);
+    let files = report["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0]["size"], 5000);
+    assert_eq!(files[0]["commit_id"], second);
+    assert_eq!(files[1]["size"], 3000);
+    assert_eq!(files[1]["commit_id"], first);
+    assert_eq!(report["paths"][0]["path"], "assets/blob.bin");
+    assert_eq!(report["paths"][0]["versions"], 2);
+    assert_eq!(report["paths"][0]["total_size"], 8000);
+
+    let output = repo
+        .jjka()
+        .args(["find-large", "--threshold", "2K"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("git filter-repo --invert-paths --path 'assets/blob.bin'"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_find_large_nothing_found() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+
+    let output = repo.jjka().arg("find-large").output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "No files of at least 1.0 MiB in all()\n"
+    );
+    let output = repo
+        .jjka()
+        .args(["find-large", "--threshold", "lots"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
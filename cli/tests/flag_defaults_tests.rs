@@ -0,0 +1,59 @@
+mod common;
+
+use common::TestRepo;
+
+fn two_hunk_edit(repo: &TestRepo) -> String {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("1\n2\n3\n4\n5\n6\n7\n8\n"))],
+        "Add a",
+    );
+    repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("one\n2\n3\n4\n5\n6\n7\neight\n"))],
+        "Edit a",
+    )
+}
+
+#[test]
+fn test_config_sets_option_defaults() {
+    let repo = TestRepo::init();
+    let edit = two_hunk_edit(&repo);
+    repo.add_config("jjka.diff.context = 0");
+
+    let output = repo.jjka().args(["diff", "-r", &edit]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "--- a/a.txt\n+++ b/a.txt\n@@ -1,1 +1,1 @@\n-1\n+one\n@@ -8,1 +8,1 @@\n-8\n+eight\n"
+    );
+
+    // The command line wins over the config.
+    let output = repo
+        .jjka()
+        .args(["diff", "-r", &edit, "--context", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("@@ -1,2 +1,2 @@\n-1\n+one\n 2\n"),
+        "{stdout:?}"
+    );
+}
+
+#[test]
+fn test_config_option_defaults_are_checked() {
+    let repo = TestRepo::init();
+    let edit = two_hunk_edit(&repo);
+    repo.add_config("jjka.diff.context = \"lots\"");
+
+    repo.jjka()
+        .args(["diff", "-r", &edit])
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains(
+            "Invalid option from the jjka.diff config table: invalid value 'lots' for '--context <CONTEXT>'",
+        ));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_from_git_prints_suggestions() {
+    let repo = TestRepo::init();
+
+    repo.jjka()
+        .args(["from-git", "I want to do git add -p"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Instead of `git add -p`:"))
+        .stdout(predicate::str::contains(
+            "1. jjka hunksplit <path:start-end>",
+        ))
+        .stdout(predicate::str::contains("2. jj split -i"));
+}
+
+#[test]
+fn test_from_git_unknown_workflow() {
+    let repo = TestRepo::init();
+
+    repo.jjka()
+        .args(["from-git", "git frobnicate"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No git workflow matches \"git frobnicate\"",
+        ));
+}
+
+#[test]
+fn test_from_git_interactive_runs_suggestion() {
+    let repo = TestRepo::init();
+
+    // Pick "git mergetool" from the list by name, then run `jjka conflicts`.
+    repo.jjka()
+        .arg("from-git")
+        .write_stdin("mergetool\n2\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("What would you do in git?"))
+        .stdout(predicate::str::contains("Running: jjka conflicts\n"))
+        .stdout(predicate::str::contains("No conflicts\n"));
+}
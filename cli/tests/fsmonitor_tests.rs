@@ -0,0 +1,95 @@
+mod common;
+
+use common::TestRepo;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+
+/// PATH with `dir`, where the fake `watchman` is, in front.
+fn path_with(dir: &Path) -> std::ffi::OsString {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::join_paths(std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&path)))
+        .unwrap()
+}
+
+/// A `watchman` that reports the files listed in `$FAKE_WATCHMAN_FILES` as
+/// changed since any clock.
+fn fake_watchman(dir: &Path, root: &Path) {
+    let script = format!(
+        r#"#!/bin/sh
+input=$(cat)
+case "$input" in
+  *watch-project*) echo '{{"watch": "{}"}}' ;;
+  *'["clock"'*) echo '{{"clock": "c:1"}}' ;;
+  *query*) echo "{{\"clock\": \"c:2\", \"is_fresh_instance\": false, \"files\": $FAKE_WATCHMAN_FILES}}" ;;
+esac
+"#,
+        root.display()
+    );
+    let path = dir.join("watchman");
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn test_snapshot_looks_only_at_files_watchman_reports() {
+    let repo = TestRepo::init();
+    repo.add_config("fsmonitor.backend = \"watchman\"");
+    let bin = tempfile::tempdir().unwrap();
+    fake_watchman(bin.path(), repo.root());
+    let snapshot = |files: &str| {
+        repo.jjka()
+            .env("PATH", path_with(bin.path()))
+            .env("FAKE_WATCHMAN_FILES", files)
+            .args(["hunks", "-r", "@"])
+            .assert()
+            .success();
+    };
+
+    // There's no clock yet, so the first snapshot scans everything.
+    std::fs::write(repo.root().join("a.txt"), "a\n").unwrap();
+    snapshot("[]");
+    let clock_file = repo.root().join(".jj/working_copy/jjka-watchman-clock");
+    assert!(clock_file.is_file());
+    assert_eq!(
+        repo.file_content(&repo.wc_commit(), "a.txt").as_deref(),
+        Some("a\n")
+    );
+
+    // A change Watchman doesn't report isn't seen...
+    std::fs::write(repo.root().join("b.txt"), "b\n").unwrap();
+    snapshot("[]");
+    assert_eq!(repo.file_content(&repo.wc_commit(), "b.txt"), None);
+    // ...until it does.
+    snapshot(r#"["b.txt"]"#);
+    assert_eq!(
+        repo.file_content(&repo.wc_commit(), "b.txt").as_deref(),
+        Some("b\n")
+    );
+}
+
+#[test]
+fn test_snapshot_scans_everything_without_watchman() {
+    let repo = TestRepo::init();
+    repo.add_config("fsmonitor.backend = \"watchman\"");
+    let bin = tempfile::tempdir().unwrap();
+    let broken = bin.path().join("watchman");
+    std::fs::write(&broken, "#!/bin/sh\nexit 1\n").unwrap();
+    std::fs::set_permissions(&broken, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    std::fs::write(repo.root().join("a.txt"), "a\n").unwrap();
+    repo.jjka()
+        .env("PATH", path_with(bin.path()))
+        .args(["hunks", "-r", "@"])
+        .assert()
+        .success();
+    assert_eq!(
+        repo.file_content(&repo.wc_commit(), "a.txt").as_deref(),
+        Some("a\n")
+    );
+    assert!(
+        !repo
+            .root()
+            .join(".jj/working_copy/jjka-watchman-clock")
+            .exists()
+    );
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args([
+            "-c",
+            "user.name=Upstream",
+            "-c",
+            "user.email=upstream@example.com",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+fn make_executable(path: &Path, script: &str) {
+    std::fs::write(path, script).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// A test repo whose `origin` takes pushes to `refs/for/*` like Gerrit
+/// does: it records the commits and keeps no ref.
+struct Setup {
+    repo: TestRepo,
+    origin: PathBuf,
+    trunk: String,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let repo = TestRepo::init();
+        let parent = repo.root().parent().unwrap().to_path_buf();
+        let origin = parent.join("origin.git");
+        git(
+            &parent,
+            &["init", "--bare", "-b", "main", origin.to_str().unwrap()],
+        );
+        make_executable(
+            &origin.join("hooks/post-receive"),
+            "#!/bin/sh\n\
+             while read old new ref; do\n\
+               case \"$ref\" in refs/for/*) ;; *) continue ;; esac\n\
+               git log --format='%H%n%B' \"$new\" --not refs/heads/main >> received\n\
+               git update-ref -d \"$ref\"\n\
+             done\n",
+        );
+        let clone = parent.join("upstream");
+        git(
+            &parent,
+            &["clone", origin.to_str().unwrap(), clone.to_str().unwrap()],
+        );
+        std::fs::write(clone.join("a.txt"), "1\n").unwrap();
+        git(&clone, &["add", "a.txt"]);
+        git(&clone, &["commit", "-m", "Base"]);
+        git(&clone, &["push", "origin", "main"]);
+        let trunk = git(&clone, &["rev-parse", "HEAD"]);
+        git(
+            &repo.root().join(".jj/repo/store/git"),
+            &["remote", "add", "origin", origin.to_str().unwrap()],
+        );
+        repo.jjka().arg("sync").assert().success();
+        Setup {
+            repo,
+            origin,
+            trunk,
+        }
+    }
+
+    /// What the remote was sent, and forgets it.
+    fn take_received(&self) -> String {
+        let path = self.origin.join("received");
+        let received = std::fs::read_to_string(&path).unwrap_or_default();
+        std::fs::remove_file(&path).ok();
+        received
+    }
+
+    /// A stack of two commits with the bookmark `stack` on top. Returns
+    /// their change ids.
+    fn create_stack(&self, second_description: &str) -> (String, String) {
+        let repo = &self.repo;
+        let first = repo.create_commit(&[&self.trunk], &[("a.txt", Some("2\n"))], "First\n");
+        let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], second_description);
+        repo.set_bookmark("stack", &second);
+        (
+            repo.commit(&first).change_id().hex(),
+            repo.commit(&second).change_id().hex(),
+        )
+    }
+}
+
+#[test]
+fn test_gerrit_push_adds_change_ids_and_pushes_for_review() {
+    let setup = Setup::new();
+    let repo = &setup.repo;
+    let (first, _) =
+        setup.create_stack("Second\n\nChange-Id: I0123456789abcdef0123456789abcdef01234567\n");
+
+    repo.jjka()
+        .args(["gerrit", "push", "-r", "trunk()..stack"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Pushed 2 commit(s) to refs/for/main on origin",
+        ))
+        .stderr(predicate::str::contains(
+            "Added Change-Id trailers to 1 commit(s)",
+        ));
+
+    let head = repo.bookmark_commit("stack").unwrap();
+    let bottom = repo.commit(&head.parent_ids()[0].hex());
+    assert_eq!(
+        bottom.description(),
+        format!("First\n\nChange-Id: I{first}6a6a6964\n")
+    );
+    assert_eq!(
+        head.description(),
+        "Second\n\nChange-Id: I0123456789abcdef0123456789abcdef01234567\n"
+    );
+    let received = setup.take_received();
+    assert!(received.contains(&format!("{}\nSecond\n", head.id().hex())));
+    assert!(received.contains(&format!("{}\nFirst\n", bottom.id().hex())));
+    assert!(received.contains(&format!("Change-Id: I{first}6a6a6964")));
+
+    // Pushing again uploads the same commits without rewriting them.
+    repo.jjka()
+        .args(["gerrit", "push", "-r", "trunk()..stack"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Added").not());
+    assert_eq!(repo.bookmark_commit("stack").unwrap().id(), head.id());
+    assert!(setup.take_received().contains(&head.id().hex()));
+}
+
+#[test]
+fn test_gerrit_status_shows_review_state() {
+    let setup = Setup::new();
+    let repo = &setup.repo;
+    let (first, _) = setup.create_stack("Second\n");
+    repo.add_config(
+        "jjka.forge.remotes.origin = { kind = 'gerrit', url = 'https://review.example.com' }",
+    );
+    repo.jjka()
+        .args(["gerrit", "push", "-r", "trunk()..stack"])
+        .assert()
+        .success();
+    let bottom = repo.commit(&repo.bookmark_commit("stack").unwrap().parent_ids()[0].hex());
+
+    let bin = tempfile::tempdir().unwrap();
+    make_executable(
+        &bin.path().join("curl"),
+        r#"#!/bin/sh
+for url; do :; done
+case "$url" in
+  *"change:$FAKE_UPLOADED&"*) printf ")]}'\n[%s]\n" "$FAKE_CHANGE" ;;
+  *) printf ")]}'\n[]\n" ;;
+esac
+"#,
+    );
+    let change = format!(
+        r#"{{"_number": 1234, "project": "app", "status": "NEW",
+            "current_revision": "{}", "revisions": {{"{}": {{"_number": 3}}}},
+            "labels": {{"Code-Review": {{"all": [{{"value": 2}}, {{"value": 0}}]}},
+                        "Verified": {{"all": [{{"value": -1}}]}}}}}}"#,
+        bottom.id().hex(),
+        bottom.id().hex()
+    );
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let path = std::env::join_paths(
+        std::iter::once(bin.path().to_path_buf()).chain(std::env::split_paths(&path)),
+    )
+    .unwrap();
+
+    repo.jjka()
+        .env("PATH", path)
+        .env("FAKE_UPLOADED", format!("I{first}6a6a6964"))
+        .env("FAKE_CHANGE", change.replace('\n', " "))
+        .args(["gerrit", "status", "-r", "trunk()..stack"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "First -> 1234 NEW, patch set 3, Code-Review+2 Verified-1 \
+             https://review.example.com/c/app/+/1234",
+        ))
+        .stdout(predicate::str::contains("Second: not uploaded yet"));
+}
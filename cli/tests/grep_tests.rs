@@ -0,0 +1,104 @@
+mod common;
+
+use common::TestRepo;
+use serde_json::Value;
+
+#[test]
+fn test_grep_across_revisions() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(
+        &[&root],
+        &[
+            ("src/a.rs", Some("// TODO: parse\nfn a() {}\n")),
+            ("README.md", Some("todo list\n")),
+        ],
+        "Add a",
+    );
+    let second = repo.create_commit(
+        &[&first],
+        &[("src/b.rs", Some("fn b() {} // TODO\n"))],
+        "Add b",
+    );
+    repo.set_bookmark("second", &second);
+    let first_change = &repo.commit(&first).change_id().reverse_hex()[..12];
+    let second_change = &repo.commit(&second).change_id().reverse_hex()[..12];
+
+    let output = repo
+        .jjka()
+        .args(["grep", "TODO", "-r", "::second", "src"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{second_change} src/a.rs:1:// TODO: parse\n\
+             {second_change} src/b.rs:1:fn b() {{}} // TODO\n\
+             {first_change} src/a.rs:1:// TODO: parse\n"
+        )
+    );
+
+    let output = repo
+        .jjka()
+        .args(["grep", "-i", "todo", "-r", "second", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let matches: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let paths: Vec<&str> = matches
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|found| found["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["README.md", "src/a.rs", "src/b.rs"]);
+    assert_eq!(matches[0]["commit_id"], second);
+}
+
+#[test]
+fn test_grep_no_matches() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("f(x)\n"))], "Add a");
+
+    // Without -F, the parentheses are a group.
+    let output = repo
+        .jjka()
+        .args(["grep", "-F", "f(x)", "-r", &commit])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let output = repo
+        .jjka()
+        .args(["grep", "missing", "-r", &commit])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No matches for missing"), "{stderr}");
+}
+
+#[test]
+fn test_grep_nul_terminated_paths() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(
+        &[&root],
+        &[
+            ("with space.txt", Some("TODO\n")),
+            ("new\nline.txt", Some("TODO\nTODO\n")),
+            ("clean.txt", Some("done\n")),
+        ],
+        "Add files",
+    );
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("TODO\n"))], "Add b");
+
+    let output = repo
+        .jjka()
+        .args(["grep", "TODO", "-z", "-r", &format!("{first}::{second}")])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"b.txt\0new\nline.txt\0with space.txt\0");
+}
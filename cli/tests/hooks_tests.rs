@@ -0,0 +1,58 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+
+/// A stack with an empty commit for `tidy --fix` to abandon. Returns the
+/// id of the empty commit.
+fn stack_with_empty_commit(repo: &TestRepo) -> String {
+    let root = repo.root_commit_id();
+    let parser = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add parser");
+    let empty = repo.create_commit(&[&parser], &[], "Nothing");
+    let top = repo.create_commit(&[&empty], &[("b.txt", Some("b\n"))], "Add lexer");
+    repo.set_bookmark("stack", &top);
+    empty
+}
+
+#[test]
+fn test_post_hook_gets_payload() {
+    let repo = TestRepo::init();
+    let payload = repo.root().parent().unwrap().join("payload.json");
+    repo.add_config(&format!(
+        "jjka.hooks.post = \"(cat; echo; echo $JJKA_HOOK) > '{}'\"",
+        payload.display()
+    ));
+    let empty = stack_with_empty_commit(&repo);
+
+    repo.jjka()
+        .args(["tidy", "-r", "root()..stack", "--fix"])
+        .assert()
+        .success();
+
+    let written = std::fs::read_to_string(&payload).unwrap();
+    let (json, hook) = written.split_once('\n').unwrap();
+    assert_eq!(hook, "post\n");
+    let json: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(json["hook"], "post");
+    assert_eq!(json["command"], "tidy");
+    assert_eq!(json["abandoned"][0]["commit_id"], empty);
+    assert_eq!(json["rewritten"][0]["subject"], "Add lexer");
+}
+
+#[test]
+fn test_pre_hook_rejects_transaction() {
+    let repo = TestRepo::init();
+    repo.add_config("jjka.hooks.pre = ['sh', '-c', 'echo no abandoning >&2; exit 1']");
+    let empty = stack_with_empty_commit(&repo);
+
+    repo.jjka()
+        .args(["tidy", "-r", "root()..stack", "--fix"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no abandoning"))
+        .stderr(predicate::str::contains("The jjka.hooks.pre hook failed"));
+
+    let top = repo.bookmark_commit("stack").unwrap();
+    assert_eq!(top.parent_ids()[0].hex(), empty);
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+const BASE: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+
+/// An edit to `f.txt` and the same edit rebased onto a parent that
+/// prepended two lines, plus one edit that was dropped in the rewrite.
+fn rewritten_change() -> (TestRepo, String, String) {
+    let repo = TestRepo::init();
+    let base = repo.create_commit(&[&repo.root_commit_id()], &[("f.txt", Some(BASE))], "base");
+    let old = repo.create_commit(
+        &[&base],
+        &[("f.txt", Some("1\n2\n3\nfour\n5\n6\n7\n8\n9\nten\n"))],
+        "edit",
+    );
+    let new_base = repo.create_commit(
+        &[&base],
+        &[("f.txt", Some(&format!("a\nb\n{BASE}")))],
+        "prepend",
+    );
+    let new = repo.create_commit(
+        &[&new_base],
+        &[("f.txt", Some("a\nb\n1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n"))],
+        "edit",
+    );
+    (repo, old, new)
+}
+
+#[test]
+fn test_hunks_lists_fingerprints() {
+    let (repo, old, _new) = rewritten_change();
+
+    repo.jjka()
+        .args(["hunks", "-r", &old])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"^f.txt:4-4  \+1 -1  [0-9a-f]{16}\nf.txt:10-10  \+1 -1  [0-9a-f]{16}\n$",
+            )
+            .unwrap(),
+        );
+}
+
+#[test]
+fn test_hunks_json() {
+    let (repo, old, new) = rewritten_change();
+
+    let output = repo
+        .jjka()
+        .args(["hunks", "-r", &old, "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["commit"]["commit_id"], old);
+    assert_eq!(report["hunks"][1]["path"], "f.txt");
+    assert_eq!(report["hunks"][1]["start"], 10);
+    assert_eq!(report["hunks"][1]["added"], 1);
+
+    let output = repo
+        .jjka()
+        .args([
+            "hunks",
+            "-r",
+            &new,
+            "--remap-from",
+            &old,
+            "--format",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["to"]["commit_id"], new);
+    assert_eq!(report["selectors"][0]["old"], "f.txt:4-4");
+    assert_eq!(report["selectors"][0]["new"][0], "f.txt:6-6");
+    assert_eq!(report["selectors"][1]["status"], "lost");
+}
+
+#[test]
+fn test_hunks_remap_report() {
+    let (repo, old, new) = rewritten_change();
+
+    repo.jjka()
+        .args(["hunks", "-r", &new, "--remap-from", &old])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  f.txt:4-4 -> f.txt:6-6 (relocated)\n",
+        ))
+        .stdout(predicate::str::contains("  f.txt:10-10 (lost)\n"))
+        .stdout(predicate::str::contains(
+            "0 unchanged, 1 relocated, 0 fuzzy, 1 lost",
+        ));
+}
+
+#[test]
+fn test_hunks_remap_explicit_selector() {
+    let (repo, old, new) = rewritten_change();
+
+    repo.jjka()
+        .args(["hunks", "-r", &new, "--remap-from", &old, "f.txt:3-5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  f.txt:3-5 -> f.txt:6-6 (relocated)\n",
+        ));
+}
+
+#[test]
+fn test_hunks_selectors_require_remap_from() {
+    let (repo, _old, new) = rewritten_change();
+
+    repo.jjka()
+        .args(["hunks", "-r", &new, "f.txt:1-2"])
+        .assert()
+        .failure();
+}
@@ -0,0 +1,852 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+use serde_json::Value;
+
+#[test]
+fn test_hunksplit_splits_revision() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\n3\nfour\n"))], "Edit a");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "-m", "First line", "a.txt:1-1"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"Split \w+ \w+ Edit a into:\n  \w+ \w+ First line\n  \w+ \w+ Edit a\n",
+            )
+            .unwrap(),
+        );
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(selected.description(), "First line");
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(remaining.change_id(), repo.commit(&commit).change_id());
+    assert_eq!(
+        repo.file_content(&remaining, "a.txt").as_deref(),
+        Some("one\n2\n3\nfour\n")
+    );
+}
+
+#[test]
+fn test_hunksplit_dry_run_emits_plan() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\ntwo\n"))], "Edit a");
+    let plan_path = repo.root().join("plan.json");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--dry-run", "--emit-plan"])
+        .arg(&plan_path)
+        .arg("a.txt:2-2")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Wrote the plan to"));
+
+    let plan: Value = serde_json::from_slice(&std::fs::read(&plan_path).unwrap()).unwrap();
+    assert_eq!(plan["version"], 1);
+    assert_eq!(plan["commit_id"], commit);
+    assert_eq!(plan["ranges"], serde_json::json!(["a.txt:2-2"]));
+    assert_eq!(plan["message"], Value::Null);
+    assert_eq!(plan["files"][0]["path"], "a.txt");
+    assert_eq!(plan["files"][0]["selected_insertions"], 1);
+    assert_eq!(plan["files"][0]["remaining_insertions"], 1);
+    // Nothing was split.
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+
+    repo.jjka()
+        .args(["hunksplit", "--emit-plan", "plan.json", "a.txt:2-2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--dry-run"));
+}
+
+/// Breaks the repo so that loading it at its latest operation fails.
+fn remove_op_heads(repo: &TestRepo) {
+    std::fs::remove_dir_all(repo.root().join(".jj/repo/op_heads/heads")).unwrap();
+}
+
+#[test]
+fn test_hunksplit_dry_run_does_not_load_the_repo() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n3\n").unwrap();
+    remove_op_heads(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "--dry-run", "a.txt:2-3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("      2\n      3\n"));
+    // Splitting does need it.
+    repo.jjka()
+        .args(["hunksplit", "a.txt:2-3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to load repository"));
+}
+
+#[test]
+fn test_hunksplit_dry_run_previews_the_revision() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\ntwo\nthree\n").unwrap();
+    // Leaves the selected lines in @- and the whole file in @.
+    repo.jjka()
+        .args(["hunksplit", "a.txt:2-3"])
+        .assert()
+        .success();
+    std::fs::write(repo.root().join("a.txt"), "x\ny\nz\n").unwrap();
+    let parent = repo.wc_commit().parent_ids()[0].hex();
+
+    repo.jjka()
+        .args(["hunksplit", "-r", "@-", "--dry-run", "a.txt:1-1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Selected content:\n      two\n"))
+        .stdout(predicate::str::contains("Remaining: 5 bytes (1 lines)"));
+
+    let output = repo
+        .jjka()
+        .args(["hunksplit", "-r", "@-", "--dry-run", "--json", "a.txt:1-1"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let preview: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(preview["files"][0]["selected"], "two");
+    assert_eq!(preview["files"][0]["remaining"], "three");
+    // Nothing was split.
+    assert_eq!(repo.wc_commit().parent_ids()[0].hex(), parent);
+}
+
+#[test]
+fn test_hunksplit_dry_run_highlights_syntax() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.rs"), "let n = 1;\n").unwrap();
+    std::fs::write(repo.root().join("a.txt"), "let\n").unwrap();
+
+    repo.jjka()
+        .args([
+            "--color",
+            "always",
+            "hunksplit",
+            "--dry-run",
+            "a.rs:1-1",
+            "a.txt:1-1",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "      \x1b[35mlet\x1b[0m n = \x1b[36m1\x1b[0m;\n",
+        ))
+        .stdout(predicate::str::contains("      let\n"));
+
+    repo.add_config("jjka.diff.theme = \"none\"");
+    repo.jjka()
+        .args(["--color", "always", "hunksplit", "--dry-run", "a.rs:1-1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("      let n = 1;\n"));
+}
+
+#[test]
+fn test_hunksplit_checks_ranges_before_loading() {
+    let repo = TestRepo::init();
+    remove_op_heads(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "a.txt:3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid range format"))
+        .stderr(predicate::str::contains("Failed to load repository").not());
+}
+
+#[test]
+fn test_hunksplit_merges_overlapping_ranges() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n3\n4\n5\n").unwrap();
+
+    let output = repo
+        .jjka()
+        .args(["hunksplit", "--dry-run", "--json", "a.txt:2-3", "a.txt:3-4"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let preview: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(preview["files"][0]["selected"], "2\n3\n4");
+    assert_eq!(preview["files"][0]["remaining"], "1\n5");
+}
+
+#[test]
+fn test_hunksplit_refuses_unchanged_ranges() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\n3\nfour\n"))], "Edit a");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "a.txt:1-1", "a.txt:3-3"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "a.txt:3-3 select only lines unchanged in",
+        ))
+        .stderr(predicate::str::contains("pass --force"));
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+
+    repo.jjka()
+        .args([
+            "hunksplit",
+            "-r",
+            &commit,
+            "--force",
+            "a.txt:1-1",
+            "a.txt:3-3",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning: a.txt:3-3 select only"));
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\n")
+    );
+}
+
+#[test]
+fn test_hunksplit_refuses_to_leave_a_commit_empty() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\n"))], "Edit a");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "a.txt:1-2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("are all of the changes in"))
+        .stderr(predicate::str::contains("so it would be left empty"))
+        .stderr(predicate::str::contains("pass --allow-empty"));
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--allow-empty", "a.txt:1-2"])
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("one\n2\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(remaining.tree_id(), selected.tree_id());
+}
+
+#[test]
+fn test_hunksplit_refuses_an_empty_new_commit() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\n"))], "Edit a");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--force", "a.txt:2-2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("so the new commit would be empty"));
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--force", "--allow-empty"])
+        .arg("a.txt:2-2")
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(selected.tree_id(), repo.commit(&base).tree_id());
+}
+
+#[test]
+fn test_hunksplit_lists_the_commits_of_a_divergent_change() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\nthree\n"))], "Edit a");
+    let copy = repo.create_divergent_copy(&commit, &[("a.txt", Some("ONE\n2\nthree\n"))], "Edit a");
+    let change_id = repo.commit(&commit).change_id().reverse_hex();
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &change_id, "a.txt:1-1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(format!(
+            "Change {change_id} is divergent: it has 2 visible commits"
+        )))
+        .stderr(predicate::str::contains(&commit[..12]))
+        .stderr(predicate::str::contains(&copy[..12]));
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 2);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &copy[..12], "a.txt:1-1"])
+        .assert()
+        .success();
+    let children = repo.children(&repo.commit(&base));
+    let selected = children
+        .iter()
+        .find(|child| child.description() == "Edit a" && child.id().hex() != commit)
+        .unwrap();
+    assert_eq!(
+        repo.file_content(selected, "a.txt").as_deref(),
+        Some("ONE\n2\n3\n")
+    );
+}
+
+#[test]
+fn test_hunksplit_takes_paths_relative_to_the_current_directory() {
+    let repo = TestRepo::init();
+    std::fs::create_dir(repo.root().join("src")).unwrap();
+    std::fs::write(repo.root().join("src/main.rs"), "1\n2\n3\n").unwrap();
+
+    let output = repo
+        .jjka()
+        .current_dir(repo.root().join("src"))
+        .args(["hunksplit", "--dry-run", "--json", "./main.rs:2-3"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let preview: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(preview["ranges"][0]["path"], "src/main.rs");
+    assert_eq!(preview["files"][0]["selected"], "2\n3");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_hunksplit_keeps_backslashes_in_file_names() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a\\b.txt"), "1\n2\n3\n").unwrap();
+
+    let output = repo
+        .jjka()
+        .args(["hunksplit", "--dry-run", "--json", "a\\b.txt:2-3"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let preview: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(preview["ranges"][0]["path"], "a\\b.txt");
+    assert_eq!(preview["files"][0]["selected"], "2\n3");
+}
+
+#[test]
+fn test_hunksplit_rejects_paths_outside_the_workspace() {
+    let repo = TestRepo::init();
+
+    repo.jjka()
+        .args(["hunksplit", "--dry-run", "../a.txt:1-2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid range ../a.txt:1-2"));
+}
+
+#[test]
+fn test_hunksplit_suggests_changed_paths() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("src/main.rs", Some("1\n")), ("notes.txt", Some("n\n"))],
+        "Base",
+    );
+    let commit = repo.create_commit(&[&base], &[("src/main.rs", Some("one\n2\n"))], "Edit");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "src/mian.rs:1-1"])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::is_match(
+                r"src/mian.rs doesn't exist in \w+ \w+ Edit; did you mean src/main.rs\?",
+            )
+            .unwrap(),
+        );
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "notes.txt:1-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(r"notes.txt isn't changed in \w+ \w+ Edit\n").unwrap());
+}
+
+#[test]
+fn test_hunksplit_splits_part_of_a_deletion() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n5\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", None)], "Delete a");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "-m", "Trim a", "a.txt:2-4"])
+        .assert()
+        .success();
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("1\n5\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(repo.file_content(&remaining, "a.txt"), None);
+}
+
+#[test]
+fn test_hunksplit_moves_a_whole_deletion() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("1\n2\n")), ("b.txt", Some("b\n"))],
+        "Base",
+    );
+    let commit = repo.create_commit(
+        &[&base],
+        &[("a.txt", None), ("b.txt", Some("B\n"))],
+        "Delete a, edit b",
+    );
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "a.txt:2-3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "a.txt:2-3 is past the end of the file, which has 2 line(s) before",
+        ));
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "a.txt:1-2"])
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(repo.file_content(&selected, "a.txt"), None);
+    assert_eq!(
+        repo.file_content(&selected, "b.txt").as_deref(),
+        Some("b\n")
+    );
+}
+
+#[test]
+fn test_hunksplit_splits_an_added_file() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("new.txt", Some("1\n2\n3\n4\n"))], "Add new");
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "new.txt:2-3"])
+        .assert()
+        .success();
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "new.txt").as_deref(),
+        Some("2\n3\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&remaining, "new.txt").as_deref(),
+        Some("1\n2\n3\n4\n")
+    );
+}
+
+/// A revision that renames old.txt to new.txt and changes lines 3 and 8.
+fn rename_and_edit(repo: &TestRepo) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("old.txt", Some("1\n2\n3\n4\n5\n6\n7\n8\n"))],
+        "Base",
+    );
+    let commit = repo.create_commit(
+        &[&base],
+        &[
+            ("old.txt", None),
+            ("new.txt", Some("1\n2\nthree\n4\n5\n6\n7\neight\n")),
+        ],
+        "Rename and edit",
+    );
+    (base, commit)
+}
+
+#[test]
+fn test_hunksplit_moves_the_rename_with_the_selected_lines() {
+    let repo = TestRepo::init();
+    let (base, commit) = rename_and_edit(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "new.txt:3-3"])
+        .assert()
+        .success();
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(repo.file_content(&selected, "old.txt"), None);
+    assert_eq!(
+        repo.file_content(&selected, "new.txt").as_deref(),
+        Some("1\n2\nthree\n4\n5\n6\n7\n8\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(repo.file_content(&remaining, "old.txt"), None);
+    assert_eq!(
+        repo.file_content(&remaining, "new.txt").as_deref(),
+        Some("1\n2\nthree\n4\n5\n6\n7\neight\n")
+    );
+}
+
+#[test]
+fn test_hunksplit_takes_the_old_path_of_a_renamed_file() {
+    let repo = TestRepo::init();
+    let (base, commit) = rename_and_edit(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "old.txt:8-8"])
+        .assert()
+        .success();
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(repo.file_content(&selected, "old.txt"), None);
+    assert_eq!(
+        repo.file_content(&selected, "new.txt").as_deref(),
+        Some("1\n2\n3\n4\n5\n6\n7\neight\n")
+    );
+}
+
+/// A revision with a.txt conflicted.
+fn conflicted(repo: &TestRepo) -> String {
+    let base = repo.create_commit(&[&repo.root_commit_id()], &[("a.txt", Some("1\n"))], "Base");
+    let left = repo.create_commit(&[&base], &[("a.txt", Some("2\n"))], "Left");
+    let right = repo.create_commit(&[&base], &[("a.txt", Some("3\n"))], "Right");
+    repo.cherry_pick(&right, &left, "Pick right")
+}
+
+#[test]
+fn test_hunksplit_refuses_conflicted_files() {
+    let repo = TestRepo::init();
+    let picked = conflicted(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &picked, "a.txt:1-1"])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::is_match(
+                r"a.txt has unresolved conflicts in \w+ \w+ Pick right; resolve them",
+            )
+            .unwrap(),
+        );
+}
+
+#[test]
+fn test_hunksplit_refuses_files_conflicted_in_the_parent() {
+    let repo = TestRepo::init();
+    let picked = conflicted(&repo);
+    let resolved = repo.create_commit(
+        &[&picked],
+        &[("a.txt", Some("2\n3\n")), ("b.txt", Some("b\n"))],
+        "Resolve",
+    );
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &resolved, "a.txt:1-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "a.txt has unresolved conflicts in the parent of",
+        ));
+}
+
+fn is_symlink(commit: &jj_lib::commit::Commit, path: &str) -> bool {
+    let path = jj_lib::repo_path::RepoPathBuf::from_internal_string(path).unwrap();
+    matches!(
+        commit
+            .tree()
+            .unwrap()
+            .path_value(&path)
+            .unwrap()
+            .as_resolved(),
+        Some(Some(jj_lib::backend::TreeValue::Symlink(_)))
+    )
+}
+
+#[test]
+fn test_hunksplit_moves_a_symlink_as_a_whole() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n").unwrap();
+    std::os::unix::fs::symlink("a.txt", repo.root().join("link")).unwrap();
+
+    repo.jjka()
+        .args(["hunksplit", "link:1-1"])
+        .assert()
+        .success();
+
+    let remaining = repo.wc_commit();
+    let [selected_id] = remaining.parent_ids() else {
+        panic!("expected one parent");
+    };
+    let selected = repo.commit(&jj_lib::object_id::ObjectId::hex(selected_id));
+    assert!(is_symlink(&selected, "link"));
+    assert_eq!(repo.file_content(&selected, "a.txt"), None);
+    assert!(is_symlink(&remaining, "link"));
+
+    std::fs::write(repo.root().join("b.txt"), "b\n").unwrap();
+    std::fs::remove_file(repo.root().join("link")).unwrap();
+    std::os::unix::fs::symlink("b.txt", repo.root().join("link")).unwrap();
+    repo.jjka()
+        .args(["hunksplit", "link:1-2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "select the whole symlink as link:1-1",
+        ));
+}
+
+#[test]
+fn test_hunksplit_moves_a_submodule_pointer_as_a_whole() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let old = "1111111111111111111111111111111111111111";
+    let new = "2222222222222222222222222222222222222222";
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n"))], "Base");
+    let base = repo.create_submodule_commit(&base, "sub", Some(old), &[], "Add sub");
+    let commit = repo.create_submodule_commit(
+        &base,
+        "sub",
+        Some(new),
+        &[("a.txt", Some("one\n"))],
+        "Bump sub and edit",
+    );
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "sub:1-2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "select the whole submodule as sub:1-1",
+        ));
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "sub:1-1"])
+        .assert()
+        .success();
+
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.submodule_target(&selected, "sub").as_deref(),
+        Some(new)
+    );
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("1\n")
+    );
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(
+        repo.submodule_target(&remaining, "sub").as_deref(),
+        Some(new)
+    );
+    assert_eq!(
+        repo.file_content(&remaining, "a.txt").as_deref(),
+        Some("one\n")
+    );
+}
+
+/// A revision that makes run.sh executable and changes its first line.
+fn chmod_and_edit(repo: &TestRepo) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("run.sh", Some("a\nb\n"))], "Base");
+    let commit = repo.create_commit_with_mode(
+        &[&base],
+        &[("run.sh", Some("A\nb\n"))],
+        true,
+        "Chmod and edit",
+    );
+    (base, commit)
+}
+
+#[test]
+fn test_hunksplit_leaves_the_mode_change_in_the_revision() {
+    let repo = TestRepo::init();
+    let (base, commit) = chmod_and_edit(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "run.sh:1-1"])
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "run.sh").as_deref(),
+        Some("A\nb\n")
+    );
+    assert!(!repo.is_executable(&selected, "run.sh"));
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert!(repo.is_executable(&remaining, "run.sh"));
+}
+
+#[test]
+fn test_hunksplit_moves_only_the_mode_change() {
+    let repo = TestRepo::init();
+    let (base, commit) = chmod_and_edit(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--mode", "run.sh"])
+        .assert()
+        .success();
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "run.sh").as_deref(),
+        Some("a\nb\n")
+    );
+    assert!(repo.is_executable(&selected, "run.sh"));
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &base, "--mode", "run.sh"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "run.sh's executable bit isn't changed in",
+        ));
+}
+
+#[test]
+fn test_hunksplit_dry_run_with_diff_formatter() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n3\n4\n").unwrap();
+    repo.add_config("ui.diff-formatter = 'udiff'");
+    repo.add_config(
+        "merge-tools.udiff = { program = 'diff', diff-args = ['-u', '--label', '$path', '--label', '$path', '$left', '$right'] }",
+    );
+
+    repo.jjka()
+        .args(["hunksplit", "--dry-run", "a.txt:2-3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Selected changes (udiff):\n--- a.txt\n+++ a.txt\n",
+        ))
+        .stdout(predicate::str::contains(" 1\n+2\n+3\n 4\n"))
+        .stdout(predicate::str::contains("Selected content:").not());
+}
+
+#[test]
+fn test_hunksplit_dry_run_diff_formatter_errors() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n3\n").unwrap();
+    // jj's built-in formatters keep the plain preview.
+    repo.add_config("ui.diff-formatter = ':git'");
+    repo.jjka()
+        .args(["hunksplit", "--dry-run", "a.txt:2-3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Selected content:"));
+
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n3\n").unwrap();
+    repo.add_config("ui.diff-formatter = 'jjka-no-such-difft'");
+    repo.jjka()
+        .args(["hunksplit", "--dry-run", "a.txt:2-3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Failed to run diff tool jjka-no-such-difft",
+        ));
+}
+
+#[test]
+fn test_hunksplit_tool_edits_selection() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("1\n2\n3\n")), ("b.txt", Some("b\n"))],
+        "Base",
+    );
+    let commit = repo.create_commit(
+        &[&base],
+        &[
+            ("a.txt", Some("one\n2\nthree\n")),
+            ("b.txt", None),
+            ("c.txt", Some("c\n")),
+        ],
+        "Edit",
+    );
+    // Keeps the first line's change and b.txt's removal, and leaves c.txt
+    // out, checking that both sides are there to start with.
+    repo.add_config(
+        r#"merge-tools.fake = { program = "sh", edit-args = ["-c", "test -f $0/b.txt && ! test -e $1/b.txt && printf 'one\n2\n3\n' > $1/a.txt && rm $1/c.txt", "$left", "$right"] }"#,
+    );
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--tool", "fake", "-m", "First"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Split "));
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(selected.description(), "First");
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("one\n2\n3\n")
+    );
+    assert_eq!(repo.file_content(&selected, "b.txt"), None);
+    assert_eq!(repo.file_content(&selected, "c.txt"), None);
+    let [remaining] = repo.children(&selected).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&remaining, "a.txt").as_deref(),
+        Some("one\n2\nthree\n")
+    );
+    assert_eq!(
+        repo.file_content(&remaining, "c.txt").as_deref(),
+        Some("c\n")
+    );
+}
+
+#[test]
+fn test_hunksplit_interactive_starts_from_ranges() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\nthree\n"))], "Edit");
+    let log = repo.root().parent().unwrap().join("right.txt");
+    repo.add_config(&format!(
+        "ui.diff-editor = 'fake'\nmerge-tools.fake = {{ program = 'sh', edit-args = ['-c', 'cp $0/a.txt {}', '$right'] }}",
+        log.display()
+    ));
+
+    // An editor that fails splits nothing.
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "--tool", "false"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Diff editor false failed (exit status: 1); nothing was split",
+        ));
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "-i", "a.txt:3-3"])
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), "1\n2\nthree\n");
+    let [selected] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("1\n2\nthree\n")
+    );
+}
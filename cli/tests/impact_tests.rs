@@ -0,0 +1,115 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+
+/// root -> parser -> fixup -> docs (stack)
+fn fixup_stack() -> (TestRepo, String, String) {
+    let repo = TestRepo::init();
+    let parser = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("a.txt", Some("parser\n"))],
+        "Add parser",
+    );
+    let fixup = repo.create_commit(
+        &[&parser],
+        &[("a.txt", Some("parser\nfix\n"))],
+        "fixup! Add parser",
+    );
+    let docs = repo.create_commit(&[&fixup], &[("b.txt", Some("docs\n"))], "Add docs");
+    repo.set_bookmark("stack", &docs);
+    (repo, parser, fixup)
+}
+
+#[test]
+fn test_impact_summary_text() {
+    let (repo, parser, fixup) = fixup_stack();
+    let old_stack = repo.bookmark_commit("stack").unwrap().id().hex();
+    let fixup_commit = repo.commit(&fixup);
+
+    let assert = repo
+        .jjka()
+        .args(["tidy", "-r", "root()..stack", "--fix"])
+        .assert()
+        .success();
+
+    let new_stack = repo.bookmark_commit("stack").unwrap();
+    let new_parser = repo.commit(&new_stack.parent_ids()[0].hex());
+    assert_eq!(new_parser.change_id(), repo.commit(&parser).change_id());
+    assert
+        .stderr(predicate::str::contains(
+            "Impact of \"tidy root()..stack\":\n",
+        ))
+        .stderr(predicate::str::contains(format!(
+            "  Rewrote {} {} Add parser (was {})  1 file(s) +2 -0, was 1 file(s) +1 -0\n",
+            &new_parser.change_id().reverse_hex()[..12],
+            &new_parser.id().hex()[..12],
+            &parser[..12],
+        )))
+        .stderr(predicate::str::contains(format!(
+            "  Abandoned {} {fixup:.12} fixup! Add parser\n",
+            &fixup_commit.change_id().reverse_hex()[..12],
+        )))
+        .stderr(predicate::str::contains(format!(
+            "  Moved bookmark stack from {old_stack:.12} to {:.12}\n",
+            new_stack.id().hex()
+        )));
+}
+
+#[test]
+fn test_impact_summary_json() {
+    let (repo, parser, _) = fixup_stack();
+
+    let output = repo
+        .jjka()
+        .args(["tidy", "-r", "root()..stack", "--fix", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let report: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+
+    let operation = &report["operations"][0];
+    assert_eq!(operation["operation"], "tidy root()..stack");
+    let rewritten = operation["rewritten"].as_array().unwrap();
+    let parser_entry = rewritten
+        .iter()
+        .find(|entry| entry["old_commit_id"] == parser.as_str())
+        .unwrap();
+    assert_eq!(parser_entry["subject"], "Add parser");
+    assert_eq!(parser_entry["old_diffstat"]["insertions"], 1);
+    assert_eq!(parser_entry["new_diffstat"]["insertions"], 2);
+    assert_eq!(parser_entry["new_conflict"], false);
+    assert_eq!(operation["abandoned"].as_array().unwrap().len(), 1);
+    assert_eq!(operation["bookmarks"][0]["name"], "stack");
+}
+
+#[test]
+fn test_no_impact_summary_for_read_only_commands() {
+    let (repo, _, _) = fixup_stack();
+
+    repo.jjka()
+        .args(["tidy", "-r", "root()..stack"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Impact of").not());
+}
+
+#[test]
+fn test_quiet_prints_only_results() {
+    let (repo, _, _) = fixup_stack();
+
+    repo.jjka()
+        .args(["tidy", "-r", "root()..stack", "--fix", "--quiet"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("squashed 1 fixup(s)"))
+        .stderr("");
+    repo.jjka()
+        .args(["-q", "tidy", "-r", "root()..stack"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
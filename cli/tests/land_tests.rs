@@ -0,0 +1,165 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args([
+            "-c",
+            "user.name=Upstream",
+            "-c",
+            "user.email=upstream@example.com",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// A test repo with an `origin` remote and a stack of two commits on its
+/// main branch, the bottom one pushed to `first`. Returns the repo, the
+/// bare origin repo and the commits bottom first.
+fn setup() -> (TestRepo, PathBuf, [String; 2]) {
+    let repo = TestRepo::init();
+    let parent = repo.root().parent().unwrap().to_path_buf();
+    let origin = parent.join("origin.git");
+    git(
+        &parent,
+        &["init", "--bare", "-b", "main", origin.to_str().unwrap()],
+    );
+    let clone = parent.join("upstream");
+    git(
+        &parent,
+        &["clone", origin.to_str().unwrap(), clone.to_str().unwrap()],
+    );
+    std::fs::write(clone.join("a.txt"), "1\n").unwrap();
+    git(&clone, &["add", "a.txt"]);
+    git(&clone, &["commit", "-m", "Base"]);
+    git(&clone, &["push", "origin", "main"]);
+    let trunk = git(&clone, &["rev-parse", "HEAD"]);
+    let git_dir = repo.root().join(".jj/repo/store/git");
+    git(
+        &git_dir,
+        &["remote", "add", "origin", origin.to_str().unwrap()],
+    );
+    repo.jjka().arg("sync").assert().success();
+
+    let first = repo.create_commit(&[&trunk], &[("a.txt", Some("2\n"))], "First\n");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second\n");
+    repo.set_bookmark("top", &second);
+    git(
+        &git_dir,
+        &["push", "origin", &format!("{first}:refs/heads/first")],
+    );
+    (repo, origin, [first, second])
+}
+
+#[test]
+fn test_land_pushes_the_bottom_commit() {
+    let (repo, origin, [first, second]) = setup();
+
+    repo.jjka()
+        .args(["land", "-r", &second])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "isn't the bottom of its stack; land the 1 commit(s) below it first",
+        ));
+    assert_ne!(git(&origin, &["rev-parse", "main"]), first);
+
+    repo.jjka()
+        .args(["land", "-r", &first])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Not checking review or CI: can't tell which forge origin is on",
+        ))
+        .stdout(predicate::str::is_match(r"(?m)^Landed \S+ \S+ First on main$").unwrap());
+    assert_eq!(git(&origin, &["rev-parse", "main"]), first);
+    assert_eq!(repo.bookmark_commit("top").unwrap().id().hex(), second);
+}
+
+#[test]
+fn test_land_merges_an_approved_merge_request() {
+    let (repo, origin, [first, _]) = setup();
+    repo.add_config(
+        "jjka.forge.remotes.origin = { kind = 'gitlab', url = 'https://gitlab.example.com/group/app' }",
+    );
+    // A fake GitLab whose merge squashes `first` onto main in the origin and
+    // deletes its branch.
+    let forge = tempfile::tempdir().unwrap();
+    let script = r#"#!/bin/sh
+# Read the config jjka writes, so it doesn't hit a closed pipe.
+cat > /dev/null
+for url; do :; done
+case "$url" in
+  */merge)
+    export GIT_AUTHOR_NAME=GitLab GIT_AUTHOR_EMAIL=gitlab@example.com
+    export GIT_COMMITTER_NAME=GitLab GIT_COMMITTER_EMAIL=gitlab@example.com
+    squashed=$(git --git-dir "$FAKE_ORIGIN" commit-tree "$FAKE_SHA^{tree}" -p main -m First)
+    git --git-dir "$FAKE_ORIGIN" update-ref refs/heads/main "$squashed"
+    git --git-dir "$FAKE_ORIGIN" update-ref -d refs/heads/first
+    cat "$FAKE_FORGE/request" ;;
+  *"/merge_requests?"*) echo "[$(cat "$FAKE_FORGE/request")]" ;;
+  */approvals) cat "$FAKE_FORGE/approvals" ;;
+  *"/pipelines?"*) echo '[{"status": "success"}]' ;;
+  *) exit 22 ;;
+esac
+"#;
+    let curl = forge.path().join("curl");
+    std::fs::write(&curl, script).unwrap();
+    std::fs::set_permissions(&curl, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::write(
+        forge.path().join("request"),
+        r#"{"iid": 7, "title": "First", "target_branch": "main",
+            "web_url": "https://gitlab.example.com/group/app/-/merge_requests/7"}"#,
+    )
+    .unwrap();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let path = std::env::join_paths(
+        std::iter::once(forge.path().to_path_buf()).chain(std::env::split_paths(&path)),
+    )
+    .unwrap();
+    let land = || {
+        let mut command = repo.jjka();
+        command
+            .env("PATH", &path)
+            .env("FAKE_FORGE", forge.path())
+            .env("FAKE_ORIGIN", &origin)
+            .env("FAKE_SHA", &first)
+            .env("GITLAB_TOKEN", "secret")
+            .args(["land", "-r", &first, "--merge"]);
+        command
+    };
+
+    std::fs::write(forge.path().join("approvals"), r#"{"approved": false}"#).unwrap();
+    land()
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("!7 for "))
+        .stderr(predicate::str::contains("isn't approved yet"));
+
+    std::fs::write(forge.path().join("approvals"), r#"{"approved": true}"#).unwrap();
+    land()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("!7 is approved and CI passed"))
+        .stderr(predicate::str::contains("Merged !7 into main"))
+        .stdout(predicate::str::contains("Rebased 1 commit(s) onto trunk"));
+    let squashed = git(&origin, &["rev-parse", "main"]);
+    assert_ne!(squashed, first);
+    let top = repo.bookmark_commit("top").unwrap();
+    assert_eq!(top.parent_ids()[0].hex(), squashed);
+    assert_eq!(repo.file_content(&top, "a.txt").as_deref(), Some("2\n"));
+    // The local copy of `first` was abandoned.
+    let trunk = repo.commit(&squashed).parent_ids()[0].hex();
+    let children = repo.children(&repo.commit(&trunk));
+    assert!(children.iter().all(|child| child.id().hex() != first));
+}
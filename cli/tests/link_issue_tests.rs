@@ -0,0 +1,105 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+
+/// A stack of three commits on a base, with `top` on the last. Returns the
+/// base.
+fn create_stack(repo: &TestRepo, descriptions: [&str; 3]) -> String {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n"))], "Base\n");
+    let mut parent = base.clone();
+    for (index, description) in descriptions.into_iter().enumerate() {
+        let content = format!("{index}\n");
+        parent = repo.create_commit(&[&parent], &[("a.txt", Some(&content))], description);
+    }
+    repo.set_bookmark("top", &parent);
+    base
+}
+
+/// The descriptions from `base` up to `top`, excluding `base`.
+fn descriptions(repo: &TestRepo) -> Vec<String> {
+    let mut commit = repo.bookmark_commit("top").unwrap();
+    let mut descriptions = Vec::new();
+    for _ in 0..3 {
+        descriptions.push(commit.description().to_string());
+        commit = repo.commit(&commit.parent_ids()[0].hex());
+    }
+    descriptions.reverse();
+    descriptions
+}
+
+#[test]
+fn test_link_issue_from_bookmark_adds_trailers() {
+    let repo = TestRepo::init();
+    let base = create_stack(
+        &repo,
+        ["Add lexer\n", "Fix tabs\n\nRefs: PROJ-7\n", "Parse minus\n"],
+    );
+    repo.add_config(&format!("revset-aliases.'trunk()' = '{base}'"));
+    let top = repo.bookmark_commit("top").unwrap();
+    repo.set_bookmark("PROJ-7-unary-minus", &top.id().hex());
+
+    repo.jjka()
+        .args(["link-issue", "-r", "trunk()..top"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Add lexer to PROJ-7"))
+        .stdout(predicate::str::contains("Parse minus to PROJ-7"))
+        .stdout(predicate::str::contains("Fix tabs").not());
+    assert_eq!(
+        descriptions(&repo),
+        [
+            "Add lexer\n\nRefs: PROJ-7\n",
+            "Fix tabs\n\nRefs: PROJ-7\n",
+            "Parse minus\n\nRefs: PROJ-7\n",
+        ]
+    );
+
+    // Everything mentions it now.
+    repo.jjka()
+        .args(["link-issue", "-r", "trunk()..top"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Every commit in trunk()..top already mentions PROJ-7",
+        ));
+}
+
+#[test]
+fn test_link_issue_as_subject_prefix() {
+    let repo = TestRepo::init();
+    let base = create_stack(&repo, ["Add lexer\n", "Fix tabs\n", "Parse minus\n"]);
+    repo.add_config(&format!("revset-aliases.'trunk()' = '{base}'"));
+    repo.add_config("jjka.issue.prefix = '[{issue}] '");
+
+    // No bookmark names an issue and nobody can be asked.
+    repo.jjka()
+        .args(["link-issue", "-r", "trunk()..top"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "No bookmark on trunk()..top names an issue",
+        ));
+
+    repo.jjka()
+        .args([
+            "link-issue",
+            "ABC-1",
+            "-r",
+            "trunk()..top",
+            "--style",
+            "prefix",
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        descriptions(&repo),
+        [
+            "[ABC-1] Add lexer\n",
+            "[ABC-1] Fix tabs\n",
+            "[ABC-1] Parse minus\n"
+        ]
+    );
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_lint_finds_problems() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let good = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a\n");
+    let wip = repo.create_commit(&[&good], &[("b.txt", Some("b\n"))], "WIP: add b\n");
+    let revset = format!("{root}..{wip}");
+
+    repo.jjka()
+        .args(["lint", "-r", &revset])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("WIP: add b\n  wip: "))
+        .stdout(predicate::str::contains("Add a").not())
+        .stderr(predicate::str::contains(
+            "Found 1 problem(s) in 1 of 2 commit(s)",
+        ));
+
+    repo.jjka()
+        .args(["lint", "-r", &good])
+        .assert()
+        .success()
+        .stdout("Checked 1 commit(s): no problems\n");
+}
+
+#[test]
+fn test_lint_configured_rules() {
+    let repo = TestRepo::init();
+    repo.add_config(
+        "[jjka.lint]\nconventional = true\nrequired-trailers = ['Signed-off-by']\nmax-subject-length = 20\n",
+    );
+    let root = repo.root_commit_id();
+    let good = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("a\n"))],
+        "feat: Add a\n\nSigned-off-by: Test <test@example.com>\n",
+    );
+    let bad = repo.create_commit(
+        &[&good],
+        &[("b.txt", Some("b\n"))],
+        "Add b, which is rather long\n",
+    );
+
+    let output = repo
+        .jjka()
+        .args(["lint", "-r", &format!("{root}..{bad}"), "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["commit_id"], bad);
+    let rules: Vec<&str> = json[0]["problems"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|problem| problem["rule"].as_str().unwrap())
+        .collect();
+    assert_eq!(rules, ["subject-length", "conventional", "trailer"]);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::TestRepo;
+use serde_json::Value;
+
+#[test]
+fn test_log_search_string_and_regex() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let add = repo.create_commit(
+        &[&root],
+        &[("src/a.rs", Some("fn parse_config() {}\n"))],
+        "Add parser",
+    );
+    let moved = repo.create_commit(
+        &[&add],
+        &[
+            ("src/a.rs", Some("// config\nfn parse_config() {}\n")),
+            ("notes.txt", Some("call parse_config\n")),
+        ],
+        "Document parser",
+    );
+    let remove = repo.create_commit(
+        &[&moved],
+        &[("src/a.rs", Some("// config\n"))],
+        "Remove parser",
+    );
+    repo.set_bookmark("main", &remove);
+    let summary = |hex: &str| {
+        let commit = repo.commit(hex);
+        format!(
+            "{} {} {}",
+            &commit.change_id().reverse_hex()[..12],
+            &hex[..12],
+            commit.description().trim()
+        )
+    };
+
+    let output = repo
+        .jjka()
+        .args(["log-search", "-S", "parse_config()", "-r", "::main", "src"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{}\n  src/a.rs: +0 -1\n{}\n  src/a.rs: +1 -0\n",
+            summary(&remove),
+            summary(&add)
+        )
+    );
+
+    // Without paths, the revision that only mentions it in notes.txt is
+    // found too.
+    let output = repo
+        .jjka()
+        .args([
+            "log-search",
+            "-G",
+            r"parse_\w+",
+            "-r",
+            "::main",
+            "--format",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let found: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let ids: Vec<&str> = found
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|revision| revision["commit_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, [remove.as_str(), moved.as_str(), add.as_str()]);
+    assert_eq!(found[1]["files"][0]["path"], "notes.txt");
+}
+
+#[test]
+fn test_log_search_requires_pattern() {
+    let repo = TestRepo::init();
+    let output = repo.jjka().arg("log-search").output().unwrap();
+    assert!(!output.status.success());
+
+    let output = repo
+        .jjka()
+        .args(["log-search", "-S", "missing"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No revisions in ::@"), "{stderr}");
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_verbose_logs_spans_with_timings() {
+    let repo = TestRepo::init();
+    let commit = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("a.txt", Some("a\n"))],
+        "Add a",
+    );
+    repo.jjka()
+        .args(["-v", "stats", "-r", &commit])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("1 commit(s)"))
+        .stderr(
+            predicate::str::is_match(
+                "(?m) DEBUG jjka::workspace: load_repo: loaded repo operation=",
+            )
+            .unwrap(),
+        )
+        .stderr(
+            predicate::str::is_match("(?m)  INFO jjka::workspace: load_repo took \\S+$").unwrap(),
+        )
+        .stderr(predicate::str::contains("jj_lib").not());
+}
+
+#[test]
+fn test_jjka_log_filter() {
+    let repo = TestRepo::init();
+    repo.jjka()
+        .args(["stats", "-r", "root()"])
+        .env("JJKA_LOG", "jjka::workspace=info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("load_repo took"))
+        .stderr(predicate::str::contains("DEBUG").not());
+
+    repo.jjka()
+        .args(["stats", "-r", "root()"])
+        .env("JJKA_LOG", "jjka=loud")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid log level \"loud\" in JJKA_LOG",
+        ));
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::TestRepo;
+use serde_json::{Value, json};
+
+/// Initializes a `jjka mcp` session, sends `requests` and returns the
+/// responses to them.
+fn mcp(repo: &TestRepo, requests: &[Value]) -> Vec<Value> {
+    let mut input = format!(
+        "{}\n{}\n",
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1" },
+            },
+        }),
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+    );
+    for request in requests {
+        input.push_str(&format!("{request}\n"));
+    }
+    let output = repo.jjka().arg("mcp").write_stdin(input).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let mut responses: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let initialize = responses.remove(0);
+    assert_eq!(initialize["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(initialize["result"]["serverInfo"]["name"], "jjka");
+    responses
+}
+
+fn call(id: u64, tool: &str, arguments: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": tool, "arguments": arguments },
+    })
+}
+
+#[test]
+fn test_mcp_list_hunks_and_describe() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let edit = repo.create_commit(&[&root], &[("a.txt", Some("a\nb\nc\n"))], "wip");
+    repo.set_bookmark("edit", &edit);
+
+    let responses = mcp(
+        &repo,
+        &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }),
+            call(2, "list_hunks", json!({ "revision": "edit" })),
+            call(
+                3,
+                "describe",
+                json!({ "revision": "edit", "message": "Add a\n" }),
+            ),
+            call(4, "describe", json!({ "revision": "nonexistent" })),
+            call(5, "rebase", json!({})),
+        ],
+    );
+    assert_eq!(responses.len(), 5);
+    let tools: Vec<&str> = responses[0]["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        tools,
+        ["list_hunks", "split_revision", "absorb", "describe"]
+    );
+
+    let hunks = &responses[1]["result"];
+    assert_eq!(hunks["isError"], false);
+    assert_eq!(hunks["structuredContent"]["commit"]["commit_id"], edit);
+    assert_eq!(hunks["structuredContent"]["hunks"][0]["range"], "a.txt:1-3");
+    assert_eq!(hunks["structuredContent"]["hunks"][0]["added"], 3);
+
+    let described = &responses[2]["result"]["structuredContent"];
+    assert_eq!(described["changed"], true);
+    assert_eq!(
+        repo.bookmark_commit("edit").unwrap().description(),
+        "Add a\n"
+    );
+
+    // Tool failures are results the agent can read; unknown tools are
+    // protocol errors.
+    assert_eq!(responses[3]["result"]["isError"], true);
+    let message = responses[3]["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap();
+    assert!(message.contains("nonexistent"), "{message}");
+    assert_eq!(responses[4]["error"]["code"], -32602);
+}
+
+#[test]
+fn test_mcp_absorb() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\nb\nc\n"))], "Add a");
+    let fix = repo.create_commit(&[&base], &[("a.txt", Some("a\nB\nc\n"))], "Fix b");
+    repo.set_bookmark("base", &base);
+    repo.set_bookmark("fix", &fix);
+
+    let responses = mcp(&repo, &[call(1, "absorb", json!({ "revision": "fix" }))]);
+    let result = &responses[0]["result"]["structuredContent"];
+    assert_eq!(responses[0]["result"]["isError"], false, "{responses:?}");
+    assert_eq!(result["absorbed_into"][0]["subject"], "Add a");
+    // The fix keeps its description, so it stays around, empty.
+    assert_eq!(result["source"]["subject"], "Fix b");
+    assert_eq!(result["skipped"], json!([]));
+    let base = repo.bookmark_commit("base").unwrap();
+    assert_eq!(
+        repo.file_content(&base, "a.txt").as_deref(),
+        Some("a\nB\nc\n")
+    );
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+
+/// The merge commit below the working copy, with its parents' ids.
+fn megamerge_parents(repo: &TestRepo) -> Vec<String> {
+    let wc = repo.wc_commit();
+    assert_eq!(wc.parent_ids().len(), 1);
+    let merge = repo.commit(&wc.parent_ids()[0].hex());
+    merge.parent_ids().iter().map(|id| id.hex()).collect()
+}
+
+fn repo_with_features() -> (TestRepo, String, String, String) {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let a = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Feature A");
+    let b = repo.create_commit(&[&root], &[("b.txt", Some("b\n"))], "Feature B");
+    let c = repo.create_commit(&[&root], &[("c.txt", Some("c\n"))], "Feature C");
+    (repo, a, b, c)
+}
+
+#[test]
+fn test_megamerge_create_and_update() {
+    let (repo, a, b, _) = repo_with_features();
+
+    repo.jjka()
+        .args(["megamerge", "create", &a, &b])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Created megamerge"))
+        .stdout(predicate::str::contains("of 2 feature(s):"));
+    assert_eq!(megamerge_parents(&repo), [a.clone(), b.clone()]);
+    let wc = repo.wc_commit();
+    assert_eq!(repo.file_content(&wc, "a.txt").as_deref(), Some("a\n"));
+    assert_eq!(repo.file_content(&wc, "b.txt").as_deref(), Some("b\n"));
+
+    repo.jjka()
+        .args(["megamerge", "update"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("is up to date"));
+
+    let a2 = repo.create_commit(&[&a], &[("a.txt", Some("a2\n"))], "More of feature A");
+    repo.jjka()
+        .args(["megamerge", "update"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Updated megamerge"))
+        .stdout(predicate::str::contains("More of feature A"));
+    assert_eq!(megamerge_parents(&repo), [a2, b]);
+    let wc = repo.wc_commit();
+    assert_eq!(repo.file_content(&wc, "a.txt").as_deref(), Some("a2\n"));
+
+    repo.jjka()
+        .args(["megamerge", "create", &a])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There already is a megamerge"));
+}
+
+#[test]
+fn test_megamerge_add_and_remove() {
+    let (repo, a, b, c) = repo_with_features();
+
+    repo.jjka()
+        .args(["megamerge", "add", &c])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There is no megamerge"));
+
+    repo.jjka()
+        .args(["megamerge", "create", &a, &b])
+        .assert()
+        .success();
+    repo.jjka()
+        .args(["megamerge", "add", &c])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("of 3 feature(s):"));
+    assert_eq!(megamerge_parents(&repo), [a.clone(), b.clone(), c.clone()]);
+
+    repo.jjka()
+        .args(["megamerge", "add", &b])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is already in the megamerge"));
+
+    repo.jjka()
+        .args(["megamerge", "remove", &a])
+        .assert()
+        .success();
+    assert_eq!(megamerge_parents(&repo), [b.clone(), c]);
+    assert_eq!(repo.file_content(&repo.wc_commit(), "a.txt"), None);
+
+    repo.jjka()
+        .args(["megamerge", "remove", &a])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "is not a feature of the megamerge",
+        ));
+}
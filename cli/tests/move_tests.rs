@@ -0,0 +1,86 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+/// A base with a five-line file and two commits on it, the first changing
+/// another file and the second the first and last lines. Returns the base
+/// and the commits.
+fn setup(repo: &TestRepo) -> (String, String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n5\n"))], "Base");
+    let first = repo.create_commit(&[&base], &[("b.txt", Some("b\n"))], "First");
+    let second = repo.create_commit(
+        &[&first],
+        &[("a.txt", Some("one\n2\n3\n4\nfive\n"))],
+        "Second",
+    );
+    (base, first, second)
+}
+
+#[test]
+fn test_move_into_an_ancestor() {
+    let repo = TestRepo::init();
+    let (base, first, second) = setup(&repo);
+
+    repo.jjka()
+        .args(["move", "--from", &second, "--to", &first, "a.txt:1-1"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"Moved lines from \w+ \w+ Second to \w+ \w+ First\n")
+                .unwrap(),
+        );
+    let [first] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(first.description(), "First");
+    assert_eq!(
+        repo.file_content(&first, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\n5\n")
+    );
+    assert_eq!(repo.file_content(&first, "b.txt").as_deref(), Some("b\n"));
+    let [second_after] = repo.children(&first).try_into().unwrap();
+    assert_eq!(second_after.tree_id(), repo.commit(&second).tree_id());
+}
+
+#[test]
+fn test_move_into_a_descendant() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n5\n"))], "Base");
+    let first = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("one\n2\n3\n4\nfive\n"))],
+        "First",
+    );
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second");
+    let third = repo.create_commit(&[&second], &[("c.txt", Some("c\n"))], "Third");
+
+    repo.jjka()
+        .args(["move", "--from", &first, "--to", &second, "a.txt:5-5"])
+        .assert()
+        .success();
+    let [first] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&first, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\n5\n")
+    );
+    let [second] = repo.children(&first).try_into().unwrap();
+    assert_eq!(
+        repo.file_content(&second, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\nfive\n")
+    );
+    let [third_after] = repo.children(&second).try_into().unwrap();
+    assert_eq!(third_after.tree_id(), repo.commit(&third).tree_id());
+
+    let third = "description(Third)";
+    repo.jjka()
+        .args(["move", "--from", third, "--to", third, "c.txt:1-1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--from and --to are both"));
+    repo.jjka()
+        .args(["move", "--from", third, "--to", &base, "a.txt:1-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a.txt isn't changed in"));
+}
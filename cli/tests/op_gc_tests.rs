@@ -0,0 +1,94 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::op_walk;
+use predicates::prelude::*;
+use std::slice;
+
+fn abandon(repo: &TestRepo, hex: &str) {
+    let commit = repo.commit(hex);
+    let readonly = repo.repo();
+    let mut tx = readonly.start_transaction();
+    tx.repo_mut().record_abandoned_commit(&commit);
+    tx.repo_mut().rebase_descendants().unwrap();
+    tx.commit("abandon test commit").unwrap();
+}
+
+fn operation_count(repo: &TestRepo) -> usize {
+    let readonly = repo.repo();
+    op_walk::walk_ancestors(slice::from_ref(readonly.operation())).count()
+}
+
+fn object_exists(repo: &TestRepo, hex: &str) -> bool {
+    std::process::Command::new("git")
+        .arg("--git-dir")
+        .arg(repo.root().join(".jj/repo/store/git"))
+        .args(["cat-file", "-e", hex])
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn test_op_gc_prunes_operations_and_commits() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let kept = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Kept");
+    let abandoned = repo.create_commit(&[&root], &[("b.txt", Some("b\n"))], "Abandoned");
+    abandon(&repo, &abandoned);
+    // Root, workspace creation, two commits and the abandon.
+    assert_eq!(operation_count(&repo), 5);
+
+    repo.jjka()
+        .args(["op-gc"])
+        .assert()
+        .success()
+        .stderr("Nothing to prune: all 4 operation(s) are within the retention policy\n");
+
+    repo.jjka()
+        .args(["op-gc", "--keep", "1", "--keep-days", "0", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Would prune 3 operation(s), keeping 1, and make 1 commit(s) unreachable",
+        ))
+        .stdout(predicate::str::contains("Newest pruned operation: "));
+    assert_eq!(operation_count(&repo), 5);
+
+    repo.jjka()
+        .args(["op-gc", "--keep", "1", "--keep-days", "0"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Pruned 3 operation(s), keeping 1; 1 commit(s) became unreachable",
+        ));
+    assert_eq!(operation_count(&repo), 2);
+    assert!(object_exists(&repo, &kept));
+    assert!(!object_exists(&repo, &abandoned));
+    repo.jjka().args(["verify"]).assert().success();
+}
+
+#[test]
+fn test_op_gc_keeps_snapshots() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "work in progress\n").unwrap();
+    repo.jjka()
+        .args(["snapshot", "save", "wip"])
+        .assert()
+        .success();
+
+    repo.jjka()
+        .args(["op-gc", "--keep", "1", "--keep-days", "0"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pruned 2 operation(s), keeping 1"));
+
+    repo.jjka()
+        .args(["snapshot", "restore", "wip"])
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(repo.root().join("a.txt")).unwrap(),
+        "work in progress\n"
+    );
+}
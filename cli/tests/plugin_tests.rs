@@ -0,0 +1,67 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+use serde_json::Value;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+
+/// PATH with `dir` in front, where the test plugins are.
+fn path_with(dir: &Path) -> std::ffi::OsString {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::join_paths(std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&path)))
+        .unwrap()
+}
+
+#[test]
+fn test_plugin_gets_arguments_and_context() {
+    let repo = TestRepo::init();
+    let bin = tempfile::tempdir().unwrap();
+    let plugin = bin.path().join("jjka-hello");
+    std::fs::write(
+        &plugin,
+        "#!/bin/sh\necho \"args: $*\"\necho \"root: $JJKA_WORKSPACE_ROOT\"\necho \"$JJKA_CONTEXT\"\nexit 7\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = repo
+        .jjka()
+        .env("PATH", path_with(bin.path()))
+        .args(["--json", "hello", "world", "--flag"])
+        .assert()
+        .code(7)
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("args: world --flag"));
+    assert_eq!(
+        lines.next(),
+        Some(format!("root: {}", repo.root().display()).as_str())
+    );
+    let context: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(context["version"], 1);
+    assert_eq!(context["format"], "json");
+    assert_eq!(context["workspace"]["name"], "default");
+    assert_eq!(
+        context["workspace"]["working_copy_commit_id"],
+        repo.wc_commit().id().hex()
+    );
+}
+
+#[test]
+fn test_unknown_command_without_plugin() {
+    let repo = TestRepo::init();
+    let bin = tempfile::tempdir().unwrap();
+    repo.jjka()
+        .env("PATH", path_with(bin.path()))
+        .arg("no-such-command")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Unknown command 'no-such-command', and there is no jjka-no-such-command on PATH",
+        ));
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+
+/// root -> empty -> reviewed (frozen by its trailer)
+fn frozen_repo() -> (TestRepo, String, String) {
+    let repo = TestRepo::init();
+    repo.add_config(r#"jjka.policies.frozen = 'description(regex:"(?m)^Reviewed-on:")'"#);
+    let empty = repo.create_commit(&[&repo.root_commit_id()], &[], "Empty");
+    let reviewed = repo.create_commit(
+        &[&empty],
+        &[("a.txt", Some("a\n"))],
+        "Add a\n\nReviewed-on: https://review.example.com/123\n",
+    );
+    repo.set_bookmark("reviewed", &reviewed);
+    (repo, empty, reviewed)
+}
+
+#[test]
+fn test_frozen_descendant_blocks_rewrite() {
+    let (repo, empty, reviewed) = frozen_repo();
+
+    // Abandoning the empty commit would rebase the reviewed one.
+    repo.jjka()
+        .args(["tidy", "-r", &empty, "--fix"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "is frozen by jjka.policies.frozen",
+        ))
+        .stderr(predicate::str::contains("Add a"));
+
+    assert_eq!(
+        repo.bookmark_commit("reviewed").unwrap().id().hex(),
+        reviewed
+    );
+}
+
+#[test]
+fn test_ignore_frozen_overrides_policy() {
+    let (repo, empty, reviewed) = frozen_repo();
+
+    repo.jjka()
+        .args(["tidy", "-r", &empty, "--fix", "--ignore-frozen"])
+        .assert()
+        .success();
+
+    let rebased = repo.bookmark_commit("reviewed").unwrap();
+    assert_ne!(rebased.id().hex(), reviewed);
+    assert_eq!(rebased.parent_ids()[0].hex(), repo.root_commit_id());
+}
+
+#[test]
+fn test_unfrozen_rewrites_are_allowed() {
+    let repo = TestRepo::init();
+    repo.add_config(r#"jjka.policies.frozen = 'description(regex:"(?m)^Reviewed-on:")'"#);
+    let empty = repo.create_commit(&[&repo.root_commit_id()], &[], "Empty");
+    let child = repo.create_commit(&[&empty], &[("a.txt", Some("a\n"))], "Add a");
+    repo.set_bookmark("child", &child);
+
+    repo.jjka()
+        .args(["tidy", "-r", &empty, "--fix"])
+        .assert()
+        .success();
+
+    let rebased = repo.bookmark_commit("child").unwrap();
+    assert_eq!(rebased.parent_ids()[0].hex(), repo.root_commit_id());
+}
+
+/// root -> released (immutable through revset-aliases) -> child
+fn immutable_repo() -> (TestRepo, String, String) {
+    let repo = TestRepo::init();
+    repo.add_config("[revset-aliases]\n'immutable_heads()' = 'bookmarks(exact:\"release\")'");
+    let released = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("a.txt", Some("1\n2\n")), ("b.txt", Some("b\n"))],
+        "Release",
+    );
+    let child = repo.create_commit(&[&released], &[("a.txt", Some("one\ntwo\n"))], "Edit a");
+    repo.set_bookmark("release", &released);
+    (repo, released, child)
+}
+
+#[test]
+fn test_immutable_revision_blocks_split() {
+    let (repo, released, child) = immutable_repo();
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &released, "b.txt:1-1"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Release is immutable"))
+        .stderr(predicate::str::contains("pass --ignore-immutable"));
+    assert_eq!(
+        repo.bookmark_commit("release").unwrap().id().hex(),
+        released
+    );
+
+    // Its mutable descendants can still be split.
+    repo.jjka()
+        .args(["hunksplit", "-r", &child, "a.txt:1-1"])
+        .assert()
+        .success();
+    assert_eq!(
+        repo.bookmark_commit("release").unwrap().id().hex(),
+        released
+    );
+}
+
+#[test]
+fn test_ignore_immutable_overrides_protection() {
+    let (repo, released, _) = immutable_repo();
+
+    repo.jjka()
+        .args([
+            "hunksplit",
+            "-r",
+            &released,
+            "--ignore-immutable",
+            "b.txt:1-1",
+        ])
+        .assert()
+        .success();
+
+    let rewritten = repo.bookmark_commit("release").unwrap();
+    assert_ne!(rewritten.id().hex(), released);
+    assert_eq!(
+        repo.file_content(&rewritten, "b.txt").as_deref(),
+        Some("b\n")
+    );
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+fn cache_entry(repo: &TestRepo, commit_id: &str) -> std::path::PathBuf {
+    repo.root()
+        .join(".jj/repo/jjka/cache/hunks-v1")
+        .join(format!("{commit_id}.json"))
+}
+
+#[test]
+fn test_prefetch_fills_and_clears_cache() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    let second = repo.create_commit(&[&first], &[("a.txt", Some("A\n"))], "Edit a");
+    repo.set_bookmark("main", &second);
+
+    repo.jjka()
+        .args(["prefetch", "-r", "::main ~ root()"])
+        .assert()
+        .success()
+        .stdout("Cached 2 hunk(s) of 2 revision(s) in ::main ~ root(); 0 were already cached\n");
+    assert!(cache_entry(&repo, &first).is_file());
+    assert!(cache_entry(&repo, &second).is_file());
+
+    repo.jjka()
+        .args(["prefetch", "-r", "::main ~ root()"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("2 were already cached\n"));
+
+    repo.jjka()
+        .args(["prefetch", "--clear"])
+        .assert()
+        .success()
+        .stdout("Removed the cached hunks of 2 revision(s)\n");
+    assert!(!cache_entry(&repo, &first).exists());
+}
+
+#[test]
+fn test_hunks_reads_the_cache() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    repo.jjka()
+        .args(["prefetch", "-r", &commit])
+        .assert()
+        .success();
+
+    // An entry is trusted as it is, so an edited one shows through.
+    let path = cache_entry(&repo, &commit);
+    let entry = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, entry.replace("a.txt", "cached.txt")).unwrap();
+    repo.jjka()
+        .args(["hunks", "-r", &commit])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("cached.txt:1-1  +1 -0"));
+}
+
+#[test]
+fn test_diff_reads_the_cache() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    repo.jjka()
+        .args(["prefetch", "-r", &commit])
+        .assert()
+        .success();
+
+    let path = repo
+        .root()
+        .join(".jj/repo/jjka/cache/diffs-v1")
+        .join(format!("{commit}-u3.json"));
+    let entry = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, entry.replace("a.txt", "cached.txt")).unwrap();
+    repo.jjka()
+        .args(["diff", "-r", &commit, "--color", "never"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cached.txt"));
+    // Other amounts of context are diffed afresh.
+    repo.jjka()
+        .args(["diff", "-r", &commit, "--context", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.txt"));
+}
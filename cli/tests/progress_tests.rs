@@ -0,0 +1,54 @@
+mod common;
+
+use common::TestRepo;
+
+/// A repo with a two-commit stack, and a revset selecting it.
+fn stack_repo() -> (TestRepo, String) {
+    let repo = TestRepo::init();
+    let first = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("a.txt", Some("a\n"))],
+        "First",
+    );
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second");
+    (repo, format!("{first}::{second}"))
+}
+
+#[test]
+fn test_progress_json_on_stderr() {
+    let (repo, stack) = stack_repo();
+    let output = repo
+        .jjka()
+        .args(["stats", "-r", &stack, "--progress", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("2 commit(s)"));
+
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let progress: Vec<_> = events
+        .iter()
+        .map(|event| {
+            assert_eq!(event["phase"], "stats");
+            assert_eq!(event["total"], 2);
+            event["current"].as_u64().unwrap()
+        })
+        .collect();
+    assert_eq!(progress, vec![0, 1, 2]);
+    assert!(events[0]["message"].as_str().unwrap().ends_with(" First"));
+    assert_eq!(events[2]["message"], "done");
+}
+
+#[test]
+fn test_no_progress_by_default() {
+    let (repo, stack) = stack_repo();
+    repo.jjka()
+        .args(["stats", "-r", &stack])
+        .assert()
+        .success()
+        .stderr("");
+}
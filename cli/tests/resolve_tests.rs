@@ -0,0 +1,80 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+/// A repo with a 2-sided conflict in `a.txt` at the `conflicted` bookmark,
+/// and a `concat` merge tool that resolves by concatenating both sides.
+fn conflicted_repo() -> TestRepo {
+    let repo = TestRepo::init();
+    let base = repo.create_commit(&[&repo.root_commit_id()], &[("a.txt", Some("1\n"))], "Base");
+    let left = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some("2\n")), ("b.txt", Some("b\n"))],
+        "Left",
+    );
+    let right = repo.create_commit(&[&base], &[("a.txt", Some("3\n"))], "Right");
+    let picked = repo.cherry_pick(&right, &left, "Pick right");
+    repo.set_bookmark("conflicted", &picked);
+    repo.add_config(
+        r#"[merge-tools.concat]
+program = "sh"
+merge-args = ["-c", 'cat "$0" "$1" > "$2"', "$left", "$right", "$output"]"#,
+    );
+    repo
+}
+
+#[test]
+fn test_resolve_with_merge_tool() {
+    let repo = conflicted_repo();
+
+    repo.jjka()
+        .args(["resolve", "-r", "conflicted", "--tool", "concat"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Resolved a.txt\n"))
+        .stdout(predicate::str::contains("Resolved 1 of 1 path(s)"));
+
+    let resolved = repo.bookmark_commit("conflicted").unwrap();
+    assert!(!resolved.has_conflict().unwrap());
+    assert_eq!(
+        repo.file_content(&resolved, "a.txt").as_deref(),
+        Some("2\n3\n")
+    );
+    assert_eq!(resolved.description(), "Pick right");
+}
+
+#[test]
+fn test_resolve_fails_without_output() {
+    let repo = conflicted_repo();
+    repo.add_config("[ui]\nmerge-editor = 'true'\n[merge-tools.true]\nmerge-args = []");
+    let before = repo.bookmark_commit("conflicted").unwrap();
+
+    repo.jjka()
+        .args(["resolve", "-r", "conflicted", "a.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Merge tool true didn't write a resolution for a.txt",
+        ));
+    assert_eq!(
+        repo.bookmark_commit("conflicted").unwrap().id(),
+        before.id()
+    );
+}
+
+#[test]
+fn test_resolve_errors() {
+    let repo = conflicted_repo();
+
+    repo.jjka()
+        .args(["resolve", "-r", "conflicted"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No merge tool configured"));
+    repo.jjka()
+        .args(["resolve", "-r", "conflicted", "--tool", "concat", "b.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("b.txt is not conflicted"));
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_revert_lines_in_working_copy() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[("a.txt", Some("one\ntwo\nthree\nfour\n"))],
+        "Base",
+    );
+    std::fs::write(repo.root().join("a.txt"), "ONE\ntwo\nTHREE\nfour\nfive\n").unwrap();
+
+    repo.jjka()
+        .args(["revert-lines", "-r", &base, "a.txt:3-5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Reverted lines in the working copy to",
+        ))
+        .stdout(predicate::str::contains("  a.txt +1 -2"));
+    assert_eq!(
+        std::fs::read_to_string(repo.root().join("a.txt")).unwrap(),
+        "ONE\ntwo\nthree\nfour\n"
+    );
+
+    repo.jjka()
+        .args(["revert-lines", "-r", &base, "a.txt:2-3"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Nothing to revert"));
+
+    repo.jjka()
+        .args(["revert-lines", "-r", &base, "a.txt:4-9"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Range a.txt:4-9 is past the end of the file, which has 4 line(s) in the working copy",
+        ));
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_review_needs_terminal() {
+    let repo = TestRepo::init();
+    let base = repo.root_commit_id();
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("a\n"))], "Add a");
+
+    repo.jjka()
+        .args(["review", "-r", &commit])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "This command needs an interactive terminal",
+        ));
+}
+
+#[test]
+fn test_review_empty_revset() {
+    let repo = TestRepo::init();
+
+    repo.jjka()
+        .args(["review", "-r", "none()"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No revisions to review"));
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use serde_json::{Value, json};
+
+/// Sends `requests` to `jjka serve` on stdin and returns the responses.
+fn serve(repo: &TestRepo, requests: &[Value]) -> Vec<Value> {
+    let input: String = requests
+        .iter()
+        .map(|request| format!("{request}\n"))
+        .collect();
+    let output = repo
+        .jjka()
+        .arg("serve")
+        .write_stdin(input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+#[test]
+fn test_serve_split_and_annotate() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\nb\nc\n"))], "Add a");
+    let edit = repo.create_commit(&[&base], &[("a.txt", Some("A\nb\nC\n"))], "Edit a");
+    repo.set_bookmark("edit", &edit);
+
+    let responses = serve(
+        &repo,
+        &[
+            request(1, "status", Value::Null),
+            request(
+                2,
+                "previewSplit",
+                json!({ "revision": "edit", "ranges": ["a.txt:1-1"] }),
+            ),
+            request(
+                3,
+                "applySplit",
+                json!({ "revision": "edit", "ranges": ["a.txt:1-1"], "message": "Capitalize a" }),
+            ),
+            request(
+                4,
+                "annotate",
+                json!({ "revision": "edit", "path": "a.txt" }),
+            ),
+        ],
+    );
+    assert_eq!(responses.len(), 4);
+    let status = &responses[0]["result"];
+    assert_eq!(
+        status["working_copy"]["commit_id"],
+        repo.wc_commit().id().hex()
+    );
+    assert_eq!(status["files"], json!([]));
+
+    let preview = &responses[1]["result"];
+    assert_eq!(preview["commit"]["commit_id"], edit);
+    assert_eq!(
+        preview["files"],
+        json!([{
+            "path": "a.txt",
+            "selected_insertions": 1,
+            "selected_deletions": 1,
+            "remaining_insertions": 1,
+            "remaining_deletions": 1,
+        }])
+    );
+
+    let applied = &responses[2]["result"];
+    assert_eq!(applied["selected"]["subject"], "Capitalize a");
+    assert_eq!(applied["remaining"]["subject"], "Edit a");
+    assert_eq!(
+        applied["operations"][0]["rewritten"][0]["old_commit_id"],
+        edit
+    );
+    let remaining = repo.bookmark_commit("edit").unwrap();
+    assert_eq!(
+        remaining.id().hex(),
+        applied["remaining"]["commit_id"].as_str().unwrap()
+    );
+    let selected = repo.commit(&remaining.parent_ids()[0].hex());
+    assert_eq!(
+        selected.id().hex(),
+        applied["selected"]["commit_id"].as_str().unwrap()
+    );
+    assert_eq!(selected.parent_ids()[0].hex(), base);
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("A\nb\nc\n")
+    );
+    assert_eq!(
+        repo.file_content(&remaining, "a.txt").as_deref(),
+        Some("A\nb\nC\n")
+    );
+
+    let lines = responses[3]["result"]["lines"].as_array().unwrap();
+    let origins: Vec<&str> = lines
+        .iter()
+        .map(|line| line["subject"].as_str().unwrap())
+        .collect();
+    assert_eq!(origins, ["Capitalize a", "Add a", "Edit a"]);
+}
+
+#[test]
+fn test_serve_errors() {
+    let repo = TestRepo::init();
+    let output = repo
+        .jjka()
+        .arg("serve")
+        .write_stdin(format!(
+            "not json\n{}\n{}\n{}\n{}\n",
+            request(1, "frobnicate", Value::Null),
+            request(2, "previewSplit", json!({ "ranges": "a.txt:1-2" })),
+            request(3, "previewSplit", json!({ "ranges": ["a.txt:1-2"] })),
+            json!({ "jsonrpc": "2.0", "method": "status" }),
+        ))
+        .output()
+        .unwrap();
+    let responses: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    // The notification gets no response.
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[0]["error"]["code"], -32700);
+    assert_eq!(responses[0]["id"], Value::Null);
+    assert_eq!(responses[1]["error"]["code"], -32601);
+    assert_eq!(responses[2]["error"]["code"], -32602);
+    assert_eq!(responses[3]["id"], 3);
+    assert_eq!(responses[3]["error"]["code"], -32000);
+    assert!(
+        responses[3]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("a.txt doesn't exist in"),
+        "{}",
+        responses[3]
+    );
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+/// Configures SSH signing with a fresh key.
+fn configure_ssh_signing(repo: &TestRepo) {
+    let key = repo.ssh_key("signing-key");
+    repo.add_config(&format!("signing.backend = \"ssh\"\nsigning.key = {key:?}"));
+}
+
+#[test]
+fn test_sign_rewrites_unsigned_commits() {
+    let repo = TestRepo::init();
+    configure_ssh_signing(&repo);
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Add b");
+    let third = repo.create_commit(&[&second], &[("c.txt", Some("c\n"))], "Add c");
+    repo.set_bookmark("main", &third);
+
+    repo.jjka()
+        .args(["sign", "-r", &format!("{first}::{second}")])
+        .assert()
+        .success()
+        .stderr(predicate::str::starts_with("Signed 2 commit(s):\n"));
+
+    let main = repo.bookmark_commit("main").unwrap();
+    assert!(!main.is_signed());
+    assert_eq!(main.description(), "Add c");
+    let parent = main.parents().next().unwrap().unwrap();
+    assert!(parent.is_signed());
+    assert_eq!(parent.description(), "Add b");
+    let grandparent = parent.parents().next().unwrap().unwrap();
+    assert!(grandparent.is_signed());
+    assert_eq!(repo.file_content(&main, "a.txt").as_deref(), Some("a\n"));
+
+    repo.jjka()
+        .args(["sign", "-r", "main-"])
+        .assert()
+        .success()
+        .stderr("Nothing to sign: every revision in main- is already signed\n");
+}
+
+#[test]
+fn test_sign_requires_a_backend() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+
+    repo.jjka()
+        .args(["sign", "-r", &commit])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No signing backend is configured"));
+}
+
+/// A signed stack of two commits on an unsigned base, with `top` on the
+/// last. Returns the base.
+fn signed_stack(repo: &TestRepo) -> String {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    let first = repo.create_commit(&[&base], &[("b.txt", Some("b\n"))], "Add b");
+    let second = repo.create_commit(&[&first], &[("c.txt", Some("c\n"))], "Add c");
+    repo.set_bookmark("top", &second);
+    repo.jjka()
+        .args(["sign", "-r", &format!("{first}::")])
+        .assert()
+        .success();
+    base
+}
+
+#[test]
+fn test_rewrite_warns_about_dropped_signatures() {
+    let repo = TestRepo::init();
+    configure_ssh_signing(&repo);
+    let base = signed_stack(&repo);
+    repo.add_config(&format!("revset-aliases.'trunk()' = '{base}'"));
+
+    // jj only re-signs the user's own commits.
+    repo.jjka()
+        .env("JJ_EMAIL", "someone.else@example.com")
+        .args(["link-issue", "PROJ-1", "-r", "trunk()+"])
+        .assert()
+        .success()
+        .stderr(
+            predicate::str::is_match(
+                r"Warning: Rewriting dropped the signatures of 2 commit\(s\): .*Add [bc], .*Add [bc]; set jjka.signing.resign = true",
+            )
+            .unwrap(),
+        );
+    let top = repo.bookmark_commit("top").unwrap();
+    assert!(!top.is_signed());
+}
+
+#[test]
+fn test_rewrite_resigns_dropped_signatures() {
+    let repo = TestRepo::init();
+    configure_ssh_signing(&repo);
+    let base = signed_stack(&repo);
+    repo.add_config(&format!(
+        "revset-aliases.'trunk()' = '{base}'\njjka.signing.resign = true"
+    ));
+
+    repo.jjka()
+        .env("JJ_EMAIL", "someone.else@example.com")
+        .args(["link-issue", "PROJ-1", "-r", "trunk()+"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Re-signed 2 rewritten commit(s)"))
+        .stderr(predicate::str::contains("Warning").not());
+    let top = repo.bookmark_commit("top").unwrap();
+    assert!(top.is_signed());
+    let parent = top.parents().next().unwrap().unwrap();
+    assert!(parent.is_signed());
+    assert!(parent.description().contains("PROJ-1"));
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+}
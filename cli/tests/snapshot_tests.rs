@@ -0,0 +1,85 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_snapshot_save_and_restore() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "work in progress\n").unwrap();
+
+    repo.jjka()
+        .args(["snapshot", "save", "wip"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Saved snapshot wip (1 file(s) +1 -0)",
+        ))
+        .stderr(predicate::str::contains(
+            "Reset the working copy to its parent",
+        ));
+    assert!(!repo.root().join("a.txt").exists());
+    assert!(repo.wc_commit().is_empty(repo.repo().as_ref()).unwrap());
+
+    repo.jjka()
+        .args(["snapshot", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^wip  .*  1 file\(s\) \+1 -0  on \w+ 0{12}").unwrap());
+
+    std::fs::write(repo.root().join("b.txt"), "other work\n").unwrap();
+    repo.jjka()
+        .args(["snapshot", "restore", "wip", "--drop"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored snapshot wip into"))
+        .stdout(predicate::str::contains("Dropped snapshot wip"));
+    assert_eq!(
+        std::fs::read_to_string(repo.root().join("a.txt")).unwrap(),
+        "work in progress\n"
+    );
+    let wc = repo.wc_commit();
+    assert_eq!(
+        repo.file_content(&wc, "b.txt").as_deref(),
+        Some("other work\n")
+    );
+
+    repo.jjka()
+        .args(["snapshot", "list"])
+        .assert()
+        .success()
+        .stderr("No snapshots\n");
+}
+
+#[test]
+fn test_snapshot_keep_and_errors() {
+    let repo = TestRepo::init();
+    repo.jjka()
+        .args(["snapshot", "save", "empty"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Nothing to snapshot: the working copy has no changes",
+        ));
+
+    std::fs::write(repo.root().join("a.txt"), "a\n").unwrap();
+    repo.jjka()
+        .args(["snapshot", "save", "wip", "--keep"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Reset the working copy").not());
+    assert!(repo.root().join("a.txt").exists());
+
+    repo.jjka()
+        .args(["snapshot", "save", "wip"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Snapshot \"wip\" already exists; drop it first",
+        ));
+    repo.jjka()
+        .args(["snapshot", "restore", "other"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No snapshot named \"other\""));
+}
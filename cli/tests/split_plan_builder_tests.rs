@@ -0,0 +1,167 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use jjka_lib::fingerprint::fingerprint_file;
+use jjka_lib::{LineRange, SplitPlanBuilder};
+
+const BEFORE: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+const AFTER: &str = "one\n2\n3\n4\nfive\n6\n7\n8\nnine\n";
+
+#[tokio::test]
+async fn test_builder_combines_ranges_hunks_and_files() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some(BEFORE))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("a.txt", Some(AFTER)), ("b.txt", Some("new\n"))],
+        "Edit",
+    );
+    let last_hunk = fingerprint_file("a.txt", BEFORE.as_bytes(), AFTER.as_bytes())
+        .pop()
+        .unwrap();
+
+    let ids = SplitPlanBuilder::new(repo.repo(), repo.commit(&commit))
+        .range(LineRange::parse("a.txt:1-1").unwrap())
+        .hunk(last_hunk.fingerprint.content)
+        .file("b.txt")
+        .description("Selected")
+        .execute()
+        .await
+        .unwrap();
+
+    let selected = repo.commit(&ids.selected.hex());
+    assert_eq!(selected.description(), "Selected");
+    assert_eq!(selected.parent_ids()[0].hex(), base);
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\n5\n6\n7\n8\nnine\n")
+    );
+    assert_eq!(
+        repo.file_content(&selected, "b.txt").as_deref(),
+        Some("new\n")
+    );
+    let remaining = repo.commit(&ids.remaining.hex());
+    assert_eq!(remaining.parent_ids()[0], ids.selected);
+    assert_eq!(remaining.change_id(), repo.commit(&commit).change_id());
+    assert_eq!(remaining.tree_id(), repo.commit(&commit).tree_id());
+    assert_eq!(repo.children(&selected), [remaining]);
+}
+
+#[tokio::test]
+async fn test_builder_selects_deleted_files() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(
+        &[&root],
+        &[
+            ("a.txt", Some(BEFORE)),
+            ("empty.txt", Some("")),
+            ("b.txt", Some("b\n")),
+        ],
+        "Base",
+    );
+    let commit = repo.create_commit(
+        &[&base],
+        &[("a.txt", None), ("empty.txt", None), ("b.txt", Some("B\n"))],
+        "Delete",
+    );
+
+    let ids = SplitPlanBuilder::new(repo.repo(), repo.commit(&commit))
+        .file("a.txt")
+        .file("empty.txt")
+        .execute()
+        .await
+        .unwrap();
+
+    let selected = repo.commit(&ids.selected.hex());
+    assert_eq!(repo.file_content(&selected, "a.txt"), None);
+    assert_eq!(repo.file_content(&selected, "empty.txt"), None);
+    assert_eq!(
+        repo.file_content(&selected, "b.txt").as_deref(),
+        Some("b\n")
+    );
+    let remaining = repo.commit(&ids.remaining.hex());
+    assert_eq!(remaining.tree_id(), repo.commit(&commit).tree_id());
+}
+
+#[tokio::test]
+async fn test_builder_validates_selection() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some(BEFORE))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some(AFTER))], "Edit");
+    let builder = || SplitPlanBuilder::new(repo.repo(), repo.commit(&commit));
+    let error = |builder: SplitPlanBuilder| async move {
+        let Err(err) = builder.plan().await else {
+            panic!("the plan should fail");
+        };
+        err.to_string()
+    };
+
+    assert!(error(builder()).await.starts_with("Nothing is selected"));
+    assert!(
+        error(builder().hunk("0123456789abcdef"))
+            .await
+            .starts_with("No hunk 0123456789abcdef in")
+    );
+    assert!(
+        error(builder().file("b.txt"))
+            .await
+            .starts_with("b.txt isn't changed in")
+    );
+    assert!(
+        error(builder().file("a.txt"))
+            .await
+            .starts_with("The selected lines are all of the changes")
+    );
+    let plan = builder()
+        .range(LineRange::parse("a.txt:5-5").unwrap())
+        .plan()
+        .await
+        .unwrap();
+    assert_eq!(plan.files[0].selected_insertions, 1);
+    assert_eq!(plan.files[0].remaining_insertions, 2);
+}
+
+#[tokio::test]
+async fn test_builder_plans_the_same_on_any_number_of_threads() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let paths = (0..8).map(|i| format!("file{i}.txt")).collect::<Vec<_>>();
+    let before_files = paths
+        .iter()
+        .map(|path| (path.as_str(), Some(BEFORE)))
+        .collect::<Vec<_>>();
+    let after_files = paths
+        .iter()
+        .map(|path| (path.as_str(), Some(AFTER)))
+        .collect::<Vec<_>>();
+    let base = repo.create_commit(&[&root], &before_files, "Base");
+    let commit = repo.create_commit(&[&base], &after_files, "Edit");
+    let plan = |jobs| {
+        let mut builder = SplitPlanBuilder::new(repo.repo(), repo.commit(&commit)).jobs(jobs);
+        for path in &paths {
+            builder = builder.range(LineRange::parse(&format!("{path}:1-5")).unwrap());
+        }
+        async move { builder.plan().await.unwrap() }
+    };
+
+    let serial = plan(1).await;
+    let parallel = plan(4).await;
+    assert_eq!(parallel.selected_tree_id, serial.selected_tree_id);
+    assert_eq!(parallel.files, serial.files);
+    let planned = parallel
+        .files
+        .iter()
+        .map(|file| &file.path)
+        .collect::<Vec<_>>();
+    assert_eq!(planned, paths.iter().collect::<Vec<_>>());
+    assert!(
+        parallel
+            .files
+            .iter()
+            .all(|file| file.selected_insertions == 2)
+    );
+}
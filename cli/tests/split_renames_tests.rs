@@ -0,0 +1,123 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+
+const ORIGINAL: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n";
+const EDITED: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\nEIGHT\n";
+
+#[test]
+fn test_split_renames_puts_renames_first() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("old.txt", Some(ORIGINAL))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[
+            ("old.txt", None),
+            ("new.txt", Some(EDITED)),
+            ("other.txt", Some("other\n")),
+        ],
+        "Rework things",
+    );
+
+    repo.jjka()
+        .args(["split-renames", "-r", &commit])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"Split \w+ \w+ Rework things into:\n  \w+ \w+ Rename old.txt to new.txt\n  \w+ \w+ Rework things\n")
+                .unwrap(),
+        );
+
+    let old = repo.commit(&commit);
+    let [child] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(child.description(), "Rename old.txt to new.txt\n");
+    assert_eq!(
+        repo.file_content(&child, "new.txt").as_deref(),
+        Some(ORIGINAL)
+    );
+    assert_eq!(repo.file_content(&child, "old.txt"), None);
+    assert_eq!(repo.file_content(&child, "other.txt"), None);
+    let [edits] = repo.children(&child).try_into().unwrap();
+    assert_eq!(edits.change_id(), old.change_id());
+    assert_eq!(edits.tree_id(), old.tree_id());
+    assert_ne!(edits.id().hex(), commit);
+}
+
+#[test]
+fn test_split_renames_edits_first() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("old.txt", Some(ORIGINAL))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("old.txt", None), ("new.txt", Some(EDITED))],
+        "Rename and edit",
+    );
+    let child = repo.create_commit(&[&commit], &[("c.txt", Some("c\n"))], "Child");
+
+    repo.jjka()
+        .args(["split-renames", "-r", &commit, "--edits-first"])
+        .assert()
+        .success();
+
+    let [edits] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(edits.change_id(), repo.commit(&commit).change_id());
+    assert_eq!(
+        repo.file_content(&edits, "old.txt").as_deref(),
+        Some(EDITED)
+    );
+    assert_eq!(repo.file_content(&edits, "new.txt"), None);
+    let [renames] = repo.children(&edits).try_into().unwrap();
+    assert_eq!(renames.description(), "Rename old.txt to new.txt\n");
+    assert_eq!(renames.tree_id(), repo.commit(&commit).tree_id());
+    let [new_child] = repo.children(&renames).try_into().unwrap();
+    assert_eq!(new_child.change_id(), repo.commit(&child).change_id());
+}
+
+#[test]
+fn test_split_renames_nothing_to_split() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("old.txt", Some(ORIGINAL))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("old.txt", None), ("new.txt", Some(ORIGINAL))],
+        "Pure rename",
+    );
+
+    repo.jjka()
+        .args(["split-renames", "-r", &commit])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Nothing to split: no renamed or copied file in",
+        ));
+}
+
+#[test]
+fn test_split_renames_dry_run() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("old.txt", Some(ORIGINAL))], "Base");
+    let commit = repo.create_commit(
+        &[&base],
+        &[("old.txt", None), ("new.txt", Some(EDITED))],
+        "Rename and edit",
+    );
+
+    repo.jjka()
+        .args(["split-renames", "-r", &commit, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"^Would split \w+ \w+ Rename and edit:\n  Rename old.txt -> new.txt \(modified\)\n$",
+            )
+            .unwrap(),
+        );
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+    assert_eq!(repo.children(&repo.commit(&base))[0].id().hex(), commit);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_squash_ranges_into_the_parent() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n4\n5\n"))], "Base");
+    let parent = repo.create_commit(&[&base], &[("b.txt", Some("b\n"))], "Parent");
+    let child = repo.create_commit(
+        &[&parent],
+        &[("a.txt", Some("one\n2\n3\n4\nfive\n"))],
+        "Child",
+    );
+    let grandchild = repo.create_commit(&[&child], &[("c.txt", Some("c\n"))], "Grandchild");
+
+    repo.jjka()
+        .args(["squash-ranges", "-r", &child, "a.txt:5-5"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"Squashed lines of \w+ \w+ Child into \w+ \w+ Parent\n")
+                .unwrap(),
+        );
+    let [parent] = repo.children(&repo.commit(&base)).try_into().unwrap();
+    assert_eq!(parent.description(), "Parent");
+    assert_eq!(
+        repo.file_content(&parent, "a.txt").as_deref(),
+        Some("1\n2\n3\n4\nfive\n")
+    );
+    let [child] = repo.children(&parent).try_into().unwrap();
+    assert_eq!(child.description(), "Child");
+    assert_eq!(
+        repo.file_content(&child, "a.txt").as_deref(),
+        Some("one\n2\n3\n4\nfive\n")
+    );
+    let [grandchild_after] = repo.children(&child).try_into().unwrap();
+    assert_eq!(
+        grandchild_after.tree_id(),
+        repo.commit(&grandchild).tree_id()
+    );
+
+    repo.jjka()
+        .args(["squash-ranges", "-r", "description(Child)", "a.txt:1-1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"\w+ \w+ Child is now empty\n").unwrap());
+}
+
+#[test]
+fn test_squash_ranges_needs_one_parent() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let left = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Left");
+    let right = repo.create_commit(&[&root], &[("b.txt", Some("b\n"))], "Right");
+    let merge = repo.create_commit(&[&left, &right], &[("a.txt", Some("A\n"))], "Merge");
+
+    repo.jjka()
+        .args(["squash-ranges", "-r", &merge, "a.txt:1-1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "is a merge; squash-ranges needs a revision with one parent",
+        ));
+    repo.jjka()
+        .args(["squash-ranges", "-r", &left, "a.txt:1-1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The root commit can't be changed, so the lines can't move into it",
+        ));
+}
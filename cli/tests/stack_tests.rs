@@ -0,0 +1,184 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args([
+            "-c",
+            "user.name=Upstream",
+            "-c",
+            "user.email=upstream@example.com",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// A test repo with an `origin` remote and a stack of three commits on its
+/// main branch, the bottom two pushed to `first` and `second`. Returns the
+/// repo, the commits bottom first, and a directory of CI results with a
+/// fake `curl` that serves them: `<dir>/<commit id>` is the response for a
+/// commit.
+fn setup() -> (TestRepo, [String; 3], tempfile::TempDir) {
+    let repo = TestRepo::init();
+    let parent = repo.root().parent().unwrap().to_path_buf();
+    let origin = parent.join("origin.git");
+    git(
+        &parent,
+        &["init", "--bare", "-b", "main", origin.to_str().unwrap()],
+    );
+    let clone = parent.join("upstream");
+    git(
+        &parent,
+        &["clone", origin.to_str().unwrap(), clone.to_str().unwrap()],
+    );
+    std::fs::write(clone.join("a.txt"), "1\n").unwrap();
+    git(&clone, &["add", "a.txt"]);
+    git(&clone, &["commit", "-m", "Base"]);
+    git(&clone, &["push", "origin", "main"]);
+    let trunk = git(&clone, &["rev-parse", "HEAD"]);
+    let git_dir = repo.root().join(".jj/repo/store/git");
+    git(
+        &git_dir,
+        &["remote", "add", "origin", origin.to_str().unwrap()],
+    );
+    repo.jjka().arg("sync").assert().success();
+
+    let first = repo.create_commit(&[&trunk], &[("a.txt", Some("2\n"))], "First\n");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second\n");
+    let third = repo.create_commit(&[&second], &[("c.txt", Some("c\n"))], "Third\n");
+    repo.set_bookmark("top", &third);
+    git(
+        &git_dir,
+        &["push", "origin", &format!("{first}:refs/heads/first")],
+    );
+    git(
+        &git_dir,
+        &["push", "origin", &format!("{second}:refs/heads/second")],
+    );
+    repo.jjka().arg("sync").assert().success();
+
+    let ci = tempfile::tempdir().unwrap();
+    let script = r#"#!/bin/sh
+# Read the config jjka writes, so it doesn't hit a closed pipe.
+cat > /dev/null
+for url; do :; done
+echo "$url" >> "$FAKE_CI/log"
+for sha in $(ls "$FAKE_CI"); do
+  case "$url" in *"$sha"*) cat "$FAKE_CI/$sha"; exit 0 ;; esac
+done
+exit 22
+"#;
+    let curl = ci.path().join("curl");
+    std::fs::write(&curl, script).unwrap();
+    std::fs::set_permissions(&curl, std::fs::Permissions::from_mode(0o755)).unwrap();
+    (repo, [first, second, third], ci)
+}
+
+fn jjka_with_ci(repo: &TestRepo, ci: &Path) -> assert_cmd::Command {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let path =
+        std::env::join_paths(std::iter::once(ci.to_path_buf()).chain(std::env::split_paths(&path)))
+            .unwrap();
+    let mut command = repo.jjka();
+    command.env("PATH", path).env("FAKE_CI", ci);
+    command
+}
+
+#[test]
+fn test_stack_formats_commits_with_a_template() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(&[&root], &[("a.txt", Some("1\n"))], "First\n");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second\n");
+
+    repo.jjka()
+        .args(["stack", "-r", &format!("root()..{second}")])
+        .args(["-T", r#"description.first_line() ++ "!""#])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  Second!  -  not pushed\n  First!   -  not pushed\n",
+        ));
+}
+
+#[test]
+fn test_stack_shows_github_checks() {
+    let (repo, [first, second, _], ci) = setup();
+    repo.add_config(
+        "jjka.forge.remotes.origin = { kind = 'github', url = 'https://github.com/owner/app' }",
+    );
+    std::fs::write(
+        ci.path().join(&first),
+        r#"{"check_runs": [{"status": "completed", "conclusion": "success"},
+                           {"status": "completed", "conclusion": "skipped"}]}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        ci.path().join(&second),
+        r#"{"check_runs": [{"status": "in_progress"},
+                           {"status": "completed", "conclusion": "failure"}]}"#,
+    )
+    .unwrap();
+
+    jjka_with_ci(&repo, ci.path())
+        .args(["stack", "-r", "trunk()..top"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^  \S+ \S+ Third +-  not pushed$").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^  \S+ \S+ Second +second  failed$").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^  \S+ \S+ First +first  passed$").unwrap())
+        .stdout(predicate::str::contains(
+            "Safe to land: the bottom 1 commit(s)",
+        ));
+    let log = std::fs::read_to_string(ci.path().join("log")).unwrap();
+    assert!(log.contains(&format!(
+        "https://api.github.com/repos/owner/app/commits/{first}/check-runs"
+    )));
+}
+
+#[test]
+fn test_stack_shows_gitlab_pipelines_as_json() {
+    let (repo, [first, second, _], ci) = setup();
+    repo.add_config(
+        "jjka.forge.remotes.origin = { kind = 'gitlab', url = 'https://gitlab.example.com/group/app' }",
+    );
+    std::fs::write(ci.path().join(&first), r#"[{"status": "success"}]"#).unwrap();
+    std::fs::write(ci.path().join(&second), r#"[{"status": "running"}]"#).unwrap();
+
+    let output = jjka_with_ci(&repo, ci.path())
+        .env("GITLAB_TOKEN", "secret")
+        .args(["stack", "-r", "trunk()..top", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let statuses: Vec<(&str, &str)> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["subject"].as_str().unwrap(),
+                entry["ci"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("Third", "not-pushed"),
+            ("Second", "pending"),
+            ("First", "passed")
+        ]
+    );
+    assert_eq!(entries[1]["branch"], "second");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+/// root -> a (src/lib.rs, README.md) -> b (edits src/lib.rs)
+fn churn_repo() -> (TestRepo, String) {
+    let repo = TestRepo::init();
+    let a = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[
+            ("src/lib.rs", Some("one\ntwo\nthree\n")),
+            ("README.md", Some("hello\n")),
+        ],
+        "Add lib",
+    );
+    let b = repo.create_commit(
+        &[&a],
+        &[("src/lib.rs", Some("one\nTWO\nthree\nfour\n"))],
+        "Edit lib",
+    );
+    (repo, b)
+}
+
+#[test]
+fn test_stats_text() {
+    let (repo, b) = churn_repo();
+
+    repo.jjka()
+        .args(["stats", "-r", &format!("::{b}"), "--top", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2 commit(s), 2 file(s) changed, 6 insertion(s)(+), 1 deletion(s)(-)",
+        ))
+        .stdout(predicate::str::contains("Most churned paths (top 1)"))
+        .stdout(predicate::str::is_match(r"\+5 -1\s+2 commit\(s\)  src/lib.rs").unwrap())
+        .stdout(predicate::str::is_match(r"\+5 -1\s+2 commit\(s\)  src\n").unwrap());
+}
+
+#[test]
+fn test_stats_json() {
+    let (repo, b) = churn_repo();
+
+    let output = repo
+        .jjka()
+        .args(["stats", "-r", &format!("::{b}"), "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["commits"], 2);
+    assert_eq!(report["files"][0]["path"], "README.md");
+    assert_eq!(report["most_churned"][0]["path"], "src/lib.rs");
+    assert_eq!(report["most_churned"][0]["insertions"], 5);
+    assert_eq!(report["directories"][0]["commits"], 2);
+}
+
+#[test]
+fn test_stats_jobs() {
+    let (repo, b) = churn_repo();
+    repo.add_config("[jjka.perf]\nmax-threads = 2\nnice = 5\nio-priority = \"low\"");
+
+    let expected = "2 commit(s), 2 file(s) changed, 6 insertion(s)(+), 1 deletion(s)(-)";
+    for jobs in ["1", "4"] {
+        repo.jjka()
+            .args(["stats", "-r", &format!("::{b}"), "--jobs", jobs])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+    repo.jjka()
+        .args(["stats", "--jobs", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("0 is not in 1..="));
+}
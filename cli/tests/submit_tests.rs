@@ -0,0 +1,232 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args([
+            "-c",
+            "user.name=Upstream",
+            "-c",
+            "user.email=upstream@example.com",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// PATH with `dir`, where the fake `curl` is, in front.
+fn path_with(dir: &Path) -> std::ffi::OsString {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::join_paths(std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&path)))
+        .unwrap()
+}
+
+/// A `curl` playing GitLab's merge request API, keeping the merge requests
+/// in `$FAKE_GITLAB` and logging each request to `$FAKE_GITLAB/log`.
+fn fake_curl(dir: &Path) {
+    let script = r#"#!/bin/sh
+state=$FAKE_GITLAB
+grep -q 'PRIVATE-TOKEN: secret' || { echo '{"message": "401 Unauthorized"}'; exit 22; }
+while [ $# -gt 1 ]; do
+  case "$1" in
+    --request) method=$2; shift ;;
+    --data-binary) body=${2#@}; shift ;;
+  esac
+  shift
+done
+url=$1
+echo "$method $url" >> "$state/log"
+case "$method" in
+  GET)
+    file="$state/mr-${url##*source_branch=}"
+    if [ -f "$file" ]; then echo "[$(cat "$file")]"; else echo '[]'; fi ;;
+  POST)
+    n=$(($(ls "$state" | grep -c '^mr-') + 1))
+    branch=$(sed 's/.*"source_branch":"\([^"]*\)".*/\1/; s|/|%2F|g' "$body")
+    sed "s|^{|{\"iid\":$n,\"web_url\":\"https://gitlab.example.com/group/app/-/merge_requests/$n\",|" "$body" > "$state/mr-$branch"
+    cat "$state/mr-$branch" ;;
+  PUT)
+    n=${url##*/}
+    file=$(grep -l "\"iid\":$n," "$state"/mr-*)
+    sed "s|^{|{\"iid\":$n,\"web_url\":\"https://gitlab.example.com/group/app/-/merge_requests/$n\",|" "$body" > "$file"
+    cat "$file" ;;
+esac
+"#;
+    let path = dir.join("curl");
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// A test repo with an `origin` remote on a fake GitLab, and a trunk
+/// commit fetched from it.
+struct Setup {
+    repo: TestRepo,
+    origin: PathBuf,
+    trunk: String,
+    bin: tempfile::TempDir,
+    gitlab: tempfile::TempDir,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let repo = TestRepo::init();
+        let parent = repo.root().parent().unwrap().to_path_buf();
+        let origin = parent.join("origin.git");
+        git(
+            &parent,
+            &["init", "--bare", "-b", "main", origin.to_str().unwrap()],
+        );
+        let clone = parent.join("upstream");
+        git(
+            &parent,
+            &["clone", origin.to_str().unwrap(), clone.to_str().unwrap()],
+        );
+        std::fs::write(clone.join("a.txt"), "1\n").unwrap();
+        git(&clone, &["add", "a.txt"]);
+        git(&clone, &["commit", "-m", "Base"]);
+        git(&clone, &["push", "origin", "main"]);
+        let trunk = git(&clone, &["rev-parse", "HEAD"]);
+        git(
+            &repo.root().join(".jj/repo/store/git"),
+            &["remote", "add", "origin", origin.to_str().unwrap()],
+        );
+        repo.jjka().arg("sync").assert().success();
+        repo.add_config(
+            "jjka.forge.remotes.origin = \
+             { kind = 'gitlab', url = 'https://gitlab.example.com/group/app' }",
+        );
+
+        let bin = tempfile::tempdir().unwrap();
+        fake_curl(bin.path());
+        Setup {
+            repo,
+            origin,
+            trunk,
+            bin,
+            gitlab: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn submit(&self, revisions: &str) -> assert_cmd::assert::Assert {
+        self.repo
+            .jjka()
+            .env("PATH", path_with(self.bin.path()))
+            .env("FAKE_GITLAB", self.gitlab.path())
+            .env("GITLAB_TOKEN", "secret")
+            .args(["submit", "-r", revisions])
+            .assert()
+    }
+
+    /// The requests the fake GitLab got, and forgets them.
+    fn take_log(&self) -> Vec<String> {
+        let log = self.gitlab.path().join("log");
+        let requests = std::fs::read_to_string(&log).unwrap_or_default();
+        std::fs::remove_file(&log).ok();
+        requests.lines().map(str::to_string).collect()
+    }
+
+    fn merge_request(&self, branch: &str) -> String {
+        let file = format!("mr-{}", branch.replace('/', "%2F"));
+        std::fs::read_to_string(self.gitlab.path().join(file)).unwrap()
+    }
+}
+
+fn branch(repo: &TestRepo, hex: &str) -> String {
+    let change_id = repo.commit(hex).change_id().reverse_hex();
+    format!("jjka/{}", &change_id[..12])
+}
+
+#[test]
+fn test_submit_opens_chained_merge_requests() {
+    let setup = Setup::new();
+    let repo = &setup.repo;
+    let first = repo.create_commit(&[&setup.trunk], &[("a.txt", Some("2\n"))], "First\n");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second\n\nAdds b.\n");
+    let (first_branch, second_branch) = (branch(repo, &first), branch(repo, &second));
+
+    setup
+        .submit(&format!("trunk()..{second}"))
+        .success()
+        .stdout(predicate::str::contains("-> !1 (created)"))
+        .stdout(predicate::str::contains(
+            "-> !2 (created) https://gitlab.example.com/group/app/-/merge_requests/2",
+        ));
+
+    assert_eq!(git(&setup.origin, &["rev-parse", &first_branch]), first);
+    assert_eq!(git(&setup.origin, &["rev-parse", &second_branch]), second);
+    let first_request = setup.merge_request(&first_branch);
+    assert!(first_request.contains(r#""target_branch":"main""#));
+    assert!(first_request.contains(r#""title":"First""#));
+    let second_request = setup.merge_request(&second_branch);
+    assert!(second_request.contains(&format!(r#""target_branch":"{first_branch}""#)));
+    assert!(second_request.contains(r#""description":"Adds b.\n\nDepends on !1""#));
+    let api = "https://gitlab.example.com/api/v4/projects/group%2Fapp/merge_requests";
+    assert_eq!(
+        setup.take_log(),
+        [
+            format!(
+                "GET {api}?state=opened&source_branch={}",
+                first_branch.replace('/', "%2F")
+            ),
+            format!("POST {api}"),
+            format!(
+                "GET {api}?state=opened&source_branch={}",
+                second_branch.replace('/', "%2F")
+            ),
+            format!("POST {api}"),
+        ]
+    );
+}
+
+#[test]
+fn test_submit_again_updates_only_changed_merge_requests() {
+    let setup = Setup::new();
+    let repo = &setup.repo;
+    let first = repo.create_commit(&[&setup.trunk], &[("a.txt", Some("2\n"))], "First\n");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Second\n");
+    let second_branch = branch(repo, &second);
+    repo.set_bookmark("stack", &second);
+    setup.submit("trunk()..stack").success();
+    setup.take_log();
+
+    let editor = repo.root().parent().unwrap().join("editor.sh");
+    std::fs::write(&editor, "#!/bin/sh\necho 'Second, reworded' > \"$1\"\n").unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+    repo.add_config(&format!("ui.editor = '{}'", editor.display()));
+    repo.jjka()
+        .args(["describe", "-r", "stack"])
+        .assert()
+        .success();
+    setup
+        .submit("trunk()..stack")
+        .success()
+        .stdout(predicate::str::contains("-> !1 (up to date)"))
+        .stdout(predicate::str::contains("-> !2 (updated)"));
+
+    let log = setup.take_log();
+    assert_eq!(log.iter().filter(|line| line.starts_with("PUT")).count(), 1);
+    assert!(log.iter().any(|line| line.ends_with("/merge_requests/2")));
+    assert!(!log.iter().any(|line| line.starts_with("POST")));
+    assert!(
+        setup
+            .merge_request(&second_branch)
+            .contains(r#""title":"Second, reworded""#)
+    );
+
+    // Without a token, nothing is pushed.
+    repo.jjka()
+        .env("PATH", path_with(setup.bin.path()))
+        .env_remove("GITLAB_TOKEN")
+        .args(["submit", "-r", "trunk()..stack"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Set GITLAB_TOKEN"));
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args([
+            "-c",
+            "user.name=Upstream",
+            "-c",
+            "user.email=upstream@example.com",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// An upstream clone pushing to `origin.git`, which the test repo has as
+/// its `origin` remote.
+struct Upstream {
+    clone: PathBuf,
+}
+
+impl Upstream {
+    fn new(repo: &TestRepo) -> Self {
+        let parent = repo.root().parent().unwrap();
+        let origin = parent.join("origin.git");
+        git(
+            parent,
+            &["init", "--bare", "-b", "main", origin.to_str().unwrap()],
+        );
+        let clone = parent.join("upstream");
+        git(
+            parent,
+            &["clone", origin.to_str().unwrap(), clone.to_str().unwrap()],
+        );
+        let git_dir = repo.root().join(".jj/repo/store/git");
+        git(
+            &git_dir,
+            &["remote", "add", "origin", origin.to_str().unwrap()],
+        );
+        Upstream { clone }
+    }
+
+    /// Commits `content` to `path` on main and pushes it. Returns the commit id.
+    fn push_change(&self, path: &str, content: &str, message: &str) -> String {
+        std::fs::write(self.clone.join(path), content).unwrap();
+        git(&self.clone, &["add", path]);
+        git(&self.clone, &["commit", "-m", message]);
+        git(&self.clone, &["push", "origin", "main"]);
+        git(&self.clone, &["rev-parse", "HEAD"])
+    }
+}
+
+#[test]
+fn test_sync_rebases_stacks_onto_new_trunk() {
+    let repo = TestRepo::init();
+    let upstream = Upstream::new(&repo);
+    let base = upstream.push_change("a.txt", "one\n", "Base");
+    repo.jjka().args(["sync"]).assert().success();
+
+    let feature = repo.create_commit(&[&base], &[("a.txt", Some("feature\n"))], "Feature");
+    let docs = repo.create_commit(&[&feature], &[("docs.txt", Some("docs\n"))], "Docs");
+    let dup = repo.create_commit(&[&docs], &[("b.txt", Some("b\n"))], "Add b");
+    repo.set_bookmark("stack", &dup);
+    upstream.push_change("a.txt", "upstream\n", "Upstream change");
+    upstream.push_change("b.txt", "b\n", "Add b upstream");
+    let trunk_head = git(&upstream.clone, &["rev-parse", "HEAD"]);
+
+    repo.jjka()
+        .args(["sync", "-r", "stack"])
+        .assert()
+        // The rebase left Feature conflicted.
+        .code(4)
+        .stderr(
+            predicate::str::is_match(format!(
+                r"Trunk is at \w+ {} Add b upstream\n",
+                &trunk_head[..12]
+            ))
+            .unwrap(),
+        )
+        .stdout(predicate::str::contains(
+            "Rebased 3 commit(s) in 1 stack(s) onto trunk",
+        ))
+        .stdout(predicate::str::is_match(r"New conflicts:\n  \w+ \w+ Feature\n").unwrap())
+        .stdout(predicate::str::is_match(r"Became empty [^\n]*\n  \w+ \w+ Add b\n").unwrap());
+
+    let new_stack = repo.bookmark_commit("stack").unwrap();
+    let new_docs = repo.commit(&new_stack.parent_ids()[0].hex());
+    let new_feature = repo.commit(&new_docs.parent_ids()[0].hex());
+    assert_eq!(new_feature.parent_ids()[0].hex(), trunk_head);
+    assert_eq!(
+        repo.file_content(&new_docs, "docs.txt").as_deref(),
+        Some("docs\n")
+    );
+}
+
+#[test]
+fn test_sync_nothing_to_rebase() {
+    let repo = TestRepo::init();
+    let upstream = Upstream::new(&repo);
+    let base = upstream.push_change("a.txt", "one\n", "Base");
+    repo.jjka().args(["sync"]).assert().success();
+    let feature = repo.create_commit(&[&base], &[("a.txt", Some("two\n"))], "Feature");
+
+    repo.jjka()
+        .args(["sync", "-r", &feature])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Nothing to rebase"));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+#[test]
+fn test_log_search_template() {
+    let repo = TestRepo::init();
+    let first = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("a.txt", Some("needle\n"))],
+        "Add needle\n\nWith a body.\n",
+    );
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("hay\n"))], "Add hay");
+    repo.set_bookmark("main", &second);
+    repo.set_bookmark("needle-added", &first);
+
+    repo.jjka()
+        .args(["log-search", "-S", "needle", "-r", "::main", "-T"])
+        .arg(
+            r#"commit_id.short(6) ++ " " ++ description.first_line().upper()
+               ++ surround(" [", "]", bookmarks.join(",")) ++ if(!empty, " (non-empty)")"#,
+        )
+        .assert()
+        .success()
+        .stdout(format!(
+            "{} ADD NEEDLE [needle-added] (non-empty)\n  a.txt: +1 -0\n",
+            &first[..6]
+        ));
+}
+
+#[test]
+fn test_template_errors_are_usage_errors() {
+    let repo = TestRepo::init();
+    repo.jjka()
+        .args(["conflicts", "-T", "change_id.first_line()"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "CommitId or ChangeId has no method first_line()",
+        ));
+    repo.jjka()
+        .args(["verify-signatures", "-T", "author.name() ++"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("at column 17"));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use predicates::prelude::*;
+
+/// root -> parser -> wip (empty) -> fixup -> squash -> cherry (stack)
+///   \-> upstream (main, same change as cherry)
+fn messy_stack() -> (TestRepo, String) {
+    let repo = TestRepo::init();
+    repo.add_config("revset-aliases.'trunk()' = 'main'");
+    let root = repo.root_commit_id();
+    let upstream = repo.create_commit(&[&root], &[("c.txt", Some("x\n"))], "Cherry upstream");
+    repo.set_bookmark("main", &upstream);
+
+    let parser = repo.create_commit(&[&root], &[("a.txt", Some("parser\n"))], "Add parser");
+    let wip = repo.create_commit(&[&parser], &[], "WIP");
+    let fixup = repo.create_commit(
+        &[&wip],
+        &[("a.txt", Some("parser v2\n"))],
+        "fixup! Add parser",
+    );
+    let squash = repo.create_commit(
+        &[&fixup],
+        &[("b.txt", Some("more\n"))],
+        "squash! Add parser\n\nExtra notes.\n",
+    );
+    let cherry = repo.create_commit(&[&squash], &[("c.txt", Some("x\n"))], "Cherry");
+    repo.set_bookmark("stack", &cherry);
+    (repo, cherry)
+}
+
+#[test]
+fn test_tidy_reports_without_fixing() {
+    let (repo, cherry) = messy_stack();
+
+    repo.jjka()
+        .args(["tidy", "-r", "trunk()..stack"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Empty commits:\n  "))
+        .stdout(predicate::str::contains(" WIP\n"))
+        .stdout(predicate::str::is_match(r"fixup! Add parser -> \w+ \w+ Add parser\n").unwrap())
+        .stdout(predicate::str::contains("Already merged into trunk:"))
+        .stdout(predicate::str::contains("Cherry upstream"))
+        .stderr(predicate::str::contains("Run with --fix"));
+
+    assert_eq!(repo.bookmark_commit("stack").unwrap().id().hex(), cherry);
+}
+
+#[test]
+fn test_tidy_fix() {
+    let (repo, _cherry) = messy_stack();
+
+    repo.jjka()
+        .args(["tidy", "-r", "trunk()..stack", "--fix"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Abandoned 1 empty commit(s), squashed 2 fixup(s), dropped 1 merged commit(s)",
+        ));
+
+    let tidied = repo.bookmark_commit("stack").unwrap();
+    assert_eq!(tidied.parent_ids()[0].hex(), repo.root_commit_id());
+    assert_eq!(tidied.description(), "Add parser\n\nExtra notes.\n");
+    assert_eq!(
+        repo.file_content(&tidied, "a.txt").as_deref(),
+        Some("parser v2\n")
+    );
+    assert_eq!(
+        repo.file_content(&tidied, "b.txt").as_deref(),
+        Some("more\n")
+    );
+    assert_eq!(repo.file_content(&tidied, "c.txt"), None);
+}
+
+#[test]
+fn test_tidy_nothing_to_do() {
+    let repo = TestRepo::init();
+    let commit = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("a.txt", Some("a\n"))],
+        "Add a",
+    );
+
+    repo.jjka()
+        .args(["tidy", "-r", &commit])
+        .assert()
+        .success()
+        .stderr("Nothing to tidy\n");
+}
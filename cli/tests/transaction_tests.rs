@@ -0,0 +1,124 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use predicates::prelude::*;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt as _;
+
+/// A revision editing the first and last lines of a.txt, with the
+/// bookmark `feature` on it. Returns the base and the revision.
+fn setup(repo: &TestRepo) -> (String, String) {
+    let root = repo.root_commit_id();
+    let base = repo.create_commit(&[&root], &[("a.txt", Some("1\n2\n3\n"))], "Base");
+    let commit = repo.create_commit(&[&base], &[("a.txt", Some("one\n2\nthree\n"))], "Edit a");
+    repo.set_bookmark("feature", &commit);
+    (base, commit)
+}
+
+fn op_id(repo: &TestRepo) -> String {
+    repo.repo().op_id().hex()
+}
+
+/// The commit the git ref of the bookmark points to, if it was exported.
+fn git_ref(repo: &TestRepo, bookmark: &str) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .arg("--git-dir")
+        .arg(repo.root().join(".jj/repo/store/git"))
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("refs/heads/{bookmark}"))
+        .output()
+        .unwrap();
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8(output.stdout).unwrap().trim().to_string())
+}
+
+#[test]
+fn test_failure_before_commit_publishes_nothing() {
+    for point in ["split", "commit"] {
+        let repo = TestRepo::init();
+        let (base, commit) = setup(&repo);
+        let op = op_id(&repo);
+
+        repo.jjka()
+            .env("JJKA_FAILPOINTS", point)
+            .args(["hunksplit", "-r", &commit, "a.txt:1-1"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "Injected failure at {point}"
+            )));
+
+        assert_eq!(op_id(&repo), op);
+        assert_eq!(repo.bookmark_commit("feature").unwrap().id().hex(), commit);
+        assert_eq!(git_ref(&repo, "feature"), None);
+        assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+    }
+}
+
+#[test]
+fn test_rejected_transaction_exports_no_git_refs() {
+    let repo = TestRepo::init();
+    repo.add_config("jjka.hooks.pre = ['sh', '-c', 'exit 1']");
+    let (_, commit) = setup(&repo);
+
+    repo.jjka()
+        .args(["hunksplit", "-r", &commit, "a.txt:1-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("The jjka.hooks.pre hook failed"));
+    assert_eq!(git_ref(&repo, "feature"), None);
+}
+
+#[test]
+fn test_autosplit_publishes_no_commits_when_a_later_one_fails() {
+    let repo = TestRepo::init();
+    let (base, commit) = setup(&repo);
+    let op = op_id(&repo);
+    let script = r#"cat > /dev/null; echo '{"commits": [{"description": "First", "ranges": ["a.txt:1-1"]}, {"description": "Second", "ranges": ["b.txt:1-1"]}]}'"#;
+
+    repo.jjka()
+        .args(["autosplit", "-r", &commit, "--script", script])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Can't make the commit \"Second\""));
+
+    assert_eq!(op_id(&repo), op);
+    assert_eq!(repo.children(&repo.commit(&base)).len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_while_committing_waits_for_the_commit() {
+    let repo = TestRepo::init();
+    std::fs::write(repo.root().join("a.txt"), "1\n2\n").unwrap();
+    let op = op_id(&repo);
+
+    let status = repo
+        .jjka_std()
+        .env("JJKA_FAILPOINTS", "commit=interrupt")
+        .args(["hunksplit", "a.txt:1-1"])
+        .status()
+        .unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGINT));
+
+    // The split went through and the working copy was checked out.
+    assert_ne!(op_id(&repo), op);
+    let remaining = repo.wc_commit();
+    let selected = repo.commit(&remaining.parent_ids()[0].hex());
+    assert_eq!(
+        repo.file_content(&selected, "a.txt").as_deref(),
+        Some("1\n")
+    );
+    assert_eq!(
+        repo.file_content(&remaining, "a.txt").as_deref(),
+        Some("1\n2\n")
+    );
+    repo.jjka()
+        .args(["hunks", "-r", "@"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("stale").not());
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::TestRepo;
+use predicates::prelude::*;
+
+/// Signs with a key listed in the allowed signers, and returns a second key
+/// that isn't.
+fn configure_ssh_signing(repo: &TestRepo) -> std::path::PathBuf {
+    let trusted = repo.ssh_key("trusted-key");
+    let public_key = std::fs::read_to_string(trusted.with_extension("pub")).unwrap();
+    let allowed_signers = trusted.with_file_name("allowed_signers");
+    std::fs::write(
+        &allowed_signers,
+        format!("test.user@example.com {public_key}"),
+    )
+    .unwrap();
+    repo.add_config(&format!(
+        "signing.backend = \"ssh\"\nsigning.key = {trusted:?}\n\
+         signing.backends.ssh.allowed-signers = {allowed_signers:?}"
+    ));
+    repo.ssh_key("untrusted-key")
+}
+
+#[test]
+fn test_verify_signatures_reports_each_status() {
+    let repo = TestRepo::init();
+    let untrusted = configure_ssh_signing(&repo);
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    let second = repo.create_commit(&[&first], &[("b.txt", Some("b\n"))], "Add b");
+    let third = repo.create_commit(&[&second], &[("c.txt", Some("c\n"))], "Add c");
+    repo.set_bookmark("main", &third);
+    repo.jjka().args(["sign", "-r", &first]).assert().success();
+    repo.jjka()
+        .args(["sign", "-r", "main-", "--key"])
+        .arg(&untrusted)
+        .assert()
+        .success();
+
+    repo.jjka()
+        .args(["verify-signatures", "-r", "::main ~ root()"])
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::is_match("(?m)^good +\\S+ \\S+ Add a  \\(test.user@example.com\\)$")
+                .unwrap(),
+        )
+        .stdout(predicate::str::is_match("(?m)^UNKNOWN +\\S+ \\S+ Add b").unwrap())
+        .stdout(predicate::str::is_match("(?m)^UNSIGNED +\\S+ \\S+ Add c$").unwrap())
+        .stderr(predicate::str::contains(
+            "2 of 3 revision(s) in ::main ~ root() are not validly signed",
+        ));
+
+    repo.jjka()
+        .args(["verify-signatures", "-r", "main-", "--allow-unknown"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_verify_signatures_json() {
+    let repo = TestRepo::init();
+    configure_ssh_signing(&repo);
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    repo.set_bookmark("main", &commit);
+    repo.jjka().args(["sign", "-r", "main"]).assert().success();
+
+    let output = repo
+        .jjka()
+        .args(["verify-signatures", "-r", "main", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let signatures: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(signatures[0]["status"], "good");
+    assert_eq!(signatures[0]["signer"], "test.user@example.com");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::backend::TreeValue;
+use jj_lib::object_id::ObjectId;
+use jj_lib::repo_path::RepoPath;
+use predicates::prelude::*;
+use std::path::PathBuf;
+
+/// Path of the loose git object for a file in `commit`.
+fn loose_object(repo: &TestRepo, commit: &str, path: &str) -> (String, PathBuf) {
+    let commit = repo.commit(commit);
+    let value = commit
+        .tree()
+        .unwrap()
+        .path_value(RepoPath::from_internal_string(path).unwrap())
+        .unwrap();
+    let Some(Some(TreeValue::File { id, .. })) = value.as_resolved() else {
+        panic!("{path} is not a file");
+    };
+    let hex = id.hex();
+    let object = repo
+        .root()
+        .join(".jj/repo/store/git/objects")
+        .join(&hex[..2])
+        .join(&hex[2..]);
+    (hex, object)
+}
+
+fn repo_with_files() -> (TestRepo, String) {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let first = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "First");
+    let second = repo.create_commit(
+        &[&first],
+        &[("dir/b.txt", Some("b\n")), ("a.txt", Some("a2\n"))],
+        "Second",
+    );
+    (repo, second)
+}
+
+#[test]
+fn test_verify_clean_repo() {
+    let (repo, _) = repo_with_files();
+
+    repo.jjka()
+        .args(["verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Checked 4 commit(s), 4 tree(s), 3 file(s) and 0 symlink(s)",
+        ))
+        .stdout(predicate::str::contains("No problems found"));
+}
+
+#[test]
+fn test_verify_finds_corrupt_and_missing_objects() {
+    let (repo, second) = repo_with_files();
+    let (corrupt, corrupt_path) = loose_object(&repo, &second, "a.txt");
+    let (missing, missing_path) = loose_object(&repo, &second, "dir/b.txt");
+    // A valid object with the wrong name still decompresses fine; only the
+    // hash gives it away.
+    std::fs::copy(&missing_path, &corrupt_path).unwrap();
+    std::fs::remove_file(&missing_path).unwrap();
+
+    repo.jjka()
+        .args(["verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(format!(
+            "file {corrupt} at a.txt: content hashes to {missing}"
+        )))
+        .stdout(predicate::str::contains(format!(
+            "file {missing} at dir/b.txt: "
+        )))
+        .stderr(predicate::str::contains("Found 2 problem(s) in the store"));
+}
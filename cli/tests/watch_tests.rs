@@ -0,0 +1,91 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdout, Stdio};
+
+/// A running `jjka watch` process, killed on drop.
+struct Watch {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Watch {
+    fn start(repo: &TestRepo, args: &[&str]) -> Self {
+        let mut child = repo
+            .jjka_std()
+            .args(["watch", "--interval", "100"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut watch = Watch { child, stdout };
+        let line = watch.next_line();
+        assert!(line.starts_with("Watching "), "unexpected output: {line:?}");
+        watch
+    }
+
+    fn next_line(&mut self) -> String {
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        line
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+#[test]
+fn test_watch_snapshots_changes() {
+    let repo = TestRepo::init();
+    let exec_output = repo.root().parent().unwrap().join("exec.txt");
+    let exec = format!("echo $JJKA_COMMIT_ID > '{}'", exec_output.display());
+    let mut watch = Watch::start(&repo, &["--exec", &exec]);
+
+    std::fs::write(repo.root().join("a.txt"), "one\ntwo\n").unwrap();
+    let line = watch.next_line();
+    assert!(
+        line.ends_with(" snapshot 1: 1 file(s) +2 -0 (session: 1 file(s) +2 -0)\n"),
+        "{line:?}"
+    );
+    std::fs::create_dir(repo.root().join("src")).unwrap();
+    std::fs::write(repo.root().join("src/b.txt"), "three\n").unwrap();
+    let line = watch.next_line();
+    assert!(
+        line.ends_with(" snapshot 2: 1 file(s) +1 -0 (session: 2 file(s) +3 -0)\n"),
+        "{line:?}"
+    );
+    let wc = repo.wc_commit();
+    assert_eq!(
+        repo.file_content(&wc, "src/b.txt").as_deref(),
+        Some("three\n")
+    );
+    // The command runs after the snapshot is printed.
+    let expected = format!("{}\n", wc.id().hex());
+    for _ in 0..50 {
+        if std::fs::read_to_string(&exec_output).ok().as_ref() == Some(&expected) {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("--exec didn't see the last snapshot");
+}
+
+#[test]
+fn test_watch_polling() {
+    let repo = TestRepo::init();
+    let mut watch = Watch::start(&repo, &["--poll"]);
+
+    std::fs::write(repo.root().join("a.txt"), "one\n").unwrap();
+    let line = watch.next_line();
+    assert!(
+        line.ends_with(" snapshot 1: 1 file(s) +1 -0 (session: 1 file(s) +1 -0)\n"),
+        "{line:?}"
+    );
+}
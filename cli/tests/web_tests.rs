@@ -0,0 +1,169 @@
+mod common;
+
+use common::TestRepo;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Stdio};
+
+/// A running `jjka web` process, killed on drop.
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(repo: &TestRepo, revisions: &str) -> Self {
+        let mut child = repo
+            .jjka_std()
+            .args(["web", "--port", "0", "-r", revisions])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("Serving on http://")
+            .unwrap_or_else(|| panic!("unexpected output: {line:?}"))
+            .to_string();
+        Server { child, addr }
+    }
+
+    /// Returns the status line and body.
+    fn get(&self, path: &str) -> (String, String) {
+        self.send(&format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n"))
+    }
+
+    /// Sends `request` as is and returns the status line and body.
+    fn send(&self, request: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+fn web_repo() -> (TestRepo, String) {
+    let repo = TestRepo::init();
+    let base = repo.create_commit(
+        &[&repo.root_commit_id()],
+        &[("hello.txt", Some("hello\nworld\n"))],
+        "Add hello",
+    );
+    let edit = repo.create_commit(
+        &[&base],
+        &[("hello.txt", Some("hello\n<there>\n"))],
+        "Greet <there>",
+    );
+    repo.set_bookmark("feature", &edit);
+    (repo, edit)
+}
+
+#[test]
+fn test_web_stack_and_commit_pages() {
+    let (repo, edit) = web_repo();
+    let server = Server::start(&repo, "root()..feature");
+
+    let (status, body) = server.get("/");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(body.contains("Greet &lt;there&gt;"));
+    assert!(body.contains("Add hello"));
+    assert!(body.contains(&format!("href=\"/commit/{edit}\"")));
+    assert!(body.contains("<span class=\"bookmark\">feature</span>"));
+
+    let (status, body) = server.get(&format!("/commit/{edit}"));
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(body.contains("<span class=\"removed\">-world</span>"));
+    assert!(body.contains("<span class=\"added\">+&lt;there&gt;</span>"));
+
+    let (status, body) = server.get("/oplog");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(body.contains("create test commit"));
+}
+
+#[test]
+fn test_web_json_api() {
+    let (repo, edit) = web_repo();
+    let server = Server::start(&repo, "root()..feature");
+
+    let (_, body) = server.get("/api/log");
+    let log: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(log.as_array().unwrap().len(), 2);
+    assert_eq!(log[0]["commit_id"], edit.as_str());
+    assert_eq!(log[0]["bookmarks"][0], "feature");
+    assert_eq!(log[0]["author"]["name"], "Test User");
+
+    let (_, body) = server.get(&format!("/api/commit/{edit}"));
+    let detail: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(detail["description"], "Greet <there>");
+    assert_eq!(detail["files"][0]["path"], "hello.txt");
+    assert_eq!(detail["files"][0]["status"], "modified");
+    assert_eq!(
+        detail["files"][0]["hunks"][0]["lines"][1]["kind"],
+        "removed"
+    );
+
+    let (_, body) = server.get("/api/oplog");
+    let ops: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(ops[0]["description"], "set test bookmark");
+}
+
+#[test]
+fn test_web_not_found() {
+    let (repo, _) = web_repo();
+    let server = Server::start(&repo, "root()..feature");
+
+    assert_eq!(server.get("/nope").0, "HTTP/1.1 404 Not Found");
+    assert_eq!(server.get("/commit/zzzz").0, "HTTP/1.1 404 Not Found");
+    assert_eq!(
+        server.get(&format!("/commit/{}", "1".repeat(40))).0,
+        "HTTP/1.1 404 Not Found"
+    );
+}
+
+#[test]
+fn test_web_idle_client_does_not_block_others() {
+    let (repo, _) = web_repo();
+    let server = Server::start(&repo, "root()..feature");
+
+    // A client that connects and never sends its request.
+    let _idle = TcpStream::connect(&server.addr).unwrap();
+    let (status, body) = server.get("/");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(body.contains("Add hello"));
+}
+
+#[test]
+fn test_web_rejects_large_headers() {
+    let (repo, _) = web_repo();
+    let server = Server::start(&repo, "root()..feature");
+
+    // Each request ends where a limit is crossed, so the server has read all
+    // of it when it answers.
+
+    // One header line a byte over 8 KiB.
+    let long_header = format!("X-Long: {}", "a".repeat(8 * 1024 + 1 - 8));
+    let (status, _) = server.send(&format!("GET / HTTP/1.1\r\n{long_header}"));
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+
+    // Headers that are each short but over 32 KiB in all.
+    let headers: String = (0..33)
+        .map(|i| format!("X-Header-{i}: {}\r\n", "a".repeat(1000)))
+        .collect();
+    let (status, _) = server.send(&format!("GET / HTTP/1.1\r\n{headers}"));
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+
+    let (status, _) = server.get("/");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::TestRepo;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::ref_name::WorkspaceName;
+use predicates::prelude::*;
+
+#[test]
+fn test_workspace_add_list_forget() {
+    let repo = TestRepo::init();
+    let root = repo.root_commit_id();
+    let commit = repo.create_commit(&[&root], &[("a.txt", Some("a\n"))], "Add a");
+    repo.set_bookmark("main", &commit);
+    let destination = repo.root().parent().unwrap().join("second");
+
+    repo.jjka()
+        .args(["workspace", "add", "second", "-r", "main", "--destination"])
+        .arg(&destination)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("Added workspace second at"));
+    assert_eq!(
+        std::fs::read_to_string(destination.join("a.txt")).unwrap(),
+        "a\n"
+    );
+    let wc_commit_id = repo
+        .repo()
+        .view()
+        .get_wc_commit_id(WorkspaceName::new("second"))
+        .unwrap()
+        .hex();
+    let wc_commit = repo.commit(&wc_commit_id);
+    assert_eq!(wc_commit.parent_ids(), [repo.commit(&commit).id().clone()]);
+
+    repo.jjka()
+        .args(["workspace", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("default (current): "))
+        .stdout(predicate::str::contains(format!(
+            "second: {} ",
+            &wc_commit.change_id().reverse_hex()[..12]
+        )))
+        .stdout(predicate::str::contains(format!(
+            "\n  {}\n",
+            destination.display()
+        )));
+
+    repo.jjka()
+        .args(["workspace", "forget", "second"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "Forgot workspace second; its files are left on disk\n",
+        ));
+    repo.jjka()
+        .args(["workspace", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("second").not());
+    assert!(destination.join("a.txt").exists());
+}
+
+#[test]
+fn test_workspace_add_defaults() {
+    let repo = TestRepo::init();
+
+    repo.jjka()
+        .args(["workspace", "add", "build"])
+        .assert()
+        .success();
+    assert!(repo.root().parent().unwrap().join("repo-build").is_dir());
+
+    repo.jjka()
+        .args(["workspace", "add", "build"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Workspace build already exists"));
+    repo.jjka()
+        .args(["workspace", "forget", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No workspace named missing"));
+}
@@ -0,0 +1,121 @@
+//! jjka's selection logic compiled to WebAssembly, so a review UI can show
+//! what a split would look like without a round trip to the CLI.
+//!
+//! The functions take file contents the page already has and return JSON
+//! in the same shapes as `jjka --json`. They run the same code as the CLI:
+//! [`jjka_lib::split_file`] and the diffing behind `jjka hunks`. Build with
+//! `wasm-pack build wasm --target web`.
+
+use jjka_lib::fingerprint::{FingerprintedHunk, fingerprint_file};
+use jjka_lib::selection::line_count;
+use jjka_lib::unified_diff::{Hunk, unified_hunks};
+use jjka_lib::{LineRange, Selection, SplitFile, split_file};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// What splitting some lines out of one file's change gives.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct SplitPreview {
+    /// The file as the first commit would have it.
+    pub selected: String,
+    #[serde(flatten)]
+    pub file: SplitFile,
+}
+
+/// Splits the lines `ranges` (`path:start-end`, as on the command line)
+/// select out of the change from `parent` to `content`.
+pub fn preview_split(
+    path: &str,
+    parent: &str,
+    content: &str,
+    ranges: &[String],
+) -> Result<SplitPreview, String> {
+    let ranges: Vec<LineRange> = ranges
+        .iter()
+        .map(|range| LineRange::parse(range).map_err(|err| err.to_string()))
+        .collect::<Result<_, _>>()?;
+    if let Some(range) = ranges.iter().find(|range| range.path != path) {
+        return Err(format!("{} is not a range of {path}", range.path));
+    }
+    let selection: Selection = ranges.iter().collect();
+    let ranges = selection
+        .files()
+        .next()
+        .map(|(_, ranges)| ranges)
+        .unwrap_or_default();
+    let lines = line_count(content.as_bytes());
+    if let Some((start, end)) = ranges.iter().find(|(_, end)| *end > lines) {
+        return Err(format!(
+            "Range {path}:{start}-{end} is past the end of the file, which has {lines} line(s)"
+        ));
+    }
+    let (selected, file) = split_file(path, parent.as_bytes(), content.as_bytes(), ranges);
+    Ok(SplitPreview {
+        selected: String::from_utf8_lossy(&selected).into_owned(),
+        file,
+    })
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, JsError> {
+    serde_json::to_string(value).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// [`preview_split`] as JSON.
+#[wasm_bindgen(js_name = splitFile)]
+pub fn split_file_js(
+    path: &str,
+    parent: &str,
+    content: &str,
+    ranges: Vec<String>,
+) -> Result<String, JsError> {
+    let preview =
+        preview_split(path, parent, content, &ranges).map_err(|err| JsError::new(&err))?;
+    to_json(&preview)
+}
+
+/// The hunks of the change from `before` to `after`, with `context` lines
+/// around each, as JSON.
+#[wasm_bindgen]
+pub fn hunks(before: &str, after: &str, context: usize) -> Result<String, JsError> {
+    let hunks: Vec<Hunk> = unified_hunks(before.as_bytes(), after.as_bytes(), context);
+    to_json(&hunks)
+}
+
+/// The fingerprinted change blocks of one file, as JSON; their `start` and
+/// `end` are the ranges to pass to `splitFile`.
+#[wasm_bindgen]
+pub fn fingerprints(path: &str, before: &str, after: &str) -> Result<String, JsError> {
+    let hunks: Vec<FingerprintedHunk> = fingerprint_file(path, before.as_bytes(), after.as_bytes());
+    to_json(&hunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_split() {
+        let ranges = ["a.txt:3-3".to_string()];
+        let preview = preview_split("a.txt", "1\n2\n3\n", "one\n2\nthree\n", &ranges).unwrap();
+        assert_eq!(preview.selected, "1\n2\nthree\n");
+        assert_eq!(
+            (
+                preview.file.selected_insertions,
+                preview.file.remaining_insertions
+            ),
+            (1, 1)
+        );
+
+        let other = ["b.txt:1-1".to_string()];
+        assert_eq!(
+            preview_split("a.txt", "", "x\n", &other).unwrap_err(),
+            "b.txt is not a range of a.txt"
+        );
+        let past = ["a.txt:2-4".to_string()];
+        assert!(
+            preview_split("a.txt", "", "x\n", &past)
+                .unwrap_err()
+                .contains("past the end of the file")
+        );
+    }
+}